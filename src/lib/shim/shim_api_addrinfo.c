@@ -103,7 +103,7 @@ static void _getaddrinfo_append(struct addrinfo** head, struct addrinfo** tail,
     }
     struct addrinfo* new_tail = malloc(sizeof(*new_tail));
     *new_tail = (struct addrinfo){.ai_flags = 0,
-                                  .ai_family = AF_INET,
+                                  .ai_family = addr->sa_family,
                                   .ai_socktype = socktype,
                                   .ai_protocol = protocol,
                                   .ai_addrlen = addrlen,
@@ -140,11 +140,36 @@ static void _getaddrinfo_appendv4(struct addrinfo** head, struct addrinfo** tail
     }
 }
 
-// Looks for matching IPv4 addresses in /etc/hosts and them to the list
-// specified by `head` and `tail`.
-static void _getaddrinfo_add_matching_hosts_ipv4(struct addrinfo** head, struct addrinfo** tail,
-                                                 const char* node, bool add_tcp, bool add_udp,
-                                                 bool add_raw, in_port_t port) {
+// IPv6 wrapper for _getaddrinfo_append. Appends an entry for the address and
+// port for each requested socket type.
+static void _getaddrinfo_appendv6(struct addrinfo** head, struct addrinfo** tail, bool add_tcp,
+                                  bool add_udp, bool add_raw, const struct in6_addr* addr,
+                                  in_port_t port) {
+    if (add_tcp) {
+        struct sockaddr_in6* sai = malloc(sizeof(*sai));
+        *sai = (struct sockaddr_in6){
+            .sin6_family = AF_INET6, .sin6_port = port, .sin6_addr = *addr};
+        _getaddrinfo_append(head, tail, SOCK_STREAM, (struct sockaddr*)sai, sizeof(*sai));
+    }
+    if (add_udp) {
+        struct sockaddr_in6* sai = malloc(sizeof(*sai));
+        *sai = (struct sockaddr_in6){
+            .sin6_family = AF_INET6, .sin6_port = port, .sin6_addr = *addr};
+        _getaddrinfo_append(head, tail, SOCK_DGRAM, (struct sockaddr*)sai, sizeof(*sai));
+    }
+    if (add_raw) {
+        struct sockaddr_in6* sai = malloc(sizeof(*sai));
+        *sai = (struct sockaddr_in6){
+            .sin6_family = AF_INET6, .sin6_port = port, .sin6_addr = *addr};
+        _getaddrinfo_append(head, tail, SOCK_RAW, (struct sockaddr*)sai, sizeof(*sai));
+    }
+}
+
+// Looks for matching addresses of the given family (AF_INET or AF_INET6) in
+// /etc/hosts and adds them to the list specified by `head` and `tail`.
+static void _getaddrinfo_add_matching_hosts(struct addrinfo** head, struct addrinfo** tail,
+                                            int family, const char* node, bool add_tcp,
+                                            bool add_udp, bool add_raw, in_port_t port) {
     // TODO: Parse hosts file once and keep it in an efficiently-searchable
     // in-memory format.
     GError* error = NULL;
@@ -166,9 +191,12 @@ static void _getaddrinfo_add_matching_hosts_ipv4(struct addrinfo** head, struct
 
     {
         gchar* escaped_node = g_regex_escape_string(node, -1);
-        // Build a regex to match an IPv4 address entry for the given `node` in
-        // /etc/hosts. See HOSTS(5) for format specification.
-        int rv = asprintf(&pattern, "^(\\d+\\.\\d+\\.\\d+\\.\\d+)[^#\n]*\\b%s\\b", escaped_node);
+        // Build a regex to match an address entry of the requested family for
+        // the given `node` in /etc/hosts. See HOSTS(5) for format
+        // specification.
+        const char* address_pattern = (family == AF_INET6) ? "[0-9a-fA-F]*:[0-9a-fA-F:.]+"
+                                                           : "\\d+\\.\\d+\\.\\d+\\.\\d+";
+        int rv = asprintf(&pattern, "^(%s)[^#\n]*\\b%s\\b", address_pattern, escaped_node);
         g_free(escaped_node);
         if (rv < 0) {
             panic("asprintf failed: %d", rv);
@@ -199,12 +227,22 @@ static void _getaddrinfo_add_matching_hosts_ipv4(struct addrinfo** head, struct
         gchar* address_string = g_match_info_fetch(match_info, 1);
         trace("Node:%s -> address string:%s", node, address_string);
         assert(address_string != NULL);
-        uint32_t addr;
-        int rv = inet_pton(AF_INET, address_string, &addr);
+        int rv;
+        if (family == AF_INET6) {
+            struct in6_addr addr;
+            rv = inet_pton(AF_INET6, address_string, &addr);
+            if (rv == 1) {
+                _getaddrinfo_appendv6(head, tail, add_tcp, add_udp, add_raw, &addr, port);
+            }
+        } else {
+            uint32_t addr;
+            rv = inet_pton(AF_INET, address_string, &addr);
+            if (rv == 1) {
+                _getaddrinfo_appendv4(head, tail, add_tcp, add_udp, add_raw, addr, port);
+            }
+        }
         if (rv != 1) {
             panic("Bad address in /etc/hosts: %s\n", address_string);
-        } else {
-            _getaddrinfo_appendv4(head, tail, add_tcp, add_udp, add_raw, addr, port);
         }
         g_free(address_string);
     }
@@ -311,9 +349,10 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
     // configured."
     //
     // Determining what kind of addresses the local system has configured is
-    // unimplemented. For now we assume it has IPv4 and not IPv6.
+    // unimplemented. Every host has at least the IPv6 loopback address, so we
+    // assume it has both.
     const bool system_has_an_ipv4_address = true;
-    const bool system_has_an_ipv6_address = false;
+    const bool system_has_an_ipv6_address = true;
 
     // "There are several reasons why the linked list may have more than one
     // addrinfo structure, including: the network host is ... accessible  over
@@ -324,9 +363,12 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
     const bool add_ipv4 = hints->ai_family == AF_UNSPEC ||
                           (hints->ai_family == AF_INET &&
                            !((hints->ai_flags & AI_ADDRCONFIG) && !system_has_an_ipv4_address));
-    const bool add_ipv6 = hints->ai_family == AF_UNSPEC ||
-                          (hints->ai_family == AF_INET6 &&
-                           !((hints->ai_flags & AI_ADDRCONFIG) && !system_has_an_ipv6_address));
+    //
+    // Every host has an IPv4 address, but not every host has an IPv6 address
+    // and finding them requires scanning /etc/hosts. We only return IPv6
+    // addresses when they're explicitly requested.
+    const bool add_ipv6 = hints->ai_family == AF_INET6 &&
+                          !((hints->ai_flags & AI_ADDRCONFIG) && !system_has_an_ipv6_address);
 
     // "EAI_ADDRFAMILY: The specified network host does not have any network
     // addresses in the requested address family."
@@ -353,7 +395,7 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
                     res, &tail, add_tcp, add_udp, add_raw, ntohl(INADDR_ANY), port);
             }
             if (add_ipv6) {
-                // TODO: IPv6
+                _getaddrinfo_appendv6(res, &tail, add_tcp, add_udp, add_raw, &in6addr_any, port);
             }
         } else {
             // "If the AI_PASSIVE flag is not set in hints.ai_flags, then the
@@ -367,7 +409,8 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
                     res, &tail, add_tcp, add_udp, add_raw, ntohl(INADDR_LOOPBACK), port);
             }
             if (add_ipv6) {
-                // TODO: IPv6
+                _getaddrinfo_appendv6(
+                    res, &tail, add_tcp, add_udp, add_raw, &in6addr_loopback, port);
             }
         }
         // We've finished adding all relevant addresses.
@@ -380,7 +423,10 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
 
     // "`node` specifies either a numerical network address..."
     if (add_ipv6) {
-        // TODO: try parsing as IPv6
+        struct in6_addr addr;
+        if (inet_pton(AF_INET6, node, &addr) == 1) {
+            _getaddrinfo_appendv6(res, &tail, add_tcp, add_udp, add_raw, &addr, port);
+        }
     }
     if (add_ipv4) {
         uint32_t addr;
@@ -414,7 +460,22 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
     // (and for now, only). For hosts lookups, the corresponding file is
     // /etc/hosts. See NSSWITCH.CONF(5).
     if (add_ipv6) {
-        // TODO: look for IPv6 addresses in /etc/hosts.
+        _getaddrinfo_add_matching_hosts(
+            res, &tail, AF_INET6, node, add_tcp, add_udp, add_raw, port);
+
+        // "If hints.ai_flags specifies the AI_V4MAPPED flag, and hints.ai_family
+        // was specified as AF_INET6, and no matching IPv6 addresses could be
+        // found, then return IPv4-mapped IPv6 addresses in the list pointed to
+        // by res."
+        uint32_t addr;
+        if (*res == NULL && (hints->ai_flags & AI_V4MAPPED) &&
+            _shim_api_hostname_to_addr_ipv4(node, &addr)) {
+            struct in6_addr mapped = {0};
+            mapped.s6_addr[10] = 0xff;
+            mapped.s6_addr[11] = 0xff;
+            memcpy(&mapped.s6_addr[12], &addr, sizeof(addr));
+            _getaddrinfo_appendv6(res, &tail, add_tcp, add_udp, add_raw, &mapped, port);
+        }
     }
     if (add_ipv4) {
         // Try first to avoid scanning the /etc/hosts file.
@@ -427,7 +488,8 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
             warning("shadow_hostname_to_addr_ipv4 syscall failed for name %s, falling back to less "
                     "efficient scan of '/etc/hosts' file.",
                     node);
-            _getaddrinfo_add_matching_hosts_ipv4(res, &tail, node, add_tcp, add_udp, add_raw, port);
+            _getaddrinfo_add_matching_hosts(
+                res, &tail, AF_INET, node, add_tcp, add_udp, add_raw, port);
         }
     }
 
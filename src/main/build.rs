@@ -177,6 +177,7 @@ fn run_bindgen(build_common: &ShadowBuildCommon) {
         .allowlist_function("managedthread_.*")
         .allowlist_function("tcp_.*")
         .allowlist_function("tcpcong_.*")
+        .allowlist_function("tcpCongestion_.*")
         .allowlist_function("legacyfile_.*")
         .allowlist_function("legacysocket_.*")
         .blocklist_function("legacysocket_init")
@@ -225,6 +226,9 @@ fn run_bindgen(build_common: &ShadowBuildCommon) {
         .allowlist_type("LogInfoFlags")
         .allowlist_type("SimulationTime")
         .allowlist_type("ProtocolTCPFlags")
+        .allowlist_type("ProtocolECN")
+        .allowlist_type("ProtocolICMPType")
+        .allowlist_type("ProtocolICMPCode")
         .allowlist_type("PacketDeliveryStatusFlags")
         .allowlist_var("AFFINITY_UNINIT")
         .allowlist_var("CONFIG_HEADER_SIZE_TCP")
@@ -341,6 +345,8 @@ fn build_shadow_c(build_common: &ShadowBuildCommon) {
         "host/descriptor/socket.c",
         "host/descriptor/tcp.c",
         "host/descriptor/tcp_cong.c",
+        "host/descriptor/tcp_cong_bbr.c",
+        "host/descriptor/tcp_cong_cubic.c",
         "host/descriptor/tcp_cong_reno.c",
         "host/process.c",
         "host/futex.c",
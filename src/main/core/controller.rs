@@ -53,8 +53,10 @@ impl<'a> Controller<'a> {
         let manager_config = ManagerConfig {
             random: Xoshiro256PlusPlus::from_rng(&mut sim_config.random).unwrap(),
             ip_assignment: sim_config.ip_assignment,
+            ipv6_aliases: sim_config.ipv6_aliases,
             routing_info: sim_config.routing_info,
             host_bandwidths: sim_config.host_bandwidths,
+            partitions: sim_config.partitions,
             hosts: sim_config.hosts,
         };
 
@@ -23,13 +23,16 @@ use crate::core::cpu;
 use crate::core::resource_usage;
 use crate::core::scheduler::runahead::Runahead;
 use crate::core::scheduler::{HostIter, Scheduler, ThreadPerCoreSched, ThreadPerHostSched};
-use crate::core::sim_config::{Bandwidth, HostInfo};
+use crate::core::sim_config::{HostBandwidth, HostInfo};
 use crate::core::sim_stats;
 use crate::core::support::configuration::{self, ConfigOptions, EnvName, Flatten};
 use crate::core::worker;
 use crate::cshadow as c;
 use crate::host::host::{Host, HostParameters};
-use crate::network::graph::{IpAssignment, RoutingInfo};
+use crate::network::bottleneck::Bottlenecks;
+use crate::network::graph::{IpAssignment, Ipv6Aliases, RoutingInfo};
+use crate::network::multicast::MulticastGroups;
+use crate::network::partition::{PartitionChange, PartitionTable};
 use crate::utility;
 use crate::utility::childpid_watcher::ChildPidWatcher;
 use crate::utility::status_bar::Status;
@@ -197,6 +200,10 @@ impl<'a> Manager<'a> {
             )
         })?;
 
+        // save the routing info so that it can be checked before the simulation finishes; it's
+        // written again with the packet counts when the simulation finishes
+        write_routing_info(&data_path, &manager_config.routing_info)?;
+
         let meminfo_file =
             std::fs::File::open("/proc/meminfo").context("Failed to open '/proc/meminfo'")?;
 
@@ -243,6 +250,8 @@ impl<'a> Manager<'a> {
         let bootstrap_end_time: SimulationTime = bootstrap_end_time.try_into().unwrap();
         let bootstrap_end_time = EmulatedTime::SIMULATION_START + bootstrap_end_time;
 
+        // the smallest latency after accounting for jitter, so that jittered packets are never
+        // delivered earlier than the runahead allows
         let smallest_latency = SimulationTime::from_nanos(
             manager_config
                 .routing_info
@@ -305,13 +314,35 @@ impl<'a> Manager<'a> {
         }
         assert_eq!(cpus.len(), parallelism);
 
+        // the partition changes to log during the simulation, in order of time
+        let mut partition_changes = manager_config
+            .partitions
+            .changes()
+            .into_iter()
+            .map(|(time, change, partition)| {
+                (
+                    EmulatedTime::SIMULATION_START + time,
+                    change,
+                    partition.description.clone(),
+                )
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .peekable();
+
         // set the simulation's global state
         worker::WORKER_SHARED
             .borrow_mut()
             .replace(worker::WorkerShared {
                 ip_assignment: manager_config.ip_assignment,
+                ipv6_aliases: manager_config.ipv6_aliases,
                 routing_info: manager_config.routing_info,
                 host_bandwidths: manager_config.host_bandwidths,
+                partitions: manager_config.partitions,
+                bottlenecks: Bottlenecks::new(),
+                multicast_groups: MulticastGroups::new(
+                    hosts.iter().map(|x| (x.id(), x.default_ip())).collect(),
+                ),
                 // safe since the DNS type has an internal mutex
                 dns: unsafe { SyncSendPointer::new(dns) },
                 num_plugin_errors: AtomicU32::new(0),
@@ -400,6 +431,21 @@ impl<'a> Manager<'a> {
                         state.current = display_time;
                     });
 
+                // log any network partitions that start or end during this round
+                while let Some((time, change, description)) =
+                    partition_changes.next_if(|(time, _, _)| *time < window_end)
+                {
+                    let time = (time - EmulatedTime::SIMULATION_START).as_nanos();
+                    match change {
+                        PartitionChange::Start => {
+                            log::info!("Network partition {description} started at {time} ns")
+                        }
+                        PartitionChange::End => {
+                            log::info!("Network partition {description} ended at {time} ns")
+                        }
+                    }
+                }
+
                 // run the events
                 scheduler.scope(|s| {
                     // run the closure on each of the scheduler's threads
@@ -454,12 +500,27 @@ impl<'a> Manager<'a> {
                     }
                 });
 
+                // queue the packets that were sent over bottleneck edges during this round
+                let bottleneck_next_event_time = worker::WORKER_SHARED
+                    .borrow()
+                    .as_ref()
+                    .unwrap()
+                    .flush_bottleneck_packets();
+
+                // apply the multicast group memberships that changed during this round
+                worker::WORKER_SHARED
+                    .borrow()
+                    .as_ref()
+                    .unwrap()
+                    .flush_multicast_memberships();
+
                 // get the minimum next event time for all threads (also resets the next event times
                 // to None while we have them borrowed)
                 let min_next_event_time = thread_next_event_times
                     .iter()
                     // the take() resets it to None for the next scheduling loop
                     .filter_map(|x| x.borrow_mut().take())
+                    .chain(bottleneck_next_event_time)
                     .reduce(std::cmp::min)
                     .unwrap_or(EmulatedTime::MAX);
 
@@ -513,6 +574,15 @@ impl<'a> Manager<'a> {
             .unwrap()
             .plugin_error_count();
 
+        write_routing_info(
+            &self.data_path,
+            &worker::WORKER_SHARED
+                .borrow()
+                .as_ref()
+                .unwrap()
+                .routing_info,
+        )?;
+
         // drop the simulation's global state
         // must drop before the allocation counters have been checked
         worker::WORKER_SHARED.borrow_mut().take();
@@ -571,9 +641,11 @@ impl<'a> Manager<'a> {
                     // the config only allows ipv4 addresses, so this shouldn't happen
                     std::net::IpAddr::V6(_) => unreachable!("IPv6 not supported"),
                 },
+                ip6_addr: host_info.ip6_addr,
                 sim_end_time: self.end_time,
                 requested_bw_down_bits: host_info.bandwidth_down_bits.unwrap(),
                 requested_bw_up_bits: host_info.bandwidth_up_bits.unwrap(),
+                bandwidth_changes: host_info.bandwidth_changes.clone(),
                 cpu_threshold: host_info.cpu_threshold,
                 cpu_precision: host_info.cpu_precision,
                 heartbeat_interval: host_info.heartbeat_interval,
@@ -593,6 +665,9 @@ impl<'a> Manager<'a> {
                     .unwrap_or(c::_LogLevel_LOGLEVEL_UNSET),
                 pcap_config: host_info.pcap_config,
                 qdisc: host_info.qdisc,
+                tcp_congestion_control: host_info.tcp_congestion_control,
+                tcp_ecn: host_info.tcp_ecn,
+                router_queue: host_info.router_queue,
                 init_sock_recv_buf_size: host_info.recv_buf_size,
                 autotune_recv_buf: host_info.autotune_recv_buf,
                 init_sock_send_buf_size: host_info.send_buf_size,
@@ -863,16 +938,39 @@ pub struct ManagerConfig {
     // map of ip addresses to graph nodes
     pub ip_assignment: IpAssignment<u32>,
 
+    // map of hosts' ipv6 addresses to their ipv4 addresses
+    pub ipv6_aliases: Ipv6Aliases,
+
     // routing information for paths between graph nodes
     pub routing_info: RoutingInfo<u32>,
 
     // bandwidths of hosts at ip addresses
-    pub host_bandwidths: HashMap<std::net::IpAddr, Bandwidth>,
+    pub host_bandwidths: HashMap<std::net::IpAddr, HostBandwidth>,
+
+    // network partitions between hosts
+    pub partitions: PartitionTable,
 
     // a list of hosts and their processes
     pub hosts: Vec<HostInfo>,
 }
 
+/// Write the routing info to "routing.json" in the data directory.
+fn write_routing_info(
+    data_path: &std::path::Path,
+    routing_info: &RoutingInfo<u32>,
+) -> anyhow::Result<()> {
+    let filename = data_path.join("routing.json");
+    let file = std::fs::File::create(&filename)
+        .with_context(|| format!("Failed to create file '{}'", filename.display()))?;
+
+    routing_info.write_json(file).with_context(|| {
+        format!(
+            "Failed to write routing json to file '{}'",
+            filename.display()
+        )
+    })
+}
+
 /// Helper function to initialize the global [`Host`] before running the closure.
 fn for_each_host(host_iter: &mut HostIter, mut f: impl FnMut(&Host)) {
     host_iter.for_each(|host| {
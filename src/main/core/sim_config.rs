@@ -15,11 +15,16 @@ use shadow_shim_helper_rs::simulation_time::SimulationTime;
 
 use crate::core::support::configuration::Flatten;
 use crate::core::support::configuration::{
-    parse_string_as_args, ConfigOptions, EnvName, HostOptions, LogInfoFlag, LogLevel, ProcessArgs,
-    ProcessOptions, QDiscMode,
+    parse_string_as_args, ConfigOptions, EnvName, HostDefaultOptions, HostOptions, LogInfoFlag,
+    LogLevel, NetworkEventOptions, PartitionOptions, ProcessArgs, ProcessOptions, QDiscMode,
+    RouterQueueDiscipline, TcpCongestionControl,
 };
 use crate::core::support::units::{self, Unit};
-use crate::network::graph::{load_network_graph, IpAssignment, NetworkGraph, RoutingInfo};
+use crate::network::graph::{
+    load_network_graph, IpAssignment, Ipv6Aliases, NetworkGraph, PathTable, RoutingInfo,
+};
+use crate::network::partition::{Partition, PartitionTable};
+use crate::network::router::{RedParams, RouterQueueConfig};
 use crate::utility::tilde_expansion;
 
 use super::support::configuration::ProcessFinalState;
@@ -32,11 +37,17 @@ pub struct SimConfig {
     // map of ip addresses to graph nodes
     pub ip_assignment: IpAssignment<u32>,
 
+    // map of hosts' ipv6 addresses to their ipv4 addresses
+    pub ipv6_aliases: Ipv6Aliases,
+
     // routing information for paths between graph nodes
     pub routing_info: RoutingInfo<u32>,
 
     // bandwidths of hosts at ip addresses
-    pub host_bandwidths: HashMap<std::net::IpAddr, Bandwidth>,
+    pub host_bandwidths: HashMap<std::net::IpAddr, HostBandwidth>,
+
+    // network partitions between hosts
+    pub partitions: PartitionTable,
 
     // a list of hosts and their processes
     pub hosts: Vec<HostInfo>,
@@ -71,14 +82,54 @@ impl SimConfig {
             ));
         }
 
+        if config.network.use_ecmp.unwrap() && !config.network.use_shortest_path.unwrap() {
+            return Err(anyhow::anyhow!(
+                "The 'use_ecmp' network option requires 'use_shortest_path'"
+            ));
+        }
+
         // load and parse the network graph
         let graph: String = load_network_graph(config.network.graph.as_ref().unwrap())
             .map_err(|e| anyhow::anyhow!(e))
             .context("Failed to load the network graph")?;
-        let graph = NetworkGraph::parse(&graph)
+        let mut graph = NetworkGraph::parse(&graph)
             .map_err(|e| anyhow::anyhow!(e))
             .context("Failed to parse the network graph")?;
 
+        // scheduled changes to the network graph, sorted by time
+        let mut network_events = config.network.events.clone().unwrap_or_default();
+        network_events.sort_by_key(|x| time_to_simtime(x.time()));
+
+        // edge events grouped by time, since we only need to regenerate the routing info once for
+        // each time that the graph changes
+        let mut edge_events: BTreeMap<SimulationTime, Vec<_>> = BTreeMap::new();
+        for event in &network_events {
+            if let NetworkEventOptions::Edge(x) = event {
+                edge_events
+                    .entry(time_to_simtime(x.time))
+                    .or_default()
+                    .push(x);
+            }
+        }
+        let node_events: Vec<_> = network_events
+            .iter()
+            .filter_map(|x| match x {
+                NetworkEventOptions::Node(x) => Some(x),
+                _ => None,
+            })
+            .collect();
+
+        // check that each node event's node ID is valid
+        for event in &node_events {
+            if graph.node_id_to_index(event.id).is_none() {
+                return Err(anyhow::anyhow!(
+                    "The network node id {} for the node event at time '{}' does not exist",
+                    event.id,
+                    event.time,
+                ));
+            }
+        }
+
         // check that each node ID is valid
         for host in &hosts {
             if graph.node_id_to_index(host.network_node_id).is_none() {
@@ -102,6 +153,28 @@ impl SimConfig {
                 .bandwidth_up
                 .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value());
 
+            // node events only change the bandwidth of hosts that get their bandwidth from the
+            // graph node rather than from the host options
+            let uses_graph_bw_down = host.bandwidth_down_bits.is_none();
+            let uses_graph_bw_up = host.bandwidth_up_bits.is_none();
+
+            host.bandwidth_changes = node_events
+                .iter()
+                .filter(|x| x.id == host.network_node_id)
+                .map(|x| BandwidthChange {
+                    time: time_to_simtime(x.time),
+                    down_bits: x
+                        .host_bandwidth_down
+                        .filter(|_| uses_graph_bw_down)
+                        .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+                    up_bits: x
+                        .host_bandwidth_up
+                        .filter(|_| uses_graph_bw_up)
+                        .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+                })
+                .filter(|x| x.down_bits.is_some() || x.up_bits.is_some())
+                .collect();
+
             host.bandwidth_down_bits = host.bandwidth_down_bits.or(graph_bw_down_bits);
             host.bandwidth_up_bits = host.bandwidth_up_bits.or(graph_bw_up_bits);
 
@@ -131,13 +204,57 @@ impl SimConfig {
 
         // assign IP addresses to hosts and graph nodes
         let ip_assignment = assign_ips(&mut hosts)?;
+        let ipv6_aliases = assign_ip6s(&hosts)?;
 
         // generate routing info between every pair of in-use nodes
-        let routing_info = generate_routing_info(
+        let used_nodes = ip_assignment.get_nodes();
+        let mut routing_info = RoutingInfo::new(generate_paths(
             &graph,
-            &ip_assignment.get_nodes(),
+            &used_nodes,
             config.network.use_shortest_path.unwrap(),
-        )?;
+            config.network.use_ecmp.unwrap(),
+        )?);
+
+        // apply the edge events to the graph, and regenerate the routing info for each time that
+        // the graph changes
+        for (time, events) in edge_events {
+            for event in &events {
+                graph
+                    .update_edge(event.source, event.target, |edge| {
+                        if let Some(latency) = event.latency {
+                            edge.latency = latency;
+                        }
+                        if let Some(jitter) = event.jitter {
+                            edge.jitter = jitter;
+                        }
+                        if let Some(packet_loss) = event.packet_loss {
+                            edge.packet_loss = packet_loss;
+                        }
+                    })
+                    .map_err(|e| anyhow::anyhow!(e))
+                    .with_context(|| {
+                        format!(
+                            "Failed to apply the edge event at time '{}' for edge {}->{}",
+                            event.time, event.source, event.target,
+                        )
+                    })?;
+            }
+
+            let paths = generate_paths(
+                &graph,
+                &used_nodes,
+                config.network.use_shortest_path.unwrap(),
+                config.network.use_ecmp.unwrap(),
+            )
+            .with_context(|| {
+                format!(
+                    "Failed to generate routing info for the network graph at time '{}'",
+                    events[0].time
+                )
+            })?;
+
+            routing_info.schedule_paths(time, paths);
+        }
 
         // get all host bandwidths
         let host_bandwidths = hosts
@@ -149,15 +266,29 @@ impl SimConfig {
                     down_bytes: host.bandwidth_down_bits.unwrap() / 8,
                 };
 
-                (host.ip_addr.unwrap(), bw)
+                (
+                    host.ip_addr.unwrap(),
+                    HostBandwidth::new(bw, &host.bandwidth_changes),
+                )
             })
             .collect();
 
+        let partitions = config
+            .network
+            .partitions
+            .iter()
+            .flatten()
+            .map(|x| build_partition(x, &hosts))
+            .collect::<anyhow::Result<_>>()?;
+        let partitions = PartitionTable::new(partitions);
+
         Ok(Self {
             random,
             ip_assignment,
+            ipv6_aliases,
             routing_info,
             host_bandwidths,
+            partitions,
             hosts,
         })
     }
@@ -174,7 +305,9 @@ pub struct HostInfo {
     pub cpu_precision: Option<SimulationTime>,
     pub bandwidth_down_bits: Option<u64>,
     pub bandwidth_up_bits: Option<u64>,
+    pub bandwidth_changes: Vec<BandwidthChange>,
     pub ip_addr: Option<std::net::IpAddr>,
+    pub ip6_addr: Option<std::net::Ipv6Addr>,
     pub log_level: Option<LogLevel>,
     pub pcap_config: Option<PcapConfig>,
     pub heartbeat_log_level: Option<LogLevel>,
@@ -185,6 +318,9 @@ pub struct HostInfo {
     pub autotune_send_buf: bool,
     pub autotune_recv_buf: bool,
     pub qdisc: QDiscMode,
+    pub tcp_congestion_control: TcpCongestionControl,
+    pub tcp_ecn: bool,
+    pub router_queue: RouterQueueConfig,
 }
 
 #[derive(Clone)]
@@ -198,12 +334,60 @@ pub struct ProcessInfo {
     pub expected_final_state: ProcessFinalState,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct Bandwidth {
     pub up_bytes: u64,
     pub down_bytes: u64,
 }
 
+/// A host's bandwidth over the course of the simulation.
+#[derive(Debug, Clone)]
+pub struct HostBandwidth {
+    initial: Bandwidth,
+    /// The bandwidth starting at each change time, in order of increasing time.
+    scheduled: Vec<(SimulationTime, Bandwidth)>,
+}
+
+impl HostBandwidth {
+    /// Build the host's bandwidth from its initial bandwidth and its bandwidth changes, which
+    /// must be sorted by time.
+    pub fn new(initial: Bandwidth, changes: &[BandwidthChange]) -> Self {
+        let mut current = initial;
+        let scheduled = changes
+            .iter()
+            .map(|change| {
+                if let Some(down_bits) = change.down_bits {
+                    current.down_bytes = down_bits / 8;
+                }
+                if let Some(up_bits) = change.up_bits {
+                    current.up_bytes = up_bits / 8;
+                }
+                (change.time, current)
+            })
+            .collect();
+
+        Self { initial, scheduled }
+    }
+
+    /// Get the host's bandwidth at the given simulation time.
+    pub fn at(&self, time: SimulationTime) -> Bandwidth {
+        // the number of bandwidth changes that have happened by `time`
+        let num_changes = self.scheduled.partition_point(|(t, _)| *t <= time);
+        match num_changes {
+            0 => self.initial,
+            x => self.scheduled[x - 1].1,
+        }
+    }
+}
+
+/// A change to a host's bandwidth at a given simulation time.
+#[derive(Debug, Clone, Copy)]
+pub struct BandwidthChange {
+    pub time: SimulationTime,
+    pub down_bits: Option<u64>,
+    pub up_bits: Option<u64>,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct PcapConfig {
     pub capture_size: u64,
@@ -237,6 +421,8 @@ fn build_host(
         })
         .collect::<anyhow::Result<_>>()?;
 
+    let router_queue = build_router_queue(&host.host_options)?;
+
     Ok(HostInfo {
         name: hostname,
         processes,
@@ -254,8 +440,10 @@ fn build_host(
         bandwidth_up_bits: host
             .bandwidth_down
             .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
+        bandwidth_changes: Vec::new(),
 
         ip_addr: host.ip_addr.map(|x| x.into()),
+        ip6_addr: host.ip6_addr,
         log_level: host.host_options.log_level.flatten(),
         pcap_config: host
             .host_options
@@ -300,6 +488,50 @@ fn build_host(
         autotune_send_buf: config.experimental.socket_send_autotune.unwrap(),
         autotune_recv_buf: config.experimental.socket_recv_autotune.unwrap(),
         qdisc: config.experimental.interface_qdisc.unwrap(),
+        tcp_congestion_control: host.host_options.tcp_congestion_control.unwrap(),
+        tcp_ecn: host.host_options.tcp_ecn.unwrap(),
+        router_queue,
+    })
+}
+
+/// For the router queue options of a host, build a `RouterQueueConfig` object.
+fn build_router_queue(options: &HostDefaultOptions) -> anyhow::Result<RouterQueueConfig> {
+    let limit: usize = options.router_queue_limit.unwrap().try_into().unwrap();
+
+    if limit == 0 {
+        return Err(anyhow::anyhow!(
+            "The router queue limit must be greater than 0"
+        ));
+    }
+
+    Ok(match options.router_queue.unwrap() {
+        RouterQueueDiscipline::Codel => RouterQueueConfig::CoDel,
+        RouterQueueDiscipline::DropTail => RouterQueueConfig::DropTail { limit },
+        RouterQueueDiscipline::Red => {
+            let params = RedParams {
+                min_threshold: options.router_red_min_threshold.unwrap(),
+                max_threshold: options.router_red_max_threshold.unwrap(),
+                max_probability: options.router_red_max_probability.unwrap(),
+            };
+
+            if params.min_threshold >= params.max_threshold {
+                return Err(anyhow::anyhow!(
+                    "The RED minimum threshold '{}' must be less than the maximum threshold '{}'",
+                    params.min_threshold,
+                    params.max_threshold,
+                ));
+            }
+
+            if !(params.max_probability > 0.0 && params.max_probability <= 1.0) {
+                return Err(anyhow::anyhow!(
+                    "The RED maximum probability '{}' must be in the range (0, 1]",
+                    params.max_probability,
+                ));
+            }
+
+            RouterQueueConfig::Red { limit, params }
+        }
+        RouterQueueDiscipline::FqCodel => RouterQueueConfig::FqCoDel { limit },
     })
 }
 
@@ -394,6 +626,58 @@ fn build_process(proc: &ProcessOptions, config: &ConfigOptions) -> anyhow::Resul
     })
 }
 
+/// Build a network partition, resolving its hostnames to the hosts' IP addresses.
+fn build_partition(options: &PartitionOptions, hosts: &[HostInfo]) -> anyhow::Result<Partition> {
+    let start = time_to_simtime(options.start_time);
+    let end = options.end_time.map(time_to_simtime);
+
+    if let Some(end) = end {
+        if end <= start {
+            return Err(anyhow::anyhow!(
+                "The partition end time '{}' must be after its start time '{}'",
+                options.end_time.unwrap(),
+                options.start_time,
+            ));
+        }
+    }
+
+    let lookup_ip = |name: &String| -> anyhow::Result<std::net::IpAddr> {
+        hosts
+            .iter()
+            .find(|host| &host.name == name)
+            .map(|host| host.ip_addr.unwrap())
+            .ok_or_else(|| anyhow::anyhow!("The partition host '{name}' doesn't exist"))
+    };
+
+    let sources = options
+        .source_hosts
+        .iter()
+        .map(lookup_ip)
+        .collect::<anyhow::Result<_>>()?;
+    let targets = options
+        .target_hosts
+        .iter()
+        .map(lookup_ip)
+        .collect::<anyhow::Result<_>>()?;
+
+    let symmetric = options.symmetric.unwrap();
+    let arrow = if symmetric { "<->" } else { "->" };
+    let description = format!(
+        "[{}] {arrow} [{}]",
+        options.source_hosts.join(", "),
+        options.target_hosts.join(", "),
+    );
+
+    Ok(Partition {
+        start,
+        end,
+        sources,
+        targets,
+        symmetric,
+        description,
+    })
+}
+
 /// Generate an IP assignment map using hosts' configured IP addresses and graph node IDs. For hosts
 /// without IP addresses, they will be assigned an arbitrary IP address.
 fn assign_ips(hosts: &mut [HostInfo]) -> anyhow::Result<IpAssignment<u32>> {
@@ -419,45 +703,118 @@ fn assign_ips(hosts: &mut [HostInfo]) -> anyhow::Result<IpAssignment<u32>> {
     Ok(ip_assignment)
 }
 
-/// Generate a map containing routing information (latency, packet loss, etc) for each pair of
-/// nodes.
-fn generate_routing_info(
+/// Map hosts' configured IPv6 addresses to their IPv4 addresses. Must be called after IPv4
+/// addresses have been assigned.
+fn assign_ip6s(hosts: &[HostInfo]) -> anyhow::Result<Ipv6Aliases> {
+    let mut ipv6_aliases = Ipv6Aliases::new();
+
+    for host in hosts.iter().filter(|x| x.ip6_addr.is_some()) {
+        let ip6 = host.ip6_addr.unwrap();
+        let hostname = &host.name;
+
+        if ip6.is_unspecified()
+            || ip6.is_loopback()
+            || ip6.is_multicast()
+            || ip6.to_ipv4_mapped().is_some()
+        {
+            return Err(anyhow::anyhow!(
+                "The IPv6 address {ip6} for host '{hostname}' is not a unicast host address"
+            ));
+        }
+
+        let std::net::IpAddr::V4(ip) = host.ip_addr.unwrap() else {
+            unreachable!("Hosts are only assigned IPv4 addresses");
+        };
+
+        ipv6_aliases
+            .assign(ip6, ip)
+            .with_context(|| format!("Failed to assign IPv6 address {ip6} to host '{hostname}'"))?;
+    }
+
+    Ok(ipv6_aliases)
+}
+
+/// Generate a map containing the path properties (latency, packet loss, etc) and the nodes along
+/// the path for each pair of nodes.
+fn generate_paths(
     graph: &NetworkGraph,
     nodes: &std::collections::HashSet<u32>,
     use_shortest_paths: bool,
-) -> anyhow::Result<RoutingInfo<u32>> {
+    use_ecmp: bool,
+) -> anyhow::Result<PathTable<u32>> {
     // convert gml node IDs to petgraph indexes
     let nodes: Vec<_> = nodes
         .iter()
         .map(|x| *graph.node_id_to_index(*x).unwrap())
         .collect();
 
-    // helper to convert petgraph indexes back to gml node IDs
-    let to_ids = |((src, dst), path)| {
-        let src = graph.node_index_to_id(src).unwrap();
-        let dst = graph.node_index_to_id(dst).unwrap();
-        ((src, dst), path)
-    };
+    // helpers to convert petgraph indexes back to gml node IDs
+    let to_id = |x| graph.node_index_to_id(x).unwrap();
+    let to_ids = |(src, dst)| (to_id(src), to_id(dst));
+
+    let mut primary = HashMap::new();
+    let mut hops = HashMap::new();
 
-    let paths = if use_shortest_paths {
-        graph
+    if use_shortest_paths {
+        let paths = graph
             .compute_shortest_paths(&nodes[..])
             .map_err(|e| anyhow::anyhow!(e))
-            .context("Failed to compute shortest paths between graph nodes")?
-            .into_iter()
-            .map(to_ids)
-            .collect()
+            .context("Failed to compute shortest paths between graph nodes")?;
+
+        for (key, (path_hops, path)) in paths {
+            let key = to_ids(key);
+            primary.insert(key, path);
+            hops.insert(key, path_hops.into_iter().map(to_id).collect());
+        }
     } else {
-        graph
+        let paths = graph
             .get_direct_paths(&nodes[..])
             .map_err(|e| anyhow::anyhow!(e))
-            .context("Failed to get the direct paths between graph nodes")?
-            .into_iter()
-            .map(to_ids)
-            .collect()
-    };
+            .context("Failed to get the direct paths between graph nodes")?;
+
+        for (key, path) in paths {
+            let key = to_ids(key);
+            primary.insert(key, path);
+            // packets always follow the direct edge
+            hops.insert(key, vec![key.0, key.1]);
+        }
+    }
+
+    let mut ecmp = HashMap::new();
+    let mut ecmp_hops = HashMap::new();
+
+    // equal-cost multipath routing is only possible when packets can take different paths
+    if use_shortest_paths && use_ecmp {
+        let paths = graph
+            .compute_equal_cost_paths(&nodes[..])
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("Failed to compute equal-cost paths between graph nodes")?;
+
+        for (key, paths) in paths {
+            let key = to_ids(key);
+            let (paths_hops, paths): (Vec<_>, Vec<_>) = paths.into_iter().unzip();
+            ecmp.insert(key, paths);
+            ecmp_hops.insert(
+                key,
+                paths_hops
+                    .into_iter()
+                    .map(|x| x.into_iter().map(to_id).collect())
+                    .collect(),
+            );
+        }
+    }
+
+    Ok(PathTable {
+        primary,
+        ecmp,
+        hops,
+        ecmp_hops,
+    })
+}
 
-    Ok(RoutingInfo::new(paths))
+/// Convert a configuration time to a `SimulationTime`.
+fn time_to_simtime(time: units::Time<units::TimePrefix>) -> SimulationTime {
+    Duration::from(time).try_into().unwrap()
 }
 
 /// Check that the plugin path is valid.
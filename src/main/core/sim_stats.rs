@@ -1,9 +1,11 @@
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::sync::Mutex;
 
 use anyhow::Context;
 use serde::Serialize;
 
+use crate::network::router::RouterQueueStats;
 use crate::utility::counter::Counter;
 
 /// Simulation statistics to be accessed by a single thread.
@@ -12,6 +14,8 @@ pub struct LocalSimStats {
     pub alloc_counts: RefCell<Counter>,
     pub dealloc_counts: RefCell<Counter>,
     pub syscall_counts: RefCell<Counter>,
+    /// The statistics of each host's router queue, keyed by hostname.
+    pub router_queues: RefCell<BTreeMap<String, RouterQueueStats>>,
 }
 
 impl LocalSimStats {
@@ -20,6 +24,7 @@ impl LocalSimStats {
             alloc_counts: RefCell::new(Counter::new()),
             dealloc_counts: RefCell::new(Counter::new()),
             syscall_counts: RefCell::new(Counter::new()),
+            router_queues: RefCell::new(BTreeMap::new()),
         }
     }
 }
@@ -36,6 +41,7 @@ pub struct SharedSimStats {
     pub alloc_counts: Mutex<Counter>,
     pub dealloc_counts: Mutex<Counter>,
     pub syscall_counts: Mutex<Counter>,
+    pub router_queues: Mutex<BTreeMap<String, RouterQueueStats>>,
 }
 
 impl SharedSimStats {
@@ -44,6 +50,7 @@ impl SharedSimStats {
             alloc_counts: Mutex::new(Counter::new()),
             dealloc_counts: Mutex::new(Counter::new()),
             syscall_counts: Mutex::new(Counter::new()),
+            router_queues: Mutex::new(BTreeMap::new()),
         }
     }
 
@@ -64,6 +71,11 @@ impl SharedSimStats {
         *local_alloc_counts = Counter::new();
         *local_dealloc_counts = Counter::new();
         *local_syscall_counts = Counter::new();
+
+        self.router_queues
+            .lock()
+            .unwrap()
+            .append(&mut local.router_queues.borrow_mut());
     }
 }
 
@@ -78,6 +90,7 @@ impl Default for SharedSimStats {
 struct SimStatsForOutput {
     pub objects: ObjectStatsForOutput,
     pub syscalls: Counter,
+    pub router_queues: BTreeMap<String, RouterQueueStatsForOutput>,
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -86,6 +99,30 @@ struct ObjectStatsForOutput {
     pub dealloc_counts: Counter,
 }
 
+#[derive(Serialize, Clone, Debug)]
+struct RouterQueueStatsForOutput {
+    pub arrived: u64,
+    pub forwarded: u64,
+    pub dropped: u64,
+    pub marked: u64,
+    /// Null if no packets were forwarded.
+    pub mean_delay_ns: Option<u64>,
+    pub max_delay_ns: u64,
+}
+
+impl From<&RouterQueueStats> for RouterQueueStatsForOutput {
+    fn from(stats: &RouterQueueStats) -> Self {
+        Self {
+            arrived: stats.arrived,
+            forwarded: stats.forwarded,
+            dropped: stats.dropped,
+            marked: stats.marked,
+            mean_delay_ns: stats.mean_delay().map(|x| x.as_nanos().try_into().unwrap()),
+            max_delay_ns: stats.max_delay.as_nanos().try_into().unwrap(),
+        }
+    }
+}
+
 impl SimStatsForOutput {
     /// Takes data from `stats` and puts it into a structure designed for output. May reset fields
     /// of `stats`.
@@ -102,6 +139,10 @@ impl SimStatsForOutput {
                 ),
             },
             syscalls: std::mem::replace(&mut stats.syscall_counts.lock().unwrap(), Counter::new()),
+            router_queues: std::mem::take(&mut *stats.router_queues.lock().unwrap())
+                .into_iter()
+                .map(|(hostname, stats)| (hostname, (&stats).into()))
+                .collect(),
         }
     }
 }
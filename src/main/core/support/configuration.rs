@@ -274,6 +274,24 @@ pub struct NetworkOptions {
     #[clap(long, value_name = "bool")]
     #[clap(help = NETWORK_HELP.get("use_shortest_path").unwrap().as_str())]
     pub use_shortest_path: Option<bool>,
+
+    /// When following shortest paths, spread flows across all equal-cost shortest paths between
+    /// nodes rather than using a single path. The path for each flow is chosen from a hash of its
+    /// protocol, addresses, and ports. Requires `use_shortest_path`.
+    #[serde(default = "default_some_false")]
+    #[clap(long, value_name = "bool")]
+    #[clap(help = NETWORK_HELP.get("use_ecmp").unwrap().as_str())]
+    pub use_ecmp: Option<bool>,
+
+    /// Changes to the network graph's edges and nodes at specific simulation times
+    #[clap(skip)]
+    #[serde(default)]
+    pub events: Option<Vec<NetworkEventOptions>>,
+
+    /// Network partitions that drop all packets between groups of hosts for a period of time
+    #[clap(skip)]
+    #[serde(default)]
+    pub partitions: Option<Vec<PartitionOptions>>,
 }
 
 impl NetworkOptions {
@@ -532,6 +550,41 @@ pub struct HostDefaultOptions {
     #[clap(long, value_name = "bytes")]
     #[clap(help = HOST_HELP.get("pcap_capture_size").unwrap().as_str())]
     pub pcap_capture_size: Option<units::Bytes<units::SiPrefixUpper>>,
+
+    /// The congestion control algorithm used by new TCP sockets
+    #[clap(long, value_name = "algorithm")]
+    #[clap(help = HOST_HELP.get("tcp_congestion_control").unwrap().as_str())]
+    pub tcp_congestion_control: Option<TcpCongestionControl>,
+
+    /// Should new TCP connections request ECN?
+    #[clap(long, value_name = "bool")]
+    #[clap(help = HOST_HELP.get("tcp_ecn").unwrap().as_str())]
+    pub tcp_ecn: Option<bool>,
+
+    /// The queueing discipline of the router that queues packets inbound to the host
+    #[clap(long, value_name = "discipline")]
+    #[clap(help = HOST_HELP.get("router_queue").unwrap().as_str())]
+    pub router_queue: Option<RouterQueueDiscipline>,
+
+    /// The maximum number of packets held by the router's queue (not used by `codel`)
+    #[clap(long, value_name = "packets")]
+    #[clap(help = HOST_HELP.get("router_queue_limit").unwrap().as_str())]
+    pub router_queue_limit: Option<u32>,
+
+    /// The average queue length (packets) at which the `red` router queue starts dropping packets
+    #[clap(long, value_name = "packets")]
+    #[clap(help = HOST_HELP.get("router_red_min_threshold").unwrap().as_str())]
+    pub router_red_min_threshold: Option<u32>,
+
+    /// The average queue length (packets) at which the `red` router queue drops every packet
+    #[clap(long, value_name = "packets")]
+    #[clap(help = HOST_HELP.get("router_red_max_threshold").unwrap().as_str())]
+    pub router_red_max_threshold: Option<u32>,
+
+    /// The drop probability of the `red` router queue at `router_red_max_threshold`
+    #[clap(long, value_name = "prob")]
+    #[clap(help = HOST_HELP.get("router_red_max_probability").unwrap().as_str())]
+    pub router_red_max_probability: Option<f64>,
 }
 
 impl HostDefaultOptions {
@@ -543,6 +596,13 @@ impl HostDefaultOptions {
             // capture all the data available from the packet". The maximum length of an IP packet
             // (including the header) is 65535 bytes.
             pcap_capture_size: Some(units::Bytes::new(65535, units::SiPrefixUpper::Base)),
+            tcp_congestion_control: Some(TcpCongestionControl::Reno),
+            tcp_ecn: Some(false),
+            router_queue: Some(RouterQueueDiscipline::Codel),
+            router_queue_limit: Some(1000),
+            router_red_min_threshold: Some(5),
+            router_red_max_threshold: Some(15),
+            router_red_max_probability: Some(0.1),
         }
     }
 
@@ -565,6 +625,13 @@ impl Default for HostDefaultOptions {
             log_level: None,
             pcap_enabled: None,
             pcap_capture_size: None,
+            tcp_congestion_control: None,
+            tcp_ecn: None,
+            router_queue: None,
+            router_queue_limit: None,
+            router_red_min_threshold: None,
+            router_red_max_threshold: None,
+            router_red_max_probability: None,
         }
     }
 }
@@ -651,6 +718,10 @@ pub struct HostOptions {
     #[serde(default)]
     pub ip_addr: Option<std::net::Ipv4Addr>,
 
+    /// IPv6 address to assign to the host
+    #[serde(default)]
+    pub ip6_addr: Option<std::net::Ipv6Addr>,
+
     /// Downstream bandwidth capacity of the host
     #[serde(default)]
     pub bandwidth_down: Option<units::BitsPerSec<units::SiPrefixUpper>>,
@@ -940,10 +1011,45 @@ impl FromStr for QDiscMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum TcpCongestionControl {
+    Reno,
+    Cubic,
+    Bbr,
+}
+
+impl FromStr for TcpCongestionControl {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum RouterQueueDiscipline {
+    Codel,
+    DropTail,
+    Red,
+    FqCodel,
+}
+
+impl FromStr for RouterQueueDiscipline {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum Compression {
     Xz,
+    Gzip,
+    Zstd,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -951,7 +1057,7 @@ pub enum Compression {
 pub struct FileSource {
     /// The path to the file
     pub path: String,
-    /// The file's compression format
+    /// The file's compression format, or null to detect it from the file extension
     pub compression: Option<Compression>,
 }
 
@@ -972,6 +1078,86 @@ pub enum GraphOptions {
     OneGbitSwitch,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NetworkEventOptions {
+    Edge(EdgeEventOptions),
+    Node(NodeEventOptions),
+}
+
+impl NetworkEventOptions {
+    pub fn time(&self) -> units::Time<units::TimePrefix> {
+        match self {
+            Self::Edge(x) => x.time,
+            Self::Node(x) => x.time,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EdgeEventOptions {
+    /// The simulated time at which the change is applied
+    pub time: units::Time<units::TimePrefix>,
+
+    /// The id of the edge's source node
+    pub source: u32,
+
+    /// The id of the edge's target node
+    pub target: u32,
+
+    /// The new latency of the edge
+    #[serde(default)]
+    pub latency: Option<units::Time<units::TimePrefix>>,
+
+    /// The new jitter of the edge
+    #[serde(default)]
+    pub jitter: Option<units::Time<units::TimePrefix>>,
+
+    /// The new packet loss of the edge
+    #[serde(default)]
+    pub packet_loss: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NodeEventOptions {
+    /// The simulated time at which the change is applied
+    pub time: units::Time<units::TimePrefix>,
+
+    /// The id of the node
+    pub id: u32,
+
+    /// The new downstream bandwidth of hosts attached to the node
+    #[serde(default)]
+    pub host_bandwidth_down: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+
+    /// The new upstream bandwidth of hosts attached to the node
+    #[serde(default)]
+    pub host_bandwidth_up: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PartitionOptions {
+    /// The simulated time at which the partition starts
+    pub start_time: units::Time<units::TimePrefix>,
+
+    /// The simulated time at which the partition ends, or null if it never ends
+    #[serde(default)]
+    pub end_time: Option<units::Time<units::TimePrefix>>,
+
+    /// Hosts whose packets to the `target_hosts` are dropped
+    pub source_hosts: Vec<String>,
+
+    /// Hosts that don't receive packets from the `source_hosts`
+    pub target_hosts: Vec<String>,
+
+    /// Also drop packets sent from the `target_hosts` to the `source_hosts`
+    #[serde(default = "default_some_true")]
+    pub symmetric: Option<bool>,
+}
+
 #[derive(Debug, Clone, Serialize, JsonSchema)]
 #[serde(untagged)]
 pub enum ProcessArgs {
@@ -53,6 +53,19 @@ typedef struct Host Host;
 #define CONFIG_TCP_DELACK_MIN (NET_TCP_HZ / 25)
 #define CONFIG_TCP_DELACK_MAX (NET_TCP_HZ / 5)
 
+/**
+ * Maximum time that corked data is held before being sent (TCP_CORK), from "man tcp"
+ */
+#define CONFIG_TCP_CORK_TIMEOUT (NET_TCP_HZ / 5)
+
+/**
+ * Default keepalive idle time and probe interval (in seconds), and the number of unanswered probes
+ * before the connection is dropped, from net/tcp.h
+ */
+#define CONFIG_TCP_KEEPALIVE_TIME (2 * 60 * 60)
+#define CONFIG_TCP_KEEPALIVE_INTVL 75
+#define CONFIG_TCP_KEEPALIVE_PROBES 9
+
 /**
  * Minimum size of the send buffer per socket when TCP-autotuning is used.
  * This value was computed from "man tcp"
@@ -106,6 +119,11 @@ typedef struct Host Host;
  */
 #define CONFIG_HEADER_SIZE_TCP 20
 
+/**
+ * Default ICMP header size in bytes.
+ */
+#define CONFIG_HEADER_SIZE_ICMP 8
+
 /**
  * Header size in bytes of a routable packet with UDP encapsulation; includes
  * the IP and UDP headers but excludes the ethernet header and packet payload.
@@ -118,6 +136,12 @@ typedef struct Host Host;
  */
 #define CONFIG_HEADER_SIZE_TCPIP (CONFIG_HEADER_SIZE_TCP + CONFIG_HEADER_SIZE_IP)
 
+/**
+ * Header size in bytes of a routable packet with ICMP encapsulation; includes
+ * the IP and ICMP headers but excludes the ethernet header and packet payload.
+ */
+#define CONFIG_HEADER_SIZE_ICMPIP (CONFIG_HEADER_SIZE_ICMP + CONFIG_HEADER_SIZE_IP)
+
 /**
  * Maximum size of an IP packet without fragmenting over Ethernetv2
  */
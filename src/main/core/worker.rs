@@ -16,15 +16,19 @@ use shadow_shim_helper_rs::HostId;
 use super::work::event_queue::EventQueue;
 use crate::core::controller::ShadowStatusBarState;
 use crate::core::scheduler::runahead::Runahead;
-use crate::core::sim_config::Bandwidth;
+use crate::core::sim_config::{Bandwidth, HostBandwidth};
 use crate::core::sim_stats::{LocalSimStats, SharedSimStats};
 use crate::core::work::event::Event;
 use crate::cshadow;
 use crate::host::host::Host;
 use crate::host::process::{Process, ProcessId};
 use crate::host::thread::{Thread, ThreadId};
-use crate::network::graph::{IpAssignment, RoutingInfo};
-use crate::network::packet::PacketRc;
+use crate::network::bottleneck::{Bottlenecks, PendingPacket};
+use crate::network::graph::{IpAssignment, Ipv6Aliases, PathProperties, RoutingInfo};
+use crate::network::multicast::{self, MulticastGroups};
+use crate::network::packet::{PacketRc, PacketStatus};
+use crate::network::partition::PartitionTable;
+use crate::network::router::RouterQueueStats;
 use crate::utility::childpid_watcher::ChildPidWatcher;
 use crate::utility::counter::Counter;
 use crate::utility::status_bar;
@@ -329,24 +333,49 @@ impl Worker {
         assert!(!packet.is_null());
 
         let current_time = Worker::current_time().unwrap();
-        let round_end_time = Worker::round_end_time().unwrap();
 
         let is_completed = current_time >= Worker::with(|w| w.shared.sim_end_time).unwrap();
-        let is_bootstrapping =
-            current_time < Worker::with(|w| w.shared.bootstrap_end_time).unwrap();
 
         if is_completed {
             // the simulation is over, don't bother
             return;
         }
 
-        let src_ip = unsafe { cshadow::packet_getSourceIP(packet) };
         let dst_ip = unsafe { cshadow::packet_getDestinationIP(packet) };
-        let payload_size = unsafe { cshadow::packet_getPayloadSize(packet) };
-
-        let src_ip: std::net::Ipv4Addr = u32::from_be(src_ip).into();
         let dst_ip: std::net::Ipv4Addr = u32::from_be(dst_ip).into();
 
+        // multicast and broadcast packets are sent separately to each host in the group, and each
+        // copy takes its own path with its own latency and chance of being dropped
+        if multicast::is_group_address(dst_ip) {
+            // like linux routers, limited broadcast packets aren't forwarded, so they only reach
+            // the hosts on the sender's graph node
+            if dst_ip.is_broadcast() {
+                unsafe { cshadow::packet_setTTL(packet, 1) };
+            }
+
+            let dsts = Worker::with(|w| {
+                w.shared
+                    .multicast_groups
+                    .destinations(dst_ip, src_host.id())
+            })
+            .unwrap();
+
+            for (dst_host_id, dst_host_ip) in dsts {
+                // the delivery statuses (such as being dropped) are added to a separate copy for
+                // each host, so that they don't appear on the copies sent to the other hosts
+                let packet = PacketRc::from_raw(unsafe { cshadow::packet_copy(packet) });
+                unsafe {
+                    Worker::send_packet_to_host(
+                        src_host,
+                        packet.borrow_inner(),
+                        dst_host_id,
+                        dst_host_ip,
+                    )
+                };
+            }
+            return;
+        }
+
         let dst_host_id = Worker::with(|w| {
             w.shared
                 .resolve_ip_to_host_id(dst_ip)
@@ -354,20 +383,114 @@ impl Worker {
         })
         .unwrap();
 
+        unsafe { Worker::send_packet_to_host(src_host, packet, dst_host_id, dst_ip) };
+    }
+
+    /// Send the packet to the host `dst_host_id`. The path that the packet takes is chosen using
+    /// the host's address `dst_ip`.
+    ///
+    /// # Safety
+    ///
+    /// `packet` must be valid and not accessed by another thread while this function is
+    /// running.
+    unsafe fn send_packet_to_host(
+        src_host: &Host,
+        packet: *mut cshadow::Packet,
+        dst_host_id: HostId,
+        dst_ip: std::net::Ipv4Addr,
+    ) {
+        let current_time = Worker::current_time().unwrap();
+        let round_end_time = Worker::round_end_time().unwrap();
+
+        let is_bootstrapping =
+            current_time < Worker::with(|w| w.shared.bootstrap_end_time).unwrap();
+
+        let src_ip = unsafe { cshadow::packet_getSourceIP(packet) };
+        let payload_size = unsafe { cshadow::packet_getPayloadSize(packet) };
+
+        let src_ip: std::net::Ipv4Addr = u32::from_be(src_ip).into();
+
         let src_ip = std::net::IpAddr::V4(src_ip);
         let dst_ip = std::net::IpAddr::V4(dst_ip);
 
+        // all packets of a flow hash to the same value, so they take the same path when using
+        // equal-cost multipath routing
+        let flow_hash = unsafe { flow_hash(packet) };
+        let path = Worker::with(|w| {
+            w.shared
+                .flow_path(src_ip, dst_ip, current_time, flow_hash)
+                .unwrap()
+        })
+        .unwrap();
+
+        // drop all packets (including control packets and packets sent while bootstrapping) that
+        // cross an active network partition
+        if Worker::with(|w| w.shared.is_partitioned(src_ip, dst_ip, current_time)).unwrap() {
+            unsafe {
+                cshadow::packet_addDeliveryStatus(
+                    packet,
+                    cshadow::_PacketDeliveryStatusFlags_PDS_INET_DROPPED,
+                )
+            };
+            return;
+        }
+
+        // each router hop decrements the TTL, and the packet is dropped by the router where it
+        // reaches 0
+        let ttl = unsafe { cshadow::packet_getTTL(packet) };
+        if path.num_hops > 0 && u32::from(ttl) <= path.num_hops {
+            unsafe {
+                cshadow::packet_addDeliveryStatus(
+                    packet,
+                    cshadow::_PacketDeliveryStatusFlags_PDS_INET_DROPPED,
+                )
+            };
+
+            // like linux routers, never send an error for a group packet or for another error
+            let packet_dst_ip = u32::from_be(unsafe { cshadow::packet_getDestinationIP(packet) });
+            if !multicast::is_group_address(packet_dst_ip.into())
+                && unsafe { cshadow::packet_isICMPError(packet) } == 0
+            {
+                let error = PacketRc::new();
+                unsafe { cshadow::packet_setICMPTimeExceeded(error.borrow_inner(), packet) };
+
+                // the error travels to the router where the packet expired and back
+                let expired_latency_ns =
+                    2 * path.latency_ns * u64::from(ttl) / u64::from(path.num_hops);
+                let mut deliver_time =
+                    current_time + SimulationTime::from_nanos(expired_latency_ns);
+                if deliver_time < round_end_time {
+                    deliver_time = round_end_time;
+                }
+
+                Worker::update_next_event_time(deliver_time);
+                Worker::with(|w| {
+                    w.shared
+                        .push_packet_to_host(error, src_host.id(), deliver_time, src_host)
+                })
+                .unwrap();
+            }
+            return;
+        }
+
         // check if network reliability forces us to 'drop' the packet
-        let reliability: f64 = Worker::with(|w| w.shared.reliability(src_ip, dst_ip).unwrap())
-            .unwrap()
-            .try_into()
-            .unwrap();
+        let reliability: f64 = (1.0 - path.packet_loss).try_into().unwrap();
         let chance: f64 = src_host.random_mut().gen();
 
         // don't drop control packets with length 0, otherwise congestion control has problems
         // responding to packet loss
         // https://github.com/shadow/shadow/issues/2517
-        if !is_bootstrapping && chance >= reliability && payload_size > 0 {
+        let can_drop = !is_bootstrapping && payload_size > 0;
+
+        // optional path properties are only sampled from the host's rng if the path has them, so
+        // that graphs that don't use a property draw the same random numbers (and have the same
+        // simulation results) as before the property existed
+        let is_burst_lost = match path.burst_loss {
+            Some(burst_loss) if can_drop => src_host.sample_burst_loss(dst_ip, &burst_loss),
+            _ => false,
+        };
+
+        if can_drop && (chance >= reliability || is_burst_lost) {
             unsafe {
                 cshadow::packet_addDeliveryStatus(
                     packet,
@@ -377,10 +500,40 @@ impl Worker {
             return;
         }
 
-        let delay = Worker::with(|w| w.shared.latency(src_ip, dst_ip).unwrap()).unwrap();
+        let latency = SimulationTime::from_nanos(path.latency_ns);
+        let jitter = SimulationTime::from_nanos(path.jitter_ns);
+
+        // the jitter is always smaller than the latency, so the delay is never 0
+        let min_delay = latency - jitter;
 
-        Worker::update_lowest_used_latency(delay);
-        Worker::with(|w| w.shared.increment_packet_count(src_ip, dst_ip)).unwrap();
+        // only sample the jitter if the path has jitter (see the burst loss above)
+        let delay = if jitter.is_zero() {
+            latency
+        } else {
+            let max_offset: u64 = (jitter * 2).as_nanos().try_into().unwrap();
+            min_delay + SimulationTime::from_nanos(src_host.random_mut().gen_range(0..=max_offset))
+        };
+
+        let reorder_probability = path.reorder_probability;
+        let reorder_delay = SimulationTime::from_nanos(path.reorder_delay_ns);
+        let duplicate_probability = path.duplicate_probability;
+
+        // only sample the reordering and duplication if the path has them (see the burst loss above)
+        let is_reordered = !is_bootstrapping
+            && reorder_probability > 0.0
+            && src_host.random_mut().gen::<f32>() < reorder_probability;
+        let is_duplicated = !is_bootstrapping
+            && duplicate_probability > 0.0
+            && src_host.random_mut().gen::<f32>() < duplicate_probability;
+
+        // use the smallest possible latency for this path rather than the sampled delay so that
+        // the runahead doesn't depend on the sampled jitter
+        Worker::update_lowest_used_latency(min_delay);
+        Worker::with(|w| {
+            w.shared
+                .increment_packet_count(src_ip, dst_ip, current_time, flow_hash)
+        })
+        .unwrap();
 
         // TODO: this should change for sending to remote manager (on a different machine); this is
         // the only place where tasks are sent between separate host
@@ -392,24 +545,71 @@ impl Worker {
             )
         };
 
+        // the TTL is decremented by each router on the path
+        let remaining_ttl = ttl - u8::try_from(path.num_hops).unwrap();
+
+        // the duplicate is a separate copy of the packet, and isn't held back if the original is
+        // reordered
+        let duplicate = is_duplicated.then(|| {
+            let mut duplicate = PacketRc::from_raw(unsafe { cshadow::packet_copy(packet) });
+            duplicate.set_ttl(remaining_ttl);
+            duplicate.add_status(PacketStatus::InetDuplicated);
+            duplicate
+        });
+
         // copy the packet
-        let packet = PacketRc::from_raw(unsafe { cshadow::packet_copy(packet) });
+        let mut packet = PacketRc::from_raw(unsafe { cshadow::packet_copy(packet) });
+        packet.set_ttl(remaining_ttl);
+
+        // a reordered packet is held back so that packets sent after it can overtake it
+        let packet_delay = if is_reordered {
+            packet.add_status(PacketStatus::InetReordered);
+            delay + reorder_delay
+        } else {
+            delay
+        };
 
-        // delay the packet until the next round
-        let mut deliver_time = current_time + delay;
-        if deliver_time < round_end_time {
-            deliver_time = round_end_time;
-        }
+        // bottleneck edges don't limit the bandwidth while bootstrapping
+        let bottlenecks = if is_bootstrapping {
+            Vec::new()
+        } else {
+            path.bottlenecks
+        };
 
-        // we may have sent this packet after the destination host finished running the current
-        // round and calculated its min event time, so we put this in our min event time instead
-        Worker::update_next_event_time(deliver_time);
+        let deliver = |packet: PacketRc, delay: SimulationTime| {
+            // delay the packet until the next round
+            let mut deliver_time = current_time + delay;
+            if deliver_time < round_end_time {
+                deliver_time = round_end_time;
+            }
 
-        Worker::with(|w| {
-            w.shared
-                .push_packet_to_host(packet, dst_host_id, deliver_time, src_host)
-        })
-        .unwrap();
+            // we may have sent this packet after the destination host finished running the
+            // current round and calculated its min event time, so we put this in our min event
+            // time instead
+            Worker::update_next_event_time(deliver_time);
+
+            Worker::with(|w| {
+                if bottlenecks.is_empty() {
+                    w.shared
+                        .push_packet_to_host(packet, dst_host_id, deliver_time, src_host);
+                } else {
+                    // the packet will be queued on the bottleneck edges at the end of the round
+                    w.shared.bottlenecks.push_pending(PendingPacket {
+                        send_time: current_time,
+                        bottlenecks: bottlenecks.clone(),
+                        size_bytes: packet.total_size().try_into().unwrap(),
+                        event: Event::new_packet(packet, deliver_time, src_host),
+                        dst_host_id,
+                    });
+                }
+            })
+            .unwrap();
+        };
+
+        deliver(packet, packet_delay);
+        if let Some(duplicate) = duplicate {
+            deliver(duplicate, delay);
+        }
     }
 
     // Runs `f` with a shared reference to the current thread's Worker. Returns
@@ -474,6 +674,18 @@ impl Worker {
         });
     }
 
+    /// Record the statistics of a host's router queue, to be written to the simulation's
+    /// statistics file.
+    pub fn add_router_queue_stats(hostname: &str, stats: RouterQueueStats) {
+        Worker::with(|w| {
+            w.sim_stats
+                .router_queues
+                .borrow_mut()
+                .insert(hostname.to_string(), stats);
+        })
+        .unwrap()
+    }
+
     pub fn add_to_global_sim_stats() {
         Worker::with(|w| SIM_STATS.add_from_local_stats(&w.sim_stats)).unwrap()
     }
@@ -482,6 +694,31 @@ impl Worker {
         Worker::with(|w| w.shared.is_routable(src, dst)).unwrap()
     }
 
+    /// Add or remove the host's membership of the multicast group `group`. Other hosts will start
+    /// or stop sending the group's packets to the host from the next scheduling round.
+    pub fn update_multicast_membership(
+        host_id: HostId,
+        group: std::net::Ipv4Addr,
+        is_member: bool,
+    ) {
+        Worker::with(|w| {
+            w.shared
+                .multicast_groups
+                .push_pending(host_id, group, is_member)
+        })
+        .unwrap();
+    }
+
+    /// Get the IPv4 address of the host that was assigned the IPv6 address `ip`.
+    pub fn ipv6_alias_to_ipv4(ip: std::net::Ipv6Addr) -> Option<std::net::Ipv4Addr> {
+        Worker::with(|w| w.shared.ipv6_aliases.to_ipv4(ip)).unwrap()
+    }
+
+    /// Get the IPv6 address of the host with IPv4 address `ip`, if it was assigned one.
+    pub fn ipv4_to_ipv6_alias(ip: std::net::Ipv4Addr) -> Option<std::net::Ipv6Addr> {
+        Worker::with(|w| w.shared.ipv6_aliases.to_ipv6(ip)).unwrap()
+    }
+
     pub fn increment_plugin_error_count() {
         Worker::with(|w| w.shared.increment_plugin_error_count()).unwrap()
     }
@@ -497,8 +734,12 @@ impl Worker {
 #[derive(Debug)]
 pub struct WorkerShared {
     pub ip_assignment: IpAssignment<u32>,
+    pub ipv6_aliases: Ipv6Aliases,
     pub routing_info: RoutingInfo<u32>,
-    pub host_bandwidths: HashMap<std::net::IpAddr, Bandwidth>,
+    pub host_bandwidths: HashMap<std::net::IpAddr, HostBandwidth>,
+    pub partitions: PartitionTable,
+    pub bottlenecks: Bottlenecks,
+    pub multicast_groups: MulticastGroups,
     pub dns: SyncSendPointer<cshadow::DNS>,
     // allows for easy updating of the status bar's state
     pub status_logger_state: Option<Arc<status_bar::Status<ShadowStatusBarState>>>,
@@ -526,34 +767,93 @@ impl WorkerShared {
         unsafe { self.dns.ptr().as_ref() }.unwrap()
     }
 
-    pub fn latency(&self, src: std::net::IpAddr, dst: std::net::IpAddr) -> Option<SimulationTime> {
+    /// Get the properties of the path between two addresses at the given time.
+    fn path(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        time: EmulatedTime,
+    ) -> Option<PathProperties> {
         let src = self.ip_assignment.get_node(src)?;
         let dst = self.ip_assignment.get_node(dst)?;
 
-        Some(SimulationTime::from_nanos(
-            self.routing_info.path(src, dst)?.latency_ns,
-        ))
+        self.routing_info.path(src, dst, time.to_abs_simtime())
     }
 
-    pub fn reliability(&self, src: std::net::IpAddr, dst: std::net::IpAddr) -> Option<f32> {
+    /// Get the properties of the path taken by a flow between two addresses at the given time.
+    /// `flow_hash` chooses between equal-cost paths when using equal-cost multipath routing.
+    pub fn flow_path(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        time: EmulatedTime,
+        flow_hash: u64,
+    ) -> Option<PathProperties> {
         let src = self.ip_assignment.get_node(src)?;
         let dst = self.ip_assignment.get_node(dst)?;
 
-        Some(1.0 - self.routing_info.path(src, dst)?.packet_loss)
+        self.routing_info
+            .flow_path(src, dst, time.to_abs_simtime(), flow_hash)
+    }
+
+    pub fn latency(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        time: EmulatedTime,
+    ) -> Option<SimulationTime> {
+        Some(SimulationTime::from_nanos(
+            self.path(src, dst, time)?.latency_ns,
+        ))
+    }
+
+    pub fn reliability(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        time: EmulatedTime,
+    ) -> Option<f32> {
+        Some(1.0 - self.path(src, dst, time)?.packet_loss)
     }
 
-    pub fn bandwidth(&self, ip: std::net::IpAddr) -> Option<&Bandwidth> {
-        self.host_bandwidths.get(&ip)
+    /// Get the bandwidth of the host at an address at the given time, including any changes from
+    /// node events.
+    pub fn bandwidth(&self, ip: std::net::IpAddr, time: EmulatedTime) -> Option<Bandwidth> {
+        Some(self.host_bandwidths.get(&ip)?.at(time.to_abs_simtime()))
     }
 
-    pub fn increment_packet_count(&self, src: std::net::IpAddr, dst: std::net::IpAddr) {
+    /// Increment the number of packets sent on the path taken by a flow between two addresses at
+    /// the given time.
+    pub fn increment_packet_count(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        time: EmulatedTime,
+        flow_hash: u64,
+    ) {
         let src = self.ip_assignment.get_node(src).unwrap();
         let dst = self.ip_assignment.get_node(dst).unwrap();
 
-        self.routing_info.increment_packet_count(src, dst)
+        self.routing_info
+            .increment_packet_count(src, dst, time.to_abs_simtime(), flow_hash)
+    }
+
+    /// Returns true if packets from `src` to `dst` are dropped at the given time due to a network
+    /// partition.
+    pub fn is_partitioned(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        time: EmulatedTime,
+    ) -> bool {
+        self.partitions
+            .is_partitioned(src, dst, time.to_abs_simtime())
     }
 
     pub fn is_routable(&self, src: std::net::IpAddr, dst: std::net::IpAddr) -> bool {
+        // network partitions are not considered here; partitioned hosts are still routable, but
+        // their packets are silently dropped so that the partition looks like a blackhole rather
+        // than an unreachable network
         if self.ip_assignment.get_node(src).is_none() {
             return false;
         }
@@ -624,9 +924,34 @@ impl WorkerShared {
         src_host: &Host,
     ) {
         let event = Event::new_packet(packet, time, src_host);
+        self.push_event_to_host(event, dst_host_id);
+    }
+
+    fn push_event_to_host(&self, event: Event, dst_host_id: HostId) {
         let event_queue = self.event_queues.get(&dst_host_id).unwrap();
         event_queue.lock().unwrap().push(event);
     }
+
+    /// Queue the packets that were sent over bottleneck edges during the current round, and push
+    /// them to their destination hosts. Must be called after each round, while no hosts are
+    /// running. Returns the earliest delivery time of the pushed packets.
+    pub fn flush_bottleneck_packets(&self) -> Option<EmulatedTime> {
+        self.bottlenecks
+            .process_pending()
+            .into_iter()
+            .map(|(event, dst_host_id)| {
+                let time = event.time();
+                self.push_event_to_host(event, dst_host_id);
+                time
+            })
+            .min()
+    }
+
+    /// Apply the multicast group memberships that changed during the current round. Must be
+    /// called after each round, while no hosts are running.
+    pub fn flush_multicast_memberships(&self) {
+        self.multicast_groups.process_pending();
+    }
 }
 
 impl std::ops::Drop for WorkerShared {
@@ -644,6 +969,21 @@ pub fn with_global_sim_stats<T>(f: impl FnOnce(&SharedSimStats) -> T) -> T {
     f(&SIM_STATS)
 }
 
+/// A hash of the packet's protocol, addresses, and ports, which is the same for all packets of a
+/// flow. See [`crate::network::packet::flow_hash`].
+unsafe fn flow_hash(packet: *const cshadow::Packet) -> u64 {
+    let src_ip = u32::from_be(unsafe { cshadow::packet_getSourceIP(packet) });
+    let src_port = u16::from_be(unsafe { cshadow::packet_getSourcePort(packet) });
+    let dst_ip = u32::from_be(unsafe { cshadow::packet_getDestinationIP(packet) });
+    let dst_port = u16::from_be(unsafe { cshadow::packet_getDestinationPort(packet) });
+
+    crate::network::packet::flow_hash(
+        unsafe { cshadow::packet_getProtocol(packet) },
+        std::net::SocketAddrV4::new(src_ip.into(), src_port),
+        std::net::SocketAddrV4::new(dst_ip.into(), dst_port),
+    )
+}
+
 mod export {
     use shadow_shim_helper_rs::emulated_time::CEmulatedTime;
     use shadow_shim_helper_rs::simulation_time::CSimulationTime;
@@ -665,7 +1005,8 @@ mod export {
         let src = std::net::IpAddr::V4(u32::from_be(src).into());
         let dst = std::net::IpAddr::V4(u32::from_be(dst).into());
 
-        let latency = Worker::with(|w| w.shared.latency(src, dst)).unwrap();
+        let now = Worker::current_time().unwrap();
+        let latency = Worker::with(|w| w.shared.latency(src, dst, now)).unwrap();
         SimulationTime::to_c_simtime(latency)
     }
 
@@ -673,14 +1014,16 @@ mod export {
     #[no_mangle]
     pub extern "C" fn worker_getBandwidthDownBytes(ip: libc::in_addr_t) -> u64 {
         let ip = std::net::IpAddr::V4(u32::from_be(ip).into());
-        Worker::with(|w| w.shared.bandwidth(ip).unwrap().down_bytes).unwrap()
+        let now = Worker::current_time().unwrap();
+        Worker::with(|w| w.shared.bandwidth(ip, now).unwrap().down_bytes).unwrap()
     }
 
     /// Addresses must be provided in network byte order.
     #[no_mangle]
     pub extern "C" fn worker_getBandwidthUpBytes(ip: libc::in_addr_t) -> u64 {
         let ip = std::net::IpAddr::V4(u32::from_be(ip).into());
-        Worker::with(|w| w.shared.bandwidth(ip).unwrap().up_bytes).unwrap()
+        let now = Worker::current_time().unwrap();
+        Worker::with(|w| w.shared.bandwidth(ip, now).unwrap().up_bytes).unwrap()
     }
 
     /// Addresses must be provided in network byte order.
@@ -120,6 +120,9 @@ bitflags::bitflags! {
         /// A listening socket is allowing connections. Only applicable to connection-oriented unix
         /// sockets.
         const SOCKET_ALLOWING_CONNECT = c::_Status_STATUS_SOCKET_ALLOWING_CONNECT;
+        /// A closing TCP socket no longer has unacknowledged data, either because the peer
+        /// acknowledged it or because the connection is gone. Only applicable to TCP sockets.
+        const SOCKET_LINGER_DONE = c::_Status_STATUS_SOCKET_LINGER_DONE;
     }
 }
 
@@ -79,6 +79,11 @@ impl Pipe {
         self.buffer.as_ref().unwrap().borrow().max_len()
     }
 
+    /// The number of bytes that can currently be written to the pipe.
+    pub fn space_available(&self) -> usize {
+        self.buffer.as_ref().unwrap().borrow().space_available()
+    }
+
     pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
         if self.state.contains(FileState::CLOSED) {
             log::warn!("Attempting to close an already-closed pipe");
@@ -163,6 +168,47 @@ impl Pipe {
         }
     }
 
+    /// Copy data from the pipe into `bytes` without removing it from the pipe. Like
+    /// [`readv()`](Self::readv), returns `EWOULDBLOCK` if the pipe is empty but has writers.
+    pub fn peek(&self, bytes: &mut [u8]) -> Result<usize, SyscallError> {
+        // if the file is not open for reading, return EBADF
+        if !self.mode.contains(FileMode::READ) {
+            return Err(linux_api::errno::Errno::EBADF.into());
+        }
+
+        let buffer = self.buffer.as_ref().unwrap().borrow();
+        let num_copied = buffer.peek(bytes)?;
+
+        if num_copied == 0 && !bytes.is_empty() && buffer.num_writers() > 0 {
+            Err(Errno::EWOULDBLOCK.into())
+        } else {
+            Ok(num_copied)
+        }
+    }
+
+    /// Remove up to `len` bytes from the front of the pipe, for example after they were
+    /// [peeked](Self::peek). Returns the number of bytes removed.
+    pub fn discard(
+        &mut self,
+        len: usize,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<usize, SyscallError> {
+        // if the file is not open for reading, return EBADF
+        if !self.mode.contains(FileMode::READ) {
+            return Err(linux_api::errno::Errno::EBADF.into());
+        }
+
+        let mut bytes = vec![0u8; len];
+        let (_num_copied, num_removed_from_buf) = self
+            .buffer
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .read(&mut bytes[..], cb_queue)?;
+
+        Ok(num_removed_from_buf)
+    }
+
     pub fn writev(
         &mut self,
         iovs: &[IoVec],
@@ -0,0 +1,915 @@
+//! ICMP "ping" sockets (`socket(AF_INET, SOCK_DGRAM, IPPROTO_ICMP)`), which allow unprivileged
+//! processes to send ICMP echo requests and receive the corresponding echo replies. See the
+//! "ping_group_range" section of icmp(7).
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::Arc;
+
+use atomic_refcell::AtomicRefCell;
+use bytes::{BufMut, Bytes, BytesMut};
+use linux_api::errno::Errno;
+use linux_api::ioctls::IoctlRequest;
+use nix::sys::socket::{AddressFamily, MsgFlags, Shutdown, SockaddrIn};
+use shadow_shim_helper_rs::syscall_types::ForeignPtr;
+
+use crate::core::worker::Worker;
+use crate::cshadow as c;
+use crate::host::descriptor::socket::inet::udp::MessageBuffer;
+use crate::host::descriptor::socket::inet::{self, InetFamily, InetSocket};
+use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, ShutdownFlags};
+use crate::host::descriptor::{
+    File, FileMode, FileState, FileStatus, OpenFile, Socket, StateEventSource, StateListenerFilter,
+    SyscallResult,
+};
+use crate::host::memory_manager::MemoryManager;
+use crate::host::network::interface::{FifoPacketPriority, ReuseOptions};
+use crate::host::network::namespace::{AssociationHandle, NetworkNamespace};
+use crate::host::syscall::io::{write_partial, IoVec, IoVecReader, IoVecWriter};
+use crate::host::syscall_types::SyscallError;
+use crate::network::packet::{PacketRc, PacketStatus};
+use crate::utility::callback_queue::{CallbackQueue, Handle};
+use crate::utility::sockaddr::SockaddrStorage;
+use crate::utility::{HostTreePointer, ObjectCounter};
+
+/// The size of an ICMP echo header (type, code, checksum, identifier, and sequence number).
+const ICMP_ECHO_HEADER_SIZE: usize = 8;
+
+/// Maximum size of an ICMP message (including the ICMP header) that we are allowed to send.
+// 65,535 (2^16 - 1) - 20 (ip header)
+const CONFIG_ICMP_MAX_SIZE: usize = 65515;
+
+pub struct IcmpSocket {
+    event_source: StateEventSource,
+    status: FileStatus,
+    state: FileState,
+    shutdown_status: ShutdownFlags,
+    send_buffer: MessageBuffer<MessageSendHeader>,
+    recv_buffer: MessageBuffer<MessageRecvHeader>,
+    peer_addr: Option<SocketAddrV4>,
+    /// The bound address. The port is used as the ICMP echo identifier.
+    bound_addr: Option<SocketAddrV4>,
+    association: Option<AssociationHandle>,
+    /// The `IP_TTL` option.
+    ttl: u8,
+    // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
+    // this file
+    has_open_file: bool,
+    _counter: ObjectCounter,
+}
+
+impl IcmpSocket {
+    pub fn new(
+        status: FileStatus,
+        send_buf_size: usize,
+        recv_buf_size: usize,
+    ) -> Arc<AtomicRefCell<Self>> {
+        let mut socket = Self {
+            event_source: StateEventSource::new(),
+            status,
+            state: FileState::ACTIVE,
+            shutdown_status: ShutdownFlags::empty(),
+            send_buffer: MessageBuffer::new(send_buf_size),
+            recv_buffer: MessageBuffer::new(recv_buf_size),
+            peer_addr: None,
+            bound_addr: None,
+            association: None,
+            ttl: inet::DEFAULT_IP_TTL,
+            has_open_file: false,
+            _counter: ObjectCounter::new("IcmpSocket"),
+        };
+
+        CallbackQueue::queue_and_run(|cb_queue| socket.refresh_readable_writable(cb_queue));
+
+        Arc::new(AtomicRefCell::new(socket))
+    }
+
+    pub fn get_status(&self) -> FileStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: FileStatus) {
+        self.status = status;
+    }
+
+    pub fn mode(&self) -> FileMode {
+        FileMode::READ | FileMode::WRITE
+    }
+
+    pub fn has_open_file(&self) -> bool {
+        self.has_open_file
+    }
+
+    pub fn supports_sa_restart(&self) -> bool {
+        true
+    }
+
+    pub fn set_has_open_file(&mut self, val: bool) {
+        self.has_open_file = val;
+    }
+
+    pub fn push_in_packet(&mut self, mut packet: PacketRc, cb_queue: &mut CallbackQueue) {
+        packet.add_status(PacketStatus::RcvSocketProcessed);
+
+        // ping sockets only receive echo replies; ICMP errors are not reported
+        let Some(icmp_header) = packet
+            .icmp_header()
+            .filter(|x| x.type_ == c::ProtocolICMPType_PICMP_ECHO_REPLY)
+        else {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        };
+
+        if let Some(peer_addr) = self.peer_addr {
+            if peer_addr.ip() != packet.src_address().ip() {
+                // we have a peer, but received a reply from a different host
+                packet.add_status(PacketStatus::RcvSocketDropped);
+                return;
+            }
+        }
+
+        // don't bother copying the bytes if we know the push will fail
+        if !self.recv_buffer.has_space() {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        let mut payload = vec![0; packet.payload_size()];
+        let num_bytes_copied = packet.get_payload(&mut payload);
+        assert_eq!(num_bytes_copied, packet.payload_size());
+
+        // the application receives the ICMP header along with the payload
+        let identifier = u16::from_be(icmp_header.identifier);
+        let sequence = u16::from_be(icmp_header.sequence);
+        let message = icmp_echo_message(
+            c::ProtocolICMPType_PICMP_ECHO_REPLY,
+            identifier,
+            sequence,
+            &payload,
+        );
+
+        let header = MessageRecvHeader {
+            src: *packet.src_address().ip(),
+        };
+
+        // push the message to the receive buffer (shouldn't fail since we checked for available
+        // space above)
+        self.recv_buffer.push_message(message, header).unwrap();
+
+        log::trace!("Added a packet to the ICMP socket's recv buffer");
+        packet.add_status(PacketStatus::RcvSocketBuffered);
+
+        self.refresh_readable_writable(cb_queue);
+    }
+
+    pub fn pull_out_packet(&mut self, cb_queue: &mut CallbackQueue) -> Option<PacketRc> {
+        // pop the message from the send buffer
+        let Some((message, header)) = self.send_buffer.pop_message() else {
+            log::debug!(
+                "Attempted to remove a message from the ICMP socket's send buffer, but none available"
+            );
+
+            return None;
+        };
+
+        log::trace!("Removed a message from the ICMP socket's send buffer");
+
+        let mut packet = PacketRc::new();
+
+        packet.set_icmp_echo(
+            c::ProtocolICMPType_PICMP_ECHO_REQUEST,
+            *header.src.ip(),
+            header.dst,
+            header.src.port(),
+            header.sequence,
+        );
+        packet.set_ttl(self.ttl);
+        packet.set_payload(&message, header.packet_priority);
+        packet.add_status(PacketStatus::SndCreated);
+
+        self.refresh_readable_writable(cb_queue);
+
+        Some(packet)
+    }
+
+    pub fn peek_next_packet_priority(&self) -> Option<FifoPacketPriority> {
+        self.send_buffer.peek_message().map(|x| x.1.packet_priority)
+    }
+
+    pub fn has_data_to_send(&self) -> bool {
+        !self.send_buffer.is_empty()
+    }
+
+    pub fn update_packet_header(&self, _packet: &mut PacketRc) {
+        // do nothing for ICMP
+    }
+
+    pub fn getsockname(&self) -> Result<Option<SockaddrIn>, SyscallError> {
+        let mut addr = self
+            .bound_addr
+            .unwrap_or(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+
+        // if we are bound to INADDR_ANY, we should instead return the IP used to communicate with
+        // the connected peer (if we have one)
+        if *addr.ip() == Ipv4Addr::UNSPECIFIED {
+            if let Some(peer_addr) = self.peer_addr {
+                addr.set_ip(*peer_addr.ip());
+            }
+        }
+
+        Ok(Some(addr.into()))
+    }
+
+    pub fn getpeername(&self) -> Result<Option<SockaddrIn>, SyscallError> {
+        Ok(Some(self.peer_addr.ok_or(Errno::ENOTCONN)?.into()))
+    }
+
+    pub fn address_family(&self) -> AddressFamily {
+        AddressFamily::Inet
+    }
+
+    pub fn family(&self) -> InetFamily {
+        // only IPv4 ping sockets are supported
+        InetFamily::Ipv4
+    }
+
+    pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
+        // drop the existing association handle to disassociate the socket
+        self.association = None;
+
+        self.copy_state(
+            /* mask= */ FileState::all(),
+            FileState::CLOSED,
+            cb_queue,
+        );
+        Ok(())
+    }
+
+    pub fn bind(
+        socket: &Arc<AtomicRefCell<Self>>,
+        addr: Option<&SockaddrStorage>,
+        net_ns: &NetworkNamespace,
+        rng: impl rand::Rng,
+    ) -> SyscallResult {
+        // if the address pointer was NULL
+        let Some(addr) = addr else {
+            return Err(Errno::EFAULT.into());
+        };
+
+        // if not an inet socket address
+        let Some(addr) = addr.as_inet() else {
+            return Err(Errno::EINVAL.into());
+        };
+
+        let addr: SocketAddrV4 = (*addr).into();
+
+        // if the socket is already bound
+        if socket.borrow().bound_addr.is_some() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // the port is the echo identifier, and a port of 0 will choose a random identifier
+        Self::associate(socket, addr, net_ns, rng)?;
+
+        Ok(0.into())
+    }
+
+    /// Associate the socket with the network interface for `local_addr`, and update the bound
+    /// address.
+    fn associate(
+        socket: &Arc<AtomicRefCell<Self>>,
+        local_addr: SocketAddrV4,
+        net_ns: &NetworkNamespace,
+        rng: impl rand::Rng,
+    ) -> Result<(), SyscallError> {
+        {
+            let socket = socket.borrow();
+
+            // must not have been associated with the network interface
+            assert!(socket.bound_addr.is_none());
+            assert!(socket.association.is_none());
+        }
+
+        // this will allow us to receive replies from any peer, but `push_in_packet` should drop
+        // any replies that aren't from the peer
+        let unspecified_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+
+        let (local_addr, handle) = inet::associate_socket(
+            InetSocket::Icmp(Arc::clone(socket)),
+            local_addr,
+            unspecified_addr,
+            ReuseOptions::default(),
+            net_ns,
+            rng,
+        )?;
+
+        let mut socket = socket.borrow_mut();
+        socket.bound_addr = Some(local_addr);
+        socket.association = Some(handle);
+
+        Ok(())
+    }
+
+    pub fn readv(
+        &mut self,
+        _iovs: &[IoVec],
+        _offset: Option<libc::off_t>,
+        _flags: libc::c_int,
+        _mem: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        // the readv() syscall handler should have called IcmpSocket::recvmsg() instead
+        panic!("Called IcmpSocket::readv() on an ICMP socket");
+    }
+
+    pub fn writev(
+        &mut self,
+        _iovs: &[IoVec],
+        _offset: Option<libc::off_t>,
+        _flags: libc::c_int,
+        _mem: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        // the writev() syscall handler should have called IcmpSocket::sendmsg() instead
+        panic!("Called IcmpSocket::writev() on an ICMP socket");
+    }
+
+    pub fn sendmsg(
+        socket: &Arc<AtomicRefCell<Self>>,
+        args: SendmsgArgs,
+        mem: &mut MemoryManager,
+        net_ns: &NetworkNamespace,
+        rng: impl rand::Rng,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        // if the file's writing has been shut down, return EPIPE
+        if socket
+            .borrow()
+            .shutdown_status
+            .contains(ShutdownFlags::WRITE)
+        {
+            return Err(Errno::EPIPE.into());
+        }
+
+        let Some(mut flags) = MsgFlags::from_bits(args.flags) else {
+            log::debug!("Unrecognized send flags: {:#b}", args.flags);
+            return Err(Errno::EINVAL.into());
+        };
+
+        let dst_addr = match args.addr {
+            Some(addr) => match addr.as_inet() {
+                // an inet socket address (the port is ignored)
+                Some(x) => *SocketAddrV4::from(*x).ip(),
+                // not an inet socket address
+                None => return Err(Errno::EAFNOSUPPORT.into()),
+            },
+            // no destination address provided
+            None => match socket.borrow().peer_addr {
+                Some(x) => *x.ip(),
+                None => return Err(Errno::EDESTADDRREQ.into()),
+            },
+        };
+
+        if socket.borrow().get_status().contains(FileStatus::NONBLOCK) {
+            flags.insert(MsgFlags::MSG_DONTWAIT);
+        }
+
+        let len: libc::size_t = args.iovs.iter().map(|x| x.len).sum();
+
+        if len > CONFIG_ICMP_MAX_SIZE {
+            return Err(Errno::EMSGSIZE.into());
+        }
+
+        // the message must begin with an ICMP echo request header
+        if len < ICMP_ECHO_HEADER_SIZE {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // read the ICMP header and payload from the iovs
+        let mut message = vec![0; len];
+        IoVecReader::new(args.iovs, mem)
+            .read_exact(&mut message[..])
+            .map_err(|e| Errno::try_from(e).unwrap())?;
+
+        let (icmp_header, payload) = message.split_at(ICMP_ECHO_HEADER_SIZE);
+
+        // linux only allows echo requests on ping sockets
+        if u32::from(icmp_header[0]) != c::ProtocolICMPType_PICMP_ECHO_REQUEST
+            || u32::from(icmp_header[1]) != c::ProtocolICMPCode_PICMP_CODE_NONE
+        {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // linux uses the sequence number from the application, but replaces the checksum and the
+        // identifier
+        let sequence = u16::from_be_bytes([icmp_header[6], icmp_header[7]]);
+
+        // make sure that we're bound
+        if socket.borrow().bound_addr.is_none() {
+            // implicit bind (use default interface unless the remote peer is on loopback)
+            let local_addr = if dst_addr == Ipv4Addr::LOCALHOST {
+                SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)
+            } else {
+                SocketAddrV4::new(net_ns.default_ip, 0)
+            };
+
+            Self::associate(socket, local_addr, net_ns, rng)?;
+        }
+
+        let mut socket_ref = socket.borrow_mut();
+
+        // run in a closure so that an early return doesn't skip checking if we should block
+        let result = (|| {
+            if !socket_ref.send_buffer.has_space() {
+                return Err(Errno::EWOULDBLOCK);
+            }
+
+            // get the priority that we'll assign to the eventual packet
+            let packet_priority =
+                Worker::with_active_host(|host| host.get_next_packet_priority()).unwrap();
+
+            let header = MessageSendHeader {
+                src: socket_ref.bound_addr.unwrap(),
+                dst: dst_addr,
+                sequence,
+                packet_priority,
+            };
+
+            // push the message to the send buffer (shouldn't fail since we checked for available
+            // space above)
+            socket_ref
+                .send_buffer
+                .push_message(Bytes::copy_from_slice(payload), header)
+                .unwrap();
+
+            // notify the host that this socket has packets to send
+            let socket = Arc::clone(socket);
+            let interface_ip = *socket_ref.bound_addr.unwrap().ip();
+            cb_queue.add(move |_cb_queue| {
+                Worker::with_active_host(|host| {
+                    let inet_socket = InetSocket::Icmp(socket);
+                    let compat_socket = unsafe { c::compatsocket_fromInetSocket(&inet_socket) };
+                    host.notify_socket_has_packets(interface_ip, &compat_socket);
+                })
+                .unwrap();
+            });
+
+            Ok(len)
+        })();
+
+        socket_ref.refresh_readable_writable(cb_queue);
+
+        // if the syscall would block and we don't have the MSG_DONTWAIT flag
+        if result == Err(Errno::EWOULDBLOCK) && !flags.contains(MsgFlags::MSG_DONTWAIT) {
+            return Err(SyscallError::new_blocked(
+                File::Socket(Socket::Inet(InetSocket::Icmp(socket.clone()))),
+                FileState::WRITABLE,
+                socket_ref.supports_sa_restart(),
+            ));
+        }
+
+        Ok(result?.try_into().unwrap())
+    }
+
+    pub fn recvmsg(
+        socket: &Arc<AtomicRefCell<Self>>,
+        args: RecvmsgArgs,
+        mem: &mut MemoryManager,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<RecvmsgReturn, SyscallError> {
+        let mut socket_ref = socket.borrow_mut();
+
+        let Some(mut flags) = MsgFlags::from_bits(args.flags) else {
+            log::debug!("Unrecognized recv flags: {:#b}", args.flags);
+            return Err(Errno::EINVAL.into());
+        };
+
+        if socket_ref.get_status().contains(FileStatus::NONBLOCK) {
+            flags.insert(MsgFlags::MSG_DONTWAIT);
+        }
+
+        let len: libc::size_t = args.iovs.iter().map(|x| x.len).sum();
+
+        // run in a closure so that an early return doesn't skip checking if we should block
+        let result = (|| {
+            // a temporary location to store the message and header if we popped them
+            let message_storage;
+            let header_storage;
+
+            let (message, header) = if !flags.contains(MsgFlags::MSG_PEEK) {
+                // pop the message from the receive buffer
+                (message_storage, header_storage) = socket_ref
+                    .recv_buffer
+                    .pop_message()
+                    .ok_or(Errno::EWOULDBLOCK)?;
+                (&message_storage, &header_storage)
+            } else {
+                // peek the message from the receive buffer
+                let (message, header) = socket_ref
+                    .recv_buffer
+                    .peek_message()
+                    .ok_or(Errno::EWOULDBLOCK)?;
+                (message, header)
+            };
+
+            // truncate the message if it's larger than the user-provided buffers
+            let truncated_message = &message[..std::cmp::min(len, message.len())];
+
+            // write the truncated message to the iovs
+            let mut writer = IoVecWriter::new(args.iovs, mem);
+            writer
+                .write_all(truncated_message)
+                .map_err(|e| Errno::try_from(e).unwrap())?;
+
+            let return_val = if flags.contains(MsgFlags::MSG_TRUNC) {
+                message.len()
+            } else {
+                // the number of bytes written
+                truncated_message.len()
+            };
+
+            let mut return_flags = MsgFlags::empty();
+            return_flags.set(MsgFlags::MSG_TRUNC, truncated_message.len() < message.len());
+
+            Ok(RecvmsgReturn {
+                return_val: return_val.try_into().unwrap(),
+                // linux returns a port of 0 for ping sockets
+                addr: Some(SocketAddrV4::new(header.src, 0).into()),
+                msg_flags: return_flags.bits(),
+                control: Default::default(),
+            })
+        })();
+
+        socket_ref.refresh_readable_writable(cb_queue);
+
+        // if the syscall would block and we don't have the MSG_DONTWAIT flag
+        if result.as_ref().err() == Some(&Errno::EWOULDBLOCK)
+            && !flags.contains(MsgFlags::MSG_DONTWAIT)
+        {
+            // if the syscall would block but the file's reading has been shut down, return EOF
+            if socket_ref.shutdown_status.contains(ShutdownFlags::READ) {
+                return Ok(RecvmsgReturn {
+                    return_val: 0,
+                    addr: None,
+                    msg_flags: 0,
+                    control: Default::default(),
+                });
+            }
+
+            return Err(SyscallError::new_blocked(
+                File::Socket(Socket::Inet(InetSocket::Icmp(socket.clone()))),
+                FileState::READABLE,
+                socket_ref.supports_sa_restart(),
+            ));
+        }
+
+        Ok(result?)
+    }
+
+    pub fn ioctl(
+        &mut self,
+        request: IoctlRequest,
+        arg_ptr: ForeignPtr<()>,
+        mem: &mut MemoryManager,
+    ) -> SyscallResult {
+        match request {
+            // equivalent to SIOCINQ
+            IoctlRequest::FIONREAD => {
+                let len = self
+                    .recv_buffer
+                    .peek_message()
+                    .map(|m| m.0.len())
+                    .unwrap_or(0)
+                    .try_into()
+                    .unwrap();
+
+                let arg_ptr = arg_ptr.cast::<libc::c_int>();
+                mem.write(arg_ptr, &len)?;
+
+                Ok(0.into())
+            }
+            IoctlRequest::FIONBIO => {
+                panic!("This should have been handled by the ioctl syscall handler");
+            }
+            IoctlRequest::TCGETS
+            | IoctlRequest::TCSETS
+            | IoctlRequest::TCSETSW
+            | IoctlRequest::TCSETSF
+            | IoctlRequest::TCGETA
+            | IoctlRequest::TCSETA
+            | IoctlRequest::TCSETAW
+            | IoctlRequest::TCSETAF
+            | IoctlRequest::TIOCGWINSZ
+            | IoctlRequest::TIOCSWINSZ => {
+                // not a terminal
+                Err(Errno::ENOTTY.into())
+            }
+            request => {
+                warn_once_then_debug!(
+                    "(LOG_ONCE) We do not yet handle ioctl request {request:?} on icmp sockets"
+                );
+                Err(Errno::EINVAL.into())
+            }
+        }
+    }
+
+    pub fn listen(
+        _socket: &Arc<AtomicRefCell<Self>>,
+        _backlog: i32,
+        _net_ns: &NetworkNamespace,
+        _rng: impl rand::Rng,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<(), SyscallError> {
+        Err(Errno::EOPNOTSUPP.into())
+    }
+
+    pub fn connect(
+        socket: &Arc<AtomicRefCell<Self>>,
+        peer_addr: &SockaddrStorage,
+        net_ns: &NetworkNamespace,
+        rng: impl rand::Rng,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<(), SyscallError> {
+        // if not an inet socket address
+        let Some(peer_addr) = peer_addr.as_inet() else {
+            return Err(Errno::EINVAL.into());
+        };
+
+        let mut peer_addr: SocketAddrV4 = (*peer_addr).into();
+
+        if peer_addr.ip().is_unspecified() {
+            peer_addr.set_ip(Ipv4Addr::LOCALHOST);
+        }
+
+        // the peer's port has no meaning for ping sockets
+        peer_addr.set_port(0);
+
+        let bound_addr = socket.borrow().bound_addr;
+
+        if let Some(bound_addr) = bound_addr {
+            // make sure the new peer address is reachable from the bound interface
+            if (bound_addr.ip() == &Ipv4Addr::LOCALHOST) != (peer_addr.ip() == &Ipv4Addr::LOCALHOST)
+            {
+                return Err(Errno::EINVAL.into());
+            }
+        } else {
+            // implicit bind (use default interface unless the remote peer is on loopback)
+            let local_addr = if peer_addr.ip() == &Ipv4Addr::LOCALHOST {
+                SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)
+            } else {
+                SocketAddrV4::new(net_ns.default_ip, 0)
+            };
+
+            Self::associate(socket, local_addr, net_ns, rng)?;
+        }
+
+        socket.borrow_mut().peer_addr = Some(peer_addr);
+
+        Ok(())
+    }
+
+    pub fn accept(&mut self, _cb_queue: &mut CallbackQueue) -> Result<OpenFile, SyscallError> {
+        Err(Errno::EOPNOTSUPP.into())
+    }
+
+    pub fn shutdown(
+        &mut self,
+        how: Shutdown,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<(), SyscallError> {
+        if self.peer_addr.is_none() {
+            return Err(Errno::ENOTCONN.into());
+        }
+
+        if how == Shutdown::Write || how == Shutdown::Both {
+            // writing has been shut down
+            self.shutdown_status.insert(ShutdownFlags::WRITE)
+        }
+
+        if how == Shutdown::Read || how == Shutdown::Both {
+            // reading has been shut down
+            self.shutdown_status.insert(ShutdownFlags::READ)
+        }
+
+        Ok(())
+    }
+
+    pub fn getsockopt(
+        &mut self,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        mem: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::socklen_t, SyscallError> {
+        let val: libc::c_int = match (level, optname) {
+            (libc::SOL_SOCKET, libc::SO_SNDBUF) => {
+                self.send_buffer.soft_limit_bytes().try_into().unwrap()
+            }
+            (libc::SOL_SOCKET, libc::SO_RCVBUF) => {
+                self.recv_buffer.soft_limit_bytes().try_into().unwrap()
+            }
+            (libc::SOL_SOCKET, libc::SO_ERROR) => 0,
+            (libc::SOL_SOCKET, libc::SO_DOMAIN) => libc::AF_INET,
+            (libc::SOL_SOCKET, libc::SO_TYPE) => libc::SOCK_DGRAM,
+            (libc::SOL_SOCKET, libc::SO_PROTOCOL) => libc::IPPROTO_ICMP,
+            (libc::SOL_SOCKET, libc::SO_ACCEPTCONN) => 0,
+            (libc::SOL_SOCKET, _) => {
+                log::debug!("getsockopt called with unsupported level {level} and opt {optname}");
+                return Err(Errno::ENOPROTOOPT.into());
+            }
+            (libc::IPPROTO_IP, libc::IP_TTL) => self.ttl.into(),
+            _ => {
+                log::debug!("getsockopt called with unsupported level {level} and opt {optname}");
+                return Err(Errno::EOPNOTSUPP.into());
+            }
+        };
+
+        let optval_ptr = optval_ptr.cast::<libc::c_int>();
+        let bytes_written = write_partial(mem, &val, optval_ptr, optlen as usize)?;
+
+        Ok(bytes_written as libc::socklen_t)
+    }
+
+    pub fn setsockopt(
+        &mut self,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        mem: &MemoryManager,
+    ) -> Result<(), SyscallError> {
+        match (level, optname) {
+            (libc::SOL_SOCKET, libc::SO_SNDBUF | libc::SO_RCVBUF) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let val: u64 = mem.read(optval_ptr)?.try_into().or(Err(Errno::EINVAL))?;
+
+                // linux kernel doubles this value upon setting, and we use the same limits as for
+                // udp sockets
+                let val = val * 2;
+                let val = std::cmp::min(val, 268435456); // 2^28 = 256 MiB
+
+                if optname == libc::SO_SNDBUF {
+                    let val = std::cmp::max(val, 4096);
+                    self.send_buffer
+                        .set_soft_limit_bytes(val.try_into().unwrap());
+                } else {
+                    let val = std::cmp::max(val, 2048);
+                    self.recv_buffer
+                        .set_soft_limit_bytes(val.try_into().unwrap());
+                }
+            }
+            (libc::IPPROTO_IP, libc::IP_TTL) => {
+                self.ttl = inet::read_ip_ttl_option(optval_ptr, optlen, mem)?;
+            }
+            _ => {
+                log::debug!("setsockopt called with unsupported level {level} and opt {optname}");
+                return Err(Errno::ENOPROTOOPT.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn add_listener(
+        &mut self,
+        monitoring: FileState,
+        filter: StateListenerFilter,
+        notify_fn: impl Fn(FileState, FileState, &mut CallbackQueue) + Send + Sync + 'static,
+    ) -> Handle<(FileState, FileState)> {
+        self.event_source
+            .add_listener(monitoring, filter, notify_fn)
+    }
+
+    pub fn add_legacy_listener(&mut self, ptr: HostTreePointer<c::StatusListener>) {
+        self.event_source.add_legacy_listener(ptr);
+    }
+
+    pub fn remove_legacy_listener(&mut self, ptr: *mut c::StatusListener) {
+        self.event_source.remove_legacy_listener(ptr);
+    }
+
+    pub fn state(&self) -> FileState {
+        self.state
+    }
+
+    fn refresh_readable_writable(&mut self, cb_queue: &mut CallbackQueue) {
+        let readable = !self.recv_buffer.is_empty();
+        let writable = self.send_buffer.has_space();
+
+        let readable = readable.then_some(FileState::READABLE).unwrap_or_default();
+        let writable = writable.then_some(FileState::WRITABLE).unwrap_or_default();
+
+        self.copy_state(
+            /* mask= */ FileState::READABLE | FileState::WRITABLE,
+            readable | writable,
+            cb_queue,
+        );
+    }
+
+    fn copy_state(&mut self, mask: FileState, state: FileState, cb_queue: &mut CallbackQueue) {
+        let old_state = self.state;
+
+        // remove the masked flags, then copy the masked flags
+        self.state.remove(mask);
+        self.state.insert(state & mask);
+
+        self.handle_state_change(old_state, cb_queue);
+    }
+
+    fn handle_state_change(&mut self, old_state: FileState, cb_queue: &mut CallbackQueue) {
+        let states_changed = self.state ^ old_state;
+
+        // if nothing changed
+        if states_changed.is_empty() {
+            return;
+        }
+
+        self.event_source
+            .notify_listeners(self.state, states_changed, cb_queue);
+    }
+}
+
+/// Non-payload data for a message in the send buffer.
+#[derive(Debug)]
+struct MessageSendHeader {
+    /// The bound address, whose port is the echo identifier.
+    src: SocketAddrV4,
+    /// The destination address.
+    dst: Ipv4Addr,
+    /// The echo sequence number chosen by the application.
+    sequence: u16,
+    /// The priority for the packet that we'll create in the future, given to us by the host.
+    packet_priority: FifoPacketPriority,
+}
+
+/// Non-payload data for a message in the receive buffer.
+#[derive(Debug)]
+struct MessageRecvHeader {
+    /// The address of the host that sent the reply.
+    src: Ipv4Addr,
+}
+
+/// Build an ICMP echo message (header and payload) as it would appear on the wire.
+fn icmp_echo_message(
+    icmp_type: c::ProtocolICMPType,
+    identifier: u16,
+    sequence: u16,
+    payload: &[u8],
+) -> Bytes {
+    let mut message = BytesMut::with_capacity(ICMP_ECHO_HEADER_SIZE + payload.len());
+    message.put_u8(icmp_type.try_into().unwrap());
+    message.put_u8(c::ProtocolICMPCode_PICMP_CODE_NONE.try_into().unwrap());
+    // the checksum is filled in below
+    message.put_u16(0);
+    message.put_u16(identifier);
+    message.put_u16(sequence);
+    message.put_slice(payload);
+
+    let checksum = internet_checksum(&message);
+    message[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+    message.freeze()
+}
+
+/// The internet checksum (RFC 1071) of `bytes`.
+fn internet_checksum(bytes: &[u8]) -> u16 {
+    // the max message size is small enough that the sum can't overflow a u32
+    let mut sum: u32 = bytes
+        .chunks(2)
+        .map(|x| u32::from(u16::from_be_bytes([x[0], *x.get(1).unwrap_or(&0)])))
+        .sum();
+
+    // fold the carry bits
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internet_checksum() {
+        // example from RFC 1071 section 3
+        let bytes = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(internet_checksum(&bytes), !0xddf2);
+
+        // a message containing its own checksum should have a checksum of 0
+        let message = icmp_echo_message(c::ProtocolICMPType_PICMP_ECHO_REPLY, 1234, 7, b"hello");
+        assert_eq!(internet_checksum(&message), 0);
+
+        // odd-length messages are padded with a zero byte
+        assert_eq!(internet_checksum(&[0x12]), internet_checksum(&[0x12, 0x00]));
+    }
+}
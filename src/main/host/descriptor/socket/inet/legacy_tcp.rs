@@ -1,4 +1,4 @@
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::net::{Ipv4Addr, SocketAddrV4};
 use std::sync::Arc;
 
@@ -6,18 +6,20 @@ use atomic_refcell::AtomicRefCell;
 use linux_api::errno::Errno;
 use linux_api::ioctls::IoctlRequest;
 use nix::sys::socket::{MsgFlags, Shutdown, SockaddrIn};
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 
+use crate::core::support::configuration::TcpCongestionControl;
 use crate::core::worker::Worker;
 use crate::cshadow as c;
-use crate::host::descriptor::socket::inet::{self, InetSocket};
+use crate::host::descriptor::socket::inet::{self, InetFamily, InetSocket};
 use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, Socket};
 use crate::host::descriptor::{
     CompatFile, File, FileMode, FileState, FileStatus, OpenFile, StateListenerFilter, SyscallResult,
 };
 use crate::host::host::Host;
 use crate::host::memory_manager::MemoryManager;
-use crate::host::network::interface::FifoPacketPriority;
+use crate::host::network::interface::{FifoPacketPriority, ReuseOptions};
 use crate::host::network::namespace::NetworkNamespace;
 use crate::host::syscall::io::{write_partial, IoVec};
 use crate::host::syscall_types::{ForeignArrayPtr, SyscallError};
@@ -34,18 +36,31 @@ pub struct LegacyTcpSocket {
     has_open_file: bool,
     /// Did the last connect() call block, and if so what thread?
     thread_of_blocked_connect: Option<ThreadId>,
+    family: InetFamily,
+    /// The `SO_REUSEADDR` and `SO_REUSEPORT` options.
+    reuse: ReuseOptions,
     _counter: ObjectCounter,
 }
 
 impl LegacyTcpSocket {
-    pub fn new(status: FileStatus, host: &Host) -> Arc<AtomicRefCell<Self>> {
+    pub fn new(status: FileStatus, family: InetFamily, host: &Host) -> Arc<AtomicRefCell<Self>> {
         let recv_buf_size = host.params.init_sock_recv_buf_size.try_into().unwrap();
         let send_buf_size = host.params.init_sock_send_buf_size.try_into().unwrap();
 
         let tcp = unsafe { c::tcp_new(host, recv_buf_size, send_buf_size) };
+
+        let cong_type = match host.params.tcp_congestion_control {
+            TcpCongestionControl::Reno => c::_TCPCongestionType_TCP_CC_RENO,
+            TcpCongestionControl::Cubic => c::_TCPCongestionType_TCP_CC_CUBIC,
+            TcpCongestionControl::Bbr => c::_TCPCongestionType_TCP_CC_BBR,
+        };
+        unsafe { c::tcp_setCongestionType(tcp, cong_type) };
+        unsafe { c::tcp_setECNRequested(tcp, host.params.tcp_ecn.into()) };
+
         let tcp = unsafe { Self::new_from_legacy(tcp) };
 
         tcp.borrow_mut().set_status(status);
+        tcp.borrow_mut().family = family;
 
         tcp
     }
@@ -62,6 +77,8 @@ impl LegacyTcpSocket {
             socket: HostTreePointer::new(legacy_tcp),
             has_open_file: false,
             thread_of_blocked_connect: None,
+            family: InetFamily::Ipv4,
+            reuse: ReuseOptions::default(),
             _counter: ObjectCounter::new("LegacyTcpSocket"),
         };
 
@@ -220,7 +237,20 @@ impl LegacyTcpSocket {
     }
 
     pub fn address_family(&self) -> nix::sys::socket::AddressFamily {
-        nix::sys::socket::AddressFamily::Inet
+        self.family.address_family()
+    }
+
+    pub fn family(&self) -> InetFamily {
+        self.family
+    }
+
+    pub fn set_v6_only(&mut self, v6_only: bool) -> Result<(), SyscallError> {
+        // the option can't be changed after the socket is bound
+        if unsafe { c::legacysocket_isBound(self.as_legacy_socket()) } == 1 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        self.family.set_v6_only(v6_only)
     }
 
     pub fn close(&mut self, _cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
@@ -231,6 +261,22 @@ impl LegacyTcpSocket {
         Ok(())
     }
 
+    /// How long `close()` should block waiting for the peer to acknowledge our data, or `None` if
+    /// it shouldn't block. This is only meaningful after the socket was closed.
+    pub fn linger_timeout(&self) -> Option<SimulationTime> {
+        let tcp = self.as_legacy_tcp();
+
+        if unsafe { c::tcp_getLingerEnabled(tcp) } == 0
+            || self.state().contains(FileState::SOCKET_LINGER_DONE)
+        {
+            return None;
+        }
+
+        // a zero linger time aborted the connection, so the state above should already be set
+        let secs = unsafe { c::tcp_getLingerTime(tcp) };
+        Some(SimulationTime::from_secs(secs.into()))
+    }
+
     pub fn bind(
         socket: &Arc<AtomicRefCell<Self>>,
         addr: Option<&SockaddrStorage>,
@@ -272,11 +318,14 @@ impl LegacyTcpSocket {
         // this will allow us to receive packets from any peer
         let peer_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
 
+        let reuse = socket.borrow().reuse;
+
         // associate the socket
         let (addr, handle) = inet::associate_socket(
             InetSocket::LegacyTcp(Arc::clone(socket)),
             addr,
             peer_addr,
+            reuse,
             net_ns,
             rng,
         )?;
@@ -526,7 +575,7 @@ impl LegacyTcpSocket {
                 return_val: bytes_read.try_into().unwrap(),
                 addr: None,
                 msg_flags: 0,
-                control_len: 0,
+                control: Default::default(),
             })
         })();
 
@@ -649,6 +698,7 @@ impl LegacyTcpSocket {
                 super::InetSocket::LegacyTcp(socket.clone()),
                 local_addr,
                 peer_addr,
+                socket_ref.reuse,
                 net_ns,
                 rng,
             )?;
@@ -664,6 +714,25 @@ impl LegacyTcpSocket {
                     local_addr.port().to_be(),
                 )
             };
+        } else {
+            // sockets can be bound to the same address using SO_REUSEADDR, but only one of them
+            // can listen unless they all use SO_REUSEPORT
+            let mut ip: libc::in_addr_t = 0;
+            let mut port: libc::in_port_t = 0;
+            unsafe {
+                c::legacysocket_getSocketName(socket_ref.as_legacy_socket(), &mut ip, &mut port)
+            };
+            let local_addr = SocketAddrV4::new(u32::from_be(ip).into(), u16::from_be(port));
+
+            if net_ns.is_listen_conflict(
+                socket_ref.canonical_handle(),
+                local_addr,
+                socket_ref.reuse.port,
+                socket_ref.family().association_family(),
+            ) {
+                log::debug!("Another socket is already listening on {local_addr}");
+                return Err(Errno::EADDRINUSE.into());
+            }
         }
 
         // we are allowed to listen but not already listening; start now
@@ -747,14 +816,23 @@ impl LegacyTcpSocket {
                 SocketAddrV4::new(host_default_ip, 0)
             };
 
+            let reuse = socket_ref.reuse;
+
+            // the socket can't be mutably borrowed while associating it
+            drop(socket_ref);
+
             // associate the socket
-            let (local_addr, handle) = super::associate_socket(
+            let association = super::associate_socket(
                 super::InetSocket::LegacyTcp(socket.clone()),
                 local_addr,
                 peer_addr,
+                reuse,
                 net_ns,
                 rng,
-            )?;
+            );
+
+            socket_ref = socket.borrow_mut();
+            let (local_addr, handle) = association?;
 
             // the handle normally disassociates the socket when dropped, but the C TCP code does
             // it's own manual disassociation, so we'll just let it do its own thing
@@ -822,7 +900,10 @@ impl LegacyTcpSocket {
         }
 
         // make sure we return valid error codes for connect
-        if errcode == Err(Errno::ECONNRESET) || errcode == Err(Errno::ENOTCONN) {
+        if errcode == Err(Errno::ECONNRESET)
+            || errcode == Err(Errno::ETIMEDOUT)
+            || errcode == Err(Errno::ENOTCONN)
+        {
             errcode = Err(Errno::EISCONN);
         }
         // EALREADY is well defined in man page, but Linux returns EINPROGRESS
@@ -895,7 +976,10 @@ impl LegacyTcpSocket {
                 panic!("Expected this to be a LegacyTcpSocket");
             };
 
-            let new_socket = new_socket.borrow();
+            let mut new_socket = new_socket.borrow_mut();
+
+            // the accepted socket has the same address family as the listening socket
+            new_socket.family = self.family;
 
             let mut ip: libc::in_addr_t = 0;
             let mut port: libc::in_port_t = 0;
@@ -939,28 +1023,54 @@ impl LegacyTcpSocket {
     }
 
     pub fn getsockopt(
-        &self,
+        &mut self,
         level: libc::c_int,
         optname: libc::c_int,
         optval_ptr: ForeignPtr<()>,
         optlen: libc::socklen_t,
         memory_manager: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
     ) -> Result<libc::socklen_t, SyscallError> {
         match (level, optname) {
             (libc::SOL_TCP, libc::TCP_INFO) => {
                 let mut info = shadow_pod::zeroed();
                 unsafe { c::tcp_getInfo(self.as_legacy_tcp(), &mut info) };
 
-                let optval_ptr = optval_ptr.cast::<crate::cshadow::tcp_info>();
+                let optval_ptr = optval_ptr.cast::<crate::cshadow::TCPInfo>();
                 let bytes_written =
                     write_partial(memory_manager, &info, optval_ptr, optlen as usize)?;
 
                 Ok(bytes_written as libc::socklen_t)
             }
             (libc::SOL_TCP, libc::TCP_NODELAY) => {
-                // shadow doesn't support nagle's algorithm, so shadow always behaves as if
-                // TCP_NODELAY is enabled
-                let val = 1;
+                let val = unsafe { c::tcp_getNoDelay(self.as_legacy_tcp()) };
+                let val = libc::c_int::from(val != 0);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    write_partial(memory_manager, &val, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_TCP, libc::TCP_CORK) => {
+                let val = unsafe { c::tcp_getCork(self.as_legacy_tcp()) };
+                let val = libc::c_int::from(val != 0);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    write_partial(memory_manager, &val, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_TCP, libc::TCP_KEEPIDLE | libc::TCP_KEEPINTVL | libc::TCP_KEEPCNT) => {
+                let tcp = self.as_legacy_tcp();
+                let val = match optname {
+                    libc::TCP_KEEPIDLE => unsafe { c::tcp_getKeepIdle(tcp) },
+                    libc::TCP_KEEPINTVL => unsafe { c::tcp_getKeepInterval(tcp) },
+                    libc::TCP_KEEPCNT => unsafe { c::tcp_getKeepCount(tcp) },
+                    _ => unreachable!(),
+                };
+                let val = libc::c_int::try_from(val).unwrap();
 
                 let optval_ptr = optval_ptr.cast::<libc::c_int>();
                 let bytes_written =
@@ -1024,7 +1134,10 @@ impl LegacyTcpSocket {
                 // return error for failed connect() attempts
                 let conn_err = unsafe { c::tcp_getConnectionError(self.as_legacy_tcp()) };
 
-                let error = if conn_err == -libc::ECONNRESET || conn_err == -libc::ECONNREFUSED {
+                let error = if conn_err == -libc::ECONNRESET
+                    || conn_err == -libc::ECONNREFUSED
+                    || conn_err == -libc::ETIMEDOUT
+                {
                     // result is a positive errcode
                     -conn_err
                 } else {
@@ -1037,8 +1150,45 @@ impl LegacyTcpSocket {
 
                 Ok(bytes_written as libc::socklen_t)
             }
+            (libc::SOL_SOCKET, libc::SO_KEEPALIVE) => {
+                let val = unsafe { c::tcp_getKeepAlive(self.as_legacy_tcp()) };
+                let val = libc::c_int::from(val != 0);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    write_partial(memory_manager, &val, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_SOCKET, libc::SO_LINGER) => {
+                let tcp = self.as_legacy_tcp();
+                let linger = libc::linger {
+                    l_onoff: unsafe { c::tcp_getLingerEnabled(tcp) },
+                    l_linger: unsafe { c::tcp_getLingerTime(tcp) }.try_into().unwrap(),
+                };
+
+                let optval_ptr = optval_ptr.cast::<libc::linger>();
+                let bytes_written =
+                    write_partial(memory_manager, &linger, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_SOCKET, libc::SO_REUSEADDR | libc::SO_REUSEPORT) => {
+                let val = if optname == libc::SO_REUSEADDR {
+                    self.reuse.addr
+                } else {
+                    self.reuse.port
+                };
+                let val = libc::c_int::from(val);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    write_partial(memory_manager, &val, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
             (libc::SOL_SOCKET, libc::SO_DOMAIN) => {
-                let domain = libc::AF_INET;
+                let domain = self.family.domain();
 
                 let optval_ptr = optval_ptr.cast::<libc::c_int>();
                 let bytes_written =
@@ -1073,6 +1223,16 @@ impl LegacyTcpSocket {
 
                 Ok(bytes_written as libc::socklen_t)
             }
+            (libc::IPPROTO_IP, libc::IP_TOS) => {
+                let val = unsafe { c::tcp_getTOS(self.as_legacy_tcp()) };
+                let val = libc::c_int::from(val);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written =
+                    write_partial(memory_manager, &val, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
             _ => {
                 log::warn!("getsockopt called with unsupported level {level} and opt {optname}");
                 Err(Errno::ENOPROTOOPT.into())
@@ -1090,28 +1250,77 @@ impl LegacyTcpSocket {
     ) -> Result<(), SyscallError> {
         match (level, optname) {
             (libc::SOL_TCP, libc::TCP_NODELAY) => {
-                // Shadow doesn't support nagle's algorithm, so Shadow always behaves as if
-                // TCP_NODELAY is enabled. Some programs will fail if `setsockopt(fd, SOL_TCP,
-                // TCP_NODELAY, &1, sizeof(int))` returns an error, so we treat this as a no-op for
-                // compatibility.
+                type OptType = libc::c_int;
 
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let enable = memory_manager.read(optval_ptr)? != 0;
+
+                // enabling TCP_NODELAY may flush a partial segment that was held back by nagle's
+                // algorithm
+                Worker::with_active_host(|host| unsafe {
+                    c::tcp_setNoDelay(self.as_legacy_tcp(), host, enable.into())
+                })
+                .unwrap();
+            }
+            (libc::SOL_TCP, libc::TCP_KEEPIDLE | libc::TCP_KEEPINTVL | libc::TCP_KEEPCNT) => {
                 type OptType = libc::c_int;
 
+                // the values of MAX_TCP_KEEPIDLE, MAX_TCP_KEEPINTVL and MAX_TCP_KEEPCNT in linux
+                const MAX_KEEPIDLE: OptType = 32767;
+                const MAX_KEEPINTVL: OptType = 32767;
+                const MAX_KEEPCNT: OptType = 127;
+
                 if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
                     return Err(Errno::EINVAL.into());
                 }
 
                 let optval_ptr = optval_ptr.cast::<OptType>();
-                let enable = memory_manager.read(optval_ptr)?;
+                let val = memory_manager.read(optval_ptr)?;
 
-                if enable != 0 {
-                    // wants to enable TCP_NODELAY
-                    log::debug!("Ignoring TCP_NODELAY");
-                } else {
-                    // wants to disable TCP_NODELAY
-                    log::warn!("Cannot disable TCP_NODELAY since shadow does not implement Nagle's algorithm.");
-                    return Err(Errno::ENOPROTOOPT.into());
+                let max = match optname {
+                    libc::TCP_KEEPIDLE => MAX_KEEPIDLE,
+                    libc::TCP_KEEPINTVL => MAX_KEEPINTVL,
+                    libc::TCP_KEEPCNT => MAX_KEEPCNT,
+                    _ => unreachable!(),
+                };
+
+                if !(1..=max).contains(&val) {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let val = val.try_into().unwrap();
+                let tcp = self.as_legacy_tcp();
+
+                match optname {
+                    // a new idle time applies to a running keepalive timer
+                    libc::TCP_KEEPIDLE => Worker::with_active_host(|host| unsafe {
+                        c::tcp_setKeepIdle(tcp, host, val)
+                    })
+                    .unwrap(),
+                    libc::TCP_KEEPINTVL => unsafe { c::tcp_setKeepInterval(tcp, val) },
+                    libc::TCP_KEEPCNT => unsafe { c::tcp_setKeepCount(tcp, val) },
+                    _ => unreachable!(),
+                }
+            }
+            (libc::SOL_TCP, libc::TCP_CORK) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
                 }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let enable = memory_manager.read(optval_ptr)? != 0;
+
+                // uncorking will flush any partial segment that was held back
+                Worker::with_active_host(|host| unsafe {
+                    c::tcp_setCork(self.as_legacy_tcp(), host, enable.into())
+                })
+                .unwrap();
             }
             (libc::SOL_TCP, libc::TCP_CONGESTION) => {
                 // the value of TCP_CA_NAME_MAX in linux
@@ -1134,14 +1343,17 @@ impl LegacyTcpSocket {
                     .map(|x| &name[..x])
                     .unwrap_or(name);
 
-                let reno = unsafe { CStr::from_ptr(c::TCP_CONG_RENO_NAME) }.to_bytes();
+                // the name can't contain a NUL since we truncated it above
+                let name = CString::new(name).unwrap();
 
-                if name != reno {
-                    log::warn!("Shadow sockets only support '{reno:?}' for TCP_CONGESTION");
+                let cong_type = unsafe { c::tcpCongestion_getType(name.as_ptr()) };
+
+                if cong_type == c::_TCPCongestionType_TCP_CC_UNKNOWN {
+                    log::warn!("Unsupported congestion control {name:?} for TCP_CONGESTION");
                     return Err(Errno::ENOENT.into());
                 }
 
-                // shadow doesn't support other congestion types, so do nothing
+                unsafe { c::tcp_setCongestionType(self.as_legacy_tcp(), cong_type) };
             }
             (libc::SOL_SOCKET, libc::SO_SNDBUF) => {
                 type OptType = libc::c_int;
@@ -1201,23 +1413,71 @@ impl LegacyTcpSocket {
                 unsafe { c::legacysocket_setInputBufferSize(self.as_legacy_socket(), val) };
                 unsafe { c::tcp_disableReceiveBufferAutotuning(self.as_legacy_tcp()) };
             }
-            (libc::SOL_SOCKET, libc::SO_REUSEADDR) => {
-                // TODO: implement this, tor and tgen use it
-                log::trace!("setsockopt SO_REUSEADDR not yet implemented");
-            }
-            (libc::SOL_SOCKET, libc::SO_REUSEPORT) => {
-                // TODO: implement this, tgen uses it
-                log::trace!("setsockopt SO_REUSEPORT not yet implemented");
+            (libc::SOL_SOCKET, libc::SO_REUSEADDR | libc::SO_REUSEPORT) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let enable = memory_manager.read(optval_ptr)? != 0;
+
+                // like linux, this only affects future binds and listens
+                if optname == libc::SO_REUSEADDR {
+                    self.reuse.addr = enable;
+                } else {
+                    self.reuse.port = enable;
+                }
             }
             (libc::SOL_SOCKET, libc::SO_KEEPALIVE) => {
-                // TODO: implement this, libevent uses it in
-                // evconnlistener_new_bind()
-                log::trace!("setsockopt SO_KEEPALIVE not yet implemented");
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let enable = memory_manager.read(optval_ptr)? != 0;
+
+                // starts or stops the keepalive timer if the socket is connected
+                Worker::with_active_host(|host| unsafe {
+                    c::tcp_setKeepAlive(self.as_legacy_tcp(), host, enable.into())
+                })
+                .unwrap();
+            }
+            (libc::SOL_SOCKET, libc::SO_LINGER) => {
+                type OptType = libc::linger;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let linger = memory_manager.read(optval_ptr)?;
+
+                // like linux, a negative linger time means "forever"
+                let secs = if linger.l_linger < 0 {
+                    libc::c_int::MAX
+                } else {
+                    linger.l_linger
+                };
+                let secs = secs.try_into().unwrap();
+
+                unsafe {
+                    c::tcp_setLinger(self.as_legacy_tcp(), (linger.l_onoff != 0).into(), secs)
+                };
             }
             (libc::SOL_SOCKET, libc::SO_BROADCAST) => {
                 // TODO: implement this, pkg.go.dev/net uses it
                 log::trace!("setsockopt SO_BROADCAST not yet implemented");
             }
+            (libc::IPPROTO_IP, libc::IP_TOS) => {
+                let tos = inet::read_ip_option(optval_ptr, optlen, memory_manager)?;
+
+                // like linux, the ecn bits are managed by the tcp stack and are ignored here
+                unsafe { c::tcp_setTOS(self.as_legacy_tcp(), tos as u8) };
+            }
             _ => {
                 log::warn!("setsockopt called with unsupported level {level} and opt {optname}");
                 return Err(Errno::ENOPROTOOPT.into());
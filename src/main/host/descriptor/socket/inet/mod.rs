@@ -1,28 +1,31 @@
-use std::net::SocketAddrV4;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
 use std::sync::{Arc, Weak};
 
 use atomic_refcell::AtomicRefCell;
 use linux_api::errno::Errno;
 use linux_api::ioctls::IoctlRequest;
-use nix::sys::socket::Shutdown;
+use nix::sys::socket::{AddressFamily, Shutdown};
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 
+use crate::core::worker::Worker;
 use crate::cshadow as c;
 use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs};
 use crate::host::descriptor::{FileMode, FileState, FileStatus, OpenFile, SyscallResult};
 use crate::host::memory_manager::MemoryManager;
-use crate::host::network::interface::FifoPacketPriority;
+use crate::host::network::interface::{FifoPacketPriority, ReuseOptions};
 use crate::host::network::namespace::{AssociationHandle, NetworkNamespace};
-use crate::host::syscall::io::IoVec;
+use crate::host::syscall::io::{write_partial, IoVec};
 use crate::host::syscall_types::SyscallError;
 use crate::network::packet::PacketRc;
 use crate::utility::callback_queue::CallbackQueue;
 use crate::utility::sockaddr::SockaddrStorage;
 use crate::utility::HostTreePointer;
 
+use self::icmp::IcmpSocket;
 use self::legacy_tcp::LegacyTcpSocket;
 use self::udp::UdpSocket;
 
+pub mod icmp;
 pub mod legacy_tcp;
 pub mod udp;
 
@@ -30,6 +33,7 @@ pub mod udp;
 pub enum InetSocket {
     LegacyTcp(Arc<AtomicRefCell<LegacyTcpSocket>>),
     Udp(Arc<AtomicRefCell<UdpSocket>>),
+    Icmp(Arc<AtomicRefCell<IcmpSocket>>),
 }
 
 impl InetSocket {
@@ -37,6 +41,7 @@ impl InetSocket {
         match self {
             Self::LegacyTcp(ref f) => InetSocketRef::LegacyTcp(f.borrow()),
             Self::Udp(ref f) => InetSocketRef::Udp(f.borrow()),
+            Self::Icmp(ref f) => InetSocketRef::Icmp(f.borrow()),
         }
     }
 
@@ -44,6 +49,7 @@ impl InetSocket {
         Ok(match self {
             Self::LegacyTcp(ref f) => InetSocketRef::LegacyTcp(f.try_borrow()?),
             Self::Udp(ref f) => InetSocketRef::Udp(f.try_borrow()?),
+            Self::Icmp(ref f) => InetSocketRef::Icmp(f.try_borrow()?),
         })
     }
 
@@ -51,6 +57,7 @@ impl InetSocket {
         match self {
             Self::LegacyTcp(ref f) => InetSocketRefMut::LegacyTcp(f.borrow_mut()),
             Self::Udp(ref f) => InetSocketRefMut::Udp(f.borrow_mut()),
+            Self::Icmp(ref f) => InetSocketRefMut::Icmp(f.borrow_mut()),
         }
     }
 
@@ -58,6 +65,7 @@ impl InetSocket {
         Ok(match self {
             Self::LegacyTcp(ref f) => InetSocketRefMut::LegacyTcp(f.try_borrow_mut()?),
             Self::Udp(ref f) => InetSocketRefMut::Udp(f.try_borrow_mut()?),
+            Self::Icmp(ref f) => InetSocketRefMut::Icmp(f.try_borrow_mut()?),
         })
     }
 
@@ -65,6 +73,7 @@ impl InetSocket {
         match self {
             Self::LegacyTcp(x) => InetSocketWeak::LegacyTcp(Arc::downgrade(x)),
             Self::Udp(x) => InetSocketWeak::Udp(Arc::downgrade(x)),
+            Self::Icmp(x) => InetSocketWeak::Icmp(Arc::downgrade(x)),
         }
     }
 
@@ -74,6 +83,7 @@ impl InetSocket {
             // object for consistency with the handle for the `LegacySocket`
             Self::LegacyTcp(f) => f.borrow().canonical_handle(),
             Self::Udp(f) => Arc::as_ptr(f) as usize,
+            Self::Icmp(f) => Arc::as_ptr(f) as usize,
         }
     }
 
@@ -83,9 +93,19 @@ impl InetSocket {
         net_ns: &NetworkNamespace,
         rng: impl rand::Rng,
     ) -> SyscallResult {
+        let family = self.borrow().family();
+        let addr = match (family, addr) {
+            (InetFamily::Ipv6 { v6_only }, Some(addr)) => {
+                Some(ipv6_sockaddr_to_ipv4(addr, v6_only).ok_or(Errno::EADDRNOTAVAIL)?)
+            }
+            (_, addr) => addr.copied(),
+        };
+        let addr = addr.as_ref();
+
         match self {
             Self::LegacyTcp(socket) => LegacyTcpSocket::bind(socket, addr, net_ns, rng),
             Self::Udp(socket) => UdpSocket::bind(socket, addr, net_ns, rng),
+            Self::Icmp(socket) => IcmpSocket::bind(socket, addr, net_ns, rng),
         }
     }
 
@@ -101,6 +121,7 @@ impl InetSocket {
                 LegacyTcpSocket::listen(socket, backlog, net_ns, rng, cb_queue)
             }
             Self::Udp(socket) => UdpSocket::listen(socket, backlog, net_ns, rng, cb_queue),
+            Self::Icmp(socket) => IcmpSocket::listen(socket, backlog, net_ns, rng, cb_queue),
         }
     }
 
@@ -111,22 +132,39 @@ impl InetSocket {
         rng: impl rand::Rng,
         cb_queue: &mut CallbackQueue,
     ) -> Result<(), SyscallError> {
+        let family = self.borrow().family();
+        let translated_addr;
+        let addr = match family {
+            // an `AF_UNSPEC` address dissolves the association, so doesn't need translating
+            InetFamily::Ipv6 { v6_only } if addr.family() != Some(AddressFamily::Unspec) => {
+                translated_addr = ipv6_sockaddr_to_ipv4(addr, v6_only).ok_or(Errno::ENETUNREACH)?;
+                &translated_addr
+            }
+            _ => addr,
+        };
+
         match self {
             Self::LegacyTcp(socket) => {
                 LegacyTcpSocket::connect(socket, addr, net_ns, rng, cb_queue)
             }
             Self::Udp(socket) => UdpSocket::connect(socket, addr, net_ns, rng, cb_queue),
+            Self::Icmp(socket) => IcmpSocket::connect(socket, addr, net_ns, rng, cb_queue),
         }
     }
 
     pub fn sendmsg(
         &self,
-        args: SendmsgArgs,
+        mut args: SendmsgArgs,
         memory_manager: &mut MemoryManager,
         net_ns: &NetworkNamespace,
         rng: impl rand::Rng,
         cb_queue: &mut CallbackQueue,
     ) -> Result<libc::ssize_t, SyscallError> {
+        let family = self.borrow().family();
+        if let (InetFamily::Ipv6 { v6_only }, Some(addr)) = (family, args.addr) {
+            args.addr = Some(ipv6_sockaddr_to_ipv4(&addr, v6_only).ok_or(Errno::ENETUNREACH)?);
+        }
+
         match self {
             Self::LegacyTcp(socket) => {
                 LegacyTcpSocket::sendmsg(socket, args, memory_manager, net_ns, rng, cb_queue)
@@ -134,6 +172,9 @@ impl InetSocket {
             Self::Udp(socket) => {
                 UdpSocket::sendmsg(socket, args, memory_manager, net_ns, rng, cb_queue)
             }
+            Self::Icmp(socket) => {
+                IcmpSocket::sendmsg(socket, args, memory_manager, net_ns, rng, cb_queue)
+            }
         }
     }
 
@@ -143,12 +184,21 @@ impl InetSocket {
         memory_manager: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<RecvmsgReturn, SyscallError> {
-        match self {
+        let family = self.borrow().family();
+
+        let mut rv = match self {
             Self::LegacyTcp(socket) => {
                 LegacyTcpSocket::recvmsg(socket, args, memory_manager, cb_queue)
             }
             Self::Udp(socket) => UdpSocket::recvmsg(socket, args, memory_manager, cb_queue),
+            Self::Icmp(socket) => IcmpSocket::recvmsg(socket, args, memory_manager, cb_queue),
+        }?;
+
+        if let Some(addr) = rv.addr.and_then(|x| x.as_inet().copied()) {
+            rv.addr = Some(family.sockaddr_from_ipv4(addr.into()));
         }
+
+        Ok(rv)
     }
 }
 
@@ -157,6 +207,7 @@ impl std::fmt::Debug for InetSocket {
         match self {
             Self::LegacyTcp(_) => write!(f, "LegacyTcp")?,
             Self::Udp(_) => write!(f, "Udp")?,
+            Self::Icmp(_) => write!(f, "Icmp")?,
         }
 
         if let Ok(file) = self.try_borrow() {
@@ -175,28 +226,30 @@ impl std::fmt::Debug for InetSocket {
 pub enum InetSocketRef<'a> {
     LegacyTcp(atomic_refcell::AtomicRef<'a, LegacyTcpSocket>),
     Udp(atomic_refcell::AtomicRef<'a, UdpSocket>),
+    Icmp(atomic_refcell::AtomicRef<'a, IcmpSocket>),
 }
 
 pub enum InetSocketRefMut<'a> {
     LegacyTcp(atomic_refcell::AtomicRefMut<'a, LegacyTcpSocket>),
     Udp(atomic_refcell::AtomicRefMut<'a, UdpSocket>),
+    Icmp(atomic_refcell::AtomicRefMut<'a, IcmpSocket>),
 }
 
 // file functions
 impl InetSocketRef<'_> {
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn state(&self) -> FileState
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn mode(&self) -> FileMode
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn get_status(&self) -> FileStatus
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn has_open_file(&self) -> bool
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn supports_sa_restart(&self) -> bool
     );
 }
@@ -204,83 +257,92 @@ impl InetSocketRef<'_> {
 // socket-specific functions
 impl InetSocketRef<'_> {
     pub fn getpeername(&self) -> Result<Option<SockaddrStorage>, SyscallError> {
-        match self {
-            Self::LegacyTcp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
-            Self::Udp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
-        }
+        let addr = match self {
+            Self::LegacyTcp(socket) => socket.getpeername()?,
+            Self::Udp(socket) => socket.getpeername()?,
+            Self::Icmp(socket) => socket.getpeername()?,
+        };
+        Ok(addr.map(|x| self.family().sockaddr_from_ipv4(x.into())))
     }
 
     pub fn getsockname(&self) -> Result<Option<SockaddrStorage>, SyscallError> {
-        match self {
-            Self::LegacyTcp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
-            Self::Udp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
-        }
+        let addr = match self {
+            Self::LegacyTcp(socket) => socket.getsockname()?,
+            Self::Udp(socket) => socket.getsockname()?,
+            Self::Icmp(socket) => socket.getsockname()?,
+        };
+        Ok(addr.map(|x| self.family().sockaddr_from_ipv4(x.into())))
     }
 
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn address_family(&self) -> nix::sys::socket::AddressFamily
     );
 
-    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager), LegacyTcp, Udp;
-        pub fn getsockopt(&self, level: libc::c_int, optname: libc::c_int, optval_ptr: ForeignPtr<()>,
-                          optlen: libc::socklen_t, memory_manager: &mut MemoryManager)
-        -> Result<libc::socklen_t, SyscallError>
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
+        pub fn family(&self) -> InetFamily
     );
 }
 
 // inet socket-specific functions
 impl InetSocketRef<'_> {
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn peek_next_packet_priority(&self) -> Option<FifoPacketPriority>
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn has_data_to_send(&self) -> bool
     );
-    enum_passthrough!(self, (packet), LegacyTcp, Udp;
-        pub fn update_packet_header(&self, packet: &mut PacketRc)
-    );
+
+    pub fn update_packet_header(&self, packet: &mut PacketRc) {
+        mark_ipv6_packet(self.family(), packet);
+
+        match self {
+            Self::LegacyTcp(socket) => socket.update_packet_header(packet),
+            Self::Udp(socket) => socket.update_packet_header(packet),
+            Self::Icmp(socket) => socket.update_packet_header(packet),
+        }
+    }
 }
 
 // file functions
 impl InetSocketRefMut<'_> {
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn state(&self) -> FileState
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn mode(&self) -> FileMode
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn get_status(&self) -> FileStatus
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn has_open_file(&self) -> bool
     );
-    enum_passthrough!(self, (val), LegacyTcp, Udp;
+    enum_passthrough!(self, (val), LegacyTcp, Udp, Icmp;
         pub fn set_has_open_file(&mut self, val: bool)
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn supports_sa_restart(&self) -> bool
     );
-    enum_passthrough!(self, (cb_queue), LegacyTcp, Udp;
+    enum_passthrough!(self, (cb_queue), LegacyTcp, Udp, Icmp;
         pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError>
     );
-    enum_passthrough!(self, (status), LegacyTcp, Udp;
+    enum_passthrough!(self, (status), LegacyTcp, Udp, Icmp;
         pub fn set_status(&mut self, status: FileStatus)
     );
-    enum_passthrough!(self, (request, arg_ptr, memory_manager), LegacyTcp, Udp;
+    enum_passthrough!(self, (request, arg_ptr, memory_manager), LegacyTcp, Udp, Icmp;
         pub fn ioctl(&mut self, request: IoctlRequest, arg_ptr: ForeignPtr<()>, memory_manager: &mut MemoryManager) -> SyscallResult
     );
-    enum_passthrough!(self, (ptr), LegacyTcp, Udp;
+    enum_passthrough!(self, (ptr), LegacyTcp, Udp, Icmp;
         pub fn add_legacy_listener(&mut self, ptr: HostTreePointer<c::StatusListener>)
     );
-    enum_passthrough!(self, (ptr), LegacyTcp, Udp;
+    enum_passthrough!(self, (ptr), LegacyTcp, Udp, Icmp;
         pub fn remove_legacy_listener(&mut self, ptr: *mut c::StatusListener)
     );
-    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), LegacyTcp, Udp;
+    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), LegacyTcp, Udp, Icmp;
         pub fn readv(&mut self, iovs: &[IoVec], offset: Option<libc::off_t>, flags: libc::c_int,
                      mem: &mut MemoryManager, cb_queue: &mut CallbackQueue) -> Result<libc::ssize_t, SyscallError>
     );
-    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), LegacyTcp, Udp;
+    enum_passthrough!(self, (iovs, offset, flags, mem, cb_queue), LegacyTcp, Udp, Icmp;
         pub fn writev(&mut self, iovs: &[IoVec], offset: Option<libc::off_t>, flags: libc::c_int,
                       mem: &mut MemoryManager, cb_queue: &mut CallbackQueue) -> Result<libc::ssize_t, SyscallError>
     );
@@ -289,64 +351,165 @@ impl InetSocketRefMut<'_> {
 // socket-specific functions
 impl InetSocketRefMut<'_> {
     pub fn getpeername(&self) -> Result<Option<SockaddrStorage>, SyscallError> {
-        match self {
-            Self::LegacyTcp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
-            Self::Udp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
-        }
+        let addr = match self {
+            Self::LegacyTcp(socket) => socket.getpeername()?,
+            Self::Udp(socket) => socket.getpeername()?,
+            Self::Icmp(socket) => socket.getpeername()?,
+        };
+        Ok(addr.map(|x| self.family().sockaddr_from_ipv4(x.into())))
     }
 
     pub fn getsockname(&self) -> Result<Option<SockaddrStorage>, SyscallError> {
-        match self {
-            Self::LegacyTcp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
-            Self::Udp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
-        }
+        let addr = match self {
+            Self::LegacyTcp(socket) => socket.getsockname()?,
+            Self::Udp(socket) => socket.getsockname()?,
+            Self::Icmp(socket) => socket.getsockname()?,
+        };
+        Ok(addr.map(|x| self.family().sockaddr_from_ipv4(x.into())))
     }
 
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn address_family(&self) -> nix::sys::socket::AddressFamily
     );
 
-    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager), LegacyTcp, Udp;
-        pub fn getsockopt(&self, level: libc::c_int, optname: libc::c_int, optval_ptr: ForeignPtr<()>,
-                          optlen: libc::socklen_t, memory_manager: &mut MemoryManager)
-        -> Result<libc::socklen_t, SyscallError>
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
+        pub fn family(&self) -> InetFamily
     );
 
-    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager), LegacyTcp, Udp;
-        pub fn setsockopt(&mut self, level: libc::c_int, optname: libc::c_int, optval_ptr: ForeignPtr<()>,
-                          optlen: libc::socklen_t, memory_manager: &MemoryManager)
-        -> Result<(), SyscallError>
-    );
+    pub fn getsockopt(
+        &mut self,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        memory_manager: &mut MemoryManager,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::socklen_t, SyscallError> {
+        if level == libc::IPPROTO_IPV6 {
+            return ipv6_getsockopt(self.family(), optname, optval_ptr, optlen, memory_manager);
+        }
+
+        match self {
+            Self::LegacyTcp(socket) => {
+                socket.getsockopt(level, optname, optval_ptr, optlen, memory_manager, cb_queue)
+            }
+            Self::Udp(socket) => {
+                socket.getsockopt(level, optname, optval_ptr, optlen, memory_manager, cb_queue)
+            }
+            Self::Icmp(socket) => {
+                socket.getsockopt(level, optname, optval_ptr, optlen, memory_manager, cb_queue)
+            }
+        }
+    }
+
+    pub fn setsockopt(
+        &mut self,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        memory_manager: &MemoryManager,
+    ) -> Result<(), SyscallError> {
+        if level == libc::IPPROTO_IPV6 {
+            return self.ipv6_setsockopt(optname, optval_ptr, optlen, memory_manager);
+        }
+
+        match self {
+            Self::LegacyTcp(socket) => {
+                socket.setsockopt(level, optname, optval_ptr, optlen, memory_manager)
+            }
+            Self::Udp(socket) => {
+                socket.setsockopt(level, optname, optval_ptr, optlen, memory_manager)
+            }
+            Self::Icmp(socket) => {
+                socket.setsockopt(level, optname, optval_ptr, optlen, memory_manager)
+            }
+        }
+    }
+
+    fn ipv6_setsockopt(
+        &mut self,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        memory_manager: &MemoryManager,
+    ) -> Result<(), SyscallError> {
+        if !matches!(self.family(), InetFamily::Ipv6 { .. }) {
+            return Err(Errno::ENOPROTOOPT.into());
+        }
+
+        match optname {
+            libc::IPV6_V6ONLY => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let v6_only = memory_manager.read(optval_ptr)? != 0;
+
+                match self {
+                    Self::LegacyTcp(socket) => socket.set_v6_only(v6_only),
+                    Self::Udp(socket) => socket.set_v6_only(v6_only),
+                    // we don't support IPv6 ping sockets
+                    Self::Icmp(_) => Err(Errno::ENOPROTOOPT.into()),
+                }
+            }
+            _ => {
+                log::debug!("setsockopt called with unsupported IPv6 option {optname}");
+                Err(Errno::ENOPROTOOPT.into())
+            }
+        }
+    }
 
     pub fn accept(&mut self, cb_queue: &mut CallbackQueue) -> Result<OpenFile, SyscallError> {
         match self {
             Self::LegacyTcp(socket) => socket.accept(cb_queue),
             Self::Udp(socket) => socket.accept(cb_queue),
+            Self::Icmp(socket) => socket.accept(cb_queue),
         }
     }
 
-    enum_passthrough!(self, (how, cb_queue), LegacyTcp, Udp;
+    enum_passthrough!(self, (how, cb_queue), LegacyTcp, Udp, Icmp;
         pub fn shutdown(&mut self, how: Shutdown, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError>
     );
 }
 
 // inet socket-specific functions
 impl InetSocketRefMut<'_> {
-    enum_passthrough!(self, (packet, cb_queue), LegacyTcp, Udp;
-        pub fn push_in_packet(&mut self, packet: PacketRc, cb_queue: &mut CallbackQueue)
-    );
-    enum_passthrough!(self, (cb_queue), LegacyTcp, Udp;
+    pub fn push_in_packet(&mut self, mut packet: PacketRc, cb_queue: &mut CallbackQueue) {
+        if !is_family_packet(self.family(), &packet) {
+            log::trace!("Dropping IPv4 packet received by an IPv6-only socket");
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
+
+        match self {
+            Self::LegacyTcp(socket) => socket.push_in_packet(packet, cb_queue),
+            Self::Udp(socket) => socket.push_in_packet(packet, cb_queue),
+            Self::Icmp(socket) => socket.push_in_packet(packet, cb_queue),
+        }
+    }
+    enum_passthrough!(self, (cb_queue), LegacyTcp, Udp, Icmp;
         pub fn pull_out_packet(&mut self, cb_queue: &mut CallbackQueue) -> Option<PacketRc>
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn peek_next_packet_priority(&self) -> Option<FifoPacketPriority>
     );
-    enum_passthrough!(self, (), LegacyTcp, Udp;
+    enum_passthrough!(self, (), LegacyTcp, Udp, Icmp;
         pub fn has_data_to_send(&self) -> bool
     );
-    enum_passthrough!(self, (packet), LegacyTcp, Udp;
-        pub fn update_packet_header(&self, packet: &mut PacketRc)
-    );
+
+    pub fn update_packet_header(&self, packet: &mut PacketRc) {
+        mark_ipv6_packet(self.family(), packet);
+
+        match self {
+            Self::LegacyTcp(socket) => socket.update_packet_header(packet),
+            Self::Udp(socket) => socket.update_packet_header(packet),
+            Self::Icmp(socket) => socket.update_packet_header(packet),
+        }
+    }
 }
 
 impl std::fmt::Debug for InetSocketRef<'_> {
@@ -354,6 +517,7 @@ impl std::fmt::Debug for InetSocketRef<'_> {
         match self {
             Self::LegacyTcp(_) => write!(f, "LegacyTcp")?,
             Self::Udp(_) => write!(f, "Udp")?,
+            Self::Icmp(_) => write!(f, "Icmp")?,
         }
 
         write!(
@@ -370,6 +534,7 @@ impl std::fmt::Debug for InetSocketRefMut<'_> {
         match self {
             Self::LegacyTcp(_) => write!(f, "LegacyTcp")?,
             Self::Udp(_) => write!(f, "Udp")?,
+            Self::Icmp(_) => write!(f, "Icmp")?,
         }
 
         write!(
@@ -385,6 +550,7 @@ impl std::fmt::Debug for InetSocketRefMut<'_> {
 pub enum InetSocketWeak {
     LegacyTcp(Weak<AtomicRefCell<LegacyTcpSocket>>),
     Udp(Weak<AtomicRefCell<UdpSocket>>),
+    Icmp(Weak<AtomicRefCell<IcmpSocket>>),
 }
 
 impl InetSocketWeak {
@@ -392,7 +558,191 @@ impl InetSocketWeak {
         match self {
             Self::LegacyTcp(x) => x.upgrade().map(InetSocket::LegacyTcp),
             Self::Udp(x) => x.upgrade().map(InetSocket::Udp),
+            Self::Icmp(x) => x.upgrade().map(InetSocket::Icmp),
+        }
+    }
+}
+
+/// The address family of an inet socket. Shadow's network stack only uses IPv4 addresses, so the
+/// addresses of IPv6 sockets are translated to and from IPv4 addresses at the socket API boundary.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InetFamily {
+    Ipv4,
+    /// An IPv6 socket. Unless `v6_only` is set, the socket can also communicate with IPv4 peers
+    /// using IPv4-mapped addresses (`::ffff:a.b.c.d`).
+    Ipv6 {
+        v6_only: bool,
+    },
+}
+
+impl InetFamily {
+    pub fn address_family(&self) -> AddressFamily {
+        match self {
+            Self::Ipv4 => AddressFamily::Inet,
+            Self::Ipv6 { .. } => AddressFamily::Inet6,
+        }
+    }
+
+    /// The socket domain, as returned by `SO_DOMAIN`.
+    pub fn domain(&self) -> libc::c_int {
+        match self {
+            Self::Ipv4 => libc::AF_INET,
+            Self::Ipv6 { .. } => libc::AF_INET6,
+        }
+    }
+
+    pub fn set_v6_only(&mut self, val: bool) -> Result<(), SyscallError> {
+        match self {
+            Self::Ipv4 => Err(Errno::ENOPROTOOPT.into()),
+            Self::Ipv6 { v6_only } => {
+                *v6_only = val;
+                Ok(())
+            }
+        }
+    }
+
+    /// The family used to associate the socket with network interfaces. IPv6-only sockets can
+    /// share addresses with IPv4 sockets.
+    pub fn association_family(&self) -> c::AssociationFamily {
+        match self {
+            Self::Ipv4 => c::_AssociationFamily_ASSOC_IPV4,
+            Self::Ipv6 { v6_only: false } => c::_AssociationFamily_ASSOC_IPV6_DUAL_STACK,
+            Self::Ipv6 { v6_only: true } => c::_AssociationFamily_ASSOC_IPV6_ONLY,
+        }
+    }
+
+    /// Convert an IPv4 socket address used internally by Shadow to a socket address of this
+    /// family, to be returned to the application.
+    pub fn sockaddr_from_ipv4(&self, addr: SocketAddrV4) -> SockaddrStorage {
+        match self {
+            Self::Ipv4 => addr.into(),
+            Self::Ipv6 { .. } => {
+                SocketAddrV6::new(ipv4_to_ipv6(*addr.ip()), addr.port(), 0, 0).into()
+            }
+        }
+    }
+}
+
+/// Translate an IPv6 address to the IPv4 address that Shadow routes on. The unspecified and
+/// loopback addresses map to their IPv4 counterparts, IPv4-mapped addresses map to the embedded
+/// IPv4 address (unless `v6_only` is set), and hosts' configured IPv6 addresses map to the hosts'
+/// IPv4 addresses. Returns `None` for any other address.
+fn ipv6_to_ipv4(addr: Ipv6Addr, v6_only: bool) -> Option<Ipv4Addr> {
+    if addr.is_unspecified() {
+        Some(Ipv4Addr::UNSPECIFIED)
+    } else if addr.is_loopback() {
+        Some(Ipv4Addr::LOCALHOST)
+    } else if let Some(addr) = addr.to_ipv4_mapped() {
+        (!v6_only).then_some(addr)
+    } else {
+        Worker::ipv6_alias_to_ipv4(addr)
+    }
+}
+
+/// Translate an IPv4 address used internally by Shadow to the IPv6 address that an IPv6 socket
+/// should see. This is the reverse of [`ipv6_to_ipv4`], where an IPv4 address of a host without an
+/// IPv6 address is returned as an IPv4-mapped address.
+fn ipv4_to_ipv6(addr: Ipv4Addr) -> Ipv6Addr {
+    if addr.is_unspecified() {
+        Ipv6Addr::UNSPECIFIED
+    } else if addr == Ipv4Addr::LOCALHOST {
+        Ipv6Addr::LOCALHOST
+    } else {
+        Worker::ipv4_to_ipv6_alias(addr).unwrap_or_else(|| addr.to_ipv6_mapped())
+    }
+}
+
+/// Shadow only routes IPv4 packets, so mark the packets that an IPv6 socket sends to an IPv6 peer
+/// (rather than to an IPv4-mapped address) as IPv6 packets.
+fn mark_ipv6_packet(family: InetFamily, packet: &mut PacketRc) {
+    if let InetFamily::Ipv6 { .. } = family {
+        let dst = ipv4_to_ipv6(*packet.dst_address().ip());
+        if dst.to_ipv4_mapped().is_none() {
+            packet.set_ipv6(true);
+        }
+    }
+}
+
+/// Returns false if a socket of the family shouldn't receive the packet. Like Linux, IPv6-only
+/// sockets don't receive IPv4 packets or packets from IPv4-mapped addresses.
+fn is_family_packet(family: InetFamily, packet: &PacketRc) -> bool {
+    match family {
+        InetFamily::Ipv6 { v6_only: true } => {
+            let src = ipv4_to_ipv6(*packet.src_address().ip());
+            packet.is_ipv6() && src.to_ipv4_mapped().is_none()
         }
+        _ => true,
+    }
+}
+
+/// Translate a socket address provided to an IPv6 socket to an IPv4 socket address. Returns
+/// `None` if the address isn't an IPv6 address or has no IPv4 equivalent.
+fn ipv6_sockaddr_to_ipv4(addr: &SockaddrStorage, v6_only: bool) -> Option<SockaddrStorage> {
+    let addr = SocketAddrV6::from(*addr.as_inet6()?);
+    let ip = ipv6_to_ipv4(*addr.ip(), v6_only)?;
+    Some(SocketAddrV4::new(ip, addr.port()).into())
+}
+
+/// Get an `IPPROTO_IPV6` socket option.
+fn ipv6_getsockopt(
+    family: InetFamily,
+    optname: libc::c_int,
+    optval_ptr: ForeignPtr<()>,
+    optlen: libc::socklen_t,
+    memory_manager: &mut MemoryManager,
+) -> Result<libc::socklen_t, SyscallError> {
+    let InetFamily::Ipv6 { v6_only } = family else {
+        return Err(Errno::ENOPROTOOPT.into());
+    };
+
+    match optname {
+        libc::IPV6_V6ONLY => {
+            let optval_ptr = optval_ptr.cast::<libc::c_int>();
+            let bytes_written = write_partial(
+                memory_manager,
+                &libc::c_int::from(v6_only),
+                optval_ptr,
+                optlen as usize,
+            )?;
+            Ok(bytes_written as libc::socklen_t)
+        }
+        _ => {
+            log::debug!("getsockopt called with unsupported IPv6 option {optname}");
+            Err(Errno::ENOPROTOOPT.into())
+        }
+    }
+}
+
+/// The default time-to-live of unicast packets.
+const DEFAULT_IP_TTL: u8 = 64;
+
+/// Read the value of the `IP_TTL` socket option. Like linux, -1 restores the default TTL, and the
+/// TTL must otherwise be between 1 and 255.
+fn read_ip_ttl_option(
+    optval_ptr: ForeignPtr<()>,
+    optlen: libc::socklen_t,
+    mem: &MemoryManager,
+) -> Result<u8, SyscallError> {
+    match read_ip_option(optval_ptr, optlen, mem)? {
+        -1 => Ok(DEFAULT_IP_TTL),
+        x @ 1..=255 => Ok(x.try_into().unwrap()),
+        _ => Err(Errno::EINVAL.into()),
+    }
+}
+
+/// Read the value of an `IPPROTO_IP` socket option, which linux allows to be either an int or a
+/// single byte.
+fn read_ip_option(
+    optval_ptr: ForeignPtr<()>,
+    optlen: libc::socklen_t,
+    mem: &MemoryManager,
+) -> Result<libc::c_int, SyscallError> {
+    if usize::try_from(optlen).unwrap() >= std::mem::size_of::<libc::c_int>() {
+        Ok(mem.read(optval_ptr.cast::<libc::c_int>())?)
+    } else if optlen >= 1 {
+        Ok(mem.read(optval_ptr.cast::<u8>())?.into())
+    } else {
+        Err(Errno::EINVAL.into())
     }
 }
 
@@ -401,10 +751,14 @@ impl InetSocketWeak {
 /// non-zero port will be chosen. The final local address will be returned. If the peer address is
 /// unspecified and has a port of 0, the socket will receive packets from every peer address. The
 /// socket will be automatically disassociated when the returned [`AssociationHandle`] is dropped.
+/// The reuse options decide whether the socket can share its addresses with other sockets.
+///
+/// The socket must not be mutably borrowed.
 fn associate_socket(
     socket: InetSocket,
     local_addr: SocketAddrV4,
     peer_addr: SocketAddrV4,
+    reuse: ReuseOptions,
     net_ns: &NetworkNamespace,
     rng: impl rand::Rng,
 ) -> Result<(SocketAddrV4, AssociationHandle), SyscallError> {
@@ -421,13 +775,15 @@ fn associate_socket(
     let protocol = match socket {
         InetSocket::LegacyTcp(_) => c::_ProtocolType_PTCP,
         InetSocket::Udp(_) => c::_ProtocolType_PUDP,
+        InetSocket::Icmp(_) => c::_ProtocolType_PICMP,
     };
+    let family = socket.borrow().family().association_family();
 
     // get a free ephemeral port if they didn't specify one
     let local_addr = if local_addr.port() != 0 {
         local_addr
     } else {
-        let Some(new_port) = net_ns.get_random_free_port(protocol, *local_addr.ip(), peer_addr, rng) else {
+        let Some(new_port) = net_ns.get_random_free_port(protocol, *local_addr.ip(), peer_addr, family, rng) else {
             log::debug!("Association required an ephemeral port but none are available");
             return Err(Errno::EADDRINUSE.into());
         };
@@ -439,7 +795,7 @@ fn associate_socket(
     };
 
     // make sure the port is available at this address for this protocol
-    if !net_ns.is_interface_available(protocol, local_addr, peer_addr) {
+    if !net_ns.is_interface_available(protocol, local_addr, peer_addr, reuse, family) {
         log::debug!(
             "The provided addresses (local={local_addr}, peer={peer_addr}) are not available"
         );
@@ -449,7 +805,9 @@ fn associate_socket(
     let socket = unsafe { c::compatsocket_fromInetSocket(&socket) };
 
     // associate the interfaces corresponding to addr with socket
-    let handle = unsafe { net_ns.associate_interface(&socket, protocol, local_addr, peer_addr) };
+    let handle = unsafe {
+        net_ns.associate_interface(&socket, protocol, local_addr, peer_addr, reuse, family)
+    };
 
     Ok((local_addr, handle))
 }
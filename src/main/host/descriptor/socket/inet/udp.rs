@@ -1,4 +1,4 @@
-use std::collections::LinkedList;
+use std::collections::{HashMap, LinkedList};
 use std::io::{Read, Write};
 use std::net::{Ipv4Addr, SocketAddrV4};
 use std::sync::Arc;
@@ -12,17 +12,18 @@ use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 
 use crate::core::worker::Worker;
 use crate::cshadow as c;
-use crate::host::descriptor::socket::inet::{self, InetSocket};
+use crate::host::descriptor::socket::inet::{self, InetFamily, InetSocket};
 use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, ShutdownFlags};
 use crate::host::descriptor::{
     File, FileMode, FileState, FileStatus, OpenFile, Socket, StateEventSource, StateListenerFilter,
     SyscallResult,
 };
 use crate::host::memory_manager::MemoryManager;
-use crate::host::network::interface::FifoPacketPriority;
-use crate::host::network::namespace::{AssociationHandle, NetworkNamespace};
+use crate::host::network::interface::{FifoPacketPriority, ReuseOptions};
+use crate::host::network::namespace::{AssociationHandle, MembershipHandle, NetworkNamespace};
 use crate::host::syscall::io::{write_partial, IoVec, IoVecReader, IoVecWriter};
 use crate::host::syscall_types::SyscallError;
+use crate::network::multicast;
 use crate::network::packet::{PacketRc, PacketStatus};
 use crate::utility::callback_queue::{CallbackQueue, Handle};
 use crate::utility::sockaddr::SockaddrStorage;
@@ -32,6 +33,10 @@ use crate::utility::{HostTreePointer, ObjectCounter};
 // 65,535 (2^16 - 1) - 20 (ip header) - 8 (udp header)
 const CONFIG_DATAGRAM_MAX_SIZE: usize = 65507;
 
+/// Maximum number of multicast groups that a socket can join (linux's default
+/// `net.ipv4.igmp_max_memberships`).
+const MAX_MULTICAST_MEMBERSHIPS: usize = 20;
+
 pub struct UdpSocket {
     event_source: StateEventSource,
     status: FileStatus,
@@ -42,6 +47,25 @@ pub struct UdpSocket {
     peer_addr: Option<SocketAddrV4>,
     bound_addr: Option<SocketAddrV4>,
     association: Option<AssociationHandle>,
+    /// An error from an ICMP message that will be returned by the next socket operation.
+    pending_error: Option<Errno>,
+    family: InetFamily,
+    /// The `SO_REUSEADDR` and `SO_REUSEPORT` options.
+    reuse: ReuseOptions,
+    /// The `SO_LINGER` option, which has no effect for UDP sockets.
+    linger: libc::linger,
+    /// The `SO_BROADCAST` option, which is required to send to the broadcast address.
+    broadcast: bool,
+    /// The `IP_MULTICAST_TTL` option.
+    multicast_ttl: u8,
+    /// The `IP_MULTICAST_LOOP` option.
+    multicast_loop: bool,
+    /// The `IP_TOS` option, including the ECN codepoint.
+    tos: u8,
+    /// The `IP_TTL` option, which is used for unicast and broadcast packets.
+    ttl: u8,
+    /// The multicast groups joined using `IP_ADD_MEMBERSHIP`.
+    multicast_memberships: HashMap<Ipv4Addr, MembershipHandle>,
     // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
     // this file
     has_open_file: bool,
@@ -51,6 +75,7 @@ pub struct UdpSocket {
 impl UdpSocket {
     pub fn new(
         status: FileStatus,
+        family: InetFamily,
         send_buf_size: usize,
         recv_buf_size: usize,
     ) -> Arc<AtomicRefCell<Self>> {
@@ -64,6 +89,19 @@ impl UdpSocket {
             peer_addr: None,
             bound_addr: None,
             association: None,
+            pending_error: None,
+            family,
+            reuse: ReuseOptions::default(),
+            linger: libc::linger {
+                l_onoff: 0,
+                l_linger: 0,
+            },
+            broadcast: false,
+            multicast_ttl: 1,
+            multicast_loop: true,
+            tos: 0,
+            ttl: inet::DEFAULT_IP_TTL,
+            multicast_memberships: HashMap::new(),
             has_open_file: false,
             _counter: ObjectCounter::new("UdpSocket"),
         };
@@ -100,6 +138,11 @@ impl UdpSocket {
     pub fn push_in_packet(&mut self, mut packet: PacketRc, cb_queue: &mut CallbackQueue) {
         packet.add_status(PacketStatus::RcvSocketProcessed);
 
+        if let Some(icmp_header) = packet.icmp_header() {
+            self.push_in_icmp_error(&icmp_header, cb_queue);
+            return;
+        }
+
         if let Some(peer_addr) = self.peer_addr {
             if peer_addr != packet.src_address() {
                 // connect(2): "If the socket sockfd is of type SOCK_DGRAM, then addr is the address
@@ -121,7 +164,18 @@ impl UdpSocket {
             }
         };
 
-        // TODO: also check the dst address to make sure we are the intended socket?
+        // like linux, a socket bound to a specific address doesn't receive multicast or broadcast
+        // packets
+        // TODO: also check the dst address of unicast packets to make sure we are the intended
+        // socket?
+        if multicast::is_group_address(*packet.dst_address().ip())
+            && self
+                .bound_addr
+                .map_or(false, |addr| !addr.ip().is_unspecified())
+        {
+            packet.add_status(PacketStatus::RcvSocketDropped);
+            return;
+        }
 
         // don't bother copying the bytes if we know the push will fail
         if !self.recv_buffer.has_space() {
@@ -153,6 +207,32 @@ impl UdpSocket {
         self.refresh_readable_writable(cb_queue);
     }
 
+    /// Handle an ICMP error for a packet that this socket sent.
+    fn push_in_icmp_error(&mut self, header: &c::PacketICMPHeader, cb_queue: &mut CallbackQueue) {
+        if header.type_ != c::ProtocolICMPType_PICMP_DEST_UNREACH
+            || header.code != c::ProtocolICMPCode_PICMP_CODE_PORT_UNREACH
+        {
+            return;
+        }
+
+        let original_dst = SocketAddrV4::new(
+            u32::from_be(header.originalDestinationIP).into(),
+            u16::from_be(header.originalDestinationPort),
+        );
+
+        // udp(7): "All fatal errors will be passed to the user as an error return even when the
+        // socket is not connected", but linux only reports errors for connected sockets unless
+        // `IP_RECVERR` is set, which we don't support
+        if self.peer_addr != Some(original_dst) {
+            return;
+        }
+
+        log::trace!("UDP socket received a port unreachable error for peer {original_dst}");
+        self.pending_error = Some(Errno::ECONNREFUSED);
+
+        self.refresh_readable_writable(cb_queue);
+    }
+
     pub fn pull_out_packet(&mut self, cb_queue: &mut CallbackQueue) -> Option<PacketRc> {
         // pop the message from the send buffer
         let Some((message, header)) = self.send_buffer.pop_message() else {
@@ -172,6 +252,12 @@ impl UdpSocket {
         // transfer the `Bytes` directly from the buffer to the packet without copying the bytes
 
         packet.set_udp(header.src, header.dst);
+        if header.dst.ip().is_multicast() {
+            packet.set_udp_multicast(self.multicast_ttl, self.multicast_loop);
+        } else {
+            packet.set_ttl(self.ttl);
+        }
+        packet.set_tos(self.tos);
         packet.set_payload(&message, priority);
         packet.add_status(PacketStatus::SndCreated);
 
@@ -181,7 +267,7 @@ impl UdpSocket {
     }
 
     pub fn peek_next_packet_priority(&self) -> Option<FifoPacketPriority> {
-        self.send_buffer.buffer.front().map(|x| x.1.packet_priority)
+        self.send_buffer.peek_message().map(|x| x.1.packet_priority)
     }
 
     pub fn has_data_to_send(&self) -> bool {
@@ -213,13 +299,29 @@ impl UdpSocket {
     }
 
     pub fn address_family(&self) -> AddressFamily {
-        AddressFamily::Inet
+        self.family.address_family()
+    }
+
+    pub fn family(&self) -> InetFamily {
+        self.family
+    }
+
+    pub fn set_v6_only(&mut self, v6_only: bool) -> Result<(), SyscallError> {
+        // the option can't be changed after the socket is bound
+        if self.bound_addr.is_some() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        self.family.set_v6_only(v6_only)
     }
 
     pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
         // drop the existing association handle to disassociate the socket
         self.association = None;
 
+        // leave all multicast groups
+        self.multicast_memberships.clear();
+
         self.copy_state(
             /* mask= */ FileState::all(),
             FileState::CLOSED,
@@ -262,6 +364,8 @@ impl UdpSocket {
             assert!(socket.association.is_none());
         }
 
+        let reuse = socket.borrow().reuse;
+
         // this will allow us to receive packets from any peer
         let unspecified_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
 
@@ -270,6 +374,7 @@ impl UdpSocket {
             InetSocket::Udp(Arc::clone(socket)),
             addr,
             unspecified_addr,
+            reuse,
             net_ns,
             rng,
         )?;
@@ -332,6 +437,12 @@ impl UdpSocket {
             return Err(Errno::EINVAL.into());
         };
 
+        // report an error from a previous ICMP message
+        if let Some(error) = socket_ref.pending_error.take() {
+            socket_ref.refresh_readable_writable(cb_queue);
+            return Err(error.into());
+        }
+
         let dst_addr = match args.addr {
             Some(addr) => match addr.as_inet() {
                 // an inet socket address
@@ -346,6 +457,11 @@ impl UdpSocket {
             },
         };
 
+        // like linux, sending to the broadcast address requires `SO_BROADCAST`
+        if dst_addr.ip().is_broadcast() && !socket_ref.broadcast {
+            return Err(Errno::EACCES.into());
+        }
+
         if socket_ref.get_status().contains(FileStatus::NONBLOCK) {
             flags.insert(MsgFlags::MSG_DONTWAIT);
         }
@@ -381,6 +497,7 @@ impl UdpSocket {
                 InetSocket::Udp(Arc::clone(socket)),
                 local_addr,
                 unspecified_addr,
+                socket_ref.reuse,
                 net_ns,
                 rng,
             )?;
@@ -389,6 +506,23 @@ impl UdpSocket {
             socket_ref.association = Some(handle);
         }
 
+        let mut src_addr = socket_ref.bound_addr.unwrap();
+
+        // multicast and broadcast packets can't be sent from the loopback interface
+        if src_addr.ip() == &Ipv4Addr::LOCALHOST && multicast::is_group_address(*dst_addr.ip()) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // a socket bound to INADDR_ANY sends from the interface that the destination is reached
+        // through
+        if src_addr.ip().is_unspecified() {
+            if dst_addr.ip() == &Ipv4Addr::LOCALHOST {
+                src_addr.set_ip(Ipv4Addr::LOCALHOST);
+            } else {
+                src_addr.set_ip(net_ns.default_ip);
+            }
+        }
+
         // run in a closure so that an early return doesn't skip checking if we should block
         let result = (|| {
             // don't bother copying the bytes if we know the push will fail
@@ -408,7 +542,7 @@ impl UdpSocket {
                 Worker::with_active_host(|host| host.get_next_packet_priority()).unwrap();
 
             let header = MessageSendHeader {
-                src: socket_ref.bound_addr.unwrap(),
+                src: src_addr,
                 dst: dst_addr,
                 packet_priority,
             };
@@ -422,7 +556,7 @@ impl UdpSocket {
 
             // notify the host that this socket has packets to send
             let socket = Arc::clone(socket);
-            let interface_ip = *socket_ref.bound_addr.unwrap().ip();
+            let interface_ip = *src_addr.ip();
             cb_queue.add(move |_cb_queue| {
                 Worker::with_active_host(|host| {
                     let inet_socket = InetSocket::Udp(socket);
@@ -470,6 +604,11 @@ impl UdpSocket {
 
         // run in a closure so that an early return doesn't skip checking if we should block
         let result = (|| {
+            // report an error from a previous ICMP message before any buffered messages
+            if let Some(error) = socket_ref.pending_error.take() {
+                return Err(error);
+            }
+
             // a temporary location to store the message and header if we popped them
             let message_storage;
             let header_storage;
@@ -513,7 +652,7 @@ impl UdpSocket {
                 return_val: return_val.try_into().unwrap(),
                 addr: Some(header.src.into()),
                 msg_flags: return_flags.bits(),
-                control_len: 0,
+                control: Default::default(),
             })
         })();
 
@@ -529,7 +668,7 @@ impl UdpSocket {
                     return_val: 0,
                     addr: None,
                     msg_flags: 0,
-                    control_len: 0,
+                    control: Default::default(),
                 });
             }
 
@@ -633,9 +772,17 @@ impl UdpSocket {
         // to `Ipv4Addr::LOCALHOST`, but the rest of Shadow probably can't handle other loopback
         // addresses (ex: 127.0.0.2) and it's probably best not to change this behaviour
 
-        // make sure we will be able to route this later
+        // like linux, connecting to the broadcast address requires `SO_BROADCAST`
+        if peer_addr.ip().is_broadcast() && !socket.borrow().broadcast {
+            return Err(Errno::EACCES.into());
+        }
+
+        // make sure we will be able to route this later (multicast and broadcast packets are
+        // routed to the hosts in the group)
         // TODO: UDP sockets probably shouldn't return `ECONNREFUSED`
-        if peer_addr.ip() != &std::net::Ipv4Addr::LOCALHOST {
+        if peer_addr.ip() != &std::net::Ipv4Addr::LOCALHOST
+            && !multicast::is_group_address(*peer_addr.ip())
+        {
             let is_routable =
                 Worker::is_routable(net_ns.default_ip.into(), (*peer_addr.ip()).into());
 
@@ -688,6 +835,7 @@ impl UdpSocket {
                     InetSocket::Udp(Arc::clone(socket)),
                     local_addr,
                     unspecified_addr,
+                    socket_ref.reuse,
                     net_ns,
                     rng,
                 )?;
@@ -730,12 +878,13 @@ impl UdpSocket {
     }
 
     pub fn getsockopt(
-        &self,
+        &mut self,
         level: libc::c_int,
         optname: libc::c_int,
         optval_ptr: ForeignPtr<()>,
         optlen: libc::socklen_t,
         mem: &mut MemoryManager,
+        cb_queue: &mut CallbackQueue,
     ) -> Result<libc::socklen_t, SyscallError> {
         match (level, optname) {
             (libc::SOL_SOCKET, libc::SO_SNDBUF) => {
@@ -755,15 +904,44 @@ impl UdpSocket {
                 Ok(bytes_written as libc::socklen_t)
             }
             (libc::SOL_SOCKET, libc::SO_ERROR) => {
-                let error = 0;
+                // like linux, reading the pending error clears it
+                let error: libc::c_int = self.pending_error.take().map(i32::from).unwrap_or(0);
+                self.refresh_readable_writable(cb_queue);
 
                 let optval_ptr = optval_ptr.cast::<libc::c_int>();
                 let bytes_written = write_partial(mem, &error, optval_ptr, optlen as usize)?;
 
                 Ok(bytes_written as libc::socklen_t)
             }
+            (libc::SOL_SOCKET, libc::SO_REUSEADDR | libc::SO_REUSEPORT) => {
+                let val = if optname == libc::SO_REUSEADDR {
+                    self.reuse.addr
+                } else {
+                    self.reuse.port
+                };
+                let val = libc::c_int::from(val);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &val, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_SOCKET, libc::SO_LINGER) => {
+                let optval_ptr = optval_ptr.cast::<libc::linger>();
+                let bytes_written = write_partial(mem, &self.linger, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_SOCKET, libc::SO_BROADCAST) => {
+                let val = libc::c_int::from(self.broadcast);
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &val, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
             (libc::SOL_SOCKET, libc::SO_DOMAIN) => {
-                let domain = libc::AF_INET;
+                let domain = self.family.domain();
 
                 let optval_ptr = optval_ptr.cast::<libc::c_int>();
                 let bytes_written = write_partial(mem, &domain, optval_ptr, optlen as usize)?;
@@ -796,6 +974,24 @@ impl UdpSocket {
                 log::debug!("getsockopt called with unsupported level {level} and opt {optname}");
                 Err(Errno::ENOPROTOOPT.into())
             }
+            (
+                libc::IPPROTO_IP,
+                libc::IP_MULTICAST_TTL | libc::IP_MULTICAST_LOOP | libc::IP_TOS | libc::IP_TTL,
+            ) => {
+                let val = match optname {
+                    libc::IP_MULTICAST_TTL => libc::c_int::from(self.multicast_ttl),
+                    libc::IP_MULTICAST_LOOP => libc::c_int::from(self.multicast_loop),
+                    libc::IP_TTL => libc::c_int::from(self.ttl),
+                    _ => libc::c_int::from(self.tos),
+                };
+
+                // linux writes a single byte if the buffer is smaller than an int, which is the same
+                // as a partial write of the int on little-endian platforms
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(mem, &val, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
             _ => {
                 log::debug!("getsockopt called with unsupported level {level} and opt {optname}");
                 Err(Errno::EOPNOTSUPP.into())
@@ -864,13 +1060,43 @@ impl UdpSocket {
                 self.recv_buffer
                     .set_soft_limit_bytes(val.try_into().unwrap());
             }
-            (libc::SOL_SOCKET, libc::SO_REUSEADDR) => {
-                // TODO: implement this, tor and tgen use it
-                log::warn!("setsockopt SO_REUSEADDR not yet implemented");
+            (libc::SOL_SOCKET, libc::SO_REUSEADDR | libc::SO_REUSEPORT) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let enable = mem.read(optval_ptr)? != 0;
+
+                // like linux, this only affects future binds
+                if optname == libc::SO_REUSEADDR {
+                    self.reuse.addr = enable;
+                } else {
+                    self.reuse.port = enable;
+                }
             }
-            (libc::SOL_SOCKET, libc::SO_REUSEPORT) => {
-                // TODO: implement this, tgen uses it
-                log::warn!("setsockopt SO_REUSEPORT not yet implemented");
+            (libc::SOL_SOCKET, libc::SO_LINGER) => {
+                type OptType = libc::linger;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let linger = mem.read(optval_ptr)?;
+
+                // like linux, the option is stored but there's nothing to linger on. disabling
+                // linger keeps the old linger time, and a negative linger time means "forever".
+                self.linger.l_onoff = (linger.l_onoff != 0).into();
+                if linger.l_onoff != 0 {
+                    self.linger.l_linger = if linger.l_linger < 0 {
+                        libc::c_int::MAX
+                    } else {
+                        linger.l_linger
+                    };
+                }
             }
             (libc::SOL_SOCKET, libc::SO_KEEPALIVE) => {
                 // TODO: implement this, libevent uses it in
@@ -878,8 +1104,80 @@ impl UdpSocket {
                 log::warn!("setsockopt SO_KEEPALIVE not yet implemented");
             }
             (libc::SOL_SOCKET, libc::SO_BROADCAST) => {
-                // TODO: implement this, pkg.go.dev/net uses it
-                log::warn!("setsockopt SO_BROADCAST not yet implemented");
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                self.broadcast = mem.read(optval_ptr)? != 0;
+            }
+            (libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP | libc::IP_DROP_MEMBERSHIP) => {
+                // the option is either an `ip_mreq` or a larger `ip_mreqn`, which both start with
+                // the group address and the interface address
+                type OptType = libc::ip_mreq;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let mreq = mem.read(optval_ptr)?;
+
+                let group = Ipv4Addr::from(u32::from_be(mreq.imr_multiaddr.s_addr));
+                let interface = Ipv4Addr::from(u32::from_be(mreq.imr_interface.s_addr));
+
+                if !group.is_multicast() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                // the internet interface is the only interface that receives multicast packets
+                let default_ip = Worker::with_active_host(|host| host.default_ip()).unwrap();
+                if !interface.is_unspecified() && interface != default_ip {
+                    return Err(Errno::ENODEV.into());
+                }
+
+                if optname == libc::IP_DROP_MEMBERSHIP {
+                    // dropping the handle leaves the group
+                    if self.multicast_memberships.remove(&group).is_none() {
+                        return Err(Errno::EADDRNOTAVAIL.into());
+                    }
+                    return Ok(());
+                }
+
+                if self.multicast_memberships.contains_key(&group) {
+                    return Err(Errno::EADDRINUSE.into());
+                }
+
+                if self.multicast_memberships.len() >= MAX_MULTICAST_MEMBERSHIPS {
+                    return Err(Errno::ENOBUFS.into());
+                }
+
+                let handle = Worker::with_active_host(|host| {
+                    host.network_namespace_borrow().join_multicast_group(group)
+                })
+                .unwrap();
+                self.multicast_memberships.insert(group, handle);
+            }
+            (libc::IPPROTO_IP, libc::IP_MULTICAST_TTL) => {
+                // like linux, -1 restores the default ttl
+                let ttl = match inet::read_ip_option(optval_ptr, optlen, mem)? {
+                    -1 => 1,
+                    x => u8::try_from(x).or(Err(Errno::EINVAL))?,
+                };
+
+                self.multicast_ttl = ttl;
+            }
+            (libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP) => {
+                self.multicast_loop = inet::read_ip_option(optval_ptr, optlen, mem)? != 0;
+            }
+            (libc::IPPROTO_IP, libc::IP_TOS) => {
+                // linux keeps only the low byte
+                self.tos = inet::read_ip_option(optval_ptr, optlen, mem)? as u8;
+            }
+            (libc::IPPROTO_IP, libc::IP_TTL) => {
+                self.ttl = inet::read_ip_ttl_option(optval_ptr, optlen, mem)?;
             }
             _ => {
                 log::debug!("setsockopt called with unsupported level {level} and opt {optname}");
@@ -913,7 +1211,8 @@ impl UdpSocket {
     }
 
     fn refresh_readable_writable(&mut self, cb_queue: &mut CallbackQueue) {
-        let readable = !self.recv_buffer.is_empty();
+        // a pending error makes the socket readable so that a blocked `recv()` can return it
+        let readable = !self.recv_buffer.is_empty() || self.pending_error.is_some();
         let writable = self.send_buffer.has_space();
 
         let readable = readable.then_some(FileState::READABLE).unwrap_or_default();
@@ -972,9 +1271,9 @@ struct MessageRecvHeader {
     dst: SocketAddrV4,
 }
 
-/// A buffer of UDP messages and message headers.
+/// A buffer of datagram messages and message headers.
 #[derive(Debug)]
-struct MessageBuffer<Hdr> {
+pub(super) struct MessageBuffer<Hdr> {
     /// The message payloads and headers.
     // use a `LinkedList` so that socket buffers can shrink when they're empty (as opposed to
     // `VecDeque`)
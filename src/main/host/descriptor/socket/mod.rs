@@ -8,17 +8,20 @@ use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use unix::UnixSocket;
 
 use crate::cshadow as c;
-use crate::host::descriptor::{FileMode, FileState, FileStatus, OpenFile, SyscallResult};
+use crate::host::descriptor::{
+    CompatFile, FileMode, FileState, FileStatus, OpenFile, SyscallResult,
+};
 use crate::host::memory_manager::MemoryManager;
 use crate::host::network::namespace::NetworkNamespace;
 use crate::host::syscall::io::IoVec;
-use crate::host::syscall_types::{ForeignArrayPtr, SyscallError};
+use crate::host::syscall_types::SyscallError;
 use crate::utility::callback_queue::CallbackQueue;
 use crate::utility::sockaddr::SockaddrStorage;
 use crate::utility::HostTreePointer;
 
 pub mod abstract_unix_ns;
 pub mod inet;
+pub mod pathname_unix_ns;
 pub mod unix;
 
 bitflags::bitflags! {
@@ -210,12 +213,6 @@ impl SocketRef<'_> {
     enum_passthrough!(self, (), Unix, Inet;
         pub fn address_family(&self) -> nix::sys::socket::AddressFamily
     );
-
-    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager), Unix, Inet;
-        pub fn getsockopt(&self, level: libc::c_int, optname: libc::c_int, optval_ptr: ForeignPtr<()>,
-                          optlen: libc::socklen_t, memory_manager: &mut MemoryManager)
-        -> Result<libc::socklen_t, SyscallError>
-    );
 }
 
 // file functions
@@ -283,9 +280,10 @@ impl SocketRefMut<'_> {
         pub fn address_family(&self) -> nix::sys::socket::AddressFamily
     );
 
-    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager), Unix, Inet;
-        pub fn getsockopt(&self, level: libc::c_int, optname: libc::c_int, optval_ptr: ForeignPtr<()>,
-                          optlen: libc::socklen_t, memory_manager: &mut MemoryManager)
+    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager, cb_queue), Unix, Inet;
+        pub fn getsockopt(&mut self, level: libc::c_int, optname: libc::c_int, optval_ptr: ForeignPtr<()>,
+                          optlen: libc::socklen_t, memory_manager: &mut MemoryManager,
+                          cb_queue: &mut CallbackQueue)
         -> Result<libc::socklen_t, SyscallError>
     );
 
@@ -345,8 +343,8 @@ pub struct SendmsgArgs<'a> {
     pub addr: Option<SockaddrStorage>,
     /// [`IoVec`] buffers in plugin memory containing the message data.
     pub iovs: &'a [IoVec],
-    /// Buffer in plugin memory containg message control data.
-    pub control_ptr: ForeignArrayPtr<u8>,
+    /// Control data parsed from the message. Only unix sockets currently use this.
+    pub control: ControlData,
     /// Send flags.
     pub flags: libc::c_int,
 }
@@ -355,8 +353,6 @@ pub struct SendmsgArgs<'a> {
 pub struct RecvmsgArgs<'a> {
     /// [`IoVec`] buffers in plugin memory to store the message data.
     pub iovs: &'a [IoVec],
-    /// Buffer in plugin memory to store the message control data.
-    pub control_ptr: ForeignArrayPtr<u8>,
     /// Recv flags.
     pub flags: libc::c_int,
 }
@@ -370,6 +366,21 @@ pub struct RecvmsgReturn {
     pub addr: Option<SockaddrStorage>,
     /// Message flags.
     pub msg_flags: libc::c_int,
-    /// The number of control data bytes read.
-    pub control_len: libc::size_t,
+    /// Control data received with the message.
+    pub control: ControlData,
+}
+
+/// Control data (also called "ancillary data") sent or received with a message. See `cmsg(3)`.
+#[derive(Default)]
+pub struct ControlData {
+    /// Files passed with `SCM_RIGHTS`.
+    pub rights: Vec<CompatFile>,
+    /// Process credentials passed with `SCM_CREDENTIALS`.
+    pub credentials: Option<libc::ucred>,
+}
+
+impl ControlData {
+    pub fn is_empty(&self) -> bool {
+        self.rights.is_empty() && self.credentials.is_none()
+    }
 }
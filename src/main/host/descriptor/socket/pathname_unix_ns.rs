@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::ffi::{CStr, OsStr};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+
+use atomic_refcell::AtomicRefCell;
+use linux_api::errno::Errno;
+use nix::sys::stat::{Mode, SFlag};
+
+use crate::core::worker::Worker;
+use crate::host::descriptor::socket::unix::{UnixSocket, UnixSocketType};
+use crate::host::descriptor::FileState;
+use crate::host::descriptor::{StateEventSource, StateListenerFilter};
+use crate::utility::callback_queue::Handle;
+
+struct NamespaceEntry {
+    /// The type of the bound socket.
+    sock_type: UnixSocketType,
+    /// The bound socket.
+    socket: Weak<AtomicRefCell<UnixSocket>>,
+    /// The event listener handle, which removes the listener when dropped.
+    _handle: Handle<(FileState, FileState)>,
+}
+
+/// A per-host namespace for unix sockets bound to filesystem paths. Unlike abstract names,
+/// pathnames are backed by a real socket file so that the managed process can `stat()` and
+/// `unlink()` it. Sockets are keyed by the canonicalized path that the managed process sees, but
+/// so that hosts don't share socket files with each other or with the real system, the socket file
+/// is always created in the host's data directory. Paths inside the data directory (such as
+/// relative paths, since managed processes run in their host's data directory) are used as-is, and
+/// other paths are mirrored inside the data directory (for example `/tmp/server.sock` is backed by
+/// `<data dir>/tmp/server.sock`).
+pub struct PathnameUnixNamespace {
+    address_map: HashMap<PathBuf, NamespaceEntry>,
+    /// The canonicalized path of the host's data directory.
+    data_dir: PathBuf,
+}
+
+impl PathnameUnixNamespace {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            address_map: HashMap::new(),
+            data_dir: std::fs::canonicalize(data_dir).unwrap(),
+        }
+    }
+
+    /// The path of the file that backs the socket path `path`, which must be canonicalized.
+    fn socket_file_path(&self, path: &Path) -> PathBuf {
+        if path.starts_with(&self.data_dir) {
+            return path.to_path_buf();
+        }
+
+        // the path is absolute, so strip the root to join it with the data directory
+        self.data_dir.join(path.strip_prefix("/").unwrap())
+    }
+
+    /// If the absolute path `path` is outside of the host's data directory and there's a socket
+    /// file backing it, returns the path of the socket file. Syscall handlers that operate on
+    /// files by path (such as `stat()` and `unlink()`) should use this file instead.
+    pub fn mirrored_socket_file(&self, path: &CStr) -> Option<PathBuf> {
+        let path = canonicalize_socket_path(Path::new(OsStr::from_bytes(path.to_bytes()))).ok()?;
+        if path.starts_with(&self.data_dir) {
+            return None;
+        }
+
+        let file = self.socket_file_path(&path);
+        let metadata = std::fs::symlink_metadata(&file).ok()?;
+        metadata.file_type().is_socket().then_some(file)
+    }
+
+    /// Look up the socket bound to `path`. Like Linux, this returns `ENOENT` if the socket file
+    /// doesn't exist, `ECONNREFUSED` if no socket is bound to the file, and `EPROTOTYPE` if the
+    /// bound socket has a different socket type.
+    pub fn lookup(
+        &self,
+        sock_type: UnixSocketType,
+        path: &CStr,
+    ) -> Result<Arc<AtomicRefCell<UnixSocket>>, Errno> {
+        let path = canonicalize_socket_path(&absolute_path(path)?)?;
+
+        // follows symlinks, and fails if the file doesn't exist
+        let metadata = std::fs::metadata(self.socket_file_path(&path)).map_err(io_to_errno)?;
+
+        if !metadata.file_type().is_socket() {
+            return Err(Errno::ECONNREFUSED);
+        }
+
+        let Some(entry) = self.address_map.get(&path) else {
+            return Err(Errno::ECONNREFUSED);
+        };
+
+        if entry.sock_type != sock_type {
+            return Err(Errno::EPROTOTYPE);
+        }
+
+        // the unwrap() will panic if the socket was dropped without being closed; see
+        // `AbstractUnixNamespace::lookup()`
+        Ok(entry.socket.upgrade().unwrap())
+    }
+
+    /// Create a socket file for `path` and bind the socket to it. Returns `EADDRINUSE` if the file
+    /// already exists.
+    pub fn bind(
+        ns_arc: &Arc<AtomicRefCell<Self>>,
+        sock_type: UnixSocketType,
+        path: &CStr,
+        socket: &Arc<AtomicRefCell<UnixSocket>>,
+        socket_event_source: &mut StateEventSource,
+    ) -> Result<(), Errno> {
+        let path = canonicalize_socket_path(&absolute_path(path)?)?;
+
+        let mut ns = ns_arc.borrow_mut();
+
+        let file = ns.socket_file_path(&path);
+        if file != path {
+            // the managed process sees the real file, so it's in use even if it's not a socket
+            if std::fs::symlink_metadata(&path).is_ok() {
+                return Err(Errno::EADDRINUSE);
+            }
+
+            // the mirrored directory may not exist yet
+            std::fs::create_dir_all(file.parent().unwrap()).map_err(io_to_errno)?;
+        }
+
+        // create the socket file; shadow creates the file, so shadow's umask is applied rather than
+        // the managed process' umask
+        match nix::sys::stat::mknod(&file, SFlag::S_IFSOCK, Mode::from_bits_truncate(0o777), 0) {
+            Ok(()) => {}
+            Err(nix::errno::Errno::EEXIST) => return Err(Errno::EADDRINUSE),
+            Err(e) => return Err(Errno::try_from(e as i32).unwrap()),
+        }
+
+        let path_copy = path.clone();
+        let socket_weak = Arc::downgrade(socket);
+
+        // when the socket closes, remove this entry from the namespace (but not the socket file)
+        let ns_weak = Arc::downgrade(ns_arc);
+        let handle = socket_event_source.add_listener(
+            FileState::CLOSED,
+            StateListenerFilter::OffToOn,
+            move |state, _changed, _cb_queue| {
+                assert!(state.contains(FileState::CLOSED));
+                if let Some(ns) = ns_weak.upgrade() {
+                    ns.borrow_mut().unbind(&path_copy, &socket_weak);
+                }
+            },
+        );
+
+        // if there's an existing entry, its socket file was unlinked and the old socket is no
+        // longer reachable, so we can replace it
+        ns.address_map.insert(
+            path,
+            NamespaceEntry {
+                sock_type,
+                socket: Arc::downgrade(socket),
+                _handle: handle,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Remove the entry for `path` if it's still bound to `socket`.
+    fn unbind(&mut self, path: &Path, socket: &Weak<AtomicRefCell<UnixSocket>>) {
+        if let std::collections::hash_map::Entry::Occupied(entry) =
+            self.address_map.entry(path.to_path_buf())
+        {
+            if entry.get().socket.ptr_eq(socket) {
+                entry.remove();
+            }
+        }
+    }
+}
+
+/// Get the absolute path of `path`, resolving relative paths from the working directory of the
+/// active process.
+fn absolute_path(path: &CStr) -> Result<PathBuf, Errno> {
+    let path = Path::new(OsStr::from_bytes(path.to_bytes()));
+
+    if path.as_os_str().is_empty() {
+        return Err(Errno::ENOENT);
+    }
+
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+
+    let Some(pid) = Worker::active_process_native_pid() else {
+        log::warn!("No active process to resolve the relative unix socket path {path:?}");
+        return Err(Errno::ENOENT);
+    };
+
+    let cwd = std::fs::read_link(format!("/proc/{pid}/cwd")).map_err(io_to_errno)?;
+
+    Ok(cwd.join(path))
+}
+
+/// Canonicalize the absolute path `path` of a socket file. If the file doesn't exist (or is only
+/// backed by a mirrored socket file), only its parent directory is canonicalized.
+fn canonicalize_socket_path(path: &Path) -> Result<PathBuf, Errno> {
+    // follows symlinks, and fails if the file doesn't exist
+    if let Ok(path) = std::fs::canonicalize(path) {
+        return Ok(path);
+    }
+
+    let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+        // the path is a directory such as "/" or ".."
+        return Err(Errno::EADDRINUSE);
+    };
+
+    Ok(std::fs::canonicalize(parent)
+        .map_err(io_to_errno)?
+        .join(file_name))
+}
+
+fn io_to_errno(e: std::io::Error) -> Errno {
+    e.raw_os_error()
+        .and_then(|e| Errno::try_from(e).ok())
+        .unwrap_or(Errno::EINVAL)
+}
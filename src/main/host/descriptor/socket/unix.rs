@@ -14,14 +14,17 @@ use crate::host::descriptor::shared_buf::{
     BufferHandle, BufferState, ReaderHandle, SharedBuf, WriterHandle,
 };
 use crate::host::descriptor::socket::abstract_unix_ns::AbstractUnixNamespace;
-use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, Socket};
+use crate::host::descriptor::socket::pathname_unix_ns::PathnameUnixNamespace;
+use crate::host::descriptor::socket::{
+    ControlData, RecvmsgArgs, RecvmsgReturn, SendmsgArgs, Socket,
+};
 use crate::host::descriptor::{
     File, FileMode, FileState, FileStatus, OpenFile, StateEventSource, StateListenerFilter,
     SyscallResult,
 };
 use crate::host::memory_manager::MemoryManager;
 use crate::host::network::namespace::NetworkNamespace;
-use crate::host::syscall::io::{IoVec, IoVecReader, IoVecWriter};
+use crate::host::syscall::io::{write_partial, IoVec, IoVecReader, IoVecWriter};
 use crate::host::syscall_types::SyscallError;
 use crate::utility::callback_queue::{CallbackQueue, Handle};
 use crate::utility::sockaddr::{SockaddrStorage, SockaddrUnix};
@@ -43,6 +46,8 @@ impl UnixSocket {
         status: FileStatus,
         socket_type: UnixSocketType,
         namespace: &Arc<AtomicRefCell<AbstractUnixNamespace>>,
+        pathname_namespace: &Arc<AtomicRefCell<PathnameUnixNamespace>>,
+        credentials: libc::ucred,
     ) -> Arc<AtomicRefCell<Self>> {
         Arc::new_cyclic(|weak| {
             // each socket tracks its own send limit, and we let the receiver have an unlimited recv
@@ -59,6 +64,13 @@ impl UnixSocket {
                 status,
                 socket_type,
                 namespace: Arc::clone(namespace),
+                pathname_namespace: Arc::clone(pathname_namespace),
+                credentials,
+                peer_credentials: None,
+                pass_credentials: false,
+                recv_control: VecDeque::new(),
+                recv_written: 0,
+                recv_read: 0,
                 has_open_file: false,
             };
 
@@ -253,37 +265,111 @@ impl UnixSocket {
     }
 
     pub fn getsockopt(
-        &self,
-        _level: libc::c_int,
-        _optname: libc::c_int,
-        _optval_ptr: ForeignPtr<()>,
-        _optlen: libc::socklen_t,
-        _memory_manager: &mut MemoryManager,
+        &mut self,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        memory_manager: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
     ) -> Result<libc::socklen_t, SyscallError> {
-        log::warn!("getsockopt() syscall not yet supported for unix sockets; Returning ENOSYS");
-        Err(Errno::ENOSYS.into())
+        match (level, optname) {
+            (libc::SOL_SOCKET, libc::SO_PEERCRED) => {
+                // linux returns these values if the socket has no peer credentials
+                let credentials = self.common.peer_credentials.unwrap_or(libc::ucred {
+                    pid: 0,
+                    uid: u32::MAX,
+                    gid: u32::MAX,
+                });
+
+                let optval_ptr = optval_ptr.cast::<libc::ucred>();
+                let bytes_written =
+                    write_partial(memory_manager, &credentials, optval_ptr, optlen as usize)?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            (libc::SOL_SOCKET, libc::SO_PASSCRED) => {
+                let pass_credentials = self.common.pass_credentials as libc::c_int;
+
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let bytes_written = write_partial(
+                    memory_manager,
+                    &pass_credentials,
+                    optval_ptr,
+                    optlen as usize,
+                )?;
+
+                Ok(bytes_written as libc::socklen_t)
+            }
+            _ => {
+                log::warn!(
+                    "getsockopt() option {level}:{optname} not yet supported for unix sockets; \
+                    Returning ENOSYS"
+                );
+                Err(Errno::ENOSYS.into())
+            }
+        }
     }
 
     pub fn setsockopt(
         &mut self,
-        _level: libc::c_int,
-        _optname: libc::c_int,
-        _optval_ptr: ForeignPtr<()>,
-        _optlen: libc::socklen_t,
-        _memory_manager: &MemoryManager,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        memory_manager: &MemoryManager,
     ) -> Result<(), SyscallError> {
-        log::warn!("setsockopt() syscall not yet supported for unix sockets; Returning ENOSYS");
-        Err(Errno::ENOSYS.into())
+        match (level, optname) {
+            (libc::SOL_SOCKET, libc::SO_PASSCRED) => {
+                type OptType = libc::c_int;
+
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let val = memory_manager.read(optval_ptr)?;
+
+                self.common.pass_credentials = val != 0;
+
+                Ok(())
+            }
+            _ => {
+                log::warn!(
+                    "setsockopt() option {level}:{optname} not yet supported for unix sockets; \
+                    Returning ENOSYS"
+                );
+                Err(Errno::ENOSYS.into())
+            }
+        }
     }
 
     pub fn pair(
         status: FileStatus,
         socket_type: UnixSocketType,
         namespace: &Arc<AtomicRefCell<AbstractUnixNamespace>>,
+        pathname_namespace: &Arc<AtomicRefCell<PathnameUnixNamespace>>,
+        credentials: libc::ucred,
         cb_queue: &mut CallbackQueue,
     ) -> (Arc<AtomicRefCell<Self>>, Arc<AtomicRefCell<Self>>) {
-        let socket_1 = UnixSocket::new(status, socket_type, namespace);
-        let socket_2 = UnixSocket::new(status, socket_type, namespace);
+        let socket_1 = UnixSocket::new(
+            status,
+            socket_type,
+            namespace,
+            pathname_namespace,
+            credentials,
+        );
+        let socket_2 = UnixSocket::new(
+            status,
+            socket_type,
+            namespace,
+            pathname_namespace,
+            credentials,
+        );
+
+        // both sockets were created by the same process
+        socket_1.borrow_mut().common.peer_credentials = Some(credentials);
+        socket_2.borrow_mut().common.peer_credentials = Some(credentials);
 
         {
             let socket_1_ref = &mut *socket_1.borrow_mut();
@@ -1088,11 +1174,7 @@ impl Protocol for ConnOrientedInitial {
         };
 
         // look up the server socket
-        let server = match lookup_address(
-            &common.namespace.borrow(),
-            common.socket_type,
-            &addr.as_ref(),
-        ) {
+        let server = match lookup_address(common, &addr.as_ref()) {
             Ok(x) => x,
             Err(e) => return (self.into(), Err(e.into())),
         };
@@ -1130,6 +1212,10 @@ impl Protocol for ConnOrientedInitial {
             }
         };
 
+        // each socket's peer credentials are the credentials of the other socket
+        common.peer_credentials = Some(server_mut.common.credentials);
+        peer.borrow_mut().common.peer_credentials = Some(common.credentials);
+
         // our send buffer will be the peer's receive buffer
         let send_buffer = Arc::clone(peer.borrow().recv_buffer());
 
@@ -1335,6 +1421,8 @@ impl Protocol for ConnOrientedListening {
             common.status,
             common.socket_type,
             &common.namespace,
+            &common.pathname_namespace,
+            common.credentials,
         );
 
         let child_recv_buffer = Arc::clone(&child_socket.borrow_mut().common.recv_buffer);
@@ -1459,13 +1547,16 @@ impl Protocol for ConnOrientedConnected {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<libc::ssize_t, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for sendmsg()");
-            return Err(Errno::EINVAL.into());
-        }
-
         let recv_socket = common.resolve_destination(Some(&self.peer), args.addr)?;
-        let rv = common.sendmsg(socket, args.iovs, args.flags, &recv_socket, mem, cb_queue)?;
+        let rv = common.sendmsg(
+            socket,
+            args.iovs,
+            args.control,
+            args.flags,
+            &recv_socket,
+            mem,
+            cb_queue,
+        )?;
 
         self.refresh_file_state(common, cb_queue);
 
@@ -1480,12 +1571,7 @@ impl Protocol for ConnOrientedConnected {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<RecvmsgReturn, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for recvmsg()");
-            return Err(Errno::EINVAL.into());
-        }
-
-        let (rv, num_removed_from_buf, msg_flags) =
+        let (rv, num_removed_from_buf, msg_flags, control) =
             common.recvmsg(socket, args.iovs, args.flags, mem, cb_queue)?;
         let num_removed_from_buf = u64::try_from(num_removed_from_buf).unwrap();
 
@@ -1504,7 +1590,7 @@ impl Protocol for ConnOrientedConnected {
             return_val: rv.try_into().unwrap(),
             addr: self.peer_addr.map(Into::into),
             msg_flags,
-            control_len: 0,
+            control,
         })
     }
 
@@ -1649,13 +1735,16 @@ impl Protocol for ConnLessInitial {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<libc::ssize_t, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for sendmsg()");
-            return Err(Errno::EINVAL.into());
-        }
-
         let recv_socket = common.resolve_destination(self.peer.as_ref(), args.addr)?;
-        let rv = common.sendmsg(socket, args.iovs, args.flags, &recv_socket, mem, cb_queue)?;
+        let rv = common.sendmsg(
+            socket,
+            args.iovs,
+            args.control,
+            args.flags,
+            &recv_socket,
+            mem,
+            cb_queue,
+        )?;
 
         let byte_data = ByteData {
             from_socket: self.this_socket.upgrade().unwrap(),
@@ -1686,12 +1775,7 @@ impl Protocol for ConnLessInitial {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<RecvmsgReturn, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for recvmsg()");
-            return Err(Errno::EINVAL.into());
-        }
-
-        let (rv, num_removed_from_buf, msg_flags) =
+        let (rv, num_removed_from_buf, msg_flags, control) =
             common.recvmsg(socket, args.iovs, args.flags, mem, cb_queue)?;
         let num_removed_from_buf = u64::try_from(num_removed_from_buf).unwrap();
 
@@ -1712,7 +1796,7 @@ impl Protocol for ConnLessInitial {
             return_val: rv.try_into().unwrap(),
             addr: byte_data.from_addr.map(Into::into),
             msg_flags,
-            control_len: 0,
+            control,
         })
     }
 
@@ -1749,7 +1833,7 @@ impl Protocol for ConnLessInitial {
         };
 
         // find the socket bound at the address
-        let peer = match lookup_address(&common.namespace.borrow(), common.socket_type, &addr) {
+        let peer = match lookup_address(common, &addr) {
             Ok(x) => x,
             Err(e) => return (self.into(), Err(e.into())),
         };
@@ -1836,6 +1920,22 @@ struct UnixSocketCommon {
     status: FileStatus,
     socket_type: UnixSocketType,
     namespace: Arc<AtomicRefCell<AbstractUnixNamespace>>,
+    pathname_namespace: Arc<AtomicRefCell<PathnameUnixNamespace>>,
+    /// Credentials of the process that created the socket.
+    credentials: libc::ucred,
+    /// Credentials of the peer socket, returned by `SO_PEERCRED`.
+    peer_credentials: Option<libc::ucred>,
+    /// Whether `SO_PASSCRED` is enabled.
+    pass_credentials: bool,
+    /// Control data that was sent with data in the receive buffer, and that will be received along
+    /// with that data.
+    recv_control: VecDeque<QueuedControl>,
+    /// The number of bytes (for stream sockets) or packets (for message-based sockets) that have
+    /// been written to the receive buffer.
+    recv_written: u64,
+    /// The number of bytes (for stream sockets) or packets (for message-based sockets) that have
+    /// been read from the receive buffer.
+    recv_read: u64,
     // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
     // this file
     has_open_file: bool,
@@ -1861,6 +1961,14 @@ impl UnixSocketCommon {
             debug_panic!("When closing a unix socket, the CLOSED flag was not set");
         }
 
+        // Drop any files that were sent to this socket but never received. Closing these files may
+        // need to borrow other sockets (including the peer socket), so defer this until we're done
+        // processing the current socket.
+        let recv_control = std::mem::take(&mut self.recv_control);
+        if !recv_control.is_empty() {
+            cb_queue.add(move |_cb_queue| drop(recv_control));
+        }
+
         Ok(())
     }
 
@@ -1907,9 +2015,19 @@ impl UnixSocketCommon {
                 Ok(ref name) => SockaddrUnix::new_abstract(name).unwrap(),
                 Err(_) => return Err(Errno::EADDRINUSE.into()),
             }
+        } else if let Some(path) = addr.as_path() {
+            // if given a pathname address
+            let namespace = Arc::clone(&self.pathname_namespace);
+            PathnameUnixNamespace::bind(
+                &namespace,
+                self.socket_type,
+                &path,
+                socket,
+                &mut self.event_source,
+            )?;
+            addr.into_owned()
         } else {
-            log::warn!("Only abstract names are currently supported for unix sockets");
-            return Err(Errno::ENOTSUP.into());
+            return Err(Errno::EINVAL.into());
         };
 
         Ok(bound_addr)
@@ -1952,8 +2070,7 @@ impl UnixSocketCommon {
             Some(x) => Arc::clone(x),
             None => {
                 // look up the socket from the address name
-                let recv_socket =
-                    lookup_address(&self.namespace.borrow(), self.socket_type, &addr.unwrap())?;
+                let recv_socket = lookup_address(self, &addr.unwrap())?;
                 // store an Arc of the recv buffer
                 Arc::clone(&recv_socket)
             }
@@ -1962,10 +2079,12 @@ impl UnixSocketCommon {
         Ok(peer)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn sendmsg(
         &mut self,
         socket: &Arc<AtomicRefCell<UnixSocket>>,
         iovs: &[IoVec],
+        mut control: ControlData,
         flags: libc::c_int,
         peer: &Arc<AtomicRefCell<UnixSocket>>,
         mem: &mut MemoryManager,
@@ -1996,8 +2115,9 @@ impl UnixSocketCommon {
 
         // run in a closure so that an early return doesn't return from the syscall handler
         let result = (|| {
-            let peer_ref = peer.borrow();
-            let mut send_buffer = peer_ref.recv_buffer().borrow_mut();
+            let mut peer_ref = peer.borrow_mut();
+            let peer_common = &mut peer_ref.common;
+            let mut send_buffer = peer_common.recv_buffer.borrow_mut();
 
             // if the buffer has no readers, the destination socket is closed
             if send_buffer.num_readers() == 0 {
@@ -2062,6 +2182,16 @@ impl UnixSocketCommon {
             // if we successfully sent bytes, update the sent count
             self.sent_len += u64::try_from(num_copied).unwrap();
 
+            drop(send_buffer);
+
+            // the receiver only receives credentials if it has enabled `SO_PASSCRED`, and if we
+            // weren't given credentials to send, we send our own
+            control.credentials = peer_common
+                .pass_credentials
+                .then(|| control.credentials.unwrap_or(self.credentials));
+
+            peer_common.queue_recv_control(num_copied.try_into().unwrap(), control);
+
             Ok(num_copied)
         })();
 
@@ -2086,7 +2216,7 @@ impl UnixSocketCommon {
         flags: libc::c_int,
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
-    ) -> Result<(usize, usize, libc::c_int), SyscallError> {
+    ) -> Result<(usize, usize, libc::c_int, ControlData), SyscallError> {
         let supported_flags = MsgFlags::MSG_DONTWAIT | MsgFlags::MSG_TRUNC;
 
         // if there's a flag we don't support, it's probably best to raise an error rather than do
@@ -2119,12 +2249,50 @@ impl UnixSocketCommon {
                 return Err(Errno::EWOULDBLOCK);
             }
 
+            let had_data = recv_buffer.has_data();
+
+            // Control data is received with the first read that includes any of the data it was
+            // sent with. Like linux, a read on a stream socket stops at the end of data that was
+            // sent with files, and won't combine data that was sent with different credentials.
+            let max_len = match (self.socket_type, self.recv_control.front()) {
+                (UnixSocketType::Stream, Some(x))
+                    if x.start == self.recv_read || !x.control.rights.is_empty() =>
+                {
+                    Some(x.start + x.len - self.recv_read)
+                }
+                (UnixSocketType::Stream, Some(x)) => Some(x.start - self.recv_read),
+                _ => None,
+            };
+
+            let limited_iovs;
+            let iovs = match max_len {
+                Some(max_len) => {
+                    limited_iovs = truncate_iovs(iovs, max_len.try_into().unwrap());
+                    &limited_iovs[..]
+                }
+                None => iovs,
+            };
+
             let writer = IoVecWriter::new(iovs, mem);
 
             let (num_copied, num_removed_from_buf) = recv_buffer
                 .read(writer, cb_queue)
                 .map_err(|e| Errno::try_from(e).unwrap())?;
 
+            let num_read = match self.socket_type {
+                UnixSocketType::Stream => u64::try_from(num_removed_from_buf).unwrap(),
+                // a read from a message-based socket always removes one packet if there was one
+                UnixSocketType::Dgram | UnixSocketType::SeqPacket => u64::from(had_data),
+            };
+            self.recv_read += num_read;
+
+            let control = match self.recv_control.front() {
+                Some(x) if x.start < self.recv_read => {
+                    self.recv_control.pop_front().unwrap().control
+                }
+                _ => ControlData::default(),
+            };
+
             let mut msg_flags = 0;
 
             if flags.contains(MsgFlags::MSG_TRUNC)
@@ -2136,11 +2304,16 @@ impl UnixSocketCommon {
 
                 // we're a message-based socket and MSG_TRUNC is set, so return the total size of
                 // the message, not the number of bytes we read
-                Ok((num_removed_from_buf, num_removed_from_buf, msg_flags))
+                Ok((
+                    num_removed_from_buf,
+                    num_removed_from_buf,
+                    msg_flags,
+                    control,
+                ))
             } else {
                 // We're a stream-based socket. Unlike TCP sockets, unix stream sockets ignore the
                 // MSG_TRUNC flag.
-                Ok((num_copied, num_removed_from_buf, msg_flags))
+                Ok((num_copied, num_removed_from_buf, msg_flags, control))
             }
         })();
 
@@ -2158,6 +2331,43 @@ impl UnixSocketCommon {
         Ok(result?)
     }
 
+    /// Queue control data that was sent with `len` bytes (for stream sockets) or a single packet
+    /// (for message-based sockets) that were just written to the receive buffer.
+    fn queue_recv_control(&mut self, len: u64, control: ControlData) {
+        let start = self.recv_written;
+        let len = match self.socket_type {
+            UnixSocketType::Stream => len,
+            UnixSocketType::Dgram | UnixSocketType::SeqPacket => 1,
+        };
+        self.recv_written += len;
+
+        // linux drops control data sent with an empty write on a stream socket
+        if control.is_empty() || len == 0 {
+            return;
+        }
+
+        // linux allows a single read to return data from multiple writes that were sent with the
+        // same credentials, as long as no files were sent with them
+        if let Some(last) = self.recv_control.back_mut() {
+            let creds = |x: &ControlData| x.credentials.map(|c| (c.pid, c.uid, c.gid));
+            if self.socket_type == UnixSocketType::Stream
+                && last.start + last.len == start
+                && last.control.rights.is_empty()
+                && control.rights.is_empty()
+                && creds(&last.control) == creds(&control)
+            {
+                last.len += len;
+                return;
+            }
+        }
+
+        self.recv_control.push_back(QueuedControl {
+            start,
+            len,
+            control,
+        });
+    }
+
     pub fn ioctl(
         &mut self,
         request: IoctlRequest,
@@ -2192,20 +2402,47 @@ impl UnixSocketCommon {
 }
 
 fn lookup_address(
-    namespace: &AbstractUnixNamespace,
-    socket_type: UnixSocketType,
+    common: &UnixSocketCommon,
     addr: &SockaddrUnix<&libc::sockaddr_un>,
 ) -> Result<Arc<AtomicRefCell<UnixSocket>>, linux_api::errno::Errno> {
-    // if an abstract address
     if let Some(name) = addr.as_abstract() {
-        // look up the socket from the address name
-        namespace
-            .lookup(socket_type, name)
+        // if an abstract address, look up the socket from the address name
+        common
+            .namespace
+            .borrow()
+            .lookup(common.socket_type, name)
             .ok_or(linux_api::errno::Errno::ECONNREFUSED)
+    } else if let Some(path) = addr.as_path() {
+        // if a pathname address, look up the socket from the socket file
+        common
+            .pathname_namespace
+            .borrow()
+            .lookup(common.socket_type, &path)
     } else {
-        log::warn!("Unix sockets with pathname addresses are not yet supported");
-        Err(linux_api::errno::Errno::ENOENT)
+        // an unnamed address
+        Err(linux_api::errno::Errno::EINVAL)
+    }
+}
+
+/// Returns the prefix of `iovs` containing at most `max_len` bytes.
+fn truncate_iovs(iovs: &[IoVec], max_len: usize) -> Vec<IoVec> {
+    let mut remaining = max_len;
+    let mut truncated = Vec::new();
+
+    for iov in iovs {
+        if remaining == 0 {
+            break;
+        }
+
+        let len = std::cmp::min(iov.len, remaining);
+        truncated.push(IoVec {
+            base: iov.base,
+            len,
+        });
+        remaining -= len;
     }
+
+    truncated
 }
 
 fn backlog_to_queue_size(backlog: i32) -> u32 {
@@ -2262,6 +2499,17 @@ enum IncomingConnError {
     NotSupported,
 }
 
+/// Control data that was sent to a socket, waiting for the data it was sent with to be read.
+struct QueuedControl {
+    /// The position in the receive buffer of the first byte (for stream sockets) or the packet
+    /// (for message-based sockets) that the control data was sent with. See
+    /// `UnixSocketCommon::recv_written`.
+    start: u64,
+    /// The number of bytes or packets that the control data was sent with.
+    len: u64,
+    control: ControlData,
+}
+
 struct ByteData {
     from_socket: Arc<AtomicRefCell<UnixSocket>>,
     from_addr: Option<SockaddrUnix<libc::sockaddr_un>>,
@@ -9,6 +9,7 @@
 
 #include <errno.h>
 #include <math.h>
+#include <netinet/ip.h>
 #include <netinet/tcp.h>
 #include <stdarg.h>
 #include <stdbool.h>
@@ -26,6 +27,8 @@
 #include "main/host/descriptor/descriptor.h"
 #include "main/host/descriptor/socket.h"
 #include "main/host/descriptor/tcp_cong.h"
+#include "main/host/descriptor/tcp_cong_bbr.h"
+#include "main/host/descriptor/tcp_cong_cubic.h"
 #include "main/host/descriptor/tcp_cong_reno.h"
 #include "main/host/descriptor/tcp_retransmit_tally.h"
 #include "main/host/protocol.h"
@@ -64,6 +67,7 @@ enum TCPFlags {
     TCPF_WAS_ESTABLISHED = 1 << 6,
     TCPF_CONNECT_SIGNAL_NEEDED = 1 << 7,
     TCPF_SHOULD_SEND_WR_FIN = 1 << 8,
+    TCPF_ERROR_SIGNALED = 1 << 9,
 };
 
 enum TCPError {
@@ -71,6 +75,8 @@ enum TCPError {
     TCPE_CONNECTION_RESET = 1 << 0,
     TCPE_SEND_EOF = 1 << 1,
     TCPE_RECEIVE_EOF = 1 << 2,
+    /* the keepalive timer dropped the connection; always set with TCPE_CONNECTION_RESET */
+    TCPE_CONNECTION_TIMED_OUT = 1 << 3,
 };
 
 enum TCPChildState {
@@ -115,6 +121,26 @@ struct _TCPServer {
 
 static void _tcp_logCongestionInfo(TCP* tcp);
 
+/* what's limiting the sender, for the time statistics in TCP_INFO (like linux's tcp_chrono) */
+enum TCPChrono {
+    TCP_CHRONO_UNSPEC,
+    TCP_CHRONO_BUSY,
+    TCP_CHRONO_RWND_LIMITED,
+    TCP_CHRONO_SNDBUF_LIMITED,
+    TCP_CHRONO_MAX,
+};
+
+/* the delivery state when a data packet was sent, used to take a delivery rate sample when the
+ * packet is acked */
+typedef struct _TCPDeliveryState TCPDeliveryState;
+struct _TCPDeliveryState {
+    guint64 delivered;
+    CSimulationTime deliveredTime;
+    CSimulationTime firstSentTime;
+    CSimulationTime sentTime;
+    gboolean isAppLimited;
+};
+
 struct _TCP {
     LegacySocket super;
 
@@ -175,6 +201,14 @@ struct _TCP {
         guint32 delayedACKCounter;
         /* list of selective ACKs, packets received after a missing packet */
         GList* selectiveACKs;
+        /* TCP_NODELAY: send partial segments without waiting for outstanding data to be acked */
+        gboolean noDelay;
+        /* TCP_CORK: hold partial segments until uncorked or the cork timer expires */
+        gboolean cork;
+        gboolean corkTimerIsScheduled;
+        /* the last queued data packet if it is smaller than the MSS and hasn't been sent yet, so
+         * that later writes can be coalesced into it */
+        Packet* partialPacket;
     } send;
 
     struct {
@@ -208,9 +242,82 @@ struct _TCP {
     /* congestion object for implementing different types of congestion control (aimd, reno, cubic) */
     TCPCong cong;
 
+    /* delivery rate estimation (draft-cheng-iccrg-delivery-rate-estimation) */
+    struct {
+        /* total number of packets that were acked */
+        guint64 delivered;
+        /* when 'delivered' was last updated */
+        CSimulationTime deliveredTime;
+        /* when the first packet of the current sampling flight was sent */
+        CSimulationTime firstSentTime;
+        /* the value of 'delivered' at which we're no longer application limited, or 0 */
+        guint64 appLimited;
+        /* the most recent valid sample, in bytes per second */
+        guint64 lastRate;
+        gboolean lastRateIsAppLimited;
+        /* the delivery state when each unacked packet was sent, keyed by sequence number */
+        GHashTable* sentStates;
+    } delivery;
+
+    /* keepalive probes (SO_KEEPALIVE, TCP_KEEPIDLE, TCP_KEEPINTVL, TCP_KEEPCNT) */
+    struct {
+        gboolean isEnabled;
+        /* idle time before the first probe, and time between probes, in seconds */
+        guint idleTime;
+        guint interval;
+        /* number of unanswered probes before the connection is dropped */
+        guint maxProbes;
+        /* number of probes sent since we last heard from the peer */
+        guint probesSent;
+        /* when the keepalive timer should expire, or 0 if it's stopped. timer tasks that expire at
+         * any other time were stopped or rescheduled and are ignored. */
+        CSimulationTime timerExpiration;
+    } keepalive;
+
+    /* SO_LINGER; a zero linger time aborts the connection on close */
+    struct {
+        gboolean isEnabled;
+        guint seconds;
+    } linger;
+
+    /* explicit congestion notification (rfc 3168) */
+    struct {
+        /* we request ecn in our SYN when connecting */
+        gboolean isRequested;
+        /* both ends agreed to use ecn during the handshake */
+        gboolean isEnabled;
+        /* we received at least one ecn-capable packet */
+        gboolean isSeen;
+        /* we received a congestion experienced mark, and set ECE on our packets until the peer
+         * sets CWR */
+        gboolean isEchoPending;
+        /* we reduced our window, and set CWR on the next new data packet */
+        gboolean isCWRPending;
+        /* we don't reduce the window again until the data sent before this sequence is acked */
+        guint32 recoveryPoint;
+        /* packets acked by packets with ECE set */
+        guint32 deliveredCE;
+    } ecn;
+
+    /* IP_TOS, without the ecn codepoint */
+    guint8 tos;
+
+    /* a server child socket replies to an IPv6 connection with IPv6 packets, since it doesn't
+     * have the listening socket's address family until it's accepted */
+    gboolean isIPv6;
+
+    /* pacing of outgoing data packets, if the congestion control sets a pacing rate */
+    struct {
+        /* the earliest time that the next data packet can be sent */
+        CSimulationTime nextSendTime;
+        gboolean timerIsScheduled;
+    } pacing;
+
     struct {
       gint rttSmoothed;
       gint rttVariance;
+      /* smallest rtt sample seen, or 0 if there are no samples yet */
+      gint rttMin;
     } timing;
 
     /* TODO: these should probably be stamped when the network interface sends
@@ -221,7 +328,18 @@ struct _TCP {
         CSimulationTime lastDataReceived;
         CSimulationTime lastAckReceived;
         gsize retransmitCount;
-        guint32 rtt;
+        guint64 bytesSent;
+        guint64 bytesRetransmitted;
+        guint64 bytesAcked;
+        guint64 bytesReceived;
+        guint32 segmentsReceived;
+        guint32 dataSegmentsSent;
+        guint32 dataSegmentsReceived;
+        guint32 outOfOrderReceived;
+        /* what's currently limiting the sender and since when, and the total time of each */
+        enum TCPChrono chrono;
+        CSimulationTime chronoStart;
+        CSimulationTime chronoTotals[TCP_CHRONO_MAX];
     } info;
 
     /* TCP throttles outgoing data packets if too many are in flight */
@@ -276,6 +394,7 @@ static gint _simulationTimeCompare(const CSimulationTime* value1, const CSimulat
 }
 
 static void _tcp_flush(TCP* tcp, const Host* host);
+static void _tcp_releasePartialPacket(TCP* tcp);
 
 static TCP* _tcp_fromLegacyFile(LegacyFile* descriptor) {
     utility_debugAssert(legacyfile_getType(descriptor) == DT_TCPSOCKET);
@@ -386,6 +505,42 @@ static bool _tcpserver_acceptQueueFull(TCPServer* server) {
     return server->pendingCount >= server->pendingMax;
 }
 
+static void _tcp_initCongestion(TCP* tcp, TCPCongestionType type) {
+    switch (type) {
+        case TCP_CC_RENO: {
+            tcp_cong_reno_init(tcp);
+            break;
+        }
+        case TCP_CC_CUBIC: {
+            tcp_cong_cubic_init(tcp);
+            break;
+        }
+        case TCP_CC_BBR: {
+            tcp_cong_bbr_init(tcp);
+            break;
+        }
+        default: {
+            utility_panic("Unsupported congestion control type %d", type);
+        }
+    }
+}
+
+void tcp_setCongestionType(TCP* tcp, TCPCongestionType type) {
+    MAGIC_ASSERT(tcp);
+
+    /* keep the current window so that switching algorithms doesn't restart slow start */
+    guint32 cwnd = tcp->cong.cwnd;
+    tcp->cong.hooks->tcp_cong_delete(tcp);
+    tcp->cong.pacing_rate = 0;
+    _tcp_initCongestion(tcp, type);
+    tcp->cong.cwnd = cwnd;
+}
+
+gint tcp_getMinRTT(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->timing.rttMin;
+}
+
 struct TCPCong_ *tcp_cong(TCP *tcp) {
     return &tcp->cong;
 }
@@ -538,7 +693,6 @@ static void _tcp_tuneInitialBufferSizes(TCP* tcp, const Host* host) {
             trace("set loopback send buffer size to %"G_GSIZE_FORMAT, (gsize)CONFIG_TCP_WMEM_MAX);
         }
 
-        tcp->info.rtt = G_MAXUINT32; // not sure why this is here
         return;
     }
 
@@ -648,6 +802,41 @@ void tcp_disableSendBufferAutotuning(TCP* tcp) {
     tcp->autotune.userDisabledSend = TRUE;
 }
 
+void tcp_setNoDelay(TCP* tcp, const Host* host, gboolean noDelay) {
+    MAGIC_ASSERT(tcp);
+
+    tcp->send.noDelay = noDelay;
+
+    if (noDelay && tcp->send.partialPacket != NULL) {
+        /* like linux, enabling TCP_NODELAY pushes any pending partial segment */
+        _tcp_releasePartialPacket(tcp);
+        _tcp_flush(tcp, host);
+    }
+}
+
+gboolean tcp_getNoDelay(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->send.noDelay;
+}
+
+void tcp_setCork(TCP* tcp, const Host* host, gboolean cork) {
+    MAGIC_ASSERT(tcp);
+
+    gboolean wasCorked = tcp->send.cork;
+    tcp->send.cork = cork;
+
+    if (wasCorked && !cork && tcp->send.partialPacket != NULL) {
+        /* uncorking pushes any pending partial segment, even if nagle would hold it */
+        _tcp_releasePartialPacket(tcp);
+        _tcp_flush(tcp, host);
+    }
+}
+
+gboolean tcp_getCork(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->send.cork;
+}
+
 void tcp_disableReceiveBufferAutotuning(TCP* tcp) {
     MAGIC_ASSERT(tcp);
     tcp->autotune.userDisabledReceive = TRUE;
@@ -656,6 +845,7 @@ void tcp_disableReceiveBufferAutotuning(TCP* tcp) {
 // XXX declaration
 static void _tcp_runCloseTimerExpiredTask(const Host* host, gpointer tcp, gpointer userData);
 static void _tcp_clearRetransmit(TCP* tcp, guint sequence);
+static void _tcp_resetKeepaliveTimer(TCP* tcp, const Host* host);
 
 static void _tcp_setState(TCP* tcp, const Host* host, enum TCPState state) {
     MAGIC_ASSERT(tcp);
@@ -682,6 +872,7 @@ static void _tcp_setState(TCP* tcp, const Host* host, enum TCPState state) {
             tcp->flags |= TCPF_WAS_ESTABLISHED;
             legacyfile_adjustStatus(
                 (LegacyFile*)tcp, STATUS_FILE_ACTIVE | STATUS_FILE_WRITABLE, TRUE);
+            _tcp_resetKeepaliveTimer(tcp, host);
             break;
         }
         case TCPS_CLOSING: {
@@ -690,11 +881,17 @@ static void _tcp_setState(TCP* tcp, const Host* host, enum TCPState state) {
         case TCPS_CLOSEWAIT: {
             break;
         }
+        case TCPS_FINWAIT2: {
+            /* our FIN was acked, so a lingering close can return */
+            legacyfile_adjustStatus((LegacyFile*)tcp, STATUS_SOCKET_LINGER_DONE, TRUE);
+            break;
+        }
         case TCPS_CLOSED: {
             _tcp_clearRetransmit(tcp, (guint)-1);
 
             /* user can no longer use socket */
             legacyfile_adjustStatus((LegacyFile*)tcp, STATUS_FILE_ACTIVE, FALSE);
+            legacyfile_adjustStatus((LegacyFile*)tcp, STATUS_SOCKET_LINGER_DONE, TRUE);
 
             bool disassociate = true;
 
@@ -725,17 +922,21 @@ static void _tcp_setState(TCP* tcp, const Host* host, enum TCPState state) {
 
                     /* if i was the server's last child and its waiting to close, close it */
                     if((parent->state == TCPS_CLOSED) && (g_hash_table_size(parent->server->children) <= 0)) {
-                        if (disassociate) {
-                            /* this will unbind from the network interface and free socket */
+                        in_addr_t parent_ip = 0;
+                        in_port_t parent_port = 0;
+                        if (legacysocket_getSocketName(&parent->super, &parent_ip, &parent_port)) {
+                            /* this will unbind from the network interface and free socket; the
+                             * server was associated with the wildcard peer */
                             host_disassociateInterface(
-                                host, PTCP, sock_ip, sock_port, peer_ip, peer_port);
+                                host, (uintptr_t)parent, PTCP, parent_ip, parent_port, 0, 0);
                         }
                     }
                 }
 
                 if (disassociate) {
-                    /* TODO: we should only be disassociating non-child sockets */
-                    host_disassociateInterface(host, PTCP, sock_ip, sock_port, peer_ip, peer_port);
+                    /* children were never associated, so this does nothing for them */
+                    host_disassociateInterface(
+                        host, (uintptr_t)tcp, PTCP, sock_ip, sock_port, peer_ip, peer_port);
                 }
             }
             break;
@@ -745,6 +946,8 @@ static void _tcp_setState(TCP* tcp, const Host* host, enum TCPState state) {
             break;
         }
         case TCPS_TIMEWAIT: {
+            legacyfile_adjustStatus((LegacyFile*)tcp, STATUS_SOCKET_LINGER_DONE, TRUE);
+
             /* schedule a close timer self-event to finish out the closing process */
             utility_alwaysAssert(tcp->rustSocket != NULL);
             const InetSocket* inetSocket = inetsocketweak_upgrade(tcp->rustSocket);
@@ -920,6 +1123,7 @@ static Packet* _tcp_createPacketWithoutPayload(TCP* tcp, const Host* host,
     /* create the TCP packet. the ack, window, and timestamps will be set in _tcp_flush */
     Packet* packet = packet_new(host);
     packet_setTCP(packet, flags, sourceIP, sourcePort, destinationIP, destinationPort, sequence);
+    packet_setIPv6(packet, tcp->isIPv6);
     packet_addDeliveryStatus(packet, PDS_SND_CREATED);
 
     /* update sequence number */
@@ -950,6 +1154,15 @@ static Packet* _tcp_createControlPacket(TCP* tcp, const Host* host, enum Protoco
     return _tcp_createPacketWithoutPayload(tcp, host, flags, /*isEmpty=*/true);
 }
 
+/* keepalive probes reuse the sequence number of a packet that was already acked so that the peer
+ * responds with an ACK (rfc 1122, section 4.2.3.6), but they aren't new data and are never
+ * retransmitted */
+static gboolean _tcp_isKeepaliveProbe(Packet* packet) {
+    PacketTCPHeader* header = packet_getTCPHeader(packet);
+    return header->sequence > 0 && packet_getPayloadSize(packet) == 0 &&
+           !(header->flags & (PTCP_SYN | PTCP_FIN));
+}
+
 static void _tcp_sendControlPacket(TCP* tcp, const Host* host, enum ProtocolTCPFlags flags) {
     MAGIC_ASSERT(tcp);
 
@@ -1046,6 +1259,7 @@ static void _tcp_clearRetransmitRange(TCP* tcp, guint begin, guint end) {
 
         if (packet != NULL) {
             tcp->retransmit.queueLength -= packet_getPayloadSize(packet);
+            tcp->info.bytesAcked += packet_getPayloadSize(packet);
             packet_addDeliveryStatus(packet, PDS_SND_TCP_DEQUEUE_RETRANSMIT);
             bool success = g_hash_table_remove(tcp->retransmit.queue,
                                                GINT_TO_POINTER(seq));
@@ -1142,6 +1356,10 @@ static void _tcp_updateRTTEstimate(TCP* tcp, const Host* host, CSimulationTime t
         rtt = 1;
     }
 
+    if (!tcp->timing.rttMin || rtt < tcp->timing.rttMin) {
+        tcp->timing.rttMin = rtt;
+    }
+
     /* RFC 6298 (http://tools.ietf.org/html/rfc6298) */
     if(!tcp->timing.rttSmoothed) {
         /* first RTT measurement */
@@ -1203,6 +1421,7 @@ static void _tcp_retransmitPacket(TCP* tcp, const Host* host, gint sequence) {
     _tcp_bufferPacketOut(tcp, packet);
     packet_addDeliveryStatus(packet, PDS_SND_TCP_RETRANSMITTED);
     tcp->info.retransmitCount++;
+    tcp->info.bytesRetransmitted += packet_getPayloadSize(packet);
 
     /* free the ref that we stole */
     packet_unref(packet);
@@ -1231,6 +1450,127 @@ static void _tcp_sendShutdownFin(TCP* tcp, const Host* host) {
     }
 }
 
+/* packets that were sent but haven't been acked yet */
+static guint32 _tcp_getPacketsInFlight(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+
+    if (tcp->send.highestSequence < tcp->send.unacked) {
+        return 0;
+    }
+    return tcp->send.highestSequence + 1 - tcp->send.unacked;
+}
+
+static void _tcp_recordDeliveryState(TCP* tcp, guint sequence, CSimulationTime now) {
+    MAGIC_ASSERT(tcp);
+
+    /* start a new sampling flight if nothing is in flight */
+    if (g_hash_table_size(tcp->delivery.sentStates) == 0) {
+        tcp->delivery.firstSentTime = now;
+        tcp->delivery.deliveredTime = now;
+    }
+
+    TCPDeliveryState* state = g_new0(TCPDeliveryState, 1);
+    state->delivered = tcp->delivery.delivered;
+    state->deliveredTime = tcp->delivery.deliveredTime;
+    state->firstSentTime = tcp->delivery.firstSentTime;
+    state->sentTime = now;
+    state->isAppLimited = tcp->delivery.appLimited != 0;
+
+    /* a retransmission replaces the state of the original transmission */
+    g_hash_table_insert(tcp->delivery.sentStates, GUINT_TO_POINTER(sequence), state);
+}
+
+/* Mark the packets in the half-open interval [begin, end) as delivered, and take a delivery rate
+ * sample from the most recently sent of them. Returns FALSE if none of the packets were sent. */
+static gboolean _tcp_sampleDeliveryRate(TCP* tcp, guint begin, guint end, TCPRateSample* rs) {
+    MAGIC_ASSERT(tcp);
+
+    CSimulationTime now = worker_getCurrentSimulationTime();
+    TCPDeliveryState* newest = NULL;
+    guint32 nDelivered = 0;
+
+    for (guint seq = begin; seq < end; seq++) {
+        TCPDeliveryState* state = NULL;
+        if (!g_hash_table_steal_extended(tcp->delivery.sentStates, GUINT_TO_POINTER(seq), NULL,
+                                         (gpointer*)&state)) {
+            continue;
+        }
+
+        nDelivered++;
+
+        if (newest == NULL || state->sentTime >= newest->sentTime) {
+            g_free(newest);
+            newest = state;
+        } else {
+            g_free(state);
+        }
+    }
+
+    tcp->delivery.delivered += nDelivered;
+    tcp->delivery.deliveredTime = now;
+
+    if (tcp->delivery.appLimited != 0 && tcp->delivery.delivered > tcp->delivery.appLimited) {
+        tcp->delivery.appLimited = 0;
+    }
+
+    if (newest == NULL) {
+        return FALSE;
+    }
+
+    /* the next sampling flight starts with the packet that was just acked */
+    tcp->delivery.firstSentTime = newest->sentTime;
+
+    CSimulationTime sendElapsed = newest->sentTime - newest->firstSentTime;
+    CSimulationTime ackElapsed = now - newest->deliveredTime;
+
+    *rs = (TCPRateSample){0};
+    rs->delivered = (guint32)(tcp->delivery.delivered - newest->delivered);
+    rs->prior_delivered = newest->delivered;
+    rs->interval = MAX(sendElapsed, ackElapsed);
+    rs->rtt = now - newest->sentTime;
+    rs->acked = nDelivered;
+    rs->in_flight = _tcp_getPacketsInFlight(tcp);
+    rs->is_app_limited = newest->isAppLimited;
+
+    /* an interval shorter than the min rtt would overestimate the rate (e.g. ack compression) */
+    CSimulationTime minRTT = (CSimulationTime)tcp->timing.rttMin * SIMTIME_ONE_MILLISECOND;
+    if (rs->interval > 0 && rs->interval >= minRTT) {
+        rs->delivery_rate = (guint64)rs->delivered * CONFIG_TCP_MAX_SEGMENT_SIZE *
+                            SIMTIME_ONE_SECOND / rs->interval;
+        tcp->delivery.lastRate = rs->delivery_rate;
+        tcp->delivery.lastRateIsAppLimited = rs->is_app_limited;
+    }
+
+    g_free(newest);
+    return TRUE;
+}
+
+static void _tcp_setECNFlags(TCP* tcp, Packet* packet, PacketTCPHeader* header) {
+    MAGIC_ASSERT(tcp);
+
+    /* keep echoing the congestion experienced mark until the peer says it reduced its window */
+    if (tcp->ecn.isEchoPending) {
+        header->flags |= PTCP_ECE;
+    } else {
+        header->flags &= ~PTCP_ECE;
+    }
+
+    gboolean isData = packet_getPayloadSize(packet) > 0;
+    gboolean isRetransmit = (packet_getDeliveryStatus(packet) & PDS_SND_TCP_RETRANSMITTED) != 0;
+
+    /* only new data packets are ecn-capable (rfc 3168, section 6.1.4-6.1.5) */
+    if (isData && !isRetransmit) {
+        packet_setECN(packet, PECN_ECT0);
+
+        if (tcp->ecn.isCWRPending) {
+            header->flags |= PTCP_CWR;
+            tcp->ecn.isCWRPending = FALSE;
+        }
+    } else {
+        packet_setECN(packet, PECN_NOT_ECT);
+    }
+}
+
 void tcp_networkInterfaceIsAboutToSendPacket(TCP* tcp, const Host* host, Packet* packet) {
     MAGIC_ASSERT(tcp);
 
@@ -1246,15 +1586,25 @@ void tcp_networkInterfaceIsAboutToSendPacket(TCP* tcp, const Host* host, Packet*
 
     PacketTCPHeader* header = packet_getTCPHeader(packet);
 
+    packet_setTOS(packet, tcp->tos);
+
+    /* SYN packets keep the ecn flags that negotiate ecn */
+    if (tcp->ecn.isEnabled && !(header->flags & PTCP_SYN)) {
+        _tcp_setECNFlags(tcp, packet, header);
+    }
+
     if(header->flags & PTCP_ACK) {
         /* we are sending an ACK already, so we may not need any delayed ACK */
         tcp->send.delayedACKCounter = 0;
     }
 
-    if(header->sequence > 0) {
+    if(header->sequence > 0 && !_tcp_isKeepaliveProbe(packet)) {
         /* store in retransmission buffer */
         _tcp_addRetransmit(tcp, packet);
 
+        /* remember the delivery state for a rate sample when it's acked */
+        _tcp_recordDeliveryState(tcp, header->sequence, now);
+
         /* start retransmit timer if its not running (rfc 6298, section 5.1) */
         if(!tcp->retransmit.desiredTimerExpiration) {
             _tcp_setRetransmitTimer(tcp, host, now);
@@ -1262,6 +1612,362 @@ void tcp_networkInterfaceIsAboutToSendPacket(TCP* tcp, const Host* host, Packet*
     }
 }
 
+/* stop coalescing data into the partial packet, and allow it to be sent */
+static void _tcp_releasePartialPacket(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+
+    if (tcp->send.partialPacket != NULL) {
+        packet_unref(tcp->send.partialPacket);
+        tcp->send.partialPacket = NULL;
+    }
+}
+
+static void _tcp_runCorkTimerExpiredTask(const Host* host, gpointer voidInetSocket,
+                                         gpointer userData) {
+    const InetSocket* inetSocket = voidInetSocket;
+    utility_alwaysAssert(inetSocket != NULL);
+    TCP* tcp = inetsocket_asLegacyTcp(inetSocket);
+    MAGIC_ASSERT(tcp);
+
+    tcp->send.corkTimerIsScheduled = FALSE;
+
+    /* push the corked data if it's the same partial packet we scheduled the timer for. otherwise
+     * the flush will schedule a new timer for the current partial packet. */
+    guint sequence = GPOINTER_TO_UINT(userData);
+    if (tcp->send.partialPacket != NULL &&
+        packet_getTCPHeader(tcp->send.partialPacket)->sequence == sequence) {
+        trace("%s <-> %s: cork timer expired, pushing partial segment %u",
+              tcp->super.boundString, tcp->super.peerString, sequence);
+        _tcp_releasePartialPacket(tcp);
+    }
+
+    _tcp_flush(tcp, host);
+}
+
+static void _tcp_scheduleCorkTimer(TCP* tcp, const Host* host) {
+    MAGIC_ASSERT(tcp);
+
+    if (tcp->send.corkTimerIsScheduled) {
+        return;
+    }
+
+    guint sequence = packet_getTCPHeader(tcp->send.partialPacket)->sequence;
+
+    utility_alwaysAssert(tcp->rustSocket != NULL);
+    const InetSocket* inetSocket = inetsocketweak_upgrade(tcp->rustSocket);
+    utility_alwaysAssert(inetSocket != NULL);
+
+    TaskRef* corkTask = taskref_new_bound(host_getID(host), _tcp_runCorkTimerExpiredTask,
+                                          (void*)inetSocket, GUINT_TO_POINTER(sequence),
+                                          inetsocket_dropVoid, NULL);
+    host_scheduleTaskWithDelay(
+        host, corkTask, CONFIG_TCP_CORK_TIMEOUT * (CSimulationTime)SIMTIME_ONE_MILLISECOND);
+    taskref_drop(corkTask);
+
+    tcp->send.corkTimerIsScheduled = TRUE;
+}
+
+/* Returns TRUE if the partial packet at the head of the send queue should be held back, either
+ * because the socket is corked or because of Nagle's algorithm (rfc 896). */
+static gboolean _tcp_shouldDelayPartialPacket(TCP* tcp, const Host* host) {
+    MAGIC_ASSERT(tcp);
+
+    /* always send everything we have when closing */
+    if ((tcp->flags & TCPF_LOCAL_CLOSED_WR) || (tcp->flags & TCPF_SHOULD_SEND_WR_FIN)) {
+        return FALSE;
+    }
+
+    if (tcp->send.cork) {
+        _tcp_scheduleCorkTimer(tcp, host);
+        return TRUE;
+    }
+
+    if (tcp->send.noDelay) {
+        return FALSE;
+    }
+
+    /* nagle: only one partial segment may be outstanding at a time */
+    return tcp->retransmit.queueLength > 0;
+}
+
+static gboolean _tcp_isKeepaliveState(TCP* tcp) {
+    return tcp->state == TCPS_ESTABLISHED || tcp->state == TCPS_CLOSEWAIT;
+}
+
+/* the time since we last heard from the peer, see keepalive_time_elapsed() in linux */
+static CSimulationTime _tcp_getKeepaliveElapsed(TCP* tcp, CSimulationTime now) {
+    CSimulationTime lastReceived = MAX(tcp->info.lastAckReceived, tcp->info.lastDataReceived);
+    return now - MIN(lastReceived, now);
+}
+
+static void _tcp_runKeepaliveTimerExpiredTask(const Host* host, gpointer voidInetSocket,
+                                              gpointer userData);
+
+static void _tcp_setKeepaliveTimer(TCP* tcp, const Host* host, CSimulationTime delay) {
+    MAGIC_ASSERT(tcp);
+
+    utility_alwaysAssert(tcp->rustSocket != NULL);
+    const InetSocket* inetSocket = inetsocketweak_upgrade(tcp->rustSocket);
+    utility_alwaysAssert(inetSocket != NULL);
+
+    tcp->keepalive.timerExpiration = worker_getCurrentSimulationTime() + delay;
+
+    TaskRef* keepaliveTask =
+        taskref_new_bound(host_getID(host), _tcp_runKeepaliveTimerExpiredTask, (void*)inetSocket,
+                          NULL, inetsocket_dropVoid, NULL);
+    host_scheduleTaskWithDelay(host, keepaliveTask, delay);
+    taskref_drop(keepaliveTask);
+}
+
+/* restart the keepalive timer with the full idle time, or stop it if keepalive is disabled */
+static void _tcp_resetKeepaliveTimer(TCP* tcp, const Host* host) {
+    MAGIC_ASSERT(tcp);
+
+    if (!tcp->keepalive.isEnabled || !_tcp_isKeepaliveState(tcp)) {
+        tcp->keepalive.timerExpiration = 0;
+        return;
+    }
+
+    _tcp_setKeepaliveTimer(
+        tcp, host, tcp->keepalive.idleTime * (CSimulationTime)SIMTIME_ONE_SECOND);
+}
+
+static void _tcp_sendKeepaliveProbe(TCP* tcp, const Host* host) {
+    MAGIC_ASSERT(tcp);
+
+    trace("%s <-> %s: sending keepalive probe %u of %u", tcp->super.boundString,
+          tcp->super.peerString, tcp->keepalive.probesSent + 1, tcp->keepalive.maxProbes);
+
+    /* linux sends the sequence number before the next one to send, so that the probe carries no
+     * new data. all of our previous packets were acked, so that's already been received. */
+    Packet* probe = _tcp_createControlPacket(tcp, host, PTCP_ACK);
+    packet_getTCPHeader(probe)->sequence = tcp->send.next - 1;
+    packet_setPriority(probe, 0);
+
+    _tcp_bufferPacketOut(tcp, probe);
+    _tcp_flush(tcp, host);
+
+    /* the output buffer holds the packet ref now */
+    packet_unref(probe);
+}
+
+/* drop the connection after too many unanswered keepalive probes, like linux's tcp_write_err() */
+static void _tcp_keepaliveTimedOut(TCP* tcp, const Host* host) {
+    MAGIC_ASSERT(tcp);
+
+    trace("%s <-> %s: no response to %u keepalive probes, dropping connection",
+          tcp->super.boundString, tcp->super.peerString, tcp->keepalive.probesSent);
+
+    /* tell the peer in case it's still there */
+    _tcp_sendControlPacket(tcp, host, PTCP_RST);
+
+    tcp->error |= TCPE_CONNECTION_RESET | TCPE_CONNECTION_TIMED_OUT;
+    tcp->flags |= TCPF_REMOTE_CLOSED;
+    tcp->keepalive.timerExpiration = 0;
+
+    _tcp_setState(tcp, host, TCPS_TIMEWAIT);
+
+    /* it will send no more user data after what we have now */
+    tcp->receive.end = tcp->receive.next;
+
+    /* signal EOF and the error to the user */
+    _tcp_flush(tcp, host);
+}
+
+static void _tcp_runKeepaliveTimerExpiredTask(const Host* host, gpointer voidInetSocket,
+                                              gpointer userData) {
+    const InetSocket* inetSocket = voidInetSocket;
+    utility_alwaysAssert(inetSocket != NULL);
+    TCP* tcp = inetsocket_asLegacyTcp(inetSocket);
+    MAGIC_ASSERT(tcp);
+
+    CSimulationTime now = worker_getCurrentSimulationTime();
+    if (tcp->keepalive.timerExpiration != now) {
+        /* the timer was stopped or rescheduled */
+        return;
+    }
+    tcp->keepalive.timerExpiration = 0;
+
+    if (!tcp->keepalive.isEnabled || !_tcp_isKeepaliveState(tcp)) {
+        return;
+    }
+
+    CSimulationTime idle = tcp->keepalive.idleTime * (CSimulationTime)SIMTIME_ONE_SECOND;
+
+    /* while data is in flight or waiting to be sent, the retransmit timer handles a dead peer */
+    if (tcp->retransmit.queueLength > 0 || tcp->throttledOutputLength > 0) {
+        _tcp_setKeepaliveTimer(tcp, host, idle);
+        return;
+    }
+
+    CSimulationTime elapsed = _tcp_getKeepaliveElapsed(tcp, now);
+    if (elapsed < idle) {
+        /* we heard from the peer since the timer was set */
+        _tcp_setKeepaliveTimer(tcp, host, idle - elapsed);
+        return;
+    }
+
+    if (tcp->keepalive.probesSent >= tcp->keepalive.maxProbes) {
+        _tcp_keepaliveTimedOut(tcp, host);
+        return;
+    }
+
+    _tcp_sendKeepaliveProbe(tcp, host);
+    tcp->keepalive.probesSent++;
+    _tcp_setKeepaliveTimer(
+        tcp, host, tcp->keepalive.interval * (CSimulationTime)SIMTIME_ONE_SECOND);
+}
+
+void tcp_setKeepAlive(TCP* tcp, const Host* host, gboolean keepAlive) {
+    MAGIC_ASSERT(tcp);
+
+    gboolean wasEnabled = tcp->keepalive.isEnabled;
+    tcp->keepalive.isEnabled = keepAlive;
+
+    if (keepAlive != wasEnabled) {
+        _tcp_resetKeepaliveTimer(tcp, host);
+    }
+}
+
+gboolean tcp_getKeepAlive(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->keepalive.isEnabled;
+}
+
+void tcp_setKeepIdle(TCP* tcp, const Host* host, guint seconds) {
+    MAGIC_ASSERT(tcp);
+
+    tcp->keepalive.idleTime = seconds;
+
+    /* like linux, the new idle time applies to the running timer, counting from when we last heard
+     * from the peer */
+    if (tcp->keepalive.isEnabled && _tcp_isKeepaliveState(tcp)) {
+        CSimulationTime idle = seconds * (CSimulationTime)SIMTIME_ONE_SECOND;
+        CSimulationTime elapsed = _tcp_getKeepaliveElapsed(tcp, worker_getCurrentSimulationTime());
+        _tcp_setKeepaliveTimer(tcp, host, idle > elapsed ? idle - elapsed : 0);
+    }
+}
+
+guint tcp_getKeepIdle(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->keepalive.idleTime;
+}
+
+void tcp_setKeepInterval(TCP* tcp, guint seconds) {
+    MAGIC_ASSERT(tcp);
+    tcp->keepalive.interval = seconds;
+}
+
+guint tcp_getKeepInterval(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->keepalive.interval;
+}
+
+void tcp_setKeepCount(TCP* tcp, guint count) {
+    MAGIC_ASSERT(tcp);
+    tcp->keepalive.maxProbes = count;
+}
+
+guint tcp_getKeepCount(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->keepalive.maxProbes;
+}
+
+void tcp_setLinger(TCP* tcp, gboolean isEnabled, guint seconds) {
+    MAGIC_ASSERT(tcp);
+    tcp->linger.isEnabled = isEnabled;
+
+    /* like linux, disabling linger keeps the old linger time */
+    if (isEnabled) {
+        tcp->linger.seconds = seconds;
+    }
+}
+
+gboolean tcp_getLingerEnabled(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->linger.isEnabled;
+}
+
+guint tcp_getLingerTime(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->linger.seconds;
+}
+
+void tcp_setECNRequested(TCP* tcp, gboolean isRequested) {
+    MAGIC_ASSERT(tcp);
+    tcp->ecn.isRequested = isRequested;
+}
+
+void tcp_setTOS(TCP* tcp, guint8 tos) {
+    MAGIC_ASSERT(tcp);
+    /* like linux, the ecn codepoint is controlled by tcp and can't be set by the user */
+    tcp->tos = tos & ~IPTOS_ECN_MASK;
+}
+
+guint8 tcp_getTOS(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->tos;
+}
+
+static void _tcp_setChrono(TCP* tcp, enum TCPChrono chrono, CSimulationTime now) {
+    MAGIC_ASSERT(tcp);
+
+    if (chrono == tcp->info.chrono) {
+        return;
+    }
+
+    tcp->info.chronoTotals[tcp->info.chrono] += now - tcp->info.chronoStart;
+    tcp->info.chrono = chrono;
+    tcp->info.chronoStart = now;
+}
+
+static CSimulationTime _tcp_getChronoTotal(TCP* tcp, enum TCPChrono chrono, CSimulationTime now) {
+    MAGIC_ASSERT(tcp);
+
+    CSimulationTime total = tcp->info.chronoTotals[chrono];
+    if (chrono == tcp->info.chrono) {
+        total += now - tcp->info.chronoStart;
+    }
+    return total;
+}
+
+static void _tcp_runPacingTimerExpiredTask(const Host* host, gpointer voidInetSocket,
+                                           gpointer userData) {
+    const InetSocket* inetSocket = voidInetSocket;
+    utility_alwaysAssert(inetSocket != NULL);
+    TCP* tcp = inetsocket_asLegacyTcp(inetSocket);
+    MAGIC_ASSERT(tcp);
+
+    tcp->pacing.timerIsScheduled = FALSE;
+    _tcp_flush(tcp, host);
+}
+
+/* Returns TRUE if the pacing rate doesn't allow sending another data packet yet, in which case a
+ * timer is scheduled to flush again once it does. */
+static gboolean _tcp_isPacingLimited(TCP* tcp, const Host* host, CSimulationTime now) {
+    MAGIC_ASSERT(tcp);
+
+    if (tcp->cong.pacing_rate == 0 || tcp->pacing.nextSendTime <= now) {
+        return FALSE;
+    }
+
+    if (!tcp->pacing.timerIsScheduled) {
+        utility_alwaysAssert(tcp->rustSocket != NULL);
+        const InetSocket* inetSocket = inetsocketweak_upgrade(tcp->rustSocket);
+        utility_alwaysAssert(inetSocket != NULL);
+
+        TaskRef* pacingTask =
+            taskref_new_bound(host_getID(host), _tcp_runPacingTimerExpiredTask,
+                              (void*)inetSocket, NULL, inetsocket_dropVoid, NULL);
+        host_scheduleTaskWithDelay(host, pacingTask, tcp->pacing.nextSendTime - now);
+        taskref_drop(pacingTask);
+
+        tcp->pacing.timerIsScheduled = TRUE;
+    }
+
+    return TRUE;
+}
+
 static void _tcp_flush(TCP* tcp, const Host* host) {
     MAGIC_ASSERT(tcp);
 
@@ -1303,6 +2009,8 @@ static void _tcp_flush(TCP* tcp, const Host* host) {
 
     // bool print = true;
 
+    gboolean windowLimited = FALSE;
+
     /* flush packets that can now be sent to socket */
     while(!priorityqueue_isEmpty(tcp->throttledOutput)) {
         /* get the next throttled packet, in sequence order */
@@ -1325,12 +2033,29 @@ static void _tcp_flush(TCP* tcp, const Host* host) {
                 (length <= legacysocket_getOutputBufferSpace(&(tcp->super))) ? TRUE : FALSE;
 
             if(!fitsInBuffer || !fitsInWindow) {
+                windowLimited = !fitsInWindow;
                 _rswlog(tcp, "Can't retransmit %d, inWindow=%d, inBuffer=%d\n", header->sequence, fitsInWindow, fitsInBuffer);
                 /* we cant send the packet yet */
                 break;
+            } else if (packet == tcp->send.partialPacket &&
+                       _tcp_shouldDelayPartialPacket(tcp, host)) {
+                trace("%s <-> %s: delaying partial segment %u of %" G_GSIZE_FORMAT " bytes",
+                      tcp->super.boundString, tcp->super.peerString, header->sequence, length);
+                /* wait for more data or for the outstanding data to be acked */
+                break;
+            } else if (_tcp_isPacingLimited(tcp, host, now)) {
+                /* the pacing timer will flush again */
+                break;
             } else {
                 /* we will send the data packet */
                 tcp->info.lastDataSent = now;
+                tcp->info.bytesSent += length;
+                tcp->info.dataSegmentsSent++;
+
+                if (tcp->cong.pacing_rate > 0) {
+                    CSimulationTime delay = length * SIMTIME_ONE_SECOND / tcp->cong.pacing_rate;
+                    tcp->pacing.nextSendTime = MAX(tcp->pacing.nextSendTime, now) + delay;
+                }
             }
         }
 
@@ -1338,6 +2063,11 @@ static void _tcp_flush(TCP* tcp, const Host* host) {
         priorityqueue_pop(tcp->throttledOutput);
         tcp->throttledOutputLength -= length;
 
+        /* we can't add more data to a packet that was sent */
+        if (packet == tcp->send.partialPacket) {
+            _tcp_releasePartialPacket(tcp);
+        }
+
         /* packet will get stored in retrans queue in tcp_networkInterfaceIsAboutToSendPacket */
 
         /* socket will queue it ASAP */
@@ -1360,6 +2090,23 @@ static void _tcp_flush(TCP* tcp, const Host* host) {
         utility_debugAssert(success);
     }
 
+    /* if we ran out of data before filling the window, rate samples are limited by the
+     * application rather than by the network */
+    guint32 inFlight = _tcp_getPacketsInFlight(tcp);
+    if (priorityqueue_isEmpty(tcp->throttledOutput) && inFlight < tcp->cong.cwnd) {
+        tcp->delivery.appLimited = MAX(tcp->delivery.delivered + inFlight, 1);
+    }
+
+    if (priorityqueue_isEmpty(tcp->throttledOutput) && inFlight == 0) {
+        _tcp_setChrono(tcp, TCP_CHRONO_UNSPEC, now);
+    } else if (windowLimited && tcp->receive.lastWindow < tcp->cong.cwnd) {
+        _tcp_setChrono(tcp, TCP_CHRONO_RWND_LIMITED, now);
+    } else if (_tcp_getBufferSpaceOut(tcp) == 0) {
+        _tcp_setChrono(tcp, TCP_CHRONO_SNDBUF_LIMITED, now);
+    } else {
+        _tcp_setChrono(tcp, TCP_CHRONO_BUSY, now);
+    }
+
     /* any packets now in order can be pushed to our user input buffer */
     while(!priorityqueue_isEmpty(tcp->unorderedInput)) {
         Packet* packet = priorityqueue_peek(tcp->unorderedInput);
@@ -1381,6 +2128,7 @@ static void _tcp_flush(TCP* tcp, const Host* host) {
             if(fitInBuffer) {
                 // fprintf(stderr, "SND/RCV Recv %s %s %d @ %f\n", tcp->super.boundString, tcp->super.peerString, header.sequence, dtime);
                 tcp->receive.lastSequence = header->sequence;
+                tcp->info.bytesReceived += packet_getPayloadSize(packet);
                 priorityqueue_pop(tcp->unorderedInput);
                 tcp->unorderedInputLength -= packet_getPayloadSize(packet);
                 packet_unref(packet);
@@ -1521,6 +2269,20 @@ gboolean tcp_isValidListener(TCP* tcp) {
     }
 }
 
+/**
+ * Check if the TCP socket is a server with an accepted or pending child connection from the peer.
+ * The address and port must be in network byte order.
+ */
+gboolean tcp_hasChild(TCP* tcp, in_addr_t peerIP, in_port_t peerPort) {
+    MAGIC_ASSERT(tcp);
+    if (!tcp->server) {
+        return FALSE;
+    }
+
+    guint childKey = _ipPortHash(peerIP, peerPort);
+    return g_hash_table_contains(tcp->server->children, &childKey);
+}
+
 /**
  * Check if the TCP socket allows listening.
  * A socket must not have been used for other purposes to allow listening.
@@ -1542,7 +2304,7 @@ gint tcp_getConnectionError(TCP* tcp) {
         /* The 3-way handshake completed at some point. */
         if (tcp->error & TCPE_CONNECTION_RESET) {
             tcp->flags |= TCPF_RESET_SIGNALED;
-            return -ECONNRESET;
+            return (tcp->error & TCPE_CONNECTION_TIMED_OUT) ? -ETIMEDOUT : -ECONNRESET;
         }
 
         if (tcp->state == TCPS_CLOSED) {
@@ -1596,56 +2358,122 @@ static guint8 _tcp_getTCPInfoState(TCP* tcp) {
     }
 }
 
-void tcp_getInfo(TCP* tcp, struct tcp_info *tcpinfo) {
+static guint8 _tcp_getTCPInfoCAState(TCP* tcp) {
+    if (tcp->retransmit.backoffCount > 0) {
+        return (guint8)TCP_CA_Loss;
+    } else if (tcp->cong.hooks->tcp_cong_fast_recovery(tcp)) {
+        return (guint8)TCP_CA_Recovery;
+    } else if (retransmit_tally_num_sacked(tcp->retransmit.tally) > 0) {
+        return (guint8)TCP_CA_Disorder;
+    } else {
+        return (guint8)TCP_CA_Open;
+    }
+}
+
+/* Fills the info like linux does: times are in microseconds, except for the 'last_*' fields which
+ * are the milliseconds since the event, and windows and buffer spaces are in bytes. */
+void tcp_getInfo(TCP* tcp, TCPInfo* tcpinfo) {
     MAGIC_ASSERT(tcp);
 
-    memset(tcpinfo, 0, sizeof(struct tcp_info));
+    memset(tcpinfo, 0, sizeof(TCPInfo));
+
+    CSimulationTime now = worker_getCurrentSimulationTime();
+    gsize mss = CONFIG_TCP_MAX_SEGMENT_SIZE;
 
     tcpinfo->tcpi_state = (u_int8_t) _tcp_getTCPInfoState(tcp);
-//  tcpinfo->tcpi_ca_state;
-//  tcpinfo->tcpi_retransmits;
-//  tcpinfo->tcpi_probes;
-//  tcpinfo->tcpi_backoff;
-//  tcpinfo->tcpi_options;
-//  tcpinfo->tcpi_snd_wscale;
-//  tcpinfo->tcpi_rcv_wscale;
-
-//  tcpinfo->tcpi_rto;
-//  tcpinfo->tcpi_ato;
-    tcpinfo->tcpi_snd_mss = (u_int32_t)CONFIG_TCP_MAX_SEGMENT_SIZE;
-    tcpinfo->tcpi_rcv_mss = (u_int32_t)CONFIG_TCP_MAX_SEGMENT_SIZE;
-
-    tcpinfo->tcpi_unacked = (u_int32_t)(tcp->send.next - tcp->send.unacked);
-//  tcpinfo->tcpi_sacked;
-//  tcpinfo->tcpi_lost;
-    tcpinfo->tcpi_retrans = (u_int32_t) tcp->info.retransmitCount;
-//  tcpinfo->tcpi_fackets;
+    tcpinfo->tcpi_ca_state = _tcp_getTCPInfoCAState(tcp);
+    tcpinfo->tcpi_retransmits = (u_int8_t)MIN(tcp->retransmit.backoffCount, G_MAXUINT8);
+    /* we don't send zero window probes */
+    tcpinfo->tcpi_probes = tcp->keepalive.probesSent;
+    tcpinfo->tcpi_backoff = (u_int8_t)MIN(tcp->retransmit.backoffCount, G_MAXUINT8);
+    /* timestamps and sacks are always used, and windows aren't scaled */
+    tcpinfo->tcpi_options = TCPI_OPT_TIMESTAMPS | TCPI_OPT_SACK;
+    if (tcp->ecn.isEnabled) {
+        tcpinfo->tcpi_options |= TCPI_OPT_ECN;
+    }
+    if (tcp->ecn.isSeen) {
+        tcpinfo->tcpi_options |= TCPI_OPT_ECN_SEEN;
+    }
+    tcpinfo->tcpi_snd_wscale = 0;
+    tcpinfo->tcpi_rcv_wscale = 0;
+    tcpinfo->tcpi_delivery_rate_app_limited = tcp->delivery.lastRateIsAppLimited ? 1 : 0;
+
+    tcpinfo->tcpi_rto = (u_int32_t)tcp->retransmit.timeout * 1000;
+    tcpinfo->tcpi_ato = (tcp->send.numQuickACKsSent < 1000) ? 1000 : 5000;
+    tcpinfo->tcpi_snd_mss = (u_int32_t)mss;
+    tcpinfo->tcpi_rcv_mss = (u_int32_t)mss;
+
+    tcpinfo->tcpi_unacked = _tcp_getPacketsInFlight(tcp);
+    tcpinfo->tcpi_sacked = (u_int32_t)retransmit_tally_num_sacked(tcp->retransmit.tally);
+    tcpinfo->tcpi_lost = (u_int32_t)retransmit_tally_num_lost(tcp->retransmit.tally);
+    tcpinfo->tcpi_retrans = (u_int32_t)retransmit_tally_num_retransmitted(tcp->retransmit.tally);
+    /* linux no longer uses forward acknowledgment */
+    tcpinfo->tcpi_fackets = 0;
 
     /* Times. */
-    /* TODO not sure if these are "how long ago the events happened" or an absolute time.
-     * They can't possibly be since the epoch, since there are only 32 bits and we are
-     * returning microseconds.
-     * FIXME If absolute time, these should be the emulated time, not the simulated time.
-     */
-    tcpinfo->tcpi_last_data_sent = (u_int32_t)(tcp->info.lastDataSent/SIMTIME_ONE_MICROSECOND);
-    tcpinfo->tcpi_last_ack_sent = (u_int32_t)(tcp->info.lastAckSent/SIMTIME_ONE_MICROSECOND);
-    tcpinfo->tcpi_last_data_recv = (u_int32_t)(tcp->info.lastDataReceived/SIMTIME_ONE_MICROSECOND);
-    tcpinfo->tcpi_last_ack_recv = (u_int32_t)(tcp->info.lastAckReceived/SIMTIME_ONE_MICROSECOND);
+    tcpinfo->tcpi_last_data_sent =
+        (u_int32_t)((now - tcp->info.lastDataSent) / SIMTIME_ONE_MILLISECOND);
+    /* linux doesn't remember this */
+    tcpinfo->tcpi_last_ack_sent = 0;
+    tcpinfo->tcpi_last_data_recv =
+        (u_int32_t)((now - tcp->info.lastDataReceived) / SIMTIME_ONE_MILLISECOND);
+    tcpinfo->tcpi_last_ack_recv =
+        (u_int32_t)((now - tcp->info.lastAckReceived) / SIMTIME_ONE_MILLISECOND);
 
     /* Metrics. */
     tcpinfo->tcpi_pmtu = (u_int32_t)(CONFIG_MTU);
-//  tcpinfo->tcpi_rcv_ssthresh;
-    tcpinfo->tcpi_rtt = (u_int32_t)tcp->timing.rttSmoothed;
-    tcpinfo->tcpi_rttvar = (u_int32_t)tcp->timing.rttVariance;
+    tcpinfo->tcpi_rcv_ssthresh = (u_int32_t)(tcp->receive.window * mss);
+    tcpinfo->tcpi_rtt = (u_int32_t)tcp->timing.rttSmoothed * 1000;
+    tcpinfo->tcpi_rttvar = (u_int32_t)tcp->timing.rttVariance * 1000;
     tcpinfo->tcpi_snd_ssthresh = (u_int32_t)tcp->cong.hooks->tcp_cong_ssthresh(tcp);
     tcpinfo->tcpi_snd_cwnd = (u_int32_t)tcp->cong.cwnd;
-    tcpinfo->tcpi_advmss = (u_int32_t)CONFIG_TCP_MAX_SEGMENT_SIZE;
-    //  tcpinfo->tcpi_reordering;
+    tcpinfo->tcpi_advmss = (u_int32_t)mss;
+    /* the number of duplicate acks that indicate a loss */
+    tcpinfo->tcpi_reordering = 3;
 
-    tcpinfo->tcpi_rcv_rtt = (u_int32_t)tcp->info.rtt;
-    tcpinfo->tcpi_rcv_space = (u_int32_t)tcp->receive.window;
+    /* both ends use the same rtt estimate */
+    tcpinfo->tcpi_rcv_rtt = (u_int32_t)tcp->timing.rttSmoothed * 1000;
+    tcpinfo->tcpi_rcv_space = (u_int32_t)(tcp->autotune.space > 0 ? tcp->autotune.space
+                                                                   : tcp->receive.window * mss);
 
     tcpinfo->tcpi_total_retrans = (u_int32_t)tcp->info.retransmitCount;
+
+    tcpinfo->tcpi_pacing_rate = tcp->cong.pacing_rate > 0 ? tcp->cong.pacing_rate : UINT64_MAX;
+    /* SO_MAX_PACING_RATE isn't supported */
+    tcpinfo->tcpi_max_pacing_rate = UINT64_MAX;
+    tcpinfo->tcpi_bytes_acked = tcp->info.bytesAcked;
+    tcpinfo->tcpi_bytes_received = tcp->info.bytesReceived;
+    tcpinfo->tcpi_segs_out = tcp->send.packetsSent;
+    tcpinfo->tcpi_segs_in = tcp->info.segmentsReceived;
+
+    tcpinfo->tcpi_notsent_bytes = (u_int32_t)tcp_getNotSentBytes(tcp);
+    tcpinfo->tcpi_min_rtt = (u_int32_t)tcp->timing.rttMin * 1000;
+    tcpinfo->tcpi_data_segs_in = tcp->info.dataSegmentsReceived;
+    tcpinfo->tcpi_data_segs_out = tcp->info.dataSegmentsSent;
+
+    tcpinfo->tcpi_delivery_rate = tcp->delivery.lastRate;
+
+    /* like linux, the busy time includes the time limited by the receive window or send buffer */
+    CSimulationTime rwndLimited = _tcp_getChronoTotal(tcp, TCP_CHRONO_RWND_LIMITED, now);
+    CSimulationTime sndbufLimited = _tcp_getChronoTotal(tcp, TCP_CHRONO_SNDBUF_LIMITED, now);
+    CSimulationTime busy =
+        _tcp_getChronoTotal(tcp, TCP_CHRONO_BUSY, now) + rwndLimited + sndbufLimited;
+    tcpinfo->tcpi_busy_time = busy / SIMTIME_ONE_MICROSECOND;
+    tcpinfo->tcpi_rwnd_limited = rwndLimited / SIMTIME_ONE_MICROSECOND;
+    tcpinfo->tcpi_sndbuf_limited = sndbufLimited / SIMTIME_ONE_MICROSECOND;
+
+    tcpinfo->tcpi_delivered = (u_int32_t)tcp->delivery.delivered;
+    tcpinfo->tcpi_delivered_ce = tcp->ecn.deliveredCE;
+
+    tcpinfo->tcpi_bytes_sent = tcp->info.bytesSent;
+    tcpinfo->tcpi_bytes_retrans = tcp->info.bytesRetransmitted;
+    /* we don't send duplicate sacks, and don't detect reordering */
+    tcpinfo->tcpi_dsack_dups = 0;
+    tcpinfo->tcpi_reord_seen = 0;
+
+    tcpinfo->tcpi_rcv_ooopack = tcp->info.outOfOrderReceived;
+
+    tcpinfo->tcpi_snd_wnd = (u_int32_t)(tcp->receive.lastWindow * mss);
 }
 
 /* Address and port must be in network byte order. */
@@ -1661,7 +2489,12 @@ static gint _tcp_connectToPeer(LegacySocket* socket, const Host* host, in_addr_t
     }
 
     /* send 1st part of 3-way handshake, state->syn_sent */
-    _tcp_sendControlPacket(tcp, host, PTCP_SYN);
+    enum ProtocolTCPFlags synFlags = PTCP_SYN;
+    if (tcp->ecn.isRequested) {
+        /* request ecn (rfc 3168, section 6.1.1) */
+        synFlags |= PTCP_ECE | PTCP_CWR;
+    }
+    _tcp_sendControlPacket(tcp, host, synFlags);
 
     trace("%s <-> %s: user initiated connection", tcp->super.boundString, tcp->super.peerString);
     _tcp_setState(tcp, host, TCPS_SYNSENT);
@@ -1863,6 +2696,9 @@ TCPProcessFlags _tcp_dataProcessing(TCP* tcp, Packet* packet, PacketTCPHeader *h
             /* make sure its in order */
             _tcp_bufferPacketIn(tcp, packet);
             tcp->info.lastDataReceived = now;
+            if (!isNextPacket) {
+                tcp->info.outOfOrderReceived++;
+            }
             flags |= TCP_PF_DATA_RECEIVED;
         } else {
             trace("no space for packet even though its in our window");
@@ -1875,6 +2711,30 @@ TCPProcessFlags _tcp_dataProcessing(TCP* tcp, Packet* packet, PacketTCPHeader *h
     return flags;
 }
 
+/* The peer received a congestion experienced mark on data that we sent (rfc 3168, section
+ * 6.1.2). */
+static void _tcp_processECNEcho(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+
+    /* we already reacted to congestion in this window of data */
+    if (tcp->send.unacked < tcp->ecn.recoveryPoint) {
+        return;
+    }
+
+    /* a loss already reduced the window, otherwise we reduce it now */
+    gboolean isRecovering =
+        tcp->retransmit.backoffCount > 0 || tcp->cong.hooks->tcp_cong_fast_recovery(tcp);
+    if (!isRecovering && tcp->cong.hooks->tcp_cong_ecn_ev != NULL) {
+        debug("[CONG] peer echoed a congestion experienced mark");
+        tcp->cong.hooks->tcp_cong_ecn_ev(tcp);
+        _tcp_logCongestionInfo(tcp);
+    }
+
+    /* tell the peer that we reacted so that it stops echoing the mark */
+    tcp->ecn.isCWRPending = TRUE;
+    tcp->ecn.recoveryPoint = tcp->send.next;
+}
+
 TCPProcessFlags _tcp_ackProcessing(TCP* tcp, const Host* host, Packet* packet,
                                    PacketTCPHeader* header) {
     MAGIC_ASSERT(tcp);
@@ -1924,15 +2784,24 @@ TCPProcessFlags _tcp_ackProcessing(TCP* tcp, const Host* host, Packet* packet,
         tcp->receive.lastAcknowledgment = (guint32) header->acknowledgment;
 
         /* some data we sent got acknowledged */
+        guint32 prevUnacked = tcp->send.unacked;
         nPacketsAcked = header->acknowledgment - (guint)tcp->send.unacked;
         tcp->send.unacked = (guint32)header->acknowledgment;
 
+        TCPRateSample rateSample;
+        gboolean hasRateSample =
+            _tcp_sampleDeliveryRate(tcp, prevUnacked, header->acknowledgment, &rateSample);
+
         if(nPacketsAcked > 0) {
             flags |= TCP_PF_DATA_ACKED;
 
             debug("[CONG] %i packets were acked", nPacketsAcked);
             tcp->cong.hooks->tcp_cong_new_ack_ev(tcp, nPacketsAcked);
 
+            if (hasRateSample && tcp->cong.hooks->tcp_cong_rate_sample_ev != NULL) {
+                tcp->cong.hooks->tcp_cong_rate_sample_ev(tcp, &rateSample);
+            }
+
             /* increase send buffer size with autotuning */
             if (tcp->autotune.isEnabled && !tcp->autotune.userDisabledSend &&
                 host_autotuneSendBuffer(host)) {
@@ -1949,6 +2818,11 @@ TCPProcessFlags _tcp_ackProcessing(TCP* tcp, const Host* host, Packet* packet,
         tcp->retransmit.backoffCount = 0;
     }
 
+    if (tcp->ecn.isEnabled && (header->flags & PTCP_ECE) && !(header->flags & PTCP_SYN)) {
+        tcp->ecn.deliveredCE += MAX(nPacketsAcked, 0);
+        _tcp_processECNEcho(tcp);
+    }
+
     if(isValidWindow) {
         /* accept the window update */
         tcp->receive.lastWindow = (guint32) header->window;
@@ -2002,6 +2876,28 @@ static void _tcp_sendACKTaskCallback(const Host* host, gpointer voidInetSocket,
     }
 }
 
+/* Track congestion experienced marks so that we can echo them to the peer (rfc 3168, section
+ * 6.1.3). */
+static void _tcp_processECN(TCP* tcp, Packet* packet, PacketTCPHeader* header) {
+    MAGIC_ASSERT(tcp);
+
+    /* the peer reduced its window, so we can stop echoing */
+    if (header->flags & PTCP_CWR) {
+        tcp->ecn.isEchoPending = FALSE;
+    }
+
+    enum ProtocolECN ecn = packet_getECN(packet);
+
+    if (ecn != PECN_NOT_ECT) {
+        tcp->ecn.isSeen = TRUE;
+    }
+
+    if (ecn == PECN_CE) {
+        trace("received a congestion experienced mark");
+        tcp->ecn.isEchoPending = TRUE;
+    }
+}
+
 /* return TRUE if the packet should be retransmitted */
 static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* packet) {
     TCP* tcp = _tcp_fromLegacyFile((LegacyFile*)socket);
@@ -2017,6 +2913,14 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
     MAGIC_ASSERT(tcp);
     PacketTCPHeader* header = packet_getTCPHeader(packet);
 
+    tcp->info.segmentsReceived++;
+    if (packetLength > 0) {
+        tcp->info.dataSegmentsReceived++;
+    }
+
+    /* the peer is still there */
+    tcp->keepalive.probesSent = 0;
+
     /* if packet is reset, don't process */
     if(header->flags & PTCP_RST) {
         /* @todo: not sure if this is handled correctly */
@@ -2030,6 +2934,9 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
 
             /* it will send no more user data after what we have now */
             tcp->receive.end = tcp->receive.next;
+
+            /* wake up anyone blocked on the socket so they see the error */
+            _tcp_flush(tcp, host);
         }
         return;
     }
@@ -2085,6 +2992,18 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
 
                 /* we need to multiplex a new child */
                 TCP* multiplexed = tcp_new(host, recvBufSize, sendBufSize);
+                multiplexed->send.noDelay = tcp->send.noDelay;
+                multiplexed->send.cork = tcp->send.cork;
+                multiplexed->keepalive.isEnabled = tcp->keepalive.isEnabled;
+                multiplexed->keepalive.idleTime = tcp->keepalive.idleTime;
+                multiplexed->keepalive.interval = tcp->keepalive.interval;
+                multiplexed->keepalive.maxProbes = tcp->keepalive.maxProbes;
+                multiplexed->linger = tcp->linger;
+                multiplexed->ecn.isRequested = tcp->ecn.isRequested;
+                multiplexed->tos = tcp->tos;
+                multiplexed->isIPv6 = packet_isIPv6(packet);
+                tcp_setCongestionType(
+                    multiplexed, tcpCongestion_getType(tcpcong_nameStr(&tcp->cong)));
                 Descriptor* desc = descriptor_fromLegacyTcp(multiplexed, /* flags= */ 0);
                 int handle = process_registerDescriptor(registerInProcess, desc);
 
@@ -2113,6 +3032,13 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
                 tcp = multiplexed;
                 responseFlags = PTCP_SYN|PTCP_ACK;
 
+                /* like linux, we always accept ecn if the peer requests it (rfc 3168, section
+                 * 6.1.1) */
+                if ((header->flags & PTCP_ECE) && (header->flags & PTCP_CWR)) {
+                    tcp->ecn.isEnabled = TRUE;
+                    responseFlags |= PTCP_ECE;
+                }
+
                 trace("new child state %s", _tcp_stateToAscii(tcp->state));
             }
             break;
@@ -2125,6 +3051,12 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
                 tcp->receive.start = header->sequence;
                 tcp->receive.next = tcp->receive.start + 1;
 
+                /* the peer agreed to use ecn */
+                if (tcp->ecn.isRequested && (header->flags & PTCP_ECE) &&
+                    !(header->flags & PTCP_CWR)) {
+                    tcp->ecn.isEnabled = TRUE;
+                }
+
                 responseFlags |= PTCP_ACK;
                 _tcp_setState(tcp, host, TCPS_ESTABLISHED);
             }
@@ -2258,6 +3190,10 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
 
     trace("state after switch is %s", _tcp_stateToAscii(tcp->state));
 
+    if (tcp->ecn.isEnabled && !(header->flags & PTCP_SYN)) {
+        _tcp_processECN(tcp, packet, header);
+    }
+
     /* if TCPE_RECEIVE_EOF, we are not supposed to receive any more */
     if(packetLength > 0 && !(tcp->error & TCPE_RECEIVE_EOF)) {
         flags |= _tcp_dataProcessing(tcp, packet, header);
@@ -2314,6 +3250,12 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
         responseFlags |= PTCP_ACK;
     }
 
+    /* keepalive probes need an immediate response so the peer knows that we're still here */
+    if (_tcp_isKeepaliveProbe(packet) && !(tcp->error & TCPE_RECEIVE_EOF)) {
+        trace("responding to keepalive probe");
+        _tcp_sendControlPacket(tcp, host, PTCP_ACK);
+    }
+
     trace("checking if response is needed: flags=%i RCV_EOF=%i FIN=%i",
           (int)responseFlags, (int)(tcp->error & TCPE_RECEIVE_EOF),
           (int)(responseFlags & PTCP_FIN));
@@ -2397,6 +3339,19 @@ static void _tcp_endOfFileSignalled(TCP* tcp, enum TCPFlags flags) {
     }
 }
 
+/* returns a negative errno if the peer reset the connection or the keepalive timer dropped it and
+ * we haven't told the user yet, otherwise 0. like a socket error in linux, the error is only
+ * returned once. */
+static gint _tcp_takeError(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+
+    if ((tcp->error & TCPE_CONNECTION_RESET) && !(tcp->flags & TCPF_ERROR_SIGNALED)) {
+        tcp->flags |= TCPF_ERROR_SIGNALED;
+        return (tcp->error & TCPE_CONNECTION_TIMED_OUT) ? -ETIMEDOUT : -ECONNRESET;
+    }
+    return 0;
+}
+
 /* Address and port must be in network byte order. */
 gssize tcp_sendUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gsize nBytes,
                         in_addr_t ip, in_port_t port, const MemoryManager* mem) {
@@ -2406,7 +3361,10 @@ gssize tcp_sendUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gs
     if(tcp->error & TCPE_SEND_EOF)
     {
         trace("send EOF is set");
-        if(tcp->state == TCPS_CLOSED) {
+        gint error = _tcp_takeError(tcp);
+        if (error != 0) {
+            return error;
+        } else if(tcp->state == TCPS_CLOSED) {
             return -ENOTCONN;
         } else {
             _tcp_endOfFileSignalled(tcp, TCPF_EOF_WR_SIGNALED);
@@ -2434,6 +3392,29 @@ gssize tcp_sendUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gs
         return -EFAULT;
     }
 
+    /* fill up the last unsent packet first, so that small writes are coalesced into segments */
+    if (remaining > 0 && tcp->send.partialPacket != NULL) {
+        gsize partialLength = packet_getPayloadSize(tcp->send.partialPacket);
+        gsize copyLength = MIN(maxPacketLength - partialLength, remaining);
+
+        if (copyLength > 0) {
+            packet_appendPayloadWithMemoryManager(tcp->send.partialPacket, buffer, copyLength, mem);
+
+            /* the packet takes up more space */
+            tcp->throttledOutputLength += copyLength;
+            if (_tcp_getBufferSpaceOut(tcp) == 0) {
+                legacyfile_adjustStatus((LegacyFile*)tcp, STATUS_FILE_WRITABLE, FALSE);
+            }
+
+            remaining -= copyLength;
+            bytesCopied += copyLength;
+        }
+
+        if (partialLength + copyLength >= maxPacketLength) {
+            _tcp_releasePartialPacket(tcp);
+        }
+    }
+
     /* create as many packets as needed */
     while(remaining > 0) {
         gsize copyLength = MIN(maxPacketLength, remaining);
@@ -2451,6 +3432,13 @@ gssize tcp_sendUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gs
         /* buffer the outgoing packet in TCP */
         _tcp_bufferPacketOut(tcp, packet);
 
+        /* only the last packet can be partial; keep it so that we can append to it later */
+        if (copyLength < maxPacketLength) {
+            utility_debugAssert(tcp->send.partialPacket == NULL);
+            packet_ref(packet);
+            tcp->send.partialPacket = packet;
+        }
+
         /* the output buffer holds the packet ref now */
         packet_unref(packet);
 
@@ -2606,7 +3594,10 @@ gssize tcp_receiveUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer,
                 legacyfile_adjustStatus(&(tcp->super.super), STATUS_FILE_READABLE, TRUE);
             } else {
                 /* OK, no more data and nothing just received. */
-                if(tcp->state == TCPS_CLOSED) {
+                gint error = _tcp_takeError(tcp);
+                if (error != 0) {
+                    return error;
+                } else if(tcp->state == TCPS_CLOSED) {
                     return -ENOTCONN;
                 } else {
                     _tcp_endOfFileSignalled(tcp, TCPF_EOF_RD_SIGNALED);
@@ -2676,6 +3667,9 @@ static void _tcp_free(LegacyFile* descriptor) {
     priorityqueue_free(tcp->unorderedInput);
     g_hash_table_destroy(tcp->retransmit.queue);
     priorityqueue_free(tcp->retransmit.scheduledTimerExpirations);
+    g_hash_table_destroy(tcp->delivery.sentStates);
+
+    _tcp_releasePartialPacket(tcp);
 
     if (tcp->partialUserDataPacket != NULL) {
         packet_unref(tcp->partialUserDataPacket);
@@ -2707,6 +3701,40 @@ static void _tcp_free(LegacyFile* descriptor) {
     worker_count_deallocation(TCP);
 }
 
+/* drop the connection without the normal FIN handshake, like linux's tcp_disconnect(). any data
+ * that we haven't sent yet or that wasn't acked is discarded. */
+static void _tcp_abort(TCP* tcp, const Host* host) {
+    MAGIC_ASSERT(tcp);
+
+    trace("%s <-> %s: aborting connection", tcp->super.boundString, tcp->super.peerString);
+
+    /* the peer will never get the data that is still waiting to be sent */
+    while (!priorityqueue_isEmpty(tcp->throttledOutput)) {
+        packet_unref(priorityqueue_pop(tcp->throttledOutput));
+    }
+    tcp->throttledOutputLength = 0;
+    _tcp_releasePartialPacket(tcp);
+    tcp->flags &= ~TCPF_SHOULD_SEND_WR_FIN;
+
+    switch (tcp->state) {
+        case TCPS_SYNRECEIVED:
+        case TCPS_ESTABLISHED:
+        case TCPS_CLOSEWAIT:
+        case TCPS_FINWAIT1:
+        case TCPS_FINWAIT2:
+        case TCPS_CLOSING:
+        case TCPS_LASTACK: {
+            /* the peer may still have state for this connection */
+            _tcp_sendControlPacket(tcp, host, PTCP_RST);
+            break;
+        }
+        default: break;
+    }
+
+    tcp->error |= TCPE_CONNECTION_RESET;
+    _tcp_setState(tcp, host, TCPS_CLOSED);
+}
+
 static void _tcp_close(LegacyFile* descriptor, const Host* host) {
     TCP* tcp = _tcp_fromLegacyFile(descriptor);
     MAGIC_ASSERT(tcp);
@@ -2721,6 +3749,16 @@ static void _tcp_close(LegacyFile* descriptor, const Host* host) {
     /* the user closed the connection, so should never interact with the socket again */
     legacyfile_adjustStatus((LegacyFile*)tcp, STATUS_FILE_ACTIVE, FALSE);
 
+    /* like linux, a zero linger time or data that the user never read resets the connection
+     * (rfc 2525, section 2.17) */
+    gboolean lingerAbort = tcp->linger.isEnabled && tcp->linger.seconds == 0;
+    gboolean hasUnreadData = legacysocket_getInputBufferLength(&(tcp->super)) > 0 ||
+                             tcp->partialUserDataPacket != NULL;
+    if (lingerAbort || hasUnreadData) {
+        _tcp_abort(tcp, host);
+        return;
+    }
+
     switch (tcp->state) {
         case TCPS_LISTEN:
         case TCPS_SYNSENT: {
@@ -2831,9 +3869,9 @@ TCP* tcp_new(const Host* host, guint receiveBufferSize, guint sendBufferSize) {
     guint32 initial_window = 10;
     gint tcpSSThresh = 0;
 
-    /* in the future we'd like to support more congestion control types
-     * and allow it to be set as a host option */
-    tcp_cong_reno_init(tcp);
+    /* the host's default congestion control is set when the socket is created, or inherited from
+     * the listening socket */
+    _tcp_initCongestion(tcp, TCP_CC_RENO);
 
     tcp->send.window = initial_window;
     tcp->send.lastWindow = initial_window;
@@ -2855,6 +3893,10 @@ TCP* tcp_new(const Host* host, guint receiveBufferSize, guint sendBufferSize) {
 
     tcp->autotune.isEnabled = TRUE;
 
+    tcp->keepalive.idleTime = CONFIG_TCP_KEEPALIVE_TIME;
+    tcp->keepalive.interval = CONFIG_TCP_KEEPALIVE_INTVL;
+    tcp->keepalive.maxProbes = CONFIG_TCP_KEEPALIVE_PROBES;
+
     tcp->throttledOutput =
             priorityqueue_new((GCompareDataFunc)packet_compareTCPSequence, NULL, (GDestroyNotify)packet_unref);
     tcp->unorderedInput =
@@ -2864,6 +3906,8 @@ TCP* tcp_new(const Host* host, guint receiveBufferSize, guint sendBufferSize) {
 
     retransmit_tally_init(&tcp->retransmit.tally);
 
+    tcp->delivery.sentStates = g_hash_table_new_full(g_direct_hash, g_direct_equal, NULL, g_free);
+
     tcp->retransmit.scheduledTimerExpirations =
         priorityqueue_new((GCompareDataFunc)_simulationTimeCompare, NULL, g_free);
 
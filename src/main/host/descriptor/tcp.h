@@ -37,7 +37,83 @@ enum TCPProcessFlags {
 
 typedef enum _TCPCongestionType TCPCongestionType;
 enum _TCPCongestionType {
-    TCP_CC_UNKNOWN, TCP_CC_AIMD, TCP_CC_RENO, TCP_CC_CUBIC,
+    TCP_CC_UNKNOWN, TCP_CC_AIMD, TCP_CC_RENO, TCP_CC_CUBIC, TCP_CC_BBR,
+};
+
+/* The linux `struct tcp_info` from <linux/tcp.h>. glibc's `struct tcp_info` is missing the fields
+ * after `tcpi_total_retrans`, and the linux header can't be included alongside glibc's. */
+typedef struct _TCPInfo TCPInfo;
+struct _TCPInfo {
+    guint8 tcpi_state;
+    guint8 tcpi_ca_state;
+    guint8 tcpi_retransmits;
+    guint8 tcpi_probes;
+    guint8 tcpi_backoff;
+    guint8 tcpi_options;
+    guint8 tcpi_snd_wscale : 4, tcpi_rcv_wscale : 4;
+    guint8 tcpi_delivery_rate_app_limited : 1, tcpi_fastopen_client_fail : 2;
+
+    guint32 tcpi_rto;
+    guint32 tcpi_ato;
+    guint32 tcpi_snd_mss;
+    guint32 tcpi_rcv_mss;
+
+    guint32 tcpi_unacked;
+    guint32 tcpi_sacked;
+    guint32 tcpi_lost;
+    guint32 tcpi_retrans;
+    guint32 tcpi_fackets;
+
+    /* Times. */
+    guint32 tcpi_last_data_sent;
+    guint32 tcpi_last_ack_sent;
+    guint32 tcpi_last_data_recv;
+    guint32 tcpi_last_ack_recv;
+
+    /* Metrics. */
+    guint32 tcpi_pmtu;
+    guint32 tcpi_rcv_ssthresh;
+    guint32 tcpi_rtt;
+    guint32 tcpi_rttvar;
+    guint32 tcpi_snd_ssthresh;
+    guint32 tcpi_snd_cwnd;
+    guint32 tcpi_advmss;
+    guint32 tcpi_reordering;
+
+    guint32 tcpi_rcv_rtt;
+    guint32 tcpi_rcv_space;
+
+    guint32 tcpi_total_retrans;
+
+    guint64 tcpi_pacing_rate;
+    guint64 tcpi_max_pacing_rate;
+    guint64 tcpi_bytes_acked;
+    guint64 tcpi_bytes_received;
+    guint32 tcpi_segs_out;
+    guint32 tcpi_segs_in;
+
+    guint32 tcpi_notsent_bytes;
+    guint32 tcpi_min_rtt;
+    guint32 tcpi_data_segs_in;
+    guint32 tcpi_data_segs_out;
+
+    guint64 tcpi_delivery_rate;
+
+    guint64 tcpi_busy_time;
+    guint64 tcpi_rwnd_limited;
+    guint64 tcpi_sndbuf_limited;
+
+    guint32 tcpi_delivered;
+    guint32 tcpi_delivered_ce;
+
+    guint64 tcpi_bytes_sent;
+    guint64 tcpi_bytes_retrans;
+    guint32 tcpi_dsack_dups;
+    guint32 tcpi_reord_seen;
+
+    guint32 tcpi_rcv_ooopack;
+
+    guint32 tcpi_snd_wnd;
 };
 
 TCP* tcp_new(const Host* host, guint receiveBufferSize, guint sendBufferSize);
@@ -64,7 +140,7 @@ void tcp_setRustSocket(TCP* tcp, InetSocketWeak* rustSocket);
 gint tcp_getConnectionError(TCP* tcp);
 // clang-format on
 
-void tcp_getInfo(TCP* tcp, struct tcp_info *tcpinfo);
+void tcp_getInfo(TCP* tcp, TCPInfo* tcpinfo);
 void tcp_enterServerMode(TCP* tcp, const Host* host, pid_t process, gint backlog);
 void tcp_updateServerBacklog(TCP* tcp, gint backlog);
 /* Address and port must be in network byte order. */
@@ -72,6 +148,9 @@ gint tcp_acceptServerPeer(TCP* tcp, const Host* host, in_addr_t* ip, in_port_t*
                           gint* acceptedHandle);
 
 struct TCPCong_ *tcp_cong(TCP *tcp);
+void tcp_setCongestionType(TCP* tcp, TCPCongestionType type);
+/* the smallest measured rtt in milliseconds, or 0 if no rtt has been measured */
+gint tcp_getMinRTT(TCP* tcp);
 
 void tcp_clearAllChildrenIfServer(TCP* tcp);
 
@@ -82,7 +161,29 @@ gsize tcp_getNotSentBytes(TCP* tcp);
 void tcp_disableSendBufferAutotuning(TCP* tcp);
 void tcp_disableReceiveBufferAutotuning(TCP* tcp);
 
+void tcp_setNoDelay(TCP* tcp, const Host* host, gboolean noDelay);
+gboolean tcp_getNoDelay(TCP* tcp);
+void tcp_setCork(TCP* tcp, const Host* host, gboolean cork);
+gboolean tcp_getCork(TCP* tcp);
+void tcp_setKeepAlive(TCP* tcp, const Host* host, gboolean keepAlive);
+gboolean tcp_getKeepAlive(TCP* tcp);
+void tcp_setKeepIdle(TCP* tcp, const Host* host, guint seconds);
+guint tcp_getKeepIdle(TCP* tcp);
+void tcp_setKeepInterval(TCP* tcp, guint seconds);
+guint tcp_getKeepInterval(TCP* tcp);
+void tcp_setKeepCount(TCP* tcp, guint count);
+guint tcp_getKeepCount(TCP* tcp);
+void tcp_setLinger(TCP* tcp, gboolean isEnabled, guint seconds);
+gboolean tcp_getLingerEnabled(TCP* tcp);
+guint tcp_getLingerTime(TCP* tcp);
+/* whether new connections request ecn; incoming requests are always accepted */
+void tcp_setECNRequested(TCP* tcp, gboolean isRequested);
+/* IP_TOS; the ecn codepoint bits are ignored */
+void tcp_setTOS(TCP* tcp, guint8 tos);
+guint8 tcp_getTOS(TCP* tcp);
+
 gboolean tcp_isValidListener(TCP* tcp);
+gboolean tcp_hasChild(TCP* tcp, in_addr_t peerIP, in_port_t peerPort);
 gboolean tcp_isListeningAllowed(TCP* tcp);
 
 gssize tcp_sendUserData(TCP* tcp, const Host* host, UntypedForeignPtr buffer, gsize nBytes,
@@ -1,5 +1,25 @@
 #include "main/host/descriptor/tcp_cong.h"
 
+#include <string.h>
+
+#include "main/host/descriptor/tcp_cong_bbr.h"
+#include "main/host/descriptor/tcp_cong_cubic.h"
+#include "main/host/descriptor/tcp_cong_reno.h"
+
 const char* tcpcong_nameStr(const TCPCong *cong) {
     return cong->hooks->tcp_cong_name_str();
 }
+
+TCPCongestionType tcpCongestion_getType(const gchar* type) {
+    if (type == NULL) {
+        return TCP_CC_UNKNOWN;
+    } else if (strcmp(type, TCP_CONG_RENO_NAME) == 0) {
+        return TCP_CC_RENO;
+    } else if (strcmp(type, TCP_CONG_CUBIC_NAME) == 0) {
+        return TCP_CC_CUBIC;
+    } else if (strcmp(type, TCP_CONG_BBR_NAME) == 0) {
+        return TCP_CC_BBR;
+    } else {
+        return TCP_CC_UNKNOWN;
+    }
+}
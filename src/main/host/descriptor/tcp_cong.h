@@ -5,6 +5,27 @@
 
 #include "main/host/descriptor/tcp.h"
 
+/* A delivery rate sample (draft-cheng-iccrg-delivery-rate-estimation), taken when an ack
+ * acknowledges new data */
+typedef struct TCPRateSample_ {
+    /* the delivery rate in bytes per second, or 0 if the sample isn't valid */
+    guint64 delivery_rate;
+    /* packets delivered over the sampling interval */
+    guint32 delivered;
+    /* total packets delivered when the most recently acked packet was sent */
+    guint64 prior_delivered;
+    /* length of the sampling interval */
+    CSimulationTime interval;
+    /* round trip time of the most recently sent packet that was acked */
+    CSimulationTime rtt;
+    /* packets acknowledged by this ack */
+    guint32 acked;
+    /* data packets still in flight after this ack */
+    guint32 in_flight;
+    /* the sender didn't have enough data to fill the window when the acked packet was sent */
+    bool is_app_limited;
+} TCPRateSample;
+
 // congestion event hooks
 
 typedef void (*TCPCongDelete)(TCP *tcp);
@@ -14,6 +35,8 @@ typedef void (*TCPCongNewAckEv)(TCP *tcp, guint32 n);
 typedef void (*TCPCongTimeoutEv)(TCP *tcp);
 typedef guint32 (*TCPCongSSThresh)(TCP *tcp);
 typedef const char* (*TCPCongNameStr)();
+typedef void (*TCPCongRateSampleEv)(TCP *tcp, const TCPRateSample *rs);
+typedef void (*TCPCongECNEv)(TCP *tcp);
 
 typedef struct TCPCongHooks_ {
     TCPCongDelete tcp_cong_delete;
@@ -23,10 +46,16 @@ typedef struct TCPCongHooks_ {
     TCPCongTimeoutEv tcp_cong_timeout_ev;
     TCPCongSSThresh tcp_cong_ssthresh;
     TCPCongNameStr tcp_cong_name_str;
+    // optional, for algorithms that use delivery rate samples
+    TCPCongRateSampleEv tcp_cong_rate_sample_ev;
+    // optional, for algorithms that reduce the window when the peer echoes an ecn mark
+    TCPCongECNEv tcp_cong_ecn_ev;
 } TCPCongHooks;
 
 typedef struct TCPCong_ {
     guint32 cwnd;
+    // in bytes per second, or 0 if outgoing packets aren't paced
+    guint64 pacing_rate;
     const TCPCongHooks *hooks;
     void *ca;
 } TCPCong;
@@ -0,0 +1,440 @@
+#include "main/host/descriptor/tcp_cong_bbr.h"
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#include "lib/logger/logger.h"
+#include "main/core/support/definitions.h"
+#include "main/core/worker.h"
+#include "main/host/descriptor/descriptor.h"
+#include "main/host/descriptor/tcp.h"
+#include "main/host/descriptor/tcp_cong.h"
+
+const char* TCP_CONG_BBR_NAME = "bbr";
+
+/* the startup gain, which doubles the sending rate every round trip: 2/ln(2) */
+#define BBR_HIGH_GAIN 2.885
+/* the pacing gain used to drain the queue that was built during startup */
+#define BBR_DRAIN_GAIN (1.0 / BBR_HIGH_GAIN)
+/* the cwnd gain while probing bandwidth, to keep the pipe full despite delayed acks */
+#define BBR_CWND_GAIN 2.0
+
+/* the pacing gains that probe bandwidth cycles through, one min rtt each */
+static const double BBR_PACING_GAIN_CYCLE[] = {1.25, 0.75, 1, 1, 1, 1, 1, 1};
+#define BBR_CYCLE_LEN (sizeof(BBR_PACING_GAIN_CYCLE) / sizeof(BBR_PACING_GAIN_CYCLE[0]))
+
+/* the bottleneck bandwidth is the max delivery rate over this many round trips */
+#define BBR_BW_FILTER_LEN 10
+/* the min rtt estimate expires after this long, at which point we probe for a new one */
+#define BBR_MIN_RTT_WINDOW (10 * (CSimulationTime)SIMTIME_ONE_SECOND)
+/* how long to keep the window at its minimum while probing rtt */
+#define BBR_PROBE_RTT_DURATION (200 * (CSimulationTime)SIMTIME_ONE_MILLISECOND)
+
+/* the pipe is full once the bandwidth grows by less than 25% for 3 round trips */
+#define BBR_FULL_BW_THRESH 1.25
+#define BBR_FULL_BW_COUNT 3
+
+/* the smallest window, which is also the window while probing rtt */
+#define BBR_MIN_CWND 4
+/* the window that we pace out before we have a bandwidth estimate */
+#define BBR_INIT_CWND 10
+/* packets allowed in flight on top of the bdp, so that delayed acks don't starve the pipe */
+#define BBR_CWND_QUANTA 3
+
+typedef enum BBRMode_ {
+    BBR_STARTUP,
+    BBR_DRAIN,
+    BBR_PROBE_BW,
+    BBR_PROBE_RTT,
+} BBRMode;
+
+typedef struct CABbr_ {
+
+    BBRMode mode;
+
+    double pacing_gain;
+    double cwnd_gain;
+
+    /* the max delivery rate (in bytes per second) of each of the most recent round trips */
+    guint64 bw_samples[BBR_BW_FILTER_LEN];
+
+    /* number of round trips so far */
+    guint64 round_count;
+    /* a new round trip starts when a packet sent after this many deliveries is acked */
+    guint64 next_round_delivered;
+    bool round_start;
+
+    /* the min rtt estimate, or 0 if there isn't one, and when it was measured */
+    CSimulationTime min_rtt;
+    CSimulationTime min_rtt_stamp;
+
+    /* when probe rtt can end, or 0 if we haven't reached the minimum window yet */
+    CSimulationTime probe_rtt_done_stamp;
+    bool probe_rtt_round_done;
+
+    /* the bandwidth after the last significant growth during startup */
+    guint64 full_bw;
+    guint32 full_bw_count;
+    bool full_bw_reached;
+
+    /* the current phase of the probe bandwidth gain cycle, and when it started */
+    size_t cycle_index;
+    CSimulationTime cycle_stamp;
+
+    size_t duplicate_ack_n;
+    bool in_recovery;
+    /* recovery started since the last rate sample */
+    bool recovery_start;
+    /* don't send more than was acked during the first round trip of recovery */
+    bool packet_conservation;
+    /* the window before loss recovery or probe rtt, which is restored afterwards */
+    guint32 prior_cwnd;
+
+} CABbr;
+
+/* HELPERS *******************************************************/
+
+static guint64 bbr_max_bw_(const CABbr *bbr) {
+    guint64 bw = 0;
+    for (size_t i = 0; i < BBR_BW_FILTER_LEN; i++) {
+        bw = MAX(bw, bbr->bw_samples[i]);
+    }
+    return bw;
+}
+
+/*
+ * The bandwidth-delay product in packets scaled by the gain, plus some headroom. Before we have
+ * an estimate of the bdp this is the initial window.
+ */
+static guint32 bbr_target_cwnd_(const CABbr *bbr, double gain) {
+    guint64 bw = bbr_max_bw_(bbr);
+
+    if (bw == 0 || bbr->min_rtt == 0) {
+        return BBR_INIT_CWND;
+    }
+
+    double bdp = (double)bw * (double)bbr->min_rtt / (double)SIMTIME_ONE_SECOND /
+                 (double)CONFIG_TCP_MAX_SEGMENT_SIZE;
+    guint32 target = (guint32)(gain * bdp) + BBR_CWND_QUANTA;
+
+    return MAX(target, BBR_MIN_CWND);
+}
+
+static void bbr_save_cwnd_(TCP *tcp, CABbr *bbr) {
+    if (!bbr->in_recovery && bbr->mode != BBR_PROBE_RTT) {
+        bbr->prior_cwnd = tcp_cong(tcp)->cwnd;
+    } else {
+        bbr->prior_cwnd = MAX(bbr->prior_cwnd, tcp_cong(tcp)->cwnd);
+    }
+}
+
+static void bbr_enter_startup_(CABbr *bbr) {
+    bbr->mode = BBR_STARTUP;
+    bbr->pacing_gain = BBR_HIGH_GAIN;
+    bbr->cwnd_gain = BBR_HIGH_GAIN;
+}
+
+static void bbr_enter_drain_(TCP *tcp, CABbr *bbr) {
+    bbr->mode = BBR_DRAIN;
+    bbr->pacing_gain = BBR_DRAIN_GAIN;
+    bbr->cwnd_gain = BBR_HIGH_GAIN;
+    debug("[CONG] desc=%p bbr entering drain", (LegacyFile*)tcp);
+}
+
+static void bbr_enter_probe_bw_(TCP *tcp, CABbr *bbr, CSimulationTime now) {
+    bbr->mode = BBR_PROBE_BW;
+    bbr->cwnd_gain = BBR_CWND_GAIN;
+    bbr->cycle_index = 0;
+    bbr->cycle_stamp = now;
+    bbr->pacing_gain = BBR_PACING_GAIN_CYCLE[bbr->cycle_index];
+    debug("[CONG] desc=%p bbr entering probe bw", (LegacyFile*)tcp);
+}
+
+static void bbr_enter_probe_rtt_(TCP *tcp, CABbr *bbr) {
+    bbr_save_cwnd_(tcp, bbr);
+    bbr->mode = BBR_PROBE_RTT;
+    bbr->pacing_gain = 1;
+    bbr->cwnd_gain = 1;
+    bbr->probe_rtt_done_stamp = 0;
+    debug("[CONG] desc=%p bbr entering probe rtt", (LegacyFile*)tcp);
+}
+
+/* MODEL *******************************************************/
+
+static void bbr_update_bw_(CABbr *bbr, const TCPRateSample *rs) {
+    bbr->round_start = false;
+
+    if (rs->prior_delivered >= bbr->next_round_delivered) {
+        bbr->next_round_delivered = rs->prior_delivered + rs->delivered;
+        bbr->round_count++;
+        bbr->round_start = true;
+        // the oldest round leaves the filter window
+        bbr->bw_samples[bbr->round_count % BBR_BW_FILTER_LEN] = 0;
+    }
+
+    if (rs->delivery_rate == 0) {
+        return;
+    }
+
+    // app-limited samples underestimate the bandwidth, so only use them if they're higher
+    if (!rs->is_app_limited || rs->delivery_rate >= bbr_max_bw_(bbr)) {
+        guint64 *sample = &bbr->bw_samples[bbr->round_count % BBR_BW_FILTER_LEN];
+        *sample = MAX(*sample, rs->delivery_rate);
+    }
+}
+
+static void bbr_update_cycle_phase_(CABbr *bbr, const TCPRateSample *rs, CSimulationTime now) {
+    if (bbr->mode != BBR_PROBE_BW) {
+        return;
+    }
+
+    bool is_full_length = now - bbr->cycle_stamp > bbr->min_rtt;
+    bool advance = is_full_length;
+
+    if (bbr->pacing_gain > 1) {
+        // keep probing until we've put the extra data in flight
+        advance = is_full_length && rs->in_flight >= bbr_target_cwnd_(bbr, bbr->pacing_gain);
+    } else if (bbr->pacing_gain < 1) {
+        // stop draining early once the queue is gone
+        advance = is_full_length || rs->in_flight <= bbr_target_cwnd_(bbr, 1);
+    }
+
+    if (advance) {
+        bbr->cycle_index = (bbr->cycle_index + 1) % BBR_CYCLE_LEN;
+        bbr->cycle_stamp = now;
+        bbr->pacing_gain = BBR_PACING_GAIN_CYCLE[bbr->cycle_index];
+    }
+}
+
+static void bbr_check_full_bw_reached_(CABbr *bbr, const TCPRateSample *rs) {
+    if (bbr->full_bw_reached || !bbr->round_start || rs->is_app_limited) {
+        return;
+    }
+
+    guint64 bw = bbr_max_bw_(bbr);
+
+    if ((double)bw >= (double)bbr->full_bw * BBR_FULL_BW_THRESH) {
+        bbr->full_bw = bw;
+        bbr->full_bw_count = 0;
+        return;
+    }
+
+    bbr->full_bw_count++;
+    bbr->full_bw_reached = bbr->full_bw_count >= BBR_FULL_BW_COUNT;
+}
+
+static void bbr_check_drain_(TCP *tcp, CABbr *bbr, const TCPRateSample *rs, CSimulationTime now) {
+    if (bbr->mode == BBR_STARTUP && bbr->full_bw_reached) {
+        bbr_enter_drain_(tcp, bbr);
+    }
+
+    if (bbr->mode == BBR_DRAIN && rs->in_flight <= bbr_target_cwnd_(bbr, 1)) {
+        bbr_enter_probe_bw_(tcp, bbr, now);
+    }
+}
+
+static void bbr_update_min_rtt_(TCP *tcp, CABbr *bbr, const TCPRateSample *rs,
+                                CSimulationTime now) {
+    bool expired = bbr->min_rtt != 0 && now > bbr->min_rtt_stamp + BBR_MIN_RTT_WINDOW;
+
+    if (rs->rtt > 0 && (bbr->min_rtt == 0 || rs->rtt <= bbr->min_rtt || expired)) {
+        bbr->min_rtt = rs->rtt;
+        bbr->min_rtt_stamp = now;
+    }
+
+    if (expired && bbr->mode != BBR_PROBE_RTT) {
+        bbr_enter_probe_rtt_(tcp, bbr);
+    }
+
+    if (bbr->mode != BBR_PROBE_RTT) {
+        return;
+    }
+
+    if (bbr->probe_rtt_done_stamp == 0 && rs->in_flight <= BBR_MIN_CWND) {
+        // we've drained to the minimum window; stay here for at least a round trip
+        bbr->probe_rtt_done_stamp = now + BBR_PROBE_RTT_DURATION;
+        bbr->probe_rtt_round_done = false;
+        bbr->next_round_delivered = rs->prior_delivered + rs->delivered;
+    } else if (bbr->probe_rtt_done_stamp != 0) {
+        if (bbr->round_start) {
+            bbr->probe_rtt_round_done = true;
+        }
+
+        if (bbr->probe_rtt_round_done && now > bbr->probe_rtt_done_stamp) {
+            bbr->min_rtt_stamp = now;
+            tcp_cong(tcp)->cwnd = MAX(tcp_cong(tcp)->cwnd, bbr->prior_cwnd);
+
+            if (bbr->full_bw_reached) {
+                bbr_enter_probe_bw_(tcp, bbr, now);
+            } else {
+                bbr_enter_startup_(bbr);
+            }
+        }
+    }
+}
+
+/* CONTROL *******************************************************/
+
+static void bbr_set_pacing_rate_(TCP *tcp, CABbr *bbr) {
+    guint64 bw = bbr_max_bw_(bbr);
+
+    if (bw == 0) {
+        // keep the initial pacing rate
+        return;
+    }
+
+    guint64 rate = (guint64)(bbr->pacing_gain * (double)bw);
+
+    // during startup, don't slow down because of a low sample
+    if (bbr->full_bw_reached || rate > tcp_cong(tcp)->pacing_rate) {
+        tcp_cong(tcp)->pacing_rate = rate;
+    }
+}
+
+static void bbr_set_cwnd_(TCP *tcp, CABbr *bbr, const TCPRateSample *rs) {
+    guint32 cwnd = tcp_cong(tcp)->cwnd;
+    guint64 delivered = rs->prior_delivered + rs->delivered;
+
+    if (bbr->recovery_start) {
+        // start a new round trip of packet conservation
+        bbr->recovery_start = false;
+        bbr->packet_conservation = true;
+        bbr->next_round_delivered = delivered;
+        cwnd = rs->in_flight + rs->acked;
+    } else if (bbr->in_recovery && bbr->round_start) {
+        // a round trip has passed since recovery started
+        bbr->in_recovery = false;
+        bbr->packet_conservation = false;
+        cwnd = MAX(cwnd, bbr->prior_cwnd);
+        debug("[CONG] desc=%p bbr leaving recovery", (LegacyFile*)tcp);
+    }
+
+    if (bbr->packet_conservation) {
+        cwnd = MAX(cwnd, rs->in_flight + rs->acked);
+    } else {
+        guint32 target = bbr_target_cwnd_(bbr, bbr->cwnd_gain);
+
+        if (bbr->full_bw_reached) {
+            cwnd = MIN(cwnd + rs->acked, target);
+        } else if (cwnd < target || delivered < BBR_INIT_CWND) {
+            cwnd += rs->acked;
+        }
+
+        cwnd = MAX(cwnd, BBR_MIN_CWND);
+    }
+
+    if (bbr->mode == BBR_PROBE_RTT) {
+        cwnd = MIN(cwnd, BBR_MIN_CWND);
+    }
+
+    tcp_cong(tcp)->cwnd = cwnd;
+}
+
+/*******************************************************************/
+
+static void ca_bbr_init_(TCP *tcp, CABbr *bbr) {
+    *bbr = (CABbr){0};
+    bbr->min_rtt_stamp = worker_getCurrentSimulationTime();
+    bbr_enter_startup_(bbr);
+
+    // until we have a bandwidth estimate, pace the initial window over the rtt (or 1 ms if we
+    // haven't measured it yet)
+    CSimulationTime rtt = (tcp_getMinRTT(tcp) > 0 ? tcp_getMinRTT(tcp) : 1) *
+                          (CSimulationTime)SIMTIME_ONE_MILLISECOND;
+    tcp_cong(tcp)->pacing_rate = (guint64)(BBR_HIGH_GAIN * BBR_INIT_CWND *
+                                           CONFIG_TCP_MAX_SEGMENT_SIZE *
+                                           (double)SIMTIME_ONE_SECOND / (double)rtt);
+}
+
+static void tcp_cong_bbr_delete_(TCP *tcp) {
+    free(tcp_cong(tcp)->ca);
+}
+
+static void tcp_cong_bbr_duplicate_ack_ev_(TCP *tcp) {
+    CABbr *bbr = tcp_cong(tcp)->ca;
+    bbr->duplicate_ack_n++;
+
+    if (bbr->duplicate_ack_n == 3 && !bbr->in_recovery) {
+        debug("[CONG] desc=%p three duplicate acks, bbr entering recovery", (LegacyFile*)tcp);
+        bbr_save_cwnd_(tcp, bbr);
+        bbr->in_recovery = true;
+        bbr->recovery_start = true;
+    }
+}
+
+static bool tcp_cong_bbr_fast_recovery_(TCP *tcp) {
+    CABbr *bbr = tcp_cong(tcp)->ca;
+    return bbr->in_recovery;
+}
+
+/* the window is updated from the rate sample instead */
+static void tcp_cong_bbr_new_ack_ev_(TCP *tcp, guint32 n) {
+    CABbr *bbr = tcp_cong(tcp)->ca;
+    bbr->duplicate_ack_n = 0;
+}
+
+static void tcp_cong_bbr_timeout_ev_(TCP *tcp) {
+    CABbr *bbr = tcp_cong(tcp)->ca;
+
+    bbr->duplicate_ack_n = 0;
+    bbr_save_cwnd_(tcp, bbr);
+    bbr->in_recovery = true;
+    bbr->recovery_start = false;
+    bbr->packet_conservation = false;
+    bbr->full_bw = 0;
+
+    tcp_cong(tcp)->cwnd = BBR_MIN_CWND;
+    debug("[CONG] desc=%p bbr timeout", (LegacyFile*)tcp);
+}
+
+/* bbr doesn't use a slow start threshold */
+static guint32 tcp_cong_bbr_ssthresh_(TCP *tcp) {
+    return INT32_MAX;
+}
+
+static const char* tcp_cong_bbr_name_str_() {
+    return TCP_CONG_BBR_NAME;
+}
+
+static void tcp_cong_bbr_rate_sample_ev_(TCP *tcp, const TCPRateSample *rs) {
+    CABbr *bbr = tcp_cong(tcp)->ca;
+    CSimulationTime now = worker_getCurrentSimulationTime();
+
+    bbr_update_bw_(bbr, rs);
+    bbr_update_cycle_phase_(bbr, rs, now);
+    bbr_check_full_bw_reached_(bbr, rs);
+    bbr_check_drain_(tcp, bbr, rs, now);
+    bbr_update_min_rtt_(tcp, bbr, rs, now);
+
+    bbr_set_pacing_rate_(tcp, bbr);
+    bbr_set_cwnd_(tcp, bbr, rs);
+
+    trace("[CONG] desc=%p bbr mode=%d bw=%" G_GUINT64_FORMAT " min_rtt=%" G_GUINT64_FORMAT
+          " cwnd=%u pacing_rate=%" G_GUINT64_FORMAT,
+          (LegacyFile*)tcp, bbr->mode, bbr_max_bw_(bbr), (guint64)bbr->min_rtt,
+          tcp_cong(tcp)->cwnd, tcp_cong(tcp)->pacing_rate);
+}
+
+static const struct TCPCongHooks_ bbr_hooks_ = {
+    .tcp_cong_delete = tcp_cong_bbr_delete_,
+    .tcp_cong_duplicate_ack_ev = tcp_cong_bbr_duplicate_ack_ev_,
+    .tcp_cong_fast_recovery = tcp_cong_bbr_fast_recovery_,
+    .tcp_cong_new_ack_ev = tcp_cong_bbr_new_ack_ev_,
+    .tcp_cong_timeout_ev = tcp_cong_bbr_timeout_ev_,
+    .tcp_cong_ssthresh = tcp_cong_bbr_ssthresh_,
+    .tcp_cong_name_str = tcp_cong_bbr_name_str_,
+    .tcp_cong_rate_sample_ev = tcp_cong_bbr_rate_sample_ev_,
+    // like linux's bbr, we ignore ecn marks
+    .tcp_cong_ecn_ev = NULL,
+};
+
+void tcp_cong_bbr_init(TCP *tcp) {
+    CABbr *bbr = malloc(sizeof(CABbr));
+    ca_bbr_init_(tcp, bbr);
+
+    tcp_cong(tcp)->cwnd = 1;
+    tcp_cong(tcp)->hooks = (TCPCongHooks*)&bbr_hooks_;
+    tcp_cong(tcp)->ca = bbr;
+}
@@ -0,0 +1,323 @@
+#include "main/host/descriptor/tcp_cong_cubic.h"
+
+#include <math.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#include "lib/logger/logger.h"
+#include "main/core/worker.h"
+#include "main/host/descriptor/descriptor.h"
+#include "main/host/descriptor/tcp.h"
+#include "main/host/descriptor/tcp_cong.h"
+
+const char* TCP_CONG_CUBIC_NAME = "cubic";
+
+/* multiplicative decrease factor and cubic scaling constant (rfc 8312, section 5) */
+#define CUBIC_BETA 0.7
+#define CUBIC_C 0.4
+
+typedef struct CACubic_ {
+
+    const TCPCongHooks *state_hooks;
+
+    size_t duplicate_ack_n;
+
+    guint32 ssthresh;
+
+    /* acks that haven't yet been counted towards a window increase */
+    double cong_avoid_nacked;
+
+    /* window size just before the last reduction */
+    double w_max;
+    /* w_max before the last reduction, used for fast convergence */
+    double w_last_max;
+    /* when the current congestion avoidance epoch started, or 0 if it hasn't started */
+    CSimulationTime epoch_start;
+    /* time (in seconds) that the window takes to grow back to the origin point */
+    double k;
+    /* the window size at the plateau of the cubic function */
+    double origin_point;
+    /* estimated window of standard tcp, for the tcp-friendly region */
+    double w_est;
+
+} CACubic;
+
+/*
+ * Prototype these to avoid circular refs.
+ */
+static inline const struct TCPCongHooks_ *slow_start_hooks_();
+static inline const struct TCPCongHooks_ *fast_recovery_hooks_();
+static inline const struct TCPCongHooks_ *cong_avoid_hooks_();
+
+/* HELPERS *******************************************************/
+
+/*
+ * Remember the window at which the loss occurred and reduce ssthresh (rfc 8312, section 4.5-4.6).
+ */
+static inline void on_congestion_event(TCP *tcp, CACubic *cubic) {
+    double cwnd = tcp_cong(tcp)->cwnd;
+
+    cubic->epoch_start = 0;
+
+    // fast convergence: release bandwidth to new flows if the window is shrinking
+    if (cwnd < cubic->w_last_max) {
+        cubic->w_last_max = cwnd;
+        cubic->w_max = cwnd * (1.0 + CUBIC_BETA) / 2.0;
+    } else {
+        cubic->w_last_max = cwnd;
+        cubic->w_max = cwnd;
+    }
+
+    cubic->ssthresh = MAX((guint32)(cwnd * CUBIC_BETA), 2);
+}
+
+/*
+ * Pass in a non-zero value for n to ack n packets during the transition.
+ */
+static inline void transition_to_cong_avoid(TCP *tcp, CACubic *cubic, guint32 n) {
+    cubic->cong_avoid_nacked = 0;
+    cubic->state_hooks = cong_avoid_hooks_();
+    cubic->state_hooks->tcp_cong_new_ack_ev(tcp, n);
+    debug("[CONG] desc=%p transition_to_cong_avoid", (LegacyFile*)tcp);
+}
+
+/* SLOW START *******************************************************/
+
+static void ca_cubic_slow_start_duplicate_ack_ev_(TCP *tcp) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+    cubic->duplicate_ack_n++;
+
+    if (cubic->duplicate_ack_n == 3) { // transition to fast recovery
+
+        trace("[CONG-AVOID] three duplicate acks");
+        debug("[CONG] desc %p three duplicate acks transition_to_fast_recovery", (LegacyFile*)tcp);
+
+        on_congestion_event(tcp, cubic);
+        tcp_cong(tcp)->cwnd = cubic->ssthresh + 3;
+
+        cubic->state_hooks = fast_recovery_hooks_();
+    }
+}
+
+static void ca_cubic_slow_start_new_ack_ev_(TCP *tcp, guint32 n) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+
+    cubic->duplicate_ack_n = 0;
+
+    guint32 new_cwnd = tcp_cong(tcp)->cwnd;
+    new_cwnd += n;
+
+    if (new_cwnd >= cubic->ssthresh) { // transition to cong avoid
+
+        // up the cwnd to ssthresh and then transition into congestion avoidance with the
+        // leftover acks
+        guint32 nleft = new_cwnd - cubic->ssthresh;
+        tcp_cong(tcp)->cwnd = cubic->ssthresh;
+        transition_to_cong_avoid(tcp, cubic, nleft);
+
+    } else {
+        tcp_cong(tcp)->cwnd = new_cwnd;
+    }
+}
+
+/* FAST RECOVERY *******************************************************/
+
+static void ca_cubic_fast_recovery_duplicate_ack_ev_(TCP *tcp) {
+    tcp_cong(tcp)->cwnd += 1;
+}
+
+static void ca_cubic_fast_recovery_new_ack_ev_(TCP *tcp, guint32 n) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+
+    cubic->duplicate_ack_n = 0;
+    tcp_cong(tcp)->cwnd = cubic->ssthresh;
+
+    transition_to_cong_avoid(tcp, cubic, n);
+}
+
+/* CONG AVOID *******************************************************/
+
+static void ca_cubic_cong_avoid_new_ack_ev_(TCP *tcp, guint32 n) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+
+    cubic->duplicate_ack_n = 0;
+
+    if (n == 0) {
+        return;
+    }
+
+    double cwnd = tcp_cong(tcp)->cwnd;
+    CSimulationTime now = worker_getCurrentSimulationTime();
+
+    if (cubic->epoch_start == 0) {
+        // start of a new congestion avoidance epoch
+        cubic->epoch_start = now;
+        cubic->w_est = cwnd;
+
+        if (cwnd < cubic->w_max) {
+            cubic->k = cbrt((cubic->w_max - cwnd) / CUBIC_C);
+            cubic->origin_point = cubic->w_max;
+        } else {
+            cubic->k = 0;
+            cubic->origin_point = cwnd;
+        }
+    }
+
+    // the window we want to reach one rtt from now (rfc 8312, section 4.1)
+    CSimulationTime elapsed = now - cubic->epoch_start;
+    elapsed += (CSimulationTime)tcp_getMinRTT(tcp) * SIMTIME_ONE_MILLISECOND;
+    double t = (double)elapsed / (double)SIMTIME_ONE_SECOND;
+    double target = cubic->origin_point + CUBIC_C * pow(t - cubic->k, 3);
+
+    // tcp-friendly region: grow at least as fast as standard tcp would (rfc 8312, section 4.2)
+    cubic->w_est += n * (3.0 * (1.0 - CUBIC_BETA) / (1.0 + CUBIC_BETA)) / cwnd;
+    target = MAX(target, cubic->w_est);
+
+    // don't grow by more than half the window per rtt
+    target = MIN(target, 1.5 * cwnd);
+
+    // the number of acks needed to increase the window by one packet
+    double acks_per_increase = (target > cwnd) ? cwnd / (target - cwnd) : 100.0 * cwnd;
+
+    cubic->cong_avoid_nacked += n;
+
+    while (cubic->cong_avoid_nacked >= acks_per_increase) {
+        cubic->cong_avoid_nacked -= acks_per_increase;
+        tcp_cong(tcp)->cwnd += 1;
+    }
+}
+
+/*******************************************************************/
+
+static void ca_cubic_init_(TCP *tcp, CACubic *cubic) {
+    *cubic = (CACubic){0};
+    tcp_cong(tcp)->cwnd = 10;
+    cubic->ssthresh = INT32_MAX;
+    cubic->state_hooks = slow_start_hooks_();
+}
+
+static void tcp_cong_cubic_delete_(TCP *tcp) {
+    free(tcp_cong(tcp)->ca);
+}
+
+static void tcp_cong_cubic_duplicate_ack_ev_(TCP *tcp) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+    cubic->state_hooks->tcp_cong_duplicate_ack_ev(tcp);
+}
+
+static bool tcp_cong_cubic_fast_recovery_(TCP *tcp) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+    return cubic->state_hooks == fast_recovery_hooks_();
+}
+
+static void tcp_cong_cubic_new_ack_ev_(TCP *tcp, guint32 n) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+    cubic->state_hooks->tcp_cong_new_ack_ev(tcp, n);
+}
+
+/* All timeouts have the same behavior! */
+static void tcp_cong_cubic_timeout_ev_(TCP *tcp) {
+
+    CACubic *cubic = tcp_cong(tcp)->ca;
+
+    cubic->duplicate_ack_n = 0;
+    on_congestion_event(tcp, cubic);
+    tcp_cong(tcp)->cwnd = 10;
+
+    // transition to slow start
+    cubic->state_hooks = slow_start_hooks_();
+    debug("[CONG] desc %p transition_to_slow_start", (LegacyFile*)tcp);
+}
+
+/* Reduce the window like a loss does, but without retransmitting (rfc 3168, section 6.1.2). */
+static void tcp_cong_cubic_ecn_ev_(TCP *tcp) {
+
+    CACubic *cubic = tcp_cong(tcp)->ca;
+
+    cubic->duplicate_ack_n = 0;
+    on_congestion_event(tcp, cubic);
+    tcp_cong(tcp)->cwnd = cubic->ssthresh;
+
+    transition_to_cong_avoid(tcp, cubic, 0);
+}
+
+static guint32 tcp_cong_cubic_ssthresh_(TCP *tcp) {
+    CACubic *cubic = tcp_cong(tcp)->ca;
+    return cubic->ssthresh;
+}
+
+static const char* tcp_cong_cubic_name_str_() {
+    return TCP_CONG_CUBIC_NAME;
+}
+
+static const struct TCPCongHooks_ cubic_hooks_ = {
+    .tcp_cong_delete = tcp_cong_cubic_delete_,
+    .tcp_cong_duplicate_ack_ev = tcp_cong_cubic_duplicate_ack_ev_,
+    .tcp_cong_fast_recovery = tcp_cong_cubic_fast_recovery_,
+    .tcp_cong_new_ack_ev = tcp_cong_cubic_new_ack_ev_,
+    .tcp_cong_timeout_ev = tcp_cong_cubic_timeout_ev_,
+    .tcp_cong_ssthresh = tcp_cong_cubic_ssthresh_,
+    .tcp_cong_name_str = tcp_cong_cubic_name_str_,
+    .tcp_cong_rate_sample_ev = NULL,
+    .tcp_cong_ecn_ev = tcp_cong_cubic_ecn_ev_,
+};
+
+void tcp_cong_cubic_init(TCP *tcp) {
+    CACubic *cubic = malloc(sizeof(CACubic));
+    ca_cubic_init_(tcp, cubic);
+
+    tcp_cong(tcp)->cwnd = 1;
+    tcp_cong(tcp)->hooks = (TCPCongHooks*)&cubic_hooks_;
+    tcp_cong(tcp)->ca = cubic;
+}
+
+static const struct TCPCongHooks_ slow_start_hooks__ = {
+    .tcp_cong_delete = NULL,
+    .tcp_cong_duplicate_ack_ev = ca_cubic_slow_start_duplicate_ack_ev_,
+    .tcp_cong_fast_recovery = NULL,
+    .tcp_cong_new_ack_ev = ca_cubic_slow_start_new_ack_ev_,
+    .tcp_cong_timeout_ev = NULL,
+    .tcp_cong_ssthresh = NULL,
+    .tcp_cong_name_str = NULL,
+    .tcp_cong_rate_sample_ev = NULL,
+    .tcp_cong_ecn_ev = NULL,
+};
+
+static const struct TCPCongHooks_ fast_recovery_hooks__ = {
+    .tcp_cong_delete = NULL,
+    .tcp_cong_duplicate_ack_ev = ca_cubic_fast_recovery_duplicate_ack_ev_,
+    .tcp_cong_fast_recovery = NULL,
+    .tcp_cong_new_ack_ev = ca_cubic_fast_recovery_new_ack_ev_,
+    .tcp_cong_timeout_ev = NULL,
+    .tcp_cong_ssthresh = NULL,
+    .tcp_cong_name_str = NULL,
+    .tcp_cong_rate_sample_ev = NULL,
+    .tcp_cong_ecn_ev = NULL,
+};
+
+/* slow start and cong avoidance have the same dupl act behavior */
+static const struct TCPCongHooks_ cong_avoid_hooks__ = {
+    .tcp_cong_delete = NULL,
+    .tcp_cong_duplicate_ack_ev = ca_cubic_slow_start_duplicate_ack_ev_,
+    .tcp_cong_fast_recovery = NULL,
+    .tcp_cong_new_ack_ev = ca_cubic_cong_avoid_new_ack_ev_,
+    .tcp_cong_timeout_ev = NULL,
+    .tcp_cong_ssthresh = NULL,
+    .tcp_cong_name_str = NULL,
+    .tcp_cong_rate_sample_ev = NULL,
+    .tcp_cong_ecn_ev = NULL,
+};
+
+static inline const struct TCPCongHooks_ *slow_start_hooks_() {
+    return &slow_start_hooks__;
+}
+
+static inline const struct TCPCongHooks_ *fast_recovery_hooks_() {
+    return &fast_recovery_hooks__;
+}
+
+static inline const struct TCPCongHooks_ *cong_avoid_hooks_() {
+    return &cong_avoid_hooks__;
+}
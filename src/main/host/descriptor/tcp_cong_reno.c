@@ -162,6 +162,18 @@ static void tcp_cong_reno_timeout_ev_(TCP *tcp) {
     debug("[CONG] desc %p transition_to_slow_start", (LegacyFile*)tcp);
 }
 
+/* Reduce the window like a loss does, but without retransmitting (rfc 3168, section 6.1.2). */
+static void tcp_cong_reno_ecn_ev_(TCP *tcp) {
+
+    CAReno *reno = tcp_cong(tcp)->ca;
+
+    reno->duplicate_ack_n = 0;
+    ssthresh_halve(tcp, reno);
+    tcp_cong(tcp)->cwnd = reno->ssthresh;
+
+    transition_to_cong_avoid(tcp, reno, 0);
+}
+
 static guint32 tcp_cong_reno_ssthresh_(TCP *tcp) {
     CAReno *reno = tcp_cong(tcp)->ca;
     return reno->ssthresh;
@@ -179,6 +191,8 @@ static const struct TCPCongHooks_ reno_hooks_ = {
     .tcp_cong_timeout_ev = tcp_cong_reno_timeout_ev_,
     .tcp_cong_ssthresh = tcp_cong_reno_ssthresh_,
     .tcp_cong_name_str = tcp_cong_reno_name_str_,
+    .tcp_cong_rate_sample_ev = NULL,
+    .tcp_cong_ecn_ev = tcp_cong_reno_ecn_ev_,
 };
 
 void tcp_cong_reno_init(TCP *tcp) {
@@ -198,6 +212,8 @@ static const struct TCPCongHooks_ slow_start_hooks__ = {
     .tcp_cong_timeout_ev = NULL,
     .tcp_cong_ssthresh = NULL,
     .tcp_cong_name_str = NULL,
+    .tcp_cong_rate_sample_ev = NULL,
+    .tcp_cong_ecn_ev = NULL,
 };
 
 static const struct TCPCongHooks_ fast_recovery_hooks__ = {
@@ -208,6 +224,8 @@ static const struct TCPCongHooks_ fast_recovery_hooks__ = {
     .tcp_cong_timeout_ev = NULL,
     .tcp_cong_ssthresh = NULL,
     .tcp_cong_name_str = NULL,
+    .tcp_cong_rate_sample_ev = NULL,
+    .tcp_cong_ecn_ev = NULL,
 };
 
 /* slow start and cong avoidance have the same dupl act behavior */
@@ -219,6 +237,8 @@ static const struct TCPCongHooks_ cong_avoid_hooks__ = {
     .tcp_cong_timeout_ev = NULL,
     .tcp_cong_ssthresh = NULL,
     .tcp_cong_name_str = NULL,
+    .tcp_cong_rate_sample_ev = NULL,
+    .tcp_cong_ecn_ev = NULL,
 };
 
 static inline const struct TCPCongHooks_ *slow_start_hooks_() {
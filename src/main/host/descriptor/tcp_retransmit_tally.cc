@@ -174,6 +174,19 @@ static Ranges ranges_subtract(const Ranges &lhs, const Ranges &rhs) {
    return result;
 }
 
+static std::size_t ranges_count_from(const Ranges &ranges, SeqNum from) {
+   std::size_t count = 0;
+
+   for (const auto &range : ranges) {
+      SeqNum first = std::max(range.first, from);
+      if (first < range.second) {
+         count += range.second - first;
+      }
+   }
+
+   return count;
+}
+
 extern "C" {
 
 void retransmit_tally_init(void **p) {
@@ -282,6 +295,21 @@ void retransmit_tally_populate_lost_ranges(const void *p, uint32_t *lost) {
    }
 }
 
+size_t retransmit_tally_num_sacked(const void *p) {
+   auto rt = cast_and_assert(p);
+   return ranges_count_from(rt->sacked_, rt->last_ack_);
+}
+
+size_t retransmit_tally_num_lost(const void *p) {
+   auto rt = cast_and_assert(p);
+   return ranges_count_from(ranges_subtract(rt->marked_lost_, rt->sacked_), rt->last_ack_);
+}
+
+size_t retransmit_tally_num_retransmitted(const void *p) {
+   auto rt = cast_and_assert(p);
+   return ranges_count_from(rt->retransmitted_, rt->last_ack_);
+}
+
 } // extern "C"
 
 RetransmitTally::RetransmitTally()
@@ -44,6 +44,12 @@ void retransmit_tally_clear_retransmitted(void *p);
 size_t retransmit_tally_num_lost_ranges(const void *p);
 void retransmit_tally_populate_lost_ranges(const void *p, uint32_t *lost);
 
+/* The number of packets after the last ack that were sacked, are considered lost (but weren't
+ * sacked), or were retransmitted. */
+size_t retransmit_tally_num_sacked(const void *p);
+size_t retransmit_tally_num_lost(const void *p);
+size_t retransmit_tally_num_retransmitted(const void *p);
+
 #ifdef __cplusplus
 } // extern "C"
 #endif // __cplusplus
@@ -1,7 +1,7 @@
 use std::cell::{Cell, Ref, RefCell, RefMut, UnsafeCell};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::{CStr, CString, OsString};
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddrV4};
 use std::num::NonZeroU8;
 use std::ops::{Deref, DerefMut};
 use std::os::unix::prelude::OsStringExt;
@@ -10,7 +10,7 @@ use std::sync::{Arc, Mutex};
 
 use atomic_refcell::AtomicRefCell;
 use linux_api::signal::{siginfo_t, Signal};
-use log::{debug, trace};
+use log::{debug, info, trace};
 use logger::LogLevel;
 use once_cell::unsync::OnceCell;
 use rand::SeedableRng;
@@ -27,20 +27,24 @@ use shadow_shmem::allocator::ShMemBlock;
 use shadow_tsc::Tsc;
 use vasi_sync::scmutex::SelfContainedMutexGuard;
 
-use crate::core::sim_config::PcapConfig;
-use crate::core::support::configuration::{ProcessFinalState, QDiscMode};
+use crate::core::sim_config::{BandwidthChange, PcapConfig};
+use crate::core::support::configuration::{ProcessFinalState, QDiscMode, TcpCongestionControl};
 use crate::core::work::event::{Event, EventData};
 use crate::core::work::event_queue::EventQueue;
 use crate::core::work::task::TaskRef;
 use crate::core::worker::Worker;
 use crate::cshadow;
 use crate::host::descriptor::socket::abstract_unix_ns::AbstractUnixNamespace;
-use crate::host::network::interface::{FifoPacketPriority, NetworkInterface, PcapOptions};
+use crate::host::descriptor::socket::pathname_unix_ns::PathnameUnixNamespace;
+use crate::host::network::interface::{
+    FifoPacketPriority, NetworkInterface, PcapOptions, ReuseOptions,
+};
 use crate::host::network::namespace::NetworkNamespace;
 use crate::host::process::Process;
 use crate::host::thread::ThreadId;
+use crate::network::graph::BurstLoss;
 use crate::network::relay::{RateLimit, Relay};
-use crate::network::router::Router;
+use crate::network::router::{Router, RouterQueueConfig};
 use crate::network::PacketDevice;
 use crate::utility;
 #[cfg(feature = "perf_timers")]
@@ -54,9 +58,11 @@ pub struct HostParameters {
     pub hostname: CString,
     pub node_id: u32,
     pub ip_addr: libc::in_addr_t,
+    pub ip6_addr: Option<Ipv6Addr>,
     pub sim_end_time: EmulatedTime,
     pub requested_bw_down_bits: u64,
     pub requested_bw_up_bits: u64,
+    pub bandwidth_changes: Vec<BandwidthChange>,
     pub cpu_frequency: u64,
     pub cpu_threshold: Option<SimulationTime>,
     pub cpu_precision: Option<SimulationTime>,
@@ -66,6 +72,9 @@ pub struct HostParameters {
     pub log_level: LogLevel,
     pub pcap_config: Option<PcapConfig>,
     pub qdisc: QDiscMode,
+    pub tcp_congestion_control: TcpCongestionControl,
+    pub tcp_ecn: bool,
+    pub router_queue: RouterQueueConfig,
     pub init_sock_recv_buf_size: u64,
     pub autotune_recv_buf: bool,
     pub init_sock_send_buf_size: u64,
@@ -124,6 +133,14 @@ pub struct Host {
     // Forwards packets from the localhost interface back to itself.
     relay_loopback: Arc<Relay>,
 
+    // The current bandwidth of the internet interface, which may change during the simulation.
+    bw_down_bits: Cell<u64>,
+    bw_up_bits: Cell<u64>,
+
+    // The state of each path's burst loss model, keyed by destination address. True if the path
+    // is in the "bad" state.
+    burst_loss_states: RefCell<HashMap<IpAddr, bool>>,
+
     // a statistics tracker for in/out bytes, CPU, memory, etc.
     tracker: RefCell<Option<SyncSendPointer<cshadow::Tracker>>>,
 
@@ -139,6 +156,9 @@ pub struct Host {
 
     net_ns: NetworkNamespace,
 
+    // Unix sockets bound to filesystem paths.
+    pathname_unix_ns: Arc<AtomicRefCell<PathnameUnixNamespace>>,
+
     // Store as a CString so that we can return a borrowed pointer to C code
     // instead of having to allocate a new string.
     //
@@ -272,6 +292,7 @@ impl Host {
                 params.id,
                 hostname,
                 public_ip,
+                params.ip6_addr,
                 pcap_options,
                 params.qdisc,
                 dns,
@@ -281,7 +302,11 @@ impl Host {
         // Packets that are not for localhost or our public ip go to the router.
         // Use `Ipv4Addr::UNSPECIFIED` for the router to encode this for our
         // routing table logic inside of `Host::get_packet_device()`.
-        let router = Router::new(Ipv4Addr::UNSPECIFIED);
+        let router = Router::new(
+            Ipv4Addr::UNSPECIFIED,
+            &params.router_queue,
+            &mut *random.borrow_mut(),
+        );
         let relay_inet_out = Relay::new(
             RateLimit::BytesPerSecond(params.requested_bw_up_bits / 8),
             net_ns.internet.borrow().get_address(),
@@ -304,6 +329,9 @@ impl Host {
             relay_inet_out: Arc::new(relay_inet_out),
             relay_inet_in: Arc::new(relay_inet_in),
             relay_loopback: Arc::new(relay_loopback),
+            bw_down_bits: Cell::new(params.requested_bw_down_bits),
+            bw_up_bits: Cell::new(params.requested_bw_up_bits),
+            burst_loss_states: RefCell::new(HashMap::new()),
             tracker: RefCell::new(None),
             futex_table: RefCell::new(unsafe { SyncSendPointer::new(cshadow::futextable_new()) }),
             random,
@@ -311,6 +339,9 @@ impl Host {
             shim_shmem_lock: RefCell::new(None),
             cpu,
             net_ns,
+            pathname_unix_ns: Arc::new(AtomicRefCell::new(PathnameUnixNamespace::new(
+                &data_dir_path,
+            ))),
             data_dir_path,
             data_dir_path_cstring,
             thread_id_counter,
@@ -505,6 +536,10 @@ impl Host {
         &self.net_ns.unix
     }
 
+    pub fn pathname_unix_namespace(&self) -> &Arc<AtomicRefCell<PathnameUnixNamespace>> {
+        &self.pathname_unix_ns
+    }
+
     pub fn log_level(&self) -> Option<log::LevelFilter> {
         let level = self.params.log_level;
         log_c2rust::c_to_rust_log_level(level).map(|l| l.to_level_filter())
@@ -544,12 +579,35 @@ impl Host {
 
     #[allow(non_snake_case)]
     pub fn bw_up_kiBps(&self) -> u64 {
-        self.params.requested_bw_up_bits / (8 * 1024)
+        self.bw_up_bits.get() / (8 * 1024)
     }
 
     #[allow(non_snake_case)]
     pub fn bw_down_kiBps(&self) -> u64 {
-        self.params.requested_bw_down_bits / (8 * 1024)
+        self.bw_down_bits.get() / (8 * 1024)
+    }
+
+    /// Change the bandwidth of the internet interface. A `None` value leaves that direction's
+    /// bandwidth unchanged.
+    pub fn set_bandwidth(&self, down_bits: Option<u64>, up_bits: Option<u64>) {
+        if let Some(down_bits) = down_bits {
+            self.bw_down_bits.set(down_bits);
+            self.relay_inet_in
+                .set_rate_limit(RateLimit::BytesPerSecond(down_bits / 8));
+        }
+
+        if let Some(up_bits) = up_bits {
+            self.bw_up_bits.set(up_bits);
+            self.relay_inet_out
+                .set_rate_limit(RateLimit::BytesPerSecond(up_bits / 8));
+        }
+
+        debug!(
+            "Changed bandwidth of host '{}' to {} bwUpKiBps, {} bwDownKiBps",
+            self.name(),
+            self.bw_up_kiBps(),
+            self.bw_down_kiBps(),
+        );
     }
 
     /// Returns `None` if there is no such interface.
@@ -577,6 +635,15 @@ impl Host {
         self.random.borrow_mut()
     }
 
+    /// Returns true if a packet sent to `dst` is lost according to the path's burst loss model.
+    /// Each destination's model state is tracked separately, and is driven by the host's random
+    /// number generator.
+    pub fn sample_burst_loss(&self, dst: IpAddr, model: &BurstLoss) -> bool {
+        let mut states = self.burst_loss_states.borrow_mut();
+        let is_bad = states.entry(dst).or_insert(false);
+        model.sample(is_bad, &mut *self.random.borrow_mut())
+    }
+
     pub fn get_new_event_id(&self) -> u64 {
         let res = self.event_id_counter.get();
         self.event_id_counter.set(res + 1);
@@ -640,6 +707,14 @@ impl Host {
     }
 
     pub fn boot(&self) {
+        // schedule the bandwidth changes from the network events
+        for change in &self.params.bandwidth_changes {
+            let change = *change;
+            let task =
+                TaskRef::new(move |host| host.set_bandwidth(change.down_bits, change.up_bits));
+            self.schedule_task_at_emulated_time(task, EmulatedTime::SIMULATION_START + change.time);
+        }
+
         // must be done after the default IP exists so tracker_heartbeat works
         if let Some(heartbeat_interval) = self.params.heartbeat_interval {
             let heartbeat_interval = SimulationTime::to_c_simtime(Some(heartbeat_interval));
@@ -667,6 +742,10 @@ impl Host {
         // the network namespace object needs to be cleaned up before it's dropped
         Worker::with_dns(|dns| self.net_ns.cleanup(dns));
 
+        let queue_stats = self.router.borrow().queue_stats();
+        info!("router queue statistics: {queue_stats}");
+        Worker::add_router_queue_stats(self.name(), queue_stats);
+
         assert!(self.processes.borrow().is_empty());
 
         self.stop_execution_timer();
@@ -875,12 +954,18 @@ impl Host {
     ) {
         if let Some(iface) = self.interface_borrow(addr) {
             iface.add_data_source(socket_ptr);
-            match addr {
-                Ipv4Addr::LOCALHOST => self.relay_loopback.notify(self),
-                _ => self.relay_inet_out.notify(self),
-            };
+            self.notify_interface_has_packets(addr);
         }
     }
+
+    /// Call to trigger the forwarding of packets that were generated by the network interface
+    /// itself (for example ICMP replies) rather than by a socket.
+    pub fn notify_interface_has_packets(&self, addr: Ipv4Addr) {
+        match addr {
+            Ipv4Addr::LOCALHOST => self.relay_loopback.notify(self),
+            _ => self.relay_inet_out.notify(self),
+        };
+    }
 }
 
 impl Drop for Host {
@@ -1052,6 +1137,26 @@ mod export {
         hostrc.data_dir_path_cstring.as_ptr()
     }
 
+    /// If `path` is an absolute path outside of the host's data directory that's backed by a unix
+    /// socket file in the data directory, returns the path of that socket file. Otherwise returns
+    /// NULL. The returned string must be freed with `free()`.
+    #[no_mangle]
+    pub unsafe extern "C" fn host_getMirroredUnixSocketPath(
+        hostrc: *const Host,
+        path: *const c_char,
+    ) -> *mut c_char {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        let path = unsafe { CStr::from_ptr(path) };
+
+        let ns = hostrc.pathname_unix_namespace().borrow();
+        let Some(file) = ns.mirrored_socket_file(path) else {
+            return std::ptr::null_mut();
+        };
+
+        let file = utility::pathbuf_to_nul_term_cstring(file);
+        unsafe { libc::strdup(file.as_ptr()) }
+    }
+
     #[no_mangle]
     pub unsafe extern "C" fn host_doesInterfaceExist(
         hostrc: *const Host,
@@ -1080,14 +1185,19 @@ mod export {
             Ipv4Addr::from(u32::from_be(peer_addr)),
             u16::from_be(peer_port),
         );
-        hostrc
-            .net_ns
-            .is_interface_available(protocol_type, src, dst)
+        hostrc.net_ns.is_interface_available(
+            protocol_type,
+            src,
+            dst,
+            ReuseOptions::default(),
+            cshadow::_AssociationFamily_ASSOC_IPV4,
+        )
     }
 
     #[no_mangle]
     pub unsafe extern "C" fn host_disassociateInterface(
         hostrc: *const Host,
+        socket_handle: usize,
         protocol: cshadow::ProtocolType,
         bind_ip: in_addr_t,
         bind_port: in_port_t,
@@ -1104,10 +1214,10 @@ mod export {
         let bind_addr = SocketAddrV4::new(bind_ip, bind_port);
         let peer_addr = SocketAddrV4::new(peer_ip, peer_port);
 
-        // associate the interfaces corresponding to bind_addr with socket
+        // disassociate the socket from the interfaces corresponding to bind_addr
         hostrc
             .net_ns
-            .disassociate_interface(protocol, bind_addr, peer_addr);
+            .disassociate_interface(socket_handle, protocol, bind_addr, peer_addr);
     }
 
     #[no_mangle]
@@ -1132,6 +1242,7 @@ mod export {
                 protocol_type,
                 interface_ip,
                 peer_addr,
+                cshadow::_AssociationFamily_ASSOC_IPV4,
                 hostrc.random.borrow_mut().deref_mut(),
             )
             .unwrap_or(0)
@@ -1347,6 +1458,13 @@ mod export {
         host.notify_socket_has_packets(addr, socket);
     }
 
+    #[no_mangle]
+    pub unsafe extern "C" fn host_interfaceWantsToSend(hostrc: *const Host, addr: in_addr_t) {
+        let host = unsafe { hostrc.as_ref().unwrap() };
+        let addr = u32::from_be(addr).into();
+        host.notify_interface_has_packets(addr);
+    }
+
     #[no_mangle]
     pub unsafe extern "C" fn host_continue(host: *const Host, pid: libc::pid_t, tid: libc::pid_t) {
         let host = unsafe { host.as_ref().unwrap() };
@@ -724,28 +724,29 @@ where
     /// Allocate memory in the current active process.
     /// Must be freed explicitly via `free`.
     pub fn new(ctx: &mut ThreadContext, len: usize) -> Self {
+        Self::try_new(ctx, len).unwrap()
+    }
+
+    /// Allocate memory in the current active process, returning an error if the memory can't be
+    /// mapped. Must be freed explicitly via `free`.
+    pub fn try_new(ctx: &mut ThreadContext, len: usize) -> Result<Self, SyscallError> {
         let prot = libc::PROT_READ | libc::PROT_WRITE;
 
         // Allocate through the MemoryManager, so that it knows about this region.
-        let ptr = ForeignPtr::<()>::from(
-            ctx.process
-                .memory_borrow_mut()
-                .do_mmap(
-                    ctx,
-                    ForeignPtr::null(),
-                    len * std::mem::size_of::<T>(),
-                    prot,
-                    libc::MAP_ANONYMOUS | libc::MAP_PRIVATE,
-                    -1,
-                    0,
-                )
-                .unwrap(),
-        );
-
-        Self {
+        let ptr = ForeignPtr::<()>::from(ctx.process.memory_borrow_mut().do_mmap(
+            ctx,
+            ForeignPtr::null(),
+            len * std::mem::size_of::<T>(),
+            prot,
+            libc::MAP_ANONYMOUS | libc::MAP_PRIVATE,
+            -1,
+            0,
+        )?);
+
+        Ok(Self {
             ptr: ForeignArrayPtr::new(ptr.cast::<T>(), len),
             freed: false,
-        }
+        })
     }
 
     /// Pointer to the allocated memory.
@@ -14,6 +14,14 @@ use crate::utility::{self, HostTreePointer};
 /// The priority used by the fifo qdisc to choose the next socket to send a packet from.
 pub type FifoPacketPriority = u64;
 
+/// The `SO_REUSEADDR` and `SO_REUSEPORT` options of a socket, which decide whether it can be bound
+/// to an address that another socket is already bound to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReuseOptions {
+    pub addr: bool,
+    pub port: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct PcapOptions {
     pub path: PathBuf,
@@ -80,6 +88,8 @@ impl NetworkInterface {
         protocol_type: c::ProtocolType,
         port: u16,
         peer_addr: SocketAddrV4,
+        reuse: ReuseOptions,
+        family: c::AssociationFamily,
     ) {
         let port = port.to_be();
         let peer_ip = u32::from(*peer_addr.ip()).to_be();
@@ -93,11 +103,22 @@ impl NetworkInterface {
                 port,
                 peer_ip,
                 peer_port,
+                reuse.addr.into(),
+                reuse.port.into(),
+                family,
             )
         };
     }
 
-    pub fn disassociate(&self, protocol_type: c::ProtocolType, port: u16, peer_addr: SocketAddrV4) {
+    /// Disassociate the socket with the canonical handle `socket_handle`. Other sockets associated
+    /// with the same addresses are not affected.
+    pub fn disassociate(
+        &self,
+        socket_handle: usize,
+        protocol_type: c::ProtocolType,
+        port: u16,
+        peer_addr: SocketAddrV4,
+    ) {
         let port = port.to_be();
         let peer_ip = u32::from(*peer_addr.ip()).to_be();
         let peer_port = peer_addr.port().to_be();
@@ -105,6 +126,7 @@ impl NetworkInterface {
         unsafe {
             c::networkinterface_disassociate(
                 self.c_ptr.ptr(),
+                socket_handle,
                 protocol_type,
                 port,
                 peer_ip,
@@ -113,13 +135,53 @@ impl NetworkInterface {
         };
     }
 
-    pub fn is_associated(&self, protocol: c::ProtocolType, port: u16, peer: SocketAddrV4) -> bool {
+    /// Returns true if a socket of the association family `family` with the reuse options `reuse`
+    /// can't be associated with the port and peer because of an existing association.
+    pub fn is_associated(
+        &self,
+        protocol: c::ProtocolType,
+        port: u16,
+        peer: SocketAddrV4,
+        reuse: ReuseOptions,
+        family: c::AssociationFamily,
+    ) -> bool {
         let port = port.to_be();
         let peer_ip = u32::from(*peer.ip()).to_be();
         let peer_port = peer.port().to_be();
 
         (unsafe {
-            c::networkinterface_isAssociated(self.c_ptr.ptr(), protocol, port, peer_ip, peer_port)
+            c::networkinterface_isAssociated(
+                self.c_ptr.ptr(),
+                protocol,
+                port,
+                peer_ip,
+                peer_port,
+                reuse.addr.into(),
+                reuse.port.into(),
+                family,
+            )
+        }) != 0
+    }
+
+    /// Returns true if a TCP socket other than the one with the canonical handle `socket_handle`
+    /// is listening on the port, and the two sockets can't share it.
+    pub fn is_listen_conflict(
+        &self,
+        socket_handle: usize,
+        port: u16,
+        reuse_port: bool,
+        family: c::AssociationFamily,
+    ) -> bool {
+        let port = port.to_be();
+
+        (unsafe {
+            c::networkinterface_isListenConflict(
+                self.c_ptr.ptr(),
+                socket_handle,
+                port,
+                reuse_port.into(),
+                family,
+            )
         }) != 0
     }
 
@@ -1,6 +1,7 @@
 use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::ffi::{CString, OsStr};
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4};
 use std::num::NonZeroU8;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
@@ -13,7 +14,7 @@ use crate::core::support::configuration::QDiscMode;
 use crate::core::worker::Worker;
 use crate::cshadow;
 use crate::host::descriptor::socket::abstract_unix_ns::AbstractUnixNamespace;
-use crate::host::network::interface::{NetworkInterface, PcapOptions};
+use crate::host::network::interface::{NetworkInterface, PcapOptions, ReuseOptions};
 
 // The start of our random port range in host order, used if application doesn't
 // specify the port it wants to bind to, and for client connections.
@@ -34,6 +35,11 @@ pub struct NetworkNamespace {
     pub default_address: SyncSendPointer<cshadow::Address>,
     pub default_ip: Ipv4Addr,
 
+    host_id: HostId,
+
+    // the number of memberships of each multicast group joined by sockets in this namespace
+    multicast_groups: RefCell<HashMap<Ipv4Addr, usize>>,
+
     // used for debugging to make sure we've cleaned up before being dropped
     has_run_cleanup: Cell<bool>,
 }
@@ -46,6 +52,7 @@ impl NetworkNamespace {
         host_id: HostId,
         hostname: Vec<NonZeroU8>,
         public_ip: Ipv4Addr,
+        public_ip6: Option<Ipv6Addr>,
         pcap: Option<PcapOptions>,
         qdisc: QDiscMode,
         dns: *mut cshadow::DNS,
@@ -71,7 +78,7 @@ impl NetworkNamespace {
                 OsStr::new("eth0"),
                 &InterfaceOptions {
                     host_id,
-                    hostname,
+                    hostname: hostname.clone(),
                     ip: public_ip,
                     pcap,
                     qdisc,
@@ -80,12 +87,23 @@ impl NetworkNamespace {
             )
         };
 
+        if let Some(public_ip6) = public_ip6 {
+            let hostname: CString = hostname.into();
+            let ip6 = libc::in6_addr {
+                s6_addr: public_ip6.octets(),
+            };
+            let ip6 = &ip6 as *const libc::in6_addr;
+            unsafe { cshadow::dns_registerIPv6(dns, hostname.as_ptr(), ip6.cast()) };
+        }
+
         Self {
             unix: Arc::new(AtomicRefCell::new(AbstractUnixNamespace::new())),
             localhost: RefCell::new(localhost),
             internet: RefCell::new(internet),
             default_address: unsafe { SyncSendPointer::new(public_addr) },
             default_ip: public_ip,
+            host_id,
+            multicast_groups: RefCell::new(HashMap::new()),
             has_run_cleanup: Cell::new(false),
         }
     }
@@ -169,26 +187,62 @@ impl NetworkNamespace {
         }
     }
 
+    /// Returns true if a socket of the association family `family` with the reuse options `reuse`
+    /// can be associated with `src` and `dst`.
     pub fn is_interface_available(
         &self,
         protocol_type: cshadow::ProtocolType,
         src: SocketAddrV4,
         dst: SocketAddrV4,
+        reuse: ReuseOptions,
+        family: cshadow::AssociationFamily,
     ) -> bool {
         if src.ip().is_unspecified() {
             // Check that all interfaces are available.
             !self
                 .localhost
                 .borrow()
-                .is_associated(protocol_type, src.port(), dst)
-                && !self
-                    .internet
-                    .borrow()
-                    .is_associated(protocol_type, src.port(), dst)
+                .is_associated(protocol_type, src.port(), dst, reuse, family)
+                && !self.internet.borrow().is_associated(
+                    protocol_type,
+                    src.port(),
+                    dst,
+                    reuse,
+                    family,
+                )
         } else {
             // The interface is not available if it does not exist.
             match self.interface_borrow(*src.ip()) {
-                Some(i) => !i.is_associated(protocol_type, src.port(), dst),
+                Some(i) => !i.is_associated(protocol_type, src.port(), dst, reuse, family),
+                None => false,
+            }
+        }
+    }
+
+    /// Returns true if the TCP socket with the canonical handle `socket_handle` can't start
+    /// listening on `addr` because a different socket is already listening there.
+    pub fn is_listen_conflict(
+        &self,
+        socket_handle: usize,
+        addr: SocketAddrV4,
+        reuse_port: bool,
+        family: cshadow::AssociationFamily,
+    ) -> bool {
+        if addr.ip().is_unspecified() {
+            self.localhost.borrow().is_listen_conflict(
+                socket_handle,
+                addr.port(),
+                reuse_port,
+                family,
+            ) || self.internet.borrow().is_listen_conflict(
+                socket_handle,
+                addr.port(),
+                reuse_port,
+                family,
+            )
+        } else {
+            match self.interface_borrow(*addr.ip()) {
+                Some(i) => i.is_listen_conflict(socket_handle, addr.port(), reuse_port, family),
                 None => false,
             }
         }
@@ -200,6 +254,7 @@ impl NetworkNamespace {
         protocol_type: cshadow::ProtocolType,
         interface_ip: Ipv4Addr,
         peer: SocketAddrV4,
+        family: cshadow::AssociationFamily,
         mut rng: impl rand::Rng,
     ) -> Option<u16> {
         // we need a random port that is free everywhere we need it to be.
@@ -217,6 +272,8 @@ impl NetworkNamespace {
                 protocol_type,
                 SocketAddrV4::new(interface_ip, random_port),
                 peer,
+                ReuseOptions::default(),
+                family,
             ) {
                 return Some(random_port);
             }
@@ -231,6 +288,8 @@ impl NetworkNamespace {
                 protocol_type,
                 SocketAddrV4::new(interface_ip, port),
                 peer,
+                ReuseOptions::default(),
+                family,
             ) {
                 return Some(port);
             }
@@ -252,56 +311,112 @@ impl NetworkNamespace {
         protocol: cshadow::ProtocolType,
         bind_addr: SocketAddrV4,
         peer_addr: SocketAddrV4,
+        reuse: ReuseOptions,
+        family: cshadow::AssociationFamily,
     ) -> AssociationHandle {
         if bind_addr.ip().is_unspecified() {
             // need to associate all interfaces
-            self.localhost
-                .borrow()
-                .associate(socket, protocol, bind_addr.port(), peer_addr);
-            self.internet
-                .borrow()
-                .associate(socket, protocol, bind_addr.port(), peer_addr);
+            self.localhost.borrow().associate(
+                socket,
+                protocol,
+                bind_addr.port(),
+                peer_addr,
+                reuse,
+                family,
+            );
+            self.internet.borrow().associate(
+                socket,
+                protocol,
+                bind_addr.port(),
+                peer_addr,
+                reuse,
+                family,
+            );
         } else {
             // TODO: return error if interface does not exist
             if let Some(iface) = self.interface_borrow(*bind_addr.ip()) {
-                iface.associate(socket, protocol, bind_addr.port(), peer_addr);
+                iface.associate(socket, protocol, bind_addr.port(), peer_addr, reuse, family);
             }
         }
 
         AssociationHandle {
+            socket_handle: unsafe { cshadow::compatsocket_getCanonicalHandle(socket) },
             protocol,
             local_addr: bind_addr,
             remote_addr: peer_addr,
         }
     }
 
-    /// Disassociate the socket associated using the local and remote addresses from all network
-    /// interfaces.
+    /// Disassociate the socket with the canonical handle `socket_handle`, associated using the
+    /// local and remote addresses, from all network interfaces.
     ///
     /// Is only public so that it can be called from `host_disassociateInterface`. Normally this
     /// should only be called from the [`AssociationHandle`].
     pub fn disassociate_interface(
         &self,
+        socket_handle: usize,
         protocol: cshadow::ProtocolType,
         bind_addr: SocketAddrV4,
         peer_addr: SocketAddrV4,
     ) {
         if bind_addr.ip().is_unspecified() {
             // need to disassociate all interfaces
-            self.localhost
-                .borrow()
-                .disassociate(protocol, bind_addr.port(), peer_addr);
+            self.localhost.borrow().disassociate(
+                socket_handle,
+                protocol,
+                bind_addr.port(),
+                peer_addr,
+            );
 
-            self.internet
-                .borrow()
-                .disassociate(protocol, bind_addr.port(), peer_addr);
+            self.internet.borrow().disassociate(
+                socket_handle,
+                protocol,
+                bind_addr.port(),
+                peer_addr,
+            );
         } else {
             // TODO: return error if interface does not exist
             if let Some(iface) = self.interface_borrow(*bind_addr.ip()) {
-                iface.disassociate(protocol, bind_addr.port(), peer_addr);
+                iface.disassociate(socket_handle, protocol, bind_addr.port(), peer_addr);
             }
         }
     }
+
+    /// Add a membership of the multicast group `group`. The host is a member of the group while it
+    /// has at least one membership, and the membership is removed when the returned handle is
+    /// dropped. Other hosts start sending the group's packets to this host from the next
+    /// scheduling round.
+    pub fn join_multicast_group(&self, group: Ipv4Addr) -> MembershipHandle {
+        assert!(group.is_multicast());
+
+        let mut groups = self.multicast_groups.borrow_mut();
+        let count = groups.entry(group).or_insert(0);
+        *count += 1;
+
+        if *count == 1 {
+            Worker::update_multicast_membership(self.host_id, group, true);
+        }
+
+        MembershipHandle { group }
+    }
+
+    /// Remove a membership of the multicast group `group`. Should only be called from the
+    /// [`MembershipHandle`].
+    fn leave_multicast_group(&self, group: Ipv4Addr) {
+        let mut groups = self.multicast_groups.borrow_mut();
+        let count = groups.get_mut(&group).unwrap();
+        *count -= 1;
+
+        if *count == 0 {
+            groups.remove(&group);
+            Worker::update_multicast_membership(self.host_id, group, false);
+        }
+    }
+
+    /// Returns true if the host is a member of the multicast group `group`.
+    pub fn is_multicast_member(&self, group: Ipv4Addr) -> bool {
+        self.multicast_groups.borrow().contains_key(&group)
+    }
 }
 
 impl std::ops::Drop for NetworkNamespace {
@@ -327,6 +442,7 @@ struct InterfaceOptions {
 /// [`callback_queue::Handle`](crate::utility::callback_queue::Handle)).
 #[derive(Debug)]
 pub struct AssociationHandle {
+    socket_handle: usize,
     protocol: cshadow::ProtocolType,
     local_addr: SocketAddrV4,
     remote_addr: SocketAddrV4,
@@ -336,6 +452,7 @@ impl std::ops::Drop for AssociationHandle {
     fn drop(&mut self) {
         Worker::with_active_host(|host| {
             host.network_namespace_borrow().disassociate_interface(
+                self.socket_handle,
                 self.protocol,
                 self.local_addr,
                 self.remote_addr,
@@ -344,3 +461,20 @@ impl std::ops::Drop for AssociationHandle {
         .unwrap();
     }
 }
+
+/// A handle for a socket's membership of a multicast group. The membership will be removed when
+/// this handle is dropped.
+#[derive(Debug)]
+pub struct MembershipHandle {
+    group: Ipv4Addr,
+}
+
+impl std::ops::Drop for MembershipHandle {
+    fn drop(&mut self) {
+        Worker::with_active_host(|host| {
+            host.network_namespace_borrow()
+                .leave_multicast_group(self.group);
+        })
+        .unwrap();
+    }
+}
@@ -7,6 +7,7 @@
 #include <glib.h>
 #include <netinet/in.h>
 #include <stddef.h>
+#include <string.h>
 
 #include "lib/logger/logger.h"
 #include "main/bindings/c/bindings.h"
@@ -33,62 +34,138 @@ struct _NetworkInterface {
     /* The address associated with this interface */
     Address* address;
 
-    /* (protocol,port)-to-socket bindings. Stores CompatSocket objects as tagged pointers. */
+    /* (protocol,port)-to-socket bindings. Each key maps to a GArray of BoundSocket entries in the
+     * order that the sockets were bound. More than one socket can only share a key if they were
+     * bound with SO_REUSEADDR or SO_REUSEPORT. */
     GHashTable* boundSockets;
 
     /* Transports wanting to send data out. */
     RrSocketQueue rrQueue;
     FifoSocketQueue fifoQueue;
 
+    /* ICMP packets generated by the interface itself (echo replies and errors), which are sent
+     * before any socket packets. */
+    GQueue* icmpQueue;
+
     /* To support capturing incoming and outgoing packets */
     PcapWriter_BufWriter_File* pcap;
 
     MAGIC_DECLARE;
 };
 
-static void _compatsocket_unrefTaggedVoid(void* taggedSocketPtr) {
-    utility_debugAssert(taggedSocketPtr != NULL);
-    if (taggedSocketPtr == NULL) {
+/* A socket associated with the interface, and the options it was bound with. */
+typedef struct _BoundSocket {
+    /* The CompatSocket object stored as a tagged pointer. */
+    uintptr_t taggedSocket;
+    /* The socket's canonical handle. For TCP sockets this is the `TCP` object, which lets us query
+     * the socket without needing to borrow it. */
+    uintptr_t handle;
+    gboolean reuseAddr;
+    gboolean reusePort;
+    /* Whether the socket is an IPv6 socket. IPv6-only sockets use separate keys, so this is only
+     * needed to find dual-stack sockets. */
+    gboolean isIPv6;
+} BoundSocket;
+
+static void _boundsocketgroup_freeVoid(void* groupPtr) {
+    utility_debugAssert(groupPtr != NULL);
+    if (groupPtr == NULL) {
         return;
     }
 
-    uintptr_t taggedSocket = (uintptr_t)taggedSocketPtr;
-    CompatSocket socket = compatsocket_fromTagged(taggedSocket);
-    compatsocket_unref(&socket);
+    GArray* group = groupPtr;
+    for (guint i = 0; i < group->len; i++) {
+        BoundSocket* entry = &g_array_index(group, BoundSocket, i);
+        CompatSocket socket = compatsocket_fromTagged(entry->taggedSocket);
+        compatsocket_unref(&socket);
+    }
+
+    g_array_free(group, TRUE);
 }
 
-/* The address and ports must be in network byte order. */
-static gchar* _networkinterface_getAssociationKey(NetworkInterface* interface,
-        ProtocolType type, in_port_t port, in_addr_t peerAddr, in_port_t peerPort) {
+/* Returns true if the socket is a TCP socket in the listening state. */
+static gboolean _boundsocket_isListening(const BoundSocket* entry, ProtocolType type) {
+    return type == PTCP && tcp_isValidListener((TCP*)entry->handle);
+}
+
+/* Returns true if a new socket with the given options can share a key with the existing socket,
+ * following the rules that Linux uses for SO_REUSEADDR and SO_REUSEPORT. */
+static gboolean _boundsocket_canShare(const BoundSocket* entry, ProtocolType type,
+                                      gboolean reuseAddr, gboolean reusePort) {
+    if (entry->reusePort && reusePort) {
+        return TRUE;
+    }
+
+    /* a listening tcp socket can't share its address with SO_REUSEADDR alone */
+    if (entry->reuseAddr && reuseAddr && !_boundsocket_isListening(entry, type)) {
+        return TRUE;
+    }
+
+    return FALSE;
+}
+
+/* The address and ports must be in network byte order. IPv6-only sockets are associated using
+ * separate keys, so that they can share addresses with IPv4 sockets. */
+static gchar* _networkinterface_getAssociationKey(NetworkInterface* interface, ProtocolType type,
+                                                  in_port_t port, in_addr_t peerAddr,
+                                                  in_port_t peerPort, gboolean isIPv6Only) {
     MAGIC_ASSERT(interface);
 
     GString* strBuffer = g_string_new(NULL);
     g_string_printf(strBuffer,
-            "%s|%"G_GUINT32_FORMAT":%"G_GUINT16_FORMAT"|%"G_GUINT32_FORMAT":%"G_GUINT16_FORMAT,
+            "%s|%"G_GUINT32_FORMAT":%"G_GUINT16_FORMAT"|%"G_GUINT32_FORMAT":%"G_GUINT16_FORMAT"%s",
             protocol_toString(type),
             (guint)address_toNetworkIP(interface->address),
-            port, peerAddr, peerPort);
+            port, peerAddr, peerPort, isIPv6Only ? "|v6only" : "");
 
     return g_string_free(strBuffer, FALSE);
 }
 
-/* The address and ports must be in network byte order. */
-gboolean networkinterface_isAssociated(NetworkInterface* interface, ProtocolType type,
-        in_port_t port, in_addr_t peerAddr, in_port_t peerPort) {
-    MAGIC_ASSERT(interface);
+/* Returns true if the socket options conflict with any socket associated using the key. If
+ * `onlyIPv6` is set, only IPv6 sockets are considered. */
+static gboolean _networkinterface_hasConflict(NetworkInterface* interface, const gchar* key,
+                                              ProtocolType type, gboolean reuseAddr,
+                                              gboolean reusePort, gboolean onlyIPv6) {
+    GArray* group = g_hash_table_lookup(interface->boundSockets, key);
+    if (group == NULL) {
+        return FALSE;
+    }
+
+    for (guint i = 0; i < group->len; i++) {
+        const BoundSocket* entry = &g_array_index(group, BoundSocket, i);
+        if (onlyIPv6 && !entry->isIPv6) {
+            continue;
+        }
+        if (!_boundsocket_canShare(entry, type, reuseAddr, reusePort)) {
+            return TRUE;
+        }
+    }
+
+    return FALSE;
+}
 
+/* Returns true if the socket options conflict with any socket associated using the general or
+ * specific keys, which are the IPv6-only keys if `isIPv6Only` is set. If `onlyIPv6` is set, only
+ * IPv6 sockets are considered. */
+static gboolean _networkinterface_hasKeysConflict(NetworkInterface* interface, ProtocolType type,
+                                                  in_port_t port, in_addr_t peerAddr,
+                                                  in_port_t peerPort, gboolean reuseAddr,
+                                                  gboolean reusePort, gboolean isIPv6Only,
+                                                  gboolean onlyIPv6) {
     gboolean isFound = FALSE;
 
     /* we need to check the general key too (ie the ones listening sockets use) */
-    gchar* general = _networkinterface_getAssociationKey(interface, type, port, 0, 0);
-    if(g_hash_table_contains(interface->boundSockets, general)) {
+    gchar* general = _networkinterface_getAssociationKey(interface, type, port, 0, 0, isIPv6Only);
+    if (_networkinterface_hasConflict(interface, general, type, reuseAddr, reusePort, onlyIPv6)) {
         isFound = TRUE;
     }
     g_free(general);
 
     if(!isFound) {
-        gchar* specific = _networkinterface_getAssociationKey(interface, type, port, peerAddr, peerPort);
-        if(g_hash_table_contains(interface->boundSockets, specific)) {
+        gchar* specific = _networkinterface_getAssociationKey(
+            interface, type, port, peerAddr, peerPort, isIPv6Only);
+        if (_networkinterface_hasConflict(
+                interface, specific, type, reuseAddr, reusePort, onlyIPv6)) {
             isFound = TRUE;
         }
         g_free(specific);
@@ -97,34 +174,172 @@ gboolean networkinterface_isAssociated(NetworkInterface* interface, ProtocolType
     return isFound;
 }
 
+/* The address and ports must be in network byte order. */
+gboolean networkinterface_isAssociated(NetworkInterface* interface, ProtocolType type,
+                                       in_port_t port, in_addr_t peerAddr, in_port_t peerPort,
+                                       gboolean reuseAddr, gboolean reusePort,
+                                       AssociationFamily family) {
+    MAGIC_ASSERT(interface);
+
+    /* IPv4 and dual-stack sockets share keys */
+    gboolean isIPv6Only = family == ASSOC_IPV6_ONLY;
+    if (_networkinterface_hasKeysConflict(interface, type, port, peerAddr, peerPort, reuseAddr,
+                                          reusePort, isIPv6Only, FALSE)) {
+        return TRUE;
+    }
+
+    switch (family) {
+        case ASSOC_IPV4: return FALSE;
+        case ASSOC_IPV6_DUAL_STACK:
+            /* also conflicts with IPv6-only sockets */
+            return _networkinterface_hasKeysConflict(interface, type, port, peerAddr, peerPort,
+                                                     reuseAddr, reusePort, TRUE, FALSE);
+        case ASSOC_IPV6_ONLY:
+            /* also conflicts with dual-stack sockets */
+            return _networkinterface_hasKeysConflict(interface, type, port, peerAddr, peerPort,
+                                                     reuseAddr, reusePort, FALSE, TRUE);
+    }
+
+    utility_panic("Unexpected association family %d", family);
+}
+
+/* Returns true if a different TCP socket is listening using the general key, which is the
+ * IPv6-only key if `isIPv6Only` is set. If `onlyIPv6` is set, only IPv6 sockets are considered. */
+static gboolean _networkinterface_isKeyListenConflict(NetworkInterface* interface,
+                                                      uintptr_t socketHandle, in_port_t port,
+                                                      gboolean reusePort, gboolean isIPv6Only,
+                                                      gboolean onlyIPv6) {
+    gchar* key = _networkinterface_getAssociationKey(interface, PTCP, port, 0, 0, isIPv6Only);
+    GArray* group = g_hash_table_lookup(interface->boundSockets, key);
+    g_free(key);
+
+    if (group == NULL) {
+        return FALSE;
+    }
+
+    for (guint i = 0; i < group->len; i++) {
+        const BoundSocket* entry = &g_array_index(group, BoundSocket, i);
+        if (entry->handle == socketHandle || !_boundsocket_isListening(entry, PTCP)) {
+            continue;
+        }
+        if (onlyIPv6 && !entry->isIPv6) {
+            continue;
+        }
+
+        /* only SO_REUSEPORT allows more than one socket to listen on the same address */
+        if (!entry->reusePort || !reusePort) {
+            return TRUE;
+        }
+    }
+
+    return FALSE;
+}
+
+/* The port must be in network byte order. */
+gboolean networkinterface_isListenConflict(NetworkInterface* interface, uintptr_t socketHandle,
+                                           in_port_t port, gboolean reusePort,
+                                           AssociationFamily family) {
+    MAGIC_ASSERT(interface);
+
+    /* IPv4 and dual-stack sockets share keys */
+    gboolean isIPv6Only = family == ASSOC_IPV6_ONLY;
+    if (_networkinterface_isKeyListenConflict(
+            interface, socketHandle, port, reusePort, isIPv6Only, FALSE)) {
+        return TRUE;
+    }
+
+    switch (family) {
+        case ASSOC_IPV4: return FALSE;
+        case ASSOC_IPV6_DUAL_STACK:
+            /* also conflicts with IPv6-only sockets */
+            return _networkinterface_isKeyListenConflict(
+                interface, socketHandle, port, reusePort, TRUE, FALSE);
+        case ASSOC_IPV6_ONLY:
+            /* also conflicts with dual-stack sockets */
+            return _networkinterface_isKeyListenConflict(
+                interface, socketHandle, port, reusePort, FALSE, TRUE);
+    }
+
+    utility_panic("Unexpected association family %d", family);
+}
+
 void networkinterface_associate(NetworkInterface* interface, const CompatSocket* socket,
                                 ProtocolType type, in_port_t port, in_addr_t peerIP,
-                                in_port_t peerPort) {
+                                in_port_t peerPort, gboolean reuseAddr, gboolean reusePort,
+                                AssociationFamily family) {
     MAGIC_ASSERT(interface);
 
-    gchar* key = _networkinterface_getAssociationKey(interface, type, port, peerIP, peerPort);
+    gchar* key = _networkinterface_getAssociationKey(
+        interface, type, port, peerIP, peerPort, family == ASSOC_IPV6_ONLY);
 
     /* make sure there is no collision */
-    utility_debugAssert(!g_hash_table_contains(interface->boundSockets, key));
+    utility_debugAssert(
+        !_networkinterface_hasConflict(interface, key, type, reuseAddr, reusePort, FALSE));
+
+    GArray* group = g_hash_table_lookup(interface->boundSockets, key);
+    if (group == NULL) {
+        group = g_array_new(FALSE, FALSE, sizeof(BoundSocket));
+        /* key is now owned by table */
+        g_hash_table_insert(interface->boundSockets, key, group);
+    } else {
+        g_free(key);
+        key = NULL;
+    }
 
     /* need to store our own reference to the socket object */
     CompatSocket newSocketRef = compatsocket_refAs(socket);
 
-    /* insert to our storage, key is now owned by table */
-    bool key_did_not_exist = g_hash_table_replace(
-        interface->boundSockets, key, (void*)compatsocket_toTagged(&newSocketRef));
+    BoundSocket entry = {
+        .taggedSocket = compatsocket_toTagged(&newSocketRef),
+        .handle = compatsocket_getCanonicalHandle(socket),
+        .reuseAddr = reuseAddr,
+        .reusePort = reusePort,
+        .isIPv6 = family != ASSOC_IPV4,
+    };
+    g_array_append_val(group, entry);
+
+    trace("associated socket %p with port %u (%u sockets share the key)", (void*)entry.handle,
+          ntohs(port), group->len);
+}
+
+/* Removes the socket from the sockets associated using the key. Returns true if it was found. */
+static gboolean _networkinterface_disassociateKey(NetworkInterface* interface,
+                                                  uintptr_t socketHandle, const gchar* key) {
+    gboolean isFound = FALSE;
+
+    GArray* group = g_hash_table_lookup(interface->boundSockets, key);
+    if (group != NULL) {
+        for (guint i = 0; i < group->len; i++) {
+            BoundSocket* entry = &g_array_index(group, BoundSocket, i);
+            if (entry->handle != socketHandle) {
+                continue;
+            }
+
+            CompatSocket socket = compatsocket_fromTagged(entry->taggedSocket);
+            /* keep the remaining sockets in the order they were bound */
+            g_array_remove_index(group, i);
+            compatsocket_unref(&socket);
+            isFound = TRUE;
+            break;
+        }
 
-    utility_debugAssert(key_did_not_exist);
+        if (group->len == 0) {
+            g_hash_table_remove(interface->boundSockets, key);
+        }
+    }
 
-    trace("associated socket key %s", key);
+    if (isFound) {
+        trace("disassociated socket key %s", key);
+    }
+
+    return isFound;
 }
 
-void networkinterface_disassociate(NetworkInterface* interface, ProtocolType type, in_port_t port,
-                                   in_addr_t peerIP, in_port_t peerPort) {
+void networkinterface_disassociate(NetworkInterface* interface, uintptr_t socketHandle,
+                                   ProtocolType type, in_port_t port, in_addr_t peerIP,
+                                   in_port_t peerPort) {
     MAGIC_ASSERT(interface);
 
-    gchar* key = _networkinterface_getAssociationKey(interface, type, port, peerIP, peerPort);
-
     /* we will no longer receive packets for this port, this unrefs descriptor */
     /* TODO: Return an error if the disassociation fails. Generally the
      * calling code should only try to disassociate a socket if it thinks that the
@@ -133,10 +348,17 @@ void networkinterface_disassociate(NetworkInterface* interface, ProtocolType typ
      * (including ones that have never been associated) and will try to
      * disassociate the same socket multiple times, so we can't just add an assert
      * here. */
-    g_hash_table_remove(interface->boundSockets, key);
-
-    trace("disassociated socket key %s", key);
+    /* the socket handle is unique, so we can check the IPv6-only key if it wasn't found */
+    gchar* key =
+        _networkinterface_getAssociationKey(interface, type, port, peerIP, peerPort, FALSE);
+    gboolean isFound = _networkinterface_disassociateKey(interface, socketHandle, key);
     g_free(key);
+
+    if (!isFound) {
+        key = _networkinterface_getAssociationKey(interface, type, port, peerIP, peerPort, TRUE);
+        _networkinterface_disassociateKey(interface, socketHandle, key);
+        g_free(key);
+    }
 }
 
 static void _networkinterface_capturePacket(NetworkInterface* interface, Packet* packet) {
@@ -156,16 +378,219 @@ static void _networkinterface_capturePacket(NetworkInterface* interface, Packet*
     }
 }
 
-static CompatSocket _boundsockets_lookup(GHashTable* table, gchar* key) {
-    void* ptr = g_hash_table_lookup(table, key);
+/* A deterministic hash of the packet's 4-tuple (FNV-1a), used to spread packets across sockets in a
+ * SO_REUSEPORT group. All packets of a flow are given to the same socket. */
+static guint32 _networkinterface_hashFlow(in_addr_t localIP, in_port_t localPort, in_addr_t peerIP,
+                                          in_port_t peerPort) {
+    guint8 bytes[12];
+    memcpy(&bytes[0], &localIP, sizeof(localIP));
+    memcpy(&bytes[4], &peerIP, sizeof(peerIP));
+    memcpy(&bytes[8], &localPort, sizeof(localPort));
+    memcpy(&bytes[10], &peerPort, sizeof(peerPort));
+
+    guint32 hash = 2166136261u;
+    for (size_t i = 0; i < sizeof(bytes); i++) {
+        hash ^= bytes[i];
+        hash *= 16777619u;
+    }
+    return hash;
+}
+
+/* Returns true if the socket should be considered when choosing which socket in the group receives
+ * a packet. New TCP connections only go to listening sockets if there are any. */
+static gboolean _boundsocket_isCandidate(const BoundSocket* entry, ProtocolType type,
+                                         gboolean hasListener) {
+    return !hasListener || _boundsocket_isListening(entry, type);
+}
 
-    if (ptr == NULL) {
+/* Choose the socket in the group that should receive a packet from the peer. */
+static CompatSocket _boundsockets_lookup(GHashTable* table, gchar* key, ProtocolType type,
+                                         in_addr_t localIP, in_port_t localPort, in_addr_t peerIP,
+                                         in_port_t peerPort) {
+    GArray* group = g_hash_table_lookup(table, key);
+
+    if (group == NULL || group->len == 0) {
         CompatSocket compatSocket = {0};
         compatSocket.type = CST_NONE;
         return compatSocket;
     }
 
-    return compatsocket_fromTagged((uintptr_t)ptr);
+    if (group->len == 1) {
+        return compatsocket_fromTagged(g_array_index(group, BoundSocket, 0).taggedSocket);
+    }
+
+    gboolean hasListener = FALSE;
+
+    if (type == PTCP) {
+        for (guint i = 0; i < group->len; i++) {
+            const BoundSocket* entry = &g_array_index(group, BoundSocket, i);
+
+            /* packets for an existing connection go to the listener that owns it */
+            if (tcp_hasChild((TCP*)entry->handle, peerIP, peerPort)) {
+                return compatsocket_fromTagged(entry->taggedSocket);
+            }
+
+            hasListener |= _boundsocket_isListening(entry, type);
+        }
+    }
+
+    guint numCandidates = 0;
+    gboolean allReusePort = TRUE;
+    const BoundSocket* lastCandidate = NULL;
+
+    for (guint i = 0; i < group->len; i++) {
+        const BoundSocket* entry = &g_array_index(group, BoundSocket, i);
+        if (_boundsocket_isCandidate(entry, type, hasListener)) {
+            numCandidates++;
+            allReusePort &= entry->reusePort;
+            lastCandidate = entry;
+        }
+    }
+
+    utility_debugAssert(lastCandidate != NULL);
+
+    /* sockets that only share the key using SO_REUSEADDR don't balance the load, and the most
+     * recently bound socket receives the packets */
+    if (!allReusePort) {
+        return compatsocket_fromTagged(lastCandidate->taggedSocket);
+    }
+
+    guint32 hash = _networkinterface_hashFlow(localIP, localPort, peerIP, peerPort);
+    guint32 index = hash % numCandidates;
+
+    for (guint i = 0; i < group->len; i++) {
+        const BoundSocket* entry = &g_array_index(group, BoundSocket, i);
+        if (_boundsocket_isCandidate(entry, type, hasListener)) {
+            if (index == 0) {
+                return compatsocket_fromTagged(entry->taggedSocket);
+            }
+            index--;
+        }
+    }
+
+    utility_panic("Unable to select a socket from the group");
+}
+
+/* Queue an ICMP packet generated by this interface to be sent. Takes ownership of the packet. */
+static void _networkinterface_sendICMP(NetworkInterface* interface, const Host* host,
+                                       Packet* packet) {
+    MAGIC_ASSERT(interface);
+
+    packet_addDeliveryStatus(packet, PDS_SND_CREATED);
+    g_queue_push_tail(interface->icmpQueue, packet);
+
+    /* trigger the relay to forward the packet */
+    host_interfaceWantsToSend(host, address_toNetworkIP(interface->address));
+}
+
+/* Reply to an ICMP echo request, like the kernel would. */
+static void _networkinterface_replyICMPEcho(NetworkInterface* interface, const Host* host,
+                                            const Packet* request) {
+    Packet* reply = packet_new(host);
+    packet_setICMPEchoReply(reply, request);
+    _networkinterface_sendICMP(interface, host, reply);
+}
+
+/* Tell the sender of a UDP packet that there is no socket bound to the destination port. */
+static void _networkinterface_replyICMPPortUnreachable(NetworkInterface* interface,
+                                                       const Host* host, const Packet* original) {
+    Packet* error = packet_new(host);
+    packet_setICMPDestinationUnreachable(error, PICMP_CODE_PORT_UNREACH, original);
+    _networkinterface_sendICMP(interface, host, error);
+}
+
+/* Returns true if the address (in network byte order) is a multicast or broadcast address. */
+static gboolean _networkinterface_isGroupAddress(in_addr_t ip) {
+    return IN_MULTICAST(ntohl(ip)) || ip == htonl(INADDR_BROADCAST);
+}
+
+/* Append all sockets associated using the key to `sockets`. */
+static void _boundsockets_collect(GHashTable* table, gchar* key, GArray* sockets) {
+    GArray* group = g_hash_table_lookup(table, key);
+    if (group == NULL) {
+        return;
+    }
+
+    for (guint i = 0; i < group->len; i++) {
+        CompatSocket socket =
+            compatsocket_fromTagged(g_array_index(group, BoundSocket, i).taggedSocket);
+        g_array_append_val(sockets, socket);
+    }
+}
+
+/* Deliver a multicast or broadcast UDP packet to every socket bound to its destination port, rather
+ * than to a single socket. Like linux, no ICMP error is sent if there is no such socket. */
+static void _networkinterface_pushGroupPacket(NetworkInterface* interface, const Host* host,
+                                              Packet* packet) {
+    in_port_t bindPort = packet_getDestinationPort(packet);
+    in_addr_t peerIP = packet_getSourceIP(packet);
+    in_port_t peerPort = packet_getSourcePort(packet);
+
+    GArray* sockets = g_array_new(FALSE, FALSE, sizeof(CompatSocket));
+
+    /* only IPv4 group addresses are supported, so IPv6-only sockets never receive these */
+    gchar* key =
+        _networkinterface_getAssociationKey(interface, PUDP, bindPort, peerIP, peerPort, FALSE);
+    _boundsockets_collect(interface->boundSockets, key, sockets);
+    g_free(key);
+
+    key = _networkinterface_getAssociationKey(interface, PUDP, bindPort, 0, 0, FALSE);
+    _boundsockets_collect(interface->boundSockets, key, sockets);
+    g_free(key);
+
+    if (interface->pcap) {
+        _networkinterface_capturePacket(interface, packet);
+    }
+
+    if (sockets->len == 0) {
+        packet_addDeliveryStatus(packet, PDS_RCV_INTERFACE_DROPPED);
+        g_array_free(sockets, TRUE);
+        return;
+    }
+
+    /* pushing a packet to a socket may cause sockets to be disassociated and freed, so we need to
+     * hold a reference to all of them before pushing */
+    for (guint i = 0; i < sockets->len; i++) {
+        CompatSocket* socket = &g_array_index(sockets, CompatSocket, i);
+        *socket = compatsocket_refAs(socket);
+    }
+
+    Tracker* tracker = host_getTracker(host);
+
+    for (guint i = 0; i < sockets->len; i++) {
+        CompatSocket* socket = &g_array_index(sockets, CompatSocket, i);
+        compatsocket_pushInPacket(socket, host, packet);
+
+        /* the packet was only received once by the interface */
+        if (tracker != NULL && i == 0) {
+            tracker_addInputBytes(tracker, packet, socket);
+        }
+    }
+
+    for (guint i = 0; i < sockets->len; i++) {
+        compatsocket_unref(&g_array_index(sockets, CompatSocket, i));
+    }
+
+    g_array_free(sockets, TRUE);
+}
+
+/* Find the socket that should receive a packet from the sockets associated using the general or
+ * specific key, which is the IPv6-only key if `isIPv6Only` is set. The address and ports must be
+ * in network byte order. */
+static CompatSocket _networkinterface_lookup(NetworkInterface* interface, ProtocolType ptype,
+                                             in_addr_t bindIP, in_port_t bindPort,
+                                             in_addr_t peerIP, in_port_t peerPort,
+                                             gboolean isGeneral, gboolean isIPv6Only) {
+    gchar* key = _networkinterface_getAssociationKey(interface, ptype, bindPort,
+                                                     isGeneral ? 0 : peerIP,
+                                                     isGeneral ? 0 : peerPort, isIPv6Only);
+    trace("looking for socket associated with %s key %s", isGeneral ? "general" : "specific", key);
+
+    CompatSocket socket = _boundsockets_lookup(
+        interface->boundSockets, key, ptype, bindIP, bindPort, peerIP, peerPort);
+    g_free(key);
+
+    return socket;
 }
 
 void networkinterface_push(NetworkInterface* interface, Packet* packet) {
@@ -181,23 +606,79 @@ void networkinterface_push(NetworkInterface* interface, Packet* packet) {
 
     /* hand it off to the correct socket layer */
     ProtocolType ptype = packet_getProtocol(packet);
+    in_addr_t bindIP = packet_getDestinationIP(packet);
     in_port_t bindPort = packet_getDestinationPort(packet);
     in_addr_t peerIP = packet_getSourceIP(packet);
     in_port_t peerPort = packet_getSourcePort(packet);
 
+    if (ptype == PUDP && _networkinterface_isGroupAddress(bindIP)) {
+        _networkinterface_pushGroupPacket(interface, host, packet);
+        return;
+    }
+
+    if (ptype == PICMP) {
+        PacketICMPHeader* header = packet_getICMPHeader(packet);
+
+        if (header->type == PICMP_ECHO_REQUEST) {
+            /* echo requests are answered by the interface and never reach a socket */
+            if (interface->pcap) {
+                _networkinterface_capturePacket(interface, packet);
+            }
+            packet_addDeliveryStatus(packet, PDS_RCV_SOCKET_PROCESSED);
+
+            /* count our bandwidth usage by interface, since there is no socket */
+            Tracker* tracker = host_getTracker(host);
+            if (tracker != NULL) {
+                tracker_addInputBytes(tracker, packet, NULL);
+            }
+
+            _networkinterface_replyICMPEcho(interface, host, packet);
+            return;
+        }
+
+        if (header->type == PICMP_TIME_EXCEEDED) {
+            /* linux only reports these to sockets with IP_RECVERR enabled, which we don't
+             * support, so they're only captured and counted */
+            if (interface->pcap) {
+                _networkinterface_capturePacket(interface, packet);
+            }
+            packet_addDeliveryStatus(packet, PDS_RCV_INTERFACE_DROPPED);
+
+            Tracker* tracker = host_getTracker(host);
+            if (tracker != NULL) {
+                tracker_addInputBytes(tracker, packet, NULL);
+            }
+            return;
+        }
+
+        if (header->type == PICMP_DEST_UNREACH) {
+            /* errors are delivered to the socket that sent the packet that caused the error */
+            ptype = header->originalProtocol;
+            bindPort = header->originalSourcePort;
+            peerIP = header->originalDestinationIP;
+            peerPort = header->originalDestinationPort;
+        }
+    }
+
     /* first check for a socket with the specific association */
-    gchar* key = _networkinterface_getAssociationKey(interface, ptype, bindPort, peerIP, peerPort);
-    trace("looking for socket associated with specific key %s", key);
+    CompatSocket socket = _networkinterface_lookup(
+        interface, ptype, bindIP, bindPort, peerIP, peerPort, FALSE, FALSE);
 
-    CompatSocket socket = _boundsockets_lookup(interface->boundSockets, key);
-    g_free(key);
+    if (socket.type == CST_NONE) {
+        socket = _networkinterface_lookup(
+            interface, ptype, bindIP, bindPort, peerIP, peerPort, FALSE, TRUE);
+    }
+
+    /* then check for a socket with a wildcard association, where IPv6-only sockets only receive
+     * packets that were sent as IPv6 packets */
+    if (socket.type == CST_NONE && packet_isIPv6(packet)) {
+        socket = _networkinterface_lookup(
+            interface, ptype, bindIP, bindPort, peerIP, peerPort, TRUE, TRUE);
+    }
 
     if (socket.type == CST_NONE) {
-        /* then check for a socket with a wildcard association */
-        key = _networkinterface_getAssociationKey(interface, ptype, bindPort, 0, 0);
-        trace("looking for socket associated with general key %s", key);
-        socket = _boundsockets_lookup(interface->boundSockets, key);
-        g_free(key);
+        socket = _networkinterface_lookup(
+            interface, ptype, bindIP, bindPort, peerIP, peerPort, TRUE, FALSE);
     }
 
     /* record the packet before we process it, otherwise we may send more packets before we
@@ -218,6 +699,12 @@ void networkinterface_push(NetworkInterface* interface, Packet* packet) {
         compatsocket_pushInPacket(&socket, host, packet);
     } else {
         packet_addDeliveryStatus(packet, PDS_RCV_INTERFACE_DROPPED);
+
+        /* nothing is listening on this port, so let the sender know (but never send an error in
+         * response to an ICMP error) */
+        if (ptype == PUDP && packet_getProtocol(packet) == PUDP) {
+            _networkinterface_replyICMPPortUnreachable(interface, host, packet);
+        }
     }
 
     /* count our bandwidth usage by interface, and by socket if possible */
@@ -337,8 +824,13 @@ Packet* networkinterface_pop(NetworkInterface* interface) {
     // We will have an owned reference, so need to deref later.
     CompatSocket socket = {0};
 
+    // ICMP packets generated by the interface are sent first, and don't belong to a socket.
+    Packet* packet = g_queue_pop_head(interface->icmpQueue);
+
     // Now actually pop and send the packet.
-    Packet* packet = _networkinterface_pop_next_packet_out(interface, src, &socket);
+    if (packet == NULL) {
+        packet = _networkinterface_pop_next_packet_out(interface, src, &socket);
+    }
 
     if (packet != NULL) {
         packet_addDeliveryStatus(packet, PDS_SND_INTERFACE_SENT);
@@ -348,9 +840,11 @@ Packet* networkinterface_pop(NetworkInterface* interface) {
             _networkinterface_capturePacket(interface, packet);
         }
 
+        /* count our bandwidth usage by interface, and by socket if the packet has one (packets
+         * generated by the interface don't) */
         Tracker* tracker = host_getTracker(src);
-        if (tracker != NULL && socket.type != CST_NONE) {
-            tracker_addOutputBytes(tracker, packet, &socket);
+        if (tracker != NULL) {
+            tracker_addOutputBytes(tracker, packet, socket.type != CST_NONE ? &socket : NULL);
         }
     }
 
@@ -401,6 +895,11 @@ void networkinterface_removeAllSockets(NetworkInterface* interface) {
     fifosocketqueue_init(&interface->fifoQueue);
 
     g_hash_table_remove_all(interface->boundSockets);
+
+    /* drop any ICMP packets that haven't been sent yet */
+    while (!g_queue_is_empty(interface->icmpQueue)) {
+        packet_unref(g_queue_pop_head(interface->icmpQueue));
+    }
 }
 
 NetworkInterface* networkinterface_new(Address* address, const char* name, const gchar* pcapDir,
@@ -413,12 +912,14 @@ NetworkInterface* networkinterface_new(Address* address, const char* name, const
 
     /* incoming packets get passed along to sockets */
     interface->boundSockets =
-        g_hash_table_new_full(g_str_hash, g_str_equal, g_free, _compatsocket_unrefTaggedVoid);
+        g_hash_table_new_full(g_str_hash, g_str_equal, g_free, _boundsocketgroup_freeVoid);
 
     /* sockets tell us when they want to start sending */
     rrsocketqueue_init(&interface->rrQueue);
     fifosocketqueue_init(&interface->fifoQueue);
 
+    interface->icmpQueue = g_queue_new();
+
     /* parse queuing discipline */
     interface->qdisc = qdisc;
 
@@ -454,6 +955,8 @@ void networkinterface_free(NetworkInterface* interface) {
 
     g_hash_table_destroy(interface->boundSockets);
 
+    g_queue_free_full(interface->icmpQueue, packet_unrefTaskFreeFunc);
+
     address_unref(interface->address);
 
     if(interface->pcap) {
@@ -23,15 +23,43 @@ NetworkInterface* networkinterface_new(Address* address, const char* name, const
                                        guint32 pcapCaptureSize, QDiscMode qdisc);
 void networkinterface_free(NetworkInterface* interface);
 
-/* The address and ports must be in network byte order. */
+/* The IP versions that an associated socket receives packets for. Shadow only routes IPv4 packets,
+ * so IPv6 sockets are associated using IPv4 addresses, and packets sent by IPv6 sockets are marked
+ * with `packet_setIPv6()`. */
+typedef enum _AssociationFamily {
+    /* an AF_INET socket */
+    ASSOC_IPV4,
+    /* an AF_INET6 socket that also receives IPv4 packets */
+    ASSOC_IPV6_DUAL_STACK,
+    /* an AF_INET6 socket with IPV6_V6ONLY set, which only receives IPv6 packets */
+    ASSOC_IPV6_ONLY,
+} AssociationFamily;
+
+/* Returns true if a socket of the given family and with the given SO_REUSEADDR and SO_REUSEPORT
+ * options can't be associated because of an existing association. Like Linux, IPv4 and dual-stack
+ * sockets conflict with each other, and IPv6-only sockets only conflict with IPv6 sockets. The
+ * address and ports must be in network byte order. */
 gboolean networkinterface_isAssociated(NetworkInterface* interface, ProtocolType type,
-        in_port_t port, in_addr_t peerAddr, in_port_t peerPort);
+                                       in_port_t port, in_addr_t peerAddr, in_port_t peerPort,
+                                       gboolean reuseAddr, gboolean reusePort,
+                                       AssociationFamily family);
+
+/* Returns true if a different TCP socket that conflicts with the given family is already listening
+ * on the port and the two sockets don't both use SO_REUSEPORT. The port must be in network byte
+ * order. */
+gboolean networkinterface_isListenConflict(NetworkInterface* interface, uintptr_t socketHandle,
+                                           in_port_t port, gboolean reusePort,
+                                           AssociationFamily family);
 
 void networkinterface_associate(NetworkInterface* interface, const CompatSocket* socket,
                                 ProtocolType type, in_port_t port, in_addr_t peerIP,
-                                in_port_t peerPort);
-void networkinterface_disassociate(NetworkInterface* interface, ProtocolType type, in_port_t port,
-                                   in_addr_t peerIP, in_port_t peerPort);
+                                in_port_t peerPort, gboolean reuseAddr, gboolean reusePort,
+                                AssociationFamily family);
+/* Disassociate the socket with the given canonical handle. Other sockets sharing the association
+ * are not affected. */
+void networkinterface_disassociate(NetworkInterface* interface, uintptr_t socketHandle,
+                                   ProtocolType type, in_port_t port, in_addr_t peerIP,
+                                   in_port_t peerPort);
 
 void networkinterface_wantsSend(NetworkInterface* interface, const CompatSocket* socket);
 
@@ -8,7 +8,7 @@
 #define SHD_PROTOCOL_H_
 
 typedef enum _ProtocolType ProtocolType;
-enum _ProtocolType { PNONE, PLOCAL, PTCP, PUDP, PMOCK };
+enum _ProtocolType { PNONE, PLOCAL, PTCP, PUDP, PMOCK, PICMP };
 
 enum ProtocolLocalFlags {
     PLOCAL_NONE = 0,
@@ -16,6 +16,34 @@ enum ProtocolLocalFlags {
 
 enum ProtocolUDPFlags {
     PUDP_NONE = 0,
+    /* a multicast packet that should also be delivered to the sending host */
+    PUDP_MULTICAST_LOOP = 1 << 0,
+};
+
+/* ECN codepoints, with the same values as the low two bits of the IP type of service byte (rfc
+ * 3168, section 5) */
+enum ProtocolECN {
+    PECN_NOT_ECT = 0,
+    PECN_ECT1 = 1,
+    PECN_ECT0 = 2,
+    PECN_CE = 3,
+};
+
+/* ICMP message types, with the same values as the ICMP header type field */
+enum ProtocolICMPType {
+    PICMP_ECHO_REPLY = 0,
+    PICMP_DEST_UNREACH = 3,
+    PICMP_ECHO_REQUEST = 8,
+    PICMP_TIME_EXCEEDED = 11,
+};
+
+/* ICMP message codes, with the same values as the ICMP header code field */
+enum ProtocolICMPCode {
+    PICMP_CODE_NONE = 0,
+    /* for time exceeded errors */
+    PICMP_CODE_TTL_EXCEEDED = 0,
+    /* for destination unreachable errors */
+    PICMP_CODE_PORT_UNREACH = 3,
 };
 
 enum ProtocolTCPFlags {
@@ -26,6 +54,8 @@ enum ProtocolTCPFlags {
     PTCP_SACK = 1 << 4,
     PTCP_FIN =  1 << 5,
     PTCP_DUPACK =  1 << 6,
+    PTCP_ECE = 1 << 7,
+    PTCP_CWR = 1 << 8,
 };
 
 #endif /* SHD_PROTOCOL_H_ */
@@ -25,6 +25,9 @@ enum _Status {
     /* a listening socket is allowing connections; only applicable to connection-oriented unix
      * sockets */
     STATUS_SOCKET_ALLOWING_CONNECT = 1 << 5,
+    /* a closing tcp socket no longer has unacknowledged data or FIN, either because the peer
+     * acknowledged them or because the connection is gone; used to block a lingering close */
+    STATUS_SOCKET_LINGER_DONE = 1 << 6,
 };
 
 #endif // SRC_MAIN_HOST_STATUS_H
@@ -7,6 +7,9 @@
 
 #include <errno.h>
 #include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
 #include <sys/syscall.h>
 #include <sys/stat.h>
 #include <sys/time.h>
@@ -20,6 +23,7 @@
 #include "main/host/process.h"
 #include "main/host/syscall/kernel_types.h"
 #include "main/host/syscall/protected.h"
+#include "main/utility/utility.h"
 
 ///////////////////////////////////////////////////////////
 // Helpers
@@ -72,6 +76,90 @@ static int _syscallhandler_validateDirAndPathnameHelper(SysCallHandler* sys, int
         _syscallhandler_getProcess(sys), pathnamePtr, PATH_MAX, pathname_out, NULL);
 }
 
+/* If `pathname` is a unix socket path whose socket file is mirrored in the host's data directory
+ * (see `PathnameUnixNamespace`), returns the path of the socket file, which the caller must free.
+ * Otherwise returns NULL. Paths relative to a directory descriptor aren't mirrored. */
+static char* _syscallhandler_getMirroredUnixSocketPath(SysCallHandler* sys, RegularFile* dir_desc,
+                                                       const char* pathname,
+                                                       const char* workingDir) {
+    if (pathname[0] == '\0' || (pathname[0] != '/' && dir_desc != NULL)) {
+        return NULL;
+    }
+
+    char* abspath = NULL;
+    if (pathname[0] == '/') {
+        abspath = strdup(pathname);
+    } else if (asprintf(&abspath, "%s/%s", workingDir, pathname) < 0) {
+        utility_panic("asprintf could not allocate a buffer, error %i: %s", errno, strerror(errno));
+    }
+
+    char* socketPath = host_getMirroredUnixSocketPath(_syscallhandler_getHost(sys), abspath);
+    free(abspath);
+
+    return socketPath;
+}
+
+static SyscallReturn _syscallhandler_fstatatHelper(SysCallHandler* sys, int dirfd,
+                                                   UntypedForeignPtr pathnamePtr,
+                                                   UntypedForeignPtr bufPtr, int flags) {
+    /* Validate params. */
+    RegularFile* dir_desc = NULL;
+
+    ssize_t errcode = _syscallhandler_validateDirHelper(sys, dirfd, &dir_desc);
+    if (errcode < 0) {
+        return syscallreturn_makeDoneErrno(-errcode);
+    }
+
+    /* Copy the path rather than getting a reference, so that the MemoryManager
+     * will still allow us to get a mutable reference to memory below.
+     */
+    char pathname[PATH_MAX];
+    errcode = process_readString(_syscallhandler_getProcess(sys), pathname, pathnamePtr, PATH_MAX);
+    if (errcode < 0) {
+        return syscallreturn_makeDoneErrno(-errcode);
+    }
+
+    /* Get some memory in which to return the result. */
+    struct stat* buf =
+        process_getWriteablePtr(_syscallhandler_getProcess(sys), bufPtr, sizeof(*buf));
+    if (!buf) {
+        return syscallreturn_makeDoneErrno(EFAULT);
+    }
+
+    const char* plugin_cwd = process_getWorkingDir(_syscallhandler_getProcess(sys));
+
+    char* socketPath =
+        _syscallhandler_getMirroredUnixSocketPath(sys, dir_desc, pathname, plugin_cwd);
+    int result = socketPath ? regularfile_fstatat(NULL, socketPath, buf, flags, plugin_cwd)
+                            : regularfile_fstatat(dir_desc, pathname, buf, flags, plugin_cwd);
+    free(socketPath);
+
+    return syscallreturn_makeDoneI64(result);
+}
+
+static SyscallReturn _syscallhandler_unlinkatHelper(SysCallHandler* sys, int dirfd,
+                                                    UntypedForeignPtr pathnamePtr, int flags) {
+    /* Validate params. */
+    RegularFile* dir_desc = NULL;
+    const char* pathname;
+
+    int errcode = _syscallhandler_validateDirAndPathnameHelper(
+        sys, dirfd, pathnamePtr, &dir_desc, &pathname);
+    if (errcode < 0) {
+        return syscallreturn_makeDoneErrno(-errcode);
+    }
+
+    const char* plugin_cwd = process_getWorkingDir(_syscallhandler_getProcess(sys));
+
+    char* socketPath =
+        _syscallhandler_getMirroredUnixSocketPath(sys, dir_desc, pathname, plugin_cwd);
+    int result = socketPath ? regularfile_unlinkat(NULL, socketPath, flags, plugin_cwd)
+                            : regularfile_unlinkat(dir_desc, pathname, flags, plugin_cwd);
+    free(socketPath);
+
+    return syscallreturn_makeDoneI64(result);
+}
+
 static SyscallReturn _syscallhandler_renameatHelper(SysCallHandler* sys, int olddirfd,
                                                     UntypedForeignPtr oldpathPtr, int newdirfd,
                                                     UntypedForeignPtr newpathPtr,
@@ -143,39 +231,18 @@ SyscallReturn syscallhandler_openat(SysCallHandler* sys, const SysCallArgs* args
 }
 
 SyscallReturn syscallhandler_newfstatat(SysCallHandler* sys, const SysCallArgs* args) {
-    int dirfd = args->args[0].as_i64;
-    UntypedForeignPtr pathnamePtr = args->args[1].as_ptr; // const char*
-    UntypedForeignPtr bufPtr = args->args[2].as_ptr;      // struct stat*
-    int flags = args->args[3].as_i64;
-
-    /* Validate params. */
-    RegularFile* dir_desc = NULL;
-
-    ssize_t errcode = _syscallhandler_validateDirHelper(sys, dirfd, &dir_desc);
-    if (errcode < 0) {
-        return syscallreturn_makeDoneErrno(-errcode);
-    }
-
-    /* Copy the path rather than getting a reference, so that the MemoryManager
-     * will still allow us to get a mutable reference to memory below.
-     */
-    char pathname[PATH_MAX];
-    errcode = process_readString(_syscallhandler_getProcess(sys), pathname, pathnamePtr, PATH_MAX);
-    if (errcode < 0) {
-        return syscallreturn_makeDoneErrno(-errcode);
-    }
-
-    /* Get some memory in which to return the result. */
-    struct stat* buf =
-        process_getWriteablePtr(_syscallhandler_getProcess(sys), bufPtr, sizeof(*buf));
-    if (!buf) {
-        return syscallreturn_makeDoneErrno(EFAULT);
-    }
+    return _syscallhandler_fstatatHelper(sys, args->args[0].as_i64, args->args[1].as_ptr,
+                                         args->args[2].as_ptr, args->args[3].as_i64);
+}
 
-    const char* plugin_cwd = process_getWorkingDir(_syscallhandler_getProcess(sys));
+SyscallReturn syscallhandler_stat(SysCallHandler* sys, const SysCallArgs* args) {
+    return _syscallhandler_fstatatHelper(
+        sys, AT_FDCWD, args->args[0].as_ptr, args->args[1].as_ptr, 0);
+}
 
-    return syscallreturn_makeDoneI64(
-        regularfile_fstatat(dir_desc, pathname, buf, flags, plugin_cwd));
+SyscallReturn syscallhandler_lstat(SysCallHandler* sys, const SysCallArgs* args) {
+    return _syscallhandler_fstatatHelper(
+        sys, AT_FDCWD, args->args[0].as_ptr, args->args[1].as_ptr, AT_SYMLINK_NOFOLLOW);
 }
 
 SyscallReturn syscallhandler_fchownat(SysCallHandler* sys, const SysCallArgs* args) {
@@ -374,23 +441,12 @@ SyscallReturn syscallhandler_linkat(SysCallHandler* sys, const SysCallArgs* args
 }
 
 SyscallReturn syscallhandler_unlinkat(SysCallHandler* sys, const SysCallArgs* args) {
-    int dirfd = args->args[0].as_i64;
-    UntypedForeignPtr pathnamePtr = args->args[1].as_ptr; // const char*
-    int flags = args->args[2].as_i64;
-
-    /* Validate params. */
-    RegularFile* dir_desc = NULL;
-    const char* pathname;
-
-    int errcode = _syscallhandler_validateDirAndPathnameHelper(
-        sys, dirfd, pathnamePtr, &dir_desc, &pathname);
-    if (errcode < 0) {
-        return syscallreturn_makeDoneErrno(-errcode);
-    }
-
-    const char* plugin_cwd = process_getWorkingDir(_syscallhandler_getProcess(sys));
+    return _syscallhandler_unlinkatHelper(
+        sys, args->args[0].as_i64, args->args[1].as_ptr, args->args[2].as_i64);
+}
 
-    return syscallreturn_makeDoneI64(regularfile_unlinkat(dir_desc, pathname, flags, plugin_cwd));
+SyscallReturn syscallhandler_unlink(SysCallHandler* sys, const SysCallArgs* args) {
+    return _syscallhandler_unlinkatHelper(sys, AT_FDCWD, args->args[0].as_ptr, 0);
 }
 
 SyscallReturn syscallhandler_symlinkat(SysCallHandler* sys, const SysCallArgs* args) {
@@ -503,7 +559,13 @@ SyscallReturn syscallhandler_statx(SysCallHandler* sys, const SysCallArgs* args)
 
     const char* plugin_cwd = process_getWorkingDir(_syscallhandler_getProcess(sys));
 
-    return syscallreturn_makeDoneI64(
-        regularfile_statx(dir_desc, pathname, flags, mask, statxbuf, plugin_cwd));
+    char* socketPath =
+        _syscallhandler_getMirroredUnixSocketPath(sys, dir_desc, pathname, plugin_cwd);
+    int result =
+        socketPath ? regularfile_statx(NULL, socketPath, flags, mask, statxbuf, plugin_cwd)
+                   : regularfile_statx(dir_desc, pathname, flags, mask, statxbuf, plugin_cwd);
+    free(socketPath);
+
+    return syscallreturn_makeDoneI64(result);
 }
 #endif
@@ -18,6 +18,7 @@ mod mman;
 mod random;
 mod sched;
 mod socket;
+mod splice;
 mod sysinfo;
 mod time;
 mod timerfd;
@@ -49,6 +50,7 @@ impl SyscallHandler {
             libc::SYS_clone3 => SyscallHandlerFn::call(Self::clone3, &mut ctx),
             libc::SYS_close => SyscallHandlerFn::call(Self::close, &mut ctx),
             libc::SYS_connect => SyscallHandlerFn::call(Self::connect, &mut ctx),
+            libc::SYS_copy_file_range => SyscallHandlerFn::call(Self::copy_file_range, &mut ctx),
             libc::SYS_dup => SyscallHandlerFn::call(Self::dup, &mut ctx),
             libc::SYS_dup2 => SyscallHandlerFn::call(Self::dup2, &mut ctx),
             libc::SYS_dup3 => SyscallHandlerFn::call(Self::dup3, &mut ctx),
@@ -82,6 +84,7 @@ impl SyscallHandler {
             libc::SYS_read => SyscallHandlerFn::call(Self::read, &mut ctx),
             libc::SYS_readv => SyscallHandlerFn::call(Self::readv, &mut ctx),
             libc::SYS_recvfrom => SyscallHandlerFn::call(Self::recvfrom, &mut ctx),
+            libc::SYS_recvmmsg => SyscallHandlerFn::call(Self::recvmmsg, &mut ctx),
             libc::SYS_recvmsg => SyscallHandlerFn::call(Self::recvmsg, &mut ctx),
             libc::SYS_sched_getaffinity => {
                 SyscallHandlerFn::call(Self::sched_getaffinity, &mut ctx)
@@ -90,6 +93,8 @@ impl SyscallHandler {
                 SyscallHandlerFn::call(Self::sched_setaffinity, &mut ctx)
             }
             libc::SYS_sched_yield => SyscallHandlerFn::call(Self::sched_yield, &mut ctx),
+            libc::SYS_sendfile => SyscallHandlerFn::call(Self::sendfile, &mut ctx),
+            libc::SYS_sendmmsg => SyscallHandlerFn::call(Self::sendmmsg, &mut ctx),
             libc::SYS_sendmsg => SyscallHandlerFn::call(Self::sendmsg, &mut ctx),
             libc::SYS_sendto => SyscallHandlerFn::call(Self::sendto, &mut ctx),
             libc::SYS_setitimer => SyscallHandlerFn::call(Self::setitimer, &mut ctx),
@@ -97,7 +102,9 @@ impl SyscallHandler {
             libc::SYS_shutdown => SyscallHandlerFn::call(Self::shutdown, &mut ctx),
             libc::SYS_socket => SyscallHandlerFn::call(Self::socket, &mut ctx),
             libc::SYS_socketpair => SyscallHandlerFn::call(Self::socketpair, &mut ctx),
+            libc::SYS_splice => SyscallHandlerFn::call(Self::splice, &mut ctx),
             libc::SYS_sysinfo => SyscallHandlerFn::call(Self::sysinfo, &mut ctx),
+            libc::SYS_tee => SyscallHandlerFn::call(Self::tee, &mut ctx),
             libc::SYS_timerfd_create => SyscallHandlerFn::call(Self::timerfd_create, &mut ctx),
             libc::SYS_timerfd_gettime => SyscallHandlerFn::call(Self::timerfd_gettime, &mut ctx),
             libc::SYS_timerfd_settime => SyscallHandlerFn::call(Self::timerfd_settime, &mut ctx),
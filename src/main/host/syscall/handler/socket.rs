@@ -2,19 +2,26 @@ use linux_api::errno::Errno;
 use linux_api::fcntl::DescriptorFlags;
 use log::*;
 use nix::sys::socket::{Shutdown, SockFlag};
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use syscall_logger::log_syscall;
 
+use crate::core::worker::Worker;
+use crate::host::descriptor::descriptor_table::DescriptorTable;
+use crate::host::descriptor::socket::inet::icmp::IcmpSocket;
 use crate::host::descriptor::socket::inet::legacy_tcp::LegacyTcpSocket;
 use crate::host::descriptor::socket::inet::udp::UdpSocket;
-use crate::host::descriptor::socket::inet::InetSocket;
+use crate::host::descriptor::socket::inet::{InetFamily, InetSocket};
 use crate::host::descriptor::socket::unix::{UnixSocket, UnixSocketType};
-use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, Socket};
+use crate::host::descriptor::socket::{
+    ControlData, RecvmsgArgs, RecvmsgReturn, SendmsgArgs, Socket,
+};
 use crate::host::descriptor::{CompatFile, Descriptor, File, FileState, FileStatus, OpenFile};
+use crate::host::host::Host;
+use crate::host::memory_manager::MemoryManager;
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::io::{self, IoVec};
 use crate::host::syscall::type_formatting::{SyscallBufferArg, SyscallSockAddrArg};
-use crate::host::syscall_types::ForeignArrayPtr;
 use crate::host::syscall_types::{SyscallError, SyscallResult};
 use crate::utility::callback_queue::CallbackQueue;
 use crate::utility::sockaddr::SockaddrStorage;
@@ -66,34 +73,57 @@ impl SyscallHandler {
                     file_flags,
                     socket_type,
                     &ctx.objs.host.abstract_unix_namespace(),
+                    ctx.objs.host.pathname_unix_namespace(),
+                    process_credentials(ctx),
                 ))
             }
-            libc::AF_INET => match socket_type {
-                libc::SOCK_STREAM => {
-                    if protocol != 0 && protocol != libc::IPPROTO_TCP {
-                        log::debug!("Unsupported inet stream socket protocol {protocol}");
-                        return Err(Errno::EPROTONOSUPPORT.into());
+            libc::AF_INET | libc::AF_INET6 => {
+                let family = if domain == libc::AF_INET6 {
+                    // dual-stack by default, as with Linux's default "bindv6only" sysctl value
+                    InetFamily::Ipv6 { v6_only: false }
+                } else {
+                    InetFamily::Ipv4
+                };
+
+                match socket_type {
+                    libc::SOCK_STREAM => {
+                        if protocol != 0 && protocol != libc::IPPROTO_TCP {
+                            log::debug!("Unsupported inet stream socket protocol {protocol}");
+                            return Err(Errno::EPROTONOSUPPORT.into());
+                        }
+                        Socket::Inet(InetSocket::LegacyTcp(LegacyTcpSocket::new(
+                            file_flags,
+                            family,
+                            ctx.objs.host,
+                        )))
                     }
-                    Socket::Inet(InetSocket::LegacyTcp(LegacyTcpSocket::new(
-                        file_flags,
-                        ctx.objs.host,
-                    )))
-                }
-                libc::SOCK_DGRAM => {
-                    if protocol != 0 && protocol != libc::IPPROTO_UDP {
-                        log::debug!("Unsupported inet dgram socket protocol {protocol}");
-                        return Err(Errno::EPROTONOSUPPORT.into());
+                    libc::SOCK_DGRAM => {
+                        let send_buf_size = ctx.objs.host.params.init_sock_send_buf_size;
+                        let recv_buf_size = ctx.objs.host.params.init_sock_recv_buf_size;
+                        match protocol {
+                            0 | libc::IPPROTO_UDP => Socket::Inet(InetSocket::Udp(UdpSocket::new(
+                                file_flags,
+                                family,
+                                send_buf_size.try_into().unwrap(),
+                                recv_buf_size.try_into().unwrap(),
+                            ))),
+                            // an unprivileged "ping" socket (only supported for IPv4)
+                            libc::IPPROTO_ICMP if family == InetFamily::Ipv4 => {
+                                Socket::Inet(InetSocket::Icmp(IcmpSocket::new(
+                                    file_flags,
+                                    send_buf_size.try_into().unwrap(),
+                                    recv_buf_size.try_into().unwrap(),
+                                )))
+                            }
+                            _ => {
+                                log::debug!("Unsupported inet dgram socket protocol {protocol}");
+                                return Err(Errno::EPROTONOSUPPORT.into());
+                            }
+                        }
                     }
-                    let send_buf_size = ctx.objs.host.params.init_sock_send_buf_size;
-                    let recv_buf_size = ctx.objs.host.params.init_sock_recv_buf_size;
-                    Socket::Inet(InetSocket::Udp(UdpSocket::new(
-                        file_flags,
-                        send_buf_size.try_into().unwrap(),
-                        recv_buf_size.try_into().unwrap(),
-                    )))
+                    _ => return Err(Errno::ESOCKTNOSUPPORT.into()),
                 }
-                _ => return Err(Errno::ESOCKTNOSUPPORT.into()),
-            },
+            }
             _ => return Err(Errno::EAFNOSUPPORT.into()),
         };
 
@@ -203,7 +233,7 @@ impl SyscallHandler {
         let args = SendmsgArgs {
             addr,
             iovs: &[iov],
-            control_ptr: ForeignArrayPtr::new(ForeignPtr::null(), 0),
+            control: Default::default(),
             flags,
         };
 
@@ -261,26 +291,7 @@ impl SyscallHandler {
             return Err(Errno::ENOTSOCK.into());
         };
 
-        let mut mem = ctx.objs.process.memory_borrow_mut();
-        let mut rng = ctx.objs.host.random_mut();
-        let net_ns = ctx.objs.host.network_namespace_borrow();
-
-        let msg = io::read_msghdr(&mem, msg_ptr)?;
-
-        let args = SendmsgArgs {
-            addr: io::read_sockaddr(&mem, msg.name, msg.name_len)?,
-            iovs: &msg.iovs,
-            control_ptr: ForeignArrayPtr::new(msg.control, msg.control_len),
-            // note: "the msg_flags field is ignored" for sendmsg; see send(2)
-            flags,
-        };
-
-        // call the socket's sendmsg(), and run any resulting events
-        let mut result = crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
-            CallbackQueue::queue_and_run(|cb_queue| {
-                Socket::sendmsg(socket, args, &mut mem, &net_ns, &mut *rng, cb_queue)
-            })
-        });
+        let mut result = Self::sendmsg_helper(ctx, socket, msg_ptr, flags);
 
         // if the syscall will block, keep the file open until the syscall restarts
         if let Some(err) = result.as_mut().err() {
@@ -293,6 +304,97 @@ impl SyscallHandler {
         Ok(bytes_written)
     }
 
+    #[log_syscall(/* rv */ std::ffi::c_int, /* sockfd */ std::ffi::c_int,
+                  /* msgvec */ *const std::ffi::c_void, /* vlen */ std::ffi::c_uint,
+                  /* flags */ nix::sys::socket::MsgFlags)]
+    pub fn sendmmsg(
+        ctx: &mut SyscallContext,
+        fd: std::ffi::c_int,
+        msgvec_ptr: ForeignPtr<libc::mmsghdr>,
+        vlen: std::ffi::c_uint,
+        flags: std::ffi::c_int,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        // if we were previously blocked, get the active file from the last syscall handler
+        // invocation since it may no longer exist in the descriptor table
+        let file = ctx
+            .objs
+            .thread
+            .syscall_condition()
+            // if this was for a C descriptor, then there won't be an active file object
+            .and_then(|x| x.active_file().cloned());
+
+        let file = match file {
+            // we were previously blocked, so re-use the file from the previous syscall invocation
+            Some(x) => x,
+            // get the file from the descriptor table, or return early if it doesn't exist
+            None => {
+                let desc_table = ctx.objs.process.descriptor_table_borrow();
+                let CompatFile::New(file) = Self::get_descriptor(&desc_table, fd)?.file() else {
+                    // we don't have any C socket objects
+                    return Err(Errno::ENOTSOCK.into());
+                };
+                file.clone()
+            }
+        };
+
+        let File::Socket(ref socket) = file.inner_file() else {
+            return Err(Errno::ENOTSOCK.into());
+        };
+
+        // linux silently limits the number of messages
+        let vlen = std::cmp::min(vlen, libc::UIO_MAXIOV.try_into().unwrap());
+
+        // the number of messages sent before the syscall last blocked
+        let mut num_sent: u32 = match ctx.objs.thread.syscall_condition() {
+            Some(cond) => cond.progress().try_into().unwrap(),
+            None => 0,
+        };
+
+        while num_sent < vlen {
+            let (msg_ptr, msg_len_ptr) = mmsghdr_fields(msgvec_ptr, num_sent.try_into().unwrap());
+
+            let result = Self::sendmsg_helper(ctx, socket, msg_ptr, flags).and_then(|len| {
+                let len = libc::c_uint::try_from(len).unwrap();
+                let mut mem = ctx.objs.process.memory_borrow_mut();
+                Ok(mem.write(msg_len_ptr, &len)?)
+            });
+
+            match result {
+                Ok(()) => num_sent += 1,
+                // an error is only returned if no messages were sent
+                Err(err) if !matches!(err, SyscallError::Blocked(_)) => {
+                    if num_sent == 0 {
+                        return Err(err);
+                    }
+                    break;
+                }
+                Err(mut err) => {
+                    // like linux, a signal interrupts the syscall after sending some messages and
+                    // the number of sent messages is returned rather than EINTR
+                    if num_sent > 0 {
+                        let host_shmem = ctx.objs.host.shim_shmem_lock_borrow().unwrap();
+                        if ctx
+                            .objs
+                            .thread
+                            .unblocked_signal_pending(ctx.objs.process, &host_shmem)
+                        {
+                            break;
+                        }
+                    }
+
+                    // keep the file open and remember the messages that were already sent until
+                    // the syscall restarts
+                    let cond = err.blocked_condition().unwrap();
+                    cond.set_active_file(file.clone());
+                    cond.set_progress(num_sent.into());
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(num_sent.try_into().unwrap())
+    }
+
     #[log_syscall(/* rv */ libc::ssize_t, /* sockfd */ std::ffi::c_int, /* buf */ *const std::ffi::c_void,
                   /* len */ libc::size_t, /* flags */ nix::sys::socket::MsgFlags,
                   /* src_addr */ *const libc::sockaddr, /* addrlen */ *const libc::socklen_t)]
@@ -343,7 +445,6 @@ impl SyscallHandler {
 
         let args = RecvmsgArgs {
             iovs: &[iov],
-            control_ptr: ForeignArrayPtr::new(ForeignPtr::null(), 0),
             flags,
         };
 
@@ -411,22 +512,7 @@ impl SyscallHandler {
             return Err(Errno::ENOTSOCK.into());
         };
 
-        let mut mem = ctx.objs.process.memory_borrow_mut();
-
-        let mut msg = io::read_msghdr(&mem, msg_ptr)?;
-
-        let args = RecvmsgArgs {
-            iovs: &msg.iovs,
-            control_ptr: ForeignArrayPtr::new(msg.control, msg.control_len),
-            flags,
-        };
-
-        // call the socket's recvmsg(), and run any resulting events
-        let mut result = crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
-            CallbackQueue::queue_and_run(|cb_queue| {
-                Socket::recvmsg(socket, args, &mut mem, cb_queue)
-            })
-        });
+        let mut result = Self::recvmsg_helper(ctx, socket, msg_ptr, flags);
 
         // if the syscall will block, keep the file open until the syscall restarts
         if let Some(err) = result.as_mut().err() {
@@ -435,25 +521,153 @@ impl SyscallHandler {
             }
         }
 
-        let result = result?;
+        let bytes_read = result?;
+        Ok(bytes_read)
+    }
 
-        // write the socket address to the plugin and update the length in msg
-        if !msg.name.is_null() {
-            if let Some(from_addr) = result.addr.as_ref() {
-                msg.name_len = io::write_sockaddr(&mut mem, from_addr, msg.name, msg.name_len)?;
+    #[log_syscall(/* rv */ std::ffi::c_int, /* sockfd */ std::ffi::c_int,
+                  /* msgvec */ *const std::ffi::c_void, /* vlen */ std::ffi::c_uint,
+                  /* flags */ nix::sys::socket::MsgFlags, /* timeout */ *const std::ffi::c_void)]
+    pub fn recvmmsg(
+        ctx: &mut SyscallContext,
+        fd: std::ffi::c_int,
+        msgvec_ptr: ForeignPtr<libc::mmsghdr>,
+        vlen: std::ffi::c_uint,
+        flags: std::ffi::c_int,
+        timeout_ptr: ForeignPtr<linux_api::time::timespec>,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        // if we were previously blocked, get the active file from the last syscall handler
+        // invocation since it may no longer exist in the descriptor table
+        let file = ctx
+            .objs
+            .thread
+            .syscall_condition()
+            // if this was for a C descriptor, then there won't be an active file object
+            .and_then(|x| x.active_file().cloned());
+
+        let file = match file {
+            // we were previously blocked, so re-use the file from the previous syscall invocation
+            Some(x) => x,
+            // get the file from the descriptor table, or return early if it doesn't exist
+            None => {
+                let desc_table = ctx.objs.process.descriptor_table_borrow();
+                let CompatFile::New(file) = Self::get_descriptor(&desc_table, fd)?.file() else {
+                    // we don't have any C socket objects
+                    return Err(Errno::ENOTSOCK.into());
+                };
+                file.clone()
+            }
+        };
+
+        let File::Socket(ref socket) = file.inner_file() else {
+            return Err(Errno::ENOTSOCK.into());
+        };
+
+        // the number of messages received and the time that the timeout expires, if the syscall
+        // previously blocked
+        let prev_state = ctx
+            .objs
+            .thread
+            .syscall_condition()
+            .map(|cond| (cond.progress().try_into().unwrap(), cond.deadline()));
+
+        let (mut num_recv, end_time): (u32, _) = match prev_state {
+            Some(state) => state,
+            None => {
+                // Like Linux, the timeout starts when the syscall is made, but doesn't limit how
+                // long we block for the first message (see the "BUGS" section of recvmmsg(2)).
+                let timeout = if timeout_ptr.is_null() {
+                    None
+                } else {
+                    let timeout = ctx.objs.process.memory_borrow().read(timeout_ptr)?;
+                    Some(SimulationTime::try_from(timeout).or(Err(Errno::EINVAL))?)
+                };
+                let end_time = timeout.map(|x| Worker::current_time().unwrap().saturating_add(x));
+                (0, end_time)
+            }
+        };
+
+        let wait_for_one = flags & libc::MSG_WAITFORONE != 0;
+
+        // this flag is handled here, so the socket doesn't need to see it
+        let flags = flags & !libc::MSG_WAITFORONE;
+
+        // linux silently limits the number of messages
+        let vlen = std::cmp::min(vlen, libc::UIO_MAXIOV.try_into().unwrap());
+
+        while num_recv < vlen {
+            // Like Linux, the timeout is checked after each message is received. We also stop if
+            // we were woken up by the timeout while blocked for a later message.
+            if let Some(end_time) = end_time {
+                if num_recv > 0 && Worker::current_time().unwrap() >= end_time {
+                    break;
+                }
+            }
+
+            let (msg_ptr, msg_len_ptr) = mmsghdr_fields(msgvec_ptr, num_recv.try_into().unwrap());
+
+            // with `MSG_WAITFORONE`, we don't block for later messages
+            let flags = if num_recv > 0 && wait_for_one {
+                flags | libc::MSG_DONTWAIT
             } else {
-                msg.name_len = 0;
+                flags
+            };
+
+            let result = Self::recvmsg_helper(ctx, socket, msg_ptr, flags).and_then(|len| {
+                let len = libc::c_uint::try_from(len).unwrap();
+                let mut mem = ctx.objs.process.memory_borrow_mut();
+                Ok(mem.write(msg_len_ptr, &len)?)
+            });
+
+            match result {
+                Ok(()) => num_recv += 1,
+                // an error is only returned if no messages were received (linux would return a
+                // non-EAGAIN error from the next call on the socket, which we don't)
+                Err(err) if !matches!(err, SyscallError::Blocked(_)) => {
+                    if num_recv == 0 {
+                        return Err(err);
+                    }
+                    break;
+                }
+                Err(mut err) => {
+                    // like linux, a signal interrupts the syscall after receiving some messages
+                    // and the received messages are returned rather than EINTR
+                    if num_recv > 0 {
+                        let host_shmem = ctx.objs.host.shim_shmem_lock_borrow().unwrap();
+                        if ctx
+                            .objs
+                            .thread
+                            .unblocked_signal_pending(ctx.objs.process, &host_shmem)
+                        {
+                            break;
+                        }
+                    }
+
+                    // keep the file open and remember the messages that were already received
+                    // until the syscall restarts
+                    let cond = err.blocked_condition().unwrap();
+                    cond.set_active_file(file.clone());
+                    cond.set_progress(num_recv.into());
+                    cond.set_deadline(end_time);
+                    if num_recv > 0 {
+                        cond.set_timeout(end_time);
+                    }
+                    return Err(err);
+                }
             }
         }
 
-        // update the control len and flags in msg
-        msg.control_len = result.control_len;
-        msg.flags = result.msg_flags;
-
-        // write msg back to the plugin
-        io::update_msghdr(&mut mem, msg_ptr, msg)?;
+        // write the remaining time back to the plugin
+        if let Some(end_time) = end_time {
+            let remaining = end_time.saturating_duration_since(&Worker::current_time().unwrap());
+            let remaining = linux_api::time::timespec::try_from(remaining).unwrap();
+            ctx.objs
+                .process
+                .memory_borrow_mut()
+                .write(timeout_ptr, &remaining)?;
+        }
 
-        Ok(result.return_val)
+        Ok(num_recv.try_into().unwrap())
     }
 
     #[log_syscall(/* rv */ std::ffi::c_int, /* sockfd */ std::ffi::c_int, /* addr */ *const libc::sockaddr,
@@ -661,6 +875,255 @@ impl SyscallHandler {
         result
     }
 
+    /// Send a single message from the plugin's `msghdr`. Used by `sendmsg()` and `sendmmsg()`.
+    fn sendmsg_helper(
+        ctx: &mut SyscallContext,
+        socket: &Socket,
+        msg_ptr: ForeignPtr<libc::msghdr>,
+        flags: std::ffi::c_int,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        let mut mem = ctx.objs.process.memory_borrow_mut();
+        let mut rng = ctx.objs.host.random_mut();
+        let net_ns = ctx.objs.host.network_namespace_borrow();
+
+        let msg = io::read_msghdr(&mem, msg_ptr)?;
+
+        // only unix sockets support control data, and other sockets ignore it
+        let control = match socket {
+            Socket::Unix(_) => Self::read_control_data(
+                &mem,
+                &ctx.objs.process.descriptor_table_borrow(),
+                process_credentials(ctx),
+                &msg,
+            )?,
+            Socket::Inet(_) => ControlData::default(),
+        };
+
+        let args = SendmsgArgs {
+            addr: io::read_sockaddr(&mem, msg.name, msg.name_len)?,
+            iovs: &msg.iovs,
+            control,
+            // note: "the msg_flags field is ignored" for sendmsg; see send(2)
+            flags,
+        };
+
+        // call the socket's sendmsg(), and run any resulting events
+        crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
+            CallbackQueue::queue_and_run(|cb_queue| {
+                Socket::sendmsg(socket, args, &mut mem, &net_ns, &mut *rng, cb_queue)
+            })
+        })
+    }
+
+    /// Receive a single message into the plugin's `msghdr`, and update the `msghdr`. Used by
+    /// `recvmsg()` and `recvmmsg()`.
+    fn recvmsg_helper(
+        ctx: &mut SyscallContext,
+        socket: &Socket,
+        msg_ptr: ForeignPtr<libc::msghdr>,
+        flags: std::ffi::c_int,
+    ) -> Result<libc::ssize_t, SyscallError> {
+        let mut mem = ctx.objs.process.memory_borrow_mut();
+
+        let mut msg = io::read_msghdr(&mem, msg_ptr)?;
+
+        // this flag applies to the descriptors we create, so the socket doesn't need to see it
+        let cloexec = flags & libc::MSG_CMSG_CLOEXEC != 0;
+        let flags = flags & !libc::MSG_CMSG_CLOEXEC;
+
+        let args = RecvmsgArgs {
+            iovs: &msg.iovs,
+            flags,
+        };
+
+        // call the socket's recvmsg(), and run any resulting events
+        let result = crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
+            CallbackQueue::queue_and_run(|cb_queue| {
+                Socket::recvmsg(socket, args, &mut mem, cb_queue)
+            })
+        })?;
+
+        // write the socket address to the plugin and update the length in msg
+        if !msg.name.is_null() {
+            if let Some(from_addr) = result.addr.as_ref() {
+                msg.name_len = io::write_sockaddr(&mut mem, from_addr, msg.name, msg.name_len)?;
+            } else {
+                msg.name_len = 0;
+            }
+        }
+
+        // write the control data to the plugin and update the control len and flags in msg
+        let (control_len, control_truncated) = Self::write_control_data(
+            ctx.objs.host,
+            &mut mem,
+            &mut ctx.objs.process.descriptor_table_borrow_mut(),
+            result.control,
+            cloexec,
+            msg.control,
+            msg.control_len,
+        )?;
+        msg.control_len = control_len;
+        msg.flags = result.msg_flags;
+        if control_truncated {
+            msg.flags |= libc::MSG_CTRUNC;
+        }
+
+        // write msg back to the plugin
+        io::update_msghdr(&mut mem, msg_ptr, msg)?;
+
+        Ok(result.return_val)
+    }
+
+    /// Parse the control messages of a `sendmsg()` call on a unix socket. Files passed with
+    /// `SCM_RIGHTS` are looked up in the descriptor table.
+    fn read_control_data(
+        mem: &MemoryManager,
+        desc_table: &DescriptorTable,
+        credentials: libc::ucred,
+        msg: &io::MsgHdr,
+    ) -> Result<ControlData, SyscallError> {
+        let mut control = ControlData::default();
+
+        for cmsg in io::read_cmsgs(mem, msg.control, msg.control_len)? {
+            match (cmsg.level, cmsg.cmsg_type) {
+                (libc::SOL_SOCKET, libc::SCM_RIGHTS) => {
+                    for fd in cmsg.data.chunks_exact(std::mem::size_of::<libc::c_int>()) {
+                        let fd = libc::c_int::from_ne_bytes(fd.try_into().unwrap());
+
+                        if control.rights.len() >= SCM_MAX_FD {
+                            return Err(Errno::EINVAL.into());
+                        }
+
+                        let desc = Self::get_descriptor(desc_table, fd)?;
+                        control.rights.push(desc.file().clone());
+                    }
+                }
+                (libc::SOL_SOCKET, libc::SCM_CREDENTIALS) => {
+                    let Ok(data) = <[u8; 12]>::try_from(&cmsg.data[..]) else {
+                        return Err(Errno::EINVAL.into());
+                    };
+
+                    let sent = libc::ucred {
+                        pid: libc::pid_t::from_ne_bytes(data[0..4].try_into().unwrap()),
+                        uid: libc::uid_t::from_ne_bytes(data[4..8].try_into().unwrap()),
+                        gid: libc::gid_t::from_ne_bytes(data[8..12].try_into().unwrap()),
+                    };
+
+                    // we don't support privileged processes, which are allowed to send other
+                    // credentials
+                    if (sent.pid, sent.uid, sent.gid)
+                        != (credentials.pid, credentials.uid, credentials.gid)
+                    {
+                        return Err(Errno::EPERM.into());
+                    }
+
+                    control.credentials = Some(sent);
+                }
+                (libc::SOL_SOCKET, cmsg_type) => {
+                    warn!("Unsupported control message type {cmsg_type} for unix sockets");
+                    return Err(Errno::EINVAL.into());
+                }
+                // linux ignores control messages for other levels
+                _ => {}
+            }
+        }
+
+        Ok(control)
+    }
+
+    /// Write the control data received by `recvmsg()` to the plugin's control buffer, and register
+    /// any received files in the descriptor table. Returns the number of bytes written, and whether
+    /// some of the control data didn't fit in the buffer and was discarded. If the control data
+    /// can't be written, the received files are closed.
+    fn write_control_data(
+        host: &Host,
+        mem: &mut MemoryManager,
+        desc_table: &mut DescriptorTable,
+        control: ControlData,
+        cloexec: bool,
+        control_ptr: ForeignPtr<u8>,
+        control_len: libc::size_t,
+    ) -> Result<(libc::size_t, bool), SyscallError> {
+        let mut cmsgs = Vec::new();
+        let mut space = control_len;
+        let mut truncated = false;
+        let mut fds = Vec::new();
+
+        if let Some(credentials) = control.credentials {
+            let data = [
+                &credentials.pid.to_ne_bytes()[..],
+                &credentials.uid.to_ne_bytes()[..],
+                &credentials.gid.to_ne_bytes()[..],
+            ]
+            .concat();
+
+            if io::cmsg_len(data.len()) <= space {
+                space -= std::cmp::min(io::cmsg_space(data.len()), space);
+                cmsgs.push(io::ControlMessage {
+                    level: libc::SOL_SOCKET,
+                    cmsg_type: libc::SCM_CREDENTIALS,
+                    data,
+                });
+            } else {
+                truncated = true;
+            }
+        }
+
+        if !control.rights.is_empty() {
+            let num_rights = control.rights.len();
+            let max_fds =
+                space.saturating_sub(io::cmsg_len(0)) / std::mem::size_of::<libc::c_int>();
+
+            for file in control.rights.into_iter().take(max_fds) {
+                let mut desc = Descriptor::new(file);
+                if cloexec {
+                    desc.set_flags(DescriptorFlags::FD_CLOEXEC);
+                }
+
+                // if there are no more free descriptors, the file is closed when dropped
+                let Ok(fd) = desc_table.register_descriptor(desc) else {
+                    break;
+                };
+                fds.push(fd);
+            }
+
+            // like linux, any files that don't fit in the buffer are closed (when dropped above)
+            if fds.len() < num_rights {
+                truncated = true;
+            }
+
+            if !fds.is_empty() {
+                cmsgs.push(io::ControlMessage {
+                    level: libc::SOL_SOCKET,
+                    cmsg_type: libc::SCM_RIGHTS,
+                    data: fds
+                        .iter()
+                        .flat_map(|fd| i32::from(*fd).to_ne_bytes())
+                        .collect(),
+                });
+            }
+        }
+
+        let bytes_written = match io::write_cmsgs(mem, control_ptr, control_len, &cmsgs) {
+            Ok(x) => x,
+            Err(e) => {
+                // the plugin never learns about the files, so close them
+                CallbackQueue::queue_and_run(|cb_queue| {
+                    for fd in fds {
+                        // ignore any errors when closing
+                        desc_table
+                            .deregister_descriptor(fd)
+                            .unwrap()
+                            .close(host, cb_queue);
+                    }
+                });
+                return Err(e.into());
+            }
+        };
+
+        Ok((bytes_written, truncated))
+    }
+
     fn accept_helper(
         ctx: &mut SyscallContext,
         file: &File,
@@ -880,11 +1343,15 @@ impl SyscallHandler {
             descriptor_flags.insert(DescriptorFlags::FD_CLOEXEC);
         }
 
+        let credentials = process_credentials(ctx);
+
         let (socket_1, socket_2) = CallbackQueue::queue_and_run(|cb_queue| {
             UnixSocket::pair(
                 file_flags,
                 socket_type,
                 &ctx.objs.host.abstract_unix_namespace(),
+                ctx.objs.host.pathname_unix_namespace(),
+                credentials,
                 cb_queue,
             )
         });
@@ -959,9 +1426,14 @@ impl SyscallHandler {
         // get the provided optlen
         let optlen = mem.read(optlen_ptr)?;
 
-        let mut optlen_new = socket
-            .borrow()
-            .getsockopt(level, optname, optval_ptr, optlen, &mut mem)?;
+        // some options (such as `SO_ERROR`) change the socket's state, so run any resulting events
+        let mut optlen_new = crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
+            CallbackQueue::queue_and_run(|cb_queue| {
+                socket
+                    .borrow_mut()
+                    .getsockopt(level, optname, optval_ptr, optlen, &mut mem, cb_queue)
+            })
+        })?;
 
         if optlen_new > optlen {
             // this is probably a bug in the socket's getsockopt implementation
@@ -1013,3 +1485,31 @@ impl SyscallHandler {
         Ok(0.into())
     }
 }
+
+/// The maximum number of files that can be passed in a single message, from linux's `SCM_MAX_FD`.
+const SCM_MAX_FD: usize = 253;
+
+/// The credentials of the calling process, as used by `SO_PEERCRED` and `SCM_CREDENTIALS`. Shadow
+/// doesn't emulate user and group ids, so processes have the same ids as shadow.
+/// Get pointers to the `msg_hdr` and `msg_len` fields of the `libc::mmsghdr` at `index`.
+fn mmsghdr_fields(
+    msgvec_ptr: ForeignPtr<libc::mmsghdr>,
+    index: usize,
+) -> (ForeignPtr<libc::msghdr>, ForeignPtr<libc::c_uint>) {
+    let mmsg_ptr = msgvec_ptr.add(index).cast::<u8>();
+    let msg_hdr_ptr = mmsg_ptr
+        .add(memoffset::offset_of!(libc::mmsghdr, msg_hdr))
+        .cast::<libc::msghdr>();
+    let msg_len_ptr = mmsg_ptr
+        .add(memoffset::offset_of!(libc::mmsghdr, msg_len))
+        .cast::<libc::c_uint>();
+    (msg_hdr_ptr, msg_len_ptr)
+}
+
+fn process_credentials(ctx: &SyscallContext) -> libc::ucred {
+    libc::ucred {
+        pid: ctx.objs.process.id().into(),
+        uid: nix::unistd::getuid().as_raw(),
+        gid: nix::unistd::getgid().as_raw(),
+    }
+}
@@ -0,0 +1,529 @@
+use linux_api::errno::Errno;
+use linux_api::posix_types::kernel_off_t;
+use shadow_shim_helper_rs::syscall_types::ForeignPtr;
+use syscall_logger::log_syscall;
+
+use crate::cshadow as c;
+use crate::host::descriptor::{CompatFile, File, FileState, FileStatus};
+use crate::host::memory_manager::AllocdMem;
+use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
+use crate::host::syscall::io::IoVec;
+use crate::host::syscall_types::{ForeignArrayPtr, SyscallError};
+use crate::utility::callback_queue::CallbackQueue;
+
+/// The maximum number of bytes to copy between descriptors at a time. This is the default size of
+/// a pipe buffer.
+const TRANSFER_CHUNK_SIZE: usize = 65536;
+
+/// A descriptor that data is copied to or from.
+enum Endpoint {
+    /// A legacy regular file.
+    Regular(*mut c::RegularFile),
+    /// A pipe, socket, or other rust file.
+    File(File),
+}
+
+impl Endpoint {
+    fn is_pipe(&self) -> bool {
+        matches!(self, Self::File(File::Pipe(_)))
+    }
+}
+
+/// A buffer in plugin memory, used to access rust files which only operate on plugin memory. It's
+/// allocated when first needed and reused for each chunk of a transfer, and must be freed with
+/// [`PluginBuf::free`].
+struct PluginBuf {
+    mem: Option<AllocdMem<u8>>,
+    len: usize,
+}
+
+impl PluginBuf {
+    fn new(len: usize) -> Self {
+        Self { mem: None, len }
+    }
+
+    /// Get a pointer to the first `len` bytes of the buffer, allocating the buffer if needed.
+    fn get(&mut self, ctx: &mut SyscallContext, len: usize) -> Result<ForeignArrayPtr<u8>, Errno> {
+        assert!(len <= self.len);
+
+        if self.mem.is_none() {
+            // the plugin didn't ask for this memory, so any failure to map it is ENOMEM
+            let mem = AllocdMem::try_new(ctx.objs, self.len).map_err(|_| Errno::ENOMEM)?;
+            self.mem = Some(mem);
+        }
+
+        Ok(self.mem.as_ref().unwrap().ptr().slice(..len))
+    }
+
+    fn free(self, ctx: &mut SyscallContext) {
+        if let Some(mem) = self.mem {
+            mem.free(ctx.objs);
+        }
+    }
+}
+
+/// Options for a transfer between two descriptors.
+struct TransferOpts {
+    /// Whether data should be removed from a source pipe. If false, pipe data is only peeked.
+    consume: bool,
+    /// Whether the transfer should fail with `EAGAIN` rather than block.
+    nonblock: bool,
+}
+
+impl SyscallHandler {
+    #[log_syscall(/* rv */ isize, /* out_fd */ std::ffi::c_int, /* in_fd */ std::ffi::c_int,
+                  /* offset */ *const kernel_off_t, /* count */ usize)]
+    pub fn sendfile(
+        ctx: &mut SyscallContext,
+        out_fd: std::ffi::c_int,
+        in_fd: std::ffi::c_int,
+        offset_ptr: ForeignPtr<kernel_off_t>,
+        count: usize,
+    ) -> Result<isize, SyscallError> {
+        let src = Self::get_endpoint(ctx, in_fd)?;
+        let dst = Self::get_endpoint(ctx, out_fd)?;
+
+        // the input file must support mmap-like operations
+        if !matches!(src, Endpoint::Regular(_)) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let mut offset = Self::read_offset(ctx, offset_ptr)?;
+
+        let opts = TransferOpts {
+            consume: true,
+            nonblock: false,
+        };
+        let rv = Self::transfer(ctx, &src, offset.as_mut(), &dst, None, count, opts)?;
+
+        Self::write_offset(ctx, offset_ptr, offset)?;
+
+        Ok(rv.try_into().unwrap())
+    }
+
+    #[log_syscall(/* rv */ isize, /* fd_in */ std::ffi::c_int, /* off_in */ *const kernel_off_t,
+                  /* fd_out */ std::ffi::c_int, /* off_out */ *const kernel_off_t,
+                  /* len */ usize, /* flags */ std::ffi::c_uint)]
+    pub fn splice(
+        ctx: &mut SyscallContext,
+        fd_in: std::ffi::c_int,
+        off_in_ptr: ForeignPtr<kernel_off_t>,
+        fd_out: std::ffi::c_int,
+        off_out_ptr: ForeignPtr<kernel_off_t>,
+        len: usize,
+        flags: std::ffi::c_uint,
+    ) -> Result<isize, SyscallError> {
+        let src = Self::get_endpoint(ctx, fd_in)?;
+        let dst = Self::get_endpoint(ctx, fd_out)?;
+
+        // at least one of the descriptors must be a pipe
+        if !src.is_pipe() && !dst.is_pipe() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        // pipes don't have offsets
+        if (src.is_pipe() && !off_in_ptr.is_null()) || (dst.is_pipe() && !off_out_ptr.is_null()) {
+            return Err(Errno::ESPIPE.into());
+        }
+
+        let mut off_in = Self::read_offset(ctx, off_in_ptr)?;
+        let mut off_out = Self::read_offset(ctx, off_out_ptr)?;
+
+        let opts = TransferOpts {
+            consume: true,
+            nonblock: flags & libc::SPLICE_F_NONBLOCK != 0,
+        };
+        let rv = Self::transfer(
+            ctx,
+            &src,
+            off_in.as_mut(),
+            &dst,
+            off_out.as_mut(),
+            len,
+            opts,
+        )?;
+
+        Self::write_offset(ctx, off_in_ptr, off_in)?;
+        Self::write_offset(ctx, off_out_ptr, off_out)?;
+
+        Ok(rv.try_into().unwrap())
+    }
+
+    #[log_syscall(/* rv */ isize, /* fd_in */ std::ffi::c_int, /* fd_out */ std::ffi::c_int,
+                  /* len */ usize, /* flags */ std::ffi::c_uint)]
+    pub fn tee(
+        ctx: &mut SyscallContext,
+        fd_in: std::ffi::c_int,
+        fd_out: std::ffi::c_int,
+        len: usize,
+        flags: std::ffi::c_uint,
+    ) -> Result<isize, SyscallError> {
+        let src = Self::get_endpoint(ctx, fd_in)?;
+        let dst = Self::get_endpoint(ctx, fd_out)?;
+
+        // both descriptors must be pipes
+        if !src.is_pipe() || !dst.is_pipe() {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let opts = TransferOpts {
+            consume: false,
+            nonblock: flags & libc::SPLICE_F_NONBLOCK != 0,
+        };
+        let rv = Self::transfer(ctx, &src, None, &dst, None, len, opts)?;
+
+        Ok(rv.try_into().unwrap())
+    }
+
+    #[log_syscall(/* rv */ isize, /* fd_in */ std::ffi::c_int, /* off_in */ *const kernel_off_t,
+                  /* fd_out */ std::ffi::c_int, /* off_out */ *const kernel_off_t,
+                  /* len */ usize, /* flags */ std::ffi::c_uint)]
+    pub fn copy_file_range(
+        ctx: &mut SyscallContext,
+        fd_in: std::ffi::c_int,
+        off_in_ptr: ForeignPtr<kernel_off_t>,
+        fd_out: std::ffi::c_int,
+        off_out_ptr: ForeignPtr<kernel_off_t>,
+        len: usize,
+        flags: std::ffi::c_uint,
+    ) -> Result<isize, SyscallError> {
+        // no flags are currently defined
+        if flags != 0 {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let src = Self::get_endpoint(ctx, fd_in)?;
+        let dst = Self::get_endpoint(ctx, fd_out)?;
+
+        // both descriptors must be regular files
+        if !matches!(src, Endpoint::Regular(_)) || !matches!(dst, Endpoint::Regular(_)) {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let mut off_in = Self::read_offset(ctx, off_in_ptr)?;
+        let mut off_out = Self::read_offset(ctx, off_out_ptr)?;
+
+        let opts = TransferOpts {
+            consume: true,
+            nonblock: false,
+        };
+        let rv = Self::transfer(
+            ctx,
+            &src,
+            off_in.as_mut(),
+            &dst,
+            off_out.as_mut(),
+            len,
+            opts,
+        )?;
+
+        Self::write_offset(ctx, off_in_ptr, off_in)?;
+        Self::write_offset(ctx, off_out_ptr, off_out)?;
+
+        Ok(rv.try_into().unwrap())
+    }
+
+    /// Get the file for `fd`. Legacy files other than regular files aren't supported and return
+    /// `EINVAL`.
+    fn get_endpoint(ctx: &SyscallContext, fd: std::ffi::c_int) -> Result<Endpoint, Errno> {
+        let desc_table = ctx.objs.process.descriptor_table_borrow();
+        match Self::get_descriptor(&desc_table, fd)?.file() {
+            CompatFile::New(file) => Ok(Endpoint::File(file.inner_file().clone())),
+            CompatFile::Legacy(file) => {
+                let file = file.ptr();
+                if unsafe { c::legacyfile_getType(file) } != c::_LegacyFileType_DT_FILE {
+                    return Err(Errno::EINVAL);
+                }
+                Ok(Endpoint::Regular(file as *mut c::RegularFile))
+            }
+        }
+    }
+
+    /// Read an optional offset argument from plugin memory.
+    fn read_offset(
+        ctx: &SyscallContext,
+        offset_ptr: ForeignPtr<kernel_off_t>,
+    ) -> Result<Option<libc::off_t>, Errno> {
+        if offset_ptr.is_null() {
+            return Ok(None);
+        }
+
+        let offset = ctx.objs.process.memory_borrow().read(offset_ptr)?;
+        if offset < 0 {
+            return Err(Errno::EINVAL);
+        }
+
+        Ok(Some(offset))
+    }
+
+    /// Write an updated offset back to plugin memory.
+    fn write_offset(
+        ctx: &SyscallContext,
+        offset_ptr: ForeignPtr<kernel_off_t>,
+        offset: Option<libc::off_t>,
+    ) -> Result<(), Errno> {
+        if let Some(offset) = offset {
+            ctx.objs
+                .process
+                .memory_borrow_mut()
+                .write(offset_ptr, &offset)?;
+        }
+        Ok(())
+    }
+
+    /// Copy up to `len` bytes from `src` to `dst`. If an offset is given for a regular file, the
+    /// file is accessed at that offset and the offset is updated, otherwise the file's position is
+    /// used and updated. Data is only removed from the source once it has been written to the
+    /// destination, so the syscall can be restarted if the destination would block. If some data
+    /// was copied before the source or destination would block, the number of bytes copied is
+    /// returned instead.
+    fn transfer(
+        ctx: &mut SyscallContext,
+        src: &Endpoint,
+        src_offset: Option<&mut libc::off_t>,
+        dst: &Endpoint,
+        dst_offset: Option<&mut libc::off_t>,
+        len: usize,
+        opts: TransferOpts,
+    ) -> Result<usize, SyscallError> {
+        let mut plugin_buf = PluginBuf::new(std::cmp::min(len, TRANSFER_CHUNK_SIZE));
+
+        let result = Self::transfer_chunks(
+            ctx,
+            src,
+            src_offset,
+            dst,
+            dst_offset,
+            len,
+            &opts,
+            &mut plugin_buf,
+        );
+
+        // the plugin memory must always be freed
+        plugin_buf.free(ctx);
+
+        result
+    }
+
+    /// Copy chunks of data from `src` to `dst` until `len` bytes have been copied, or until the
+    /// source or destination can't make progress. See [`Self::transfer`].
+    fn transfer_chunks(
+        ctx: &mut SyscallContext,
+        src: &Endpoint,
+        mut src_offset: Option<&mut libc::off_t>,
+        dst: &Endpoint,
+        mut dst_offset: Option<&mut libc::off_t>,
+        len: usize,
+        opts: &TransferOpts,
+        plugin_buf: &mut PluginBuf,
+    ) -> Result<usize, SyscallError> {
+        let mut total_copied = 0;
+
+        while total_copied < len {
+            let result = Self::transfer_chunk(
+                ctx,
+                src,
+                src_offset.as_deref_mut(),
+                dst,
+                dst_offset.as_deref_mut(),
+                len - total_copied,
+                opts,
+                plugin_buf,
+            );
+
+            let num_copied = match result {
+                Ok(x) => x,
+                // return the bytes we've already copied rather than an error
+                Err(_) if total_copied > 0 => break,
+                Err(SyscallError::Blocked(_)) if opts.nonblock => {
+                    return Err(Errno::EWOULDBLOCK.into())
+                }
+                Err(e) => return Err(e),
+            };
+
+            total_copied += num_copied;
+
+            // reached the end of the source, or the destination is full
+            if num_copied == 0 {
+                break;
+            }
+        }
+
+        Ok(total_copied)
+    }
+
+    /// Copy a single chunk of data from `src` to `dst`. Returns the number of bytes copied, which
+    /// is 0 if the source has reached EOF or the destination is full.
+    fn transfer_chunk(
+        ctx: &mut SyscallContext,
+        src: &Endpoint,
+        src_offset: Option<&mut libc::off_t>,
+        dst: &Endpoint,
+        dst_offset: Option<&mut libc::off_t>,
+        len: usize,
+        opts: &TransferOpts,
+        plugin_buf: &mut PluginBuf,
+    ) -> Result<usize, SyscallError> {
+        let mut len = std::cmp::min(len, TRANSFER_CHUNK_SIZE);
+
+        // we can't return data to a non-pipe source (for example a socket), so don't read more than
+        // a destination pipe can hold
+        if let Endpoint::File(File::Pipe(pipe)) = dst {
+            len = std::cmp::min(len, pipe.borrow().space_available());
+            if len == 0 {
+                return Err(Self::would_block(dst, FileState::WRITABLE));
+            }
+        }
+
+        let mut bytes = vec![0u8; len];
+
+        // read from the source without removing data from pipes or advancing file positions
+        let num_read = match src {
+            Endpoint::Regular(file) => {
+                let offset = match src_offset {
+                    Some(ref x) => **x,
+                    None => Self::regular_file_position(*file)?,
+                };
+                let rv = unsafe {
+                    c::regularfile_pread(
+                        *file,
+                        ctx.objs.host,
+                        bytes.as_mut_ptr() as *mut std::ffi::c_void,
+                        bytes.len(),
+                        offset,
+                    )
+                };
+                check_rv(rv)?
+            }
+            Endpoint::File(File::Pipe(pipe)) => match pipe.borrow().peek(&mut bytes) {
+                Err(e) if e == Errno::EWOULDBLOCK.into() => {
+                    return Err(Self::would_block(src, FileState::READABLE))
+                }
+                x => x?,
+            },
+            Endpoint::File(file) => {
+                if src_offset.is_some() {
+                    return Err(Errno::ESPIPE.into());
+                }
+                Self::with_plugin_buf(ctx, plugin_buf, &mut bytes, |ctx, iov| {
+                    Self::readv_helper(ctx, file, &[iov], None, 0)
+                })?
+            }
+        };
+
+        if num_read == 0 {
+            return Ok(0);
+        }
+
+        let bytes = &mut bytes[..num_read];
+
+        let num_written = match dst {
+            Endpoint::Regular(file) => {
+                let buf = bytes.as_ptr() as *const std::ffi::c_void;
+                let rv = match dst_offset {
+                    Some(ref x) => unsafe { c::regularfile_pwrite(*file, buf, bytes.len(), **x) },
+                    None => unsafe { c::regularfile_write(*file, buf, bytes.len()) },
+                };
+                check_rv(rv)?
+            }
+            Endpoint::File(file) => {
+                if dst_offset.is_some() {
+                    return Err(Errno::ESPIPE.into());
+                }
+                Self::with_plugin_buf(ctx, plugin_buf, bytes, |ctx, iov| {
+                    Self::writev_helper(ctx, file, &[iov], None, 0)
+                })?
+            }
+        };
+
+        // now that the data has been written, remove it from the source
+        match src {
+            Endpoint::Regular(file) => match src_offset {
+                Some(x) => *x += libc::off_t::try_from(num_written).unwrap(),
+                None => {
+                    let offset = Self::regular_file_position(*file)?;
+                    let offset = offset + libc::off_t::try_from(num_written).unwrap();
+                    let rv = unsafe { c::regularfile_lseek(*file, offset, libc::SEEK_SET) };
+                    check_rv(rv)?;
+                }
+            },
+            Endpoint::File(File::Pipe(pipe)) if opts.consume => {
+                let num_removed =
+                    crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
+                        CallbackQueue::queue_and_run(|cb_queue| {
+                            pipe.borrow_mut().discard(num_written, cb_queue)
+                        })
+                    })?;
+                assert_eq!(num_removed, num_written);
+            }
+            Endpoint::File(_) => {}
+        }
+
+        if let Some(x) = dst_offset {
+            *x += libc::off_t::try_from(num_written).unwrap();
+        }
+
+        Ok(num_written)
+    }
+
+    /// Returns a blocked error for a rust file, or `EWOULDBLOCK` if the file is non-blocking.
+    fn would_block(endpoint: &Endpoint, state: FileState) -> SyscallError {
+        let Endpoint::File(file) = endpoint else {
+            // regular files never block
+            return Errno::EWOULDBLOCK.into();
+        };
+
+        if file.borrow().get_status().contains(FileStatus::NONBLOCK) {
+            return Errno::EWOULDBLOCK.into();
+        }
+
+        SyscallError::new_blocked(file.clone(), state, file.borrow().supports_sa_restart())
+    }
+
+    /// Get the current file position of a legacy regular file.
+    fn regular_file_position(file: *mut c::RegularFile) -> Result<libc::off_t, Errno> {
+        let rv = unsafe { c::regularfile_lseek(file, 0, libc::SEEK_CUR) };
+        check_rv(rv)?;
+        Ok(rv)
+    }
+
+    /// Run `f` with the buffer in plugin memory. The buffer is initialized with the contents of
+    /// `bytes`, and `bytes` is updated with the contents of the buffer after `f` returns. This
+    /// allows us to use rust files which only operate on plugin memory.
+    fn with_plugin_buf(
+        ctx: &mut SyscallContext,
+        plugin_buf: &mut PluginBuf,
+        bytes: &mut [u8],
+        f: impl FnOnce(&mut SyscallContext, IoVec) -> Result<libc::ssize_t, SyscallError>,
+    ) -> Result<usize, SyscallError> {
+        let ptr = plugin_buf.get(ctx, bytes.len())?;
+        let iov = IoVec {
+            base: ptr.ptr(),
+            len: bytes.len(),
+        };
+
+        ctx.objs
+            .process
+            .memory_borrow_mut()
+            .copy_to_ptr(ptr, bytes)?;
+
+        let rv = f(ctx, iov)?;
+        ctx.objs.process.memory_borrow().copy_from_ptr(bytes, ptr)?;
+
+        Ok(rv.try_into().unwrap())
+    }
+}
+
+/// Convert a return value from a legacy C function, which is a negative errno on failure, to a
+/// result.
+fn check_rv<T>(rv: T) -> Result<usize, Errno>
+where
+    T: TryInto<i64>,
+    T::Error: std::fmt::Debug,
+{
+    let rv: i64 = rv.try_into().unwrap();
+    if rv < 0 {
+        return Err(Errno::try_from(-rv).unwrap());
+    }
+    Ok(rv.try_into().unwrap())
+}
@@ -7,7 +7,7 @@ use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, S
 use crate::host::descriptor::{CompatFile, File, FileState, FileStatus};
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::io::{self, IoVec};
-use crate::host::syscall_types::{ForeignArrayPtr, SyscallError};
+use crate::host::syscall_types::SyscallError;
 use crate::utility::callback_queue::CallbackQueue;
 
 impl SyscallHandler {
@@ -223,11 +223,7 @@ impl SyscallHandler {
                 return Err(Errno::ESPIPE.into());
             }
 
-            let args = RecvmsgArgs {
-                iovs,
-                control_ptr: ForeignArrayPtr::new(ForeignPtr::null(), 0),
-                flags: 0,
-            };
+            let args = RecvmsgArgs { iovs, flags: 0 };
 
             // call the socket's recvmsg(), and run any resulting events
             let RecvmsgReturn { return_val, .. } =
@@ -484,7 +480,6 @@ impl SyscallHandler {
             let args = SendmsgArgs {
                 addr: None,
                 iovs,
-                control_ptr: ForeignArrayPtr::new(ForeignPtr::null(), 0),
                 flags: 0,
             };
 
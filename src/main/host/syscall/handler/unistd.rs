@@ -8,10 +8,15 @@ use log::*;
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use syscall_logger::log_syscall;
 
+use crate::core::worker::Worker;
 use crate::cshadow as c;
 use crate::host::descriptor::pipe;
 use crate::host::descriptor::shared_buf::SharedBuf;
-use crate::host::descriptor::{CompatFile, Descriptor, File, FileMode, FileStatus, OpenFile};
+use crate::host::descriptor::socket::inet::InetSocket;
+use crate::host::descriptor::socket::Socket;
+use crate::host::descriptor::{
+    CompatFile, Descriptor, File, FileMode, FileState, FileStatus, OpenFile,
+};
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::io::IoVec;
 use crate::host::syscall::type_formatting::SyscallBufferArg;
@@ -23,6 +28,12 @@ impl SyscallHandler {
     pub fn close(ctx: &mut SyscallContext, fd: std::ffi::c_int) -> SyscallResult {
         trace!("Trying to close fd {}", fd);
 
+        // we were previously blocked on a lingering tcp socket, and the descriptor was already
+        // closed; like linux, return success even if the linger time expired
+        if ctx.objs.thread.syscall_condition().is_some() {
+            return Ok(0.into());
+        }
+
         let fd = fd.try_into().or(Err(linux_api::errno::Errno::EBADF))?;
 
         // according to "man 2 close", in Linux any errors that may occur will happen after the fd is
@@ -35,13 +46,44 @@ impl SyscallHandler {
             .deregister_descriptor(fd)
             .ok_or(linux_api::errno::Errno::EBADF)?;
 
+        // keep the file in case we need to block on it after it's closed
+        let file = match desc.file() {
+            CompatFile::New(file) => Some(file.inner_file().clone()),
+            CompatFile::Legacy(_) => None,
+        };
+
         // if there are still valid descriptors to the open file, close() will do nothing
         // and return None
-        crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
+        let rv = crate::utility::legacy_callback_queue::with_global_cb_queue(|| {
             CallbackQueue::queue_and_run(|cb_queue| desc.close(ctx.objs.host, cb_queue))
-                .unwrap_or(Ok(()))
-                .map(|()| 0.into())
-        })
+        });
+
+        let Some(rv) = rv else {
+            return Ok(0.into());
+        };
+        rv?;
+
+        // with a non-zero SO_LINGER time, block until the peer acknowledges our data or the time
+        // expires
+        let linger_timeout = match &file {
+            Some(File::Socket(Socket::Inet(InetSocket::LegacyTcp(socket)))) => {
+                socket.borrow().linger_timeout()
+            }
+            _ => None,
+        };
+
+        if let Some(timeout) = linger_timeout {
+            let mut err = SyscallError::new_blocked(
+                file.unwrap(),
+                FileState::SOCKET_LINGER_DONE,
+                /* restartable= */ false,
+            );
+            let timeout = Worker::current_time().unwrap().saturating_add(timeout);
+            err.blocked_condition().unwrap().set_timeout(Some(timeout));
+            return Err(err);
+        }
+
+        Ok(0.into())
     }
 
     #[log_syscall(/* rv */ std::ffi::c_int, /* oldfd */ std::ffi::c_int)]
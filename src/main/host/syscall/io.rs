@@ -400,6 +400,107 @@ pub fn update_msghdr(
     Ok(())
 }
 
+/// A control message from a [`MsgHdr`]'s control buffer. See `cmsg(3)`.
+pub struct ControlMessage {
+    pub level: libc::c_int,
+    pub cmsg_type: libc::c_int,
+    pub data: Vec<u8>,
+}
+
+/// Analogous to `CMSG_ALIGN()`.
+fn cmsg_align(len: usize) -> usize {
+    let align = std::mem::size_of::<libc::size_t>();
+    (len + align - 1) & !(align - 1)
+}
+
+/// Analogous to [`libc::CMSG_LEN`].
+pub fn cmsg_len(data_len: usize) -> usize {
+    cmsg_align(std::mem::size_of::<libc::cmsghdr>()) + data_len
+}
+
+/// Analogous to [`libc::CMSG_SPACE`].
+pub fn cmsg_space(data_len: usize) -> usize {
+    cmsg_align(std::mem::size_of::<libc::cmsghdr>()) + cmsg_align(data_len)
+}
+
+/// Read the control messages from a plugin's control buffer, as given in a [`libc::msghdr`].
+/// Returns `EINVAL` if a control message has an invalid length.
+pub fn read_cmsgs(
+    mem: &MemoryManager,
+    control: ForeignPtr<u8>,
+    control_len: libc::size_t,
+) -> Result<Vec<ControlMessage>, Errno> {
+    const LEN_SIZE: usize = std::mem::size_of::<libc::size_t>();
+    const INT_SIZE: usize = std::mem::size_of::<libc::c_int>();
+
+    if control_len == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut buf = vec![0u8; control_len];
+    mem.copy_from_ptr(&mut buf, ForeignArrayPtr::new(control, control_len))?;
+
+    let hdr_len = cmsg_len(0);
+    let mut cmsgs = Vec::new();
+    let mut offset = 0;
+
+    // like linux, stop when there isn't enough space left for another header
+    while buf.len() - offset >= hdr_len {
+        let hdr = &buf[offset..];
+        let len = libc::size_t::from_ne_bytes(hdr[..LEN_SIZE].try_into().unwrap());
+        let level = libc::c_int::from_ne_bytes(hdr[LEN_SIZE..][..INT_SIZE].try_into().unwrap());
+        let cmsg_type =
+            libc::c_int::from_ne_bytes(hdr[LEN_SIZE + INT_SIZE..][..INT_SIZE].try_into().unwrap());
+
+        if len < hdr_len || len > buf.len() - offset {
+            return Err(Errno::EINVAL);
+        }
+
+        cmsgs.push(ControlMessage {
+            level,
+            cmsg_type,
+            data: hdr[hdr_len..len].to_vec(),
+        });
+
+        offset = std::cmp::min(offset + cmsg_align(len), buf.len());
+    }
+
+    Ok(cmsgs)
+}
+
+/// Write control messages to a plugin's control buffer of length `control_len`, returning the
+/// number of bytes used. The control messages are written in order and must fit in the buffer,
+/// other than the padding after the last message.
+pub fn write_cmsgs(
+    mem: &mut MemoryManager,
+    control: ForeignPtr<u8>,
+    control_len: libc::size_t,
+    cmsgs: &[ControlMessage],
+) -> Result<libc::size_t, Errno> {
+    let mut buf = Vec::new();
+
+    for cmsg in cmsgs {
+        let start = buf.len();
+        buf.extend_from_slice(&cmsg_len(cmsg.data.len()).to_ne_bytes());
+        buf.extend_from_slice(&cmsg.level.to_ne_bytes());
+        buf.extend_from_slice(&cmsg.cmsg_type.to_ne_bytes());
+        buf.resize(start + cmsg_len(0), 0);
+        buf.extend_from_slice(&cmsg.data);
+        buf.resize(start + cmsg_space(cmsg.data.len()), 0);
+    }
+
+    assert!(buf.len() <= cmsg_align(control_len));
+    buf.truncate(control_len);
+
+    if buf.is_empty() {
+        return Ok(0);
+    }
+
+    mem.copy_to_ptr(ForeignArrayPtr::new(control, buf.len()), &buf)?;
+
+    Ok(buf.len())
+}
+
 /// Helper to read a plugin's [`libc::msghdr`] into a [`MsgHdr`]. While `msg` is a local struct, it
 /// should have been copied from plugin memory, meaning any pointers in the struct are pointers to
 /// plugin memory, not local memory.
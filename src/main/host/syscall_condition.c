@@ -28,6 +28,12 @@ struct _SysCallCondition {
     Timer* timeout;
     // The active file in the blocked syscall. This is state used when resuming a blocked syscall.
     OpenFile* activeFile;
+    // How much of its work (such as the number of messages received) the blocked syscall had
+    // completed before blocking. This is state used when resuming a blocked syscall.
+    uint64_t progress;
+    // A time limit for the blocked syscall that, unlike timeoutExpiration, doesn't wake it up, or
+    // EMUTIME_INVALID if none. This is state used when resuming a blocked syscall.
+    CEmulatedTime deadline;
     // Non-null if we are listening for status updates on a trigger object
     StatusListener* triggerListener;
     // The host
@@ -51,6 +57,7 @@ SysCallCondition* syscallcondition_new(Trigger trigger) {
     SysCallCondition* cond = malloc(sizeof(*cond));
 
     *cond = (SysCallCondition){.timeoutExpiration = EMUTIME_INVALID,
+                               .deadline = EMUTIME_INVALID,
                                .timeout = NULL,
                                .trigger = trigger,
                                .referenceCount = 1,
@@ -527,3 +534,19 @@ CEmulatedTime syscallcondition_getTimeout(SysCallCondition* cond) {
 }
 
 OpenFile* syscallcondition_getActiveFile(SysCallCondition* cond) { return cond->activeFile; }
+
+void syscallcondition_setProgress(SysCallCondition* cond, uint64_t progress) {
+    MAGIC_ASSERT(cond);
+
+    cond->progress = progress;
+}
+
+uint64_t syscallcondition_getProgress(SysCallCondition* cond) { return cond->progress; }
+
+void syscallcondition_setDeadline(SysCallCondition* cond, CEmulatedTime t) {
+    MAGIC_ASSERT(cond);
+
+    cond->deadline = t;
+}
+
+CEmulatedTime syscallcondition_getDeadline(SysCallCondition* cond) { return cond->deadline; }
@@ -62,6 +62,14 @@ void syscallcondition_setTimeout(SysCallCondition* cond, CEmulatedTime t);
  * the descriptor table). */
 void syscallcondition_setActiveFile(SysCallCondition* cond, OpenFile* file);
 
+/* Record how much of its work the syscall completed before blocking, so that the syscall handler
+ * can continue from there once it becomes unblocked. */
+void syscallcondition_setProgress(SysCallCondition* cond, uint64_t progress);
+
+/* Record a time limit for the syscall that is checked by the syscall handler once it becomes
+ * unblocked. Unlike the timeout, the deadline doesn't wake up the syscall. */
+void syscallcondition_setDeadline(SysCallCondition* cond, CEmulatedTime t);
+
 /* Increment the reference count on the given condition. */
 void syscallcondition_ref(SysCallCondition* cond);
 
@@ -86,6 +94,12 @@ CEmulatedTime syscallcondition_getTimeout(SysCallCondition* cond);
 /* Get the active file for the condition, or NULL if there isn't one. */
 OpenFile* syscallcondition_getActiveFile(SysCallCondition* cond);
 
+/* Get the work completed before the syscall blocked, or 0 if it wasn't set. */
+uint64_t syscallcondition_getProgress(SysCallCondition* cond);
+
+/* Get the deadline for the condition, or EMUTIME_INVALID if there isn't one. */
+CEmulatedTime syscallcondition_getDeadline(SysCallCondition* cond);
+
 /* If the condition's thread doesn't have `signo` blocked, schedule a wakeup.
  *
  * Returns whether a wakeup was scheduled.
@@ -45,6 +45,18 @@ impl<'a> SysCallConditionRef<'a> {
         let timeout = unsafe { cshadow::syscallcondition_getTimeout(self.c_ptr) };
         EmulatedTime::from_c_emutime(timeout)
     }
+
+    /// How much of its work the syscall completed before blocking. See
+    /// [`SysCallConditionRefMut::set_progress`].
+    pub fn progress(&self) -> u64 {
+        unsafe { cshadow::syscallcondition_getProgress(self.c_ptr) }
+    }
+
+    /// The time limit for the syscall. See [`SysCallConditionRefMut::set_deadline`].
+    pub fn deadline(&self) -> Option<EmulatedTime> {
+        let deadline = unsafe { cshadow::syscallcondition_getDeadline(self.c_ptr) };
+        EmulatedTime::from_c_emutime(deadline)
+    }
 }
 
 /// A mutable reference to a syscall condition.
@@ -74,6 +86,20 @@ impl<'a> SysCallConditionRefMut<'a> {
         unsafe { cshadow::syscallcondition_setActiveFile(self.condition.c_ptr, file_ptr) };
     }
 
+    /// Record how much of its work (such as the number of messages received) the syscall
+    /// completed before blocking, so that the syscall handler can continue from there once it
+    /// becomes unblocked.
+    pub fn set_progress(&mut self, progress: u64) {
+        unsafe { cshadow::syscallcondition_setProgress(self.condition.c_ptr, progress) };
+    }
+
+    /// Record a time limit for the syscall that the syscall handler checks once it becomes
+    /// unblocked. Unlike [`set_timeout`](Self::set_timeout), this doesn't wake up the syscall.
+    pub fn set_deadline(&mut self, deadline: Option<EmulatedTime>) {
+        let deadline = EmulatedTime::to_c_emutime(deadline);
+        unsafe { cshadow::syscallcondition_setDeadline(self.condition.c_ptr, deadline) };
+    }
+
     pub fn wakeup_for_signal(&mut self, host: &Host, signal: Signal) -> bool {
         unsafe {
             cshadow::syscallcondition_wakeupForSignal(self.condition.c_ptr, host, signal.into())
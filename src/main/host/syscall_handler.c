@@ -311,6 +311,7 @@ SyscallReturn syscallhandler_make_syscall(SysCallHandler* sys, const SysCallArgs
 #endif
             HANDLE_RUST(close);
             HANDLE_RUST(connect);
+            HANDLE_RUST(copy_file_range);
             HANDLE_C(creat);
             HANDLE_RUST(dup);
             HANDLE_RUST(dup2);
@@ -377,6 +378,7 @@ SyscallReturn syscallhandler_make_syscall(SysCallHandler* sys, const SysCallArgs
             HANDLE_C(linkat);
             HANDLE_RUST(listen);
             HANDLE_C(lseek);
+            HANDLE_C(lstat);
             HANDLE_C(mkdirat);
             HANDLE_C(mknodat);
             HANDLE_RUST(mmap);
@@ -414,6 +416,7 @@ SyscallReturn syscallhandler_make_syscall(SysCallHandler* sys, const SysCallArgs
             HANDLE_C(readlinkat);
             HANDLE_RUST(readv);
             HANDLE_RUST(recvfrom);
+            HANDLE_RUST(recvmmsg);
             HANDLE_RUST(recvmsg);
             HANDLE_C(renameat);
             HANDLE_C(renameat2);
@@ -426,6 +429,8 @@ SyscallReturn syscallhandler_make_syscall(SysCallHandler* sys, const SysCallArgs
             HANDLE_C(shadow_init_memory_manager);
             HANDLE_C(shadow_yield);
             HANDLE_C(select);
+            HANDLE_RUST(sendfile);
+            HANDLE_RUST(sendmmsg);
             HANDLE_RUST(sendmsg);
             HANDLE_RUST(sendto);
             HANDLE_RUST(setsockopt);
@@ -450,6 +455,8 @@ SyscallReturn syscallhandler_make_syscall(SysCallHandler* sys, const SysCallArgs
             HANDLE_RUST(shutdown);
             HANDLE_RUST(socket);
             HANDLE_RUST(socketpair);
+            HANDLE_RUST(splice);
+            HANDLE_C(stat);
 #ifdef SYS_statx
             HANDLE_C(statx);
 #endif
@@ -457,6 +464,7 @@ SyscallReturn syscallhandler_make_syscall(SysCallHandler* sys, const SysCallArgs
             HANDLE_C(sync_file_range);
             HANDLE_C(syncfs);
             HANDLE_RUST(sysinfo);
+            HANDLE_RUST(tee);
             HANDLE_C(tgkill);
             SHIM_ONLY(time);
             HANDLE_RUST(timerfd_create);
@@ -464,6 +472,7 @@ SyscallReturn syscallhandler_make_syscall(SysCallHandler* sys, const SysCallArgs
             HANDLE_RUST(timerfd_settime);
             HANDLE_C(tkill);
             HANDLE_C(uname);
+            HANDLE_C(unlink);
             HANDLE_C(unlinkat);
             HANDLE_C(utimensat);
             HANDLE_RUST(vfork);
@@ -483,16 +492,7 @@ SyscallReturn syscallhandler_make_syscall(SysCallHandler* sys, const SysCallArgs
             UNSUPPORTED(waitid);
             UNSUPPORTED(msync);
 
-            // copying data between various types of fds
-            UNSUPPORTED(copy_file_range);
-            UNSUPPORTED(sendfile);
-            UNSUPPORTED(splice);
             UNSUPPORTED(vmsplice);
-            UNSUPPORTED(tee);
-
-            //// additional socket io
-            UNSUPPORTED(recvmmsg);
-            UNSUPPORTED(sendmmsg);
 
             // ***************************************
             // We think we don't need to handle these
@@ -520,7 +520,6 @@ SyscallReturn syscallhandler_make_syscall(SysCallHandler* sys, const SysCallArgs
             NATIVE(llistxattr);
             NATIVE(lremovexattr);
             NATIVE(lsetxattr);
-            NATIVE(lstat);
             NATIVE(madvise);
             NATIVE(mkdir);
             NATIVE(mknod);
@@ -539,14 +538,12 @@ SyscallReturn syscallhandler_make_syscall(SysCallHandler* sys, const SysCallArgs
             NATIVE(setrlimit);
             NATIVE(setuid);
             NATIVE(setxattr);
-            NATIVE(stat);
 #ifdef SYS_stat64
             NATIVE(stat64);
 #endif
             NATIVE(statfs);
             NATIVE(symlink);
             NATIVE(truncate);
-            NATIVE(unlink);
             NATIVE(utime);
             NATIVE(utimes);
 
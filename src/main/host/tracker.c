@@ -219,7 +219,6 @@ static void _tracker_updateCounters(Counters* c, gsize header, gsize payload,
 
 void tracker_addInputBytes(Tracker* tracker, Packet* packet, const CompatSocket* socket) {
     MAGIC_ASSERT(tracker);
-    guintptr handle = _tracker_socketHandle(socket);
 
     if(!(tracker->loginfo & LOG_INFO_FLAGS_NODE) && !(tracker->loginfo & LOG_INFO_FLAGS_SOCKET)) {
         return;
@@ -238,7 +237,9 @@ void tracker_addInputBytes(Tracker* tracker, Packet* packet, const CompatSocket*
         }
     }
 
-    if(tracker->loginfo & LOG_INFO_FLAGS_SOCKET) {
+    /* packets generated by the network interface (such as ICMP replies) don't have a socket */
+    if((tracker->loginfo & LOG_INFO_FLAGS_SOCKET) && socket != NULL) {
+        guintptr handle = _tracker_socketHandle(socket);
         SocketStats* ss = g_hash_table_lookup(tracker->socketStats, &handle);
         if(ss) {
             if(isLocal) {
@@ -252,7 +253,6 @@ void tracker_addInputBytes(Tracker* tracker, Packet* packet, const CompatSocket*
 
 void tracker_addOutputBytes(Tracker* tracker, Packet* packet, const CompatSocket* socket) {
     MAGIC_ASSERT(tracker);
-    guintptr handle = _tracker_socketHandle(socket);
 
     if(!(tracker->loginfo & LOG_INFO_FLAGS_NODE) && !(tracker->loginfo & LOG_INFO_FLAGS_SOCKET)) {
         return;
@@ -271,7 +271,9 @@ void tracker_addOutputBytes(Tracker* tracker, Packet* packet, const CompatSocket
         }
     }
 
-    if(tracker->loginfo & LOG_INFO_FLAGS_SOCKET) {
+    /* packets generated by the network interface (such as ICMP replies) don't have a socket */
+    if((tracker->loginfo & LOG_INFO_FLAGS_SOCKET) && socket != NULL) {
+        guintptr handle = _tracker_socketHandle(socket);
         SocketStats* ss = g_hash_table_lookup(tracker->socketStats, &handle);
         if(ss) {
             if(isLocal) {
@@ -22,6 +22,8 @@ void tracker_free(Tracker* tracker);
 
 void tracker_addProcessingTimeNanos(Tracker* tracker, CSimulationTime processingTime);
 void tracker_addVirtualProcessingDelay(Tracker* tracker, CSimulationTime delay);
+/* `socket` may be NULL if the packet doesn't belong to a socket, in which case the bytes are only
+ * counted for the host. */
 void tracker_addInputBytes(Tracker* tracker, Packet* packet, const CompatSocket* socket);
 void tracker_addOutputBytes(Tracker* tracker, Packet* packet, const CompatSocket* socket);
 void tracker_addAllocatedBytes(Tracker* tracker, gpointer location, gsize allocatedBytes);
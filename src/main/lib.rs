@@ -21,9 +21,9 @@ pub mod cshadow {
     include!(concat!(env!("OUT_DIR"), "/cshadow.rs"));
 }
 
-// shadow re-exports this definition from /usr/include/linux/tcp.h
+// shadow re-defines this struct from /usr/include/linux/tcp.h
 // TODO: Provide this via the linux-api crate instead.
-unsafe impl shadow_pod::Pod for crate::cshadow::tcp_info {}
+unsafe impl shadow_pod::Pod for crate::cshadow::TCPInfo {}
 
 // check that the size and alignment of `CompatUntypedForeignPtr` and `ForeignPtr<()>` are the same`
 static_assertions::assert_eq_size!(
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+use shadow_shim_helper_rs::HostId;
+
+use crate::core::work::event::Event;
+use crate::network::graph::Bottleneck;
+
+/// A drop-tail queue for a bandwidth-limited graph edge. Packets are transmitted one at a time at
+/// the edge's bandwidth, and packets that arrive when the queue is full are dropped.
+#[derive(Debug)]
+pub struct BottleneckQueue {
+    bandwidth_bits: u64,
+    queue_size_bytes: u64,
+    /// The time at which the edge will finish transmitting all queued packets.
+    busy_until: EmulatedTime,
+}
+
+impl BottleneckQueue {
+    pub fn new(bandwidth_bits: u64, queue_size_bytes: u64) -> Self {
+        assert!(bandwidth_bits > 0);
+        Self {
+            bandwidth_bits,
+            queue_size_bytes,
+            busy_until: EmulatedTime::SIMULATION_START,
+        }
+    }
+
+    /// The time needed to transmit `bytes` bytes at the edge's bandwidth.
+    fn transmit_time(&self, bytes: u64) -> SimulationTime {
+        let nanos = u128::from(bytes) * 8 * 1_000_000_000 / u128::from(self.bandwidth_bits);
+        SimulationTime::from_nanos(nanos.try_into().unwrap())
+    }
+
+    /// The number of bytes that are still queued at the given time.
+    fn queued_bytes(&self, time: EmulatedTime) -> u64 {
+        let Some(remaining) = self.busy_until.checked_duration_since(&time) else {
+            return 0;
+        };
+        let bytes = remaining.as_nanos() * u128::from(self.bandwidth_bits) / (8 * 1_000_000_000);
+        bytes.try_into().unwrap()
+    }
+
+    /// Add a packet of `size_bytes` bytes to the queue at the given time. Returns the additional
+    /// delay of the packet (its time spent queued and being transmitted), or `None` if the packet
+    /// was dropped.
+    pub fn push(&mut self, time: EmulatedTime, size_bytes: u64) -> Option<SimulationTime> {
+        if self.queued_bytes(time) + size_bytes > self.queue_size_bytes {
+            return None;
+        }
+
+        let start = std::cmp::max(time, self.busy_until);
+        self.busy_until = start + self.transmit_time(size_bytes);
+
+        Some(self.busy_until - time)
+    }
+}
+
+/// A packet that was sent over a path with bottleneck edges, and is waiting to be queued on them.
+#[derive(Debug)]
+pub struct PendingPacket {
+    /// The time that the packet was sent.
+    pub send_time: EmulatedTime,
+    /// The bottleneck edges on the packet's path, in the order that they are traversed.
+    pub bottlenecks: Vec<Bottleneck>,
+    pub size_bytes: u64,
+    /// The packet event for the destination host, using the packet's delivery time without
+    /// queueing.
+    pub event: Event,
+    pub dst_host_id: HostId,
+}
+
+/// The queues of all bottleneck edges in the graph.
+///
+/// Packets from different hosts may be sent in parallel on different threads, so packets that
+/// cross a bottleneck are collected during each scheduling round and queued at the end of the
+/// round in a deterministic order. Since packets are never delivered within the round they were
+/// sent, this doesn't change when they can be delivered.
+#[derive(Debug, Default)]
+pub struct Bottlenecks {
+    queues: Mutex<HashMap<(u32, u32), BottleneckQueue>>,
+    pending: Mutex<Vec<PendingPacket>>,
+}
+
+impl Bottlenecks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a packet to be queued at the end of the current round.
+    pub fn push_pending(&self, packet: PendingPacket) {
+        self.pending.lock().unwrap().push(packet);
+    }
+
+    /// Queue all packets that were sent during the round on their bottleneck edges. Returns the
+    /// packets that were not dropped, with their delivery times updated to include the queueing
+    /// delay.
+    pub fn process_pending(&self) -> Vec<(Event, HostId)> {
+        let mut pending = std::mem::take(&mut *self.pending.lock().unwrap());
+
+        // the order that the packets were pushed depends on the thread scheduling, so sort them
+        // by the time they were sent, and then by the event order (source host and event id)
+        pending.sort_by(|a, b| {
+            a.send_time
+                .cmp(&b.send_time)
+                .then_with(|| a.event.partial_cmp(&b.event).unwrap())
+        });
+
+        let mut queues = self.queues.lock().unwrap();
+
+        pending
+            .into_iter()
+            .filter_map(|mut packet| {
+                let delay = push_on_path(
+                    &mut queues,
+                    &packet.bottlenecks,
+                    packet.send_time,
+                    packet.size_bytes,
+                )?;
+
+                let time = packet.event.time();
+                packet.event.set_time(time + delay);
+
+                Some((packet.event, packet.dst_host_id))
+            })
+            .collect()
+    }
+}
+
+/// Queue a packet of `size_bytes` bytes on each of the `bottlenecks` in turn, starting at the given
+/// time. The packet arrives at each edge when the previous edge has finished transmitting it (the
+/// latency between the edges isn't included). Returns the total additional delay of the packet, or
+/// `None` if any of the edges dropped it.
+fn push_on_path(
+    queues: &mut HashMap<(u32, u32), BottleneckQueue>,
+    bottlenecks: &[Bottleneck],
+    time: EmulatedTime,
+    size_bytes: u64,
+) -> Option<SimulationTime> {
+    let mut arrival_time = time;
+
+    for bottleneck in bottlenecks {
+        let queue = queues
+            .entry((bottleneck.source, bottleneck.target))
+            .or_insert_with(|| {
+                BottleneckQueue::new(bottleneck.bandwidth_bits, bottleneck.queue_size_bytes)
+            });
+
+        let Some(delay) = queue.push(arrival_time, size_bytes) else {
+            log::trace!(
+                "Dropped packet on full bottleneck edge {}->{}",
+                bottleneck.source,
+                bottleneck.target,
+            );
+            return None;
+        };
+
+        arrival_time += delay;
+    }
+
+    Some(arrival_time - time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(nanos: u64) -> EmulatedTime {
+        EmulatedTime::SIMULATION_START + SimulationTime::from_nanos(nanos)
+    }
+
+    #[test]
+    fn test_queue_delay() {
+        // 8 Gbit/s, so 1 byte takes 1 ns
+        let mut queue = BottleneckQueue::new(8_000_000_000, 1000);
+
+        assert_eq!(
+            queue.push(time(0), 100),
+            Some(SimulationTime::from_nanos(100))
+        );
+        // queued behind the first packet
+        assert_eq!(
+            queue.push(time(50), 100),
+            Some(SimulationTime::from_nanos(150))
+        );
+        // the queue has emptied
+        assert_eq!(
+            queue.push(time(500), 100),
+            Some(SimulationTime::from_nanos(100))
+        );
+    }
+
+    #[test]
+    fn test_queue_drop() {
+        let mut queue = BottleneckQueue::new(8_000_000_000, 250);
+
+        assert!(queue.push(time(0), 100).is_some());
+        assert!(queue.push(time(0), 100).is_some());
+        // 200 bytes are queued, so there is no room for another 100
+        assert!(queue.push(time(0), 100).is_none());
+        // after 100 ns, 100 bytes have been transmitted
+        assert!(queue.push(time(100), 100).is_some());
+    }
+
+    #[test]
+    fn test_path_shared_edge() {
+        let bottleneck = |source, target, bandwidth_bits| Bottleneck {
+            source,
+            target,
+            bandwidth_bits,
+            queue_size_bytes: 1_000_000,
+        };
+
+        // 8 Gbit/s, so 1 byte takes 1 ns
+        let shared = bottleneck(1, 2, 8_000_000_000);
+        // 800 Mbit/s, so 1 byte takes 10 ns
+        let path_1 = [shared, bottleneck(2, 3, 800_000_000)];
+        let path_2 = [shared, bottleneck(2, 4, 800_000_000)];
+
+        let mut queues = HashMap::new();
+
+        // the packet is transmitted by each edge in turn
+        assert_eq!(
+            push_on_path(&mut queues, &path_1, time(0), 100),
+            Some(SimulationTime::from_nanos(100 + 1000))
+        );
+
+        // the shared edge isn't the narrowest edge of either path, but a packet on the second path
+        // still waits for the first path's packet on the shared edge
+        assert_eq!(
+            push_on_path(&mut queues, &path_2, time(0), 100),
+            Some(SimulationTime::from_nanos(200 + 1000))
+        );
+
+        // the edges after the shared edge are not shared, so a packet only waits for earlier
+        // packets on its own path
+        assert_eq!(
+            push_on_path(&mut queues, &path_1, time(0), 100),
+            Some(SimulationTime::from_nanos(300 + 1000 + 800))
+        );
+    }
+}
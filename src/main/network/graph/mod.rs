@@ -7,8 +7,10 @@ use std::hash::Hash;
 
 use anyhow::Context;
 use log::*;
-use petgraph::graph::NodeIndex;
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use petgraph::visit::EdgeRef;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
 
 use crate::core::support::configuration::{
     self, Compression, FileSource, GraphOptions, GraphSource,
@@ -19,6 +21,10 @@ use crate::utility::tilde_expansion;
 
 type NetGraphError = Box<dyn Error + Send + Sync + 'static>;
 
+/// The maximum number of equal-cost paths between two nodes that are used for equal-cost multipath
+/// routing.
+pub const MAX_ECMP_PATHS: usize = 64;
+
 /// A graph node.
 #[derive(Debug, PartialEq, Eq)]
 pub struct ShadowNode {
@@ -69,6 +75,12 @@ pub struct ShadowEdge {
     pub latency: units::Time<units::TimePrefix>,
     pub jitter: units::Time<units::TimePrefix>,
     pub packet_loss: f32,
+    pub burst_loss: Option<BurstLoss>,
+    pub bandwidth: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+    pub queue_size: units::Bytes<units::SiPrefixUpper>,
+    pub reorder_probability: f32,
+    pub reorder_delay: units::Time<units::TimePrefix>,
+    pub duplicate_probability: f32,
 }
 
 impl TryFrom<gml_parser::gml::Edge<'_>> for ShadowEdge {
@@ -98,17 +110,216 @@ impl TryFrom<gml_parser::gml::Edge<'_>> for ShadowEdge {
                 Some(x) => x.as_float().ok_or("Edge 'packet_loss' is not a float")?,
                 None => 0.0,
             },
+            burst_loss: BurstLoss::from_gml_edge(&mut gml_edge)?,
+            bandwidth: gml_edge
+                .other
+                .remove("bandwidth")
+                .map(|bandwidth| {
+                    bandwidth
+                        .as_str()
+                        .ok_or("Edge 'bandwidth' is not a string")?
+                        .parse()
+                        .map_err(|e| format!("Edge 'bandwidth' is not a valid unit: {}", e))
+                })
+                .transpose()?,
+            queue_size: match gml_edge.other.remove("queue_size") {
+                Some(x) => x
+                    .as_str()
+                    .ok_or("Edge 'queue_size' is not a string")?
+                    .parse()
+                    .map_err(|e| format!("Edge 'queue_size' is not a valid unit: {}", e))?,
+                None => units::Bytes::new(64, units::SiPrefixUpper::Kibi),
+            },
+            reorder_probability: match gml_edge.other.remove("reorder_probability") {
+                Some(x) => x
+                    .as_float()
+                    .ok_or("Edge 'reorder_probability' is not a float")?,
+                None => 0.0,
+            },
+            reorder_delay: match gml_edge.other.remove("reorder_delay") {
+                Some(x) => x
+                    .as_str()
+                    .ok_or("Edge 'reorder_delay' is not a string")?
+                    .parse()
+                    .map_err(|e| format!("Edge 'reorder_delay' is not a valid unit: {}", e))?,
+                None => units::Time::new(0, units::TimePrefix::Milli),
+            },
+            duplicate_probability: match gml_edge.other.remove("duplicate_probability") {
+                Some(x) => x
+                    .as_float()
+                    .ok_or("Edge 'duplicate_probability' is not a float")?,
+                None => 0.0,
+            },
         };
 
-        if rv.packet_loss < 0f32 || rv.packet_loss > 1f32 {
+        rv.validate()?;
+
+        Ok(rv)
+    }
+}
+
+impl ShadowEdge {
+    /// Check that the edge's attributes are valid.
+    fn validate(&self) -> Result<(), String> {
+        if self.packet_loss < 0f32 || self.packet_loss > 1f32 {
             return Err("Edge 'packet_loss' is not in the range [0,1]".into());
         }
 
-        if rv.latency.value() == 0 {
+        if self.latency.value() == 0 {
             return Err("Edge 'latency' must not be 0".into());
         }
 
-        Ok(rv)
+        // packets must always have a non-zero latency, even after subtracting the jitter
+        if self
+            .jitter
+            .convert(units::TimePrefix::Nano)
+            .unwrap()
+            .value()
+            >= self
+                .latency
+                .convert(units::TimePrefix::Nano)
+                .unwrap()
+                .value()
+        {
+            return Err("Edge 'jitter' must be smaller than the edge 'latency'".into());
+        }
+
+        if let Some(burst_loss) = &self.burst_loss {
+            burst_loss.validate()?;
+        }
+
+        if let Some(bandwidth) = &self.bandwidth {
+            if bandwidth.value() == 0 {
+                return Err("Edge 'bandwidth' must not be 0".into());
+            }
+        }
+
+        if self.queue_size.value() == 0 {
+            return Err("Edge 'queue_size' must not be 0".into());
+        }
+
+        if !(0f32..=1f32).contains(&self.reorder_probability) {
+            return Err("Edge 'reorder_probability' is not in the range [0,1]".into());
+        }
+
+        if self.reorder_probability > 0f32 && self.reorder_delay.value() == 0 {
+            return Err("Edge 'reorder_delay' must not be 0 if packets can be reordered".into());
+        }
+
+        if !(0f32..=1f32).contains(&self.duplicate_probability) {
+            return Err("Edge 'duplicate_probability' is not in the range [0,1]".into());
+        }
+
+        Ok(())
+    }
+}
+
+/// A two-state (Gilbert-Elliott) packet loss model. The model is either in a "good" or a "bad"
+/// state, each with its own packet loss, and may change state after each packet. This produces
+/// bursts of lost packets rather than independent losses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BurstLoss {
+    /// The chance of moving from the good state to the bad state after each packet.
+    pub good_to_bad: f32,
+    /// The chance of moving from the bad state to the good state after each packet.
+    pub bad_to_good: f32,
+    /// The packet loss in the good state.
+    pub loss_good: f32,
+    /// The packet loss in the bad state.
+    pub loss_bad: f32,
+}
+
+impl BurstLoss {
+    /// Parse the burst loss attributes of a gml edge. Returns `None` if the edge doesn't have a
+    /// burst loss model.
+    fn from_gml_edge(gml_edge: &mut gml_parser::gml::Edge) -> Result<Option<Self>, String> {
+        let mut take_float = |name: &str| -> Result<Option<f32>, String> {
+            gml_edge
+                .other
+                .remove(name)
+                .map(|x| {
+                    x.as_float()
+                        .ok_or_else(|| format!("Edge '{name}' is not a float"))
+                })
+                .transpose()
+        };
+
+        let good_to_bad = take_float("burst_loss_good_to_bad")?;
+        let bad_to_good = take_float("burst_loss_bad_to_good")?;
+        let loss_good = take_float("burst_loss_good")?;
+        let loss_bad = take_float("burst_loss_bad")?;
+
+        let (good_to_bad, bad_to_good) = match (good_to_bad, bad_to_good) {
+            (Some(good_to_bad), Some(bad_to_good)) => (good_to_bad, bad_to_good),
+            (None, None) => {
+                if loss_good.is_some() || loss_bad.is_some() {
+                    return Err(concat!(
+                        "Edge 'burst_loss_good' and 'burst_loss_bad' require ",
+                        "'burst_loss_good_to_bad' and 'burst_loss_bad_to_good'"
+                    )
+                    .into());
+                }
+                return Ok(None);
+            }
+            _ => {
+                return Err(concat!(
+                    "Edge 'burst_loss_good_to_bad' and 'burst_loss_bad_to_good' ",
+                    "must be provided together"
+                )
+                .into())
+            }
+        };
+
+        Ok(Some(Self {
+            good_to_bad,
+            bad_to_good,
+            loss_good: loss_good.unwrap_or(0.0),
+            loss_bad: loss_bad.unwrap_or(1.0),
+        }))
+    }
+
+    /// Check that the model's probabilities are valid.
+    fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("burst_loss_good_to_bad", self.good_to_bad),
+            ("burst_loss_bad_to_good", self.bad_to_good),
+            ("burst_loss_good", self.loss_good),
+            ("burst_loss_bad", self.loss_bad),
+        ] {
+            if !(0f32..=1f32).contains(&value) {
+                return Err(format!("Edge '{name}' is not in the range [0,1]"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if a packet sent while in the given state is lost, and then updates the state
+    /// for the next packet.
+    pub fn sample(&self, is_bad: &mut bool, rng: &mut impl rand::Rng) -> bool {
+        let (loss, transition) = if *is_bad {
+            (self.loss_bad, self.bad_to_good)
+        } else {
+            (self.loss_good, self.good_to_bad)
+        };
+
+        let is_lost = rng.gen::<f32>() < loss;
+        if rng.gen::<f32>() < transition {
+            *is_bad = !*is_bad;
+        }
+
+        is_lost
+    }
+
+    /// Combine the models of two consecutive edges into a single model for the path. The path is
+    /// considered to be in the bad state when either edge is, so this is an approximation.
+    fn combine(self, other: Self) -> Self {
+        Self {
+            good_to_bad: 1f32 - (1f32 - self.good_to_bad) * (1f32 - other.good_to_bad),
+            bad_to_good: f32::min(self.bad_to_good, other.bad_to_good),
+            loss_good: 1f32 - (1f32 - self.loss_good) * (1f32 - other.loss_good),
+            loss_bad: f32::max(self.loss_bad, other.loss_bad),
+        }
     }
 }
 
@@ -182,40 +393,30 @@ impl NetworkGraph {
         })
     }
 
+    /// Get the shortest path between each pair of nodes, as the nodes along the path (including
+    /// the start and end nodes) and the path's properties. If there are several shortest paths
+    /// between two nodes, any of them may be returned.
     pub fn compute_shortest_paths(
         &self,
         nodes: &[NodeIndex],
-    ) -> Result<HashMap<(NodeIndex, NodeIndex), PathProperties>, NetGraphError> {
+    ) -> Result<HashMap<(NodeIndex, NodeIndex), PathWithHops>, NetGraphError> {
         let start = std::time::Instant::now();
 
         // calculate shortest paths
-        let mut paths: HashMap<(_, _), PathProperties> = nodes
+        let mut paths: HashMap<(_, _), (Vec<_>, PathProperties)> = nodes
             .into_par_iter()
-            .flat_map(|src| {
-                match &self.graph {
-                    GraphWrapper::Directed(graph) => {
-                        petgraph::algo::dijkstra(&graph, *src, None, |e| e.weight().into())
-                    }
-                    GraphWrapper::Undirected(graph) => {
-                        petgraph::algo::dijkstra(&graph, *src, None, |e| e.weight().into())
-                    }
-                }
-                .into_iter()
-                // ignore nodes that aren't in use
-                .filter(|(dst, _)| nodes.contains(dst))
-                // include the src node
-                .map(|(dst, path)| ((*src, dst), path))
-                .collect::<HashMap<(_, _), _>>()
+            .flat_map(|src| match &self.graph {
+                GraphWrapper::Directed(graph) => self.shortest_paths(graph, *src, nodes),
+                GraphWrapper::Undirected(graph) => self.shortest_paths(graph, *src, nodes),
             })
             .collect();
 
         // use the self-loop for paths from a node to itself
         for node in nodes {
-            // the dijkstra shortest path from node -> node will always be 0
-            assert_eq!(paths[&(*node, *node)], PathProperties::default());
-
             // there must be a single self-loop for each node
-            paths.insert((*node, *node), self.get_edge_weight(node, node)?.into());
+            let edge = self.get_edge_weight(node, node)?;
+            let path = self.path_properties(*node, *node, edge);
+            paths.insert((*node, *node), (vec![*node, *node], path));
         }
 
         assert_eq!(paths.len(), nodes.len().pow(2));
@@ -229,6 +430,160 @@ impl NetworkGraph {
         Ok(paths)
     }
 
+    /// Get all of the equal-cost shortest paths between each pair of nodes that has more than one.
+    /// Paths are equal-cost if they have the same latency, even if they have different packet
+    /// loss. At most [`MAX_ECMP_PATHS`] paths are returned for each pair of nodes.
+    pub fn compute_equal_cost_paths(
+        &self,
+        nodes: &[NodeIndex],
+    ) -> Result<HashMap<(NodeIndex, NodeIndex), Vec<PathWithHops>>, NetGraphError> {
+        let start = std::time::Instant::now();
+
+        let paths: HashMap<(_, _), Vec<_>> = nodes
+            .into_par_iter()
+            .flat_map(|src| match &self.graph {
+                GraphWrapper::Directed(graph) => self.equal_cost_paths(graph, *src, nodes),
+                GraphWrapper::Undirected(graph) => self.equal_cost_paths(graph, *src, nodes),
+            })
+            .filter(|(_, paths)| paths.len() > 1)
+            .collect();
+
+        debug!(
+            "Finished computing equal-cost paths: {} seconds, {} entries",
+            (std::time::Instant::now() - start).as_secs(),
+            paths.len()
+        );
+
+        Ok(paths)
+    }
+
+    /// Get the equal-cost shortest paths from `src` to each of the `dsts`, as the nodes along each
+    /// path and the path's properties.
+    fn equal_cost_paths<Ty: petgraph::EdgeType>(
+        &self,
+        graph: &petgraph::graph::Graph<ShadowNode, ShadowEdge, Ty, u32>,
+        src: NodeIndex,
+        dsts: &[NodeIndex],
+    ) -> Vec<((NodeIndex, NodeIndex), Vec<PathWithHops>)> {
+        let costs = petgraph::algo::dijkstra(graph, src, None, |e| PathCost::of_edge(e.weight()));
+
+        dsts.iter()
+            .filter(|dst| **dst != src)
+            .map(|dst| {
+                let mut paths = Vec::new();
+
+                // a depth-first search backwards from the destination, where each entry is a node,
+                // the nodes along the path from that node to the destination (in reverse order),
+                // and the properties of that path
+                let mut stack: Vec<(NodeIndex, Vec<NodeIndex>, Option<PathProperties>)> =
+                    vec![(*dst, vec![*dst], None)];
+
+                while let Some((current, hops, suffix)) = stack.pop() {
+                    if current == src {
+                        let hops = hops.into_iter().rev().collect();
+                        paths.push((hops, suffix.unwrap()));
+                        if paths.len() >= MAX_ECMP_PATHS {
+                            break;
+                        }
+                        continue;
+                    }
+
+                    let current_latency = costs[&current].latency_ns;
+
+                    // the edges that some shortest path could have arrived on
+                    let mut prevs: Vec<_> = graph
+                        .edges_directed(current, petgraph::Direction::Incoming)
+                        .filter_map(|e| {
+                            let prev = if e.source() == current {
+                                e.target()
+                            } else {
+                                e.source()
+                            };
+                            if prev == current {
+                                return None;
+                            }
+                            let edge = self.path_properties(prev, current, e.weight());
+                            // in directed graphs, `prev` might not be reachable from `src`
+                            let prev_latency = costs.get(&prev)?.latency_ns;
+                            (prev_latency + edge.latency_ns == current_latency)
+                                .then_some((prev, edge))
+                        })
+                        .collect();
+
+                    // visit the nodes in a deterministic order
+                    prevs.sort_by_key(|(prev, _)| std::cmp::Reverse(*prev));
+
+                    for (prev, edge) in prevs {
+                        let path = match &suffix {
+                            Some(suffix) => edge + suffix.clone(),
+                            None => edge,
+                        };
+                        let mut hops = hops.clone();
+                        hops.push(prev);
+                        stack.push((prev, hops, Some(path)));
+                    }
+                }
+
+                ((src, *dst), paths)
+            })
+            .collect()
+    }
+
+    /// Get a shortest path from `src` to each of the other `dsts` that is reachable, as the nodes
+    /// along the path (including the start and end nodes) and the path's properties.
+    fn shortest_paths<Ty: petgraph::EdgeType>(
+        &self,
+        graph: &petgraph::graph::Graph<ShadowNode, ShadowEdge, Ty, u32>,
+        src: NodeIndex,
+        dsts: &[NodeIndex],
+    ) -> Vec<((NodeIndex, NodeIndex), PathWithHops)> {
+        let costs = petgraph::algo::dijkstra(graph, src, None, |e| PathCost::of_edge(e.weight()));
+
+        dsts.iter()
+            // ignore nodes that aren't reachable
+            .filter(|dst| **dst != src && costs.contains_key(dst))
+            .map(|dst| {
+                let mut hops = vec![*dst];
+                let mut edges = Vec::new();
+                let mut current = *dst;
+
+                // walk backwards from the destination, following any edge that the shortest path
+                // could have arrived on
+                while current != src {
+                    let (prev, edge) = graph
+                        .edges_directed(current, petgraph::Direction::Incoming)
+                        .find_map(|e| {
+                            let prev = if e.source() == current {
+                                e.target()
+                            } else {
+                                e.source()
+                            };
+                            if prev == current {
+                                return None;
+                            }
+                            // in directed graphs, `prev` might not be reachable from `src`
+                            let cost = *costs.get(&prev)? + PathCost::of_edge(e.weight());
+                            (cost == costs[&current]).then_some((prev, e.weight()))
+                        })
+                        .unwrap();
+                    edges.push(self.path_properties(prev, current, edge));
+                    hops.push(prev);
+                    current = prev;
+                }
+
+                hops.reverse();
+
+                // add the edges in path order, the same way that the costs were calculated
+                let path = edges
+                    .into_iter()
+                    .rev()
+                    .fold(PathProperties::default(), |path, edge| path + edge);
+
+                ((src, *dst), (hops, path))
+            })
+            .collect()
+    }
+
     pub fn get_direct_paths(
         &self,
         nodes: &[NodeIndex],
@@ -239,7 +594,10 @@ impl NetworkGraph {
             .iter()
             .flat_map(|src| nodes.iter().map(move |dst| (*src, *dst)))
             // we require the graph to be connected with exactly one edge between any two nodes
-            .map(|(src, dst)| Ok(((src, dst), self.get_edge_weight(&src, &dst)?.into())))
+            .map(|(src, dst)| {
+                let edge = self.get_edge_weight(&src, &dst)?;
+                Ok(((src, dst), self.path_properties(src, dst, edge)))
+            })
             .collect::<Result<_, NetGraphError>>()?;
 
         assert_eq!(paths.len(), nodes.len().pow(2));
@@ -253,6 +611,71 @@ impl NetworkGraph {
         Ok(paths)
     }
 
+    /// Get the properties of a path consisting of a single edge, traversed from `src` to `dst`.
+    fn path_properties(&self, src: NodeIndex, dst: NodeIndex, edge: &ShadowEdge) -> PathProperties {
+        let bottleneck = edge.bandwidth.map(|bandwidth| Bottleneck {
+            source: self.node_index_to_id(src).unwrap(),
+            target: self.node_index_to_id(dst).unwrap(),
+            bandwidth_bits: bandwidth
+                .convert(units::SiPrefixUpper::Base)
+                .unwrap()
+                .value(),
+            queue_size_bytes: edge
+                .queue_size
+                .convert(units::SiPrefixUpper::Base)
+                .unwrap()
+                .value(),
+        });
+
+        PathProperties {
+            latency_ns: edge
+                .latency
+                .convert(units::TimePrefix::Nano)
+                .unwrap()
+                .value(),
+            jitter_ns: edge
+                .jitter
+                .convert(units::TimePrefix::Nano)
+                .unwrap()
+                .value(),
+            packet_loss: edge.packet_loss,
+            burst_loss: edge.burst_loss,
+            bottlenecks: bottleneck.into_iter().collect(),
+            reorder_probability: edge.reorder_probability,
+            reorder_delay_ns: edge
+                .reorder_delay
+                .convert(units::TimePrefix::Nano)
+                .unwrap()
+                .value(),
+            duplicate_probability: edge.duplicate_probability,
+            num_hops: if src == dst { 0 } else { 1 },
+        }
+    }
+
+    /// Change the attributes of the edge between two nodes. Returns an error if the nodes don't
+    /// exist, if there is not exactly one edge between them, or if the changed edge is invalid.
+    pub fn update_edge(
+        &mut self,
+        src_id: u32,
+        dst_id: u32,
+        f: impl FnOnce(&mut ShadowEdge),
+    ) -> Result<(), NetGraphError> {
+        let src = *self
+            .node_id_to_index(src_id)
+            .ok_or(format!("Edge source {} doesn't exist", src_id))?;
+        let dst = *self
+            .node_id_to_index(dst_id)
+            .ok_or(format!("Edge target {} doesn't exist", dst_id))?;
+
+        let edge_index = self.get_edge_index(&src, &dst)?;
+        let edge = self.graph.edge_weight_mut(edge_index).unwrap();
+
+        f(edge);
+        edge.validate()?;
+
+        Ok(())
+    }
+
     /// Get the weight for the edge between two nodes. Returns an error if there
     /// is not exactly one edge between them.
     fn get_edge_weight(
@@ -260,6 +683,13 @@ impl NetworkGraph {
         src: &NodeIndex,
         dst: &NodeIndex,
     ) -> Result<&ShadowEdge, NetGraphError> {
+        let edge_index = self.get_edge_index(src, dst)?;
+        Ok(self.graph.edge_weight(edge_index).unwrap())
+    }
+
+    /// Get the index of the edge between two nodes. Returns an error if there
+    /// is not exactly one edge between them.
+    fn get_edge_index(&self, src: &NodeIndex, dst: &NodeIndex) -> Result<EdgeIndex, NetGraphError> {
         let src_id = self.node_index_to_id(*src).unwrap();
         let dst_id = self.node_index_to_id(*dst).unwrap();
         match &self.graph {
@@ -275,7 +705,7 @@ impl NetworkGraph {
                     )
                     .into());
                 }
-                Ok(edge.weight())
+                Ok(edge.id())
             }
             GraphWrapper::Undirected(graph) => {
                 let mut edges = graph.edges_connecting(*src, *dst);
@@ -289,28 +719,73 @@ impl NetworkGraph {
                     )
                     .into());
                 }
-                Ok(edge.weight())
+                Ok(edge.id())
             }
         }
     }
 }
 
+/// The nodes along a path in the graph (including the start and end nodes), and the path's
+/// properties.
+pub type PathWithHops = (Vec<NodeIndex>, PathProperties);
+
 /// Network characteristics for a path between two nodes.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone)]
 pub struct PathProperties {
     /// Latency in nanoseconds.
     pub latency_ns: u64,
+    /// Maximum deviation from the latency in nanoseconds. Each packet's latency is chosen
+    /// uniformly from `[latency_ns - jitter_ns, latency_ns + jitter_ns]`.
+    pub jitter_ns: u64,
     /// Packet loss as fraction.
     pub packet_loss: f32,
+    /// Bursty packet loss, which applies in addition to `packet_loss`.
+    pub burst_loss: Option<BurstLoss>,
+    /// The edges on the path that have a bandwidth, in the order that they are traversed.
+    pub bottlenecks: Vec<Bottleneck>,
+    /// The chance that a packet is held back by `reorder_delay_ns` so that later packets overtake
+    /// it.
+    pub reorder_probability: f32,
+    /// The extra delay of reordered packets in nanoseconds.
+    pub reorder_delay_ns: u64,
+    /// The chance that a packet is delivered twice.
+    pub duplicate_probability: f32,
+    /// The number of router hops on the path, which each decrement a packet's TTL. Self-loops
+    /// don't count as a hop since hosts on the same node share a network.
+    pub num_hops: u32,
+}
+
+/// An edge with a limited bandwidth that is shared by all packets that traverse it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bottleneck {
+    /// The gml id of the node that the edge is traversed from.
+    pub source: u32,
+    /// The gml id of the node that the edge is traversed to.
+    pub target: u32,
+    /// The edge's bandwidth in bits per second.
+    pub bandwidth_bits: u64,
+    /// The maximum number of bytes that can be queued on the edge.
+    pub queue_size_bytes: u64,
+}
+
+impl PathProperties {
+    /// The smallest latency that a packet on this path can have after applying the jitter.
+    pub fn min_latency_ns(&self) -> u64 {
+        self.latency_ns - self.jitter_ns
+    }
+
+    /// The cost of the path when choosing between paths.
+    fn cost(&self) -> PathCost {
+        PathCost {
+            latency_ns: self.latency_ns,
+            packet_loss: self.packet_loss,
+        }
+    }
 }
 
 impl PartialOrd for PathProperties {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        // order by lowest latency first, then by lowest packet loss
-        match self.latency_ns.cmp(&other.latency_ns) {
-            std::cmp::Ordering::Equal => self.packet_loss.partial_cmp(&other.packet_loss),
-            x => Some(x),
-        }
+        self.cost().partial_cmp(&other.cost())
     }
 }
 
@@ -327,16 +802,57 @@ impl core::ops::Add for PathProperties {
     fn add(self, other: Self) -> Self::Output {
         Self {
             latency_ns: self.latency_ns + other.latency_ns,
+            jitter_ns: self.jitter_ns + other.jitter_ns,
             packet_loss: 1f32 - (1f32 - self.packet_loss) * (1f32 - other.packet_loss),
+            burst_loss: match (self.burst_loss, other.burst_loss) {
+                (Some(x), Some(y)) => Some(x.combine(y)),
+                (x, None) | (None, x) => x,
+            },
+            bottlenecks: self
+                .bottlenecks
+                .into_iter()
+                .chain(other.bottlenecks)
+                .collect(),
+            reorder_probability: 1f32
+                - (1f32 - self.reorder_probability) * (1f32 - other.reorder_probability),
+            reorder_delay_ns: std::cmp::max(self.reorder_delay_ns, other.reorder_delay_ns),
+            duplicate_probability: 1f32
+                - (1f32 - self.duplicate_probability) * (1f32 - other.duplicate_probability),
+            num_hops: self.num_hops + other.num_hops,
         }
     }
 }
 
-impl std::convert::From<&ShadowEdge> for PathProperties {
-    fn from(e: &ShadowEdge) -> Self {
+/// The cost of a path, used when searching for shortest paths. Paths are ordered by lowest latency
+/// first, then by lowest packet loss.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+struct PathCost {
+    latency_ns: u64,
+    packet_loss: f32,
+}
+
+impl PathCost {
+    /// The cost of traversing a single edge.
+    fn of_edge(edge: &ShadowEdge) -> Self {
+        Self {
+            latency_ns: edge
+                .latency
+                .convert(units::TimePrefix::Nano)
+                .unwrap()
+                .value(),
+            packet_loss: edge.packet_loss,
+        }
+    }
+}
+
+impl core::ops::Add for PathCost {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        // must be consistent with adding `PathProperties`
         Self {
-            latency_ns: e.latency.convert(units::TimePrefix::Nano).unwrap().value(),
-            packet_loss: e.packet_loss,
+            latency_ns: self.latency_ns + other.latency_ns,
+            packet_loss: 1f32 - (1f32 - self.packet_loss) * (1f32 - other.packet_loss),
         }
     }
 }
@@ -427,29 +943,162 @@ impl<T: Copy + Eq + Hash + std::fmt::Display> Default for IpAssignment<T> {
     }
 }
 
+/// Hosts' IPv6 addresses. Shadow routes packets using hosts' IPv4 addresses, so each IPv6
+/// address is an alias for the IPv4 address of the same host.
+#[derive(Debug, Default)]
+pub struct Ipv6Aliases {
+    to_ipv4: HashMap<std::net::Ipv6Addr, std::net::Ipv4Addr>,
+    to_ipv6: HashMap<std::net::Ipv4Addr, std::net::Ipv6Addr>,
+}
+
+impl Ipv6Aliases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make `ipv6` an alias for the host with address `ipv4`.
+    pub fn assign(
+        &mut self,
+        ipv6: std::net::Ipv6Addr,
+        ipv4: std::net::Ipv4Addr,
+    ) -> Result<(), IpPreviouslyAssignedError> {
+        if self.to_ipv4.contains_key(&ipv6) || self.to_ipv6.contains_key(&ipv4) {
+            return Err(IpPreviouslyAssignedError);
+        }
+        self.to_ipv4.insert(ipv6, ipv4);
+        self.to_ipv6.insert(ipv4, ipv6);
+        Ok(())
+    }
+
+    /// Get the IPv4 address of the host that `ipv6` is assigned to.
+    pub fn to_ipv4(&self, ipv6: std::net::Ipv6Addr) -> Option<std::net::Ipv4Addr> {
+        self.to_ipv4.get(&ipv6).copied()
+    }
+
+    /// Get the IPv6 address of the host with address `ipv4`, if it has one.
+    pub fn to_ipv6(&self, ipv4: std::net::Ipv4Addr) -> Option<std::net::Ipv6Addr> {
+        self.to_ipv6.get(&ipv4).copied()
+    }
+}
+
+/// The paths between nodes.
+#[derive(Debug)]
+pub struct PathTable<T: Eq + Hash> {
+    /// The path between each pair of nodes.
+    pub primary: HashMap<(T, T), PathProperties>,
+    /// All of the equal-cost paths between each pair of nodes that has more than one. Only used
+    /// with equal-cost multipath routing.
+    pub ecmp: HashMap<(T, T), Vec<PathProperties>>,
+    /// The nodes along each of the `primary` paths (including the start and end nodes), if known.
+    pub hops: HashMap<(T, T), Vec<T>>,
+    /// The nodes along each of the `ecmp` paths, in the same order as `ecmp`, if known.
+    pub ecmp_hops: HashMap<(T, T), Vec<Vec<T>>>,
+}
+
+impl<T: Eq + Hash> From<HashMap<(T, T), PathProperties>> for PathTable<T> {
+    fn from(primary: HashMap<(T, T), PathProperties>) -> Self {
+        Self {
+            primary,
+            ecmp: HashMap::new(),
+            hops: HashMap::new(),
+            ecmp_hops: HashMap::new(),
+        }
+    }
+}
+
+/// Identifies a path that packets were sent on: the index of the path table that was in use (0 for
+/// the initial paths), the start and end nodes, and the index of the equal-cost path (or `None` for
+/// the primary path).
+type PathKey<T> = (usize, T, T, Option<usize>);
+
 /// Routing information for paths between nodes.
 #[derive(Debug)]
 pub struct RoutingInfo<T: Eq + Hash + std::fmt::Display + Clone + Copy> {
-    paths: HashMap<(T, T), PathProperties>,
-    packet_counters: std::sync::RwLock<HashMap<(T, T), u64>>,
+    paths: PathTable<T>,
+    /// Paths that replace `paths` starting at the given simulation time, sorted by time.
+    scheduled_paths: Vec<(SimulationTime, PathTable<T>)>,
+    packet_counters: std::sync::RwLock<HashMap<PathKey<T>, u64>>,
 }
 
 impl<T: Eq + Hash + std::fmt::Display + Clone + Copy> RoutingInfo<T> {
-    pub fn new(paths: HashMap<(T, T), PathProperties>) -> Self {
+    pub fn new(paths: impl Into<PathTable<T>>) -> Self {
         Self {
-            paths,
+            paths: paths.into(),
+            scheduled_paths: Vec::new(),
             packet_counters: std::sync::RwLock::new(HashMap::new()),
         }
     }
 
-    /// Get properties for the path from one node to another.
-    pub fn path(&self, start: T, end: T) -> Option<PathProperties> {
-        self.paths.get(&(start, end)).copied()
+    /// Replace all paths with `paths` starting at simulation time `time`. Must be called in order
+    /// of increasing time.
+    pub fn schedule_paths(&mut self, time: SimulationTime, paths: impl Into<PathTable<T>>) {
+        if let Some((last_time, _)) = self.scheduled_paths.last() {
+            assert!(time > *last_time);
+        }
+        self.scheduled_paths.push((time, paths.into()));
+    }
+
+    /// Get properties for the path from one node to another at the given simulation time.
+    pub fn path(&self, start: T, end: T, time: SimulationTime) -> Option<PathProperties> {
+        let (_, paths) = self.paths_at(time);
+        paths.primary.get(&(start, end)).cloned()
+    }
+
+    /// Get properties for the path taken by a flow from one node to another at the given
+    /// simulation time. If there are several equal-cost paths between the nodes, the path is
+    /// chosen using the flow's hash so that all packets of a flow take the same path.
+    pub fn flow_path(
+        &self,
+        start: T,
+        end: T,
+        time: SimulationTime,
+        flow_hash: u64,
+    ) -> Option<PathProperties> {
+        let (_, paths) = self.paths_at(time);
+        match Self::ecmp_index(paths, start, end, flow_hash) {
+            Some(index) => Some(paths.ecmp[&(start, end)][index].clone()),
+            None => paths.primary.get(&(start, end)).cloned(),
+        }
+    }
+
+    /// Get the index of the equal-cost path taken by a flow, or `None` if there aren't several
+    /// equal-cost paths between the nodes.
+    fn ecmp_index(paths: &PathTable<T>, start: T, end: T, flow_hash: u64) -> Option<usize> {
+        let ecmp = paths.ecmp.get(&(start, end))?;
+        let index = flow_hash % u64::try_from(ecmp.len()).unwrap();
+        Some(usize::try_from(index).unwrap())
     }
 
-    /// Increment the number of packets sent from one node to another.
-    pub fn increment_packet_count(&self, start: T, end: T) {
-        let key = (start, end);
+    /// Get the paths that are in use at the given simulation time, along with their index in
+    /// [`Self::all_paths`].
+    fn paths_at(&self, time: SimulationTime) -> (usize, &PathTable<T>) {
+        // the number of scheduled path changes that have happened by `time`
+        let num_changes = self.scheduled_paths.partition_point(|(t, _)| *t <= time);
+        match num_changes {
+            0 => (0, &self.paths),
+            x => (x, &self.scheduled_paths[x - 1].1),
+        }
+    }
+
+    /// Get the initial paths and all scheduled paths, with the times that they come into use.
+    fn all_paths(&self) -> impl Iterator<Item = (SimulationTime, &PathTable<T>)> {
+        std::iter::once((SimulationTime::ZERO, &self.paths)).chain(
+            self.scheduled_paths
+                .iter()
+                .map(|(time, paths)| (*time, paths)),
+        )
+    }
+
+    /// Increment the number of packets sent on the path taken by a flow from one node to another
+    /// at the given simulation time.
+    pub fn increment_packet_count(&self, start: T, end: T, time: SimulationTime, flow_hash: u64) {
+        let (table, paths) = self.paths_at(time);
+        let key = (
+            table,
+            start,
+            end,
+            Self::ecmp_index(paths, start, end, flow_hash),
+        );
         let mut packet_counters = self.packet_counters.write().unwrap();
         match packet_counters.get_mut(&key) {
             Some(x) => *x = x.saturating_add(1),
@@ -460,26 +1109,116 @@ impl<T: Eq + Hash + std::fmt::Display + Clone + Copy> RoutingInfo<T> {
     /// Log the number of packets sent between nodes.
     pub fn log_packet_counts(&self) {
         // only logs paths that have transmitted at least one packet
-        for ((start, end), count) in self.packet_counters.read().unwrap().iter() {
-            let path = self.paths.get(&(*start, *end)).unwrap();
+        for ((table, start, end, ecmp), count) in self.packet_counters.read().unwrap().iter() {
+            let (_, paths) = self.all_paths().nth(*table).unwrap();
+            let path = match ecmp {
+                Some(index) => &paths.ecmp[&(*start, *end)][*index],
+                None => &paths.primary[&(*start, *end)],
+            };
             log::debug!(
-                "Found path {}->{}: latency={}ns, packet_loss={}, packet_count={}",
+                "Found path {}->{}: latency={}ns, jitter={}ns, packet_loss={}, packet_count={}",
                 start,
                 end,
                 path.latency_ns,
+                path.jitter_ns,
                 path.packet_loss,
                 count,
             );
         }
     }
 
+    /// Write the paths between nodes that are used during the simulation, and the number of
+    /// packets sent on each path, as json.
+    pub fn write_json(&self, writer: impl std::io::Write) -> serde_json::Result<()>
+    where
+        T: Ord + serde::Serialize,
+    {
+        #[derive(serde::Serialize)]
+        struct PathForOutput<'a, T> {
+            start_time_ns: u64,
+            source: T,
+            target: T,
+            latency_ns: u64,
+            jitter_ns: u64,
+            packet_loss: f32,
+            hops: Option<&'a Vec<T>>,
+            packet_count: u64,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            ecmp_paths: Vec<EcmpPathForOutput<'a, T>>,
+        }
+
+        #[derive(serde::Serialize)]
+        struct EcmpPathForOutput<'a, T> {
+            latency_ns: u64,
+            jitter_ns: u64,
+            packet_loss: f32,
+            hops: Option<&'a Vec<T>>,
+            packet_count: u64,
+        }
+
+        let packet_counters = self.packet_counters.read().unwrap();
+        let packet_count = |key: PathKey<T>| packet_counters.get(&key).copied().unwrap_or(0);
+
+        let mut paths: Vec<_> = self
+            .all_paths()
+            .enumerate()
+            .flat_map(|(table, (start_time, paths))| {
+                let packet_count = &packet_count;
+                paths.primary.iter().map(move |((source, target), path)| {
+                    let key = (*source, *target);
+                    let ecmp_paths = paths
+                        .ecmp
+                        .get(&key)
+                        .into_iter()
+                        .flatten()
+                        .enumerate()
+                        .map(|(index, path)| EcmpPathForOutput {
+                            latency_ns: path.latency_ns,
+                            jitter_ns: path.jitter_ns,
+                            packet_loss: path.packet_loss,
+                            hops: paths.ecmp_hops.get(&key).map(|x| &x[index]),
+                            packet_count: packet_count((table, *source, *target, Some(index))),
+                        })
+                        .collect();
+
+                    PathForOutput {
+                        start_time_ns: start_time.as_nanos().try_into().unwrap(),
+                        source: *source,
+                        target: *target,
+                        latency_ns: path.latency_ns,
+                        jitter_ns: path.jitter_ns,
+                        packet_loss: path.packet_loss,
+                        hops: paths.hops.get(&key),
+                        packet_count: packet_count((table, *source, *target, None)),
+                        ecmp_paths,
+                    }
+                })
+            })
+            .collect();
+
+        // sort so that the output is deterministic
+        paths.sort_by_key(|x| (x.start_time_ns, x.source, x.target));
+
+        serde_json::to_writer_pretty(writer, &paths)
+    }
+
+    /// Get the smallest latency that any packet can have at any time during the simulation,
+    /// taking jitter into account.
     pub fn get_smallest_latency_ns(&self) -> Option<u64> {
-        self.paths.values().map(|x| x.latency_ns).min()
+        self.all_paths()
+            .flat_map(|(_, paths)| paths.primary.values().chain(paths.ecmp.values().flatten()))
+            .map(|x| x.min_latency_ns())
+            .min()
     }
 }
 
 /// Read and decompress a file.
-fn read_xz<P: AsRef<std::path::Path>>(path: P) -> Result<String, NetGraphError> {
+fn read_compressed<P: AsRef<std::path::Path>>(
+    path: P,
+    compression: &Compression,
+) -> Result<String, NetGraphError> {
+    use std::io::Read;
+
     let path = path.as_ref();
 
     let mut f = std::io::BufReader::new(
@@ -487,24 +1226,55 @@ fn read_xz<P: AsRef<std::path::Path>>(path: P) -> Result<String, NetGraphError>
     );
 
     let mut decomp: Vec<u8> = Vec::new();
-    lzma_rs::xz_decompress(&mut f, &mut decomp).context("Failed to decompress file")?;
+    match compression {
+        Compression::Xz => {
+            lzma_rs::xz_decompress(&mut f, &mut decomp).context("Failed to decompress file")?
+        }
+        Compression::Gzip => {
+            flate2::bufread::MultiGzDecoder::new(f)
+                .read_to_end(&mut decomp)
+                .context("Failed to decompress file")?;
+        }
+        Compression::Zstd => {
+            zstd::stream::read::Decoder::with_buffer(f)
+                .context("Failed to initialize the decompressor")?
+                .read_to_end(&mut decomp)
+                .context("Failed to decompress file")?;
+        }
+    }
     decomp.shrink_to_fit();
 
     Ok(String::from_utf8(decomp)?)
 }
 
+/// Get the compression format of a file from its extension, or `None` if the extension isn't a
+/// known compression format.
+fn compression_from_extension(path: impl AsRef<std::path::Path>) -> Option<Compression> {
+    match path.as_ref().extension()?.to_str()? {
+        "xz" => Some(Compression::Xz),
+        "gz" => Some(Compression::Gzip),
+        "zst" | "zstd" => Some(Compression::Zstd),
+        _ => None,
+    }
+}
+
 /// Get the network graph as a string.
 pub fn load_network_graph(graph_options: &GraphOptions) -> Result<String, NetGraphError> {
     Ok(match graph_options {
-        GraphOptions::Gml(GraphSource::File(FileSource {
-            compression: None,
-            path: f,
-        })) => std::fs::read_to_string(tilde_expansion(f))
-            .with_context(|| format!("Failed to read file: {f}"))?,
-        GraphOptions::Gml(GraphSource::File(FileSource {
-            compression: Some(Compression::Xz),
-            path: f,
-        })) => read_xz(tilde_expansion(f))?,
+        GraphOptions::Gml(GraphSource::File(FileSource { compression, path })) => {
+            let expanded_path = tilde_expansion(path);
+
+            // if the compression wasn't given, try to detect it from the file extension
+            let compression = compression
+                .clone()
+                .or_else(|| compression_from_extension(&expanded_path));
+
+            match compression {
+                Some(compression) => read_compressed(expanded_path, &compression)?,
+                None => std::fs::read_to_string(expanded_path)
+                    .with_context(|| format!("Failed to read file: {path}"))?,
+            }
+        }
         GraphOptions::Gml(GraphSource::Inline(s)) => s.clone(),
         GraphOptions::OneGbitSwitch => configuration::ONE_GBIT_SWITCH_GRAPH.to_string(),
     })
@@ -514,20 +1284,86 @@ pub fn load_network_graph(graph_options: &GraphOptions) -> Result<String, NetGra
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_compression_from_extension() {
+        assert!(matches!(
+            compression_from_extension("graph.gml.xz"),
+            Some(Compression::Xz)
+        ));
+        assert!(matches!(
+            compression_from_extension("graph.gml.gz"),
+            Some(Compression::Gzip)
+        ));
+        assert!(matches!(
+            compression_from_extension("graph.gml.zst"),
+            Some(Compression::Zstd)
+        ));
+        assert!(compression_from_extension("graph.gml").is_none());
+        assert!(compression_from_extension("graph").is_none());
+    }
+
+    #[test]
+    fn test_load_compressed() {
+        use std::io::Write;
+
+        let graph = configuration::ONE_GBIT_SWITCH_GRAPH;
+        let dir = tempfile::tempdir().unwrap();
+
+        let gz_path = dir.path().join("graph.gml.gz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(&gz_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(graph.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let zst_path = dir.path().join("graph.gml.zst");
+        let compressed = zstd::stream::encode_all(graph.as_bytes(), 0).unwrap();
+        std::fs::write(&zst_path, compressed).unwrap();
+
+        for path in [gz_path, zst_path] {
+            let options = GraphOptions::Gml(GraphSource::File(FileSource {
+                path: path.to_str().unwrap().to_string(),
+                compression: None,
+            }));
+            assert_eq!(load_network_graph(&options).unwrap(), graph);
+        }
+    }
+
     #[test]
     fn test_path_add() {
         let p1 = PathProperties {
             latency_ns: 23,
+            jitter_ns: 5,
             packet_loss: 0.35,
+            burst_loss: None,
+            bottlenecks: Vec::new(),
+            reorder_probability: 0.5,
+            reorder_delay_ns: 10,
+            duplicate_probability: 0.1,
+            num_hops: 2,
         };
         let p2 = PathProperties {
             latency_ns: 11,
+            jitter_ns: 2,
             packet_loss: 0.85,
+            burst_loss: None,
+            bottlenecks: Vec::new(),
+            reorder_probability: 0.0,
+            reorder_delay_ns: 0,
+            duplicate_probability: 0.0,
+            num_hops: 1,
         };
 
         let p3 = p1 + p2;
         assert_eq!(p3.latency_ns, 34);
+        assert_eq!(p3.jitter_ns, 7);
+        assert_eq!(p3.min_latency_ns(), 27);
         assert!((p3.packet_loss - 0.9025).abs() < 0.01);
+        assert!((p3.reorder_probability - 0.5).abs() < 0.01);
+        assert_eq!(p3.reorder_delay_ns, 10);
+        assert!((p3.duplicate_probability - 0.1).abs() < 0.01);
+        assert_eq!(p3.num_hops, 3);
     }
 
     #[test]
@@ -558,6 +1394,446 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_jitter() {
+        for (jitter, is_valid) in &[("0 ns", true), ("9 ns", true), ("10 ns", false)] {
+            let graph = format!(
+                r#"graph [
+                node [
+                  id 1
+                ]
+                edge [
+                  source 1
+                  target 1
+                  latency "10 ns"
+                  jitter "{}"
+                ]
+            ]"#,
+                jitter
+            );
+
+            if *is_valid {
+                NetworkGraph::parse(&graph).unwrap();
+            } else {
+                NetworkGraph::parse(&graph).unwrap_err();
+            }
+        }
+    }
+
+    #[test]
+    fn test_burst_loss_parse() {
+        let graph = |attrs: &str| {
+            format!(
+                r#"graph [
+                node [
+                  id 1
+                ]
+                edge [
+                  source 1
+                  target 1
+                  latency "10 ns"
+                  {attrs}
+                ]
+            ]"#
+            )
+        };
+
+        let parsed = NetworkGraph::parse(&graph("")).unwrap();
+        let node = *parsed.node_id_to_index(1).unwrap();
+        let edge = parsed.get_edge_weight(&node, &node).unwrap();
+        assert_eq!(edge.burst_loss, None);
+
+        let parsed = NetworkGraph::parse(&graph(
+            "burst_loss_good_to_bad 0.1 burst_loss_bad_to_good 0.5 burst_loss_bad 0.8",
+        ))
+        .unwrap();
+        let node = *parsed.node_id_to_index(1).unwrap();
+        let edge = parsed.get_edge_weight(&node, &node).unwrap();
+        assert_eq!(
+            edge.burst_loss,
+            Some(BurstLoss {
+                good_to_bad: 0.1,
+                bad_to_good: 0.5,
+                loss_good: 0.0,
+                loss_bad: 0.8,
+            })
+        );
+
+        // both transition probabilities are required
+        NetworkGraph::parse(&graph("burst_loss_good_to_bad 0.1")).unwrap_err();
+        NetworkGraph::parse(&graph("burst_loss_bad 0.5")).unwrap_err();
+        NetworkGraph::parse(&graph(
+            "burst_loss_good_to_bad 1.5 burst_loss_bad_to_good 0.5",
+        ))
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_burst_loss_sample() {
+        use rand::SeedableRng;
+
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(0);
+
+        // always lose packets in the bad state, never in the good state, and always change state
+        let model = BurstLoss {
+            good_to_bad: 1.0,
+            bad_to_good: 1.0,
+            loss_good: 0.0,
+            loss_bad: 1.0,
+        };
+
+        let mut is_bad = false;
+        let lost: Vec<_> = (0..4)
+            .map(|_| model.sample(&mut is_bad, &mut rng))
+            .collect();
+        assert_eq!(lost, [false, true, false, true]);
+
+        // never leave the bad state
+        is_bad = true;
+        let model = BurstLoss {
+            bad_to_good: 0.0,
+            ..model
+        };
+        assert!((0..10).all(|_| model.sample(&mut is_bad, &mut rng)));
+    }
+
+    #[test]
+    fn test_bottleneck() {
+        let graph = r#"graph [
+          directed 1
+          node [
+            id 1
+          ]
+          node [
+            id 2
+          ]
+          edge [
+            source 1
+            target 1
+            latency "10 ns"
+          ]
+          edge [
+            source 2
+            target 2
+            latency "10 ns"
+          ]
+          edge [
+            source 1
+            target 2
+            latency "10 ns"
+            bandwidth "10 Mbit"
+          ]
+          edge [
+            source 2
+            target 1
+            latency "10 ns"
+            bandwidth "1 Gbit"
+            queue_size "1 MiB"
+          ]
+        ]"#;
+        let graph = NetworkGraph::parse(graph).unwrap();
+
+        let node_1 = *graph.node_id_to_index(1).unwrap();
+        let node_2 = *graph.node_id_to_index(2).unwrap();
+        let paths = graph.compute_shortest_paths(&[node_1, node_2]).unwrap();
+
+        let b_1_2 = Bottleneck {
+            source: 1,
+            target: 2,
+            bandwidth_bits: 10_000_000,
+            queue_size_bytes: 64 * 1024,
+        };
+        let b_2_1 = Bottleneck {
+            source: 2,
+            target: 1,
+            bandwidth_bits: 1_000_000_000,
+            queue_size_bytes: 1024 * 1024,
+        };
+
+        assert!(paths[&(node_1, node_1)].1.bottlenecks.is_empty());
+        assert_eq!(paths[&(node_1, node_2)].1.bottlenecks, [b_1_2]);
+        assert_eq!(paths[&(node_2, node_1)].1.bottlenecks, [b_2_1]);
+
+        // every edge with a bandwidth is kept, in the order that the path traverses them
+        let p1 = paths[&(node_1, node_2)].1.clone();
+        let p2 = paths[&(node_2, node_1)].1.clone();
+        assert_eq!((p1.clone() + p2.clone()).bottlenecks, [b_1_2, b_2_1]);
+        assert_eq!((p2 + p1).bottlenecks, [b_2_1, b_1_2]);
+    }
+
+    #[test]
+    fn test_reorder_duplicate() {
+        for (attrs, is_valid) in &[
+            ("", true),
+            ("reorder_probability 0.1 reorder_delay \"5 ms\"", true),
+            ("reorder_probability 0.1", false),
+            ("reorder_probability 1.1 reorder_delay \"5 ms\"", false),
+            ("duplicate_probability 0.01", true),
+            ("duplicate_probability -0.5", false),
+        ] {
+            let graph = format!(
+                r#"graph [
+                node [
+                  id 1
+                ]
+                edge [
+                  source 1
+                  target 1
+                  latency "10 ns"
+                  {attrs}
+                ]
+            ]"#
+            );
+
+            if *is_valid {
+                NetworkGraph::parse(&graph).unwrap();
+            } else {
+                NetworkGraph::parse(&graph).unwrap_err();
+            }
+        }
+    }
+
+    #[test]
+    fn test_shortest_path_hops() {
+        // the direct edge from 1 to 3 is slower than going through 2
+        let graph = r#"graph [
+          node [
+            id 1
+          ]
+          node [
+            id 2
+          ]
+          node [
+            id 3
+          ]
+          edge [
+            source 1
+            target 2
+            latency "10 ns"
+          ]
+          edge [
+            source 2
+            target 3
+            latency "10 ns"
+          ]
+          edge [
+            source 1
+            target 3
+            latency "100 ns"
+          ]
+        ]"#;
+        let graph = NetworkGraph::parse(graph).unwrap();
+
+        let nodes: Vec<_> = [1, 2, 3]
+            .iter()
+            .map(|x| *graph.node_id_to_index(*x).unwrap())
+            .collect();
+        let paths = graph.compute_shortest_paths(&nodes).unwrap();
+
+        let hops = |src: usize, dst: usize| -> Vec<u32> {
+            paths[&(nodes[src], nodes[dst])]
+                .0
+                .iter()
+                .map(|x| graph.node_index_to_id(*x).unwrap())
+                .collect()
+        };
+
+        assert_eq!(hops(0, 2), [1, 2, 3]);
+        assert_eq!(hops(2, 0), [3, 2, 1]);
+        assert_eq!(hops(0, 1), [1, 2]);
+        assert_eq!(hops(1, 1), [2, 2]);
+        assert_eq!(paths[&(nodes[0], nodes[2])].1.latency_ns, 20);
+    }
+
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_equal_cost_paths() {
+        // two paths from 1 to 4 with the same latency, but only one of them has packet loss
+        let graph = r#"graph [
+          node [
+            id 1
+          ]
+          node [
+            id 2
+          ]
+          node [
+            id 3
+          ]
+          node [
+            id 4
+          ]
+          edge [
+            source 1
+            target 2
+            latency "10 ns"
+          ]
+          edge [
+            source 2
+            target 4
+            latency "10 ns"
+          ]
+          edge [
+            source 1
+            target 3
+            latency "10 ns"
+            packet_loss 0.5
+          ]
+          edge [
+            source 3
+            target 4
+            latency "10 ns"
+          ]
+        ]"#;
+        let graph = NetworkGraph::parse(graph).unwrap();
+
+        let nodes: Vec<_> = [1, 2, 3, 4]
+            .iter()
+            .map(|x| *graph.node_id_to_index(*x).unwrap())
+            .collect();
+        let paths = graph.compute_equal_cost_paths(&nodes).unwrap();
+
+        // only pairs of nodes with more than one path are included
+        assert_eq!(paths.len(), 4);
+        assert!(!paths.contains_key(&(nodes[0], nodes[1])));
+
+        let paths_1_4 = &paths[&(nodes[0], nodes[3])];
+        assert_eq!(paths_1_4.len(), 2);
+        assert!(paths_1_4.iter().all(|x| x.1.latency_ns == 20));
+
+        let mut loss: Vec<_> = paths_1_4.iter().map(|x| x.1.packet_loss).collect();
+        loss.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(loss, [0.0, 0.5]);
+
+        // each flow hash always chooses the same path, and different hashes can choose different
+        // paths
+        let mut ecmp = HashMap::new();
+        let mut ecmp_hops = HashMap::new();
+        for ((src, dst), x) in paths {
+            let src = graph.node_index_to_id(src).unwrap();
+            let dst = graph.node_index_to_id(dst).unwrap();
+            let (hops, x): (Vec<_>, Vec<_>) = x.into_iter().unzip();
+            let hops = hops
+                .iter()
+                .map(|x| {
+                    x.iter()
+                        .map(|x| graph.node_index_to_id(*x).unwrap())
+                        .collect()
+                })
+                .collect();
+            ecmp.insert((src, dst), x);
+            ecmp_hops.insert((src, dst), hops);
+        }
+        let routing_info = RoutingInfo::new(PathTable {
+            primary: HashMap::new(),
+            ecmp,
+            hops: HashMap::new(),
+            ecmp_hops,
+        });
+        let time = SimulationTime::ZERO;
+        let loss = |hash| {
+            routing_info
+                .flow_path(1, 4, time, hash)
+                .unwrap()
+                .packet_loss
+        };
+        assert_eq!(loss(0), loss(0));
+        assert_eq!(loss(1), loss(1));
+        assert_ne!(loss(0), loss(1));
+
+        // packets are counted on the equal-cost path that their flow takes
+        routing_info.increment_packet_count(1, 4, time, 0);
+        routing_info.increment_packet_count(1, 4, time, 0);
+        routing_info.increment_packet_count(1, 4, time, 1);
+        let counters = routing_info.packet_counters.read().unwrap();
+        assert_eq!(counters[&(0, 1, 4, Some(0))], 2);
+        assert_eq!(counters[&(0, 1, 4, Some(1))], 1);
+    }
+
+    #[test]
+    fn test_update_edge() {
+        let graph = r#"graph [
+          node [
+            id 1
+          ]
+          node [
+            id 2
+          ]
+          edge [
+            source 1
+            target 2
+            latency "10 ns"
+          ]
+        ]"#;
+        let mut graph = NetworkGraph::parse(graph).unwrap();
+
+        // edges in undirected graphs can be referenced in either direction
+        graph.update_edge(2, 1, |e| e.packet_loss = 0.5).unwrap();
+        graph
+            .update_edge(1, 2, |e| {
+                e.latency = units::Time::new(20, units::TimePrefix::Nano)
+            })
+            .unwrap();
+
+        let node_1 = *graph.node_id_to_index(1).unwrap();
+        let node_2 = *graph.node_id_to_index(2).unwrap();
+        let edge = graph.get_edge_weight(&node_1, &node_2).unwrap();
+        assert_eq!(edge.packet_loss, 0.5);
+        assert_eq!(edge.latency, units::Time::new(20, units::TimePrefix::Nano));
+
+        // invalid changes and missing edges are errors
+        graph
+            .update_edge(1, 2, |e| e.packet_loss = 2.0)
+            .unwrap_err();
+        graph
+            .update_edge(1, 1, |e| e.packet_loss = 0.1)
+            .unwrap_err();
+        graph
+            .update_edge(1, 3, |e| e.packet_loss = 0.1)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_scheduled_paths() {
+        let path = |latency_ns| PathProperties {
+            latency_ns,
+            jitter_ns: 0,
+            packet_loss: 0.0,
+            burst_loss: None,
+            bottlenecks: Vec::new(),
+            reorder_probability: 0.0,
+            reorder_delay_ns: 0,
+            duplicate_probability: 0.0,
+            num_hops: 1,
+        };
+
+        let mut routing_info = RoutingInfo::new(HashMap::from([((0, 1), path(10))]));
+        routing_info.schedule_paths(
+            SimulationTime::from_secs(5),
+            HashMap::from([((0, 1), path(5))]),
+        );
+        routing_info.schedule_paths(
+            SimulationTime::from_secs(8),
+            HashMap::from([((0, 1), path(20))]),
+        );
+
+        let latency = |secs| {
+            routing_info
+                .path(0, 1, SimulationTime::from_secs(secs))
+                .unwrap()
+                .latency_ns
+        };
+
+        assert_eq!(latency(0), 10);
+        assert_eq!(latency(4), 10);
+        assert_eq!(latency(5), 5);
+        assert_eq!(latency(7), 5);
+        assert_eq!(latency(8), 20);
+        assert_eq!(latency(100), 20);
+
+        assert_eq!(routing_info.get_smallest_latency_ns(), Some(5));
+    }
+
     // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
     #[test]
     #[cfg_attr(miri, ignore)]
@@ -622,7 +1898,7 @@ mod tests {
                 .compute_shortest_paths(&[node_0, node_1, node_2])
                 .unwrap();
 
-            let lookup_latency = |a, b| shortest_paths.get(&(a, b)).unwrap().latency_ns;
+            let lookup_latency = |a, b| shortest_paths.get(&(a, b)).unwrap().1.latency_ns;
 
             if *directed {
                 assert_eq!(lookup_latency(node_0, node_0), 3333);
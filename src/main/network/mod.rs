@@ -2,8 +2,11 @@ use std::net::Ipv4Addr;
 
 use crate::network::packet::PacketRc;
 
+pub mod bottleneck;
 pub mod graph;
+pub mod multicast;
 pub mod packet;
+pub mod partition;
 pub mod relay;
 pub mod router;
 
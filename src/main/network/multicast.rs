@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::Ipv4Addr;
+use std::sync::{Mutex, RwLock};
+
+use shadow_shim_helper_rs::HostId;
+
+/// Returns true if packets sent to `addr` are delivered to a group of hosts rather than a single
+/// host. Only the limited broadcast address is supported for broadcasts; subnet-directed broadcast
+/// addresses are treated as regular unicast addresses.
+pub fn is_group_address(addr: Ipv4Addr) -> bool {
+    addr.is_multicast() || addr.is_broadcast()
+}
+
+/// A host joining or leaving a multicast group.
+#[derive(Debug)]
+struct MembershipChange {
+    host_id: HostId,
+    group: Ipv4Addr,
+    is_member: bool,
+}
+
+/// The IPv4 multicast group memberships of all hosts.
+///
+/// Hosts join and leave groups in parallel on different threads, so membership changes are
+/// collected during each scheduling round and applied at the end of the round in a deterministic
+/// order. A packet sent to a group is delivered to the hosts that were members when the round
+/// started, and each host checks its own membership again when the packet arrives.
+#[derive(Debug)]
+pub struct MulticastGroups {
+    /// The default address of each host, which broadcast and multicast packets are delivered to.
+    hosts: BTreeMap<HostId, Ipv4Addr>,
+    members: RwLock<HashMap<Ipv4Addr, BTreeSet<HostId>>>,
+    pending: Mutex<Vec<MembershipChange>>,
+}
+
+impl MulticastGroups {
+    pub fn new(hosts: BTreeMap<HostId, Ipv4Addr>) -> Self {
+        Self {
+            hosts,
+            members: RwLock::new(HashMap::new()),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Add or remove the host's membership of `group` at the end of the current round.
+    pub fn push_pending(&self, host_id: HostId, group: Ipv4Addr, is_member: bool) {
+        debug_assert!(group.is_multicast());
+        self.pending.lock().unwrap().push(MembershipChange {
+            host_id,
+            group,
+            is_member,
+        });
+    }
+
+    /// Apply all membership changes that were made during the round. Must be called after each
+    /// round, while no hosts are running.
+    pub fn process_pending(&self) {
+        let mut pending = std::mem::take(&mut *self.pending.lock().unwrap());
+
+        // the order that the changes were pushed depends on the thread scheduling, but each host
+        // runs on a single thread so a stable sort by host keeps each host's changes in order
+        pending.sort_by_key(|change| change.host_id);
+
+        let mut members = self.members.write().unwrap();
+
+        for change in pending {
+            if change.is_member {
+                members
+                    .entry(change.group)
+                    .or_default()
+                    .insert(change.host_id);
+            } else if let Some(hosts) = members.get_mut(&change.group) {
+                hosts.remove(&change.host_id);
+                if hosts.is_empty() {
+                    members.remove(&change.group);
+                }
+            }
+        }
+    }
+
+    /// The hosts (and their addresses) that a packet sent to the multicast or broadcast address
+    /// `group` by the host `src` should be delivered to, in a deterministic order. The source host
+    /// is never included. The packet's TTL limits which of these hosts it reaches.
+    pub fn destinations(&self, group: Ipv4Addr, src: HostId) -> Vec<(HostId, Ipv4Addr)> {
+        if group.is_broadcast() {
+            return self
+                .hosts
+                .iter()
+                .filter(|(id, _)| **id != src)
+                .map(|(id, ip)| (*id, *ip))
+                .collect();
+        }
+
+        let members = self.members.read().unwrap();
+        let Some(hosts) = members.get(&group) else {
+            return Vec::new();
+        };
+
+        hosts
+            .iter()
+            .filter(|id| **id != src)
+            .map(|id| (*id, *self.hosts.get(id).unwrap()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups() -> MulticastGroups {
+        MulticastGroups::new(
+            (0..3)
+                .map(|x| (HostId::from(x), Ipv4Addr::new(11, 0, 0, x as u8 + 1)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_is_group_address() {
+        assert!(is_group_address(Ipv4Addr::new(224, 0, 0, 251)));
+        assert!(is_group_address(Ipv4Addr::new(239, 255, 255, 250)));
+        assert!(is_group_address(Ipv4Addr::BROADCAST));
+        assert!(!is_group_address(Ipv4Addr::new(11, 0, 0, 1)));
+        assert!(!is_group_address(Ipv4Addr::UNSPECIFIED));
+    }
+
+    #[test]
+    fn test_broadcast() {
+        let groups = groups();
+        let dsts = groups.destinations(Ipv4Addr::BROADCAST, HostId::from(1));
+        assert_eq!(
+            dsts,
+            [
+                (HostId::from(0), Ipv4Addr::new(11, 0, 0, 1)),
+                (HostId::from(2), Ipv4Addr::new(11, 0, 0, 3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_membership() {
+        let groups = groups();
+        let group = Ipv4Addr::new(224, 0, 0, 251);
+
+        groups.push_pending(HostId::from(2), group, true);
+        groups.push_pending(HostId::from(0), group, true);
+
+        // changes aren't visible until the end of the round
+        assert!(groups.destinations(group, HostId::from(1)).is_empty());
+        groups.process_pending();
+
+        assert_eq!(
+            groups.destinations(group, HostId::from(1)),
+            [
+                (HostId::from(0), Ipv4Addr::new(11, 0, 0, 1)),
+                (HostId::from(2), Ipv4Addr::new(11, 0, 0, 3)),
+            ]
+        );
+        assert_eq!(
+            groups.destinations(group, HostId::from(0)),
+            [(HostId::from(2), Ipv4Addr::new(11, 0, 0, 3))]
+        );
+
+        // a host that joins and leaves within a round is not a member
+        groups.push_pending(HostId::from(1), group, true);
+        groups.push_pending(HostId::from(0), group, false);
+        groups.push_pending(HostId::from(1), group, false);
+        groups.process_pending();
+
+        assert_eq!(
+            groups.destinations(group, HostId::from(1)),
+            [(HostId::from(2), Ipv4Addr::new(11, 0, 0, 3))]
+        );
+    }
+}
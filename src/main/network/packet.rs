@@ -22,6 +22,8 @@ pub enum PacketStatus {
     SndInterfaceSent = c::_PacketDeliveryStatusFlags_PDS_SND_INTERFACE_SENT,
     InetSent = c::_PacketDeliveryStatusFlags_PDS_INET_SENT,
     InetDropped = c::_PacketDeliveryStatusFlags_PDS_INET_DROPPED,
+    InetReordered = c::_PacketDeliveryStatusFlags_PDS_INET_REORDERED,
+    InetDuplicated = c::_PacketDeliveryStatusFlags_PDS_INET_DUPLICATED,
     RouterEnqueued = c::_PacketDeliveryStatusFlags_PDS_ROUTER_ENQUEUED,
     RouterDequeued = c::_PacketDeliveryStatusFlags_PDS_ROUTER_DEQUEUED,
     RouterDropped = c::_PacketDeliveryStatusFlags_PDS_ROUTER_DROPPED,
@@ -80,6 +82,15 @@ impl PacketRc {
         PacketRc::from_raw(c_ptr)
     }
 
+    #[cfg(test)]
+    /// Creates a UDP packet with no payload for unit tests.
+    pub fn mock_new_udp(src: SocketAddrV4, dst: SocketAddrV4) -> PacketRc {
+        let c_ptr = unsafe { c::packet_new_inner(1, 1) };
+        let mut packet = PacketRc::from_raw(c_ptr);
+        packet.set_udp(src, dst);
+        packet
+    }
+
     /// Set UDP headers for this packet. Will panic if the packet already has a header.
     pub fn set_udp(&mut self, src: SocketAddrV4, dst: SocketAddrV4) {
         unsafe {
@@ -94,6 +105,59 @@ impl PacketRc {
         };
     }
 
+    /// Set the time-to-live of a multicast UDP packet, and whether it should also be delivered to
+    /// the sending host. Will panic if the packet is not a UDP packet.
+    pub fn set_udp_multicast(&mut self, ttl: u8, loopback: bool) {
+        unsafe { c::packet_setUDPMulticast(self.c_ptr.ptr(), ttl, loopback.into()) };
+    }
+
+    /// Set the IP type of service byte, including the ECN codepoint in its low two bits.
+    pub fn set_tos(&mut self, tos: u8) {
+        unsafe { c::packet_setTOS(self.c_ptr.ptr(), tos) };
+    }
+
+    /// Set the IP time-to-live.
+    pub fn set_ttl(&mut self, ttl: u8) {
+        unsafe { c::packet_setTTL(self.c_ptr.ptr(), ttl) };
+    }
+
+    /// Mark whether the packet was sent as an IPv6 packet. Shadow only routes IPv4 packets.
+    pub fn set_ipv6(&mut self, is_ipv6: bool) {
+        unsafe { c::packet_setIPv6(self.c_ptr.ptr(), is_ipv6.into()) };
+    }
+
+    /// Set the ECN codepoint without changing the rest of the type of service byte.
+    pub fn set_ecn(&mut self, ecn: c::ProtocolECN) {
+        unsafe { c::packet_setECN(self.c_ptr.ptr(), ecn) };
+    }
+
+    /// The packet's ECN codepoint.
+    pub fn ecn(&self) -> c::ProtocolECN {
+        unsafe { c::packet_getECN(self.c_ptr.ptr()) }
+    }
+
+    /// Set ICMP echo request or reply headers for this packet. Will panic if the packet already has
+    /// a header.
+    pub fn set_icmp_echo(
+        &mut self,
+        icmp_type: c::ProtocolICMPType,
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        identifier: u16,
+        sequence: u16,
+    ) {
+        unsafe {
+            c::packet_setICMPEcho(
+                self.c_ptr.ptr(),
+                icmp_type,
+                u32::from(src).to_be(),
+                u32::from(dst).to_be(),
+                identifier.to_be(),
+                sequence.to_be(),
+            )
+        };
+    }
+
     /// Set the packet payload. Will panic if the packet already has a payload.
     pub fn set_payload(&mut self, payload: &[u8], priority: FifoPacketPriority) {
         unsafe {
@@ -200,6 +264,42 @@ impl PacketRc {
         SocketAddrV4::new(ip, port)
     }
 
+    pub fn protocol(&self) -> c::ProtocolType {
+        unsafe { c::packet_getProtocol(self.c_ptr.ptr()) }
+    }
+
+    /// A copy of the packet's ICMP header, or `None` if it's not an ICMP packet.
+    pub fn icmp_header(&self) -> Option<c::PacketICMPHeader> {
+        if self.protocol() != c::_ProtocolType_PICMP {
+            return None;
+        }
+
+        let header = unsafe { c::packet_getICMPHeader(self.c_ptr.ptr()) };
+        Some(*unsafe { header.as_ref() }.unwrap())
+    }
+
+    /// The time-to-live of a multicast UDP packet, and whether it should also be delivered to the
+    /// sending host. Returns `None` if it's not a UDP packet.
+    pub fn udp_multicast(&self) -> Option<(u8, bool)> {
+        if self.protocol() != c::_ProtocolType_PUDP {
+            return None;
+        }
+
+        let ttl = self.ttl();
+        let loopback = unsafe { c::packet_getUDPMulticastLoop(self.c_ptr.ptr()) } != 0;
+        Some((ttl, loopback))
+    }
+
+    /// The IP time-to-live, which is decremented for each router hop the packet crosses.
+    pub fn ttl(&self) -> u8 {
+        unsafe { c::packet_getTTL(self.c_ptr.ptr()) }
+    }
+
+    /// Whether the packet was sent as an IPv6 packet.
+    pub fn is_ipv6(&self) -> bool {
+        unsafe { c::packet_isIPv6(self.c_ptr.ptr()) != 0 }
+    }
+
     pub fn priority(&self) -> FifoPacketPriority {
         unsafe { c::packet_getPriority(self.c_ptr.ptr()) }
     }
@@ -227,6 +327,26 @@ impl PacketRc {
     }
 }
 
+/// A deterministic hash (64-bit FNV-1a) of a packet's protocol, addresses, and ports, used to
+/// assign packets to flows. Unlike the standard library's hashers, the algorithm is fixed, so
+/// packets are assigned to the same flows with every Rust version.
+pub fn flow_hash(protocol: c::ProtocolType, src: SocketAddrV4, dst: SocketAddrV4) -> u64 {
+    let bytes = protocol
+        .to_be_bytes()
+        .into_iter()
+        .chain(src.ip().octets())
+        .chain(dst.ip().octets())
+        .chain(src.port().to_be_bytes())
+        .chain(dst.port().to_be_bytes());
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
 impl Drop for PacketRc {
     fn drop(&mut self) {
         if !self.c_ptr.ptr().is_null() {
@@ -258,14 +378,15 @@ impl PacketDisplay for *const c::Packet {
         // write the IP header
 
         let version_and_header_length: u8 = 0x45;
-        let fields: u8 = 0x0;
+        let fields: u8 = unsafe { c::packet_getTOS(*self) };
         let total_length: u16 = header_len + payload_len;
         let identification: u16 = 0x0;
         let flags_and_fragment: u16 = 0x4000;
-        let time_to_live: u8 = 64;
+        let time_to_live: u8 = unsafe { c::packet_getTTL(*self) };
         let iana_protocol: u8 = match protocol {
             c::_ProtocolType_PTCP => 6,
             c::_ProtocolType_PUDP => 17,
+            c::_ProtocolType_PICMP => 1,
             _ => panic!("Unexpected packet protocol"),
         };
         let header_checksum: u16 = 0x0;
@@ -298,6 +419,7 @@ impl PacketDisplay for *const c::Packet {
         match protocol {
             c::_ProtocolType_PTCP => display_tcp_bytes(*self, &mut writer)?,
             c::_ProtocolType_PUDP => display_udp_bytes(*self, &mut writer)?,
+            c::_ProtocolType_PICMP => display_icmp_bytes(*self, &mut writer)?,
             _ => panic!("Unexpected packet protocol"),
         }
 
@@ -376,6 +498,12 @@ fn display_tcp_bytes(packet: *const c::Packet, mut writer: impl Write) -> std::i
     if tcp_header.flags & c::ProtocolTCPFlags_PTCP_FIN != 0 {
         tcp_flags |= 0x01;
     }
+    if tcp_header.flags & c::ProtocolTCPFlags_PTCP_ECE != 0 {
+        tcp_flags |= 0x40;
+    }
+    if tcp_header.flags & c::ProtocolTCPFlags_PTCP_CWR != 0 {
+        tcp_flags |= 0x80;
+    }
     let window: [u8; 2] = u16::try_from(tcp_header.window).unwrap().to_be_bytes();
     let checksum: u16 = 0x0;
     let urgent_pointer: u16 = 0x0;
@@ -431,3 +559,43 @@ fn display_udp_bytes(packet: *const c::Packet, mut writer: impl Write) -> std::i
 
     Ok(())
 }
+
+/// Helper for writing the icmp bytes of the packet.
+fn display_icmp_bytes(packet: *const c::Packet, mut writer: impl Write) -> std::io::Result<()> {
+    assert_eq!(
+        unsafe { c::packet_getProtocol(packet) },
+        c::_ProtocolType_PICMP
+    );
+
+    let icmp_header = unsafe { c::packet_getICMPHeader(packet) };
+    assert!(!icmp_header.is_null());
+    let icmp_header = unsafe { icmp_header.as_ref() }.unwrap();
+
+    // write the ICMP header
+
+    let icmp_type: u8 = icmp_header.type_.try_into().unwrap();
+    let code: u8 = icmp_header.code.try_into().unwrap();
+    let checksum: u16 = 0x0;
+
+    // type: 1 byte
+    // code: 1 byte
+    writer.write_all(&[icmp_type, code])?;
+    // checksum: 2 bytes
+    writer.write_all(&checksum.to_be_bytes())?;
+
+    if unsafe { c::packet_isICMPError(packet) } != 0 {
+        // unused: 4 bytes
+        writer.write_all(&[0u8; 4])?;
+    } else {
+        // identifier and sequence number are already in network byte order
+        let identifier: [u8; 2] = u16::from_be(icmp_header.identifier).to_be_bytes();
+        let sequence: [u8; 2] = u16::from_be(icmp_header.sequence).to_be_bytes();
+
+        // identifier: 2 bytes
+        writer.write_all(&identifier)?;
+        // sequence number: 2 bytes
+        writer.write_all(&sequence)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+/// A network partition that drops all packets sent from one group of addresses to another during
+/// a period of simulated time.
+#[derive(Debug, Clone)]
+pub struct Partition {
+    /// The simulation time at which the partition starts.
+    pub start: SimulationTime,
+    /// The simulation time at which the partition ends, or `None` if it never ends.
+    pub end: Option<SimulationTime>,
+    /// Addresses whose packets to the `targets` are dropped.
+    pub sources: HashSet<IpAddr>,
+    /// Addresses that don't receive packets from the `sources`.
+    pub targets: HashSet<IpAddr>,
+    /// If true, packets from the `targets` to the `sources` are also dropped.
+    pub symmetric: bool,
+    /// A human-readable description of the partition, used in log messages.
+    pub description: String,
+}
+
+impl Partition {
+    fn is_active(&self, time: SimulationTime) -> bool {
+        time >= self.start && self.end.map(|end| time < end).unwrap_or(true)
+    }
+
+    fn cuts(&self, src: IpAddr, dst: IpAddr) -> bool {
+        let forward = self.sources.contains(&src) && self.targets.contains(&dst);
+        let reverse = self.targets.contains(&src) && self.sources.contains(&dst);
+        forward || (self.symmetric && reverse)
+    }
+}
+
+/// The start or end of a network partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionChange {
+    Start,
+    End,
+}
+
+/// A table of the network partitions that occur during the simulation.
+#[derive(Debug, Default)]
+pub struct PartitionTable {
+    partitions: Vec<Partition>,
+}
+
+impl PartitionTable {
+    pub fn new(partitions: Vec<Partition>) -> Self {
+        Self { partitions }
+    }
+
+    /// Returns true if packets sent from `src` to `dst` at the given time should be dropped due to
+    /// a network partition.
+    pub fn is_partitioned(&self, src: IpAddr, dst: IpAddr, time: SimulationTime) -> bool {
+        // a host is never partitioned from itself
+        if src == dst {
+            return false;
+        }
+
+        self.partitions
+            .iter()
+            .any(|x| x.is_active(time) && x.cuts(src, dst))
+    }
+
+    /// The times at which each partition starts and ends, sorted by time.
+    pub fn changes(&self) -> Vec<(SimulationTime, PartitionChange, &Partition)> {
+        let mut changes: Vec<_> = self
+            .partitions
+            .iter()
+            .flat_map(|x| {
+                let start = Some((x.start, PartitionChange::Start, x));
+                let end = x.end.map(|end| (end, PartitionChange::End, x));
+                start.into_iter().chain(end)
+            })
+            .collect();
+
+        // a stable sort so that changes at the same time stay in configuration order
+        changes.sort_by_key(|(time, _, _)| *time);
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(x: u8) -> IpAddr {
+        IpAddr::V4(std::net::Ipv4Addr::new(11, 0, 0, x))
+    }
+
+    fn partition(symmetric: bool) -> Partition {
+        Partition {
+            start: SimulationTime::from_secs(10),
+            end: Some(SimulationTime::from_secs(20)),
+            sources: HashSet::from([ip(1), ip(2)]),
+            targets: HashSet::from([ip(3)]),
+            symmetric,
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_symmetric() {
+        let table = PartitionTable::new(vec![partition(true)]);
+        let time = SimulationTime::from_secs(15);
+
+        assert!(table.is_partitioned(ip(1), ip(3), time));
+        assert!(table.is_partitioned(ip(2), ip(3), time));
+        assert!(table.is_partitioned(ip(3), ip(1), time));
+        assert!(!table.is_partitioned(ip(1), ip(2), time));
+        assert!(!table.is_partitioned(ip(1), ip(4), time));
+    }
+
+    #[test]
+    fn test_one_way() {
+        let table = PartitionTable::new(vec![partition(false)]);
+        let time = SimulationTime::from_secs(15);
+
+        assert!(table.is_partitioned(ip(1), ip(3), time));
+        assert!(!table.is_partitioned(ip(3), ip(1), time));
+    }
+
+    #[test]
+    fn test_time_window() {
+        let table = PartitionTable::new(vec![partition(true)]);
+        let is_partitioned =
+            |secs| table.is_partitioned(ip(1), ip(3), SimulationTime::from_secs(secs));
+
+        assert!(!is_partitioned(9));
+        assert!(is_partitioned(10));
+        assert!(is_partitioned(19));
+        assert!(!is_partitioned(20));
+
+        let changes: Vec<_> = table.changes().iter().map(|x| (x.0, x.1)).collect();
+        assert_eq!(
+            changes,
+            [
+                (SimulationTime::from_secs(10), PartitionChange::Start),
+                (SimulationTime::from_secs(20), PartitionChange::End),
+            ]
+        );
+    }
+}
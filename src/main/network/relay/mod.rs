@@ -9,9 +9,10 @@ use crate::core::work::task::TaskRef;
 use crate::core::worker::Worker;
 use crate::cshadow as c;
 use crate::host::host::Host;
+use crate::network::multicast;
 use crate::network::packet::PacketStatus;
 use crate::network::relay::token_bucket::TokenBucket;
-use crate::network::PacketRc;
+use crate::network::{PacketDevice, PacketRc};
 use crate::utility::ObjectCounter;
 
 mod token_bucket;
@@ -88,15 +89,10 @@ impl Relay {
     /// internally schedules tasks as needed to ensure packets continue to be
     /// forwarded over time without exceeding the configured `RateLimit`.
     pub fn new(rate: RateLimit, src_dev_address: Ipv4Addr) -> Self {
-        let rate_limiter = match rate {
-            RateLimit::BytesPerSecond(bytes) => Some(create_token_bucket(bytes)),
-            RateLimit::Unlimited => None,
-        };
-
         Self {
             internal: AtomicRefCell::new(RelayInternal {
                 _counter: ObjectCounter::new("Relay"),
-                rate_limiter,
+                rate_limiter: create_rate_limiter(rate),
                 src_dev_address,
                 state: RelayState::Idle,
                 next_packet: None,
@@ -104,6 +100,12 @@ impl Relay {
         }
     }
 
+    /// Replace the relay's `RateLimit`. If the relay is currently blocked waiting for tokens, the
+    /// new rate limit will be used when it next tries to forward a packet.
+    pub fn set_rate_limit(&self, rate: RateLimit) {
+        self.internal.borrow_mut().rate_limiter = create_rate_limiter(rate);
+    }
+
     /// Notify the relay that its packet source now has packets available for
     /// relaying to the packet sink. This must be called when the source changes
     /// state from empty to non-empty to signal the relay to resume forwarding.
@@ -259,7 +261,10 @@ impl Relay {
 
             // Forward the packet to the destination device now.
             packet.add_status(PacketStatus::RelayForwarded);
-            if is_local {
+            if multicast::is_group_address(*packet.dst_address().ip()) {
+                // Multicast and broadcast packets may go to more than one device.
+                forward_group_packet(host, &*src, packet);
+            } else if is_local {
                 // The source and destination are the same. Avoid a double
                 // mutable borrow of the packet device.
                 src.push(packet);
@@ -272,6 +277,51 @@ impl Relay {
     }
 }
 
+/// Forwards a multicast or broadcast packet from the source device. Packets
+/// sent by the host are looped back to the source device if the host should
+/// receive them itself, and are sent to the router unless their TTL restricts
+/// them to the host. Packets from the router are only received if the host is a
+/// member of the group.
+fn forward_group_packet(host: &Host, src: &dyn PacketDevice, mut packet: PacketRc) {
+    let group = *packet.dst_address().ip();
+    let is_member =
+        group.is_broadcast() || host.network_namespace_borrow().is_multicast_member(group);
+
+    if src.get_address() != host.default_ip() {
+        // The packet was sent to this host by the network.
+        if is_member {
+            host.get_packet_device(host.default_ip()).push(packet);
+        } else {
+            packet.add_status(PacketStatus::RcvInterfaceDropped);
+        }
+        return;
+    }
+
+    let (ttl, loopback) = match packet.udp_multicast() {
+        // Like Linux, broadcast packets are always looped back.
+        Some((ttl, _)) if group.is_broadcast() => (ttl, true),
+        Some(options) => options,
+        None => (1, false),
+    };
+
+    if loopback && is_member {
+        let copy = PacketRc::from_raw(unsafe { c::packet_copy(packet.borrow_inner()) });
+        src.push(copy);
+    }
+
+    if ttl > 0 {
+        host.get_packet_device(group).push(packet);
+    }
+}
+
+/// Creates the token bucket for the given `RateLimit`, if there is a limit.
+fn create_rate_limiter(rate: RateLimit) -> Option<TokenBucket> {
+    match rate {
+        RateLimit::BytesPerSecond(bytes) => Some(create_token_bucket(bytes)),
+        RateLimit::Unlimited => None,
+    }
+}
+
 /// Configures a token bucket according the the given bytes_per_second rate
 /// limit. We always refill at least 1 byte per millisecond.
 fn create_token_bucket(bytes_per_second: u64) -> TokenBucket {
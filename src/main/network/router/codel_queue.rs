@@ -1,6 +1,9 @@
 //! An active queue management (AQM) algorithm implementing CoDel.
 //! <https://tools.ietf.org/html/rfc8289>
 //!
+//!  Packets that are ECN-capable are marked with the "congestion experienced"
+//!  codepoint instead of being dropped (<https://tools.ietf.org/html/rfc3168>).
+//!
 //!  The "Flow Queue" variant is not implemented.
 //!  <https://tools.ietf.org/html/rfc8290>
 //!
@@ -14,6 +17,7 @@ use std::{collections::VecDeque, time::Duration};
 
 use shadow_shim_helper_rs::{emulated_time::EmulatedTime, simulation_time::SimulationTime};
 
+use super::{drop_packet, mark_packet, RouterQueue, RouterQueueStats};
 use crate::cshadow as c;
 use crate::network::packet::{PacketRc, PacketStatus};
 
@@ -36,6 +40,7 @@ const LIMIT: usize = usize::MAX;
 struct CoDelPopItem {
     packet: PacketRc,
     ok_to_drop: bool,
+    standing_delay: SimulationTime,
 }
 
 /// Represents the possible states of the CoDel algorithm.
@@ -78,6 +83,8 @@ pub struct CoDelQueue {
     current_drop_count: usize,
     /// The number of packets dropped the last time we were in drop mode.
     previous_drop_count: usize,
+    /// Counters reported at the end of the simulation.
+    stats: RouterQueueStats,
 }
 
 impl CoDelQueue {
@@ -91,19 +98,13 @@ impl CoDelQueue {
             drop_next: None,
             current_drop_count: 0,
             previous_drop_count: 0,
+            stats: RouterQueueStats::new(),
         }
     }
 
-    /// Returns the total number of packets stored in the queue.
-    #[cfg(test)]
-    pub fn len(&self) -> usize {
-        self.elements.len()
-    }
-
-    /// Returns true if the queue is holding zero packets, false otherwise.
-    #[cfg(test)]
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
+    /// Returns the total size in bytes of the packets stored in the queue.
+    pub fn total_bytes_stored(&self) -> usize {
+        self.total_bytes_stored
     }
 
     /// Returns the packet at the front of the queue, or None if the queue is
@@ -115,45 +116,20 @@ impl CoDelQueue {
         self.elements.front().map(|x| &x.packet)
     }
 
-    /// Returns the next packet in the queue that conforms to the CoDel standing
-    /// delay requirements, or None if the queue is empty before a conforming
-    /// packet is found. The CoDel packet dropping logic is applied during this
-    /// operation, which could result in packets being dropped before a packet
-    /// that conforms to the standing delay requirements is returned.
-    /// Requires the current time as an argument to avoid calling into the
-    /// worker module internally.
-    pub fn pop(&mut self, now: EmulatedTime) -> Option<PacketRc> {
-        let maybe_packet = match self.codel_pop(&now) {
-            Some(item) => match item.ok_to_drop {
-                true => match self.mode {
-                    CoDelMode::Store => self.drop_from_store_mode(&now, item.packet),
-                    CoDelMode::Drop => self.drop_from_drop_mode(&now, item.packet),
-                },
-                false => {
-                    // Always set Store mode when standing delay below TARGET.
-                    self.mode = CoDelMode::Store;
-                    Some(item.packet)
-                }
-            },
-            None => {
-                // Always set Store mode when the queue is empty.
-                self.mode = CoDelMode::Store;
-                None
-            }
-        };
-
-        maybe_packet.map(|mut p| {
-            p.add_status(PacketStatus::RouterDequeued);
-            p
-        })
-    }
-
-    fn drop_from_store_mode(&mut self, now: &EmulatedTime, packet: PacketRc) -> Option<PacketRc> {
+    fn drop_from_store_mode(
+        &mut self,
+        now: &EmulatedTime,
+        mut item: CoDelPopItem,
+    ) -> Option<CoDelPopItem> {
         debug_assert_eq!(self.mode, CoDelMode::Store);
 
-        // Drop one packet and move to drop mode.
-        self.drop_packet(packet);
-        let next_item = self.codel_pop(now);
+        // Drop (or mark) one packet and move to drop mode.
+        let next_item = if self.mark_packet(&mut item.packet) {
+            Some(item)
+        } else {
+            self.drop_packet(item.packet);
+            self.codel_pop(now)
+        };
         self.mode = CoDelMode::Drop;
 
         // Reset to the drop rate that was known to control the queue.
@@ -167,20 +143,34 @@ impl CoDelQueue {
         self.drop_next = Some(CoDelQueue::apply_control_law(now, self.current_drop_count));
         self.previous_drop_count = self.current_drop_count;
 
-        next_item.map(|x| x.packet)
+        next_item
     }
 
-    fn drop_from_drop_mode(&mut self, now: &EmulatedTime, packet: PacketRc) -> Option<PacketRc> {
+    fn drop_from_drop_mode(
+        &mut self,
+        now: &EmulatedTime,
+        item: CoDelPopItem,
+    ) -> Option<CoDelPopItem> {
         debug_assert_eq!(self.mode, CoDelMode::Drop);
 
-        let mut item = Some(CoDelPopItem {
-            packet,
-            ok_to_drop: true,
-        });
+        let mut item = Some(item);
 
         // Drop as many packets as the control law dictates.
         while item.is_some() && self.mode == CoDelMode::Drop && self.should_drop(now) {
-            self.drop_packet(item.unwrap().packet);
+            let mut dropped = item.unwrap();
+
+            if self.mark_packet(&mut dropped.packet) {
+                // A marked packet is forwarded, so we don't look at any more packets until
+                // the next drop time.
+                self.current_drop_count += 1;
+                self.drop_next = Some(CoDelQueue::apply_control_law(
+                    &self.drop_next.unwrap(),
+                    self.current_drop_count,
+                ));
+                return Some(dropped);
+            }
+
+            self.drop_packet(dropped.packet);
             self.current_drop_count += 1;
 
             item = self.codel_pop(now);
@@ -198,7 +188,7 @@ impl CoDelQueue {
             }
         }
 
-        item.map(|x| x.packet)
+        item
     }
 
     // Corresponds to the `dodequeue` function in the RFC.
@@ -218,6 +208,7 @@ impl CoDelQueue {
                 Some(CoDelPopItem {
                     packet: element.packet,
                     ok_to_drop,
+                    standing_delay,
                 })
             }
             None => {
@@ -299,10 +290,44 @@ impl CoDelQueue {
         EmulatedTime::from_abs_simtime(adjusted)
     }
 
+    /// Drop the packet at the front of the queue without applying the CoDel
+    /// logic. Returns false if the queue is empty.
+    pub fn drop_front(&mut self) -> bool {
+        let Some(element) = self.elements.pop_front() else {
+            return false;
+        };
+
+        self.total_bytes_stored = self
+            .total_bytes_stored
+            .saturating_sub(element.packet.total_size());
+        self.drop_packet(element.packet);
+
+        true
+    }
+
+    fn drop_packet(&mut self, packet: PacketRc) {
+        self.stats.dropped += 1;
+        drop_packet(packet);
+    }
+
+    /// Signal congestion to an ECN-capable packet instead of dropping it.
+    /// Returns false if the packet must be dropped instead.
+    fn mark_packet(&mut self, packet: &mut PacketRc) -> bool {
+        let marked = mark_packet(packet);
+        if marked {
+            self.stats.marked += 1;
+        }
+        marked
+    }
+}
+
+impl RouterQueue for CoDelQueue {
     /// Append a packet to the end of the queue.
     /// Requires the current time as an argument to avoid calling into the
     /// worker module internally.
-    pub fn push(&mut self, mut packet: PacketRc, now: EmulatedTime) {
+    fn push(&mut self, mut packet: PacketRc, now: EmulatedTime) {
+        self.stats.arrived += 1;
+
         if self.elements.len() < LIMIT {
             packet.add_status(PacketStatus::RouterEnqueued);
             self.total_bytes_stored += packet.total_size();
@@ -318,8 +343,47 @@ impl CoDelQueue {
         }
     }
 
-    fn drop_packet(&self, mut packet: PacketRc) {
-        packet.add_status(PacketStatus::RouterDropped);
+    /// Returns the next packet in the queue that conforms to the CoDel standing
+    /// delay requirements, or None if the queue is empty before a conforming
+    /// packet is found. The CoDel packet dropping logic is applied during this
+    /// operation, which could result in packets being dropped before a packet
+    /// that conforms to the standing delay requirements is returned.
+    /// Requires the current time as an argument to avoid calling into the
+    /// worker module internally.
+    fn pop(&mut self, now: EmulatedTime) -> Option<PacketRc> {
+        let maybe_item = match self.codel_pop(&now) {
+            Some(item) => match item.ok_to_drop {
+                true => match self.mode {
+                    CoDelMode::Store => self.drop_from_store_mode(&now, item),
+                    CoDelMode::Drop => self.drop_from_drop_mode(&now, item),
+                },
+                false => {
+                    // Always set Store mode when standing delay below TARGET.
+                    self.mode = CoDelMode::Store;
+                    Some(item)
+                }
+            },
+            None => {
+                // Always set Store mode when the queue is empty.
+                self.mode = CoDelMode::Store;
+                None
+            }
+        };
+
+        maybe_item.map(|mut item| {
+            self.stats.record_forwarded(item.standing_delay);
+            item.packet.add_status(PacketStatus::RouterDequeued);
+            item.packet
+        })
+    }
+
+    /// Returns the total number of packets stored in the queue.
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    fn stats(&self) -> RouterQueueStats {
+        self.stats
     }
 }
 
@@ -484,6 +548,44 @@ mod tests {
         assert_eq!(cdq.mode, CoDelMode::Store);
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn mark_ecn_capable() {
+        let start = mock_time_millis(1000);
+        let end = mock_time_millis(1000000);
+
+        let mut cdq = CoDelQueue::new();
+        const N: usize = 20;
+        for _ in 0..N {
+            let mut packet = PacketRc::mock_new();
+            packet.set_ecn(c::ProtocolECN_PECN_ECT0);
+            cdq.push(packet, start);
+        }
+
+        // Sets the interval.
+        let packet = cdq.pop(start + TARGET).unwrap();
+        assert_eq!(packet.ecn(), c::ProtocolECN_PECN_ECT0);
+        assert_eq!(cdq.mode, CoDelMode::Store);
+
+        // Enters Drop mode, but marks the packet instead of dropping it.
+        let packet = cdq.pop(start + TARGET + INTERVAL).unwrap();
+        assert_eq!(packet.ecn(), c::ProtocolECN_PECN_CE);
+        assert_eq!(cdq.len(), N - 2);
+        assert_eq!(cdq.current_drop_count, 1);
+        assert_eq!(cdq.mode, CoDelMode::Drop);
+
+        // Each pop marks at most one packet, and no packets are dropped.
+        let packet = cdq.pop(end).unwrap();
+        assert_eq!(packet.ecn(), c::ProtocolECN_PECN_CE);
+        assert_eq!(cdq.len(), N - 3);
+        assert_eq!(cdq.current_drop_count, 2);
+
+        let stats = cdq.stats();
+        assert_eq!(stats.forwarded, 3);
+        assert_eq!(stats.marked, 2);
+        assert_eq!(stats.dropped, 0);
+    }
+
     #[test]
     fn drop_empty() {
         let start = mock_time_millis(1000);
@@ -536,5 +638,10 @@ mod tests {
         assert_eq!(cdq.len(), 1);
         assert_eq!(cdq.current_drop_count, N - 4);
         assert_eq!(cdq.mode, CoDelMode::Store);
+
+        let stats = cdq.stats();
+        assert_eq!(stats.arrived, N as u64);
+        assert_eq!(stats.dropped, N as u64 - 4);
+        assert_eq!(stats.forwarded, 3);
     }
 }
@@ -0,0 +1,136 @@
+//! A first-in first-out packet queue with a fixed capacity, which drops packets
+//! that arrive while the queue is full. This is the behavior of a router
+//! without active queue management, and corresponds to linux's `pfifo`
+//! queueing discipline.
+//!
+//!  More info:
+//!   - <https://en.wikipedia.org/wiki/Tail_drop>
+//!   - <http://man7.org/linux/man-pages/man8/tc-pfifo.8.html>
+
+use std::collections::VecDeque;
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+use super::{drop_packet, RouterQueue, RouterQueueStats};
+use crate::network::packet::{PacketRc, PacketStatus};
+
+/// An entry in the drop-tail queue.
+struct DropTailElement {
+    packet: PacketRc,
+    enqueue_ts: EmulatedTime,
+}
+
+/// A packet queue that stores up to `limit` packets and drops any packets
+/// that arrive while it is full.
+pub struct DropTailQueue {
+    /// A queue holding packets and insertion times.
+    elements: VecDeque<DropTailElement>,
+    /// The maximum number of packets we will store.
+    limit: usize,
+    /// Counters reported at the end of the simulation.
+    stats: RouterQueueStats,
+}
+
+impl DropTailQueue {
+    /// Creates a new empty packet queue that can hold up to `limit` packets.
+    pub fn new(limit: usize) -> DropTailQueue {
+        DropTailQueue {
+            elements: VecDeque::new(),
+            limit,
+            stats: RouterQueueStats::new(),
+        }
+    }
+
+    /// Returns true if the queue will drop the next packet pushed to it.
+    pub fn is_full(&self) -> bool {
+        self.elements.len() >= self.limit
+    }
+
+    /// Drop a packet that arrived at the queue without storing it. This is
+    /// used by queues that build on the drop-tail queue to make their own drop
+    /// decisions.
+    pub fn drop_arriving(&mut self, packet: PacketRc) {
+        self.stats.arrived += 1;
+        self.stats.dropped += 1;
+        drop_packet(packet);
+    }
+}
+
+impl RouterQueue for DropTailQueue {
+    fn push(&mut self, mut packet: PacketRc, now: EmulatedTime) {
+        if self.is_full() {
+            self.drop_arriving(packet);
+            return;
+        }
+
+        self.stats.arrived += 1;
+        packet.add_status(PacketStatus::RouterEnqueued);
+        self.elements.push_back(DropTailElement {
+            packet,
+            enqueue_ts: now,
+        });
+    }
+
+    fn pop(&mut self, now: EmulatedTime) -> Option<PacketRc> {
+        let mut element = self.elements.pop_front()?;
+
+        debug_assert!(now >= element.enqueue_ts);
+        self.stats
+            .record_forwarded(now.saturating_duration_since(&element.enqueue_ts));
+
+        element.packet.add_status(PacketStatus::RouterDequeued);
+        Some(element.packet)
+    }
+
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    fn stats(&self) -> RouterQueueStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tests::mock_time_millis;
+
+    // Some of the tests here don't run in miri because they cause c::packet*
+    // functions to be called during the test.
+
+    #[test]
+    fn empty() {
+        let now = mock_time_millis(1000);
+        let mut queue = DropTailQueue::new(10);
+        assert!(queue.is_empty());
+        assert!(queue.pop(now).is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn drop_when_full() {
+        let start = mock_time_millis(1000);
+        let end = mock_time_millis(1050);
+
+        const LIMIT: usize = 5;
+        let mut queue = DropTailQueue::new(LIMIT);
+
+        for i in 1..=2 * LIMIT {
+            queue.push(PacketRc::mock_new(), start);
+            assert_eq!(queue.len(), std::cmp::min(i, LIMIT));
+        }
+
+        for _ in 0..LIMIT {
+            assert!(queue.pop(end).is_some());
+        }
+        assert!(queue.pop(end).is_none());
+
+        let stats = queue.stats();
+        assert_eq!(stats.arrived, 2 * LIMIT as u64);
+        assert_eq!(stats.dropped, LIMIT as u64);
+        assert_eq!(stats.forwarded, LIMIT as u64);
+        assert_eq!(stats.max_delay, end - start);
+        assert_eq!(stats.mean_delay(), Some(end - start));
+    }
+}
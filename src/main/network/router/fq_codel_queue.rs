@@ -0,0 +1,259 @@
+//! An active queue management (AQM) algorithm implementing FQ-CoDel.
+//! <https://tools.ietf.org/html/rfc8290>
+//!
+//!  Packets are hashed into one of a fixed number of flow queues by their
+//!  protocol, addresses, and ports. Each flow queue is managed by CoDel, and
+//!  the flow queues are served using deficit round robin, giving priority to
+//!  flows that have recently become active.
+//!
+//!  More info:
+//!   - <http://man7.org/linux/man-pages/man8/tc-fq_codel.8.html>
+
+use std::collections::{BTreeMap, VecDeque};
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+use super::codel_queue::CoDelQueue;
+use super::{RouterQueue, RouterQueueStats};
+use crate::cshadow as c;
+use crate::network::packet::{flow_hash, PacketRc};
+
+/// The number of flow queues, corresponding to the "flows" parameter in the
+/// fq_codel man page.
+const FLOWS: usize = 1024;
+
+/// The number of bytes a flow may dequeue in each round, corresponding to the
+/// "quantum" parameter in the RFC. This is the size of an ethernet frame.
+const QUANTUM: i64 = 1514;
+
+/// A flow queue and its scheduling state.
+struct Flow {
+    queue: CoDelQueue,
+    /// The number of bytes the flow may dequeue before its turn ends.
+    deficit: i64,
+    /// Whether the flow is in the list of new flows or the list of old flows.
+    is_active: bool,
+}
+
+/// A packet queue implementing the FQ-CoDel active queue management (AQM)
+/// algorithm, which isolates flows from each other so that a flow that fills
+/// the queue doesn't add delay to other flows.
+pub struct FqCoDelQueue {
+    /// The flow queues, which are created when a flow's first packet arrives.
+    flows: BTreeMap<usize, Flow>,
+    /// Flows that have become active since they were last served.
+    new_flows: VecDeque<usize>,
+    /// Active flows that have used up their first quantum.
+    old_flows: VecDeque<usize>,
+    /// The maximum number of packets we will store across all flows.
+    limit: usize,
+    /// The total number of packets stored across all flows.
+    len: usize,
+}
+
+impl FqCoDelQueue {
+    /// Creates a new empty packet queue that can hold up to `limit` packets.
+    pub fn new(limit: usize) -> FqCoDelQueue {
+        FqCoDelQueue {
+            flows: BTreeMap::new(),
+            new_flows: VecDeque::new(),
+            old_flows: VecDeque::new(),
+            limit,
+            len: 0,
+        }
+    }
+
+    /// Returns the index of the flow queue for the packet.
+    fn classify(packet: &PacketRc) -> usize {
+        // the flow hash uses a fixed algorithm, so the flow assignment is deterministic
+        let hash = if packet.protocol() == c::_ProtocolType_PMOCK {
+            // mock packets used in tests don't have addresses
+            let unspecified = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+            flow_hash(packet.protocol(), unspecified, unspecified)
+        } else {
+            flow_hash(
+                packet.protocol(),
+                packet.src_address(),
+                packet.dst_address(),
+            )
+        };
+        (hash % FLOWS as u64) as usize
+    }
+
+    /// Drop a packet from the front of the flow queue holding the most bytes.
+    fn drop_from_fattest_flow(&mut self) {
+        let fattest = self
+            .flows
+            .values_mut()
+            .max_by_key(|flow| flow.queue.total_bytes_stored())
+            .unwrap();
+
+        if fattest.queue.drop_front() {
+            self.len -= 1;
+        }
+    }
+}
+
+impl RouterQueue for FqCoDelQueue {
+    fn push(&mut self, packet: PacketRc, now: EmulatedTime) {
+        let index = FqCoDelQueue::classify(&packet);
+        let flow = self.flows.entry(index).or_insert_with(|| Flow {
+            queue: CoDelQueue::new(),
+            deficit: 0,
+            is_active: false,
+        });
+
+        flow.queue.push(packet, now);
+        self.len += 1;
+
+        if !flow.is_active {
+            flow.is_active = true;
+            flow.deficit = QUANTUM;
+            self.new_flows.push_back(index);
+        }
+
+        if self.len > self.limit {
+            self.drop_from_fattest_flow();
+        }
+    }
+
+    fn pop(&mut self, now: EmulatedTime) -> Option<PacketRc> {
+        loop {
+            let (index, is_new) = match (self.new_flows.front(), self.old_flows.front()) {
+                (Some(&index), _) => (index, true),
+                (None, Some(&index)) => (index, false),
+                (None, None) => return None,
+            };
+            let flow = self.flows.get_mut(&index).unwrap();
+            let list = if is_new {
+                &mut self.new_flows
+            } else {
+                &mut self.old_flows
+            };
+
+            // The flow has used up its quantum, so it goes to the back of the
+            // old flows with a new quantum.
+            if flow.deficit <= 0 {
+                flow.deficit += QUANTUM;
+                list.pop_front();
+                self.old_flows.push_back(index);
+                continue;
+            }
+
+            // CoDel may drop packets from the flow while dequeueing.
+            let len_before = flow.queue.len();
+            let packet = flow.queue.pop(now);
+            self.len -= len_before - flow.queue.len();
+
+            if let Some(packet) = packet {
+                flow.deficit -= packet.total_size() as i64;
+                return Some(packet);
+            }
+
+            // The flow is empty. A new flow is moved to the old flows so that
+            // it can't starve the old flows by repeatedly becoming new.
+            list.pop_front();
+            if is_new && !self.old_flows.is_empty() {
+                self.old_flows.push_back(index);
+            } else {
+                flow.is_active = false;
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn stats(&self) -> RouterQueueStats {
+        let mut stats = RouterQueueStats::new();
+        for flow in self.flows.values() {
+            stats.add(&flow.queue.stats());
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    use super::*;
+    use crate::network::tests::mock_time_millis;
+
+    // Some of the tests here don't run in miri because they cause c::packet*
+    // functions to be called during the test.
+
+    fn udp_packet(dst_port: u16) -> PacketRc {
+        PacketRc::mock_new_udp(
+            SocketAddrV4::new(Ipv4Addr::new(1, 2, 3, 4), 1000),
+            SocketAddrV4::new(Ipv4Addr::new(5, 6, 7, 8), dst_port),
+        )
+    }
+
+    #[test]
+    fn empty() {
+        let now = mock_time_millis(1000);
+        let mut queue = FqCoDelQueue::new(10);
+        assert!(queue.is_empty());
+        assert!(queue.pop(now).is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn new_flow_is_not_delayed() {
+        let now = mock_time_millis(1000);
+        let mut queue = FqCoDelQueue::new(1000);
+
+        const N: usize = 100;
+        let (bulk, sparse) = (udp_packet(1), udp_packet(2));
+        assert_ne!(
+            FqCoDelQueue::classify(&bulk),
+            FqCoDelQueue::classify(&sparse)
+        );
+
+        queue.push(bulk, now);
+        for _ in 1..N {
+            queue.push(udp_packet(1), now);
+        }
+        queue.push(sparse, now);
+        assert_eq!(queue.len(), N + 1);
+
+        // The sparse flow is served after the bulk flow uses up its quantum,
+        // and not after the bulk flow's entire backlog.
+        let position = (0..=N)
+            .position(|_| queue.pop(now).unwrap().dst_address().port() == 2)
+            .unwrap();
+        let packets_per_quantum = QUANTUM as usize / udp_packet(1).total_size() + 1;
+        assert_eq!(position, packets_per_quantum);
+        assert_eq!(queue.len(), N - position);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn drop_from_fattest_flow() {
+        let now = mock_time_millis(1000);
+
+        const LIMIT: usize = 10;
+        let mut queue = FqCoDelQueue::new(LIMIT);
+
+        for _ in 0..LIMIT {
+            queue.push(udp_packet(1), now);
+        }
+        queue.push(udp_packet(2), now);
+
+        // The bulk flow lost a packet, not the new flow.
+        assert_eq!(queue.len(), LIMIT);
+        let ports: Vec<_> = std::iter::from_fn(|| queue.pop(now))
+            .map(|packet| packet.dst_address().port())
+            .collect();
+        assert_eq!(ports.iter().filter(|&&port| port == 1).count(), LIMIT - 1);
+        assert_eq!(ports.iter().filter(|&&port| port == 2).count(), 1);
+
+        let stats = queue.stats();
+        assert_eq!(stats.arrived, LIMIT as u64 + 1);
+        assert_eq!(stats.dropped, 1);
+        assert_eq!(stats.forwarded, LIMIT as u64);
+    }
+}
@@ -1,16 +1,179 @@
 use std::cell::RefCell;
 use std::net::Ipv4Addr;
 
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
 use self::codel_queue::CoDelQueue;
+use self::drop_tail_queue::DropTailQueue;
+use self::fq_codel_queue::FqCoDelQueue;
+use self::red_queue::RedQueue;
 use crate::core::worker::Worker;
 use crate::cshadow as c;
-use crate::network::packet::PacketRc;
+use crate::network::packet::{PacketRc, PacketStatus};
 use crate::network::PacketDevice;
 use crate::utility::{Magic, ObjectCounter};
 mod codel_queue;
+mod drop_tail_queue;
+mod fq_codel_queue;
+mod red_queue;
+
+pub use self::red_queue::RedParams;
 
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 
+/// A queue that holds packets inbound to a host from the simulated network
+/// until the host is ready to receive them. Implementations may apply active
+/// queue management (AQM), dropping or marking packets to signal congestion.
+/// Queues must be `Send` since the host that owns the router must be `Send`.
+pub trait RouterQueue: Send {
+    /// Append a packet to the queue, or drop it if the queue does not accept
+    /// it. Requires the current time as an argument to avoid calling into the
+    /// worker module internally.
+    fn push(&mut self, packet: PacketRc, now: EmulatedTime);
+
+    /// Returns the next packet that should be forwarded to the host, or None
+    /// if the queue is empty. Packets may be dropped during this operation.
+    /// Requires the current time as an argument to avoid calling into the
+    /// worker module internally.
+    fn pop(&mut self, now: EmulatedTime) -> Option<PacketRc>;
+
+    /// Returns the total number of packets stored in the queue.
+    fn len(&self) -> usize;
+
+    /// Returns true if the queue is holding zero packets, false otherwise.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the packet and delay counters accumulated since the queue was
+    /// created.
+    fn stats(&self) -> RouterQueueStats;
+}
+
+/// The queueing discipline used by a router, along with its parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouterQueueConfig {
+    /// CoDel with an unlimited queue size.
+    CoDel,
+    /// A FIFO queue that drops arriving packets when it holds `limit` packets.
+    DropTail { limit: usize },
+    /// Random early detection, with a hard limit of `limit` packets.
+    Red { limit: usize, params: RedParams },
+    /// Flow-queue CoDel, with a limit of `limit` packets across all flows.
+    FqCoDel { limit: usize },
+}
+
+impl RouterQueueConfig {
+    /// Create a new empty queue. Randomness used by the queue is seeded from
+    /// `rng`, which is only drawn from if the queue needs it.
+    fn new_queue(&self, rng: &mut impl Rng) -> Box<dyn RouterQueue> {
+        match *self {
+            Self::CoDel => Box::new(CoDelQueue::new()),
+            Self::DropTail { limit } => Box::new(DropTailQueue::new(limit)),
+            Self::Red { limit, params } => {
+                let rng = Xoshiro256PlusPlus::seed_from_u64(rng.gen());
+                Box::new(RedQueue::new(limit, params, rng))
+            }
+            Self::FqCoDel { limit } => Box::new(FqCoDelQueue::new(limit)),
+        }
+    }
+}
+
+/// Packet and delay counters for a router queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouterQueueStats {
+    /// The number of packets that arrived at the queue.
+    pub arrived: u64,
+    /// The number of packets that were forwarded to the host.
+    pub forwarded: u64,
+    /// The number of packets that were dropped, either because the queue was
+    /// full or to signal congestion.
+    pub dropped: u64,
+    /// The number of packets that were marked with the ECN "congestion
+    /// experienced" codepoint instead of being dropped.
+    pub marked: u64,
+    /// The sum of the queueing delays of all forwarded packets.
+    pub total_delay: SimulationTime,
+    /// The largest queueing delay of any forwarded packet.
+    pub max_delay: SimulationTime,
+}
+
+impl RouterQueueStats {
+    pub fn new() -> Self {
+        Self {
+            arrived: 0,
+            forwarded: 0,
+            dropped: 0,
+            marked: 0,
+            total_delay: SimulationTime::ZERO,
+            max_delay: SimulationTime::ZERO,
+        }
+    }
+
+    /// The mean queueing delay of the forwarded packets, or None if no
+    /// packets were forwarded.
+    pub fn mean_delay(&self) -> Option<SimulationTime> {
+        self.total_delay.checked_div(self.forwarded)
+    }
+
+    fn record_forwarded(&mut self, delay: SimulationTime) {
+        self.forwarded += 1;
+        self.total_delay = self.total_delay.saturating_add(delay);
+        self.max_delay = std::cmp::max(self.max_delay, delay);
+    }
+
+    /// Add the counters of `other` to our counters.
+    fn add(&mut self, other: &Self) {
+        self.arrived += other.arrived;
+        self.forwarded += other.forwarded;
+        self.dropped += other.dropped;
+        self.marked += other.marked;
+        self.total_delay = self.total_delay.saturating_add(other.total_delay);
+        self.max_delay = std::cmp::max(self.max_delay, other.max_delay);
+    }
+}
+
+impl Default for RouterQueueStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for RouterQueueStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "arrived={}, forwarded={}, dropped={}, marked={}, mean_delay={:?}, max_delay={:?}",
+            self.arrived,
+            self.forwarded,
+            self.dropped,
+            self.marked,
+            std::time::Duration::from(self.mean_delay().unwrap_or(SimulationTime::ZERO)),
+            std::time::Duration::from(self.max_delay),
+        )
+    }
+}
+
+/// Mark the packet as dropped by the router.
+fn drop_packet(mut packet: PacketRc) {
+    packet.add_status(PacketStatus::RouterDropped);
+}
+
+/// Signal congestion to an ECN-capable packet by marking it as "congestion
+/// experienced" instead of dropping it. Returns false if the packet isn't
+/// ECN-capable and must be dropped instead.
+fn mark_packet(packet: &mut PacketRc) -> bool {
+    match packet.ecn() {
+        c::ProtocolECN_PECN_ECT0 | c::ProtocolECN_PECN_ECT1 | c::ProtocolECN_PECN_CE => {
+            packet.set_ecn(c::ProtocolECN_PECN_CE);
+            true
+        }
+        _ => false,
+    }
+}
+
 /// A router assists with moving packets between hosts across the simulated
 /// network.
 pub struct Router {
@@ -18,19 +181,20 @@ pub struct Router {
     _counter: ObjectCounter,
     address: Ipv4Addr,
     /// Packets inbound to the host from the simulated network.
-    inbound_packets: RefCell<CoDelQueue>,
+    inbound_packets: RefCell<Box<dyn RouterQueue>>,
 }
 
 impl Router {
     /// Create a new router for a host that will help route packets between it
     /// and other hosts. The `address` must uniquely identify this router to the
-    /// host that owns it.
-    pub fn new(address: Ipv4Addr) -> Router {
+    /// host that owns it. Inbound packets are queued using the discipline in
+    /// `queue_config`, which may draw from `rng` to seed its own randomness.
+    pub fn new(address: Ipv4Addr, queue_config: &RouterQueueConfig, rng: &mut impl Rng) -> Router {
         Router {
             magic: Magic::new(),
             address,
             _counter: ObjectCounter::new("Router"),
-            inbound_packets: RefCell::new(CoDelQueue::new()),
+            inbound_packets: RefCell::new(queue_config.new_queue(rng)),
         }
     }
 
@@ -54,11 +218,17 @@ impl Router {
         unsafe { c::packet_unref(cpacket) };
     }
 
-    /// Routes the packet from the virtual internet into our CoDel queue, which
-    /// can then be received by the destiantion host by calling pop().
+    /// Routes the packet from the virtual internet into our inbound queue,
+    /// which can then be received by the destiantion host by calling pop().
     pub fn route_incoming_packet(&self, packet: PacketRc) {
         self.push_inner(packet, Worker::current_time().unwrap())
     }
+
+    /// The packet and delay counters of our inbound queue.
+    pub fn queue_stats(&self) -> RouterQueueStats {
+        self.magic.debug_check();
+        self.inbound_packets.borrow().stats()
+    }
 }
 
 impl PacketDevice for Router {
@@ -67,7 +237,7 @@ impl PacketDevice for Router {
     }
 
     fn pop(&self) -> Option<PacketRc> {
-        // When the host calls pop, we provide the next packet from the CoDel queue.
+        // When the host calls pop, we provide the next packet from the inbound queue.
         self.pop_inner(Worker::current_time().unwrap())
     }
 
@@ -82,11 +252,16 @@ mod tests {
     use super::*;
     use crate::network::tests::mock_time_millis;
 
+    fn new_router(queue_config: &RouterQueueConfig) -> Router {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+        Router::new(Ipv4Addr::UNSPECIFIED, queue_config, &mut rng)
+    }
+
     #[test]
     fn empty() {
         let now = mock_time_millis(1000);
-        let router = Router::new(Ipv4Addr::UNSPECIFIED);
-        assert!(router.inbound_packets.borrow().peek().is_none());
+        let router = new_router(&RouterQueueConfig::CoDel);
+        assert!(router.inbound_packets.borrow().is_empty());
         assert!(router.pop_inner(now).is_none());
     }
 
@@ -95,20 +270,57 @@ mod tests {
     #[cfg_attr(miri, ignore)]
     fn push_pop_simple() {
         let now = mock_time_millis(1000);
-        let router = Router::new(Ipv4Addr::UNSPECIFIED);
 
         const N: usize = 10;
 
-        for _ in 1..=N {
-            router.push_inner(PacketRc::mock_new(), now);
-            assert!(router.inbound_packets.borrow().peek().is_some());
-        }
-        for _ in 1..=N {
-            assert!(router.inbound_packets.borrow().peek().is_some());
-            assert!(router.pop_inner(now).is_some());
+        let configs = [
+            RouterQueueConfig::CoDel,
+            RouterQueueConfig::DropTail { limit: N },
+            RouterQueueConfig::Red {
+                limit: N,
+                params: RedParams::default(),
+            },
+            RouterQueueConfig::FqCoDel { limit: N },
+        ];
+
+        for config in &configs {
+            let router = new_router(config);
+
+            for _ in 1..=N {
+                router.push_inner(PacketRc::mock_new(), now);
+                assert!(!router.inbound_packets.borrow().is_empty());
+            }
+            for _ in 1..=N {
+                assert!(!router.inbound_packets.borrow().is_empty());
+                assert!(router.pop_inner(now).is_some());
+            }
+
+            assert!(router.inbound_packets.borrow().is_empty());
+            assert!(router.pop_inner(now).is_none());
+
+            let stats = router.queue_stats();
+            assert_eq!(stats.arrived, N as u64);
+            assert_eq!(stats.forwarded, N as u64);
+            assert_eq!(stats.dropped, 0);
         }
+    }
 
-        assert!(router.inbound_packets.borrow().peek().is_none());
-        assert!(router.pop_inner(now).is_none());
+    #[test]
+    fn stats_delay() {
+        let mut stats = RouterQueueStats::new();
+        assert_eq!(stats.mean_delay(), None);
+
+        stats.record_forwarded(SimulationTime::from_millis(10));
+        stats.record_forwarded(SimulationTime::from_millis(30));
+        assert_eq!(stats.forwarded, 2);
+        assert_eq!(stats.mean_delay(), Some(SimulationTime::from_millis(20)));
+        assert_eq!(stats.max_delay, SimulationTime::from_millis(30));
+
+        let mut total = RouterQueueStats::new();
+        total.add(&stats);
+        total.add(&stats);
+        assert_eq!(total.forwarded, 4);
+        assert_eq!(total.mean_delay(), Some(SimulationTime::from_millis(20)));
+        assert_eq!(total.max_delay, SimulationTime::from_millis(30));
     }
 }
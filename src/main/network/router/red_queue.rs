@@ -0,0 +1,255 @@
+//! An active queue management (AQM) algorithm implementing random early
+//! detection (RED), in packet mode.
+//! <https://www.icir.org/floyd/papers/red/red.html>
+//!
+//!  Packets are dropped (or marked, if they are ECN-capable) with a probability
+//!  that grows with the average queue length, so that senders reduce their
+//!  rates before the queue fills.
+//!
+//!  The "gentle" variant is not implemented, and the average queue length is
+//!  not decayed for the time that the queue was idle since the queue does not
+//!  know the transmission rate of the link.
+//!
+//!  More info:
+//!   - <https://en.wikipedia.org/wiki/Random_early_detection>
+//!   - <http://man7.org/linux/man-pages/man8/tc-red.8.html>
+
+use rand::Rng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+use super::drop_tail_queue::DropTailQueue;
+use super::{mark_packet, RouterQueue, RouterQueueStats};
+use crate::network::packet::PacketRc;
+
+/// The weight of the current queue length in the exponentially weighted
+/// moving average of the queue length, corresponding to the "w_q" parameter
+/// in the paper.
+const WEIGHT: f64 = 0.002;
+
+/// The tunable parameters of the RED algorithm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RedParams {
+    /// The average queue length (in packets) at which we start dropping
+    /// packets.
+    pub min_threshold: u32,
+    /// The average queue length (in packets) at which we drop every packet.
+    pub max_threshold: u32,
+    /// The drop probability when the average queue length reaches
+    /// `max_threshold`.
+    pub max_probability: f64,
+}
+
+impl Default for RedParams {
+    fn default() -> Self {
+        // The values recommended in the paper.
+        Self {
+            min_threshold: 5,
+            max_threshold: 15,
+            max_probability: 0.1,
+        }
+    }
+}
+
+/// A packet queue implementing the RED active queue management (AQM)
+/// algorithm, which drops arriving packets early to signal congestion. Packets
+/// that arrive while the queue is full are always dropped.
+pub struct RedQueue {
+    /// The queue holding packets that weren't dropped.
+    fifo: DropTailQueue,
+    params: RedParams,
+    /// The average queue length.
+    average: f64,
+    /// The number of packets that arrived since the last dropped packet, or -1
+    /// if the average queue length was below the minimum threshold.
+    count: i64,
+    /// The number of packets that were marked instead of dropped.
+    marked: u64,
+    rng: Xoshiro256PlusPlus,
+}
+
+impl RedQueue {
+    /// Creates a new empty packet queue that can hold up to `limit` packets.
+    pub fn new(limit: usize, params: RedParams, rng: Xoshiro256PlusPlus) -> RedQueue {
+        debug_assert!(params.min_threshold < params.max_threshold);
+        RedQueue {
+            fifo: DropTailQueue::new(limit),
+            params,
+            average: 0.0,
+            count: -1,
+            marked: 0,
+            rng,
+        }
+    }
+
+    /// Update the average queue length based on the current queue length.
+    fn update_average(&mut self) {
+        let len = self.fifo.len() as f64;
+        self.average = (1.0 - WEIGHT) * self.average + WEIGHT * len;
+    }
+
+    /// Returns true if the arriving packet should be dropped (or marked) to
+    /// signal congestion.
+    fn should_drop(&mut self) -> bool {
+        let min = f64::from(self.params.min_threshold);
+        let max = f64::from(self.params.max_threshold);
+
+        if self.average < min {
+            self.count = -1;
+            return false;
+        }
+
+        if self.average >= max {
+            self.count = 0;
+            return true;
+        }
+
+        // Spread the drops out evenly by increasing the drop probability with
+        // the number of packets since the last drop.
+        self.count += 1;
+        let prob = self.params.max_probability * (self.average - min) / (max - min);
+        let prob = match self.count as f64 * prob {
+            x if x >= 1.0 => 1.0,
+            x => prob / (1.0 - x),
+        };
+
+        if self.rng.gen::<f64>() < prob {
+            self.count = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl RouterQueue for RedQueue {
+    fn push(&mut self, mut packet: PacketRc, now: EmulatedTime) {
+        self.update_average();
+
+        // The drop-tail queue drops the packet if it's full.
+        if !self.fifo.is_full() && self.should_drop() {
+            if !mark_packet(&mut packet) {
+                self.fifo.drop_arriving(packet);
+                return;
+            }
+            self.marked += 1;
+        }
+
+        self.fifo.push(packet, now);
+    }
+
+    fn pop(&mut self, now: EmulatedTime) -> Option<PacketRc> {
+        self.fifo.pop(now)
+    }
+
+    fn len(&self) -> usize {
+        self.fifo.len()
+    }
+
+    fn stats(&self) -> RouterQueueStats {
+        let mut stats = self.fifo.stats();
+        stats.marked += self.marked;
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::cshadow as c;
+    use crate::network::tests::mock_time_millis;
+
+    // Some of the tests here don't run in miri because they cause c::packet*
+    // functions to be called during the test.
+
+    const PARAMS: RedParams = RedParams {
+        min_threshold: 1,
+        max_threshold: 2,
+        max_probability: 0.1,
+    };
+
+    fn new_queue(limit: usize) -> RedQueue {
+        RedQueue::new(limit, PARAMS, Xoshiro256PlusPlus::seed_from_u64(0))
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn no_drops_below_min_threshold() {
+        let now = mock_time_millis(1000);
+        let mut queue = new_queue(100);
+
+        // Packets leave the queue as fast as they arrive.
+        for _ in 0..1000 {
+            queue.push(PacketRc::mock_new(), now);
+            assert!(queue.pop(now).is_some());
+        }
+
+        assert_eq!(queue.average, 0.0);
+        assert_eq!(queue.stats().dropped, 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn drop_above_max_threshold() {
+        let now = mock_time_millis(1000);
+        let mut queue = new_queue(10000);
+
+        const N: usize = 2000;
+        for _ in 0..N {
+            queue.push(PacketRc::mock_new(), now);
+        }
+
+        // The queue stops growing once the average exceeds the maximum threshold.
+        assert!(queue.average >= f64::from(PARAMS.max_threshold));
+        let len = queue.len();
+        for _ in 0..100 {
+            queue.push(PacketRc::mock_new(), now);
+        }
+        assert_eq!(queue.len(), len);
+
+        let stats = queue.stats();
+        assert_eq!(stats.arrived, N as u64 + 100);
+        assert_eq!(stats.dropped, (N + 100 - len) as u64);
+        assert_eq!(stats.marked, 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn mark_ecn_capable() {
+        let now = mock_time_millis(1000);
+        let mut queue = new_queue(10000);
+
+        const N: usize = 2000;
+        for _ in 0..N {
+            let mut packet = PacketRc::mock_new();
+            packet.set_ecn(c::ProtocolECN_PECN_ECT0);
+            queue.push(packet, now);
+        }
+
+        assert_eq!(queue.len(), N);
+
+        let stats = queue.stats();
+        assert_eq!(stats.dropped, 0);
+        assert!(stats.marked > 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn drop_when_full() {
+        let now = mock_time_millis(1000);
+        let mut queue = RedQueue::new(
+            5,
+            RedParams::default(),
+            Xoshiro256PlusPlus::seed_from_u64(0),
+        );
+
+        for _ in 0..10 {
+            queue.push(PacketRc::mock_new(), now);
+        }
+
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.stats().dropped, 5);
+    }
+}
@@ -4,6 +4,7 @@
  * See LICENSE for licensing information
  */
 
+#include <arpa/inet.h>
 #include <errno.h>
 #include <glib.h>
 #include <netinet/in.h>
@@ -30,6 +31,8 @@ struct _DNS {
     /* address mappings */
     GHashTable* addressByIP;
     GHashTable* addressByName;
+    /* IPv6 address strings of hosts that have one, keyed by host name */
+    GHashTable* ipv6ByName;
 
     int hosts_file_fd;
 
@@ -165,6 +168,7 @@ void dns_deregister(DNS* dns, Address* address) {
         /* these remove functions will call address_unref as necessary */
         g_hash_table_remove(dns->addressByIP, GUINT_TO_POINTER(address_toNetworkIP(address)));
         g_hash_table_remove(dns->addressByName, address_toHostName(address));
+        g_hash_table_remove(dns->ipv6ByName, address_toHostName(address));
 
         /* Any existing hosts file needs to be (lazily) updated. */
         if (dns->hosts_file_fd >= 0) {
@@ -176,6 +180,29 @@ void dns_deregister(DNS* dns, Address* address) {
     }
 }
 
+void dns_registerIPv6(DNS* dns, const gchar* name, const struct in6_addr* ip) {
+    MAGIC_ASSERT(dns);
+    utility_debugAssert(name);
+    utility_debugAssert(ip);
+
+    gchar ipStr[INET6_ADDRSTRLEN] = {0};
+    if (inet_ntop(AF_INET6, ip, ipStr, sizeof(ipStr)) == NULL) {
+        utility_panic("Unable to convert IPv6 address to a string: %s", strerror(errno));
+    }
+
+    g_mutex_lock(&dns->lock);
+
+    g_hash_table_replace(dns->ipv6ByName, g_strdup(name), g_strdup(ipStr));
+
+    /* Any existing hosts file needs to be (lazily) updated. */
+    if (dns->hosts_file_fd >= 0) {
+        close(dns->hosts_file_fd);
+        dns->hosts_file_fd = -1;
+    }
+
+    g_mutex_unlock(&dns->lock);
+}
+
 /* Address must be in network byte order. */
 Address* dns_resolveIPToAddress(DNS* dns, in_addr_t ip) {
     MAGIC_ASSERT(dns);
@@ -204,6 +231,13 @@ static void _dns_writeHostLine(gpointer key, gpointer value, gpointer data) {
     g_string_append_printf(buf, "%s %s\n", address_toHostIPString(address), name);
 }
 
+static void _dns_writeHostLineIPv6(gpointer key, gpointer value, gpointer data) {
+    const gchar* name = key;
+    const gchar* ipStr = value;
+    GString* buf = data;
+    g_string_append_printf(buf, "%s %s\n", ipStr, name);
+}
+
 static bool _dns_writeNewHostsFile(DNS* dns) {
     MAGIC_ASSERT(dns);
     utility_debugAssert(dns->hosts_file_fd < 0);
@@ -215,8 +249,9 @@ static bool _dns_writeNewHostsFile(DNS* dns) {
         return false;
     }
 
-    GString* buf = g_string_new("127.0.0.1 localhost\n");
+    GString* buf = g_string_new("127.0.0.1 localhost\n::1 localhost\n");
     g_hash_table_foreach(dns->addressByName, _dns_writeHostLine, buf);
+    g_hash_table_foreach(dns->ipv6ByName, _dns_writeHostLineIPv6, buf);
 
     trace("Hosts file string buffer is %zu bytes.", buf->len);
 
@@ -273,6 +308,7 @@ DNS* dns_new() {
 
     dns->addressByIP = g_hash_table_new_full(g_direct_hash, g_direct_equal, NULL, (GDestroyNotify) address_unref);
     dns->addressByName = g_hash_table_new_full(g_str_hash, g_str_equal, NULL, (GDestroyNotify) address_unref);
+    dns->ipv6ByName = g_hash_table_new_full(g_str_hash, g_str_equal, g_free, g_free);
 
     /* 11.0.0.0 -- 100.0.0.0 is the longest available unrestricted range */
     dns->ipAddressCounter = ntohl(address_stringToIP("11.0.0.0"));
@@ -292,6 +328,7 @@ void dns_free(DNS* dns) {
 
     g_hash_table_destroy(dns->addressByIP);
     g_hash_table_destroy(dns->addressByName);
+    g_hash_table_destroy(dns->ipv6ByName);
 
     g_mutex_clear(&(dns->lock));
 
@@ -22,6 +22,10 @@ void dns_free(DNS* dns);
 Address* dns_register(DNS* dns, HostId id, const gchar* name, in_addr_t requestedIP);
 void dns_deregister(DNS* dns, Address* address);
 
+/* Adds an IPv6 address for the already-registered host `name`, to be included
+ * in the hosts file. The entry is removed when the host is deregistered. */
+void dns_registerIPv6(DNS* dns, const gchar* name, const struct in6_addr* ip);
+
 /* Address must be in network byte order. */
 Address* dns_resolveIPToAddress(DNS* dns, in_addr_t ip);
 Address* dns_resolveNameToAddress(DNS* dns, const gchar* name);
@@ -6,6 +6,7 @@
 
 #include <assert.h>
 #include <netinet/in.h>
+#include <netinet/ip.h>
 #include <stddef.h>
 #include <stdint.h>
 
@@ -81,6 +82,13 @@ struct _Packet {
      */
     uint64_t priority;
 
+    /* the IP type of service byte; the low two bits are the ECN codepoint */
+    guint8 tos;
+    /* the IP time-to-live, decremented by each router hop the packet crosses */
+    guint8 ttl;
+    /* shadow only routes IPv4 packets; this marks packets that were sent as IPv6 packets */
+    gboolean isIPv6;
+
     PacketDeliveryStatusFlags allStatus;
     GQueue* orderedStatus;
 
@@ -93,6 +101,7 @@ const gchar* protocol_toString(ProtocolType type) {
         case PUDP: return "UDP";
         case PTCP: return "TCP";
         case PMOCK: return "MOCK";
+        case PICMP: return "ICMP";
         default: return "UNKNOWN";
     }
 }
@@ -106,6 +115,7 @@ Packet* packet_new_inner(guint hostID, guint64 packetID) {
 
     packet->hostID = hostID;
     packet->packetID = packetID;
+    packet->ttl = IPDEFTTL;
 
     packet->orderedStatus = g_queue_new();
 
@@ -166,6 +176,36 @@ void packet_setPayloadFromShadow(Packet* packet, const void* payload, gsize payl
     packet->priority = packetPriority;
 }
 
+/* Append `payloadLength` bytes of plugin memory to the packet's existing payload. The packet must
+ * not have been sent yet, since the payload may otherwise be shared with other packets. */
+void packet_appendPayloadWithMemoryManager(Packet* packet, UntypedForeignPtr payload,
+                                           gsize payloadLength, const MemoryManager* mem) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(payload.val);
+    utility_debugAssert(packet->payload);
+
+    Payload* tail = payload_newWithMemoryManager(payload, payloadLength, mem);
+    utility_alwaysAssert(tail != NULL);
+
+    gsize headLength = payload_getLength(packet->payload);
+    gsize totalLength = headLength + payloadLength;
+    guint8* buffer = g_malloc(totalLength);
+
+    gsize copied = payload_getDataShadow(packet->payload, 0, buffer, headLength);
+    utility_debugAssert(copied == headLength);
+    copied = payload_getDataShadow(tail, 0, buffer + headLength, payloadLength);
+    utility_debugAssert(copied == payloadLength);
+
+    payload_unref(tail);
+    payload_unref(packet->payload);
+
+    /* the payload starts with 1 ref, which we hold */
+    packet->payload = payload_newFromShadow(buffer, totalLength);
+    utility_alwaysAssert(packet->payload != NULL);
+
+    g_free(buffer);
+}
+
 /* copy everything except the payload.
  * the payload will point to the same payload as the original packet.
  * the payload is protected so it is safe to send the copied packet to a different host. */
@@ -186,6 +226,9 @@ Packet* packet_copy(Packet* packet) {
         copy->priority = packet->priority;
     }
 
+    copy->tos = packet->tos;
+    copy->ttl = packet->ttl;
+    copy->isIPv6 = packet->isIPv6;
     copy->allStatus = packet->allStatus;
 
     if(packet->orderedStatus) {
@@ -206,6 +249,11 @@ Packet* packet_copy(Packet* packet) {
                 break;
             }
 
+            case PICMP: {
+                copy->header = compat_static_g_memdup(packet->header, sizeof(PacketICMPHeader));
+                break;
+            }
+
             case PTCP: {
                 copy->header = compat_static_g_memdup(packet->header, sizeof(PacketTCPHeader));
 
@@ -276,6 +324,46 @@ void packet_setPriority(Packet *packet, uint64_t value) {
    packet->priority = value;
 }
 
+void packet_setTOS(Packet* packet, guint8 tos) {
+    MAGIC_ASSERT(packet);
+    packet->tos = tos;
+}
+
+guint8 packet_getTOS(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+    return packet->tos;
+}
+
+void packet_setECN(Packet* packet, enum ProtocolECN ecn) {
+    MAGIC_ASSERT(packet);
+    packet->tos = (packet->tos & ~IPTOS_ECN_MASK) | (ecn & IPTOS_ECN_MASK);
+}
+
+enum ProtocolECN packet_getECN(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+    return packet->tos & IPTOS_ECN_MASK;
+}
+
+void packet_setTTL(Packet* packet, guint8 ttl) {
+    MAGIC_ASSERT(packet);
+    packet->ttl = ttl;
+}
+
+guint8 packet_getTTL(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+    return packet->ttl;
+}
+
+void packet_setIPv6(Packet* packet, gboolean isIPv6) {
+    MAGIC_ASSERT(packet);
+    packet->isIPv6 = isIPv6;
+}
+
+gboolean packet_isIPv6(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+    return packet->isIPv6;
+}
+
 gint packet_compareTCPSequence(Packet* packet1, Packet* packet2, gpointer user_data) {
     MAGIC_ASSERT(packet1);
     MAGIC_ASSERT(packet2);
@@ -337,6 +425,20 @@ void packet_setUDP(Packet* packet, enum ProtocolUDPFlags flags,
     packet->protocol = PUDP;
 }
 
+void packet_setUDPMulticast(Packet* packet, guint8 ttl, gboolean loop) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(packet->header && packet->protocol == PUDP);
+
+    PacketUDPHeader* header = packet->header;
+
+    packet->ttl = ttl;
+    if (loop) {
+        header->flags |= PUDP_MULTICAST_LOOP;
+    } else {
+        header->flags &= ~PUDP_MULTICAST_LOOP;
+    }
+}
+
 // The addresses and ports must be in network byte order.
 void packet_setTCP(Packet* packet, enum ProtocolTCPFlags flags,
         in_addr_t sourceIP, in_port_t sourcePort,
@@ -358,6 +460,82 @@ void packet_setTCP(Packet* packet, enum ProtocolTCPFlags flags,
     packet->protocol = PTCP;
 }
 
+// The addresses, identifier, and sequence number must be in network byte order.
+void packet_setICMPEcho(Packet* packet, enum ProtocolICMPType type, in_addr_t sourceIP,
+                        in_addr_t destinationIP, guint16 identifier, guint16 sequence) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(!(packet->header) && packet->protocol == PNONE);
+    utility_debugAssert(type == PICMP_ECHO_REQUEST || type == PICMP_ECHO_REPLY);
+    utility_debugAssert(sourceIP && destinationIP);
+
+    PacketICMPHeader* header = g_new0(PacketICMPHeader, 1);
+
+    header->type = type;
+    header->code = PICMP_CODE_NONE;
+    header->sourceIP = sourceIP;
+    header->destinationIP = destinationIP;
+    header->identifier = identifier;
+    header->sequence = sequence;
+    header->originalProtocol = PNONE;
+
+    packet->header = header;
+    packet->protocol = PICMP;
+}
+
+void packet_setICMPEchoReply(Packet* packet, const Packet* request) {
+    MAGIC_ASSERT(packet);
+    MAGIC_ASSERT(request);
+    utility_debugAssert(!packet->payload);
+    utility_debugAssert(request->protocol == PICMP);
+
+    PacketICMPHeader* requestHeader = request->header;
+    utility_debugAssert(requestHeader->type == PICMP_ECHO_REQUEST);
+
+    /* the reply goes back to the sender with the same identifier, sequence number, and data */
+    packet_setICMPEcho(packet, PICMP_ECHO_REPLY, requestHeader->destinationIP,
+                       requestHeader->sourceIP, requestHeader->identifier,
+                       requestHeader->sequence);
+
+    if (request->payload) {
+        packet->payload = request->payload;
+        payload_ref(request->payload);
+        packet->priority = request->priority;
+    }
+}
+
+/* Errors are sent from the original destination address, since the routers in the network graph
+ * don't have addresses of their own. */
+static void _packet_setICMPError(Packet* packet, enum ProtocolICMPType type,
+                                 enum ProtocolICMPCode code, const Packet* original) {
+    MAGIC_ASSERT(packet);
+    MAGIC_ASSERT(original);
+    utility_debugAssert(!(packet->header) && packet->protocol == PNONE);
+
+    PacketICMPHeader* header = g_new0(PacketICMPHeader, 1);
+
+    header->type = type;
+    header->code = code;
+    header->sourceIP = packet_getDestinationIP(original);
+    header->destinationIP = packet_getSourceIP(original);
+    header->originalProtocol = original->protocol;
+    header->originalSourcePort = packet_getSourcePort(original);
+    header->originalDestinationIP = packet_getDestinationIP(original);
+    header->originalDestinationPort = packet_getDestinationPort(original);
+
+    packet->header = header;
+    packet->protocol = PICMP;
+    packet->isIPv6 = original->isIPv6;
+}
+
+void packet_setICMPDestinationUnreachable(Packet* packet, enum ProtocolICMPCode code,
+                                          const Packet* original) {
+    _packet_setICMPError(packet, PICMP_DEST_UNREACH, code, original);
+}
+
+void packet_setICMPTimeExceeded(Packet* packet, const Packet* original) {
+    _packet_setICMPError(packet, PICMP_TIME_EXCEEDED, PICMP_CODE_TTL_EXCEEDED, original);
+}
+
 void packet_updateTCP(Packet* packet, guint acknowledgement, GList* selectiveACKs, guint window,
                       CSimulationTime timestampValue, CSimulationTime timestampEcho) {
     MAGIC_ASSERT(packet);
@@ -401,9 +579,10 @@ gsize packet_getPayloadSize(const Packet* packet) {
 
 gsize packet_getHeaderSize(const Packet* packet) {
     MAGIC_ASSERT(packet);
-    gsize size = packet->protocol == PUDP   ? CONFIG_HEADER_SIZE_UDPIP
-                 : packet->protocol == PTCP ? CONFIG_HEADER_SIZE_TCPIP
-                                            : 0;
+    gsize size = packet->protocol == PUDP    ? CONFIG_HEADER_SIZE_UDPIP
+                 : packet->protocol == PTCP  ? CONFIG_HEADER_SIZE_TCPIP
+                 : packet->protocol == PICMP ? CONFIG_HEADER_SIZE_ICMPIP
+                                             : 0;
     return size;
 }
 
@@ -435,6 +614,12 @@ in_addr_t packet_getDestinationIP(const Packet* packet) {
             break;
         }
 
+        case PICMP: {
+            PacketICMPHeader* header = packet->header;
+            ip = header->destinationIP;
+            break;
+        }
+
         default: {
             utility_panic("unrecognized protocol");
             break;
@@ -469,6 +654,14 @@ in_port_t packet_getDestinationPort(const Packet* packet) {
             break;
         }
 
+        case PICMP: {
+            /* ICMP has no ports, but echo messages are delivered to the ping socket bound to the
+             * identifier */
+            PacketICMPHeader* header = packet->header;
+            port = packet_isICMPError(packet) ? 0 : header->identifier;
+            break;
+        }
+
         default: {
             utility_panic("unrecognized protocol");
             break;
@@ -502,6 +695,12 @@ in_addr_t packet_getSourceIP(const Packet* packet) {
             break;
         }
 
+        case PICMP: {
+            PacketICMPHeader* header = packet->header;
+            ip = header->sourceIP;
+            break;
+        }
+
         default: {
             utility_panic("unrecognized protocol");
             break;
@@ -536,6 +735,12 @@ in_port_t packet_getSourcePort(const Packet* packet) {
             break;
         }
 
+        case PICMP: {
+            /* ICMP has no ports */
+            port = 0;
+            break;
+        }
+
         default: {
             utility_panic("unrecognized protocol");
             break;
@@ -550,6 +755,25 @@ ProtocolType packet_getProtocol(const Packet* packet) {
     return packet->protocol;
 }
 
+gboolean packet_isICMPError(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+
+    if (packet->protocol != PICMP) {
+        return FALSE;
+    }
+
+    const PacketICMPHeader* header = packet->header;
+    return header->type == PICMP_DEST_UNREACH || header->type == PICMP_TIME_EXCEEDED;
+}
+
+gboolean packet_getUDPMulticastLoop(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(packet->header && packet->protocol == PUDP);
+
+    const PacketUDPHeader* header = packet->header;
+    return (header->flags & PUDP_MULTICAST_LOOP) != 0;
+}
+
 /* If modifying this function, you should also modify `packet_copyPayloadWithMemoryManager` below.
  */
 gssize packet_copyPayload(const Packet* packet, const Thread* thread, gsize payloadOffset,
@@ -612,6 +836,12 @@ PacketTCPHeader* packet_getTCPHeader(const Packet* packet) {
     return (PacketTCPHeader*)packet->header;
 }
 
+PacketICMPHeader* packet_getICMPHeader(const Packet* packet) {
+    MAGIC_ASSERT(packet);
+    utility_debugAssert(packet->protocol == PICMP);
+    return (PacketICMPHeader*)packet->header;
+}
+
 static const gchar* _packet_deliveryStatusToAscii(PacketDeliveryStatusFlags status) {
     switch (status) {
         case PDS_NONE: return "NONE";
@@ -637,6 +867,8 @@ static const gchar* _packet_deliveryStatusToAscii(PacketDeliveryStatusFlags stat
         case PDS_DESTROYED: return "PDS_DESTROYED";
         case PDS_RELAY_CACHED: return "RELAY_CACHED";
         case PDS_RELAY_FORWARDED: return "RELAY_FORWARDED";
+        case PDS_INET_REORDERED: return "INET_REORDERED";
+        case PDS_INET_DUPLICATED: return "INET_DUPLICATED";
         default: return "UKNOWN";
     }
 }
@@ -730,6 +962,12 @@ gchar* packet_toString(Packet* packet) {
                 if(header->flags & PTCP_DUPACK) {
                     g_string_append_printf(packetString, "DUPACK");
                 }
+                if(header->flags & PTCP_ECE) {
+                    g_string_append_printf(packetString, "ECE");
+                }
+                if(header->flags & PTCP_CWR) {
+                    g_string_append_printf(packetString, "CWR");
+                }
             }
 
             g_string_append_printf(packetString, " tsval=%"G_GUINT64_FORMAT" tsechoreply=%"G_GUINT64_FORMAT,
@@ -740,6 +978,33 @@ gchar* packet_toString(Packet* packet) {
             break;
         }
 
+        case PICMP: {
+            PacketICMPHeader* header = packet->header;
+            gchar* sourceIPString = address_ipToNewString(header->sourceIP);
+            gchar* destinationIPString = address_ipToNewString(header->destinationIP);
+
+            g_string_append_printf(packetString, "%s -> %s type=%u code=%u", sourceIPString,
+                                   destinationIPString, header->type, header->code);
+
+            if (packet_isICMPError(packet)) {
+                gchar* originalIPString = address_ipToNewString(header->originalDestinationIP);
+                g_string_append_printf(packetString, " original=%s:%u->%s:%u",
+                                       protocol_toString(header->originalProtocol),
+                                       ntohs(header->originalSourcePort), originalIPString,
+                                       ntohs(header->originalDestinationPort));
+                g_free(originalIPString);
+            } else {
+                g_string_append_printf(packetString, " id=%u seq=%u", ntohs(header->identifier),
+                                       ntohs(header->sequence));
+            }
+
+            g_string_append_printf(packetString, " bytes=%u", payloadLength);
+
+            g_free(sourceIPString);
+            g_free(destinationIPString);
+            break;
+        }
+
         case PMOCK: {
             // TODO: We should panic here if this isn't a test.  We don't have a
             // good way to check whether this is being run inside a test in C.
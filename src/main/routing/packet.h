@@ -39,6 +39,28 @@ struct _PacketTCPHeader {
     CSimulationTime timestampEcho;
 };
 
+typedef struct _PacketICMPHeader PacketICMPHeader;
+struct _PacketICMPHeader {
+    enum ProtocolICMPType type;
+    enum ProtocolICMPCode code;
+
+    // address is in network byte order
+    in_addr_t sourceIP;
+    // address is in network byte order
+    in_addr_t destinationIP;
+
+    // for echo messages; in network byte order
+    guint16 identifier;
+    guint16 sequence;
+
+    // for error messages, the packet that caused the error; addresses and ports are in network
+    // byte order
+    ProtocolType originalProtocol;
+    in_port_t originalSourcePort;
+    in_addr_t originalDestinationIP;
+    in_port_t originalDestinationPort;
+};
+
 const gchar* protocol_toString(ProtocolType type);
 
 Packet* packet_new(const Host* host);
@@ -49,6 +71,8 @@ void packet_setPayloadWithMemoryManager(Packet* packet, UntypedForeignPtr payloa
                                         uint64_t packetPriority);
 void packet_setPayloadFromShadow(Packet* packet, const void* payload, gsize payloadLength,
                                  uint64_t packetPriority);
+void packet_appendPayloadWithMemoryManager(Packet* packet, UntypedForeignPtr payload,
+                                           gsize payloadLength, const MemoryManager* mem);
 Packet* packet_copy(Packet* packet);
 
 // Exposed for unit testing only. Use `packet_new` outside of tests.
@@ -63,6 +87,23 @@ static inline void packet_unrefTaskFreeFunc(gpointer packet) { packet_unref(pack
 void packet_setPriority(Packet *packet, uint64_t value);
 uint64_t packet_getPriority(const Packet* packet);
 
+// The IP type of service byte, including the ECN codepoint.
+void packet_setTOS(Packet* packet, guint8 tos);
+guint8 packet_getTOS(const Packet* packet);
+
+// Set or get only the ECN codepoint of the type of service byte.
+void packet_setECN(Packet* packet, enum ProtocolECN ecn);
+enum ProtocolECN packet_getECN(const Packet* packet);
+
+// The IP time-to-live, which is decremented for each router hop the packet crosses.
+void packet_setTTL(Packet* packet, guint8 ttl);
+guint8 packet_getTTL(const Packet* packet);
+
+// Whether the packet was sent as an IPv6 packet. Shadow only routes IPv4 packets, so the addresses
+// of IPv6 packets are the IPv4 addresses of the hosts.
+void packet_setIPv6(Packet* packet, gboolean isIPv6);
+gboolean packet_isIPv6(const Packet* packet);
+
 // The port must be in network byte order.
 void packet_setLocal(Packet* packet, enum ProtocolLocalFlags flags,
         gint sourceDescriptorHandle, gint destinationDescriptorHandle, in_port_t port);
@@ -72,11 +113,29 @@ void packet_setUDP(Packet* packet, enum ProtocolUDPFlags flags,
         in_addr_t sourceIP, in_port_t sourcePort,
         in_addr_t destinationIP, in_port_t destinationPort);
 
+// Set the time-to-live of a multicast UDP packet, and whether it should also be delivered to the
+// sending host.
+void packet_setUDPMulticast(Packet* packet, guint8 ttl, gboolean loop);
+
 // The addresses and ports must be in network byte order.
 void packet_setTCP(Packet* packet, enum ProtocolTCPFlags flags,
         in_addr_t sourceIP, in_port_t sourcePort,
         in_addr_t destinationIP, in_port_t destinationPort, guint sequence);
 
+// The addresses, identifier, and sequence number must be in network byte order.
+void packet_setICMPEcho(Packet* packet, enum ProtocolICMPType type, in_addr_t sourceIP,
+                        in_addr_t destinationIP, guint16 identifier, guint16 sequence);
+
+// Make the packet an echo reply to `request`. The reply shares the request's payload.
+void packet_setICMPEchoReply(Packet* packet, const Packet* request);
+
+// Make the packet a destination unreachable error for `original`, sent back to its source.
+void packet_setICMPDestinationUnreachable(Packet* packet, enum ProtocolICMPCode code,
+                                          const Packet* original);
+
+// Make the packet a time exceeded error for `original`, sent back to its source.
+void packet_setICMPTimeExceeded(Packet* packet, const Packet* original);
+
 void packet_updateTCP(Packet* packet, guint acknowledgement, GList* selectiveACKs, guint window,
                       CSimulationTime timestampValue, CSimulationTime timestampEcho);
 
@@ -96,6 +155,12 @@ in_port_t packet_getSourcePort(const Packet* packet);
 
 ProtocolType packet_getProtocol(const Packet* packet);
 
+// Returns true if the packet is an ICMP error message (destination unreachable or time exceeded).
+gboolean packet_isICMPError(const Packet* packet);
+
+// Returns true if a multicast UDP packet should also be delivered to the sending host.
+gboolean packet_getUDPMulticastLoop(const Packet* packet);
+
 gssize packet_copyPayload(const Packet* packet, const Thread* thread, gsize payloadOffset,
                           UntypedForeignPtr buffer, gsize bufferLength);
 gssize packet_copyPayloadWithMemoryManager(const Packet* packet, gsize payloadOffset,
@@ -105,6 +170,7 @@ guint packet_copyPayloadShadow(const Packet* packet, gsize payloadOffset, void*
                                gsize bufferLength);
 GList* packet_copyTCPSelectiveACKs(Packet* packet);
 PacketTCPHeader* packet_getTCPHeader(const Packet* packet);
+PacketICMPHeader* packet_getICMPHeader(const Packet* packet);
 gint packet_compareTCPSequence(Packet* packet1, Packet* packet2, gpointer user_data);
 
 void packet_addDeliveryStatus(Packet* packet, PacketDeliveryStatusFlags status);
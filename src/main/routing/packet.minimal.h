@@ -34,6 +34,8 @@ enum _PacketDeliveryStatusFlags {
     PDS_DESTROYED = 1 << 20,
     PDS_RELAY_CACHED = 1 << 21,
     PDS_RELAY_FORWARDED = 1 << 22,
+    PDS_INET_REORDERED = 1 << 23,
+    PDS_INET_DUPLICATED = 1 << 24,
 };
 
 typedef struct _PacketTCPHeader PacketTCPHeader;
@@ -1,6 +1,7 @@
 use std::borrow::Borrow;
 use std::borrow::BorrowMut;
-use std::ffi::CStr;
+use std::borrow::Cow;
+use std::ffi::{CStr, CString};
 use std::mem::MaybeUninit;
 
 use nix::sys::socket::AddressFamily;
@@ -296,8 +297,8 @@ where
     }
 
     /// If the socket address represents a pathname address, returns the C string representing the
-    /// filesystem path.
-    pub fn as_path(&self) -> Option<&CStr> {
+    /// filesystem path. The path is copied if it isn't nul-terminated.
+    pub fn as_path(&self) -> Option<Cow<CStr>> {
         let path = self.sun_path()?;
 
         // if the address length is too short, or it's an abstract named address
@@ -306,8 +307,12 @@ where
         }
 
         // For pathname socket addresses, the path is a C-style nul-terminated string which may be
-        // shorter than the address length (`self.len`). Bytes after the nul are ignored.
-        CStr::from_bytes_until_nul(path).ok()
+        // shorter than the address length (`self.len`). Bytes after the nul are ignored. Like
+        // linux, we also accept a path without a nul if it fills the address length.
+        match CStr::from_bytes_until_nul(path) {
+            Ok(path) => Some(Cow::Borrowed(path)),
+            Err(_) => Some(Cow::Owned(CString::new(path).unwrap())),
+        }
     }
 
     /// If the socket address represents an abstract address, returns the bytes representing the
@@ -615,7 +620,7 @@ mod tests {
         assert!(addr.as_abstract().is_none());
         assert!(!addr.is_unnamed());
 
-        assert_eq!(addr.as_path().unwrap(), pathname_cstr);
+        assert_eq!(addr.as_path().unwrap().as_ref(), pathname_cstr);
 
         let addr = unsafe { SockaddrStorage::from_ptr(ptr, len_struct) }.unwrap();
 
@@ -627,7 +632,32 @@ mod tests {
         assert!(addr.as_abstract().is_none());
         assert!(!addr.is_unnamed());
 
-        assert_eq!(addr.as_path().unwrap(), pathname_cstr);
+        assert_eq!(addr.as_path().unwrap().as_ref(), pathname_cstr);
+    }
+
+    /// Convert from a pathname `sockaddr_un` without a nul terminator to a `SockaddrUnix`.
+    #[test]
+    fn unix_addr_from_libc_to_path_without_nul() {
+        let pathname = [1, 2, 3];
+        let pathname_cstr = CStr::from_bytes_with_nul(&[1, 2, 3, 0]).unwrap();
+
+        let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+        addr.sun_family = libc::AF_UNIX as u16;
+        addr.sun_path = [1; 108];
+        addr.sun_path[..pathname.len()].copy_from_slice(&pathname);
+
+        // the address length leaves out the rest of `sun_path`, so the path has no nul
+        let ptr = &addr as *const _ as *const MaybeUninit<u8>;
+        let len = memoffset::offset_of!(libc::sockaddr_un, sun_path) + pathname.len();
+        let len = len.try_into().unwrap();
+
+        let addr = unsafe { SockaddrStorage::from_ptr(ptr, len) }.unwrap();
+        let addr = addr.as_unix().unwrap();
+
+        assert!(addr.as_abstract().is_none());
+        assert!(!addr.is_unnamed());
+
+        assert_eq!(addr.as_path().unwrap().as_ref(), pathname_cstr);
     }
 
     /// Convert from a pathname `SockaddrUnix` to a `SockaddrStorage` to a `sockaddr_un`.
@@ -0,0 +1,677 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use test_utils::check_system_call;
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    // ping sockets are only available on linux if the user's group is within the
+    // "net.ipv4.ping_group_range" sysctl, so we don't run the ping tests outside of shadow
+    vec![
+        test_utils::ShadowTest::new(
+            "test_ping_localhost",
+            test_ping_localhost,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ping_connected",
+            test_ping_connected,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ping_invalid_type",
+            test_ping_invalid_type,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ping_short_message",
+            test_ping_short_message,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ping_sockopt",
+            test_ping_sockopt,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_udp_port_unreachable",
+            test_udp_port_unreachable,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_udp_port_unreachable_so_error",
+            test_udp_port_unreachable_so_error,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_udp_unconnected_no_error",
+            test_udp_unconnected_no_error,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ip_ttl_sockopt",
+            test_ip_ttl_sockopt,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        // the remaining tests need the "peer" host from the shadow config
+        test_utils::ShadowTest::new(
+            "test_udp_ttl_expired",
+            test_udp_ttl_expired,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_ping_ttl_expired",
+            test_ping_ttl_expired,
+            set![TestEnv::Shadow],
+        ),
+    ]
+}
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+
+fn localhost_addr(port: u16) -> libc::sockaddr_in {
+    libc::sockaddr_in {
+        sin_family: libc::AF_INET as u16,
+        sin_port: port.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(std::net::Ipv4Addr::LOCALHOST).to_be(),
+        },
+        sin_zero: [0; 8],
+    }
+}
+
+/// The address of the "peer" host, which is one router hop away in the shadow config.
+fn peer_addr(port: u16) -> libc::sockaddr_in {
+    libc::sockaddr_in {
+        sin_family: libc::AF_INET as u16,
+        sin_port: port.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(std::net::Ipv4Addr::new(100, 0, 0, 2)).to_be(),
+        },
+        sin_zero: [0; 8],
+    }
+}
+
+/// An ICMP echo request with the given sequence number and payload. The identifier and checksum
+/// are set by the kernel.
+fn echo_request(sequence: u16, payload: &[u8]) -> Vec<u8> {
+    let mut message = vec![ICMP_ECHO_REQUEST, 0, 0, 0, 0, 0];
+    message.extend_from_slice(&sequence.to_be_bytes());
+    message.extend_from_slice(payload);
+    message
+}
+
+/// The internet checksum (RFC 1071) of `bytes`.
+fn internet_checksum(bytes: &[u8]) -> u16 {
+    let mut sum: u32 = bytes
+        .chunks(2)
+        .map(|x| u32::from(u16::from_be_bytes([x[0], *x.get(1).unwrap_or(&0)])))
+        .sum();
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+fn ping_socket() -> Result<libc::c_int, String> {
+    check_system_call!(
+        || unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, libc::IPPROTO_ICMP) },
+        &[],
+    )
+}
+
+fn sendto(fd: libc::c_int, buf: &[u8], addr: Option<&libc::sockaddr_in>) -> libc::ssize_t {
+    let (addr_ptr, addr_len) = match addr {
+        Some(addr) => (
+            addr as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of_val(addr) as libc::socklen_t,
+        ),
+        None => (std::ptr::null(), 0),
+    };
+
+    unsafe {
+        libc::sendto(
+            fd,
+            buf.as_ptr() as *const _,
+            buf.len(),
+            0,
+            addr_ptr,
+            addr_len,
+        )
+    }
+}
+
+/// Receive a message, returning the message and the source address.
+fn recvfrom(fd: libc::c_int, flags: libc::c_int) -> Result<(Vec<u8>, libc::sockaddr_in), String> {
+    let mut buf = vec![0u8; 1024];
+    let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    let mut addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+
+    let rv = check_system_call!(
+        || unsafe {
+            libc::recvfrom(
+                fd,
+                buf.as_mut_ptr() as *mut _,
+                buf.len(),
+                flags,
+                &mut addr as *mut libc::sockaddr_in as *mut libc::sockaddr,
+                &mut addr_len,
+            )
+        },
+        &[],
+    )?;
+
+    buf.truncate(rv as usize);
+    Ok((buf, addr))
+}
+
+/// Check that `reply` is a valid echo reply to an echo request with the given sequence number and
+/// payload.
+fn check_echo_reply(reply: &[u8], sequence: u16, payload: &[u8]) -> Result<(), String> {
+    test_utils::result_assert_eq(reply.len(), 8 + payload.len(), "Unexpected reply length")?;
+    test_utils::result_assert_eq(reply[0], ICMP_ECHO_REPLY, "Unexpected ICMP type")?;
+    test_utils::result_assert_eq(reply[1], 0, "Unexpected ICMP code")?;
+    test_utils::result_assert_eq(internet_checksum(reply), 0, "Invalid ICMP checksum")?;
+    test_utils::result_assert_eq(
+        u16::from_be_bytes([reply[6], reply[7]]),
+        sequence,
+        "Unexpected sequence number",
+    )?;
+    test_utils::result_assert_eq(&reply[8..], payload, "Unexpected payload")?;
+
+    Ok(())
+}
+
+/// Test sending an echo request to localhost and receiving the reply.
+fn test_ping_localhost() -> Result<(), String> {
+    let fd = ping_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let payload = b"hello";
+        let request = echo_request(7, payload);
+        let addr = localhost_addr(0);
+
+        let rv = check_system_call!(|| sendto(fd, &request, Some(&addr)), &[])?;
+        test_utils::result_assert_eq(rv as usize, request.len(), "Unexpected send length")?;
+
+        let (reply, src) = recvfrom(fd, 0)?;
+        check_echo_reply(&reply, 7, payload)?;
+
+        // the reply is from localhost, and ping sockets always use a port of 0
+        test_utils::result_assert_eq(src.sin_addr.s_addr, addr.sin_addr.s_addr, "Wrong source")?;
+        test_utils::result_assert_eq(src.sin_port, 0, "Unexpected source port")?;
+
+        // the identifier is the socket's bound port
+        let mut local: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        let mut local_len = std::mem::size_of_val(&local) as libc::socklen_t;
+        check_system_call!(
+            || unsafe {
+                libc::getsockname(
+                    fd,
+                    &mut local as *mut libc::sockaddr_in as *mut libc::sockaddr,
+                    &mut local_len,
+                )
+            },
+            &[],
+        )?;
+        test_utils::result_assert_eq(
+            u16::from_be_bytes([reply[4], reply[5]]),
+            u16::from_be(local.sin_port),
+            "Identifier doesn't match the bound port",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test sending several echo requests on a connected ping socket.
+fn test_ping_connected() -> Result<(), String> {
+    let fd = ping_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = localhost_addr(0);
+        check_system_call!(
+            || unsafe {
+                libc::connect(
+                    fd,
+                    &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+                    std::mem::size_of_val(&addr) as libc::socklen_t,
+                )
+            },
+            &[],
+        )?;
+
+        for sequence in 1..=3 {
+            let request = echo_request(sequence, &[]);
+            check_system_call!(|| sendto(fd, &request, None), &[])?;
+        }
+
+        for sequence in 1..=3 {
+            let (reply, _) = recvfrom(fd, 0)?;
+            check_echo_reply(&reply, sequence, &[])?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Test that ping sockets can only send echo requests.
+fn test_ping_invalid_type() -> Result<(), String> {
+    let fd = ping_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let mut request = echo_request(1, b"hello");
+        request[0] = ICMP_ECHO_REPLY;
+
+        let addr = localhost_addr(0);
+        check_system_call!(|| sendto(fd, &request, Some(&addr)), &[libc::EINVAL])?;
+
+        // a non-zero code is also invalid
+        let mut request = echo_request(1, b"hello");
+        request[1] = 1;
+        check_system_call!(|| sendto(fd, &request, Some(&addr)), &[libc::EINVAL])?;
+
+        Ok(())
+    })
+}
+
+/// Test that messages shorter than an ICMP header are rejected.
+fn test_ping_short_message() -> Result<(), String> {
+    let fd = ping_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = localhost_addr(0);
+        let request = [ICMP_ECHO_REQUEST, 0, 0, 0];
+        check_system_call!(|| sendto(fd, &request, Some(&addr)), &[libc::EINVAL])?;
+
+        Ok(())
+    })
+}
+
+/// Test the socket type and protocol of a ping socket.
+fn test_ping_sockopt() -> Result<(), String> {
+    let fd = ping_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        for (optname, expected) in [
+            (libc::SO_TYPE, libc::SOCK_DGRAM),
+            (libc::SO_PROTOCOL, libc::IPPROTO_ICMP),
+            (libc::SO_DOMAIN, libc::AF_INET),
+        ] {
+            let mut val: libc::c_int = 0;
+            let mut len = std::mem::size_of_val(&val) as libc::socklen_t;
+            check_system_call!(
+                || unsafe {
+                    libc::getsockopt(
+                        fd,
+                        libc::SOL_SOCKET,
+                        optname,
+                        &mut val as *mut libc::c_int as *mut _,
+                        &mut len,
+                    )
+                },
+                &[],
+            )?;
+            test_utils::result_assert_eq(val, expected, "Unexpected socket option value")?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Get a UDP port on localhost that no socket is bound to.
+fn unused_udp_port() -> Result<u16, String> {
+    let fd = check_system_call!(
+        || unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) },
+        &[],
+    )?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = localhost_addr(0);
+        check_system_call!(
+            || unsafe {
+                libc::bind(
+                    fd,
+                    &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+                    std::mem::size_of_val(&addr) as libc::socklen_t,
+                )
+            },
+            &[],
+        )?;
+
+        let mut local: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        let mut local_len = std::mem::size_of_val(&local) as libc::socklen_t;
+        check_system_call!(
+            || unsafe {
+                libc::getsockname(
+                    fd,
+                    &mut local as *mut libc::sockaddr_in as *mut libc::sockaddr,
+                    &mut local_len,
+                )
+            },
+            &[],
+        )?;
+
+        Ok(u16::from_be(local.sin_port))
+    })
+}
+
+/// Test that a connected UDP socket receives `ECONNREFUSED` after sending to a closed port.
+fn test_udp_port_unreachable() -> Result<(), String> {
+    let port = unused_udp_port()?;
+
+    let fd = check_system_call!(
+        || unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) },
+        &[],
+    )?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = localhost_addr(port);
+        check_system_call!(
+            || unsafe {
+                libc::connect(
+                    fd,
+                    &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+                    std::mem::size_of_val(&addr) as libc::socklen_t,
+                )
+            },
+            &[],
+        )?;
+
+        check_system_call!(|| sendto(fd, b"hello", None), &[])?;
+
+        // the ICMP error makes the socket readable, and the error is returned by the next recv
+        let mut buf = [0u8; 16];
+        check_system_call!(
+            || unsafe { libc::recv(fd, buf.as_mut_ptr() as *mut _, buf.len(), 0) },
+            &[libc::ECONNREFUSED],
+        )?;
+
+        // the error is cleared after being reported
+        check_system_call!(
+            || unsafe {
+                libc::recv(
+                    fd,
+                    buf.as_mut_ptr() as *mut _,
+                    buf.len(),
+                    libc::MSG_DONTWAIT,
+                )
+            },
+            &[libc::EAGAIN],
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that `SO_ERROR` returns the error from an ICMP port unreachable message, and clears it.
+fn test_udp_port_unreachable_so_error() -> Result<(), String> {
+    let port = unused_udp_port()?;
+
+    let fd = check_system_call!(
+        || unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) },
+        &[],
+    )?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = localhost_addr(port);
+        check_system_call!(
+            || unsafe {
+                libc::connect(
+                    fd,
+                    &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+                    std::mem::size_of_val(&addr) as libc::socklen_t,
+                )
+            },
+            &[],
+        )?;
+
+        check_system_call!(|| sendto(fd, b"hello", None), &[])?;
+
+        // wait for the ICMP error to make the socket readable
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        check_system_call!(|| unsafe { libc::poll(&mut pfd, 1, 1000) }, &[])?;
+        test_utils::result_assert_eq(pfd.revents & libc::POLLIN, libc::POLLIN, "Not readable")?;
+
+        let so_error = || -> Result<libc::c_int, String> {
+            let mut error: libc::c_int = -1;
+            let mut error_len = std::mem::size_of_val(&error) as libc::socklen_t;
+            check_system_call!(
+                || unsafe {
+                    libc::getsockopt(
+                        fd,
+                        libc::SOL_SOCKET,
+                        libc::SO_ERROR,
+                        &mut error as *mut libc::c_int as *mut libc::c_void,
+                        &mut error_len,
+                    )
+                },
+                &[],
+            )?;
+            Ok(error)
+        };
+
+        test_utils::result_assert_eq(so_error()?, libc::ECONNREFUSED, "Unexpected error")?;
+
+        // reading the error cleared it
+        test_utils::result_assert_eq(so_error()?, 0, "Error was not cleared")?;
+
+        let mut buf = [0u8; 16];
+        check_system_call!(
+            || unsafe {
+                libc::recv(
+                    fd,
+                    buf.as_mut_ptr() as *mut _,
+                    buf.len(),
+                    libc::MSG_DONTWAIT,
+                )
+            },
+            &[libc::EAGAIN],
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that an unconnected UDP socket does not receive errors after sending to a closed port.
+fn test_udp_unconnected_no_error() -> Result<(), String> {
+    let port = unused_udp_port()?;
+
+    let fd = check_system_call!(
+        || unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) },
+        &[],
+    )?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = localhost_addr(port);
+        check_system_call!(|| sendto(fd, b"hello", Some(&addr)), &[])?;
+
+        // give the ICMP error time to arrive
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let mut buf = [0u8; 16];
+        check_system_call!(
+            || unsafe {
+                libc::recv(
+                    fd,
+                    buf.as_mut_ptr() as *mut _,
+                    buf.len(),
+                    libc::MSG_DONTWAIT,
+                )
+            },
+            &[libc::EAGAIN],
+        )?;
+
+        check_system_call!(|| sendto(fd, b"hello", Some(&addr)), &[])?;
+
+        Ok(())
+    })
+}
+
+fn set_ip_ttl(fd: libc::c_int, ttl: libc::c_int) -> libc::c_int {
+    unsafe {
+        libc::setsockopt(
+            fd,
+            libc::IPPROTO_IP,
+            libc::IP_TTL,
+            &ttl as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of_val(&ttl) as libc::socklen_t,
+        )
+    }
+}
+
+fn get_ip_ttl(fd: libc::c_int) -> Result<libc::c_int, String> {
+    let mut ttl: libc::c_int = 0;
+    let mut ttl_len = std::mem::size_of_val(&ttl) as libc::socklen_t;
+    check_system_call!(
+        || unsafe {
+            libc::getsockopt(
+                fd,
+                libc::IPPROTO_IP,
+                libc::IP_TTL,
+                &mut ttl as *mut libc::c_int as *mut libc::c_void,
+                &mut ttl_len,
+            )
+        },
+        &[],
+    )?;
+    Ok(ttl)
+}
+
+/// Test getting and setting the `IP_TTL` option of a UDP socket.
+fn test_ip_ttl_sockopt() -> Result<(), String> {
+    let fd = check_system_call!(
+        || unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) },
+        &[],
+    )?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        test_utils::result_assert_eq(get_ip_ttl(fd)?, 64, "Unexpected default ttl")?;
+
+        check_system_call!(|| set_ip_ttl(fd, 5), &[])?;
+        test_utils::result_assert_eq(get_ip_ttl(fd)?, 5, "Unexpected ttl")?;
+
+        // -1 restores the default ttl
+        check_system_call!(|| set_ip_ttl(fd, -1), &[])?;
+        test_utils::result_assert_eq(get_ip_ttl(fd)?, 64, "Unexpected ttl")?;
+
+        check_system_call!(|| set_ip_ttl(fd, 0), &[libc::EINVAL])?;
+        check_system_call!(|| set_ip_ttl(fd, 256), &[libc::EINVAL])?;
+        test_utils::result_assert_eq(get_ip_ttl(fd)?, 64, "Unexpected ttl")?;
+
+        Ok(())
+    })
+}
+
+/// Test that a UDP packet whose TTL runs out before reaching a closed port doesn't cause a port
+/// unreachable error, and that it does once the TTL is large enough.
+fn test_udp_ttl_expired() -> Result<(), String> {
+    let fd = check_system_call!(
+        || unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) },
+        &[],
+    )?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        // the peer has no sockets, so every port is closed
+        let addr = peer_addr(9);
+        check_system_call!(
+            || unsafe {
+                libc::connect(
+                    fd,
+                    &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+                    std::mem::size_of_val(&addr) as libc::socklen_t,
+                )
+            },
+            &[],
+        )?;
+
+        // the packet expires at the router between the hosts, and the time exceeded error isn't
+        // reported to the socket
+        check_system_call!(|| set_ip_ttl(fd, 1), &[])?;
+        check_system_call!(|| sendto(fd, b"hello", None), &[])?;
+
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let rv = check_system_call!(|| unsafe { libc::poll(&mut pfd, 1, 100) }, &[])?;
+        test_utils::result_assert_eq(rv, 0, "Socket became readable")?;
+
+        // the packet reaches the peer, which replies with a port unreachable error
+        check_system_call!(|| set_ip_ttl(fd, 2), &[])?;
+        check_system_call!(|| sendto(fd, b"hello", None), &[])?;
+
+        let mut buf = [0u8; 16];
+        check_system_call!(
+            || unsafe { libc::recv(fd, buf.as_mut_ptr() as *mut _, buf.len(), 0) },
+            &[libc::ECONNREFUSED],
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that an echo request whose TTL runs out before reaching its destination isn't answered.
+fn test_ping_ttl_expired() -> Result<(), String> {
+    let fd = ping_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = peer_addr(0);
+
+        check_system_call!(|| set_ip_ttl(fd, 1), &[])?;
+        test_utils::result_assert_eq(get_ip_ttl(fd)?, 1, "Unexpected ttl")?;
+
+        let request = echo_request(1, b"hello");
+        check_system_call!(|| sendto(fd, &request, Some(&addr)), &[])?;
+
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let rv = check_system_call!(|| unsafe { libc::poll(&mut pfd, 1, 100) }, &[])?;
+        test_utils::result_assert_eq(rv, 0, "Received a reply to an expired request")?;
+
+        check_system_call!(|| set_ip_ttl(fd, 2), &[])?;
+
+        let request = echo_request(2, b"hello");
+        check_system_call!(|| sendto(fd, &request, Some(&addr)), &[])?;
+
+        let (reply, _) = recvfrom(fd, 0)?;
+        check_echo_reply(&reply, 2, b"hello")?;
+
+        Ok(())
+    })
+}
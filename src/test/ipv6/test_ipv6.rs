@@ -0,0 +1,707 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use test_utils::check_system_call;
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+/// The IPv6 address of the "ipv6peer" host in the test configuration.
+const PEER_IPV6_ADDR: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2);
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    // some docker environments don't support IPv6 networking, so we only run tests that use the
+    // network within shadow
+    vec![
+        test_utils::ShadowTest::new(
+            "test_sockopt",
+            test_sockopt,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_v6_only_after_bind",
+            test_v6_only_after_bind,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_tcp_loopback",
+            test_tcp_loopback,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_udp_loopback",
+            test_udp_loopback,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_udp_v4_mapped",
+            test_udp_v4_mapped,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new("test_udp_v6_only", test_udp_v6_only, set![TestEnv::Shadow]),
+        test_utils::ShadowTest::new(
+            "test_tcp_v6_only_shared_port",
+            test_tcp_v6_only_shared_port,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_udp_v6_only_ignores_ipv4",
+            test_udp_v6_only_ignores_ipv4,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_getaddrinfo_peer",
+            test_getaddrinfo_peer,
+            set![TestEnv::Shadow],
+        ),
+    ]
+}
+
+fn sockaddr_in6(ip: Ipv6Addr, port: u16) -> libc::sockaddr_in6 {
+    libc::sockaddr_in6 {
+        sin6_family: libc::AF_INET6 as u16,
+        sin6_port: port.to_be(),
+        sin6_flowinfo: 0,
+        sin6_addr: libc::in6_addr {
+            s6_addr: ip.octets(),
+        },
+        sin6_scope_id: 0,
+    }
+}
+
+fn sockaddr_in(ip: Ipv4Addr, port: u16) -> libc::sockaddr_in {
+    libc::sockaddr_in {
+        sin_family: libc::AF_INET as u16,
+        sin_port: port.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(ip).to_be(),
+        },
+        sin_zero: [0; 8],
+    }
+}
+
+fn socket(domain: libc::c_int, sock_type: libc::c_int) -> Result<libc::c_int, String> {
+    check_system_call!(|| unsafe { libc::socket(domain, sock_type, 0) }, &[])
+}
+
+fn bind6(fd: libc::c_int, addr: &libc::sockaddr_in6) -> libc::c_int {
+    unsafe {
+        libc::bind(
+            fd,
+            addr as *const libc::sockaddr_in6 as *const libc::sockaddr,
+            std::mem::size_of_val(addr) as libc::socklen_t,
+        )
+    }
+}
+
+fn bind4(fd: libc::c_int, addr: &libc::sockaddr_in) -> libc::c_int {
+    unsafe {
+        libc::bind(
+            fd,
+            addr as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of_val(addr) as libc::socklen_t,
+        )
+    }
+}
+
+fn connect4(fd: libc::c_int, addr: &libc::sockaddr_in) -> libc::c_int {
+    unsafe {
+        libc::connect(
+            fd,
+            addr as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of_val(addr) as libc::socklen_t,
+        )
+    }
+}
+
+fn connect6(fd: libc::c_int, addr: &libc::sockaddr_in6) -> libc::c_int {
+    unsafe {
+        libc::connect(
+            fd,
+            addr as *const libc::sockaddr_in6 as *const libc::sockaddr,
+            std::mem::size_of_val(addr) as libc::socklen_t,
+        )
+    }
+}
+
+fn sendto4(fd: libc::c_int, buf: &[u8], addr: &libc::sockaddr_in) -> libc::ssize_t {
+    unsafe {
+        libc::sendto(
+            fd,
+            buf.as_ptr() as *const _,
+            buf.len(),
+            0,
+            addr as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of_val(addr) as libc::socklen_t,
+        )
+    }
+}
+
+fn sendto6(fd: libc::c_int, buf: &[u8], addr: &libc::sockaddr_in6) -> libc::ssize_t {
+    unsafe {
+        libc::sendto(
+            fd,
+            buf.as_ptr() as *const _,
+            buf.len(),
+            0,
+            addr as *const libc::sockaddr_in6 as *const libc::sockaddr,
+            std::mem::size_of_val(addr) as libc::socklen_t,
+        )
+    }
+}
+
+/// Get the local address of an IPv6 socket.
+fn getsockname6(fd: libc::c_int) -> Result<libc::sockaddr_in6, String> {
+    let mut addr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+    let mut addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+    check_system_call!(
+        || unsafe {
+            libc::getsockname(
+                fd,
+                &mut addr as *mut libc::sockaddr_in6 as *mut libc::sockaddr,
+                &mut addr_len,
+            )
+        },
+        &[],
+    )?;
+    test_utils::result_assert_eq(
+        addr.sin6_family,
+        libc::AF_INET6 as u16,
+        "Unexpected address family",
+    )?;
+    Ok(addr)
+}
+
+/// Receive a message on an IPv6 socket, returning the message and the source address.
+fn recvfrom6(fd: libc::c_int) -> Result<(Vec<u8>, libc::sockaddr_in6), String> {
+    let mut buf = vec![0u8; 1024];
+    let mut addr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+    let mut addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+
+    let rv = check_system_call!(
+        || unsafe {
+            libc::recvfrom(
+                fd,
+                buf.as_mut_ptr() as *mut _,
+                buf.len(),
+                0,
+                &mut addr as *mut libc::sockaddr_in6 as *mut libc::sockaddr,
+                &mut addr_len,
+            )
+        },
+        &[],
+    )?;
+
+    test_utils::result_assert_eq(
+        addr.sin6_family,
+        libc::AF_INET6 as u16,
+        "Unexpected address family",
+    )?;
+
+    buf.truncate(rv as usize);
+    Ok((buf, addr))
+}
+
+fn get_int_sockopt(
+    fd: libc::c_int,
+    level: libc::c_int,
+    optname: libc::c_int,
+) -> Result<libc::c_int, String> {
+    let mut val: libc::c_int = 0;
+    let mut len = std::mem::size_of_val(&val) as libc::socklen_t;
+    check_system_call!(
+        || unsafe {
+            libc::getsockopt(
+                fd,
+                level,
+                optname,
+                &mut val as *mut libc::c_int as *mut _,
+                &mut len,
+            )
+        },
+        &[],
+    )?;
+    Ok(val)
+}
+
+fn set_v6_only(fd: libc::c_int, val: libc::c_int) -> libc::c_int {
+    unsafe {
+        libc::setsockopt(
+            fd,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_V6ONLY,
+            &val as *const libc::c_int as *const _,
+            std::mem::size_of_val(&val) as libc::socklen_t,
+        )
+    }
+}
+
+/// Look up the addresses of `node` using `getaddrinfo()`.
+fn lookup(node: &str, family: libc::c_int, flags: libc::c_int) -> Result<Vec<Ipv6Addr>, String> {
+    let node = std::ffi::CString::new(node).unwrap();
+    let hints = libc::addrinfo {
+        ai_flags: flags,
+        ai_family: family,
+        ai_socktype: libc::SOCK_DGRAM,
+        ai_protocol: 0,
+        ai_addrlen: 0,
+        ai_addr: std::ptr::null_mut(),
+        ai_canonname: std::ptr::null_mut(),
+        ai_next: std::ptr::null_mut(),
+    };
+    let mut res: *mut libc::addrinfo = std::ptr::null_mut();
+
+    let rv = unsafe { libc::getaddrinfo(node.as_ptr(), std::ptr::null(), &hints, &mut res) };
+    if rv != 0 {
+        return Err(format!("getaddrinfo returned {rv}"));
+    }
+
+    let mut addrs = Vec::new();
+    let mut next = res;
+    while let Some(info) = unsafe { next.as_ref() } {
+        test_utils::result_assert_eq(info.ai_family, libc::AF_INET6, "Unexpected family")?;
+        let addr = unsafe { &*(info.ai_addr as *const libc::sockaddr_in6) };
+        addrs.push(Ipv6Addr::from(addr.sin6_addr.s6_addr));
+        next = info.ai_next;
+    }
+
+    unsafe { libc::freeaddrinfo(res) };
+    Ok(addrs)
+}
+
+fn hostname() -> Result<String, String> {
+    let hostname = nix::unistd::gethostname().map_err(|e| e.to_string())?;
+    Ok(hostname.into_string().unwrap())
+}
+
+/// Get this host's IPv4 address.
+fn public_ipv4() -> Result<Ipv4Addr, String> {
+    let hostname = std::ffi::CString::new(hostname()?).unwrap();
+
+    let hints = libc::addrinfo {
+        ai_flags: 0,
+        ai_family: libc::AF_INET,
+        ai_socktype: libc::SOCK_DGRAM,
+        ai_protocol: 0,
+        ai_addrlen: 0,
+        ai_addr: std::ptr::null_mut(),
+        ai_canonname: std::ptr::null_mut(),
+        ai_next: std::ptr::null_mut(),
+    };
+    let mut res: *mut libc::addrinfo = std::ptr::null_mut();
+
+    let rv = unsafe { libc::getaddrinfo(hostname.as_ptr(), std::ptr::null(), &hints, &mut res) };
+    if rv != 0 {
+        return Err(format!("getaddrinfo returned {rv}"));
+    }
+
+    let info = unsafe { res.as_ref() }.unwrap();
+    let addr = unsafe { &*(info.ai_addr as *const libc::sockaddr_in) };
+    let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+
+    unsafe { libc::freeaddrinfo(res) };
+    Ok(ip)
+}
+
+/// Test the socket options of an IPv6 socket.
+fn test_sockopt() -> Result<(), String> {
+    let fd = socket(libc::AF_INET6, libc::SOCK_DGRAM)?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let domain = get_int_sockopt(fd, libc::SOL_SOCKET, libc::SO_DOMAIN)?;
+        test_utils::result_assert_eq(domain, libc::AF_INET6, "Unexpected domain")?;
+
+        // sockets are dual-stack by default
+        let v6_only = get_int_sockopt(fd, libc::IPPROTO_IPV6, libc::IPV6_V6ONLY)?;
+        test_utils::result_assert_eq(v6_only, 0, "Unexpected IPV6_V6ONLY")?;
+
+        check_system_call!(|| set_v6_only(fd, 1), &[])?;
+        let v6_only = get_int_sockopt(fd, libc::IPPROTO_IPV6, libc::IPV6_V6ONLY)?;
+        test_utils::result_assert_eq(v6_only, 1, "Unexpected IPV6_V6ONLY")?;
+
+        Ok(())
+    })
+}
+
+/// Test that `IPV6_V6ONLY` can't be changed after the socket is bound.
+fn test_v6_only_after_bind() -> Result<(), String> {
+    let fd = socket(libc::AF_INET6, libc::SOCK_DGRAM)?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = sockaddr_in6(Ipv6Addr::UNSPECIFIED, 0);
+        check_system_call!(|| bind6(fd, &addr), &[])?;
+        check_system_call!(|| set_v6_only(fd, 1), &[libc::EINVAL])?;
+
+        Ok(())
+    })
+}
+
+/// Test a TCP connection over the IPv6 loopback.
+fn test_tcp_loopback() -> Result<(), String> {
+    let listen_fd = socket(libc::AF_INET6, libc::SOCK_STREAM)?;
+    let client_fd = socket(libc::AF_INET6, libc::SOCK_STREAM)?;
+
+    test_utils::run_and_close_fds(&[listen_fd, client_fd], || {
+        let addr = sockaddr_in6(Ipv6Addr::LOCALHOST, 0);
+        check_system_call!(|| bind6(listen_fd, &addr), &[])?;
+        check_system_call!(|| unsafe { libc::listen(listen_fd, 10) }, &[])?;
+
+        let server_addr = getsockname6(listen_fd)?;
+        test_utils::result_assert_eq(
+            Ipv6Addr::from(server_addr.sin6_addr.s6_addr),
+            Ipv6Addr::LOCALHOST,
+            "Unexpected bound address",
+        )?;
+
+        check_system_call!(
+            || unsafe {
+                libc::connect(
+                    client_fd,
+                    &server_addr as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                    std::mem::size_of_val(&server_addr) as libc::socklen_t,
+                )
+            },
+            &[],
+        )?;
+
+        let mut peer_addr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+        let mut peer_addr_len = std::mem::size_of_val(&peer_addr) as libc::socklen_t;
+        let accepted_fd = check_system_call!(
+            || unsafe {
+                libc::accept(
+                    listen_fd,
+                    &mut peer_addr as *mut libc::sockaddr_in6 as *mut libc::sockaddr,
+                    &mut peer_addr_len,
+                )
+            },
+            &[],
+        )?;
+
+        test_utils::run_and_close_fds(&[accepted_fd], || {
+            // the peer address of the accepted socket is the client's address
+            let client_addr = getsockname6(client_fd)?;
+            test_utils::result_assert_eq(
+                peer_addr.sin6_family,
+                libc::AF_INET6 as u16,
+                "Unexpected peer family",
+            )?;
+            test_utils::result_assert_eq(
+                Ipv6Addr::from(peer_addr.sin6_addr.s6_addr),
+                Ipv6Addr::LOCALHOST,
+                "Unexpected peer address",
+            )?;
+            test_utils::result_assert_eq(
+                peer_addr.sin6_port,
+                client_addr.sin6_port,
+                "Unexpected peer port",
+            )?;
+
+            // the accepted socket is also an IPv6 socket
+            let domain = get_int_sockopt(accepted_fd, libc::SOL_SOCKET, libc::SO_DOMAIN)?;
+            test_utils::result_assert_eq(domain, libc::AF_INET6, "Unexpected domain")?;
+
+            let message = b"hello";
+            check_system_call!(
+                || unsafe { libc::send(client_fd, message.as_ptr() as *const _, message.len(), 0) },
+                &[],
+            )?;
+
+            let mut buf = [0u8; 16];
+            let rv = check_system_call!(
+                || unsafe { libc::recv(accepted_fd, buf.as_mut_ptr() as *mut _, buf.len(), 0) },
+                &[],
+            )?;
+            test_utils::result_assert_eq(&buf[..rv as usize], message, "Unexpected message")?;
+
+            Ok(())
+        })
+    })
+}
+
+/// Test sending UDP datagrams over the IPv6 loopback.
+fn test_udp_loopback() -> Result<(), String> {
+    let server_fd = socket(libc::AF_INET6, libc::SOCK_DGRAM)?;
+    let client_fd = socket(libc::AF_INET6, libc::SOCK_DGRAM)?;
+
+    test_utils::run_and_close_fds(&[server_fd, client_fd], || {
+        let addr = sockaddr_in6(Ipv6Addr::LOCALHOST, 0);
+        check_system_call!(|| bind6(server_fd, &addr), &[])?;
+        let server_addr = getsockname6(server_fd)?;
+
+        let message = b"hello";
+        check_system_call!(|| sendto6(client_fd, message, &server_addr), &[])?;
+
+        let (buf, src) = recvfrom6(server_fd)?;
+        test_utils::result_assert_eq(&buf[..], message, "Unexpected message")?;
+
+        let client_addr = getsockname6(client_fd)?;
+        test_utils::result_assert_eq(
+            Ipv6Addr::from(src.sin6_addr.s6_addr),
+            Ipv6Addr::LOCALHOST,
+            "Unexpected source address",
+        )?;
+        test_utils::result_assert_eq(
+            src.sin6_port,
+            client_addr.sin6_port,
+            "Unexpected source port",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that a dual-stack socket receives datagrams from IPv4 sockets as IPv4-mapped addresses.
+fn test_udp_v4_mapped() -> Result<(), String> {
+    let server_fd = socket(libc::AF_INET6, libc::SOCK_DGRAM)?;
+    let client_fd = socket(libc::AF_INET, libc::SOCK_DGRAM)?;
+
+    test_utils::run_and_close_fds(&[server_fd, client_fd], || {
+        let addr = sockaddr_in6(Ipv6Addr::UNSPECIFIED, 0);
+        check_system_call!(|| bind6(server_fd, &addr), &[])?;
+        let server_port = u16::from_be(getsockname6(server_fd)?.sin6_port);
+
+        // this host has no IPv6 address, so should appear as a mapped IPv4 address
+        let public_ip = public_ipv4()?;
+        let server_addr = sockaddr_in(public_ip, server_port);
+
+        let message = b"hello";
+        check_system_call!(
+            || unsafe {
+                libc::sendto(
+                    client_fd,
+                    message.as_ptr() as *const _,
+                    message.len(),
+                    0,
+                    &server_addr as *const libc::sockaddr_in as *const libc::sockaddr,
+                    std::mem::size_of_val(&server_addr) as libc::socklen_t,
+                )
+            },
+            &[],
+        )?;
+
+        let (buf, src) = recvfrom6(server_fd)?;
+        test_utils::result_assert_eq(&buf[..], message, "Unexpected message")?;
+        test_utils::result_assert_eq(
+            Ipv6Addr::from(src.sin6_addr.s6_addr),
+            public_ip.to_ipv6_mapped(),
+            "Unexpected source address",
+        )?;
+
+        // reply to the mapped address
+        check_system_call!(|| sendto6(server_fd, message, &src), &[])?;
+
+        let mut buf = [0u8; 16];
+        let rv = check_system_call!(
+            || unsafe { libc::recv(client_fd, buf.as_mut_ptr() as *mut _, buf.len(), 0) },
+            &[],
+        )?;
+        test_utils::result_assert_eq(&buf[..rv as usize], message, "Unexpected message")?;
+
+        Ok(())
+    })
+}
+
+/// Test that an IPv6-only socket can't send to IPv4-mapped addresses.
+fn test_udp_v6_only() -> Result<(), String> {
+    let fd = socket(libc::AF_INET6, libc::SOCK_DGRAM)?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        check_system_call!(|| set_v6_only(fd, 1), &[])?;
+
+        let addr = sockaddr_in6(public_ipv4()?.to_ipv6_mapped(), 9000);
+        check_system_call!(|| sendto6(fd, b"hello", &addr), &[libc::ENETUNREACH])?;
+
+        Ok(())
+    })
+}
+
+/// Test looking up the IPv6 addresses of hosts.
+fn test_getaddrinfo_peer() -> Result<(), String> {
+    // a host with a configured IPv6 address
+    let addrs = lookup("ipv6peer", libc::AF_INET6, 0)?;
+    test_utils::result_assert_eq(addrs, vec![PEER_IPV6_ADDR], "Unexpected peer address")?;
+
+    // a host without an IPv6 address only has an IPv4-mapped address
+    test_utils::result_assert_eq(
+        lookup(&hostname()?, libc::AF_INET6, libc::AI_V4MAPPED)?,
+        vec![public_ipv4()?.to_ipv6_mapped()],
+        "Unexpected mapped address",
+    )?;
+
+    // the loopback address
+    let addrs = lookup("localhost", libc::AF_INET6, 0)?;
+    test_utils::result_assert_eq(addrs, vec![Ipv6Addr::LOCALHOST], "Unexpected address")?;
+
+    // we can send to the peer using its IPv6 address
+    let fd = socket(libc::AF_INET6, libc::SOCK_DGRAM)?;
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = sockaddr_in6(PEER_IPV6_ADDR, 9000);
+        check_system_call!(|| sendto6(fd, b"hello", &addr), &[])?;
+
+        // the socket was implicitly bound to this host's address, which has no IPv6 address
+        let local = getsockname6(fd)?;
+        test_utils::result_assert_eq(
+            Ipv6Addr::from(local.sin6_addr.s6_addr),
+            public_ipv4()?.to_ipv6_mapped(),
+            "Unexpected local address",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that an IPv4 socket and an IPv6-only socket can listen on the same port, and that each
+/// only accepts connections from its own family.
+fn test_tcp_v6_only_shared_port() -> Result<(), String> {
+    let listen4_fd = socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_NONBLOCK)?;
+    let listen6_fd = socket(libc::AF_INET6, libc::SOCK_STREAM | libc::SOCK_NONBLOCK)?;
+    let client4_fd = socket(libc::AF_INET, libc::SOCK_STREAM)?;
+    let client6_fd = socket(libc::AF_INET6, libc::SOCK_STREAM)?;
+
+    let fds = [listen4_fd, listen6_fd, client4_fd, client6_fd];
+    test_utils::run_and_close_fds(&fds, || {
+        check_system_call!(
+            || bind4(listen4_fd, &sockaddr_in(Ipv4Addr::UNSPECIFIED, 0)),
+            &[]
+        )?;
+        check_system_call!(|| unsafe { libc::listen(listen4_fd, 10) }, &[])?;
+
+        let mut addr4: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        let mut addr4_len = std::mem::size_of_val(&addr4) as libc::socklen_t;
+        check_system_call!(
+            || unsafe {
+                libc::getsockname(
+                    listen4_fd,
+                    &mut addr4 as *mut libc::sockaddr_in as *mut libc::sockaddr,
+                    &mut addr4_len,
+                )
+            },
+            &[],
+        )?;
+        let port = u16::from_be(addr4.sin_port);
+
+        // an IPv6-only socket can use the same port
+        check_system_call!(|| set_v6_only(listen6_fd, 1), &[])?;
+        let addr6 = sockaddr_in6(Ipv6Addr::UNSPECIFIED, port);
+        check_system_call!(|| bind6(listen6_fd, &addr6), &[])?;
+        check_system_call!(|| unsafe { libc::listen(listen6_fd, 10) }, &[])?;
+
+        // a dual-stack socket can't, since it would also use the IPv4 port
+        let dual_fd = socket(libc::AF_INET6, libc::SOCK_STREAM)?;
+        test_utils::run_and_close_fds(&[dual_fd], || {
+            check_system_call!(|| bind6(dual_fd, &addr6), &[libc::EADDRINUSE])?;
+            Ok(())
+        })?;
+
+        let accept = |fd| unsafe { libc::accept(fd, std::ptr::null_mut(), std::ptr::null_mut()) };
+
+        // the connection is only ready to accept once the server receives the client's ACK
+        let wait_readable = |fd| {
+            let mut pfd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            check_system_call!(|| unsafe { libc::poll(&mut pfd, 1, -1) }, &[])
+        };
+
+        // an IPv4 connection is only accepted by the IPv4 socket
+        let server4_addr = sockaddr_in(Ipv4Addr::LOCALHOST, port);
+        check_system_call!(|| connect4(client4_fd, &server4_addr), &[])?;
+        wait_readable(listen4_fd)?;
+        check_system_call!(|| accept(listen6_fd), &[libc::EAGAIN])?;
+        let accepted_fd = check_system_call!(|| accept(listen4_fd), &[])?;
+        test_utils::run_and_close_fds(&[accepted_fd], || Ok(()))?;
+
+        // an IPv6 connection is only accepted by the IPv6-only socket, and the IPv6-only client
+        // receives the replies from the not-yet-accepted socket
+        check_system_call!(|| set_v6_only(client6_fd, 1), &[])?;
+        let server6_addr = sockaddr_in6(Ipv6Addr::LOCALHOST, port);
+        check_system_call!(|| connect6(client6_fd, &server6_addr), &[])?;
+        wait_readable(listen6_fd)?;
+        check_system_call!(|| accept(listen4_fd), &[libc::EAGAIN])?;
+        let accepted_fd = check_system_call!(|| accept(listen6_fd), &[])?;
+        test_utils::run_and_close_fds(&[accepted_fd], || {
+            let domain = get_int_sockopt(accepted_fd, libc::SOL_SOCKET, libc::SO_DOMAIN)?;
+            test_utils::result_assert_eq(domain, libc::AF_INET6, "Unexpected domain")?;
+            Ok(())
+        })?;
+
+        Ok(())
+    })
+}
+
+/// Test that an IPv6-only socket doesn't receive datagrams from IPv4 sockets or from dual-stack
+/// sockets sending to IPv4-mapped addresses.
+fn test_udp_v6_only_ignores_ipv4() -> Result<(), String> {
+    let server_fd = socket(libc::AF_INET6, libc::SOCK_DGRAM)?;
+    let loopback4_fd = socket(libc::AF_INET, libc::SOCK_DGRAM)?;
+    let public4_fd = socket(libc::AF_INET, libc::SOCK_DGRAM)?;
+    let mapped_fd = socket(libc::AF_INET6, libc::SOCK_DGRAM)?;
+    let client6_fd = socket(libc::AF_INET6, libc::SOCK_DGRAM)?;
+
+    let fds = [server_fd, loopback4_fd, public4_fd, mapped_fd, client6_fd];
+    test_utils::run_and_close_fds(&fds, || {
+        check_system_call!(|| set_v6_only(server_fd, 1), &[])?;
+        let addr = sockaddr_in6(Ipv6Addr::UNSPECIFIED, 0);
+        check_system_call!(|| bind6(server_fd, &addr), &[])?;
+        let server_port = u16::from_be(getsockname6(server_fd)?.sin6_port);
+
+        let public_ip = public_ipv4()?;
+        // each socket is implicitly bound to the interface it first sends on
+        for (fd, ip) in [(loopback4_fd, Ipv4Addr::LOCALHOST), (public4_fd, public_ip)] {
+            let server_addr = sockaddr_in(ip, server_port);
+            check_system_call!(|| sendto4(fd, b"ipv4", &server_addr), &[])?;
+        }
+        let mapped_addr = sockaddr_in6(public_ip.to_ipv6_mapped(), server_port);
+        check_system_call!(|| sendto6(mapped_fd, b"mapped", &mapped_addr), &[])?;
+
+        let server_addr = sockaddr_in6(Ipv6Addr::LOCALHOST, server_port);
+        check_system_call!(|| sendto6(client6_fd, b"ipv6", &server_addr), &[])?;
+
+        let (buf, _src) = recvfrom6(server_fd)?;
+        test_utils::result_assert_eq(&buf[..], b"ipv6", "Unexpected message")?;
+
+        // give any other datagrams time to arrive
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let mut buf = [0u8; 16];
+        check_system_call!(
+            || unsafe {
+                libc::recv(
+                    server_fd,
+                    buf.as_mut_ptr() as *mut _,
+                    buf.len(),
+                    libc::MSG_DONTWAIT,
+                )
+            },
+            &[libc::EAGAIN],
+        )?;
+
+        Ok(())
+    })
+}
@@ -0,0 +1,502 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::collections::BTreeSet;
+use std::net::Ipv4Addr;
+
+use test_utils::check_system_call;
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+/// The multicast group joined by the "member" hosts in the test configuration.
+const GROUP: Ipv4Addr = Ipv4Addr::new(239, 1, 2, 3);
+
+/// The port that the peer hosts in the test configuration echo datagrams on.
+const PEER_PORT: u16 = 5353;
+
+/// The number of peer hosts on the test host's graph node that are members of [`GROUP`].
+const NUM_MEMBERS: usize = 2;
+
+/// The number of peer hosts on the test host's graph node.
+const NUM_PEERS: usize = 3;
+
+/// The address of the peer host (a member of [`GROUP`]) on a different graph node.
+const REMOTE_PEER: Ipv4Addr = Ipv4Addr::new(100, 0, 0, 2);
+
+fn main() -> Result<(), String> {
+    // should we run as a peer that echoes datagrams back to the sender?
+    if std::env::args().any(|x| x == "--peer") {
+        let join = std::env::args().any(|x| x == "--join");
+        return run_peer(join);
+    }
+
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    // the tests that send packets need the peer hosts, and joining a group fails in environments
+    // without a multicast route, so those tests only run in shadow
+    vec![
+        test_utils::ShadowTest::new(
+            "test_multicast_sockopts",
+            test_multicast_sockopts,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_membership_errors",
+            test_membership_errors,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_broadcast_requires_sockopt",
+            test_broadcast_requires_sockopt,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_multicast_members",
+            test_multicast_members,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new("test_broadcast", test_broadcast, set![TestEnv::Shadow]),
+        test_utils::ShadowTest::new(
+            "test_multicast_loop",
+            test_multicast_loop,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_multicast_ttl_zero",
+            test_multicast_ttl_zero,
+            set![TestEnv::Shadow],
+        ),
+    ]
+}
+
+fn sockaddr_in(ip: Ipv4Addr, port: u16) -> libc::sockaddr_in {
+    libc::sockaddr_in {
+        sin_family: libc::AF_INET as u16,
+        sin_port: port.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(ip).to_be(),
+        },
+        sin_zero: [0; 8],
+    }
+}
+
+fn udp_socket() -> Result<libc::c_int, String> {
+    check_system_call!(
+        || unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) },
+        &[]
+    )
+}
+
+fn bind(fd: libc::c_int, addr: &libc::sockaddr_in) -> libc::c_int {
+    unsafe {
+        libc::bind(
+            fd,
+            addr as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of_val(addr) as libc::socklen_t,
+        )
+    }
+}
+
+fn sendto(fd: libc::c_int, buf: &[u8], addr: &libc::sockaddr_in) -> libc::ssize_t {
+    unsafe {
+        libc::sendto(
+            fd,
+            buf.as_ptr() as *const _,
+            buf.len(),
+            0,
+            addr as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of_val(addr) as libc::socklen_t,
+        )
+    }
+}
+
+/// Receive a message, returning the message and the source address.
+fn recvfrom(fd: libc::c_int) -> Result<(Vec<u8>, libc::sockaddr_in), String> {
+    let mut buf = vec![0u8; 1024];
+    let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    let mut addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+
+    let rv = check_system_call!(
+        || unsafe {
+            libc::recvfrom(
+                fd,
+                buf.as_mut_ptr() as *mut _,
+                buf.len(),
+                0,
+                &mut addr as *mut libc::sockaddr_in as *mut libc::sockaddr,
+                &mut addr_len,
+            )
+        },
+        &[],
+    )?;
+
+    buf.truncate(rv as usize);
+    Ok((buf, addr))
+}
+
+/// Receive all messages that arrive within `timeout_ms` of each other, returning the source
+/// addresses of the messages.
+fn recv_all(fd: libc::c_int, msg: &[u8], timeout_ms: i32) -> Result<Vec<Ipv4Addr>, String> {
+    let mut sources = Vec::new();
+
+    while test_utils::is_readable(fd, timeout_ms).unwrap() {
+        let (buf, addr) = recvfrom(fd)?;
+        test_utils::result_assert_eq(buf.as_slice(), msg, "Unexpected message")?;
+        sources.push(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)));
+    }
+
+    Ok(sources)
+}
+
+fn setsockopt_int(
+    fd: libc::c_int,
+    level: libc::c_int,
+    optname: libc::c_int,
+    val: libc::c_int,
+) -> libc::c_int {
+    unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            optname,
+            &val as *const libc::c_int as *const _,
+            std::mem::size_of_val(&val) as libc::socklen_t,
+        )
+    }
+}
+
+fn getsockopt_int(
+    fd: libc::c_int,
+    level: libc::c_int,
+    optname: libc::c_int,
+) -> Result<libc::c_int, String> {
+    let mut val: libc::c_int = 0;
+    let mut len = std::mem::size_of_val(&val) as libc::socklen_t;
+    check_system_call!(
+        || unsafe {
+            libc::getsockopt(
+                fd,
+                level,
+                optname,
+                &mut val as *mut libc::c_int as *mut _,
+                &mut len,
+            )
+        },
+        &[],
+    )?;
+    test_utils::result_assert_eq(len, 4, "Unexpected option length")?;
+    Ok(val)
+}
+
+fn set_membership(fd: libc::c_int, optname: libc::c_int, group: Ipv4Addr) -> libc::c_int {
+    let mreq = libc::ip_mreq {
+        imr_multiaddr: libc::in_addr {
+            s_addr: u32::from(group).to_be(),
+        },
+        imr_interface: libc::in_addr {
+            s_addr: u32::from(Ipv4Addr::UNSPECIFIED).to_be(),
+        },
+    };
+
+    unsafe {
+        libc::setsockopt(
+            fd,
+            libc::IPPROTO_IP,
+            optname,
+            &mreq as *const libc::ip_mreq as *const _,
+            std::mem::size_of_val(&mreq) as libc::socklen_t,
+        )
+    }
+}
+
+/// Echo all datagrams received on [`PEER_PORT`] back to their sender.
+fn run_peer(join: bool) -> Result<(), String> {
+    let fd = udp_socket()?;
+
+    let addr = sockaddr_in(Ipv4Addr::UNSPECIFIED, PEER_PORT);
+    check_system_call!(|| bind(fd, &addr), &[])?;
+
+    if join {
+        check_system_call!(|| set_membership(fd, libc::IP_ADD_MEMBERSHIP, GROUP), &[])?;
+    }
+
+    loop {
+        let (buf, addr) = recvfrom(fd)?;
+        check_system_call!(|| sendto(fd, &buf, &addr), &[])?;
+    }
+}
+
+/// Test the default values of the multicast and broadcast socket options, and changing them.
+fn test_multicast_sockopts() -> Result<(), String> {
+    let fd = udp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let ttl = getsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL)?;
+        let lp = getsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP)?;
+        let broadcast = getsockopt_int(fd, libc::SOL_SOCKET, libc::SO_BROADCAST)?;
+        test_utils::result_assert_eq(ttl, 1, "Unexpected default multicast ttl")?;
+        test_utils::result_assert_eq(lp, 1, "Unexpected default multicast loop")?;
+        test_utils::result_assert_eq(broadcast, 0, "Unexpected default broadcast")?;
+
+        check_system_call!(
+            || setsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL, 32),
+            &[]
+        )?;
+        check_system_call!(
+            || setsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP, 0),
+            &[]
+        )?;
+        check_system_call!(
+            || setsockopt_int(fd, libc::SOL_SOCKET, libc::SO_BROADCAST, 1),
+            &[]
+        )?;
+
+        let ttl = getsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL)?;
+        let lp = getsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP)?;
+        let broadcast = getsockopt_int(fd, libc::SOL_SOCKET, libc::SO_BROADCAST)?;
+        test_utils::result_assert_eq(ttl, 32, "Unexpected multicast ttl")?;
+        test_utils::result_assert_eq(lp, 0, "Unexpected multicast loop")?;
+        test_utils::result_assert_eq(broadcast, 1, "Unexpected broadcast")?;
+
+        // -1 restores the default ttl
+        check_system_call!(
+            || setsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL, -1),
+            &[]
+        )?;
+        let ttl = getsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL)?;
+        test_utils::result_assert_eq(ttl, 1, "Unexpected multicast ttl")?;
+
+        check_system_call!(
+            || setsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL, 256),
+            &[libc::EINVAL]
+        )?;
+
+        // the ttl can also be set using a single byte
+        let val: u8 = 7;
+        check_system_call!(
+            || unsafe {
+                libc::setsockopt(
+                    fd,
+                    libc::IPPROTO_IP,
+                    libc::IP_MULTICAST_TTL,
+                    &val as *const u8 as *const _,
+                    1,
+                )
+            },
+            &[]
+        )?;
+        let ttl = getsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL)?;
+        test_utils::result_assert_eq(ttl, 7, "Unexpected multicast ttl")?;
+
+        Ok(())
+    })
+}
+
+/// Test the errors when joining and leaving multicast groups.
+fn test_membership_errors() -> Result<(), String> {
+    let fd = udp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let group = Ipv4Addr::new(239, 9, 9, 9);
+
+        // not a multicast address
+        check_system_call!(
+            || set_membership(fd, libc::IP_ADD_MEMBERSHIP, Ipv4Addr::new(10, 0, 0, 1)),
+            &[libc::EINVAL]
+        )?;
+
+        // not a member
+        check_system_call!(
+            || set_membership(fd, libc::IP_DROP_MEMBERSHIP, group),
+            &[libc::EADDRNOTAVAIL]
+        )?;
+
+        check_system_call!(|| set_membership(fd, libc::IP_ADD_MEMBERSHIP, group), &[])?;
+
+        // already a member
+        check_system_call!(
+            || set_membership(fd, libc::IP_ADD_MEMBERSHIP, group),
+            &[libc::EADDRINUSE]
+        )?;
+
+        check_system_call!(|| set_membership(fd, libc::IP_DROP_MEMBERSHIP, group), &[])?;
+
+        // the option value is too short
+        let val: libc::c_int = 0;
+        check_system_call!(
+            || unsafe {
+                libc::setsockopt(
+                    fd,
+                    libc::IPPROTO_IP,
+                    libc::IP_ADD_MEMBERSHIP,
+                    &val as *const libc::c_int as *const _,
+                    std::mem::size_of_val(&val) as libc::socklen_t,
+                )
+            },
+            &[libc::EINVAL]
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that sending to the broadcast address requires `SO_BROADCAST`.
+fn test_broadcast_requires_sockopt() -> Result<(), String> {
+    let fd = udp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = sockaddr_in(Ipv4Addr::BROADCAST, PEER_PORT);
+        check_system_call!(|| sendto(fd, &[1, 2, 3], &addr), &[libc::EACCES])?;
+
+        check_system_call!(
+            || unsafe {
+                libc::connect(
+                    fd,
+                    &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+                    std::mem::size_of_val(&addr) as libc::socklen_t,
+                )
+            },
+            &[libc::EACCES]
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that a multicast packet is received by all members of the group on the sender's graph node
+/// (since the default ttl is 1), and no other hosts.
+fn test_multicast_members() -> Result<(), String> {
+    let fd = udp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let addr = sockaddr_in(Ipv4Addr::UNSPECIFIED, 0);
+        check_system_call!(|| bind(fd, &addr), &[])?;
+
+        let msg = b"multicast";
+        let addr = sockaddr_in(GROUP, PEER_PORT);
+        check_system_call!(|| sendto(fd, msg, &addr), &[])?;
+
+        let sources = recv_all(fd, msg, 1000)?;
+        let num_sources = sources.iter().collect::<BTreeSet<_>>().len();
+
+        test_utils::result_assert_eq(sources.len(), NUM_MEMBERS, "Unexpected number of echoes")?;
+        test_utils::result_assert_eq(num_sources, NUM_MEMBERS, "Unexpected number of members")?;
+        test_utils::result_assert(
+            !sources.contains(&REMOTE_PEER),
+            "The remote member received the packet",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that a broadcast packet is received by all hosts on the sender's graph node, and isn't
+/// forwarded to other graph nodes.
+fn test_broadcast() -> Result<(), String> {
+    let fd = udp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        check_system_call!(
+            || setsockopt_int(fd, libc::SOL_SOCKET, libc::SO_BROADCAST, 1),
+            &[]
+        )?;
+
+        let addr = sockaddr_in(Ipv4Addr::UNSPECIFIED, 0);
+        check_system_call!(|| bind(fd, &addr), &[])?;
+
+        let msg = b"broadcast";
+        let addr = sockaddr_in(Ipv4Addr::BROADCAST, PEER_PORT);
+        check_system_call!(|| sendto(fd, msg, &addr), &[])?;
+
+        let sources = recv_all(fd, msg, 1000)?;
+        let num_sources = sources.iter().collect::<BTreeSet<_>>().len();
+
+        test_utils::result_assert_eq(sources.len(), NUM_PEERS, "Unexpected number of echoes")?;
+        test_utils::result_assert_eq(num_sources, NUM_PEERS, "Unexpected number of hosts")?;
+        test_utils::result_assert(
+            !sources.contains(&REMOTE_PEER),
+            "The remote host received the packet",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that `IP_MULTICAST_LOOP` controls whether a host receives its own multicast packets.
+fn test_multicast_loop() -> Result<(), String> {
+    let fd = udp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        let group = Ipv4Addr::new(239, 4, 5, 6);
+        let port = 7000;
+
+        let addr = sockaddr_in(Ipv4Addr::UNSPECIFIED, port);
+        check_system_call!(|| bind(fd, &addr), &[])?;
+        check_system_call!(|| set_membership(fd, libc::IP_ADD_MEMBERSHIP, group), &[])?;
+
+        let addr = sockaddr_in(group, port);
+
+        // loop is enabled by default
+        let msg = b"loop";
+        check_system_call!(|| sendto(fd, msg, &addr), &[])?;
+        let sources = recv_all(fd, msg, 100)?;
+        test_utils::result_assert_eq(sources.len(), 1, "Expected to receive our own packet")?;
+
+        check_system_call!(
+            || setsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP, 0),
+            &[]
+        )?;
+
+        let msg = b"no loop";
+        check_system_call!(|| sendto(fd, msg, &addr), &[])?;
+        let sources = recv_all(fd, msg, 100)?;
+        test_utils::result_assert_eq(sources.len(), 0, "Received our own packet")?;
+
+        Ok(())
+    })
+}
+
+/// Test that a multicast packet with a ttl of 0 doesn't leave the host.
+fn test_multicast_ttl_zero() -> Result<(), String> {
+    let fd = udp_socket()?;
+
+    test_utils::run_and_close_fds(&[fd], || {
+        check_system_call!(
+            || setsockopt_int(fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL, 0),
+            &[]
+        )?;
+
+        let addr = sockaddr_in(Ipv4Addr::UNSPECIFIED, 0);
+        check_system_call!(|| bind(fd, &addr), &[])?;
+
+        let msg = b"ttl 0";
+        let addr = sockaddr_in(GROUP, PEER_PORT);
+        check_system_call!(|| sendto(fd, msg, &addr), &[])?;
+
+        let sources = recv_all(fd, msg, 1000)?;
+        test_utils::result_assert_eq(sources.len(), 0, "A member received the packet")?;
+
+        Ok(())
+    })
+}
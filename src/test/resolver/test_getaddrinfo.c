@@ -312,14 +312,15 @@ void test_ipv6() {
     struct addrinfo* res;
 
     int rv = getaddrinfo(NULL, "80", &hints, &res);
+    assert_getaddrinfo_rv_equals(rv, 0);
 
-    if (running_in_shadow()) {
-        // shadow doesn't support IPv6
-        assert_getaddrinfo_rv_equals(rv, EAI_NONAME);
-    } else {
-        // linux should return a non-error result
-        assert_getaddrinfo_rv_equals(rv, 0);
-    }
+    g_assert_nonnull(res);
+    g_assert_cmpint(res->ai_family, ==, AF_INET6);
+    const struct sockaddr_in6* addr = (const struct sockaddr_in6*)res->ai_addr;
+    g_assert_true(IN6_IS_ADDR_UNSPECIFIED(&addr->sin6_addr));
+    g_assert_cmpint(ntohs(addr->sin6_port), ==, 80);
+
+    freeaddrinfo(res);
 }
 
 int main(int argc, char* argv[]) {
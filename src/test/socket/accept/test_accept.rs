@@ -226,6 +226,12 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
         }
     }
 
+    tests.extend(vec![test_utils::ShadowTest::new(
+        "test_reuse_port_load_balancing",
+        test_reuse_port_load_balancing,
+        set![TestEnv::Libc, TestEnv::Shadow],
+    )]);
+
     tests
 }
 
@@ -867,6 +873,99 @@ fn test_correctness(
     Ok(())
 }
 
+/// Test that connections to listening sockets sharing a port with SO_REUSEPORT are spread across
+/// the listeners.
+fn test_reuse_port_load_balancing() -> Result<(), String> {
+    const NUM_LISTENERS: usize = 2;
+    const NUM_CLIENTS: usize = 20;
+
+    let new_socket = || {
+        let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_NONBLOCK, 0) };
+        assert!(fd >= 0);
+        fd
+    };
+
+    let listeners: Vec<libc::c_int> = (0..NUM_LISTENERS).map(|_| new_socket()).collect();
+    let clients: Vec<libc::c_int> = (0..NUM_CLIENTS).map(|_| new_socket()).collect();
+
+    let addr = libc::sockaddr_in {
+        sin_family: libc::AF_INET as u16,
+        sin_port: 11111u16.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: libc::INADDR_LOOPBACK.to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+    let addr_ptr = &addr as *const libc::sockaddr_in as *const libc::sockaddr;
+    let addr_len = std::mem::size_of_val(&addr) as libc::socklen_t;
+
+    let fds: Vec<libc::c_int> = listeners.iter().chain(clients.iter()).copied().collect();
+
+    test_utils::run_and_close_fds(&fds, || {
+        let enable: libc::c_int = 1;
+
+        for &fd in &listeners {
+            test_utils::check_system_call!(
+                || unsafe {
+                    libc::setsockopt(
+                        fd,
+                        libc::SOL_SOCKET,
+                        libc::SO_REUSEPORT,
+                        &enable as *const libc::c_int as *const libc::c_void,
+                        std::mem::size_of_val(&enable) as libc::socklen_t,
+                    )
+                },
+                &[]
+            )?;
+            test_utils::check_system_call!(|| unsafe { libc::bind(fd, addr_ptr, addr_len) }, &[])?;
+            test_utils::check_system_call!(
+                || unsafe { libc::listen(fd, NUM_CLIENTS as libc::c_int) },
+                &[]
+            )?;
+        }
+
+        for &fd in &clients {
+            let rv = unsafe { libc::connect(fd, addr_ptr, addr_len) };
+            assert!(rv == 0 || (rv == -1 && test_utils::get_errno() == libc::EINPROGRESS));
+        }
+
+        // shadow needs to run events so that the connections are established
+        let rv = unsafe { libc::usleep(10000) };
+        assert_eq!(rv, 0);
+
+        let mut accepted = Vec::new();
+        for &fd in &listeners {
+            let mut count = 0;
+            loop {
+                let rv = unsafe { libc::accept(fd, std::ptr::null_mut(), std::ptr::null_mut()) };
+                if rv < 0 {
+                    test_utils::result_assert_eq(
+                        test_utils::get_errno(),
+                        libc::EAGAIN,
+                        "Unexpected accept() error",
+                    )?;
+                    break;
+                }
+                assert_eq!(unsafe { libc::close(rv) }, 0);
+                count += 1;
+            }
+            accepted.push(count);
+        }
+
+        test_utils::result_assert_eq(
+            accepted.iter().sum::<usize>(),
+            NUM_CLIENTS,
+            "Not all connections were accepted",
+        )?;
+        test_utils::result_assert(
+            accepted.iter().all(|&x| x > 0),
+            &format!("Connections were not spread across the listeners: {accepted:?}"),
+        )?;
+
+        Ok(())
+    })
+}
+
 /// Test accept after the client has connected and closed.
 fn test_after_client_closed(
     accept_fn: AcceptFn,
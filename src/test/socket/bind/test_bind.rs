@@ -178,6 +178,16 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
                     },
                     set![TestEnv::Libc, TestEnv::Shadow],
                 ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_reuse_addr"),
+                    move || test_reuse_addr(sock_type, flag),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_reuse_port"),
+                    move || test_reuse_port(sock_type, flag),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
             ]);
         }
     }
@@ -678,6 +688,106 @@ fn test_double_bind_loopback_and_any(
     })
 }
 
+// test binding sockets to the same address using SO_REUSEADDR
+fn test_reuse_addr(sock_type: libc::c_int, flag: libc::c_int) -> Result<(), String> {
+    let fd1 = unsafe { libc::socket(libc::AF_INET, sock_type | flag, 0) };
+    assert!(fd1 >= 0);
+    let fd2 = unsafe { libc::socket(libc::AF_INET, sock_type | flag, 0) };
+    assert!(fd2 >= 0);
+    let fd3 = unsafe { libc::socket(libc::AF_INET, sock_type | flag, 0) };
+    assert!(fd3 >= 0);
+
+    let addr = libc::sockaddr_in {
+        sin_family: libc::AF_INET as u16,
+        sin_port: 11111u16.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: libc::INADDR_LOOPBACK.to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+
+    let args = |fd| BindArguments {
+        fd,
+        addr: Some(SockAddr::Inet(addr)),
+        addr_len: std::mem::size_of_val(&addr) as u32,
+    };
+
+    test_utils::run_and_close_fds(&[fd1, fd2, fd3], || {
+        set_reuse_option(fd1, libc::SO_REUSEADDR)?;
+        set_reuse_option(fd2, libc::SO_REUSEADDR)?;
+
+        check_bind_call(&args(fd1), None)?;
+        check_bind_call(&args(fd2), None)?;
+
+        // the option must be set on every socket sharing the address
+        check_bind_call(&args(fd3), Some(libc::EADDRINUSE))?;
+
+        if sock_type == libc::SOCK_STREAM {
+            // only one of the sockets can listen
+            test_utils::check_system_call!(|| unsafe { libc::listen(fd1, 10) }, &[])?;
+            test_utils::check_system_call!(
+                || unsafe { libc::listen(fd2, 10) },
+                &[libc::EADDRINUSE]
+            )?;
+
+            // and no other sockets can bind to the address of a listening socket
+            set_reuse_option(fd3, libc::SO_REUSEADDR)?;
+            check_bind_call(&args(fd3), Some(libc::EADDRINUSE))?;
+        }
+
+        Ok(())
+    })
+}
+
+// test binding sockets to the same address using SO_REUSEPORT
+fn test_reuse_port(sock_type: libc::c_int, flag: libc::c_int) -> Result<(), String> {
+    let fd1 = unsafe { libc::socket(libc::AF_INET, sock_type | flag, 0) };
+    assert!(fd1 >= 0);
+    let fd2 = unsafe { libc::socket(libc::AF_INET, sock_type | flag, 0) };
+    assert!(fd2 >= 0);
+    let fd3 = unsafe { libc::socket(libc::AF_INET, sock_type | flag, 0) };
+    assert!(fd3 >= 0);
+
+    let addr = libc::sockaddr_in {
+        sin_family: libc::AF_INET as u16,
+        sin_port: 11111u16.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: libc::INADDR_LOOPBACK.to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+
+    let args = |fd| BindArguments {
+        fd,
+        addr: Some(SockAddr::Inet(addr)),
+        addr_len: std::mem::size_of_val(&addr) as u32,
+    };
+
+    test_utils::run_and_close_fds(&[fd1, fd2, fd3], || {
+        set_reuse_option(fd1, libc::SO_REUSEPORT)?;
+        set_reuse_option(fd2, libc::SO_REUSEPORT)?;
+
+        check_bind_call(&args(fd1), None)?;
+
+        if sock_type == libc::SOCK_STREAM {
+            // a listening socket can share its address with SO_REUSEPORT
+            test_utils::check_system_call!(|| unsafe { libc::listen(fd1, 10) }, &[])?;
+        }
+
+        check_bind_call(&args(fd2), None)?;
+
+        if sock_type == libc::SOCK_STREAM {
+            test_utils::check_system_call!(|| unsafe { libc::listen(fd2, 10) }, &[])?;
+        }
+
+        // SO_REUSEADDR isn't enough to join the group
+        set_reuse_option(fd3, libc::SO_REUSEADDR)?;
+        check_bind_call(&args(fd3), Some(libc::EADDRINUSE))?;
+
+        Ok(())
+    })
+}
+
 // test auto-binding (ex: a port of 0 for inet sockets)
 fn test_autobind(
     domain: libc::c_int,
@@ -721,6 +831,24 @@ fn test_autobind(
     test_utils::run_and_close_fds(&[fd], || check_bind_call(&args, None))
 }
 
+/// Enable the boolean socket option `optname` at the `SOL_SOCKET` level.
+fn set_reuse_option(fd: libc::c_int, optname: libc::c_int) -> Result<(), String> {
+    let val: libc::c_int = 1;
+    test_utils::check_system_call!(
+        || unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                optname,
+                &val as *const _ as *const libc::c_void,
+                std::mem::size_of_val(&val) as libc::socklen_t,
+            )
+        },
+        &[]
+    )?;
+    Ok(())
+}
+
 fn check_bind_call(
     args: &BindArguments,
     expected_errno: Option<libc::c_int>,
@@ -0,0 +1,420 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::io::{IoSlice, IoSliceMut};
+use std::os::unix::io::RawFd;
+
+use nix::cmsg_space;
+use nix::fcntl::{FcntlArg, FdFlag};
+use nix::sys::socket::{
+    getsockopt, recvmsg, sendmsg, setsockopt, socketpair, sockopt, AddressFamily, ControlMessage,
+    ControlMessageOwned, MsgFlags, SockFlag, SockType, UnixAddr, UnixCredentials,
+};
+use nix::unistd;
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new(
+            "test_ctrunc",
+            test_ctrunc,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_no_control_buffer",
+            test_no_control_buffer,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_cmsg_cloexec",
+            test_cmsg_cloexec,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_stream_boundary",
+            test_stream_boundary,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_bad_control_buffer",
+            test_bad_control_buffer,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_bad_fd",
+            test_bad_fd,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_so_peercred",
+            test_so_peercred,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_so_passcred",
+            test_so_passcred,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_wrong_credentials",
+            test_wrong_credentials,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+    ];
+
+    for &sock_type in [SockType::Stream, SockType::Datagram, SockType::SeqPacket].iter() {
+        let append_args = |s| format!("{s} <type={sock_type:?}>");
+
+        tests.extend(vec![test_utils::ShadowTest::new(
+            &append_args("test_scm_rights"),
+            move || test_scm_rights(sock_type),
+            set![TestEnv::Libc, TestEnv::Shadow],
+        )]);
+    }
+
+    tests
+}
+
+fn pair(sock_type: SockType) -> Result<(RawFd, RawFd), String> {
+    socketpair(AddressFamily::Unix, sock_type, None, SockFlag::empty()).map_err(|e| e.to_string())
+}
+
+fn send_with(fd: RawFd, buf: &[u8], cmsgs: &[ControlMessage]) -> nix::Result<usize> {
+    sendmsg::<UnixAddr>(fd, &[IoSlice::new(buf)], cmsgs, MsgFlags::empty(), None)
+}
+
+/// Receive a message, returning the bytes read, the received fds and credentials, and the msg
+/// flags.
+fn recv_with(
+    fd: RawFd,
+    buf: &mut [u8],
+    cmsg_buf: Option<&mut Vec<u8>>,
+    flags: MsgFlags,
+) -> Result<(usize, Vec<RawFd>, Option<UnixCredentials>, MsgFlags), String> {
+    let mut iov = [IoSliceMut::new(buf)];
+    let msg = recvmsg::<UnixAddr>(fd, &mut iov, cmsg_buf, flags).map_err(|e| e.to_string())?;
+
+    let mut fds = Vec::new();
+    let mut creds = None;
+    for cmsg in msg.cmsgs() {
+        match cmsg {
+            ControlMessageOwned::ScmRights(x) => fds.extend(x),
+            ControlMessageOwned::ScmCredentials(x) => creds = Some(x),
+            x => return Err(format!("Unexpected control message {x:?}")),
+        }
+    }
+
+    Ok((msg.bytes, fds, creds, msg.flags))
+}
+
+fn close_all(fds: &[RawFd]) {
+    for fd in fds {
+        let _ = unistd::close(*fd);
+    }
+}
+
+/// Test that a file passed over a unix socket refers to the same open file.
+fn test_scm_rights(sock_type: SockType) -> Result<(), String> {
+    let (fd_1, fd_2) = pair(sock_type)?;
+    let (pipe_read, pipe_write) = unistd::pipe().map_err(|e| e.to_string())?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2, pipe_read, pipe_write], || {
+        let fds = [pipe_write];
+        let rv = send_with(fd_1, b"hello", &[ControlMessage::ScmRights(&fds)]);
+        test_utils::result_assert_eq(rv, Ok(5), "Unexpected sendmsg() result")?;
+
+        // close our copy of the write end, so only the received descriptor refers to it
+        unistd::close(pipe_write).map_err(|e| e.to_string())?;
+
+        let mut buf = [0u8; 10];
+        let mut cmsg_buf = cmsg_space!([RawFd; 1]);
+        let (len, fds, creds, flags) =
+            recv_with(fd_2, &mut buf, Some(&mut cmsg_buf), MsgFlags::empty())?;
+
+        test_utils::result_assert_eq(&buf[..len], b"hello", "Unexpected data")?;
+        test_utils::result_assert_eq(fds.len(), 1, "Expected one fd")?;
+        test_utils::result_assert(creds.is_none(), "Unexpected credentials")?;
+        test_utils::result_assert(
+            !flags.contains(MsgFlags::MSG_CTRUNC),
+            "Unexpected MSG_CTRUNC",
+        )?;
+
+        let received = fds[0];
+        let rv = unistd::write(received, b"xyz");
+        close_all(&[received]);
+        test_utils::result_assert_eq(rv, Ok(3), "Couldn't write to the received fd")?;
+
+        let mut buf = [0u8; 10];
+        let rv = unistd::read(pipe_read, &mut buf);
+        test_utils::result_assert_eq(rv, Ok(3), "Couldn't read from the pipe")?;
+        test_utils::result_assert_eq(&buf[..3], b"xyz", "Unexpected pipe data")?;
+
+        Ok(())
+    })
+}
+
+/// Test that files that don't fit in the control buffer are discarded and `MSG_CTRUNC` is set.
+fn test_ctrunc() -> Result<(), String> {
+    let (fd_1, fd_2) = pair(SockType::Datagram)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        let fds = [fd_1, fd_2];
+        let rv = send_with(fd_1, b"a", &[ControlMessage::ScmRights(&fds)]);
+        test_utils::result_assert_eq(rv, Ok(1), "Unexpected sendmsg() result")?;
+
+        let mut buf = [0u8; 10];
+        let mut cmsg_buf = cmsg_space!([RawFd; 1]);
+        let (len, fds, _, flags) =
+            recv_with(fd_2, &mut buf, Some(&mut cmsg_buf), MsgFlags::empty())?;
+        close_all(&fds);
+
+        test_utils::result_assert_eq(len, 1, "Unexpected number of bytes")?;
+        test_utils::result_assert_eq(fds.len(), 1, "Expected only one fd")?;
+        test_utils::result_assert(flags.contains(MsgFlags::MSG_CTRUNC), "Expected MSG_CTRUNC")?;
+
+        Ok(())
+    })
+}
+
+/// Test that receiving without a control buffer discards the files and sets `MSG_CTRUNC`.
+fn test_no_control_buffer() -> Result<(), String> {
+    let (fd_1, fd_2) = pair(SockType::Stream)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        let fds = [fd_1];
+        let rv = send_with(fd_1, b"a", &[ControlMessage::ScmRights(&fds)]);
+        test_utils::result_assert_eq(rv, Ok(1), "Unexpected sendmsg() result")?;
+
+        let mut buf = [0u8; 10];
+        let (len, fds, _, flags) = recv_with(fd_2, &mut buf, None, MsgFlags::empty())?;
+
+        test_utils::result_assert_eq(len, 1, "Unexpected number of bytes")?;
+        test_utils::result_assert(fds.is_empty(), "Unexpected fds")?;
+        test_utils::result_assert(flags.contains(MsgFlags::MSG_CTRUNC), "Expected MSG_CTRUNC")?;
+
+        Ok(())
+    })
+}
+
+/// Test that `MSG_CMSG_CLOEXEC` sets `FD_CLOEXEC` on the received descriptors.
+fn test_cmsg_cloexec() -> Result<(), String> {
+    let (fd_1, fd_2) = pair(SockType::Stream)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        for cloexec in [false, true] {
+            let fds = [fd_1];
+            let rv = send_with(fd_1, b"a", &[ControlMessage::ScmRights(&fds)]);
+            test_utils::result_assert_eq(rv, Ok(1), "Unexpected sendmsg() result")?;
+
+            let flags = if cloexec {
+                MsgFlags::MSG_CMSG_CLOEXEC
+            } else {
+                MsgFlags::empty()
+            };
+
+            let mut buf = [0u8; 10];
+            let mut cmsg_buf = cmsg_space!([RawFd; 1]);
+            let (_, fds, _, _) = recv_with(fd_2, &mut buf, Some(&mut cmsg_buf), flags)?;
+            test_utils::result_assert_eq(fds.len(), 1, "Expected one fd")?;
+
+            let fd_flags = nix::fcntl::fcntl(fds[0], FcntlArg::F_GETFD);
+            close_all(&fds);
+            let fd_flags = FdFlag::from_bits_truncate(fd_flags.map_err(|e| e.to_string())?);
+
+            test_utils::result_assert_eq(
+                fd_flags.contains(FdFlag::FD_CLOEXEC),
+                cloexec,
+                "Unexpected FD_CLOEXEC flag",
+            )?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Test that a read on a stream socket stops after data that was sent with files.
+fn test_stream_boundary() -> Result<(), String> {
+    let (fd_1, fd_2) = pair(SockType::Stream)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        let fds = [fd_1];
+        let rv = send_with(fd_1, b"ab", &[]);
+        test_utils::result_assert_eq(rv, Ok(2), "Unexpected sendmsg() result")?;
+        let rv = send_with(fd_1, b"cd", &[ControlMessage::ScmRights(&fds)]);
+        test_utils::result_assert_eq(rv, Ok(2), "Unexpected sendmsg() result")?;
+        let rv = send_with(fd_1, b"ef", &[]);
+        test_utils::result_assert_eq(rv, Ok(2), "Unexpected sendmsg() result")?;
+
+        let mut buf = [0u8; 10];
+        let mut cmsg_buf = cmsg_space!([RawFd; 1]);
+
+        let (len, fds, _, _) = recv_with(fd_2, &mut buf, Some(&mut cmsg_buf), MsgFlags::empty())?;
+        close_all(&fds);
+        test_utils::result_assert_eq(&buf[..len], b"abcd", "Unexpected first read")?;
+        test_utils::result_assert_eq(fds.len(), 1, "Expected an fd in the first read")?;
+
+        let (len, fds, _, _) = recv_with(fd_2, &mut buf, Some(&mut cmsg_buf), MsgFlags::empty())?;
+        test_utils::result_assert_eq(&buf[..len], b"ef", "Unexpected second read")?;
+        test_utils::result_assert(fds.is_empty(), "Unexpected fds in the second read")?;
+
+        Ok(())
+    })
+}
+
+/// Test that files received with an invalid control buffer are closed rather than leaked.
+fn test_bad_control_buffer() -> Result<(), String> {
+    let (fd_1, fd_2) = pair(SockType::Datagram)?;
+    let (pipe_read, pipe_write) =
+        unistd::pipe2(nix::fcntl::OFlag::O_NONBLOCK).map_err(|e| e.to_string())?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2, pipe_read, pipe_write], || {
+        let fds = [pipe_write];
+        let rv = send_with(fd_1, b"a", &[ControlMessage::ScmRights(&fds)]);
+        test_utils::result_assert_eq(rv, Ok(1), "Unexpected sendmsg() result")?;
+
+        // close our copy of the write end, so only the received descriptor refers to it
+        unistd::close(pipe_write).map_err(|e| e.to_string())?;
+
+        let mut buf = [0u8; 10];
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut _,
+            iov_len: buf.len(),
+        };
+        let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = std::ptr::null_mut();
+        msg.msg_controllen = cmsg_space!([RawFd; 1]).len();
+
+        test_utils::check_system_call!(
+            || unsafe { libc::recvmsg(fd_2, &mut msg, 0) },
+            &[libc::EFAULT],
+        )?;
+
+        // the received write end was closed, so the pipe has no writers
+        let rv = unistd::read(pipe_read, &mut buf);
+        test_utils::result_assert_eq(rv, Ok(0), "Expected the pipe to be closed")?;
+
+        Ok(())
+    })
+}
+
+/// Test that passing an invalid descriptor fails with `EBADF`.
+fn test_bad_fd() -> Result<(), String> {
+    let (fd_1, fd_2) = pair(SockType::Stream)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        let fds = [fd_1, 1000];
+        let rv = send_with(fd_1, b"a", &[ControlMessage::ScmRights(&fds)]);
+        test_utils::result_assert_eq(rv, Err(nix::errno::Errno::EBADF), "Expected EBADF")?;
+
+        Ok(())
+    })
+}
+
+/// Test that `SO_PEERCRED` returns the credentials of the peer's process.
+fn test_so_peercred() -> Result<(), String> {
+    let (fd_1, fd_2) = pair(SockType::Stream)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        let creds = getsockopt(fd_1, sockopt::PeerCredentials).map_err(|e| e.to_string())?;
+
+        test_utils::result_assert_eq(creds.pid(), unistd::getpid().as_raw(), "Unexpected pid")?;
+        test_utils::result_assert_eq(creds.uid(), unistd::getuid().as_raw(), "Unexpected uid")?;
+        test_utils::result_assert_eq(creds.gid(), unistd::getgid().as_raw(), "Unexpected gid")?;
+
+        Ok(())
+    })
+}
+
+/// Test that credentials are only received with `SO_PASSCRED`, and are received even if they
+/// weren't explicitly sent.
+fn test_so_passcred() -> Result<(), String> {
+    let (fd_1, fd_2) = pair(SockType::Datagram)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        let passcred = getsockopt(fd_2, sockopt::PassCred).map_err(|e| e.to_string())?;
+        test_utils::result_assert(!passcred, "SO_PASSCRED should be disabled by default")?;
+
+        let mut buf = [0u8; 10];
+        let mut cmsg_buf = cmsg_space!(libc::ucred);
+
+        let rv = send_with(fd_1, b"a", &[]);
+        test_utils::result_assert_eq(rv, Ok(1), "Unexpected sendmsg() result")?;
+        let (_, _, creds, _) = recv_with(fd_2, &mut buf, Some(&mut cmsg_buf), MsgFlags::empty())?;
+        test_utils::result_assert(creds.is_none(), "Unexpected credentials")?;
+
+        setsockopt(fd_2, sockopt::PassCred, &true).map_err(|e| e.to_string())?;
+
+        let rv = send_with(fd_1, b"a", &[]);
+        test_utils::result_assert_eq(rv, Ok(1), "Unexpected sendmsg() result")?;
+        let (_, _, creds, _) = recv_with(fd_2, &mut buf, Some(&mut cmsg_buf), MsgFlags::empty())?;
+        let Some(creds) = creds else {
+            return Err("Expected credentials".to_string());
+        };
+
+        test_utils::result_assert_eq(creds.pid(), unistd::getpid().as_raw(), "Unexpected pid")?;
+        test_utils::result_assert_eq(creds.uid(), unistd::getuid().as_raw(), "Unexpected uid")?;
+
+        // explicitly sent credentials should also be received
+        let sent = UnixCredentials::new();
+        let rv = send_with(fd_1, b"a", &[ControlMessage::ScmCredentials(&sent)]);
+        test_utils::result_assert_eq(rv, Ok(1), "Unexpected sendmsg() result")?;
+        let (_, _, creds, _) = recv_with(fd_2, &mut buf, Some(&mut cmsg_buf), MsgFlags::empty())?;
+        test_utils::result_assert_eq(
+            creds.map(|x| x.pid()),
+            Some(sent.pid()),
+            "Unexpected credentials",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that an unprivileged process can't send another process' credentials.
+fn test_wrong_credentials() -> Result<(), String> {
+    // root is allowed to send any credentials
+    if unistd::geteuid().is_root() {
+        return Ok(());
+    }
+
+    let (fd_1, fd_2) = pair(SockType::Datagram)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        let creds = libc::ucred {
+            pid: unistd::getpid().as_raw() + 1,
+            uid: unistd::getuid().as_raw(),
+            gid: unistd::getgid().as_raw(),
+        };
+        let creds = UnixCredentials::from(creds);
+
+        let rv = send_with(fd_1, b"a", &[ControlMessage::ScmCredentials(&creds)]);
+        test_utils::result_assert_eq(rv, Err(nix::errno::Errno::EPERM), "Expected EPERM")?;
+
+        Ok(())
+    })
+}
@@ -69,10 +69,8 @@ enum SendRecvMethod {
     /// For `sendto()`/`recvfrom()`.
     ToFrom,
     /// For `sendmsg()`/`recvmsg()`.
-    #[allow(dead_code)]
     Msg,
     /// For `sendmmsg()`/`recvmmsg()`.
-    #[allow(dead_code)]
     Mmsg,
 }
 
@@ -103,8 +101,7 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
     let sys_methods = [
         SendRecvMethod::ToFrom,
         SendRecvMethod::Msg,
-        // TODO: support sendmmsg/recvmmsg
-        //SendRecvMethod::Mmsg,
+        SendRecvMethod::Mmsg,
     ];
 
     for &sys_method in sys_methods.iter() {
@@ -420,6 +417,90 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
         set![TestEnv::Libc, TestEnv::Shadow],
     )]);
 
+    for &nodelay in &[false, true] {
+        tests.extend(vec![test_utils::ShadowTest::new(
+            &format!("test_tcp_small_writes <nodelay={nodelay}>"),
+            move || test_tcp_small_writes(nodelay),
+            set![TestEnv::Libc, TestEnv::Shadow],
+        )]);
+    }
+
+    tests.extend(vec![
+        test_utils::ShadowTest::new(
+            "test_tcp_cork",
+            test_tcp_cork,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        // linux doesn't reliably push corked data after exactly 200 ms
+        test_utils::ShadowTest::new(
+            "test_tcp_cork_timeout",
+            test_tcp_cork_timeout,
+            set![TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_tcp_info_after_transfer",
+            test_tcp_info_after_transfer,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_tcp_keepalive_probes",
+            test_tcp_keepalive_probes,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+    ]);
+
+    let init_methods = [
+        SocketInitMethod::Inet,
+        SocketInitMethod::Unix,
+        SocketInitMethod::UnixSocketpair,
+    ];
+
+    for &init_method in init_methods.iter() {
+        for &flag in &[0, libc::SOCK_NONBLOCK] {
+            // add details to the test names to avoid duplicates
+            let append_args = |s| format!("{s} <init_method={init_method:?}, flag={flag}>");
+
+            tests.extend(vec![
+                test_utils::ShadowTest::new(
+                    &append_args("test_mmsg_batch"),
+                    move || test_mmsg_batch(init_method, flag),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_recvmmsg_timeout"),
+                    move || test_recvmmsg_timeout(init_method, flag),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+            ]);
+        }
+
+        let append_args = |s| format!("{s} <init_method={init_method:?}>");
+
+        tests.extend(vec![
+            test_utils::ShadowTest::new(
+                &append_args("test_sendmmsg_blocking"),
+                move || test_sendmmsg_blocking(init_method),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_recvmmsg_blocking"),
+                move || test_recvmmsg_blocking(init_method),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_recvmmsg_timeout_from_entry"),
+                move || test_recvmmsg_timeout_from_entry(init_method),
+                set![TestEnv::Libc, TestEnv::Shadow],
+            ),
+            // linux would block until a later message arrives
+            test_utils::ShadowTest::new(
+                &append_args("test_recvmmsg_blocking_timeout"),
+                move || test_recvmmsg_blocking_timeout(init_method),
+                set![TestEnv::Shadow],
+            ),
+        ]);
+    }
+
     tests
 }
 
@@ -1872,6 +1953,366 @@ fn test_unix_buffer_full(
     })
 }
 
+/// Send the messages in `msgs` with a single `sendmmsg()`, and return its return value.
+fn sendmmsg_helper(fd: libc::c_int, msgs: &[&[u8]]) -> libc::c_int {
+    let mut iovs: Vec<libc::iovec> = msgs
+        .iter()
+        .map(|x| libc::iovec {
+            // casting a const pointer to a mut pointer, but syscall should not mutate data
+            iov_base: x.as_ptr() as *mut core::ffi::c_void,
+            iov_len: x.len(),
+        })
+        .collect();
+
+    let mut mmsgs: Vec<libc::mmsghdr> = iovs
+        .iter_mut()
+        .map(|iov| libc::mmsghdr {
+            msg_hdr: libc::msghdr {
+                msg_name: std::ptr::null_mut(),
+                msg_namelen: 0,
+                msg_iov: iov,
+                msg_iovlen: 1,
+                msg_control: std::ptr::null_mut(),
+                msg_controllen: 0,
+                msg_flags: 0,
+            },
+            msg_len: 0,
+        })
+        .collect();
+
+    let rv = unsafe { libc::sendmmsg(fd, mmsgs.as_mut_ptr(), mmsgs.len() as u32, 0) };
+
+    // each message should have been sent completely
+    for (mmsg, msg) in mmsgs.iter().zip(msgs).take(std::cmp::max(rv, 0) as usize) {
+        assert_eq!(mmsg.msg_len as usize, msg.len());
+    }
+
+    rv
+}
+
+/// Receive into `bufs` with a single `recvmmsg()`. Returns the return value and the length of each
+/// received message.
+fn recvmmsg_helper(
+    fd: libc::c_int,
+    bufs: &mut [[u8; 10]],
+    flags: libc::c_int,
+    timeout: Option<&mut libc::timespec>,
+) -> (libc::c_int, Vec<usize>) {
+    let mut iovs: Vec<libc::iovec> = bufs
+        .iter_mut()
+        .map(|x| libc::iovec {
+            iov_base: x.as_mut_ptr() as *mut core::ffi::c_void,
+            iov_len: x.len(),
+        })
+        .collect();
+
+    let mut mmsgs: Vec<libc::mmsghdr> = iovs
+        .iter_mut()
+        .map(|iov| libc::mmsghdr {
+            msg_hdr: libc::msghdr {
+                msg_name: std::ptr::null_mut(),
+                msg_namelen: 0,
+                msg_iov: iov,
+                msg_iovlen: 1,
+                msg_control: std::ptr::null_mut(),
+                msg_controllen: 0,
+                msg_flags: 0,
+            },
+            msg_len: 0,
+        })
+        .collect();
+
+    let timeout = match timeout {
+        Some(x) => x as *mut libc::timespec,
+        None => std::ptr::null_mut(),
+    };
+
+    let rv = unsafe {
+        libc::recvmmsg(
+            fd,
+            mmsgs.as_mut_ptr(),
+            mmsgs.len() as u32,
+            flags,
+            timeout as *mut _,
+        )
+    };
+
+    let lens = mmsgs
+        .iter()
+        .take(std::cmp::max(rv, 0) as usize)
+        .map(|x| x.msg_len as usize)
+        .collect();
+
+    (rv, lens)
+}
+
+/// Test sending and receiving several datagrams with single `sendmmsg()` and `recvmmsg()` calls.
+fn test_mmsg_batch(init_method: SocketInitMethod, flag: libc::c_int) -> Result<(), String> {
+    let (fd_client, fd_server) = socket_init_helper(
+        init_method,
+        libc::SOCK_DGRAM,
+        flag,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        // sending zero messages is a no-op
+        let rv = sendmmsg_helper(fd_client, &[]);
+        test_utils::result_assert_eq(rv, 0, "Unexpected sendmmsg() return value")?;
+
+        let rv = sendmmsg_helper(fd_client, &[b"a", b"bb", b"ccc"]);
+        test_utils::result_assert_eq(rv, 3, "Unexpected sendmmsg() return value")?;
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
+        // there are more buffers than messages, so we shouldn't wait for more messages after the
+        // first
+        let mut bufs = [[0u8; 10]; 5];
+        let (rv, lens) = recvmmsg_helper(fd_server, &mut bufs, libc::MSG_WAITFORONE, None);
+        test_utils::result_assert_eq(rv, 3, "Unexpected recvmmsg() return value")?;
+        test_utils::result_assert_eq(lens, vec![1, 2, 3], "Unexpected message lengths")?;
+        test_utils::result_assert_eq(&bufs[0][..1], b"a", "Unexpected data")?;
+        test_utils::result_assert_eq(&bufs[1][..2], b"bb", "Unexpected data")?;
+        test_utils::result_assert_eq(&bufs[2][..3], b"ccc", "Unexpected data")?;
+
+        // no messages are available
+        let (rv, _) = recvmmsg_helper(fd_server, &mut bufs, libc::MSG_DONTWAIT, None);
+        test_utils::result_assert_eq(rv, -1, "Unexpected recvmmsg() return value")?;
+        test_utils::result_assert_eq(
+            test_utils::get_errno(),
+            libc::EAGAIN,
+            "Unexpected recvmmsg() errno",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that `recvmmsg()` stops receiving messages after the timeout has expired.
+fn test_recvmmsg_timeout(init_method: SocketInitMethod, flag: libc::c_int) -> Result<(), String> {
+    let (fd_client, fd_server) = socket_init_helper(
+        init_method,
+        libc::SOCK_DGRAM,
+        flag,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        let rv = sendmmsg_helper(fd_client, &[b"a", b"bb", b"ccc"]);
+        test_utils::result_assert_eq(rv, 3, "Unexpected sendmmsg() return value")?;
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
+        // the timeout is only checked after a message is received, so a zero timeout will receive
+        // exactly one message
+        let mut timeout = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        let mut bufs = [[0u8; 10]; 5];
+        let (rv, lens) = recvmmsg_helper(fd_server, &mut bufs, 0, Some(&mut timeout));
+        test_utils::result_assert_eq(rv, 1, "Unexpected recvmmsg() return value")?;
+        test_utils::result_assert_eq(lens, vec![1], "Unexpected message lengths")?;
+
+        // a long timeout receives the remaining messages, and the remaining time is written back
+        let mut timeout = libc::timespec {
+            tv_sec: 100,
+            tv_nsec: 0,
+        };
+        let (rv, lens) = recvmmsg_helper(
+            fd_server,
+            &mut bufs,
+            libc::MSG_WAITFORONE,
+            Some(&mut timeout),
+        );
+        test_utils::result_assert_eq(rv, 2, "Unexpected recvmmsg() return value")?;
+        test_utils::result_assert_eq(lens, vec![2, 3], "Unexpected message lengths")?;
+        test_utils::result_assert(
+            timeout.tv_sec > 0 && timeout.tv_sec <= 100,
+            "Unexpected remaining time",
+        )?;
+
+        // an invalid timeout
+        let mut timeout = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 1_000_000_000,
+        };
+        let (rv, _) = recvmmsg_helper(fd_server, &mut bufs, 0, Some(&mut timeout));
+        test_utils::result_assert_eq(rv, -1, "Unexpected recvmmsg() return value")?;
+        test_utils::result_assert_eq(
+            test_utils::get_errno(),
+            libc::EINVAL,
+            "Unexpected recvmmsg() errno",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that a blocking `sendmmsg()` waits until every message can be sent when the socket's
+/// buffers are full.
+fn test_sendmmsg_blocking(init_method: SocketInitMethod) -> Result<(), String> {
+    let (fd_client, fd_server) = socket_init_helper(
+        init_method,
+        libc::SOCK_DGRAM,
+        0,
+        /* bind_client = */ false,
+    );
+
+    // more data than fits in the buffers (except for udp, which drops the messages that don't fit)
+    let msg = vec![1u8; 10_000];
+    let msgs: Vec<&[u8]> = vec![&msg; 200];
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        std::thread::scope(|scope| {
+            let handle = scope.spawn(|| sendmmsg_helper(fd_client, &msgs));
+
+            // wait until the sender has blocked, then read every message
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            if init_method != SocketInitMethod::Inet {
+                let mut buf = vec![0u8; msg.len()];
+                for _ in 0..msgs.len() {
+                    let rv =
+                        unsafe { libc::recv(fd_server, buf.as_mut_ptr() as *mut _, buf.len(), 0) };
+                    test_utils::result_assert_eq(rv, msg.len() as isize, "Unexpected recv()")?;
+                }
+            }
+
+            let rv = handle.join().unwrap();
+            test_utils::result_assert_eq(
+                rv,
+                msgs.len() as libc::c_int,
+                "Unexpected sendmmsg() return value",
+            )?;
+
+            Ok(())
+        })
+    })
+}
+
+/// Test that without `MSG_WAITFORONE`, a blocking `recvmmsg()` waits for every message.
+fn test_recvmmsg_blocking(init_method: SocketInitMethod) -> Result<(), String> {
+    let (fd_client, fd_server) = socket_init_helper(
+        init_method,
+        libc::SOCK_DGRAM,
+        0,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        std::thread::scope(|scope| {
+            // send each message 10 ms after the previous one
+            let handle = scope.spawn(move || -> Result<(), String> {
+                for msg in [&b"a"[..], b"bb", b"ccc"] {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    let rv = sendmmsg_helper(fd_client, &[msg]);
+                    test_utils::result_assert_eq(rv, 1, "Unexpected sendmmsg() return value")?;
+                }
+                Ok(())
+            });
+
+            let mut bufs = [[0u8; 10]; 3];
+            let (rv, lens) = recvmmsg_helper(fd_server, &mut bufs, 0, None);
+
+            handle.join().unwrap()?;
+
+            test_utils::result_assert_eq(rv, 3, "Unexpected recvmmsg() return value")?;
+            test_utils::result_assert_eq(lens, vec![1, 2, 3], "Unexpected message lengths")?;
+            test_utils::result_assert_eq(&bufs[0][..1], b"a", "Unexpected data")?;
+            test_utils::result_assert_eq(&bufs[1][..2], b"bb", "Unexpected data")?;
+            test_utils::result_assert_eq(&bufs[2][..3], b"ccc", "Unexpected data")?;
+
+            Ok(())
+        })
+    })
+}
+
+/// Test that the `recvmmsg()` timeout starts when the syscall is made, not when the first message
+/// is received.
+fn test_recvmmsg_timeout_from_entry(init_method: SocketInitMethod) -> Result<(), String> {
+    let (fd_client, fd_server) = socket_init_helper(
+        init_method,
+        libc::SOCK_DGRAM,
+        0,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        std::thread::scope(|scope| {
+            // send two messages after the timeout has expired
+            let handle = scope.spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                sendmmsg_helper(fd_client, &[b"a", b"bc"])
+            });
+
+            // we block for the first message, but the timeout has expired once it's received
+            let mut timeout = libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 50_000_000,
+            };
+            let mut bufs = [[0u8; 10]; 3];
+            let (rv, lens) = recvmmsg_helper(fd_server, &mut bufs, 0, Some(&mut timeout));
+
+            test_utils::result_assert_eq(rv, 1, "Unexpected recvmmsg() return value")?;
+            test_utils::result_assert_eq(lens, vec![1], "Unexpected message lengths")?;
+            test_utils::result_assert_eq(
+                (timeout.tv_sec, timeout.tv_nsec),
+                (0, 0),
+                "Unexpected remaining time",
+            )?;
+
+            let rv = handle.join().unwrap();
+            test_utils::result_assert_eq(rv, 2, "Unexpected sendmmsg() return value")?;
+
+            Ok(())
+        })
+    })
+}
+
+/// Test that the `recvmmsg()` timeout limits how long we block for messages after the first.
+fn test_recvmmsg_blocking_timeout(init_method: SocketInitMethod) -> Result<(), String> {
+    let (fd_client, fd_server) = socket_init_helper(
+        init_method,
+        libc::SOCK_DGRAM,
+        0,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_client, fd_server], || {
+        let rv = sendmmsg_helper(fd_client, &[b"a"]);
+        test_utils::result_assert_eq(rv, 1, "Unexpected sendmmsg() return value")?;
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
+        // we block for the second message until the timeout expires
+        let mut timeout = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 50_000_000,
+        };
+        let mut bufs = [[0u8; 10]; 3];
+        let time_start = std::time::Instant::now();
+        let (rv, lens) = recvmmsg_helper(fd_server, &mut bufs, 0, Some(&mut timeout));
+        let elapsed = time_start.elapsed();
+
+        test_utils::result_assert_eq(rv, 1, "Unexpected recvmmsg() return value")?;
+        test_utils::result_assert_eq(lens, vec![1], "Unexpected message lengths")?;
+        test_utils::result_assert(
+            elapsed >= std::time::Duration::from_millis(50),
+            "recvmmsg() returned before the timeout",
+        )?;
+        test_utils::result_assert_eq(
+            (timeout.tv_sec, timeout.tv_nsec),
+            (0, 0),
+            "Unexpected remaining time",
+        )?;
+
+        Ok(())
+    })
+}
+
 /// Test the behaviour of unix dgram sockets when there are multiple senders.
 fn test_unix_dgram_multiple_senders() -> Result<(), String> {
     // a single destination socket
@@ -1961,6 +2402,305 @@ fn test_unix_dgram_multiple_senders() -> Result<(), String> {
     Ok(())
 }
 
+/// Set an integer socket option, panicking on failure.
+fn set_int_sockopt(fd: libc::c_int, level: libc::c_int, optname: libc::c_int, val: libc::c_int) {
+    let rv = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            optname,
+            &val as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of_val(&val) as libc::socklen_t,
+        )
+    };
+    assert_eq!(rv, 0);
+}
+
+/// Test that many small writes are all delivered, with and without Nagle's algorithm.
+fn test_tcp_small_writes(nodelay: bool) -> Result<(), String> {
+    let (fd_client, fd_peer) = socket_init_helper(
+        SocketInitMethod::Inet,
+        libc::SOCK_STREAM,
+        libc::SOCK_NONBLOCK,
+        /* bind_client = */ false,
+    );
+
+    set_int_sockopt(fd_client, libc::SOL_TCP, libc::TCP_NODELAY, nodelay.into());
+
+    test_utils::run_and_close_fds(&[fd_client, fd_peer], || {
+        let send_buf: Vec<u8> = (0..100).collect();
+
+        for byte in &send_buf {
+            let rv = nix::unistd::write(fd_client, std::slice::from_ref(byte));
+            test_utils::result_assert_eq(rv, Ok(1), "Unexpected write() result")?;
+        }
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(100_000) }, 0);
+
+        let mut recv_buf = vec![0u8; 200];
+        let mut received = 0;
+        while received < send_buf.len() {
+            match nix::unistd::read(fd_peer, &mut recv_buf[received..]) {
+                Ok(0) => return Err("Unexpected EOF".to_string()),
+                Ok(n) => received += n,
+                Err(e) => return Err(format!("Unexpected read() error: {e}")),
+            }
+        }
+
+        test_utils::result_assert_eq(&recv_buf[..received], &send_buf[..], "Unexpected data")?;
+
+        Ok(())
+    })
+}
+
+/// Test that corked data is held until the socket is uncorked.
+fn test_tcp_cork() -> Result<(), String> {
+    let (fd_client, fd_peer) = socket_init_helper(
+        SocketInitMethod::Inet,
+        libc::SOCK_STREAM,
+        libc::SOCK_NONBLOCK,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_client, fd_peer], || {
+        set_int_sockopt(fd_client, libc::SOL_TCP, libc::TCP_CORK, 1);
+
+        let rv = nix::unistd::write(fd_client, b"hello");
+        test_utils::result_assert_eq(rv, Ok(5), "Unexpected write() result")?;
+        let rv = nix::unistd::write(fd_client, b" world");
+        test_utils::result_assert_eq(rv, Ok(6), "Unexpected write() result")?;
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
+        // the partial segment is held back while corked
+        let mut buf = [0u8; 20];
+        let rv = nix::unistd::read(fd_peer, &mut buf);
+        test_utils::result_assert_eq(
+            rv,
+            Err(nix::errno::Errno::EAGAIN),
+            "Unexpected read() result",
+        )?;
+
+        set_int_sockopt(fd_client, libc::SOL_TCP, libc::TCP_CORK, 0);
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
+        // both writes are sent together after uncorking
+        let rv = nix::unistd::read(fd_peer, &mut buf);
+        test_utils::result_assert_eq(rv, Ok(11), "Unexpected read() result")?;
+        test_utils::result_assert_eq(&buf[..11], b"hello world", "Unexpected data")?;
+
+        Ok(())
+    })
+}
+
+/// Test that corked data is sent once the 200 ms cork timer expires.
+fn test_tcp_cork_timeout() -> Result<(), String> {
+    let (fd_client, fd_peer) = socket_init_helper(
+        SocketInitMethod::Inet,
+        libc::SOCK_STREAM,
+        libc::SOCK_NONBLOCK,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_client, fd_peer], || {
+        set_int_sockopt(fd_client, libc::SOL_TCP, libc::TCP_CORK, 1);
+
+        let rv = nix::unistd::write(fd_client, b"hello");
+        test_utils::result_assert_eq(rv, Ok(5), "Unexpected write() result")?;
+
+        assert_eq!(unsafe { libc::usleep(300_000) }, 0);
+
+        let mut buf = [0u8; 20];
+        let rv = nix::unistd::read(fd_peer, &mut buf);
+        test_utils::result_assert_eq(rv, Ok(5), "Unexpected read() result")?;
+
+        Ok(())
+    })
+}
+
+/// The beginning of linux's `struct tcp_info`, which the libc crate doesn't provide.
+#[repr(C)]
+#[derive(Debug, Default)]
+#[allow(dead_code)]
+struct TcpInfo {
+    tcpi_state: u8,
+    tcpi_ca_state: u8,
+    tcpi_retransmits: u8,
+    tcpi_probes: u8,
+    tcpi_backoff: u8,
+    tcpi_options: u8,
+    tcpi_wscale: u8,
+    tcpi_flags: u8,
+    tcpi_rto: u32,
+    tcpi_ato: u32,
+    tcpi_snd_mss: u32,
+    tcpi_rcv_mss: u32,
+    tcpi_unacked: u32,
+    tcpi_sacked: u32,
+    tcpi_lost: u32,
+    tcpi_retrans: u32,
+    tcpi_fackets: u32,
+    tcpi_last_data_sent: u32,
+    tcpi_last_ack_sent: u32,
+    tcpi_last_data_recv: u32,
+    tcpi_last_ack_recv: u32,
+    tcpi_pmtu: u32,
+    tcpi_rcv_ssthresh: u32,
+    tcpi_rtt: u32,
+    tcpi_rttvar: u32,
+    tcpi_snd_ssthresh: u32,
+    tcpi_snd_cwnd: u32,
+    tcpi_advmss: u32,
+    tcpi_reordering: u32,
+    tcpi_rcv_rtt: u32,
+    tcpi_rcv_space: u32,
+    tcpi_total_retrans: u32,
+    tcpi_pacing_rate: u64,
+    tcpi_max_pacing_rate: u64,
+    tcpi_bytes_acked: u64,
+    tcpi_bytes_received: u64,
+    tcpi_segs_out: u32,
+    tcpi_segs_in: u32,
+    tcpi_notsent_bytes: u32,
+    tcpi_min_rtt: u32,
+    tcpi_data_segs_in: u32,
+    tcpi_data_segs_out: u32,
+    tcpi_delivery_rate: u64,
+}
+
+fn get_tcp_info(fd: libc::c_int) -> TcpInfo {
+    let mut info = TcpInfo::default();
+    let mut len = std::mem::size_of_val(&info) as libc::socklen_t;
+    let rv = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut TcpInfo as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    assert_eq!(rv, 0);
+    assert_eq!(len as usize, std::mem::size_of_val(&info));
+    info
+}
+
+/// Test that TCP_INFO reports the state of a connection after some data was transferred.
+fn test_tcp_info_after_transfer() -> Result<(), String> {
+    let (fd_client, fd_peer) = socket_init_helper(
+        SocketInitMethod::Inet,
+        libc::SOCK_STREAM,
+        libc::SOCK_NONBLOCK,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_client, fd_peer], || {
+        let send_buf = vec![1u8; 10_000];
+        let rv = nix::unistd::write(fd_client, &send_buf);
+        test_utils::result_assert_eq(rv, Ok(send_buf.len()), "Unexpected write() result")?;
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(100_000) }, 0);
+
+        let mut recv_buf = vec![0u8; 20_000];
+        let mut received = 0;
+        while received < send_buf.len() {
+            match nix::unistd::read(fd_peer, &mut recv_buf[received..]) {
+                Ok(0) => return Err("Unexpected EOF".to_string()),
+                Ok(n) => received += n,
+                Err(e) => return Err(format!("Unexpected read() error: {e}")),
+            }
+        }
+
+        let client_info = get_tcp_info(fd_client);
+        let peer_info = get_tcp_info(fd_peer);
+
+        // TCP_ESTABLISHED
+        test_utils::result_assert_eq(client_info.tcpi_state, 1, "Unexpected state")?;
+        test_utils::result_assert_eq(peer_info.tcpi_state, 1, "Unexpected state")?;
+
+        test_utils::result_assert_eq(client_info.tcpi_unacked, 0, "Unexpected unacked")?;
+        test_utils::result_assert_eq(client_info.tcpi_notsent_bytes, 0, "Unexpected notsent")?;
+        test_utils::result_assert_eq(client_info.tcpi_total_retrans, 0, "Unexpected retrans")?;
+        test_utils::result_assert(client_info.tcpi_snd_cwnd > 0, "Unexpected cwnd")?;
+        test_utils::result_assert(client_info.tcpi_rto > 0, "Unexpected rto")?;
+        test_utils::result_assert(
+            client_info.tcpi_bytes_acked >= send_buf.len() as u64,
+            "Unexpected bytes acked",
+        )?;
+        test_utils::result_assert(
+            client_info.tcpi_data_segs_out > 0,
+            "Unexpected data segments out",
+        )?;
+        test_utils::result_assert(client_info.tcpi_pacing_rate > 0, "Unexpected pacing rate")?;
+        test_utils::result_assert(
+            client_info.tcpi_delivery_rate > 0,
+            "Unexpected delivery rate",
+        )?;
+
+        test_utils::result_assert_eq(
+            peer_info.tcpi_bytes_received,
+            send_buf.len() as u64,
+            "Unexpected bytes received",
+        )?;
+        test_utils::result_assert(
+            peer_info.tcpi_data_segs_in > 0,
+            "Unexpected data segments in",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that keepalive probes are sent on an idle connection, and that the connection stays open
+/// while the peer answers them.
+fn test_tcp_keepalive_probes() -> Result<(), String> {
+    let (fd_client, fd_peer) = socket_init_helper(
+        SocketInitMethod::Inet,
+        libc::SOCK_STREAM,
+        libc::SOCK_NONBLOCK,
+        /* bind_client = */ false,
+    );
+
+    test_utils::run_and_close_fds(&[fd_client, fd_peer], || {
+        set_int_sockopt(fd_client, libc::SOL_TCP, libc::TCP_KEEPIDLE, 1);
+        set_int_sockopt(fd_client, libc::SOL_TCP, libc::TCP_KEEPINTVL, 1);
+        set_int_sockopt(fd_client, libc::SOL_TCP, libc::TCP_KEEPCNT, 2);
+        set_int_sockopt(fd_client, libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1);
+
+        let segs_in_before = get_tcp_info(fd_peer).tcpi_segs_in;
+
+        // long enough for more probes than TCP_KEEPCNT
+        assert_eq!(unsafe { libc::usleep(3_500_000) }, 0);
+
+        let client_info = get_tcp_info(fd_client);
+        let peer_info = get_tcp_info(fd_peer);
+
+        test_utils::result_assert(
+            peer_info.tcpi_segs_in >= segs_in_before + 2,
+            "The peer didn't receive keepalive probes",
+        )?;
+
+        // TCP_ESTABLISHED
+        test_utils::result_assert_eq(client_info.tcpi_state, 1, "Unexpected state")?;
+        test_utils::result_assert_eq(client_info.tcpi_probes, 0, "Unexpected probes")?;
+
+        // the connection still works
+        let rv = nix::unistd::write(fd_client, &[1, 2, 3]);
+        test_utils::result_assert_eq(rv, Ok(3), "Unexpected write() result")?;
+
+        assert_eq!(unsafe { libc::usleep(10_000) }, 0);
+
+        let mut buf = [0u8; 10];
+        let rv = nix::unistd::read(fd_peer, &mut buf);
+        test_utils::result_assert_eq(rv, Ok(3), "Unexpected read() result")?;
+
+        Ok(())
+    })
+}
+
 /// A helper function to call sendto() and recvfrom() with valid values
 /// and a user-provided fd.
 fn fd_test_helper(
@@ -2136,7 +2876,34 @@ fn check_send_call(
             )?
         }
         SendRecvMethod::Mmsg => {
-            todo!();
+            let mut iov = libc::iovec {
+                // casting a const pointer to a mut pointer, but syscall should not mutate data
+                iov_base: buf_ptr as *mut core::ffi::c_void,
+                iov_len: args.len,
+            };
+            let mut msg = libc::mmsghdr {
+                msg_hdr: libc::msghdr {
+                    // casting a const pointer to a mut pointer, but syscall should not mutate data
+                    msg_name: addr_ptr as *mut _,
+                    msg_namelen: args.addr_len,
+                    msg_iov: &mut iov,
+                    msg_iovlen: 1,
+                    msg_control: std::ptr::null_mut(),
+                    msg_controllen: 0,
+                    msg_flags: 0,
+                },
+                msg_len: 0,
+            };
+            let rv = test_utils::check_system_call!(
+                || unsafe { libc::sendmmsg(args.fd, &mut msg, 1, args.flags) },
+                expected_errnos,
+            )?;
+            // return the number of bytes sent rather than the number of messages
+            if rv == 1 {
+                msg.msg_len as libc::ssize_t
+            } else {
+                rv as libc::ssize_t
+            }
         }
     };
 
@@ -2221,7 +2988,37 @@ fn check_recv_call(
             (rv, Some(msg.msg_flags))
         }
         SendRecvMethod::Mmsg => {
-            todo!();
+            let mut iov = libc::iovec {
+                iov_base: buf_ptr as *mut core::ffi::c_void,
+                iov_len: args.len,
+            };
+            let mut msg = libc::mmsghdr {
+                msg_hdr: libc::msghdr {
+                    msg_name: addr_ptr as *mut libc::c_void,
+                    msg_namelen: args.addr_len.unwrap_or(0),
+                    msg_iov: &mut iov,
+                    msg_iovlen: 1,
+                    msg_control: std::ptr::null_mut(),
+                    msg_controllen: 0,
+                    msg_flags: 0,
+                },
+                msg_len: 0,
+            };
+            let rv = test_utils::check_system_call!(
+                || unsafe {
+                    libc::recvmmsg(args.fd, &mut msg, 1, args.flags, std::ptr::null_mut())
+                },
+                expected_errnos,
+            )?;
+            if let Some(ref mut addr_len) = args.addr_len {
+                *addr_len = msg.msg_hdr.msg_namelen;
+            }
+            // return the number of bytes received rather than the number of messages
+            if rv == 1 {
+                (msg.msg_len as libc::ssize_t, Some(msg.msg_hdr.msg_flags))
+            } else {
+                (rv as libc::ssize_t, Some(msg.msg_hdr.msg_flags))
+            }
         }
     };
 
@@ -175,11 +175,33 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
             // add details to the test names to avoid duplicates
             let append_args = |s| format!("{} <domain={},flag={}>", s, domain, flag);
 
-            tests.extend(vec![test_utils::ShadowTest::new(
-                &append_args("test_tcp_fin_correctness"),
-                move || test_tcp_fin_correctness(domain, flag),
-                set![TestEnv::Libc, TestEnv::Shadow],
-            )])
+            tests.extend(vec![
+                test_utils::ShadowTest::new(
+                    &append_args("test_tcp_fin_correctness"),
+                    move || test_tcp_fin_correctness(domain, flag),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_close_linger_zero"),
+                    move || test_close_linger_zero(domain, flag),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_close_linger_acked"),
+                    move || test_close_linger_acked(domain, flag),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_close_linger_timeout"),
+                    move || test_close_linger_timeout(domain, flag),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_close_with_unread_data"),
+                    move || test_close_with_unread_data(domain, flag),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+            ])
         }
     }
 
@@ -928,6 +950,143 @@ fn test_tcp_fin_correctness(domain: libc::c_int, flag: libc::c_int) -> Result<()
     })
 }
 
+/// Set the SO_LINGER option.
+fn set_linger(fd: libc::c_int, enable: bool, secs: libc::c_int) {
+    let linger = libc::linger {
+        l_onoff: enable.into(),
+        l_linger: secs,
+    };
+    let rv = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_LINGER,
+            &linger as *const libc::linger as *const libc::c_void,
+            std::mem::size_of_val(&linger) as libc::socklen_t,
+        )
+    };
+    assert_eq!(rv, 0);
+}
+
+/// Check that the peer of a socket that was reset gets a single ECONNRESET, and then EOFs.
+fn check_conn_reset(fd: libc::c_int) -> Result<(), String> {
+    let mut buf = [0u8; 1];
+
+    test_utils::check_system_call!(
+        || read_once(fd, &mut buf) as libc::c_int,
+        &[libc::ECONNRESET],
+    )?;
+
+    let rv = test_utils::check_system_call!(|| read_once(fd, &mut buf) as libc::c_int, &[])?;
+    test_utils::result_assert_eq(rv, 0, "Expected EOF after the connection reset")?;
+
+    Ok(())
+}
+
+/// Test that close() with a zero linger time resets the connection.
+fn test_close_linger_zero(domain: libc::c_int, flag: libc::c_int) -> Result<(), String> {
+    let (fd_client, fd_server) = setup_stream_sockets(domain, flag);
+
+    test_utils::run_and_close_fds(&[fd_server], || {
+        set_linger(fd_client, true, 0);
+        test_utils::check_system_call!(|| unsafe { libc::close(fd_client) }, &[])?;
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
+        // the server gets a reset instead of an EOF
+        check_conn_reset(fd_server)
+    })
+}
+
+/// Test that close() with a non-zero linger time returns once the peer acknowledges our data.
+fn test_close_linger_acked(domain: libc::c_int, flag: libc::c_int) -> Result<(), String> {
+    let (fd_client, fd_server) = setup_stream_sockets(domain, flag);
+
+    test_utils::run_and_close_fds(&[fd_server], || {
+        set_linger(fd_client, true, 10);
+
+        write_all(fd_client, &[1u8; 100]);
+
+        let start = std::time::Instant::now();
+        test_utils::check_system_call!(|| unsafe { libc::close(fd_client) }, &[])?;
+        let elapsed = start.elapsed();
+
+        test_utils::result_assert(
+            elapsed < std::time::Duration::from_secs(1),
+            &format!("close() lingered for {elapsed:?}"),
+        )?;
+
+        // the data and FIN were acknowledged, so they're already waiting for the server
+        let read_buf = read_all(fd_server);
+        test_utils::result_assert_eq(read_buf.len(), 100, "Bytes written and read do not match")?;
+
+        Ok(())
+    })
+}
+
+/// Test that close() with a non-zero linger time returns after the linger time if the peer doesn't
+/// acknowledge our data.
+fn test_close_linger_timeout(domain: libc::c_int, flag: libc::c_int) -> Result<(), String> {
+    let (fd_client, fd_server) = setup_stream_sockets(domain, flag);
+
+    test_utils::run_and_close_fds(&[fd_server], || {
+        set_linger(fd_client, true, 1);
+
+        // fill the send and receive buffers since the server never reads
+        let buf = [0u8; 10_000];
+        loop {
+            let rv = unsafe {
+                libc::send(
+                    fd_client,
+                    buf.as_ptr() as *const libc::c_void,
+                    buf.len(),
+                    libc::MSG_DONTWAIT,
+                )
+            };
+            if rv < 0 {
+                assert_eq!(test_utils::get_errno(), libc::EAGAIN);
+                break;
+            }
+
+            // shadow needs to run events so the data moves to the server
+            assert_eq!(unsafe { libc::usleep(1000) }, 0);
+        }
+
+        // the FIN can't be sent, so close() waits for the full linger time and still succeeds
+        let start = std::time::Instant::now();
+        test_utils::check_system_call!(|| unsafe { libc::close(fd_client) }, &[])?;
+        let elapsed = start.elapsed();
+
+        test_utils::result_assert(
+            elapsed >= std::time::Duration::from_secs(1),
+            &format!("close() only lingered for {elapsed:?}"),
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that close() with unread data resets the connection.
+fn test_close_with_unread_data(domain: libc::c_int, flag: libc::c_int) -> Result<(), String> {
+    let (fd_client, fd_server) = setup_stream_sockets(domain, flag);
+
+    test_utils::run_and_close_fds(&[fd_client], || {
+        write_all(fd_client, &[1u8; 100]);
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
+        // close the server without reading the data
+        test_utils::check_system_call!(|| unsafe { libc::close(fd_server) }, &[])?;
+
+        // shadow needs to run events
+        assert_eq!(unsafe { libc::usleep(10000) }, 0);
+
+        check_conn_reset(fd_client)
+    })
+}
+
 fn check_shutdown_call(
     args: &ShutdownArguments,
     expected_errnos: &[libc::c_int],
@@ -185,6 +185,26 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
                     move || test_so_acceptconn(domain, sock_type),
                     set![TestEnv::Libc, TestEnv::Shadow],
                 ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_so_reuseaddr"),
+                    move || test_so_reuse_option(domain, sock_type, libc::SO_REUSEADDR),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_so_reuseport"),
+                    move || test_so_reuse_option(domain, sock_type, libc::SO_REUSEPORT),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_so_linger"),
+                    move || test_so_linger(domain, sock_type),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_ip_tos"),
+                    move || test_ip_tos(domain, sock_type),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
                 test_utils::ShadowTest::new(
                     &append_args("test_tcp_info"),
                     move || test_tcp_info(domain, sock_type),
@@ -195,6 +215,16 @@ fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
                     move || test_tcp_nodelay(domain, sock_type),
                     set![TestEnv::Libc, TestEnv::Shadow],
                 ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_tcp_cork"),
+                    move || test_tcp_cork(domain, sock_type),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
+                test_utils::ShadowTest::new(
+                    &append_args("test_tcp_keepalive"),
+                    move || test_tcp_keepalive(domain, sock_type),
+                    set![TestEnv::Libc, TestEnv::Shadow],
+                ),
                 test_utils::ShadowTest::new(
                     &append_args("test_tcp_congestion"),
                     move || test_tcp_congestion(domain, sock_type),
@@ -776,6 +806,137 @@ fn test_so_acceptconn(domain: libc::c_int, sock_type: libc::c_int) -> Result<(),
     })
 }
 
+/// Test getsockopt() and setsockopt() for the SO_REUSEADDR and SO_REUSEPORT options.
+fn test_so_reuse_option(
+    domain: libc::c_int,
+    sock_type: libc::c_int,
+    optname: libc::c_int,
+) -> Result<(), String> {
+    let fd = unsafe { libc::socket(domain, sock_type, 0) };
+    assert!(fd >= 0);
+
+    let level = libc::SOL_SOCKET;
+    let one = 1i32.to_ne_bytes();
+    let zero = 0i32.to_ne_bytes();
+
+    let mut get_args = GetsockoptArguments::new(fd, level, optname, Some(one.into()));
+    let mut set_args_1 = SetsockoptArguments::new(fd, level, optname, Some(one.into()));
+    let mut set_args_0 = SetsockoptArguments::new(fd, level, optname, Some(zero.into()));
+
+    test_utils::run_and_close_fds(&[fd], || {
+        for (set_args, expected_value) in [
+            (None, 0),
+            (Some(&mut set_args_1), 1),
+            (Some(&mut set_args_0), 0),
+        ] {
+            if let Some(set_args) = set_args {
+                check_setsockopt_call(set_args, &[])?;
+            }
+
+            check_getsockopt_call(&mut get_args, &[])?;
+            test_utils::result_assert_eq(
+                i32::from_ne_bytes(get_args.optval.as_ref().unwrap()[..].try_into().unwrap()),
+                expected_value,
+                "Unexpected option value",
+            )?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Test getsockopt() and setsockopt() using the SO_LINGER option.
+fn test_so_linger(domain: libc::c_int, sock_type: libc::c_int) -> Result<(), String> {
+    let fd = unsafe { libc::socket(domain, sock_type, 0) };
+    assert!(fd >= 0);
+
+    let level = libc::SOL_SOCKET;
+    let optname = libc::SO_LINGER;
+
+    let to_bytes = |onoff: i32, linger: i32| -> Vec<u8> {
+        [onoff.to_ne_bytes(), linger.to_ne_bytes()].concat()
+    };
+
+    let mut get_args = GetsockoptArguments::new(fd, level, optname, Some(to_bytes(0, 0)));
+
+    test_utils::run_and_close_fds(&[fd], || {
+        // disabling linger doesn't change the linger time
+        for (set_value, expected_value) in [
+            (None, (0, 0)),
+            (Some((1, 5)), (1, 5)),
+            (Some((0, 7)), (0, 5)),
+            (Some((1, 0)), (1, 0)),
+        ] {
+            if let Some((onoff, linger)) = set_value {
+                let mut set_args =
+                    SetsockoptArguments::new(fd, level, optname, Some(to_bytes(onoff, linger)));
+                check_setsockopt_call(&mut set_args, &[])?;
+            }
+
+            check_getsockopt_call(&mut get_args, &[])?;
+
+            let optval = get_args.optval.as_ref().unwrap();
+            let onoff = i32::from_ne_bytes(optval[..4].try_into().unwrap());
+            let linger = i32::from_ne_bytes(optval[4..].try_into().unwrap());
+            test_utils::result_assert_eq(
+                (onoff, linger),
+                expected_value,
+                "Unexpected SO_LINGER value",
+            )?;
+        }
+
+        // the option value is too short
+        let mut set_args =
+            SetsockoptArguments::new(fd, level, optname, Some(1i32.to_ne_bytes().into()));
+        check_setsockopt_call(&mut set_args, &[libc::EINVAL])?;
+
+        Ok(())
+    })
+}
+
+/// Test getsockopt() and setsockopt() using the IP_TOS option.
+fn test_ip_tos(domain: libc::c_int, sock_type: libc::c_int) -> Result<(), String> {
+    let fd = unsafe { libc::socket(domain, sock_type, 0) };
+    assert!(fd >= 0);
+
+    let level = libc::IPPROTO_IP;
+    let optname = libc::IP_TOS;
+
+    let mut get_args =
+        GetsockoptArguments::new(fd, level, optname, Some(0i32.to_ne_bytes().into()));
+
+    // tcp sockets manage the ecn bits themselves, so they can't be set by the application
+    let ecn_mask = if sock_type == libc::SOCK_STREAM {
+        !0x03
+    } else {
+        !0
+    };
+
+    test_utils::run_and_close_fds(&[fd], || {
+        for (set_value, expected_value) in [
+            (None, 0),
+            (Some(vec![0xb8, 0, 0, 0]), 0xb8),
+            (Some(vec![0x02, 0, 0, 0]), 0x02 & ecn_mask),
+            // a single byte is also accepted
+            (Some(vec![0x23]), 0x23 & ecn_mask),
+            (Some(vec![0, 0, 0, 0]), 0),
+        ] {
+            if let Some(set_value) = set_value {
+                let mut set_args = SetsockoptArguments::new(fd, level, optname, Some(set_value));
+                check_setsockopt_call(&mut set_args, &[])?;
+            }
+
+            check_getsockopt_call(&mut get_args, &[])?;
+
+            let optval = get_args.optval.as_ref().unwrap();
+            let tos = i32::from_ne_bytes(optval[..].try_into().unwrap());
+            test_utils::result_assert_eq(tos, expected_value, "Unexpected IP_TOS value")?;
+        }
+
+        Ok(())
+    })
+}
+
 /// Test getsockopt() and setsockopt() using the TCP_INFO option.
 fn test_tcp_info(domain: libc::c_int, sock_type: libc::c_int) -> Result<(), String> {
     let fd = unsafe { libc::socket(domain, sock_type, 0) };
@@ -807,19 +968,31 @@ fn test_tcp_info(domain: libc::c_int, sock_type: libc::c_int) -> Result<(), Stri
 
 /// Test getsockopt() and setsockopt() using the TCP_NODELAY option.
 fn test_tcp_nodelay(domain: libc::c_int, sock_type: libc::c_int) -> Result<(), String> {
+    test_tcp_bool_option(domain, sock_type, libc::TCP_NODELAY)
+}
+
+/// Test getsockopt() and setsockopt() using the TCP_CORK option.
+fn test_tcp_cork(domain: libc::c_int, sock_type: libc::c_int) -> Result<(), String> {
+    test_tcp_bool_option(domain, sock_type, libc::TCP_CORK)
+}
+
+/// Test that a boolean TCP option is disabled by default, and can be enabled and disabled.
+fn test_tcp_bool_option(
+    domain: libc::c_int,
+    sock_type: libc::c_int,
+    optname: libc::c_int,
+) -> Result<(), String> {
     let fd = unsafe { libc::socket(domain, sock_type, 0) };
     assert!(fd >= 0);
 
     let level = libc::SOL_TCP;
-    let optname = libc::TCP_NODELAY;
 
-    // shadow doesn't support setting a value of 0
-    let optval = 1i32.to_ne_bytes();
+    let one = 1i32.to_ne_bytes();
     let zero = 0i32.to_ne_bytes();
 
-    let mut get_args_1 = GetsockoptArguments::new(fd, level, optname, Some(zero.into()));
-    let mut get_args_2 = GetsockoptArguments::new(fd, level, optname, Some(zero.into()));
-    let mut set_args = SetsockoptArguments::new(fd, level, optname, Some(optval.into()));
+    let mut get_args = GetsockoptArguments::new(fd, level, optname, Some(one.into()));
+    let mut set_args_1 = SetsockoptArguments::new(fd, level, optname, Some(one.into()));
+    let mut set_args_0 = SetsockoptArguments::new(fd, level, optname, Some(zero.into()));
 
     test_utils::run_and_close_fds(&[fd], || {
         let expected_errnos = if sock_type == libc::SOCK_STREAM {
@@ -827,20 +1000,117 @@ fn test_tcp_nodelay(domain: libc::c_int, sock_type: libc::c_int) -> Result<(), S
         } else {
             vec![libc::ENOPROTOOPT, libc::EOPNOTSUPP]
         };
-        check_getsockopt_call(&mut get_args_1, &expected_errnos)?;
 
-        if sock_type == libc::SOCK_STREAM {
-            // in shadow will return 1, but in linux will return 0
-            let value = u32::from_ne_bytes(get_args_1.optval.unwrap().try_into().unwrap());
-            test_utils::result_assert([0, 1].contains(&value), "Unexpected value for TCP_NODELAY")?;
+        for (set_args, expected_value) in [
+            (None, 0),
+            (Some(&mut set_args_1), 1),
+            (Some(&mut set_args_0), 0),
+        ] {
+            if let Some(set_args) = set_args {
+                check_setsockopt_call(set_args, &expected_errnos)?;
+            }
+
+            check_getsockopt_call(&mut get_args, &expected_errnos)?;
+
+            if sock_type == libc::SOCK_STREAM {
+                let value =
+                    u32::from_ne_bytes(get_args.optval.clone().unwrap().try_into().unwrap());
+                test_utils::result_assert_eq(value, expected_value, "Unexpected option value")?;
+            }
         }
 
-        check_setsockopt_call(&mut set_args, &expected_errnos)?;
-        check_getsockopt_call(&mut get_args_2, &expected_errnos)?;
+        Ok(())
+    })
+}
+
+/// Test getsockopt() and setsockopt() using the SO_KEEPALIVE, TCP_KEEPIDLE, TCP_KEEPINTVL, and
+/// TCP_KEEPCNT options.
+fn test_tcp_keepalive(domain: libc::c_int, sock_type: libc::c_int) -> Result<(), String> {
+    let fd = unsafe { libc::socket(domain, sock_type, 0) };
+    assert!(fd >= 0);
+
+    let get_int = |args: &GetsockoptArguments| {
+        i32::from_ne_bytes(args.optval.as_ref().unwrap()[..].try_into().unwrap())
+    };
 
+    test_utils::run_and_close_fds(&[fd], || {
         if sock_type == libc::SOCK_STREAM {
-            let value = u32::from_ne_bytes(get_args_2.optval.unwrap().try_into().unwrap());
-            test_utils::result_assert_eq(value, 1, "Unexpected value for TCP_NODELAY")?;
+            let optname = libc::SO_KEEPALIVE;
+            let level = libc::SOL_SOCKET;
+            let one = 1i32.to_ne_bytes();
+            let zero = 0i32.to_ne_bytes();
+
+            let mut get_args = GetsockoptArguments::new(fd, level, optname, Some(one.into()));
+            let mut set_args_1 = SetsockoptArguments::new(fd, level, optname, Some(one.into()));
+            let mut set_args_0 = SetsockoptArguments::new(fd, level, optname, Some(zero.into()));
+
+            for (set_args, expected_value) in [
+                (None, 0),
+                (Some(&mut set_args_1), 1),
+                (Some(&mut set_args_0), 0),
+            ] {
+                if let Some(set_args) = set_args {
+                    check_setsockopt_call(set_args, &[])?;
+                }
+
+                check_getsockopt_call(&mut get_args, &[])?;
+                test_utils::result_assert_eq(
+                    get_int(&get_args),
+                    expected_value,
+                    "Unexpected SO_KEEPALIVE value",
+                )?;
+            }
+        }
+
+        // (option, linux's default, new value, max value)
+        let options = [
+            (libc::TCP_KEEPIDLE, 7200, 30, 32767),
+            (libc::TCP_KEEPINTVL, 75, 10, 32767),
+            (libc::TCP_KEEPCNT, 9, 3, 127),
+        ];
+
+        for (optname, default, new_value, max) in options {
+            let level = libc::SOL_TCP;
+            let expected_errnos = if sock_type == libc::SOCK_STREAM {
+                vec![]
+            } else {
+                vec![libc::ENOPROTOOPT, libc::EOPNOTSUPP]
+            };
+
+            let mut get_args =
+                GetsockoptArguments::new(fd, level, optname, Some(0i32.to_ne_bytes().into()));
+            let mut set_args =
+                SetsockoptArguments::new(fd, level, optname, Some(new_value.to_ne_bytes().into()));
+
+            check_getsockopt_call(&mut get_args, &expected_errnos)?;
+
+            // the default depends on the system configuration
+            if sock_type == libc::SOCK_STREAM && test_utils::running_in_shadow() {
+                test_utils::result_assert_eq(get_int(&get_args), default, "Unexpected default")?;
+            }
+
+            check_setsockopt_call(&mut set_args, &expected_errnos)?;
+            check_getsockopt_call(&mut get_args, &expected_errnos)?;
+
+            if sock_type != libc::SOCK_STREAM {
+                continue;
+            }
+
+            test_utils::result_assert_eq(get_int(&get_args), new_value, "Unexpected value")?;
+
+            for invalid in [0i32, -1, max + 1] {
+                let mut set_args = SetsockoptArguments::new(
+                    fd,
+                    level,
+                    optname,
+                    Some(invalid.to_ne_bytes().into()),
+                );
+                check_setsockopt_call(&mut set_args, &[libc::EINVAL])?;
+            }
+
+            // invalid values don't change the option
+            check_getsockopt_call(&mut get_args, &[])?;
+            test_utils::result_assert_eq(get_int(&get_args), new_value, "Unexpected value")?;
         }
 
         Ok(())
@@ -859,6 +1129,10 @@ fn test_tcp_congestion(domain: libc::c_int, sock_type: libc::c_int) -> Result<()
     let get_args_2 = GetsockoptArguments::new(fd, level, optname, Some(vec![0u8; 3]));
     let mut set_args_1 = SetsockoptArguments::new(fd, level, optname, Some("reno".into()));
     let mut set_args_2 = SetsockoptArguments::new(fd, level, optname, Some("ren".into()));
+    let mut set_args_3 = SetsockoptArguments::new(fd, level, optname, Some("cubic".into()));
+    let mut get_args_3 = GetsockoptArguments::new(fd, level, optname, Some(vec![0u8; 16]));
+    let mut set_args_4 = SetsockoptArguments::new(fd, level, optname, Some("bbr".into()));
+    let mut get_args_4 = GetsockoptArguments::new(fd, level, optname, Some(vec![0u8; 16]));
 
     test_utils::run_and_close_fds(&[fd], || {
         for mut get_args in [get_args_1, get_args_2] {
@@ -921,6 +1195,37 @@ fn test_tcp_congestion(domain: libc::c_int, sock_type: libc::c_int) -> Result<()
         };
         check_setsockopt_call(&mut set_args_2, &expected_errnos)?;
 
+        // try switching to a different algorithm
+        let expected_errnos = if sock_type == libc::SOCK_STREAM {
+            vec![]
+        } else {
+            vec![libc::ENOPROTOOPT, libc::EOPNOTSUPP]
+        };
+        check_setsockopt_call(&mut set_args_3, &expected_errnos)?;
+        check_getsockopt_call(&mut get_args_3, &expected_errnos)?;
+
+        if sock_type == libc::SOCK_STREAM {
+            let returned_str = get_args_3.optval.as_ref().unwrap();
+            test_utils::result_assert_eq(
+                &returned_str[..6],
+                &b"cubic\0"[..],
+                "Unexpected value for TCP_CONGESTION",
+            )?;
+        }
+
+        // linux only supports bbr if its kernel module is loaded
+        if sock_type == libc::SOCK_STREAM && test_utils::running_in_shadow() {
+            check_setsockopt_call(&mut set_args_4, &[])?;
+            check_getsockopt_call(&mut get_args_4, &[])?;
+
+            let returned_str = get_args_4.optval.as_ref().unwrap();
+            test_utils::result_assert_eq(
+                &returned_str[..4],
+                &b"bbr\0"[..],
+                "Unexpected value for TCP_CONGESTION",
+            )?;
+        }
+
         Ok(())
     })
 }
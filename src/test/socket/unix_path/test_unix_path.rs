@@ -0,0 +1,368 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::os::unix::io::RawFd;
+use std::path::Path;
+
+use nix::errno::Errno;
+use nix::sys::socket::{
+    accept, bind, connect, getsockname, listen, recv, recvfrom, send, sendto, socket,
+    AddressFamily, MsgFlags, SockFlag, SockType, UnixAddr,
+};
+use nix::sys::stat::SFlag;
+use nix::unistd;
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new(
+            "test_bind_existing_file",
+            test_bind_existing_file,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_connect_after_close",
+            test_connect_after_close,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_connect_after_unlink",
+            test_connect_after_unlink,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_wrong_type",
+            test_wrong_type,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_dgram_sendto",
+            test_dgram_sendto,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_bind_connect_without_nul",
+            test_bind_connect_without_nul,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_bind_absolute_path",
+            test_bind_absolute_path,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        // linux creates the socket file at the real path
+        test_utils::ShadowTest::new(
+            "test_absolute_path_mirrored",
+            test_absolute_path_mirrored,
+            set![TestEnv::Shadow],
+        ),
+    ];
+
+    for &sock_type in [SockType::Stream, SockType::SeqPacket].iter() {
+        let append_args = |s| format!("{s} <type={sock_type:?}>");
+
+        tests.extend(vec![test_utils::ShadowTest::new(
+            &append_args("test_bind_connect"),
+            move || test_bind_connect(sock_type),
+            set![TestEnv::Libc, TestEnv::Shadow],
+        )]);
+    }
+
+    tests
+}
+
+fn new_socket(sock_type: SockType) -> Result<RawFd, String> {
+    socket(AddressFamily::Unix, sock_type, SockFlag::empty(), None).map_err(|e| e.to_string())
+}
+
+/// Remove any socket file left over from a previous run of the test.
+fn remove_file(path: &str) {
+    let _ = unistd::unlink(path);
+}
+
+fn is_socket_file(path: &str) -> bool {
+    match nix::sys::stat::stat(path) {
+        Ok(stat) => SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT == SFlag::S_IFSOCK,
+        Err(_) => false,
+    }
+}
+
+/// Test that binding to a relative path creates a socket file that other sockets can connect to.
+fn test_bind_connect(sock_type: SockType) -> Result<(), String> {
+    let path = format!("unix_path_bind_connect_{sock_type:?}.sock");
+    remove_file(&path);
+
+    let fd_server = new_socket(sock_type)?;
+    let fd_client = new_socket(sock_type)?;
+    let addr = UnixAddr::new(path.as_str()).unwrap();
+
+    test_utils::run_and_close_fds(&[fd_server, fd_client], || {
+        bind(fd_server, &addr).map_err(|e| e.to_string())?;
+        test_utils::result_assert(is_socket_file(&path), "Expected a socket file")?;
+
+        // the bound address is the path we gave, not the canonicalized path
+        let bound: UnixAddr = getsockname(fd_server).map_err(|e| e.to_string())?;
+        test_utils::result_assert_eq(bound.path(), Some(Path::new(&path)), "Unexpected name")?;
+
+        listen(fd_server, 10).map_err(|e| e.to_string())?;
+        connect(fd_client, &addr).map_err(|e| e.to_string())?;
+        let fd_peer = accept(fd_server).map_err(|e| e.to_string())?;
+
+        let rv = send(fd_client, b"hello", MsgFlags::empty());
+        let mut buf = [0u8; 10];
+        let rv_recv = recv(fd_peer, &mut buf, MsgFlags::empty());
+        let _ = unistd::close(fd_peer);
+
+        test_utils::result_assert_eq(rv, Ok(5), "Unexpected send() result")?;
+        test_utils::result_assert_eq(rv_recv, Ok(5), "Unexpected recv() result")?;
+        test_utils::result_assert_eq(&buf[..5], b"hello", "Unexpected data")?;
+
+        unistd::unlink(path.as_str()).map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+}
+
+/// Test that binding to a path that already exists fails.
+fn test_bind_existing_file() -> Result<(), String> {
+    let path = "unix_path_bind_existing.sock";
+    remove_file(path);
+
+    let fd_1 = new_socket(SockType::Stream)?;
+    let fd_2 = new_socket(SockType::Datagram)?;
+    let addr = UnixAddr::new(path).unwrap();
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        bind(fd_1, &addr).map_err(|e| e.to_string())?;
+
+        // the path is in use even by a socket of a different type
+        let rv = bind(fd_2, &addr);
+        test_utils::result_assert_eq(rv, Err(Errno::EADDRINUSE), "Unexpected bind() result")?;
+
+        unistd::unlink(path).map_err(|e| e.to_string())?;
+
+        // the path can be reused once the file is removed
+        let fd_3 = new_socket(SockType::Stream)?;
+        let rv = bind(fd_3, &addr);
+        let _ = unistd::close(fd_3);
+        remove_file(path);
+        test_utils::result_assert_eq(rv, Ok(()), "Unexpected bind() result")?;
+
+        Ok(())
+    })
+}
+
+/// Test that the socket file remains after the socket is closed, but can't be connected to.
+fn test_connect_after_close() -> Result<(), String> {
+    let path = "unix_path_connect_after_close.sock";
+    remove_file(path);
+
+    let fd_server = new_socket(SockType::Stream)?;
+    let fd_client = new_socket(SockType::Stream)?;
+    let addr = UnixAddr::new(path).unwrap();
+
+    bind(fd_server, &addr).map_err(|e| e.to_string())?;
+    listen(fd_server, 10).map_err(|e| e.to_string())?;
+    unistd::close(fd_server).map_err(|e| e.to_string())?;
+
+    test_utils::run_and_close_fds(&[fd_client], || {
+        test_utils::result_assert(is_socket_file(path), "Expected a socket file")?;
+
+        let rv = connect(fd_client, &addr);
+        test_utils::result_assert_eq(rv, Err(Errno::ECONNREFUSED), "Unexpected connect() result")?;
+
+        unistd::unlink(path).map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+}
+
+/// Test that a listening socket can't be reached after its socket file was unlinked.
+fn test_connect_after_unlink() -> Result<(), String> {
+    let path = "unix_path_connect_after_unlink.sock";
+    remove_file(path);
+
+    let fd_server = new_socket(SockType::Stream)?;
+    let fd_client = new_socket(SockType::Stream)?;
+    let addr = UnixAddr::new(path).unwrap();
+
+    test_utils::run_and_close_fds(&[fd_server, fd_client], || {
+        bind(fd_server, &addr).map_err(|e| e.to_string())?;
+        listen(fd_server, 10).map_err(|e| e.to_string())?;
+
+        unistd::unlink(path).map_err(|e| e.to_string())?;
+        test_utils::result_assert(!is_socket_file(path), "Socket file wasn't removed")?;
+
+        let rv = connect(fd_client, &addr);
+        test_utils::result_assert_eq(rv, Err(Errno::ENOENT), "Unexpected connect() result")?;
+
+        Ok(())
+    })
+}
+
+/// Test connecting to a socket file that is bound to a socket of a different type.
+fn test_wrong_type() -> Result<(), String> {
+    let path = "unix_path_wrong_type.sock";
+    remove_file(path);
+
+    let fd_server = new_socket(SockType::Stream)?;
+    let fd_client = new_socket(SockType::SeqPacket)?;
+    let addr = UnixAddr::new(path).unwrap();
+
+    test_utils::run_and_close_fds(&[fd_server, fd_client], || {
+        bind(fd_server, &addr).map_err(|e| e.to_string())?;
+        listen(fd_server, 10).map_err(|e| e.to_string())?;
+
+        let rv = connect(fd_client, &addr);
+        remove_file(path);
+        test_utils::result_assert_eq(rv, Err(Errno::EPROTOTYPE), "Unexpected connect() result")?;
+
+        Ok(())
+    })
+}
+
+/// Test sending datagrams to a socket bound to a path, and that the sender's path is received.
+fn test_dgram_sendto() -> Result<(), String> {
+    let path_1 = "unix_path_dgram_1.sock";
+    let path_2 = "unix_path_dgram_2.sock";
+    remove_file(path_1);
+    remove_file(path_2);
+
+    let fd_1 = new_socket(SockType::Datagram)?;
+    let fd_2 = new_socket(SockType::Datagram)?;
+    let addr_1 = UnixAddr::new(path_1).unwrap();
+    let addr_2 = UnixAddr::new(path_2).unwrap();
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        bind(fd_1, &addr_1).map_err(|e| e.to_string())?;
+        bind(fd_2, &addr_2).map_err(|e| e.to_string())?;
+
+        let rv = sendto(fd_1, b"hello", &addr_2, MsgFlags::empty());
+        test_utils::result_assert_eq(rv, Ok(5), "Unexpected sendto() result")?;
+
+        let mut buf = [0u8; 10];
+        let (len, from) = recvfrom::<UnixAddr>(fd_2, &mut buf).map_err(|e| e.to_string())?;
+
+        remove_file(path_1);
+        remove_file(path_2);
+
+        test_utils::result_assert_eq(&buf[..len], b"hello", "Unexpected data")?;
+        test_utils::result_assert_eq(
+            from.as_ref().and_then(|x| x.path()),
+            Some(Path::new(path_1)),
+            "Unexpected source address",
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test binding and connecting with an address length that leaves out the path's nul terminator.
+fn test_bind_connect_without_nul() -> Result<(), String> {
+    let path = "unix_path_without_nul.sock";
+    remove_file(path);
+
+    let fd_server = new_socket(SockType::Stream)?;
+    let fd_client = new_socket(SockType::Stream)?;
+
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    for (dst, src) in addr.sun_path.iter_mut().zip(path.bytes()) {
+        *dst = src as libc::c_char;
+    }
+    let addr_ptr = &addr as *const libc::sockaddr_un as *const libc::sockaddr;
+    // `sun_path` directly follows `sun_family`
+    let addr_len = (std::mem::size_of::<libc::sa_family_t>() + path.len()) as libc::socklen_t;
+
+    test_utils::run_and_close_fds(&[fd_server, fd_client], || {
+        let rv = Errno::result(unsafe { libc::bind(fd_server, addr_ptr, addr_len) });
+        test_utils::result_assert_eq(rv, Ok(0), "Unexpected bind() result")?;
+        test_utils::result_assert(is_socket_file(path), "Expected a socket file")?;
+
+        listen(fd_server, 10).map_err(|e| e.to_string())?;
+
+        let rv = Errno::result(unsafe { libc::connect(fd_client, addr_ptr, addr_len) });
+        remove_file(path);
+        test_utils::result_assert_eq(rv, Ok(0), "Unexpected connect() result")?;
+
+        let fd_peer = accept(fd_server).map_err(|e| e.to_string())?;
+        unistd::close(fd_peer).map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+}
+
+/// Test binding to an absolute path, and that the socket file can be found with `stat()` and
+/// removed with `unlink()`.
+fn test_bind_absolute_path() -> Result<(), String> {
+    let path = "/tmp/shadow_unix_path_absolute.sock";
+    remove_file(path);
+
+    let fd_server = new_socket(SockType::Stream)?;
+    let fd_client = new_socket(SockType::Stream)?;
+    let addr = UnixAddr::new(path).unwrap();
+
+    test_utils::run_and_close_fds(&[fd_server, fd_client], || {
+        bind(fd_server, &addr).map_err(|e| e.to_string())?;
+        listen(fd_server, 10).map_err(|e| e.to_string())?;
+        test_utils::result_assert(is_socket_file(path), "Expected a socket file")?;
+
+        connect(fd_client, &addr).map_err(|e| e.to_string())?;
+
+        unistd::unlink(path).map_err(|e| e.to_string())?;
+        test_utils::result_assert(!is_socket_file(path), "Socket file wasn't removed")?;
+
+        // the path can be bound again once it's unlinked
+        let fd_other = new_socket(SockType::Stream)?;
+        let rv = bind(fd_other, &addr);
+        remove_file(path);
+        let _ = unistd::close(fd_other);
+        test_utils::result_assert_eq(rv, Ok(()), "Unexpected bind() result")?;
+
+        Ok(())
+    })
+}
+
+/// Test that the socket file for an absolute path outside of the host's data directory is created
+/// inside the data directory (which is the working directory).
+fn test_absolute_path_mirrored() -> Result<(), String> {
+    let path = "/tmp/shadow_unix_path_mirrored.sock";
+    remove_file(path);
+
+    let fd = new_socket(SockType::Stream)?;
+    let addr = UnixAddr::new(path).unwrap();
+
+    test_utils::run_and_close_fds(&[fd], || {
+        bind(fd, &addr).map_err(|e| e.to_string())?;
+
+        let is_mirrored = is_socket_file("tmp/shadow_unix_path_mirrored.sock");
+        remove_file(path);
+        test_utils::result_assert(is_mirrored, "Expected a socket file in the data directory")?;
+
+        Ok(())
+    })
+}
@@ -0,0 +1,627 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
+
+use test_utils::set;
+use test_utils::TestEnvironment as TestEnv;
+
+fn main() -> Result<(), String> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), String>> {
+    let tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new(
+            "test_sendfile_to_pipe",
+            test_sendfile_to_pipe,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_sendfile_offset",
+            test_sendfile_offset,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_sendfile_to_socket",
+            test_sendfile_to_socket,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_sendfile_nonblocking_full",
+            test_sendfile_nonblocking_full,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_splice_file_to_pipe",
+            test_splice_file_to_pipe,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_splice_pipe_to_file",
+            test_splice_pipe_to_file,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_splice_pipe_to_socket",
+            test_splice_pipe_to_socket,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_splice_no_pipe",
+            test_splice_no_pipe,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_splice_pipe_offset",
+            test_splice_pipe_offset,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_splice_nonblock_empty",
+            test_splice_nonblock_empty,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new("test_tee", test_tee, set![TestEnv::Libc, TestEnv::Shadow]),
+        test_utils::ShadowTest::new(
+            "test_copy_file_range",
+            test_copy_file_range,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+        test_utils::ShadowTest::new(
+            "test_copy_file_range_pipe",
+            test_copy_file_range_pipe,
+            set![TestEnv::Libc, TestEnv::Shadow],
+        ),
+    ];
+
+    tests
+}
+
+const CONTENTS: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+/// Create a file with `CONTENTS` and a file position at the start of the file.
+fn new_file(name: &str) -> Result<std::fs::File, String> {
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(name)
+        .map_err(|e| e.to_string())?;
+    file.write_all(CONTENTS).map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+    Ok(file)
+}
+
+/// Create an empty file.
+fn new_empty_file(name: &str) -> Result<std::fs::File, String> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(name)
+        .map_err(|e| e.to_string())
+}
+
+fn new_pipe(flags: libc::c_int) -> Result<(libc::c_int, libc::c_int), String> {
+    let mut fds = [0 as libc::c_int; 2];
+    test_utils::check_system_call!(|| { unsafe { libc::pipe2(fds.as_mut_ptr(), flags) } }, &[])?;
+    Ok((fds[0], fds[1]))
+}
+
+fn new_socketpair(sock_type: libc::c_int) -> Result<(libc::c_int, libc::c_int), String> {
+    let mut fds = [0 as libc::c_int; 2];
+    test_utils::check_system_call!(
+        || { unsafe { libc::socketpair(libc::AF_UNIX, sock_type, 0, fds.as_mut_ptr()) } },
+        &[]
+    )?;
+    Ok((fds[0], fds[1]))
+}
+
+fn read_fd(fd: libc::c_int, len: usize) -> Result<Vec<u8>, String> {
+    let mut buf = vec![0u8; len];
+    let rv = test_utils::check_system_call!(
+        || { unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) } },
+        &[]
+    )?;
+    buf.truncate(rv.try_into().unwrap());
+    Ok(buf)
+}
+
+fn write_fd(fd: libc::c_int, buf: &[u8]) -> Result<(), String> {
+    let rv = test_utils::check_system_call!(
+        || { unsafe { libc::write(fd, buf.as_ptr() as *const libc::c_void, buf.len()) } },
+        &[]
+    )?;
+    test_utils::result_assert_eq(rv, buf.len() as isize, "Unexpected write() result")
+}
+
+/// Test that `sendfile()` without an offset copies from and advances the file position.
+fn test_sendfile_to_pipe() -> Result<(), String> {
+    let file = new_file("splice_sendfile_to_pipe")?;
+    let (read_end, write_end) = new_pipe(0)?;
+
+    test_utils::run_and_close_fds(&[read_end, write_end], || {
+        let rv = test_utils::check_system_call!(
+            || { unsafe { libc::sendfile(write_end, file.as_raw_fd(), std::ptr::null_mut(), 9) } },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv, 9, "Unexpected sendfile() result")?;
+
+        // the file position was advanced
+        let rv = test_utils::check_system_call!(
+            || {
+                unsafe { libc::sendfile(write_end, file.as_raw_fd(), std::ptr::null_mut(), 100) }
+            },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv as usize, CONTENTS.len() - 9, "Unexpected result")?;
+
+        // at the end of the file
+        let rv = test_utils::check_system_call!(
+            || {
+                unsafe { libc::sendfile(write_end, file.as_raw_fd(), std::ptr::null_mut(), 100) }
+            },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv, 0, "Expected EOF")?;
+
+        let buf = read_fd(read_end, 100)?;
+        test_utils::result_assert_eq(&buf[..], CONTENTS, "Unexpected pipe data")?;
+
+        Ok(())
+    })
+}
+
+/// Test that `sendfile()` with an offset updates the offset but not the file position.
+fn test_sendfile_offset() -> Result<(), String> {
+    let mut file = new_file("splice_sendfile_offset")?;
+    let (read_end, write_end) = new_pipe(0)?;
+
+    test_utils::run_and_close_fds(&[read_end, write_end], || {
+        let mut offset: libc::off_t = 4;
+        let rv = test_utils::check_system_call!(
+            || { unsafe { libc::sendfile(write_end, file.as_raw_fd(), &mut offset, 5) } },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv, 5, "Unexpected sendfile() result")?;
+        test_utils::result_assert_eq(offset, 9, "Offset wasn't updated")?;
+
+        let buf = read_fd(read_end, 100)?;
+        test_utils::result_assert_eq(&buf[..], &CONTENTS[4..9], "Unexpected pipe data")?;
+
+        // the file position is unchanged
+        let pos = file.stream_position().map_err(|e| e.to_string())?;
+        test_utils::result_assert_eq(pos, 0, "File position changed")?;
+
+        // a negative offset is invalid
+        let mut offset: libc::off_t = -1;
+        test_utils::check_system_call!(
+            || { unsafe { libc::sendfile(write_end, file.as_raw_fd(), &mut offset, 5) } },
+            &[libc::EINVAL]
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test sending a file over a stream socket.
+fn test_sendfile_to_socket() -> Result<(), String> {
+    let file = new_file("splice_sendfile_to_socket")?;
+    let (fd_1, fd_2) = new_socketpair(libc::SOCK_STREAM)?;
+
+    test_utils::run_and_close_fds(&[fd_1, fd_2], || {
+        let rv = test_utils::check_system_call!(
+            || {
+                unsafe {
+                    libc::sendfile(fd_1, file.as_raw_fd(), std::ptr::null_mut(), CONTENTS.len())
+                }
+            },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv as usize, CONTENTS.len(), "Unexpected result")?;
+
+        let buf = read_fd(fd_2, 100)?;
+        test_utils::result_assert_eq(&buf[..], CONTENTS, "Unexpected socket data")?;
+
+        Ok(())
+    })
+}
+
+/// Test that `sendfile()` to a full non-blocking pipe returns a partial count and then `EAGAIN`,
+/// and that the file position only advances by the bytes that were sent.
+fn test_sendfile_nonblocking_full() -> Result<(), String> {
+    let mut file = new_empty_file("splice_sendfile_nonblocking_full")?;
+    let (read_end, write_end) = new_pipe(libc::O_NONBLOCK)?;
+
+    test_utils::run_and_close_fds(&[read_end, write_end], || {
+        let pipe_size = test_utils::check_system_call!(
+            || { unsafe { libc::fcntl(write_end, libc::F_GETPIPE_SZ) } },
+            &[]
+        )?;
+        let pipe_size: usize = pipe_size.try_into().unwrap();
+
+        // a file larger than the pipe
+        let file_len = pipe_size + 1000;
+        let contents: Vec<u8> = (0..file_len).map(|x| x as u8).collect();
+        file.write_all(&contents).map_err(|e| e.to_string())?;
+        file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+
+        let rv = test_utils::check_system_call!(
+            || {
+                unsafe {
+                    libc::sendfile(write_end, file.as_raw_fd(), std::ptr::null_mut(), file_len)
+                }
+            },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv as usize, pipe_size, "Unexpected sendfile() result")?;
+
+        test_utils::check_system_call!(
+            || {
+                unsafe {
+                    libc::sendfile(write_end, file.as_raw_fd(), std::ptr::null_mut(), file_len)
+                }
+            },
+            &[libc::EAGAIN]
+        )?;
+
+        let pos = file.stream_position().map_err(|e| e.to_string())?;
+        test_utils::result_assert_eq(pos as usize, pipe_size, "Unexpected file position")?;
+
+        // the remaining bytes can be read from the file
+        let mut remaining = Vec::new();
+        file.read_to_end(&mut remaining)
+            .map_err(|e| e.to_string())?;
+        test_utils::result_assert_eq(&remaining[..], &contents[pipe_size..], "Unexpected data")?;
+
+        Ok(())
+    })
+}
+
+/// Test splicing from a file at an offset into a pipe.
+fn test_splice_file_to_pipe() -> Result<(), String> {
+    let file = new_file("splice_file_to_pipe")?;
+    let (read_end, write_end) = new_pipe(0)?;
+
+    test_utils::run_and_close_fds(&[read_end, write_end], || {
+        let mut off_in: libc::loff_t = 10;
+        let rv = test_utils::check_system_call!(
+            || {
+                unsafe {
+                    libc::splice(
+                        file.as_raw_fd(),
+                        &mut off_in,
+                        write_end,
+                        std::ptr::null_mut(),
+                        5,
+                        0,
+                    )
+                }
+            },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv, 5, "Unexpected splice() result")?;
+        test_utils::result_assert_eq(off_in, 15, "Offset wasn't updated")?;
+
+        let buf = read_fd(read_end, 100)?;
+        test_utils::result_assert_eq(&buf[..], &CONTENTS[10..15], "Unexpected pipe data")?;
+
+        Ok(())
+    })
+}
+
+/// Test splicing from a pipe into a file, where the pipe has more data than requested.
+fn test_splice_pipe_to_file() -> Result<(), String> {
+    let mut file = new_empty_file("splice_pipe_to_file")?;
+    let (read_end, write_end) = new_pipe(0)?;
+
+    test_utils::run_and_close_fds(&[read_end, write_end], || {
+        write_fd(write_end, CONTENTS)?;
+
+        let rv = test_utils::check_system_call!(
+            || {
+                unsafe {
+                    libc::splice(
+                        read_end,
+                        std::ptr::null_mut(),
+                        file.as_raw_fd(),
+                        std::ptr::null_mut(),
+                        9,
+                        0,
+                    )
+                }
+            },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv, 9, "Unexpected splice() result")?;
+
+        // the remaining data is still in the pipe
+        let buf = read_fd(read_end, 100)?;
+        test_utils::result_assert_eq(&buf[..], &CONTENTS[9..], "Unexpected pipe data")?;
+
+        file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+        let mut file_contents = Vec::new();
+        file.read_to_end(&mut file_contents)
+            .map_err(|e| e.to_string())?;
+        test_utils::result_assert_eq(&file_contents[..], &CONTENTS[..9], "Unexpected data")?;
+
+        Ok(())
+    })
+}
+
+/// Test splicing from a pipe into a socket.
+fn test_splice_pipe_to_socket() -> Result<(), String> {
+    let (read_end, write_end) = new_pipe(0)?;
+    let (fd_1, fd_2) = new_socketpair(libc::SOCK_STREAM)?;
+
+    test_utils::run_and_close_fds(&[read_end, write_end, fd_1, fd_2], || {
+        write_fd(write_end, CONTENTS)?;
+
+        let rv = test_utils::check_system_call!(
+            || {
+                unsafe {
+                    libc::splice(
+                        read_end,
+                        std::ptr::null_mut(),
+                        fd_1,
+                        std::ptr::null_mut(),
+                        CONTENTS.len(),
+                        0,
+                    )
+                }
+            },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv as usize, CONTENTS.len(), "Unexpected result")?;
+
+        let buf = read_fd(fd_2, 100)?;
+        test_utils::result_assert_eq(&buf[..], CONTENTS, "Unexpected socket data")?;
+
+        Ok(())
+    })
+}
+
+/// Test that `splice()` requires at least one pipe.
+fn test_splice_no_pipe() -> Result<(), String> {
+    let file_1 = new_file("splice_no_pipe_1")?;
+    let file_2 = new_empty_file("splice_no_pipe_2")?;
+
+    test_utils::check_system_call!(
+        || {
+            unsafe {
+                libc::splice(
+                    file_1.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    file_2.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    10,
+                    0,
+                )
+            }
+        },
+        &[libc::EINVAL]
+    )?;
+
+    Ok(())
+}
+
+/// Test that an offset can't be used with a pipe.
+fn test_splice_pipe_offset() -> Result<(), String> {
+    let file = new_empty_file("splice_pipe_offset")?;
+    let (read_end, write_end) = new_pipe(0)?;
+
+    test_utils::run_and_close_fds(&[read_end, write_end], || {
+        write_fd(write_end, CONTENTS)?;
+
+        let mut off_in: libc::loff_t = 0;
+        test_utils::check_system_call!(
+            || {
+                unsafe {
+                    libc::splice(
+                        read_end,
+                        &mut off_in,
+                        file.as_raw_fd(),
+                        std::ptr::null_mut(),
+                        10,
+                        0,
+                    )
+                }
+            },
+            &[libc::ESPIPE]
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test that `SPLICE_F_NONBLOCK` makes splicing from an empty pipe return `EAGAIN`, even though
+/// the pipe itself is blocking.
+fn test_splice_nonblock_empty() -> Result<(), String> {
+    let file = new_empty_file("splice_nonblock_empty")?;
+    let (read_end, write_end) = new_pipe(0)?;
+
+    test_utils::run_and_close_fds(&[read_end, write_end], || {
+        test_utils::check_system_call!(
+            || {
+                unsafe {
+                    libc::splice(
+                        read_end,
+                        std::ptr::null_mut(),
+                        file.as_raw_fd(),
+                        std::ptr::null_mut(),
+                        10,
+                        libc::SPLICE_F_NONBLOCK,
+                    )
+                }
+            },
+            &[libc::EAGAIN]
+        )?;
+
+        Ok(())
+    })?;
+
+    // once the write end is closed, an empty pipe returns EOF
+    let (read_end, write_end) = new_pipe(0)?;
+    nix::unistd::close(write_end).map_err(|e| e.to_string())?;
+
+    test_utils::run_and_close_fds(&[read_end], || {
+        let rv = test_utils::check_system_call!(
+            || {
+                unsafe {
+                    libc::splice(
+                        read_end,
+                        std::ptr::null_mut(),
+                        file.as_raw_fd(),
+                        std::ptr::null_mut(),
+                        10,
+                        0,
+                    )
+                }
+            },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv, 0, "Expected EOF")?;
+
+        Ok(())
+    })
+}
+
+/// Test that `tee()` copies data between pipes without consuming it.
+fn test_tee() -> Result<(), String> {
+    let (read_end_1, write_end_1) = new_pipe(0)?;
+    let (read_end_2, write_end_2) = new_pipe(0)?;
+
+    test_utils::run_and_close_fds(&[read_end_1, write_end_1, read_end_2, write_end_2], || {
+        write_fd(write_end_1, CONTENTS)?;
+
+        let rv = test_utils::check_system_call!(
+            || { unsafe { libc::tee(read_end_1, write_end_2, 9, 0) } },
+            &[]
+        )?;
+        test_utils::result_assert_eq(rv, 9, "Unexpected tee() result")?;
+
+        let buf = read_fd(read_end_2, 100)?;
+        test_utils::result_assert_eq(&buf[..], &CONTENTS[..9], "Unexpected data in pipe 2")?;
+
+        // the data is still in the first pipe
+        let buf = read_fd(read_end_1, 100)?;
+        test_utils::result_assert_eq(&buf[..], CONTENTS, "Unexpected data in pipe 1")?;
+
+        // both descriptors must be pipes
+        let file = new_empty_file("splice_tee")?;
+        test_utils::check_system_call!(
+            || { unsafe { libc::tee(read_end_1, file.as_raw_fd(), 9, 0) } },
+            &[libc::EINVAL]
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Test copying between regular files with and without offsets.
+fn test_copy_file_range() -> Result<(), String> {
+    let mut file_in = new_file("splice_copy_file_range_1")?;
+    let mut file_out = new_empty_file("splice_copy_file_range_2")?;
+
+    let mut off_in: libc::loff_t = 4;
+    let rv = test_utils::check_system_call!(
+        || {
+            unsafe {
+                libc::copy_file_range(
+                    file_in.as_raw_fd(),
+                    &mut off_in,
+                    file_out.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    5,
+                    0,
+                )
+            }
+        },
+        &[]
+    )?;
+    test_utils::result_assert_eq(rv, 5, "Unexpected copy_file_range() result")?;
+    test_utils::result_assert_eq(off_in, 9, "Offset wasn't updated")?;
+
+    // only the output file's position changed
+    let pos = file_in.stream_position().map_err(|e| e.to_string())?;
+    test_utils::result_assert_eq(pos, 0, "Input file position changed")?;
+    let pos = file_out.stream_position().map_err(|e| e.to_string())?;
+    test_utils::result_assert_eq(pos, 5, "Unexpected output file position")?;
+
+    file_out
+        .seek(SeekFrom::Start(0))
+        .map_err(|e| e.to_string())?;
+    let mut contents = Vec::new();
+    file_out
+        .read_to_end(&mut contents)
+        .map_err(|e| e.to_string())?;
+    test_utils::result_assert_eq(&contents[..], &CONTENTS[4..9], "Unexpected file data")?;
+
+    // unknown flags are invalid
+    test_utils::check_system_call!(
+        || {
+            unsafe {
+                libc::copy_file_range(
+                    file_in.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    file_out.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    5,
+                    1,
+                )
+            }
+        },
+        &[libc::EINVAL]
+    )?;
+
+    Ok(())
+}
+
+/// Test that `copy_file_range()` doesn't support pipes.
+fn test_copy_file_range_pipe() -> Result<(), String> {
+    let file = new_file("splice_copy_file_range_pipe")?;
+    let (read_end, write_end) = new_pipe(0)?;
+
+    test_utils::run_and_close_fds(&[read_end, write_end], || {
+        test_utils::check_system_call!(
+            || {
+                unsafe {
+                    libc::copy_file_range(
+                        file.as_raw_fd(),
+                        std::ptr::null_mut(),
+                        write_end,
+                        std::ptr::null_mut(),
+                        5,
+                        0,
+                    )
+                }
+            },
+            &[libc::EINVAL]
+        )?;
+
+        Ok(())
+    })
+}